@@ -2,17 +2,22 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::{collections::HashMap, error::Error, path::PathBuf};
+use std::{collections::HashMap, error::Error, fs, path::PathBuf, str::FromStr, time::Duration};
 
 use clap::{Arg, ArgAction, ArgGroup, Command};
 use serde_json::{json, Map, Value};
 
 use stratisd::{
     engine::{
-        InputEncryptionInfo, KeyDescription, Name, OptionalTokenSlotInput, PoolIdentifier,
-        PoolUuid, TokenUnlockMethod, CLEVIS_TANG_TRUST_URL,
+        BatchOperation, DevUuid, InputEncryptionInfo, KeyDescription, Name, OptionalTokenSlotInput,
+        PoolExport, PoolIdentifier, PoolUuid, PoolsConfig, ThinMetaSpareAction, TokenUnlockMethod,
+        CLEVIS_TANG_TRUST_URL,
+    },
+    jsonrpc::client::{
+        blockdev, config, filesystem, job, key,
+        pool::{self, SetupUnlockMethod},
+        protocol, report,
     },
-    jsonrpc::client::{filesystem, key, pool, report},
     stratis::{StratisError, VERSION},
 };
 
@@ -46,6 +51,23 @@ fn parse_args() -> Command {
                 Command::new("stop")
                     .arg(Arg::new("id").required(true))
                     .arg(Arg::new("name").long("name").num_args(0)),
+                Command::new("setup")
+                    .arg(Arg::new("id").required(true))
+                    .arg(Arg::new("name").long("name").num_args(0))
+                    .arg(
+                        Arg::new("timeout")
+                            .long("timeout")
+                            .num_args(1)
+                            .value_parser(clap::value_parser!(u64))
+                            .default_value("90"),
+                    )
+                    .arg(
+                        Arg::new("unlock")
+                            .long("unlock")
+                            .num_args(1)
+                            .value_parser(["auto", "token", "passphrase", "none"])
+                            .default_value("auto"),
+                    ),
                 Command::new("create")
                     .arg(Arg::new("name").required(true))
                     .arg(
@@ -65,7 +87,8 @@ fn parse_args() -> Command {
                             .long("clevis-infos")
                             .num_args(1)
                             .action(ArgAction::Append),
-                    ),
+                    )
+                    .arg(Arg::new("background").long("background").num_args(0)),
                 Command::new("init-cache")
                     .arg(Arg::new("name").required(true))
                     .arg(
@@ -73,7 +96,8 @@ fn parse_args() -> Command {
                             .action(ArgAction::Append)
                             .value_parser(clap::value_parser!(PathBuf))
                             .required(true),
-                    ),
+                    )
+                    .arg(Arg::new("background").long("background").num_args(0)),
                 Command::new("rename")
                     .arg(Arg::new("current_name").required(true))
                     .arg(Arg::new("new_name").required(true)),
@@ -187,6 +211,62 @@ fn parse_args() -> Command {
                                     .value_parser(clap::value_parser!(u32)),
                             ),
                     ]),
+                Command::new("info").arg(Arg::new("name").required(true)),
+                Command::new("thin-meta-spare")
+                    .arg(Arg::new("name").long("name").num_args(0))
+                    .arg(Arg::new("id").required(true))
+                    .arg(
+                        Arg::new("action")
+                            .long("action")
+                            .num_args(1)
+                            .value_parser(["report", "validate", "swap", "reprovision"])
+                            .default_value("report"),
+                    ),
+                Command::new("extend-data")
+                    .arg(Arg::new("name").required(true))
+                    .arg(Arg::new("device_uuid").long("device-uuid").num_args(1))
+                    .arg(Arg::new("background").long("background").num_args(0)),
+                Command::new("set-fs-limit")
+                    .arg(Arg::new("name").required(true))
+                    .arg(
+                        Arg::new("limit")
+                            .value_parser(clap::value_parser!(u64))
+                            .required(true),
+                    ),
+                Command::new("overprovision")
+                    .arg(Arg::new("name").required(true))
+                    .arg(
+                        Arg::new("decision")
+                            .value_parser(["yes", "no"])
+                            .required(true),
+                    ),
+                Command::new("metadata")
+                    .arg(Arg::new("name").required(true))
+                    .arg(Arg::new("written").long("written").num_args(0)),
+                Command::new("layout").arg(Arg::new("name").required(true)),
+                Command::new("compact")
+                    .arg(Arg::new("name").required(true))
+                    .arg(Arg::new("background").long("background").num_args(0)),
+                Command::new("apply-batch")
+                    .arg(Arg::new("name").required(true))
+                    .arg(
+                        Arg::new("file")
+                            .value_parser(clap::value_parser!(PathBuf))
+                            .required(true),
+                    ),
+                Command::new("export-config").arg(Arg::new("name").required(true)),
+                Command::new("import-config")
+                    .arg(
+                        Arg::new("file")
+                            .value_parser(clap::value_parser!(PathBuf))
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::new("blockdevs")
+                            .action(ArgAction::Append)
+                            .value_parser(clap::value_parser!(PathBuf))
+                            .required(true),
+                    ),
             ]),
             Command::new("filesystem").subcommands(vec![
                 Command::new("create")
@@ -202,8 +282,78 @@ fn parse_args() -> Command {
                 Command::new("origin")
                     .arg(Arg::new("pool_name").required(true))
                     .arg(Arg::new("fs_name").required(true)),
+                Command::new("info")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true)),
+                Command::new("snapshot")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("origin_name").required(true))
+                    .arg(Arg::new("snapshot_name").required(true)),
+                Command::new("clone")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true))
+                    .arg(Arg::new("target_pool_name").required(true))
+                    .arg(Arg::new("new_fs_name").required(true))
+                    .arg(Arg::new("preserve_uuid").long("preserve-uuid").num_args(0)),
+                Command::new("set-size-limit")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true))
+                    .arg(
+                        Arg::new("limit")
+                            .value_parser(clap::value_parser!(u128))
+                            .required(true),
+                    ),
+                Command::new("unset-size-limit")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true)),
+                Command::new("set-reservation")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true))
+                    .arg(
+                        Arg::new("reservation")
+                            .value_parser(clap::value_parser!(u128))
+                            .required(true),
+                    ),
+                Command::new("unset-reservation")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true)),
+                Command::new("schedule-revert")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true)),
+                Command::new("cancel-revert")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true)),
+                Command::new("metadata")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").long("fs-name").num_args(1))
+                    .arg(Arg::new("written").long("written").num_args(0)),
+            ]),
+            Command::new("blockdev").subcommands(vec![Command::new("set-user-info")
+                .arg(Arg::new("pool_name").required(true))
+                .arg(Arg::new("dev_uuid").required(true))
+                .arg(Arg::new("user_info"))]),
+            Command::new("job").subcommands(vec![
+                Command::new("list"),
+                Command::new("status").arg(
+                    Arg::new("id")
+                        .value_parser(clap::value_parser!(u64))
+                        .required(true),
+                ),
+                Command::new("cancel").arg(
+                    Arg::new("id")
+                        .value_parser(clap::value_parser!(u64))
+                        .required(true),
+                ),
             ]),
+            Command::new("apply")
+                .arg(
+                    Arg::new("file")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(Arg::new("dry_run").long("dry-run").num_args(0)),
             Command::new("report"),
+            Command::new("list-methods"),
         ])
 }
 
@@ -251,6 +401,35 @@ fn main() -> Result<(), String> {
                 let prompt = args.get_flag("prompt");
                 pool::pool_start(id, token_slot, prompt)?;
                 Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("setup") {
+                let id = if args.get_flag("name") {
+                    PoolIdentifier::Name(Name::new(
+                        args.get_one::<String>("id").expect("required").to_owned(),
+                    ))
+                } else {
+                    PoolIdentifier::Uuid(PoolUuid::parse_str(
+                        args.get_one::<String>("id")
+                            .map(|s| s.as_str())
+                            .expect("required"),
+                    )?)
+                };
+                let unlock = match args
+                    .get_one::<String>("unlock")
+                    .map(|s| s.as_str())
+                    .expect("default value")
+                {
+                    "auto" => SetupUnlockMethod::Auto,
+                    "token" => SetupUnlockMethod::Token,
+                    "passphrase" => SetupUnlockMethod::Passphrase,
+                    "none" => SetupUnlockMethod::None,
+                    _ => unreachable!("Impossible unlock method"),
+                };
+                pool::pool_setup(
+                    id,
+                    Duration::from_secs(*args.get_one::<u64>("timeout").expect("default value")),
+                    unlock,
+                )?;
+                Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("stop") {
                 let id = if args.get_flag("name") {
                     PoolIdentifier::Name(Name::new(
@@ -394,6 +573,7 @@ fn main() -> Result<(), String> {
                         .cloned()
                         .collect::<Vec<_>>(),
                     ei,
+                    args.get_flag("background"),
                 )?;
                 Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("destroy") {
@@ -406,6 +586,7 @@ fn main() -> Result<(), String> {
                         .expect("required")
                         .cloned()
                         .collect::<Vec<_>>(),
+                    args.get_flag("background"),
                 )?;
                 Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("rename") {
@@ -654,6 +835,97 @@ fn main() -> Result<(), String> {
                 } else {
                     unreachable!("Parser requires a subcommand")
                 }
+            } else if let Some(args) = subcommand.subcommand_matches("info") {
+                pool::pool_info(args.get_one::<String>("name").expect("required").to_owned())?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("thin-meta-spare") {
+                let id = if args.get_flag("name") {
+                    PoolIdentifier::Name(Name::new(
+                        args.get_one::<String>("id").expect("required").to_owned(),
+                    ))
+                } else {
+                    PoolIdentifier::Uuid(PoolUuid::parse_str(
+                        args.get_one::<String>("id")
+                            .map(|s| s.as_str())
+                            .expect("required"),
+                    )?)
+                };
+                let action = ThinMetaSpareAction::from_str(
+                    args.get_one::<String>("action").expect("default value"),
+                )?;
+                pool::pool_thin_meta_spare(id, action)?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("extend-data") {
+                pool::pool_extend_data(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                    args.get_one::<String>("device_uuid")
+                        .map(|s| DevUuid::parse_str(s))
+                        .transpose()?,
+                    args.get_flag("background"),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("set-fs-limit") {
+                pool::pool_set_fs_limit(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                    *args.get_one::<u64>("limit").expect("required"),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("overprovision") {
+                pool::pool_overprovision(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                    args.get_one::<String>("decision").expect("required") == "yes",
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("metadata") {
+                println!(
+                    "{}",
+                    pool::pool_metadata(
+                        args.get_one::<String>("name").expect("required").to_owned(),
+                        !args.get_flag("written"),
+                    )?
+                );
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("layout") {
+                println!(
+                    "{}",
+                    pool::pool_layout(
+                        args.get_one::<String>("name").expect("required").to_owned()
+                    )?
+                );
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("compact") {
+                pool::pool_compact(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                    args.get_flag("background"),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("apply-batch") {
+                let ops = serde_json::from_str::<Vec<BatchOperation>>(&fs::read_to_string(
+                    args.get_one::<PathBuf>("file").expect("required"),
+                )?)?;
+                pool::pool_apply_batch(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                    ops,
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("export-config") {
+                let export = config::pool_export_config(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                )?;
+                println!("{}", serde_json::to_string_pretty(&export)?);
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("import-config") {
+                let export = serde_json::from_str::<PoolExport>(&fs::read_to_string(
+                    args.get_one::<PathBuf>("file").expect("required"),
+                )?)?;
+                config::pool_import_config(
+                    export,
+                    args.get_many::<PathBuf>("blockdevs")
+                        .expect("required")
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                )?;
+                Ok(())
             } else {
                 pool::pool_list()?;
                 Ok(())
@@ -705,16 +977,173 @@ fn main() -> Result<(), String> {
                     println!("{}", origin);
                 })?;
                 Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("info") {
+                filesystem::filesystem_info(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("fs_name")
+                        .expect("required")
+                        .to_owned(),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("snapshot") {
+                filesystem::filesystem_snapshot(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("origin_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("snapshot_name")
+                        .expect("required")
+                        .to_owned(),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("clone") {
+                filesystem::filesystem_clone(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("fs_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("target_pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("new_fs_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_flag("preserve_uuid"),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("set-size-limit") {
+                filesystem::filesystem_set_size_limit(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("fs_name")
+                        .expect("required")
+                        .to_owned(),
+                    Some(*args.get_one::<u128>("limit").expect("required")),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("unset-size-limit") {
+                filesystem::filesystem_set_size_limit(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("fs_name")
+                        .expect("required")
+                        .to_owned(),
+                    None,
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("set-reservation") {
+                filesystem::filesystem_set_reservation(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("fs_name")
+                        .expect("required")
+                        .to_owned(),
+                    Some(*args.get_one::<u128>("reservation").expect("required")),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("unset-reservation") {
+                filesystem::filesystem_set_reservation(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("fs_name")
+                        .expect("required")
+                        .to_owned(),
+                    None,
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("schedule-revert") {
+                filesystem::filesystem_set_merge_scheduled(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("fs_name")
+                        .expect("required")
+                        .to_owned(),
+                    true,
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("cancel-revert") {
+                filesystem::filesystem_set_merge_scheduled(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("fs_name")
+                        .expect("required")
+                        .to_owned(),
+                    false,
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("metadata") {
+                println!(
+                    "{}",
+                    filesystem::filesystem_metadata(
+                        args.get_one::<String>("pool_name")
+                            .expect("required")
+                            .to_owned(),
+                        args.get_one::<String>("fs_name").cloned(),
+                        !args.get_flag("written"),
+                    )?
+                );
+                Ok(())
             } else {
                 filesystem::filesystem_list()?;
                 Ok(())
             }
+        } else if let Some(subcommand) = args.subcommand_matches("blockdev") {
+            if let Some(args) = subcommand.subcommand_matches("set-user-info") {
+                blockdev::blockdev_set_user_info(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    DevUuid::parse_str(args.get_one::<String>("dev_uuid").expect("required"))?,
+                    args.get_one::<String>("user_info").cloned(),
+                )?;
+                Ok(())
+            } else {
+                blockdev::blockdev_list()?;
+                Ok(())
+            }
+        } else if let Some(subcommand) = args.subcommand_matches("job") {
+            if let Some(args) = subcommand.subcommand_matches("status") {
+                job::job_status(*args.get_one::<u64>("id").expect("required"))?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("cancel") {
+                job::job_cancel(*args.get_one::<u64>("id").expect("required"))?;
+                Ok(())
+            } else {
+                job::job_list()?;
+                Ok(())
+            }
+        } else if let Some(args) = args.subcommand_matches("apply") {
+            config::config_apply(
+                PoolsConfig::parse(&fs::read_to_string(
+                    args.get_one::<PathBuf>("file").expect("required"),
+                )?)?,
+                args.get_flag("dry_run"),
+            )?;
+            Ok(())
         } else if let Some("report") = args.subcommand_name() {
             report::report().and_then(|j| {
                 println!("{}", serde_json::to_string_pretty(&j)?);
                 Ok(())
             })?;
             Ok(())
+        } else if let Some("list-methods") = args.subcommand_name() {
+            protocol::list_methods().and_then(|j| {
+                println!("{}", serde_json::to_string_pretty(&j)?);
+                Ok(())
+            })?;
+            Ok(())
         } else {
             unreachable!("Parser requires a subcommand.")
         }
@@ -18,6 +18,8 @@ use nix::{
     unistd::getpid,
 };
 
+#[cfg(feature = "metrics")]
+use stratisd::stratis::DEFAULT_METRICS_ADDRESS;
 use stratisd::stratis::{run, StratisError, StratisResult, VERSION};
 
 const STRATISD_PID_PATH: &str = "/run/stratisd.pid";
@@ -125,7 +127,11 @@ fn main() -> Result<(), String> {
         }
         builder.init();
 
-        run(args.get_flag("sim"))?;
+        run(
+            args.get_flag("sim"),
+            #[cfg(feature = "metrics")]
+            DEFAULT_METRICS_ADDRESS,
+        )?;
         Ok(())
     }
 
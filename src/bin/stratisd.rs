@@ -2,6 +2,8 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+#[cfg(feature = "metrics")]
+use std::net::SocketAddr;
 use std::{
     env,
     fs::{File, OpenOptions},
@@ -21,18 +23,20 @@ use nix::{
     unistd::{getpid, Pid},
 };
 
-use stratisd::stratis::{run, StratisError, StratisResult, VERSION};
+#[cfg(feature = "metrics")]
+use stratisd::stratis::DEFAULT_METRICS_ADDRESS;
+use stratisd::stratis::{init_journald_log, run, LogTarget, StratisError, StratisResult, VERSION};
 
 const STRATISD_PID_PATH: &str = "/run/stratisd.pid";
 const STRATISD_MIN_PID_PATH: &str = "/run/stratisd-min.pid";
 
-/// Configure and initialize the logger.
+/// Configure the logger.
 /// If optional log_level argument is set, use that to set the log level
 /// for both stratisd and libstratis. Otherwise, read log configuration
 /// parameters from the environment if RUST_LOG is set. Otherwise, just
 /// accept the default configuration, which is to log at the severity of
 /// error only.
-fn initialize_log(log_level: Option<&str>) {
+fn log_builder(log_level: Option<&str>) -> Builder {
     let mut builder = Builder::new();
 
     if let Some(log_level) = log_level {
@@ -45,7 +49,22 @@ fn initialize_log(log_level: Option<&str>) {
         builder.parse_filters(&s);
     }
 
-    builder.init()
+    builder
+}
+
+/// Initialize the logger for the given log target.
+/// If the log target is journald but journald can not be reached, fall back
+/// to logging to stderr.
+fn initialize_log(log_level: Option<&str>, log_target: LogTarget) {
+    match log_target {
+        LogTarget::Stderr => log_builder(log_level).init(),
+        LogTarget::Journald => {
+            if let Err(err) = init_journald_log(log_builder(log_level)) {
+                eprintln!("Failed to set up logging to journald, logging to stderr: {err}");
+                log_builder(log_level).init();
+            }
+        }
+    }
 }
 
 /// To ensure only one instance of stratisd runs at a time, acquire an
@@ -120,7 +139,7 @@ fn trylock_pid_file() -> StratisResult<Flock<File>> {
 }
 
 fn parse_args() -> Command {
-    Command::new("stratisd")
+    let cmd = Command::new("stratisd")
         .version(VERSION)
         .about("Stratis storage management")
         .arg(
@@ -135,6 +154,23 @@ fn parse_args() -> Command {
                 .long("log-level")
                 .help("Sets level for generation of log messages."),
         )
+        .arg(
+            Arg::new("log-target")
+                .value_parser(["stderr", "journald"])
+                .default_value("stderr")
+                .long("log-target")
+                .help("Sets destination for log messages and audit records."),
+        );
+
+    #[cfg(feature = "metrics")]
+    let cmd = cmd.arg(
+        Arg::new("metrics-address")
+            .value_parser(clap::value_parser!(SocketAddr))
+            .long("metrics-address")
+            .help("Sets the address of the metrics endpoint; defaults to 127.0.0.1:9849."),
+    );
+
+    cmd
 }
 
 fn main() {
@@ -148,8 +184,22 @@ fn main() {
         match lock_file {
             Err(err) => Err(err),
             Ok(_) => {
-                initialize_log(matches.get_one::<String>("log-level").map(|s| s.as_str()));
-                run(matches.get_flag("sim"))
+                initialize_log(
+                    matches.get_one::<String>("log-level").map(|s| s.as_str()),
+                    matches
+                        .get_one::<String>("log-target")
+                        .map(|s| s.parse::<LogTarget>())
+                        .expect("default value provided")
+                        .expect("argument parser only accepts valid log targets"),
+                );
+                run(
+                    matches.get_flag("sim"),
+                    #[cfg(feature = "metrics")]
+                    matches
+                        .get_one::<SocketAddr>("metrics-address")
+                        .copied()
+                        .unwrap_or(DEFAULT_METRICS_ADDRESS),
+                )
             }
         }
     };
@@ -4,17 +4,49 @@
 
 use std::{fs, path::Path};
 
-use stratisd::engine::pool_inspection::inspectors;
+use serde_json::{json, Value};
 
-pub fn run(infile: &Path, print: bool) -> Result<(), String> {
+use stratisd::engine::pool_inspection::{inspectors, Severity};
+
+use crate::tools::output::{json_finding, print_json, OutputFormat};
+
+/// Check or print the pool-level metadata in infile. If output is JSON, print
+/// an object with the key "findings", a list of the problems found, and, if
+/// print is true, the key "metadata", the metadata itself.
+pub fn run(infile: &Path, print: bool, output: OutputFormat) -> Result<(), String> {
     let metadata_str = fs::read_to_string(infile)
         .map_err(|the_io_error| format!("Error opening file: {}", the_io_error))?;
     let metadata = serde_json::from_str(&metadata_str)
         .map_err(|the_json_error| format!("Error parsing json into structs: {}", the_json_error))?;
 
-    if print {
-        inspectors::print(&metadata).map_err(|the_error| format!("Error: {}", the_error))
-    } else {
-        inspectors::check(&metadata).map_err(|the_error| format!("Error: {}", the_error))
+    match output {
+        OutputFormat::Text => {
+            if print {
+                inspectors::print(&metadata).map_err(|the_error| format!("Error: {}", the_error))
+            } else {
+                inspectors::check(&metadata).map_err(|the_error| format!("Error: {}", the_error))
+            }
+        }
+        OutputFormat::Json => {
+            let findings = inspectors::findings(&metadata)
+                .map_err(|the_error| format!("Error: {}", the_error))?;
+            let mut value = json!({
+                "findings": findings.iter().map(json_finding).collect::<Vec<_>>(),
+            });
+            if print {
+                value["metadata"] = serde_json::from_str::<Value>(&metadata_str)
+                    .map_err(|the_json_error| format!("Error parsing json: {}", the_json_error))?;
+            }
+            print_json(&value)?;
+            let errors = findings
+                .iter()
+                .filter(|finding| finding.severity() == Severity::Error)
+                .count();
+            if errors == 0 {
+                Ok(())
+            } else {
+                Err(format!("Error: {errors} problems found"))
+            }
+        }
     }
 }
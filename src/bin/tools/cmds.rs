@@ -6,9 +6,12 @@ use std::path::PathBuf;
 
 use clap::{Arg, ArgAction, ArgGroup, Command};
 
-use crate::tools::{check_metadata, dump_metadata, legacy_pool};
+use crate::tools::{
+    check_metadata, dump_metadata, fsck, legacy_pool, metadata_generations, output::OutputFormat,
+    pool_layout, upgrade_pool,
+};
 
-use stratisd::stratis::VERSION;
+use stratisd::{engine::FsckRepair, stratis::VERSION};
 
 pub trait ToolCommand<'a> {
     fn name(&self) -> &'a str;
@@ -24,6 +27,7 @@ impl StratisDumpMetadata {
             .version(VERSION)
             .about("Reads Stratis metadata from a Stratis device and displays it")
             .next_line_help(true)
+            .arg(OutputFormat::arg())
             .arg(
                 Arg::new("dev")
                     .value_parser(clap::value_parser!(PathBuf))
@@ -67,6 +71,7 @@ impl<'a> ToolCommand<'a> for StratisDumpMetadata {
                 .get_one::<String>("only")
                 .map(|v| v == "pool")
                 .unwrap_or(false),
+            OutputFormat::from_matches(&matches),
         )
     }
 
@@ -83,6 +88,7 @@ impl StratisCheckMetadata {
             .version(VERSION)
             .about("Check validity of Stratis metadata")
             .next_line_help(true)
+            .arg(OutputFormat::arg())
             .arg(
                 Arg::new("file")
                     .value_parser(clap::value_parser!(PathBuf))
@@ -103,7 +109,7 @@ impl<'a> ToolCommand<'a> for StratisCheckMetadata {
             .get_one::<PathBuf>("file")
             .expect("'file' is a mandatory argument");
 
-        check_metadata::run(infile, false)
+        check_metadata::run(infile, false, OutputFormat::from_matches(&matches))
     }
 
     fn show_in_after_help(&self) -> bool {
@@ -119,6 +125,7 @@ impl StratisPrintMetadata {
             .version(VERSION)
             .about("Print a human-suitable representation of Stratis metadata")
             .next_line_help(true)
+            .arg(OutputFormat::arg())
             .arg(
                 Arg::new("file")
                     .value_parser(clap::value_parser!(PathBuf))
@@ -139,7 +146,7 @@ impl<'a> ToolCommand<'a> for StratisPrintMetadata {
             .get_one::<PathBuf>("file")
             .expect("'file' is a mandatory argument");
 
-        check_metadata::run(infile, true)
+        check_metadata::run(infile, true, OutputFormat::from_matches(&matches))
     }
 
     fn show_in_after_help(&self) -> bool {
@@ -147,6 +154,81 @@ impl<'a> ToolCommand<'a> for StratisPrintMetadata {
     }
 }
 
+struct StratisFsck;
+
+impl StratisFsck {
+    fn cmd() -> Command {
+        Command::new("stratis-fsck")
+            .version(VERSION)
+            .about("Check a stopped pool for inconsistencies and optionally repair them")
+            .next_line_help(true)
+            .arg(OutputFormat::arg())
+            .arg(
+                Arg::new("pool_uuid")
+                    .required(true)
+                    .help("UUID of the pool to check"),
+            )
+            .arg(
+                Arg::new("rewrite_mda")
+                    .long("rewrite-mda")
+                    .action(ArgAction::SetTrue)
+                    .num_args(0)
+                    .help("Write the newest valid pool-level metadata to every device of the pool"),
+            )
+            .arg(
+                Arg::new("swap_thin_meta")
+                    .long("swap-thin-meta")
+                    .action(ArgAction::SetTrue)
+                    .num_args(0)
+                    .help("Repair the thin pool metadata into the spare thin metadata device and use it from now on"),
+            )
+            .arg(
+                Arg::new("restore_thin_meta")
+                    .long("restore-thin-meta")
+                    .action(ArgAction::SetTrue)
+                    .num_args(0)
+                    .conflicts_with("swap_thin_meta")
+                    .help("Restore the most recent thin pool metadata backup into the spare thin metadata device and use it from now on; changes made after the backup are lost"),
+            )
+            .arg(
+                Arg::new("rebuild_mdv")
+                    .long("rebuild-mdv")
+                    .action(ArgAction::SetTrue)
+                    .num_args(0)
+                    .help("Remove or correct filesystem records that are unreadable or inconsistent"),
+            )
+    }
+}
+
+impl<'a> ToolCommand<'a> for StratisFsck {
+    fn name(&self) -> &'a str {
+        "stratis-fsck"
+    }
+
+    fn run(&self, command_line_args: Vec<String>) -> Result<(), String> {
+        let matches = StratisFsck::cmd().get_matches_from(command_line_args);
+        let pool_uuid = matches
+            .get_one::<String>("pool_uuid")
+            .expect("'pool_uuid' is a mandatory argument");
+        let repairs = [
+            ("rewrite_mda", FsckRepair::RewriteMda),
+            ("swap_thin_meta", FsckRepair::SwapThinMetaSpare),
+            ("restore_thin_meta", FsckRepair::RestoreThinMetaBackup),
+            ("rebuild_mdv", FsckRepair::RebuildMdv),
+        ]
+        .into_iter()
+        .filter(|(flag, _)| matches.get_flag(flag))
+        .map(|(_, repair)| repair)
+        .collect::<Vec<_>>();
+
+        fsck::run(pool_uuid, &repairs, OutputFormat::from_matches(&matches))
+    }
+
+    fn show_in_after_help(&self) -> bool {
+        true
+    }
+}
+
 struct StratisLegacyPool;
 
 impl StratisLegacyPool {
@@ -208,19 +290,292 @@ impl<'a> ToolCommand<'a> for StratisLegacyPool {
     }
 }
 
+struct StratisListMetadataGenerations;
+
+impl StratisListMetadataGenerations {
+    fn cmd() -> Command {
+        Command::new("stratis-list-metadata-generations")
+            .version(VERSION)
+            .about("List the generations of pool-level metadata retained in the metadata history of a stopped pool")
+            .next_line_help(true)
+            .arg(OutputFormat::arg())
+            .arg(
+                Arg::new("devs")
+                    .action(ArgAction::Append)
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .required(true)
+                    .help("Devices belonging to the pool"),
+            )
+    }
+}
+
+impl<'a> ToolCommand<'a> for StratisListMetadataGenerations {
+    fn name(&self) -> &'a str {
+        "stratis-list-metadata-generations"
+    }
+
+    fn run(&self, command_line_args: Vec<String>) -> Result<(), String> {
+        let matches = StratisListMetadataGenerations::cmd().get_matches_from(command_line_args);
+        let devpaths = matches
+            .get_many::<PathBuf>("devs")
+            .expect("'devs' is a mandatory argument")
+            .cloned()
+            .collect::<Vec<_>>();
+
+        metadata_generations::list(&devpaths, OutputFormat::from_matches(&matches))
+    }
+
+    fn show_in_after_help(&self) -> bool {
+        true
+    }
+}
+
+struct StratisRestoreMetadataGeneration;
+
+impl StratisRestoreMetadataGeneration {
+    fn cmd() -> Command {
+        Command::new("stratis-restore-metadata-generation")
+            .version(VERSION)
+            .about("Restore a generation of pool-level metadata from the metadata history of a stopped pool")
+            .next_line_help(true)
+            .arg(OutputFormat::arg())
+            .arg(
+                Arg::new("generation")
+                    .long("generation")
+                    .num_args(1)
+                    .required(true)
+                    .help("Timestamp of the generation to restore, as listed by stratis-list-metadata-generations"),
+            )
+            .arg(
+                Arg::new("force")
+                    .long("force")
+                    .action(ArgAction::SetTrue)
+                    .num_args(0)
+                    .help("Restore the generation even if its allocations differ from those of the current metadata; the pool is corrupted unless its data is where the generation says it is"),
+            )
+            .arg(
+                Arg::new("devs")
+                    .action(ArgAction::Append)
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .required(true)
+                    .help("All devices belonging to the pool"),
+            )
+    }
+}
+
+impl<'a> ToolCommand<'a> for StratisRestoreMetadataGeneration {
+    fn name(&self) -> &'a str {
+        "stratis-restore-metadata-generation"
+    }
+
+    fn run(&self, command_line_args: Vec<String>) -> Result<(), String> {
+        let matches = StratisRestoreMetadataGeneration::cmd().get_matches_from(command_line_args);
+        let devpaths = matches
+            .get_many::<PathBuf>("devs")
+            .expect("'devs' is a mandatory argument")
+            .cloned()
+            .collect::<Vec<_>>();
+        let generation = matches
+            .get_one::<String>("generation")
+            .expect("'generation' is a mandatory argument");
+
+        metadata_generations::restore(
+            &devpaths,
+            generation,
+            matches.get_flag("force"),
+            OutputFormat::from_matches(&matches),
+        )
+    }
+
+    fn show_in_after_help(&self) -> bool {
+        true
+    }
+}
+
+struct StratisDiffMetadata;
+
+impl StratisDiffMetadata {
+    fn cmd() -> Command {
+        Command::new("stratis-diff-metadata")
+            .version(VERSION)
+            .about("Show the changes made to the pool-level metadata of a pool by its most recent update and report devices whose metadata lags behind")
+            .next_line_help(true)
+            .arg(OutputFormat::arg())
+            .arg(
+                Arg::new("devs")
+                    .action(ArgAction::Append)
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .required(true)
+                    .help("Devices belonging to the pool"),
+            )
+    }
+}
+
+impl<'a> ToolCommand<'a> for StratisDiffMetadata {
+    fn name(&self) -> &'a str {
+        "stratis-diff-metadata"
+    }
+
+    fn run(&self, command_line_args: Vec<String>) -> Result<(), String> {
+        let matches = StratisDiffMetadata::cmd().get_matches_from(command_line_args);
+        let devpaths = matches
+            .get_many::<PathBuf>("devs")
+            .expect("'devs' is a mandatory argument")
+            .cloned()
+            .collect::<Vec<_>>();
+
+        metadata_generations::diff(&devpaths, OutputFormat::from_matches(&matches))
+    }
+
+    fn show_in_after_help(&self) -> bool {
+        true
+    }
+}
+
+struct StratisPoolLayout;
+
+impl StratisPoolLayout {
+    fn cmd() -> Command {
+        Command::new("stratis-pool-layout")
+            .version(VERSION)
+            .about("Show which sectors of each device of a stopped pool hold Stratis metadata, integrity metadata, crypt metadata, the MDV, thin metadata and spare, thin data, or cache, and where free space lies")
+            .next_line_help(true)
+            .arg(OutputFormat::arg())
+            .arg(
+                Arg::new("width")
+                    .long("width")
+                    .num_args(1)
+                    .value_parser(clap::value_parser!(u64).range(1..))
+                    .default_value("72")
+                    .help("Width in characters of the bar which shows each device"),
+            )
+            .arg(
+                Arg::new("svg")
+                    .long("svg")
+                    .num_args(1)
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .help("Also write the layout as an SVG image to this file"),
+            )
+            .arg(
+                Arg::new("devs")
+                    .action(ArgAction::Append)
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .required(true)
+                    .help("Devices belonging to the pool"),
+            )
+    }
+}
+
+impl<'a> ToolCommand<'a> for StratisPoolLayout {
+    fn name(&self) -> &'a str {
+        "stratis-pool-layout"
+    }
+
+    fn run(&self, command_line_args: Vec<String>) -> Result<(), String> {
+        let matches = StratisPoolLayout::cmd().get_matches_from(command_line_args);
+        let devpaths = matches
+            .get_many::<PathBuf>("devs")
+            .expect("'devs' is a mandatory argument")
+            .cloned()
+            .collect::<Vec<_>>();
+
+        pool_layout::run(
+            &devpaths,
+            *matches
+                .get_one::<u64>("width")
+                .expect("'width' has a default value"),
+            matches.get_one::<PathBuf>("svg").map(|p| p.as_path()),
+            OutputFormat::from_matches(&matches),
+        )
+    }
+
+    fn show_in_after_help(&self) -> bool {
+        true
+    }
+}
+
+struct StratisUpgradePool;
+
+impl StratisUpgradePool {
+    fn cmd() -> Command {
+        Command::new("stratis-upgrade-pool")
+            .version(VERSION)
+            .about("Upgrade a stopped pool with legacy metadata to the current metadata format")
+            .next_line_help(true)
+            .arg(OutputFormat::arg())
+            .arg(
+                Arg::new("dry_run")
+                    .long("dry-run")
+                    .action(ArgAction::SetTrue)
+                    .num_args(0)
+                    .help("Report whether the pool can be upgraded without changing it"),
+            )
+            .arg(
+                Arg::new("passphrase_file")
+                    .long("passphrase-file")
+                    .num_args(1)
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .help("File containing the passphrase of an encrypted pool; if omitted, the pool's key description or Clevis binding is used"),
+            )
+            .arg(
+                Arg::new("devs")
+                    .action(ArgAction::Append)
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .required(true)
+                    .help("All devices belonging to the pool"),
+            )
+    }
+}
+
+impl<'a> ToolCommand<'a> for StratisUpgradePool {
+    fn name(&self) -> &'a str {
+        "stratis-upgrade-pool"
+    }
+
+    fn run(&self, command_line_args: Vec<String>) -> Result<(), String> {
+        let matches = StratisUpgradePool::cmd().get_matches_from(command_line_args);
+        let devpaths = matches
+            .get_many::<PathBuf>("devs")
+            .expect("'devs' is a mandatory argument")
+            .cloned()
+            .collect::<Vec<_>>();
+
+        upgrade_pool::run(
+            &devpaths,
+            matches.get_one::<PathBuf>("passphrase_file"),
+            matches.get_flag("dry_run"),
+            OutputFormat::from_matches(&matches),
+        )
+    }
+
+    fn show_in_after_help(&self) -> bool {
+        true
+    }
+}
+
 pub fn cmds<'a>() -> Vec<Box<dyn ToolCommand<'a>>> {
     vec![
         Box::new(StratisCheckMetadata),
+        Box::new(StratisDiffMetadata),
         Box::new(StratisDumpMetadata),
+        Box::new(StratisFsck),
         Box::new(StratisLegacyPool),
+        Box::new(StratisListMetadataGenerations),
+        Box::new(StratisPoolLayout),
         Box::new(StratisPrintMetadata),
+        Box::new(StratisRestoreMetadataGeneration),
+        Box::new(StratisUpgradePool),
     ]
 }
 
 #[cfg(test)]
 mod tests {
 
-    use super::{StratisCheckMetadata, StratisDumpMetadata, StratisPrintMetadata};
+    use super::{
+        StratisCheckMetadata, StratisDiffMetadata, StratisDumpMetadata, StratisFsck,
+        StratisListMetadataGenerations, StratisPoolLayout, StratisPrintMetadata,
+        StratisRestoreMetadataGeneration, StratisUpgradePool,
+    };
 
     #[test]
     fn test_dumpmetadata_parse_args() {
@@ -228,4 +583,26 @@ mod tests {
         StratisDumpMetadata::cmd().debug_assert();
         StratisPrintMetadata::cmd().debug_assert();
     }
+
+    #[test]
+    fn test_metadata_generations_parse_args() {
+        StratisListMetadataGenerations::cmd().debug_assert();
+        StratisRestoreMetadataGeneration::cmd().debug_assert();
+        StratisDiffMetadata::cmd().debug_assert();
+    }
+
+    #[test]
+    fn test_pool_layout_parse_args() {
+        StratisPoolLayout::cmd().debug_assert();
+    }
+
+    #[test]
+    fn test_upgrade_pool_parse_args() {
+        StratisUpgradePool::cmd().debug_assert();
+    }
+
+    #[test]
+    fn test_fsck_parse_args() {
+        StratisFsck::cmd().debug_assert();
+    }
 }
@@ -10,10 +10,12 @@ use std::{
 
 use pretty_hex::pretty_hex;
 
-use serde_json::Value;
+use serde_json::{json, Value};
 
 use stratisd::engine::{StaticHeader, StaticHeaderResult, BDA};
 
+use crate::tools::output::{json_bda, json_signature_block, print_json, OutputFormat};
+
 /// Format metadata on a given device
 /// Returns StaticHeader fields
 /// Returns an additional bytes buffer if print_bytes flag is True
@@ -94,9 +96,8 @@ fn print_pool_metadata(pool_metadata: &Option<Vec<u8>>, only_pool: bool) -> Resu
     if !only_pool {
         println!("\nPool metadata:");
     }
-    if let Some(loaded_state) = pool_metadata {
-        let state_json: Value = serde_json::from_slice(loaded_state)
-            .map_err(|extract_err| format!("Error during state JSON extract: {}", extract_err))?;
+    if pool_metadata.is_some() {
+        let state_json = parse_pool_metadata(pool_metadata)?;
         let state_json_pretty: String = serde_json::to_string_pretty(&state_json)
             .map_err(|parse_err| format!("Error during state JSON parse: {}", parse_err))?;
         println!("{}", state_json_pretty);
@@ -107,19 +108,45 @@ fn print_pool_metadata(pool_metadata: &Option<Vec<u8>>, only_pool: bool) -> Resu
     Ok(())
 }
 
+/// Parse the pool level metadata as JSON.
+fn parse_pool_metadata(pool_metadata: &Option<Vec<u8>>) -> Result<Value, String> {
+    pool_metadata
+        .as_ref()
+        .map_or(Ok(Value::Null), |loaded_state| {
+            serde_json::from_slice(loaded_state)
+                .map_err(|extract_err| format!("Error during state JSON extract: {}", extract_err))
+        })
+}
+
 // Print metadata, such as StaticHeaders, BDA, and Pool Metadata of given device.
 // If sigblocks match, display the StaticHeader fields of a single sigblock,
 // Otherwise display the StaticHeader fields of both sigblocks.
 // If print_bytes flag is set to True, display the bytes buffer
 // of the sigblock alongside the StaticHeader.
-pub fn run(devpath: &PathBuf, print_bytes: bool, pool_only: bool) -> Result<(), String> {
+// If output is JSON, print a single object with keys "sigblocks", "bda" and
+// "pool_metadata"; if only_pool is true, only "pool_metadata" is included.
+pub fn run(
+    devpath: &PathBuf,
+    print_bytes: bool,
+    pool_only: bool,
+    output: OutputFormat,
+) -> Result<(), String> {
     let mut devfile = OpenOptions::new()
         .read(true)
         .open(devpath)
         .map_err(|the_io_error| format!("Error opening device: {}", the_io_error))?;
 
     let read_results = StaticHeader::read_sigblocks(&mut devfile);
-    print_signature_block(&read_results, print_bytes, pool_only);
+    let sigblocks = match output {
+        OutputFormat::Text => {
+            print_signature_block(&read_results, print_bytes, pool_only);
+            Value::Null
+        }
+        OutputFormat::Json => json!([
+            json_signature_block(&read_results.0, print_bytes),
+            json_signature_block(&read_results.1, print_bytes),
+        ]),
+    };
 
     let header =
         StaticHeader::repair_sigblocks(&mut devfile, read_results, StaticHeader::do_nothing)
@@ -130,7 +157,9 @@ pub fn run(devpath: &PathBuf, print_bytes: bool, pool_only: bool) -> Result<(),
         .map_err(|bda_load_error| format!("BDA detected but error found: {}", bda_load_error))?
         .ok_or_else(|| "No Stratis BDA metadata found".to_string())?;
 
-    print_bda(&bda, pool_only);
+    if output == OutputFormat::Text {
+        print_bda(&bda, pool_only);
+    }
 
     devfile
         .seek(SeekFrom::Start(0))
@@ -140,7 +169,21 @@ pub fn run(devpath: &PathBuf, print_bytes: bool, pool_only: bool) -> Result<(),
         .load_state(&mut devfile)
         .map_err(|stateload_err| format!("Error during load state: {}", stateload_err))?;
 
-    print_pool_metadata(&loaded_state, pool_only)?;
+    match output {
+        OutputFormat::Text => print_pool_metadata(&loaded_state, pool_only)?,
+        OutputFormat::Json => {
+            let pool_metadata = parse_pool_metadata(&loaded_state)?;
+            print_json(&if pool_only {
+                json!({ "pool_metadata": pool_metadata })
+            } else {
+                json!({
+                    "sigblocks": sigblocks,
+                    "bda": json_bda(&bda),
+                    "pool_metadata": pool_metadata,
+                })
+            })?;
+        }
+    }
 
     Ok(())
 }
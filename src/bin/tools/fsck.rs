@@ -0,0 +1,47 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use serde_json::json;
+
+use stratisd::engine::{fsck_pool, FsckRepair, PoolUuid};
+
+use crate::tools::output::{json_time, print_json, OutputFormat};
+
+/// Check the stopped pool with the given UUID and perform the repairs
+/// requested. Returns an error if problems were found and no repairs were
+/// requested.
+/// If output is JSON, print an object with the keys "pool_uuid", "devices",
+/// a list of objects with the keys "dev_uuid" and "devnode",
+/// "metadata_time", "problems" and "repairs".
+pub fn run(pool_uuid: &str, repairs: &[FsckRepair], output: OutputFormat) -> Result<(), String> {
+    let pool_uuid =
+        PoolUuid::parse_str(pool_uuid).map_err(|e| format!("Invalid pool UUID: {e}"))?;
+    let report = fsck_pool(pool_uuid, repairs).map_err(|e| e.to_string())?;
+    match output {
+        OutputFormat::Text => print!("{report}"),
+        OutputFormat::Json => print_json(&json!({
+            "pool_uuid": report.pool_uuid.to_string(),
+            "devices": report
+                .devices
+                .iter()
+                .map(|(dev_uuid, devnode)| json!({
+                    "dev_uuid": dev_uuid.to_string(),
+                    "devnode": devnode.display().to_string(),
+                }))
+                .collect::<Vec<_>>(),
+            "metadata_time": report.metadata_time.as_ref().map(json_time),
+            "problems": report.problems,
+            "repairs": report.repairs,
+        }))?,
+    }
+
+    if report.is_clean() || !repairs.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} problems found; no repairs were requested",
+            report.problems.len()
+        ))
+    }
+}
@@ -0,0 +1,440 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{File, OpenOptions},
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::json;
+
+use devicemapper::Bytes;
+
+use stratisd::engine::{pool_inspection::inspectors, DevUuid, PoolUuid, StaticHeader, BDA};
+
+use crate::tools::output::{json_time, print_json, OutputFormat};
+
+/// Open a Stratis device and load its BDA. If exclusive is true, the device
+/// is opened for writing with O_EXCL, which fails if the device is in use,
+/// e.g., because the pool it belongs to is started.
+fn open_device(devpath: &PathBuf, exclusive: bool) -> Result<(File, BDA), String> {
+    let mut options = OpenOptions::new();
+    options.read(true);
+    if exclusive {
+        options.write(true).custom_flags(libc::O_EXCL);
+    }
+    let mut f = options.open(devpath).map_err(|e| {
+        format!(
+            "Error opening device {}: {}{}",
+            devpath.display(),
+            e,
+            if exclusive {
+                "; the pool must be stopped"
+            } else {
+                ""
+            }
+        )
+    })?;
+
+    let read_results = StaticHeader::read_sigblocks(&mut f);
+    let header = StaticHeader::repair_sigblocks(&mut f, read_results, StaticHeader::do_nothing)
+        .map_err(|e| {
+            format!(
+                "No valid StaticHeader found on {}: {}",
+                devpath.display(),
+                e
+            )
+        })?
+        .ok_or_else(|| format!("No valid Stratis signature found on {}", devpath.display()))?;
+    let bda = BDA::load(header, &mut f)
+        .map_err(|e| {
+            format!(
+                "BDA detected on {} but error found: {}",
+                devpath.display(),
+                e
+            )
+        })?
+        .ok_or_else(|| format!("No Stratis BDA metadata found on {}", devpath.display()))?;
+
+    Ok((f, bda))
+}
+
+/// Open all the devices and verify that they belong to the same pool.
+pub fn open_pool_devices(
+    devpaths: &[PathBuf],
+    exclusive: bool,
+) -> Result<(PoolUuid, Vec<(&PathBuf, File, BDA)>), String> {
+    let devices = devpaths
+        .iter()
+        .map(|devpath| open_device(devpath, exclusive).map(|(f, bda)| (devpath, f, bda)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let pool_uuids = devices
+        .iter()
+        .map(|(_, _, bda)| bda.pool_uuid())
+        .collect::<HashSet<_>>();
+    if pool_uuids.len() > 1 {
+        return Err(format!(
+            "The devices belong to more than one pool: {}",
+            pool_uuids
+                .iter()
+                .map(|u| u.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    let pool_uuid = pool_uuids
+        .into_iter()
+        .next()
+        .ok_or_else(|| "No devices specified".to_string())?;
+
+    Ok((pool_uuid, devices))
+}
+
+fn fmt_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+/// List the generations of pool-level metadata retained in the metadata
+/// history of the devices of a pool, newest first, with the number of
+/// devices on which each generation is available. A generation is only
+/// recorded if it fits in a slot of the metadata history; if the current
+/// metadata does not, this is reported.
+/// If output is JSON, print an object with the keys "pool_uuid", "devices",
+/// "max_generation_size", "current_size" and "generations", a list of objects
+/// with the keys "time", "devices" and "current".
+pub fn list(devpaths: &[PathBuf], output: OutputFormat) -> Result<(), String> {
+    let (pool_uuid, mut devices) = open_pool_devices(devpaths, false)?;
+
+    let current = devices
+        .iter()
+        .filter_map(|(_, _, bda)| bda.last_update_time())
+        .max()
+        .copied();
+
+    let max_size = devices
+        .iter()
+        .map(|(_, _, bda)| bda.max_generation_size())
+        .min()
+        .expect("at least one device was opened");
+    let current_size = match devices
+        .iter_mut()
+        .max_by_key(|(_, _, bda)| bda.last_update_time().copied())
+    {
+        Some((devpath, f, bda)) => bda
+            .load_state(f)
+            .map_err(|e| format!("Error reading metadata from {}: {}", devpath.display(), e))?
+            .map(|data| data.len()),
+        None => None,
+    };
+    let too_large = current_size.is_some_and(|size| Bytes::from(size) > max_size);
+
+    let mut generations: BTreeMap<DateTime<Utc>, usize> = BTreeMap::new();
+    for (_, _, bda) in devices.iter() {
+        for time in bda.metadata_generations() {
+            *generations.entry(time).or_default() += 1;
+        }
+    }
+
+    if output == OutputFormat::Json {
+        return print_json(&json!({
+            "pool_uuid": pool_uuid.to_string(),
+            "devices": devices.len(),
+            "max_generation_size": u64::try_from(*max_size).expect("a history slot is smaller than a device"),
+            "current_size": current_size,
+            "generations": generations
+                .iter()
+                .rev()
+                .map(|(time, count)| json!({
+                    "time": json_time(time),
+                    "devices": count,
+                    "current": Some(*time) == current,
+                }))
+                .collect::<Vec<_>>(),
+        }));
+    }
+
+    println!("Pool UUID: {pool_uuid}");
+    if too_large {
+        println!(
+            "The current metadata of {} bytes exceeds the {} bytes that a slot of the metadata history can hold; it is not recorded in the history",
+            current_size.expect("too_large implies current_size is known"),
+            *max_size
+        );
+    }
+    if generations.is_empty() {
+        println!("No metadata generations found");
+    }
+    for (time, count) in generations.iter().rev() {
+        println!(
+            "{}  {}/{} devices{}",
+            fmt_time(time),
+            count,
+            devices.len(),
+            if Some(*time) == current {
+                "  (current)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    Ok(())
+}
+
+/// Restore the generation of pool-level metadata written at the given time
+/// by writing it as the current metadata on all devices of a stopped pool.
+/// The generation is validated before anything is written. The devices
+/// specified must be exactly the devices described by the generation.
+/// Unless force is true, the generation is not restored if the allocations
+/// it records differ from those of the current metadata, e.g., because the
+/// pool was extended or compacted since the generation was written; the
+/// pool's data is where the current metadata says it is, so restoring the
+/// generation would corrupt the pool.
+/// If output is JSON, print an object with the keys "pool_uuid",
+/// "generation" and "devices".
+pub fn restore(
+    devpaths: &[PathBuf],
+    generation: &str,
+    force: bool,
+    output: OutputFormat,
+) -> Result<(), String> {
+    let time = DateTime::parse_from_rfc3339(generation)
+        .map_err(|e| format!("Invalid generation timestamp {generation}: {e}"))?
+        .with_timezone(&Utc);
+
+    let (pool_uuid, mut devices) = open_pool_devices(devpaths, true)?;
+
+    let mut data = None;
+    for (devpath, f, bda) in devices.iter_mut() {
+        match bda.load_generation(&time, f).map_err(|e| {
+            format!(
+                "Error reading metadata history of {}: {}",
+                devpath.display(),
+                e
+            )
+        })? {
+            Some(d) => match data {
+                Some(ref existing) if existing != &d => {
+                    return Err(format!(
+                        "Generation {} differs between devices; not restoring",
+                        fmt_time(&time)
+                    ));
+                }
+                Some(_) => (),
+                None => data = Some(d),
+            },
+            None => eprintln!(
+                "Generation {} not found in metadata history of {}",
+                fmt_time(&time),
+                devpath.display()
+            ),
+        }
+    }
+    let data = data.ok_or_else(|| {
+        format!(
+            "Generation {} not found on any device of pool {}",
+            fmt_time(&time),
+            pool_uuid
+        )
+    })?;
+
+    let metadata = serde_json::from_slice(&data)
+        .map_err(|e| format!("Error parsing generation into structs: {e}"))?;
+    inspectors::check(&metadata)
+        .map_err(|e| format!("Generation {} is not valid: {}", fmt_time(&time), e))?;
+
+    let expected = inspectors::blockdev_uuids(&metadata)
+        .into_iter()
+        .collect::<HashSet<DevUuid>>();
+    let specified = devices
+        .iter()
+        .map(|(_, _, bda)| bda.dev_uuid())
+        .collect::<HashSet<DevUuid>>();
+    if expected != specified {
+        return Err(format!(
+            "The devices specified do not match the devices described by generation {}; missing: [{}], unexpected: [{}]",
+            fmt_time(&time),
+            expected
+                .difference(&specified)
+                .map(|u| u.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            specified
+                .difference(&expected)
+                .map(|u| u.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        ));
+    }
+
+    let current = {
+        let (devpath, f, bda) = devices
+            .iter_mut()
+            .max_by_key(|(_, _, bda)| bda.last_update_time().copied())
+            .expect("at least one device was opened");
+        let current = bda
+            .load_state(f)
+            .map_err(|e| format!("Error reading metadata from {}: {}", devpath.display(), e))?
+            .ok_or_else(|| {
+                format!("No current metadata found on any device of pool {pool_uuid}")
+            })?;
+        serde_json::from_slice(&current)
+            .map_err(|e| format!("Error parsing current metadata into structs: {e}"))?
+    };
+    let changes = inspectors::allocation_changes(&current, &metadata);
+    if !changes.is_empty() {
+        let changes = changes
+            .iter()
+            .map(|change| format!("    {change}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if !force {
+            return Err(format!(
+                "The allocations recorded by generation {} differ from those of the current metadata; restoring it would make the pool use space that does not hold its data:\n{}",
+                fmt_time(&time),
+                changes
+            ));
+        }
+        eprintln!(
+            "WARNING: The allocations recorded by generation {} differ from those of the current metadata; restoring it may corrupt the pool:\n{}",
+            fmt_time(&time),
+            changes
+        );
+    }
+
+    let now = Utc::now();
+    for (devpath, f, bda) in devices.iter_mut() {
+        bda.save_state(&now, &data, f)
+            .map_err(|e| format!("Error writing metadata to {}: {}", devpath.display(), e))?;
+    }
+
+    match output {
+        OutputFormat::Text => println!(
+            "Restored generation {} of pool {} on {} devices",
+            fmt_time(&time),
+            pool_uuid,
+            devices.len()
+        ),
+        OutputFormat::Json => print_json(&json!({
+            "pool_uuid": pool_uuid.to_string(),
+            "generation": json_time(&time),
+            "devices": devices.len(),
+        }))?,
+    }
+
+    Ok(())
+}
+
+/// Show how the pool-level metadata of a pool changed in its most recent
+/// update by comparing the current metadata with the metadata it replaced,
+/// which is retained in the older of the two MDA regions of each device.
+/// Devices whose current metadata lags behind the pool's current metadata
+/// are reported.
+/// If output is JSON, print an object with the keys "pool_uuid",
+/// "current_time", "lagging", a list of objects with the keys "devnode",
+/// "dev_uuid" and "last_update_time", "previous_time" and "changes". The
+/// last two are null if no previous metadata was found.
+pub fn diff(devpaths: &[PathBuf], output: OutputFormat) -> Result<(), String> {
+    let (pool_uuid, mut devices) = open_pool_devices(devpaths, false)?;
+
+    let current = devices
+        .iter()
+        .filter_map(|(_, _, bda)| bda.last_update_time())
+        .max()
+        .copied()
+        .ok_or_else(|| format!("No metadata found on any device of pool {pool_uuid}"))?;
+
+    let mut states = None;
+    let mut lagging = Vec::new();
+    for (devpath, f, bda) in devices.iter_mut() {
+        match bda.last_update_time().copied() {
+            Some(time) if time == current => {
+                if states.is_none() {
+                    let load_error =
+                        |e| format!("Error reading metadata from {}: {}", devpath.display(), e);
+                    let new = bda
+                        .load_state(f)
+                        .map_err(load_error)?
+                        .expect("last update time is known");
+                    let old = match bda.previous_update_time().copied() {
+                        Some(time) => bda
+                            .load_previous_state(f)
+                            .map_err(load_error)?
+                            .map(|data| (time, data)),
+                        None => None,
+                    };
+                    states = Some((new, old));
+                }
+            }
+            time => lagging.push((*devpath, bda.dev_uuid(), time)),
+        }
+    }
+    let (new, old) = states.expect("some device holds the current metadata");
+
+    let changes = match old {
+        Some((old_time, old)) => {
+            let new = serde_json::from_slice(&new)
+                .map_err(|e| format!("Error parsing current metadata into structs: {e}"))?;
+            let old = serde_json::from_slice(&old)
+                .map_err(|e| format!("Error parsing previous metadata into structs: {e}"))?;
+            Some((old_time, inspectors::diff(&old, &new)))
+        }
+        None => None,
+    };
+
+    if output == OutputFormat::Json {
+        return print_json(&json!({
+            "pool_uuid": pool_uuid.to_string(),
+            "current_time": json_time(&current),
+            "lagging": lagging
+                .iter()
+                .map(|(devpath, dev_uuid, time)| json!({
+                    "devnode": devpath.display().to_string(),
+                    "dev_uuid": dev_uuid.to_string(),
+                    "last_update_time": time.as_ref().map(json_time),
+                }))
+                .collect::<Vec<_>>(),
+            "previous_time": changes.as_ref().map(|(old_time, _)| json_time(old_time)),
+            "changes": changes.as_ref().map(|(_, changes)| changes),
+        }));
+    }
+
+    println!("Pool UUID: {pool_uuid}");
+    println!("Current metadata written at {}", fmt_time(&current));
+
+    for (devpath, dev_uuid, time) in lagging {
+        println!(
+            "Device {} ({}) lags behind: {}",
+            devpath.display(),
+            dev_uuid,
+            time.map(|t| format!("metadata last written at {}", fmt_time(&t)))
+                .unwrap_or_else(|| "no metadata written".to_string())
+        );
+    }
+
+    let (old_time, changes) = match changes {
+        Some(changes) => changes,
+        None => {
+            println!("No previous metadata found");
+            return Ok(());
+        }
+    };
+
+    println!(
+        "Changes from previous metadata written at {}:",
+        fmt_time(&old_time)
+    );
+    if changes.is_empty() {
+        println!("    None");
+    }
+    for change in changes {
+        println!("    {change}");
+    }
+
+    Ok(())
+}
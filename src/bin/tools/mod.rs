@@ -5,6 +5,11 @@
 mod check_metadata;
 mod cmds;
 mod dump_metadata;
+mod fsck;
 mod legacy_pool;
+mod metadata_generations;
+mod output;
+mod pool_layout;
+mod upgrade_pool;
 
 pub use cmds::cmds;
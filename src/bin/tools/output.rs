@@ -0,0 +1,171 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Machine-readable output for the tools.
+//!
+//! Every command that produces results accepts `--output json`, in which case
+//! it prints a single JSON object to stdout instead of its usual text. Errors
+//! which prevent the command from producing results are still reported as
+//! text on stderr with a non-zero exit status.
+//!
+//! The objects common to several commands are:
+//!
+//! * timestamps: RFC 3339 strings in UTC with nanosecond precision.
+//! * sizes: integers, in the unit named by the key's suffix, `_sectors` or
+//!   `_bytes`.
+//! * signature block: `{"status": "valid" | "absent" | "invalid" |
+//!   "unreadable", "error": string | null, "header": static header | null,
+//!   "bytes": hex string | null}`. `bytes` is present only if requested, and
+//!   is null if the signature block could not be read.
+//! * static header: `{"pool_uuid", "dev_uuid", "sigblock_version",
+//!   "blkdev_size_sectors", "mda_size_sectors", "reserved_size_sectors",
+//!   "flags", "initialization_time"}`.
+//! * BDA: `{"pool_uuid", "dev_uuid", "sigblock_version", "dev_size_sectors",
+//!   "extended_size_sectors", "max_data_size_bytes", "initialization_time",
+//!   "last_update_time", "previous_update_time", "metadata_generations",
+//!   "mda_headers"}`.
+//! * MDA header: `{"region": integer, "last_updated": timestamp, "used_bytes",
+//!   "data_crc"}`, or null for a region to which no metadata has been
+//!   written.
+//! * inspection finding: `{"kind": string, "severity": "warning" | "error",
+//!   "component": "data_device" | "cache_device" | "crypt_allocs" |
+//!   "cap_device" | "flex_device", "device": UUID | null, "message": string,
+//!   "fix": fix | null}` with further keys that depend on the kind, e.g.,
+//!   "extent", a pair of start and length in sectors. A fix is an object
+//!   with the key "action", "set_crypt_meta_allocs" or
+//!   "remove_integrity_meta_allocs", and the arguments of the action.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::{Arg, ArgAction, ArgMatches};
+use serde_json::{json, Value};
+
+use stratisd::engine::{pool_inspection::InspectionFinding, StaticHeader, StaticHeaderResult, BDA};
+
+/// The format in which a command prints its results.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    /// The --output option accepted by every command which produces results.
+    pub fn arg() -> Arg {
+        Arg::new("output")
+            .long("output")
+            .action(ArgAction::Set)
+            .value_name("FORMAT")
+            .value_parser(["text", "json"])
+            .default_value("text")
+            .help("Format in which to print the results")
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> OutputFormat {
+        match matches.get_one::<String>("output").map(|s| s.as_str()) {
+            Some("json") => OutputFormat::Json,
+            _ => OutputFormat::Text,
+        }
+    }
+}
+
+/// Print a JSON value to stdout.
+pub fn print_json(value: &Value) -> Result<(), String> {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value)
+            .map_err(|e| format!("Error during JSON serialization: {e}"))?
+    );
+    Ok(())
+}
+
+pub fn json_time(time: &DateTime<Utc>) -> Value {
+    Value::from(time.to_rfc3339_opts(SecondsFormat::Nanos, true))
+}
+
+fn json_static_header(sh: &StaticHeader) -> Value {
+    json!({
+        "pool_uuid": sh.identifiers.pool_uuid.to_string(),
+        "dev_uuid": sh.identifiers.device_uuid.to_string(),
+        "sigblock_version": sh.sigblock_version as u8,
+        "blkdev_size_sectors": *sh.blkdev_size.sectors(),
+        "mda_size_sectors": *sh.mda_size.sectors(),
+        "reserved_size_sectors": *sh.reserved_size.sectors(),
+        "flags": sh.flags,
+        "initialization_time": json_time(&sh.initialization_time),
+    })
+}
+
+/// The signature block read from a device; the bytes read are included only
+/// if print_bytes is true.
+pub fn json_signature_block(shr: &StaticHeaderResult, print_bytes: bool) -> Value {
+    let (status, error, header) = match shr.header {
+        None => ("unreadable", None, Value::Null),
+        Some(Err(ref e)) => ("invalid", Some(e.to_string()), Value::Null),
+        Some(Ok(None)) => ("absent", None, Value::Null),
+        Some(Ok(Some(ref sh))) => ("valid", None, json_static_header(sh)),
+    };
+    let mut value = json!({
+        "status": status,
+        "error": error,
+        "header": header,
+    });
+    if print_bytes {
+        value["bytes"] = match shr.bytes {
+            Ok(ref bytes) => {
+                Value::from(bytes.iter().map(|b| format!("{b:02x}")).collect::<String>())
+            }
+            Err(_) => Value::Null,
+        };
+    }
+    value
+}
+
+pub fn json_bda(bda: &BDA) -> Value {
+    json!({
+        "pool_uuid": bda.pool_uuid().to_string(),
+        "dev_uuid": bda.dev_uuid().to_string(),
+        "sigblock_version": bda.sigblock_version() as u8,
+        "dev_size_sectors": *bda.dev_size().sectors(),
+        "extended_size_sectors": *bda.extended_size().sectors(),
+        "max_data_size_bytes": *bda.max_data_size().bytes(),
+        "initialization_time": json_time(&bda.initialization_time()),
+        "last_update_time": bda.last_update_time().map(json_time),
+        "previous_update_time": bda.previous_update_time().map(json_time),
+        "metadata_generations": bda
+            .metadata_generations()
+            .iter()
+            .map(json_time)
+            .collect::<Vec<_>>(),
+        "mda_headers": bda
+            .mda_headers()
+            .iter()
+            .enumerate()
+            .map(|(region, header)| {
+                header.as_ref().map(|h| {
+                    json!({
+                        "region": region,
+                        "last_updated": json_time(h.last_updated()),
+                        "used_bytes": *h.used(),
+                        "data_crc": h.data_crc(),
+                    })
+                })
+            })
+            .collect::<Vec<_>>(),
+    })
+}
+
+pub fn json_finding(finding: &InspectionFinding) -> Value {
+    let mut value = serde_json::to_value(finding).unwrap_or(Value::Null);
+    if let Value::Object(ref mut fields) = value {
+        fields.insert("severity".to_string(), json!(finding.severity()));
+        fields.insert("component".to_string(), json!(finding.component()));
+        fields.insert(
+            "device".to_string(),
+            json!(finding.device().map(|uuid| uuid.to_string())),
+        );
+        fields.insert("message".to_string(), Value::from(finding.to_string()));
+        fields.insert("fix".to_string(), json!(finding.fix()));
+    }
+    value
+}
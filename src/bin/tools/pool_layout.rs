@@ -0,0 +1,69 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::json;
+
+use stratisd::engine::pool_inspection::inspectors;
+
+use crate::tools::{
+    metadata_generations::open_pool_devices,
+    output::{print_json, OutputFormat},
+};
+
+/// Show which sectors of each block device of the pool to which the devices
+/// belong hold what, according to the most recently written pool-level
+/// metadata. The size of a device is known only if it is among devpaths.
+/// Print the layout as bars of width characters or, if output is JSON, as
+/// an object with the keys "pool_uuid" and "layout". If svg is given, also
+/// write the layout as an SVG image to that file.
+pub fn run(
+    devpaths: &[PathBuf],
+    width: u64,
+    svg: Option<&Path>,
+    output: OutputFormat,
+) -> Result<(), String> {
+    let (pool_uuid, mut devices) = open_pool_devices(devpaths, false)?;
+
+    let device_sizes = devices
+        .iter()
+        .map(|(_, _, bda)| (bda.dev_uuid(), bda.dev_size().sectors()))
+        .collect::<HashMap<_, _>>();
+
+    let (devpath, f, bda) = devices
+        .iter_mut()
+        .filter(|(_, _, bda)| bda.last_update_time().is_some())
+        .max_by_key(|(_, _, bda)| bda.last_update_time().copied())
+        .ok_or_else(|| format!("No metadata found on any device of pool {pool_uuid}"))?;
+    let data = bda
+        .load_state(f)
+        .map_err(|e| format!("Error reading metadata from {}: {}", devpath.display(), e))?
+        .expect("last update time is known");
+    let metadata = serde_json::from_slice(&data)
+        .map_err(|e| format!("Error parsing metadata into structs: {e}"))?;
+
+    let layout = inspectors::layout(&metadata, &device_sizes)
+        .map_err(|the_error| format!("Error: {}", the_error))?;
+
+    if let Some(svg) = svg {
+        fs::write(svg, layout.to_svg())
+            .map_err(|e| format!("Error writing {}: {}", svg.display(), e))?;
+    }
+
+    match output {
+        OutputFormat::Text => {
+            print!("{}", layout.to_ascii(width));
+            Ok(())
+        }
+        OutputFormat::Json => print_json(&json!({
+            "pool_uuid": pool_uuid.to_string(),
+            "layout": layout,
+        })),
+    }
+}
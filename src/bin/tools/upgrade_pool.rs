@@ -0,0 +1,52 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{fs, path::PathBuf};
+
+use libcryptsetup_rs::SafeMemHandle;
+use serde_json::json;
+
+use stratisd::engine::{upgrade_pool, SizedKeyMemory};
+
+use crate::tools::output::{print_json, OutputFormat};
+
+/// Read the passphrase from a file. A single trailing newline is not
+/// considered part of the passphrase.
+fn read_passphrase(path: &PathBuf) -> Result<SizedKeyMemory, String> {
+    let mut contents = fs::read(path)
+        .map_err(|e| format!("Error reading passphrase file {}: {e}", path.display()))?;
+    if contents.last() == Some(&b'\n') {
+        contents.pop();
+    }
+    let mut memory = SafeMemHandle::alloc(contents.len()).map_err(|e| e.to_string())?;
+    memory.as_mut().copy_from_slice(&contents);
+    contents.fill(0);
+    Ok(SizedKeyMemory::new(memory, contents.len()))
+}
+
+/// Upgrade the stopped pool to which devpaths belong to the current metadata
+/// format. If dry_run is true, only report whether the upgrade is possible.
+/// If output is JSON, print an object with the keys "pool_uuid", "encrypted",
+/// "required_bytes", "available_bytes", "sufficient_space" and "upgraded".
+pub fn run(
+    devpaths: &[PathBuf],
+    passphrase_file: Option<&PathBuf>,
+    dry_run: bool,
+    output: OutputFormat,
+) -> Result<(), String> {
+    let passphrase = passphrase_file.map(read_passphrase).transpose()?;
+    let report = upgrade_pool(devpaths, passphrase.as_ref(), dry_run).map_err(|e| e.to_string())?;
+    match output {
+        OutputFormat::Text => println!("{report}"),
+        OutputFormat::Json => print_json(&json!({
+            "pool_uuid": report.pool_uuid.to_string(),
+            "encrypted": report.encrypted,
+            "required_bytes": *report.required.bytes(),
+            "available_bytes": *report.available.bytes(),
+            "sufficient_space": report.sufficient_space,
+            "upgraded": report.upgraded,
+        }))?,
+    }
+    Ok(())
+}
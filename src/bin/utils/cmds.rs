@@ -5,10 +5,11 @@
 use std::{
     error::Error,
     fmt::{self, Display},
+    path::PathBuf,
     str::FromStr,
 };
 
-use clap::{builder::PossibleValuesParser, Arg, ArgAction, Command};
+use clap::{builder::PossibleValuesParser, Arg, ArgAction, ArgGroup, Command};
 
 #[cfg(feature = "systemd_compat")]
 use clap::builder::Str;
@@ -22,10 +23,12 @@ use stratisd::engine::{
     DEFAULT_INTEGRITY_TAG_SPEC,
 };
 
-use crate::utils::predict_usage;
+use crate::utils::predict_usage::{self, PoolChanges, PoolSource};
 
 #[cfg(feature = "systemd_compat")]
-use crate::utils::generators::{stratis_clevis_setup_generator, stratis_setup_generator};
+use crate::utils::generators::{
+    stratis_clevis_setup_generator, stratis_fstab_generator, stratis_setup_generator,
+};
 
 #[derive(Debug)]
 pub struct ExecutableError(pub String);
@@ -118,6 +121,99 @@ pool is encrypted, setting this option has no effect on the prediction."),
                         .help("Do not allocate space for integrity superblock")
                         .next_line_help(true)
                     ),
+                Command::new("existing-pool")
+                    .about("Predicts the space usage after changing an existing Stratis pool.")
+                    .arg(
+                        Arg::new("pool-name")
+                        .long("pool-name")
+                        .num_args(1)
+                        .help("Name of a pool managed by stratisd, which is queried over D-Bus.")
+                        .next_line_help(true)
+                    )
+                    .arg(
+                        Arg::new("metadata")
+                        .long("metadata")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .requires_all(["total-physical-size", "total-physical-used"])
+                        .help("File containing the current pool-level metadata of the pool, or - to read it from standard input.")
+                        .next_line_help(true)
+                    )
+                    .group(
+                        ArgGroup::new("pool")
+                        .args(["pool-name", "metadata"])
+                        .required(true)
+                    )
+                    .arg(
+                        Arg::new("total-physical-size")
+                        .long("total-physical-size")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u128))
+                        .requires("metadata")
+                        .help("Total physical size of the pool. Units are bytes.")
+                        .next_line_help(true)
+                    )
+                    .arg(
+                        Arg::new("total-physical-used")
+                        .long("total-physical-used")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u128))
+                        .requires("metadata")
+                        .help("Total physical space used by the pool. Units are bytes.")
+                        .next_line_help(true)
+                    )
+                    .arg(
+                        Arg::new("existing-filesystem-size")
+                        .long("existing-filesystem-size")
+                        .num_args(1)
+                        .action(ArgAction::Append)
+                        .value_parser(clap::value_parser!(u128))
+                        .requires("metadata")
+                        .help("Logical size of a filesystem already in the pool. May be specified multiple times, one for each filesystem. Units are bytes.")
+                        .next_line_help(true)
+                    )
+                    .arg(
+                        Arg::new("add-data-size")
+                        .long("add-data-size")
+                        .num_args(1)
+                        .action(ArgAction::Append)
+                        .value_parser(clap::value_parser!(u128))
+                        .help("Size of device to be added to the data tier of the pool. May be specified multiple times. Units are bytes.")
+                        .next_line_help(true)
+                    )
+                    .arg(
+                        Arg::new("add-cache-size")
+                        .long("add-cache-size")
+                        .num_args(1)
+                        .action(ArgAction::Append)
+                        .value_parser(clap::value_parser!(u128))
+                        .help("Size of device to be added to the cache tier of the pool. May be specified multiple times. Units are bytes.")
+                        .next_line_help(true)
+                    )
+                    .arg(
+                        Arg::new("filesystem-size")
+                        .long("filesystem-size")
+                        .num_args(1)
+                        .action(ArgAction::Append)
+                        .value_parser(clap::value_parser!(u128))
+                        .help("Size of filesystem to be made for this pool. May be specified multiple times, one for each filesystem. Units are bytes. Must be at least 512 MiB and less than 4 PiB.")
+                        .next_line_help(true)
+                    )
+                    .arg(Arg::new("encrypt")
+                        .long("encrypt")
+                        .action(ArgAction::SetTrue)
+                        .help("Whether the pool will be encrypted.")
+                        .long_help(
+"Space for crypt metadata is allocated when a pool is created, so encrypting the
+pool does not change the prediction. The prediction fails if the pool is
+already encrypted or has no space reserved for crypt metadata."),
+                    )
+                    .arg(
+                        Arg::new("no-overprovision")
+                        .long("no-overprovision")
+                        .action(ArgAction::SetTrue)
+                        .help("Indicates that overprovisioning will be disabled for the pool"),
+                    ),
                 Command::new("filesystem")
                     .about("Predicts the space usage when creating a Stratis filesystem.")
                     .arg(
@@ -182,6 +278,53 @@ impl<'a> UtilCommand<'a> for StratisPredictUsage {
                 )
                 .expect("only valid entries allowed"),
             ),
+            Some(("existing-pool", sub_m)) => predict_usage::predict_existing_pool_usage(
+                match sub_m.get_one::<PathBuf>("metadata") {
+                    Some(path) => PoolSource::Metadata {
+                        path: path.to_owned(),
+                        total_size: sub_m
+                            .get_one::<u128>("total-physical-size")
+                            .map(|n| Bytes(*n))
+                            .expect("required by metadata"),
+                        used_size: sub_m
+                            .get_one::<u128>("total-physical-used")
+                            .map(|n| Bytes(*n))
+                            .expect("required by metadata"),
+                        filesystem_sizes: sub_m
+                            .get_many::<u128>("existing-filesystem-size")
+                            .map(|szs| szs.map(|n| Bytes(*n)).collect::<Vec<_>>())
+                            .unwrap_or_default(),
+                    },
+                    None => PoolSource::Dbus(
+                        sub_m
+                            .get_one::<String>("pool-name")
+                            .expect("one of pool-name and metadata is required")
+                            .to_owned(),
+                    ),
+                },
+                PoolChanges {
+                    data_device_sizes: sub_m
+                        .get_many::<u128>("add-data-size")
+                        .map(|szs| szs.map(|n| Bytes(*n)).collect::<Vec<_>>())
+                        .unwrap_or_default(),
+                    cache_device_sizes: sub_m
+                        .get_many::<u128>("add-cache-size")
+                        .map(|szs| szs.map(|n| Bytes(*n)).collect::<Vec<_>>())
+                        .unwrap_or_default(),
+                    filesystem_sizes: sub_m
+                        .get_many::<u128>("filesystem-size")
+                        .map(|szs| szs.map(|n| Bytes(*n)).collect::<Vec<_>>())
+                        .unwrap_or_default(),
+                    encrypt: sub_m.get_flag("encrypt"),
+                    disable_overprovisioning: sub_m.get_flag("no-overprovision"),
+                },
+                LevelFilter::from_str(
+                    matches
+                        .get_one::<String>("log-level")
+                        .expect("default value set"),
+                )
+                .expect("only valid entries allowed"),
+            ),
             Some(("filesystem", sub_m)) => predict_usage::predict_filesystem_usage(
                 !sub_m.get_flag("no-overprovision"),
                 sub_m
@@ -276,11 +419,40 @@ impl<'a> UtilCommand<'a> for StratisClevisSetupGenerator {
     }
 }
 
+struct StratisFstabGenerator;
+
+impl<'a> UtilCommand<'a> for StratisFstabGenerator {
+    fn name(&self) -> &'a str {
+        "stratis-fstab-generator"
+    }
+
+    #[cfg(feature = "systemd_compat")]
+    fn run(&self, command_line_args: Vec<String>) -> Result<(), Box<dyn Error>> {
+        let matches = stratis_setup_generator_cmd("stratis-fstab-generator")
+            .get_matches_from(command_line_args);
+
+        stratis_fstab_generator::generator(
+            matches
+                .get_one::<String>("normal_priority_dir")
+                .expect("required")
+                .to_owned(),
+        )
+    }
+
+    #[cfg(not(feature = "systemd_compat"))]
+    fn run(&self, _command_line_args: Vec<String>) -> Result<(), Box<dyn Error>> {
+        Err(Box::new(ExecutableError(
+            "systemd compatibility disabled for this build".into(),
+        )))
+    }
+}
+
 pub fn cmds<'a>() -> Vec<Box<dyn UtilCommand<'a>>> {
     vec![
         Box::new(StratisPredictUsage),
         Box::new(StratisSetupGenerator),
         Box::new(StratisClevisSetupGenerator),
+        Box::new(StratisFstabGenerator),
     ]
 }
 
@@ -4,4 +4,5 @@
 
 mod lib;
 pub mod stratis_clevis_setup_generator;
+pub mod stratis_fstab_generator;
 pub mod stratis_setup_generator;
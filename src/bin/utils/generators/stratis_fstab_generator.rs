@@ -0,0 +1,407 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Entries in /etc/fstab which mount a Stratis filesystem must wait for the
+// pool to be started. For each pool named in /etc/fstab, this generator
+// writes a service which waits for the pool to appear and starts it, and a
+// drop-in for each mount unit which requires that service.
+//
+// A pool is named either by the device path, /dev/stratis/<pool>/<fs>, or by
+// the mount option x-stratis.pool=<pool UUID or name>, which is necessary when
+// the filesystem is identified by its UUID. The option x-stratis.unlock=
+// selects how an encrypted pool is unlocked: auto, token, passphrase or none.
+// The options are also accepted without the x- prefix, but mount passes such
+// options on to the kernel. The time to wait for the pool is taken from
+// x-systemd.device-timeout=.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::{create_dir_all, read_to_string},
+    path::{Path, PathBuf},
+};
+
+use log::{error, warn};
+use uuid::Uuid;
+
+use super::lib;
+
+const FSTAB_PATH: &str = "/etc/fstab";
+const STRATIS_DEV_DIR: &str = "/dev/stratis/";
+const STRATIS_MIN_PATH: &str = "/usr/bin/stratis-min";
+/// The default of DefaultDeviceTimeoutSec in systemd.
+const DEFAULT_TIMEOUT_SECS: u64 = 90;
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum PoolId {
+    Uuid(Uuid),
+    Name(String),
+}
+
+impl PoolId {
+    fn parse(value: &str) -> PoolId {
+        Uuid::parse_str(value)
+            .map(PoolId::Uuid)
+            .unwrap_or_else(|_| PoolId::Name(value.to_string()))
+    }
+
+    fn unit_name(&self) -> String {
+        match self {
+            PoolId::Uuid(u) => format!("stratis-fstab-setup-{}.service", u.as_hyphenated()),
+            PoolId::Name(n) => format!("stratis-fstab-setup-name-{}.service", escape(n)),
+        }
+    }
+}
+
+/// The settings of a pool gathered from all entries which mount one of its
+/// filesystems.
+#[derive(Debug, Eq, PartialEq)]
+struct PoolSetup {
+    unlock: String,
+    timeout_secs: u64,
+    /// Whether every entry is marked nofail, in which case the boot need not
+    /// wait for the pool.
+    nofail: bool,
+    mount_units: Vec<String>,
+}
+
+/// Undo the octal escapes which fstab uses for whitespace in paths.
+fn unescape_fstab(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|digits| bytes[i] == b'\\' && digits.iter().all(|b| (b'0'..=b'7').contains(b)))
+            .and_then(|digits| {
+                u8::try_from(
+                    digits
+                        .iter()
+                        .fold(0u16, |acc, d| acc * 8 + u16::from(d - b'0')),
+                )
+                .ok()
+            });
+        if let Some(byte) = octal {
+            result.push(byte);
+            i += 4;
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+/// Escape a string as systemd-escape does, optionally as a path.
+fn escape_with(value: &str, path: bool) -> String {
+    let value = if path {
+        let trimmed = value
+            .split('/')
+            .filter(|c| !c.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+        if trimmed.is_empty() {
+            return "-".to_string();
+        }
+        trimmed
+    } else {
+        value.to_string()
+    };
+    let mut escaped = String::new();
+    for (i, b) in value.bytes().enumerate() {
+        match b {
+            b'/' => escaped.push('-'),
+            b'.' if i == 0 => escaped.push_str("\\x2e"),
+            b if b.is_ascii_alphanumeric() || b == b':' || b == b'_' || b == b'.' => {
+                escaped.push(char::from(b))
+            }
+            b => escaped.push_str(&format!("\\x{b:02x}")),
+        }
+    }
+    escaped
+}
+
+fn escape(value: &str) -> String {
+    escape_with(value, false)
+}
+
+/// The name of the mount unit which systemd-fstab-generator creates for a
+/// mount point.
+fn mount_unit_name(mount_point: &str) -> String {
+    format!("{}.mount", escape_with(mount_point, true))
+}
+
+/// Parse a systemd time span of the forms used for device timeouts: a number
+/// of seconds, optionally followed by s, sec, m or min.
+fn parse_timeout(value: &str) -> Option<u64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number.parse::<u64>().ok()?;
+    match unit.trim() {
+        "" | "s" | "sec" => Some(number),
+        "m" | "min" => number.checked_mul(60),
+        _ => None,
+    }
+}
+
+fn stratis_option<'a>(options: &[&'a str], name: &str) -> Option<&'a str> {
+    options.iter().find_map(|o| {
+        o.strip_prefix("x-")
+            .unwrap_or(o)
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('='))
+    })
+}
+
+/// Gather the pools which must be started for the entries in an fstab.
+fn parse_fstab(fstab: &str) -> BTreeMap<PoolId, PoolSetup> {
+    let mut pools: BTreeMap<PoolId, PoolSetup> = BTreeMap::new();
+    for line in fstab.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        if fields.len() < 3 {
+            warn!("Ignoring malformed fstab entry: {line}");
+            continue;
+        }
+        let device = unescape_fstab(fields[0]);
+        let mount_point = unescape_fstab(fields[1]);
+        let options = fields
+            .get(3)
+            .map(|o| o.split(',').collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let pool_id = match stratis_option(&options, "stratis.pool") {
+            Some(value) => PoolId::parse(value),
+            None => match device
+                .strip_prefix(STRATIS_DEV_DIR)
+                .and_then(|rest| rest.split('/').next())
+            {
+                Some(pool) if !pool.is_empty() => PoolId::Name(pool.to_string()),
+                _ => continue,
+            },
+        };
+
+        let unlock = match stratis_option(&options, "stratis.unlock") {
+            Some(u @ ("auto" | "token" | "passphrase" | "none")) => u,
+            Some(u) => {
+                warn!("Unknown unlock method {u} for {mount_point}; using auto");
+                "auto"
+            }
+            None => "auto",
+        };
+        let timeout_secs = options
+            .iter()
+            .find_map(|o| o.strip_prefix("x-systemd.device-timeout="))
+            .and_then(|t| {
+                let parsed = parse_timeout(t);
+                if parsed.is_none() {
+                    warn!("Invalid device timeout {t} for {mount_point}; using the default");
+                }
+                parsed
+            })
+            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+        let nofail = options.contains(&"nofail");
+
+        let setup = pools.entry(pool_id).or_insert_with(|| PoolSetup {
+            unlock: unlock.to_string(),
+            timeout_secs,
+            nofail,
+            mount_units: Vec::new(),
+        });
+        if setup.unlock != unlock {
+            warn!(
+                "Conflicting unlock methods {} and {unlock} for the pool of {mount_point}; using {}",
+                setup.unlock, setup.unlock
+            );
+        }
+        setup.timeout_secs = setup.timeout_secs.max(timeout_secs);
+        setup.nofail &= nofail;
+        setup.mount_units.push(mount_unit_name(&mount_point));
+    }
+    pools
+}
+
+fn setup_unit(pool_id: &PoolId, setup: &PoolSetup) -> String {
+    let (description, args) = match pool_id {
+        PoolId::Uuid(u) => (format!("UUID {u}"), u.to_string()),
+        PoolId::Name(n) => (format!("name {n}"), format!("--name {}", quote(n))),
+    };
+    // The service waits for the pool itself, for as long as the device
+    // timeout permits, and may then wait for a passphrase, so systemd does
+    // not time it out.
+    format!(
+        r"[Unit]
+Description=Start Stratis pool with {description} for /etc/fstab
+Documentation=man:stratisd(8)
+DefaultDependencies=no
+Conflicts=shutdown.target
+Requires=stratisd-min-postinitrd.service
+After=stratisd-min-postinitrd.service systemd-ask-password-console.path
+Before=shutdown.target{before}
+
+[Service]
+Type=oneshot
+RemainAfterExit=yes
+TimeoutStartSec=infinity
+ExecStart={STRATIS_MIN_PATH} pool setup --timeout={timeout} --unlock={unlock} {args}
+",
+        before = if setup.nofail {
+            ""
+        } else {
+            " local-fs-pre.target"
+        },
+        timeout = setup.timeout_secs,
+        unlock = setup.unlock,
+    )
+}
+
+/// Quote an argument for a unit file command line if necessary.
+fn quote(value: &str) -> String {
+    if value
+        .chars()
+        .any(|c| c.is_whitespace() || c == '"' || c == '\'' || c == '\\' || c == '%')
+    {
+        format!(
+            "\"{}\"",
+            value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('%', "%%")
+        )
+    } else {
+        value.to_string()
+    }
+}
+
+fn mount_drop_in(unit_name: &str) -> String {
+    format!(
+        r"[Unit]
+Requires={unit_name}
+After={unit_name}
+"
+    )
+}
+
+/// The files to write, relative to the generator directory, for an fstab.
+fn generate(fstab: &str) -> Vec<(PathBuf, String)> {
+    let mut files = Vec::new();
+    for (pool_id, setup) in parse_fstab(fstab) {
+        let unit_name = pool_id.unit_name();
+        files.push((PathBuf::from(&unit_name), setup_unit(&pool_id, &setup)));
+        for mount_unit in setup.mount_units.iter() {
+            files.push((
+                [&format!("{mount_unit}.d"), "50-stratis.conf"]
+                    .iter()
+                    .collect::<PathBuf>(),
+                mount_drop_in(&unit_name),
+            ));
+        }
+    }
+    files
+}
+
+fn generator_with_err(normal_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let fstab = match read_to_string(FSTAB_PATH) {
+        Ok(fstab) => fstab,
+        Err(e) => {
+            warn!("Failed to read {FSTAB_PATH}: {e}; disabling generator");
+            return Ok(());
+        }
+    };
+    for (relative_path, contents) in generate(&fstab) {
+        let path = normal_dir.join(relative_path);
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        lib::write_unit_file(&path, contents)?;
+    }
+    Ok(())
+}
+
+pub fn generator(normal_dir: String) -> Result<(), Box<dyn Error>> {
+    lib::setup_logger()?;
+
+    let res = generator_with_err(Path::new(&normal_dir));
+    if let Err(ref e) = res {
+        error!("systemd generator failed with error: {}", e);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: &str = "c7da44cf-e1f6-4a2d-9a2d-4bd8d4ffb8c4";
+
+    #[test]
+    /// Pools are found by device path and by mount option, and entries for
+    /// other filesystems are ignored.
+    fn test_parse_fstab() {
+        let fstab = format!(
+            "# comment\n\
+             /dev/mapper/root / xfs defaults 0 0\n\
+             /dev/stratis/pool1/home /home xfs defaults 0 0\n\
+             /dev/stratis/pool1/srv /srv xfs defaults,x-systemd.device-timeout=5min 0 0\n\
+             UUID=0b7e6d43-fbc1-4ed5-8bb4-1b8b3a5c6e15 /mnt/data\\040dir xfs nofail,x-stratis.pool={UUID},x-stratis.unlock=passphrase 0 0\n"
+        );
+        let pools = parse_fstab(&fstab);
+        assert_eq!(pools.len(), 2);
+
+        let pool1 = &pools[&PoolId::Name("pool1".to_string())];
+        assert_eq!(pool1.timeout_secs, 300);
+        assert_eq!(pool1.unlock, "auto");
+        assert!(!pool1.nofail);
+        assert_eq!(pool1.mount_units, vec!["home.mount", "srv.mount"]);
+
+        let pool2 = &pools[&PoolId::Uuid(Uuid::parse_str(UUID).unwrap())];
+        assert_eq!(pool2.timeout_secs, DEFAULT_TIMEOUT_SECS);
+        assert_eq!(pool2.unlock, "passphrase");
+        assert!(pool2.nofail);
+        assert_eq!(pool2.mount_units, vec!["mnt-data\\x20dir.mount"]);
+    }
+
+    #[test]
+    /// A service is written for each pool and a drop-in for each mount; the
+    /// boot only waits for pools with entries which are not nofail.
+    fn test_generate() {
+        let fstab = format!(
+            "/dev/stratis/pool1/home /home xfs defaults 0 0\n\
+             /dev/stratis/pool2/fs /var/lib/fs xfs nofail,stratis.pool={UUID},x-systemd.device-timeout=30 0 0\n"
+        );
+        let files = generate(&fstab);
+        assert_eq!(files.len(), 4);
+
+        let name_unit = PathBuf::from("stratis-fstab-setup-name-pool1.service");
+        let (_, contents) = files.iter().find(|(p, _)| *p == name_unit).unwrap();
+        assert!(contents.contains("Before=shutdown.target local-fs-pre.target\n"));
+        assert!(contents.contains("pool setup --timeout=90 --unlock=auto --name pool1\n"));
+
+        let uuid_unit = PathBuf::from(format!("stratis-fstab-setup-{UUID}.service"));
+        let (_, contents) = files.iter().find(|(p, _)| *p == uuid_unit).unwrap();
+        assert!(contents.contains("Before=shutdown.target\n"));
+        assert!(contents.contains(&format!("pool setup --timeout=30 --unlock=auto {UUID}\n")));
+
+        let drop_in = PathBuf::from("var-lib-fs.mount.d/50-stratis.conf");
+        let (_, contents) = files.iter().find(|(p, _)| *p == drop_in).unwrap();
+        assert!(contents.contains(&format!("Requires=stratis-fstab-setup-{UUID}.service\n")));
+    }
+
+    #[test]
+    /// Names are escaped as systemd-escape does.
+    fn test_escape() {
+        assert_eq!(mount_unit_name("/"), "-.mount");
+        assert_eq!(mount_unit_name("/var//lib/"), "var-lib.mount");
+        assert_eq!(mount_unit_name("/mnt/my-fs"), "mnt-my\\x2dfs.mount");
+        assert_eq!(escape(".hidden pool"), "\\x2ehidden\\x20pool");
+        assert_eq!(parse_timeout("2m"), Some(120));
+        assert_eq!(parse_timeout("1h"), None);
+    }
+}
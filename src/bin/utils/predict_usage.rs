@@ -3,8 +3,19 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::{
+    cmp::max,
     error::Error,
     fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+};
+#[cfg(feature = "dbus_enabled")]
+use std::{collections::HashMap, time::Duration};
+
+#[cfg(feature = "dbus_enabled")]
+use dbus::{
+    arg::{PropMap, RefArg},
+    blocking::{stdintf::org_freedesktop_dbus::ObjectManager, Connection},
 };
 
 use env_logger::Builder;
@@ -27,6 +38,20 @@ const FS_SIZE_LOOKUP_TABLE_LEN: usize = 27;
 const FS_LOGICAL_SIZE_MAX: u128 = 36_028_797_018_963_968; // 32 PiB
 const FS_LOGICAL_SIZE_MIN: u128 = 536_870_912; // 512 MiB
 
+#[cfg(feature = "dbus_enabled")]
+const STRATIS_BASE_SERVICE: &str = "org.storage.stratis3";
+#[cfg(feature = "dbus_enabled")]
+const STRATIS_BASE_PATH: &str = "/org/storage/stratis3";
+#[cfg(feature = "dbus_enabled")]
+const POOL_INTERFACE_PREFIX: &str = "org.storage.stratis3.pool.";
+#[cfg(feature = "dbus_enabled")]
+const FILESYSTEM_INTERFACE_PREFIX: &str = "org.storage.stratis3.filesystem.";
+// The first revision of the pool interface with the Metadata method.
+#[cfg(feature = "dbus_enabled")]
+const POOL_INTERFACE_METADATA: &str = "org.storage.stratis3.pool.r7";
+#[cfg(feature = "dbus_enabled")]
+const DBUS_TIMEOUT: Duration = Duration::from_secs(120);
+
 struct FSSizeLookup {
     internal: Vec<u128>,
 }
@@ -164,36 +189,47 @@ pub fn predict_filesystem_usage(
     Ok(())
 }
 
+// Calculate the space on a device that remains for data after Stratis
+// metadata and, if the pool uses integrity, integrity metadata have been
+// allocated.
+fn device_usable_size(
+    size: Sectors,
+    integrity_spec: Option<ValidatedIntegritySpec>,
+) -> Result<Sectors, Box<PredictError>> {
+    let stratis_metadata_alloc = BDA::default().extended_size().sectors();
+    info!("Total size of device: {:}", size);
+
+    let integrity_deduction = integrity_spec
+        .map(|spec| integrity_meta_space(size, spec))
+        .unwrap_or(Sectors(0));
+    info!(
+        "Deduction for stratis metadata: {:}",
+        stratis_metadata_alloc
+    );
+
+    info!("Deduction for integrity space: {:}", integrity_deduction);
+    (*size)
+        .checked_sub(*stratis_metadata_alloc)
+        .and_then(|r| r.checked_sub(*integrity_deduction))
+        .map(Sectors)
+        .map(|x| {
+            info!("Size after deductions: {:}", x);
+            x
+        })
+        .ok_or_else(|| {
+            Box::new(PredictError(
+                "Some device sizes too small for DM metadata.".into(),
+            ))
+        })
+}
+
 fn predict_pool_metadata_usage(
     device_sizes: Vec<Sectors>,
     integrity_spec: ValidatedIntegritySpec,
 ) -> Result<Sectors, Box<dyn Error>> {
-    let stratis_metadata_alloc = BDA::default().extended_size().sectors();
     let stratis_avail_sizes = device_sizes
         .iter()
-        .map(|&s| {
-            info!("Total size of device: {:}", s);
-
-            let integrity_deduction = integrity_meta_space(s, integrity_spec);
-            info!(
-                "Deduction for stratis metadata: {:}",
-                stratis_metadata_alloc
-            );
-
-            info!("Deduction for integrity space: {:}", integrity_deduction);
-            (*s).checked_sub(*stratis_metadata_alloc)
-                .and_then(|r| r.checked_sub(*integrity_deduction))
-                .map(Sectors)
-                .map(|x| {
-                    info!("Size after deductions: {:}", x);
-                    x
-                })
-                .ok_or_else(|| {
-                    Box::new(PredictError(
-                        "Some device sizes too small for DM metadata.".into(),
-                    ))
-                })
-        })
+        .map(|&s| device_usable_size(s, Some(integrity_spec)))
         .collect::<Result<Vec<_>, _>>()?;
 
     let crypt_metadata_size = DEFAULT_CRYPT_DATA_OFFSET_V2;
@@ -259,3 +295,336 @@ pub fn predict_pool_usage(
 
     Ok(())
 }
+
+/// Where to find the current state of an existing pool.
+pub enum PoolSource {
+    /// The pool-level metadata of the pool, as returned by the pool's
+    /// Metadata D-Bus method, together with the pool's total physical size
+    /// and total physical usage and the logical sizes of its filesystems.
+    Metadata {
+        path: PathBuf,
+        total_size: Bytes,
+        used_size: Bytes,
+        filesystem_sizes: Vec<Bytes>,
+    },
+    /// The name of a pool managed by a running stratisd, which is queried
+    /// over D-Bus.
+    Dbus(String),
+}
+
+/// Operations on an existing pool for which to predict the space usage.
+pub struct PoolChanges {
+    pub data_device_sizes: Vec<Bytes>,
+    pub cache_device_sizes: Vec<Bytes>,
+    pub filesystem_sizes: Vec<Bytes>,
+    pub encrypt: bool,
+    pub disable_overprovisioning: bool,
+}
+
+// The state of an existing pool which determines its space usage.
+struct ExistingPool {
+    total_size: Sectors,
+    used_size: Sectors,
+    // Space for Stratis, integrity, and crypt metadata on the data devices.
+    stratis_metadata: Sectors,
+    // Space for the MDV and the thin metadata device and its spare.
+    admin: Sectors,
+    thin_meta: Sectors,
+    crypt_meta: Sectors,
+    integrity_spec: Option<ValidatedIntegritySpec>,
+    overprovisioned: bool,
+    encrypted: bool,
+    filesystem_sizes: Vec<Bytes>,
+}
+
+// Sum the lengths of the segments found at the given location in the
+// pool-level metadata. A missing list of segments is treated as empty.
+fn segments_length(metadata: &Value, pointer: &str) -> Result<Sectors, Box<dyn Error>> {
+    Ok(match metadata.pointer(pointer) {
+        Some(segments) => serde_json::from_value::<Vec<(Sectors, Sectors)>>(segments.clone())?
+            .iter()
+            .map(|(_, length)| *length)
+            .sum(),
+        None => Sectors(0),
+    })
+}
+
+impl ExistingPool {
+    fn new(
+        metadata: &Value,
+        total_size: Bytes,
+        used_size: Bytes,
+        filesystem_sizes: Vec<Bytes>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let devs = metadata
+            .pointer("/backstore/data_tier/blockdev/devs")
+            .and_then(|devs| devs.as_array())
+            .filter(|_| metadata.pointer("/flex_devs/thin_meta_dev").is_some())
+            .ok_or_else(|| {
+                Box::new(PredictError(
+                    "Metadata is not the pool-level metadata of a Stratis pool.".into(),
+                ))
+            })?;
+
+        let integrity_meta = devs
+            .iter()
+            .map(|dev| segments_length(dev, "/integrity_meta_allocs"))
+            .sum::<Result<Sectors, _>>()?;
+        let crypt_meta = segments_length(metadata, "/backstore/cap/crypt_meta_allocs")?;
+        let stratis_metadata =
+            devs.len() * BDA::default().extended_size().sectors() + integrity_meta + crypt_meta;
+
+        let thin_meta = segments_length(metadata, "/flex_devs/thin_meta_dev")?;
+        let admin = segments_length(metadata, "/flex_devs/meta_dev")?
+            + thin_meta
+            + segments_length(metadata, "/flex_devs/thin_meta_dev_spare")?;
+
+        let integrity_spec = metadata
+            .pointer("/backstore/data_tier/integrity_spec")
+            .map(|spec| serde_json::from_value::<ValidatedIntegritySpec>(spec.clone()))
+            .transpose()?;
+
+        Ok(ExistingPool {
+            total_size: total_size.sectors(),
+            used_size: used_size.sectors(),
+            stratis_metadata,
+            admin,
+            thin_meta,
+            crypt_meta,
+            integrity_spec,
+            overprovisioned: metadata
+                .pointer("/thinpool_dev/enable_overprov")
+                .and_then(|enabled| enabled.as_bool())
+                .unwrap_or(true),
+            encrypted: metadata
+                .pointer("/features")
+                .and_then(|features| features.as_array())
+                .is_some_and(|features| features.iter().any(|f| f == "Encryption")),
+            filesystem_sizes,
+        })
+    }
+}
+
+fn read_metadata(path: &Path) -> Result<Value, Box<dyn Error>> {
+    let metadata = if path == Path::new("-") {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(path)?
+    };
+    Ok(serde_json::from_str(&metadata)?)
+}
+
+// Get the value of a property from whichever interface with the given prefix
+// an object implements it on.
+#[cfg(feature = "dbus_enabled")]
+fn dbus_prop<'a>(
+    interfaces: &'a HashMap<String, PropMap>,
+    prefix: &str,
+    name: &str,
+) -> Option<&'a dyn RefArg> {
+    interfaces
+        .iter()
+        .filter(|(interface, _)| interface.starts_with(prefix))
+        .find_map(|(_, props)| props.get(name))
+        .map(|prop| &*prop.0)
+}
+
+#[cfg(feature = "dbus_enabled")]
+fn existing_pool_from_dbus(name: &str) -> Result<ExistingPool, Box<dyn Error>> {
+    let conn = Connection::new_system()?;
+    let objects = conn
+        .with_proxy(STRATIS_BASE_SERVICE, STRATIS_BASE_PATH, DBUS_TIMEOUT)
+        .get_managed_objects()?;
+
+    let (pool_path, pool_interfaces) = objects
+        .iter()
+        .find(|(_, interfaces)| {
+            dbus_prop(interfaces, POOL_INTERFACE_PREFIX, "Name").and_then(|n| n.as_str())
+                == Some(name)
+        })
+        .ok_or_else(|| Box::new(PredictError(format!("No pool named {name} found."))))?;
+
+    let total_size = dbus_prop(pool_interfaces, POOL_INTERFACE_PREFIX, "TotalPhysicalSize")
+        .and_then(|size| size.as_str())
+        .and_then(|size| size.parse::<u128>().ok())
+        .ok_or_else(|| {
+            Box::new(PredictError(format!(
+                "Total physical size of pool {name} is not known."
+            )))
+        })?;
+    let used_size = dbus_prop(pool_interfaces, POOL_INTERFACE_PREFIX, "TotalPhysicalUsed")
+        .and_then(|used| {
+            let mut used = used.as_iter()?;
+            match (used.next()?.as_u64()?, used.next()?.as_str()?) {
+                (1, size) => size.parse::<u128>().ok(),
+                _ => None,
+            }
+        })
+        .ok_or_else(|| {
+            Box::new(PredictError(format!(
+                "Total physical usage of pool {name} is not known."
+            )))
+        })?;
+
+    let filesystem_sizes = objects
+        .values()
+        .filter(|interfaces| {
+            dbus_prop(interfaces, FILESYSTEM_INTERFACE_PREFIX, "Pool").and_then(|p| p.as_str())
+                == Some(&**pool_path)
+        })
+        .map(|interfaces| {
+            dbus_prop(interfaces, FILESYSTEM_INTERFACE_PREFIX, "Size")
+                .and_then(|size| size.as_str())
+                .and_then(|size| size.parse::<u128>().ok())
+                .map(Bytes)
+                .ok_or_else(|| {
+                    Box::new(PredictError(format!(
+                        "Size of a filesystem in pool {name} is not known."
+                    )))
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (metadata, rc, rs): (String, u16, String) = conn
+        .with_proxy(STRATIS_BASE_SERVICE, pool_path.clone(), DBUS_TIMEOUT)
+        .method_call(POOL_INTERFACE_METADATA, "Metadata", (true,))?;
+    if rc != 0 {
+        return Err(Box::new(PredictError(rs)));
+    }
+
+    ExistingPool::new(
+        &serde_json::from_str(&metadata)?,
+        Bytes(total_size),
+        Bytes(used_size),
+        filesystem_sizes,
+    )
+}
+
+#[cfg(not(feature = "dbus_enabled"))]
+fn existing_pool_from_dbus(_name: &str) -> Result<ExistingPool, Box<dyn Error>> {
+    Err(Box::new(PredictError(
+        "D-Bus support disabled for this build; specify the pool's metadata instead.".into(),
+    )))
+}
+
+// Predict usage for an existing pool after the given changes have been made
+// to it.
+pub fn predict_existing_pool_usage(
+    source: PoolSource,
+    changes: PoolChanges,
+    log_level: LevelFilter,
+) -> Result<(), Box<dyn Error>> {
+    Builder::new().filter(None, log_level).init();
+
+    let pool = match source {
+        PoolSource::Metadata {
+            path,
+            total_size,
+            used_size,
+            filesystem_sizes,
+        } => ExistingPool::new(
+            &read_metadata(&path)?,
+            total_size,
+            used_size,
+            filesystem_sizes,
+        )?,
+        PoolSource::Dbus(name) => existing_pool_from_dbus(&name)?,
+    };
+
+    if changes.encrypt {
+        if pool.encrypted {
+            return Err(Box::new(PredictError("Pool is already encrypted.".into())));
+        }
+        if pool.crypt_meta == Sectors(0) {
+            return Err(Box::new(PredictError(
+                "Pool has no space reserved for crypt metadata and can not be encrypted.".into(),
+            )));
+        }
+        info!("Space for crypt metadata is already allocated; encryption has no effect on usage");
+    }
+
+    let data_device_sizes = changes
+        .data_device_sizes
+        .iter()
+        .map(|s| s.sectors())
+        .collect::<Vec<_>>();
+    let added_usable = data_device_sizes
+        .iter()
+        .map(|&s| device_usable_size(s, pool.integrity_spec))
+        .sum::<Result<Sectors, _>>()?;
+    let added_size: Sectors = data_device_sizes.iter().cloned().sum();
+
+    let total_size = pool.total_size + added_size;
+    let stratis_metadata = pool.stratis_metadata + (added_size - added_usable);
+    let non_metadata_size = total_size - stratis_metadata;
+
+    // The thin metadata device and its spare are extended as the data tier
+    // grows, but are never shrunk.
+    let size_params = ThinPoolSizeParams::new(non_metadata_size)?;
+    let thin_meta = max(pool.thin_meta, size_params.meta_size());
+    let admin = pool.admin + 2usize * (thin_meta - pool.thin_meta);
+
+    let data_limit = (*non_metadata_size)
+        .checked_sub(*admin)
+        .map(Sectors)
+        .ok_or_else(|| {
+            Box::new(PredictError(
+                "Sum of all device sizes too small for a Stratis pool.".into(),
+            ))
+        })?;
+
+    let overprovisioned = pool.overprovisioned && !changes.disable_overprovisioning;
+    if !overprovisioned {
+        let logical_size: Sectors = pool
+            .filesystem_sizes
+            .iter()
+            .chain(changes.filesystem_sizes.iter())
+            .map(|s| s.sectors())
+            .sum();
+        if logical_size > data_limit {
+            return Err(Box::new(PredictError(format!(
+                "The sum of the logical sizes of all filesystems ({}) must not exceed the data space available to the thin pool ({}) if the pool does not allow overprovisioning.",
+                logical_size.bytes(),
+                data_limit.bytes()
+            ))));
+        }
+    }
+
+    let fs_used = get_filesystem_prediction(overprovisioned, changes.filesystem_sizes)?;
+
+    let used_size = pool.used_size
+        + (stratis_metadata - pool.stratis_metadata)
+        + (admin - pool.admin)
+        + fs_used;
+    let avail_size = (*total_size)
+        .checked_sub(*used_size)
+        .map(Sectors)
+        .ok_or_else(|| {
+            Box::new(PredictError(
+                "Filesystems will take up too much space on specified pool.".into(),
+            ))
+        })?;
+
+    let total_size_str = Value::String((*(total_size.bytes())).to_string());
+    let used_size_str = Value::String((*(used_size.bytes())).to_string());
+    let avail_size_str = Value::String((*(avail_size.bytes())).to_string());
+    let stratis_admin_str = Value::String((*(admin.bytes())).to_string());
+    let stratis_metadata_str = Value::String((*(stratis_metadata.bytes())).to_string());
+
+    let mut json = json! {
+        {"total": total_size_str, "used": used_size_str, "free": avail_size_str, "stratis-admin-space": stratis_admin_str, "stratis-metadata-space": stratis_metadata_str}
+    };
+
+    if !changes.cache_device_sizes.is_empty() {
+        let cache_size = changes
+            .cache_device_sizes
+            .iter()
+            .map(|s| device_usable_size(s.sectors(), None))
+            .sum::<Result<Sectors, _>>()?;
+        json["cache"] = Value::String((*(cache_size.bytes())).to_string());
+    }
+
+    println!("{json}");
+
+    Ok(())
+}
@@ -17,6 +17,7 @@ use crate::{
         blockdev::create_dbus_blockdev,
         consts,
         filesystem::create_dbus_filesystem,
+        polkit::{ACTION_KEY_MANAGE, ACTION_POOL_CREATE, ACTION_POOL_DESTROY},
         pool::create_dbus_pool,
         types::{DbusErrorEnum, TData, OK_STRING},
         util::{engine_to_dbus_err_tuple, get_next_arg, tuple_to_option},
@@ -40,6 +41,8 @@ pub fn destroy_pool(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let default_return = (false, uuid_to_string!(PoolUuid::nil()));
     let return_message = message.method_return();
 
+    check_polkit!(m; ACTION_POOL_DESTROY; default_return; return_message);
+
     let pool_uuid = match m
         .tree
         .get(&pool_path)
@@ -88,6 +91,8 @@ pub fn unset_key(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let default_return = false;
     let return_message = message.method_return();
 
+    check_polkit!(m; ACTION_KEY_MANAGE; default_return; return_message);
+
     let msg = match handle_action!(block_on(dbus_context.engine.get_key_handler()).unset(
         &match KeyDescription::try_from(key_desc_str) {
             Ok(kd) => kd,
@@ -124,6 +129,8 @@ pub fn set_key(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let default_return = (false, false);
     let return_message = message.method_return();
 
+    check_polkit!(m; ACTION_KEY_MANAGE; default_return; return_message);
+
     let msg = match handle_action!(block_on(dbus_context.engine.get_key_handler()).set(
         &match KeyDescription::try_from(key_desc_str) {
             Ok(kd) => kd,
@@ -290,6 +297,8 @@ pub fn create_pool(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let default_return: (bool, (dbus::Path<'static>, Vec<dbus::Path<'static>>)) =
         (false, (dbus::Path::default(), Vec::new()));
 
+    check_polkit!(m; ACTION_POOL_CREATE; default_return; return_message);
+
     match tuple_to_option(redundancy_tuple) {
         None | Some(0) => {}
         Some(n) => {
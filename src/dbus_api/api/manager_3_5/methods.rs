@@ -11,6 +11,7 @@ use futures::executor::block_on;
 use crate::{
     dbus_api::{
         blockdev::create_dbus_blockdev,
+        polkit::ACTION_POOL_CREATE,
         pool::create_dbus_pool,
         types::{DbusErrorEnum, TData, OK_STRING},
         util::{engine_to_dbus_err_tuple, get_next_arg, tuple_to_option},
@@ -38,6 +39,8 @@ pub fn create_pool(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let default_return: (bool, (dbus::Path<'static>, Vec<dbus::Path<'static>>)) =
         (false, (dbus::Path::default(), Vec::new()));
 
+    check_polkit!(m; ACTION_POOL_CREATE; default_return; return_message);
+
     let key_desc = match key_desc_tuple.and_then(tuple_to_option) {
         Some(kds) => match KeyDescription::try_from(kds) {
             Ok(kd) => Some(kd),
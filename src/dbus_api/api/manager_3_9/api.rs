@@ -0,0 +1,156 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::{Factory, MTSync, Method};
+
+use crate::dbus_api::{
+    api::manager_3_9::methods::{
+        apply_configuration, create_pool_job, export_pool_config, import_pool_config,
+        start_pool_job, thin_meta_spare, upgrade_pool,
+    },
+    types::TData,
+};
+
+/// Takes the same arguments as CreatePool, which is run by the job, so an
+/// encrypted pool is created by passing key descriptions or Clevis infos.
+pub fn create_pool_job_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("CreatePoolJob", (), create_pool_job)
+        .in_arg(("name", "s"))
+        .in_arg(("devices", "as"))
+        // Optional key descriptions of key in the kernel keyring
+        // a: array of zero or more elements
+        // b: true if a token slot is specified
+        // i: token slot
+        // s: key description
+        //
+        // Rust representation: Vec<((bool, u32), String)>
+        .in_arg(("key_desc", "a((bu)s)"))
+        // Optional Clevis infos for binding on initialization.
+        // a: array of zero or more elements
+        // b: true if a token slot is specified
+        // i: token slot
+        // s: pin name
+        // s: JSON config for Clevis use
+        //
+        // Rust representation: Vec<((bool, u32), String, String)>
+        .in_arg(("clevis_info", "a((bu)ss)"))
+        // Optional journal size for integrity metadata reservation.
+        // b: true if the size should be specified.
+        //    false if the default should be used.
+        // i: Integer representing journal size in bytes.
+        //
+        // Rust representation: (bool, u64)
+        .in_arg(("journal_size", "(bt)"))
+        // Optional tag size or specification for integrity metadata
+        // reservation.
+        // b: true if the size should be specified.
+        //    false if the default should be used.
+        // s: Tag size specification.
+        //
+        // Rust representation: (bool, String)
+        .in_arg(("tag_spec", "(bs)"))
+        // Optionally specify whether to reserve space for integrity
+        // superblock.
+        // b: true if the second value is to be read, otherwise false.
+        // b: true if the superblock reservation is supposed to be done
+        //
+        // Rust representation: (bool, bool)
+        .in_arg(("allocate_superblock", "(bb)"))
+        // o: Object path of the job which creates the pool
+        .out_arg(("job", "o"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+/// Takes the same arguments as StartPool, which is run by the job. Starting a
+/// pool may take long because it checks, and if necessary repairs, the thin
+/// pool metadata.
+pub fn start_pool_job_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("StartPoolJob", (), start_pool_job)
+        .in_arg(("id", "s"))
+        .in_arg(("id_type", "s"))
+        .in_arg(("unlock_method", "(b(bu))"))
+        .in_arg(("key_fd", "(bh)"))
+        // o: Object path of the job which starts the pool
+        .out_arg(("job", "o"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn apply_configuration_method(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Method<MTSync<TData>, TData> {
+    f.method("ApplyConfiguration", (), apply_configuration)
+        // Desired state of the pools, as JSON or TOML
+        .in_arg(("config", "s"))
+        // b: true if the actions should only be computed, not performed
+        .in_arg(("dry_run", "b"))
+        // as: Descriptions of the actions needed to reach the desired state
+        .out_arg(("actions", "as"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn export_pool_config_method(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Method<MTSync<TData>, TData> {
+    f.method("ExportPoolConfig", (), export_pool_config)
+        .in_arg(("pool", "o"))
+        // s: JSON document describing the pool and the UUIDs of its
+        //    filesystems
+        .out_arg(("config", "s"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn import_pool_config_method(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Method<MTSync<TData>, TData> {
+    f.method("ImportPoolConfig", (), import_pool_config)
+        // Document produced by ExportPoolConfig
+        .in_arg(("config", "s"))
+        // Data devices for the new pool
+        .in_arg(("devices", "as"))
+        // b: true if a pool was created
+        // o: Object path of the pool
+        .out_arg(("result", "(bo)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn upgrade_pool_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("UpgradePool", (), upgrade_pool)
+        // UUID of a stopped pool with legacy metadata
+        .in_arg(("pool_uuid", "s"))
+        // Optional file descriptor from which to read the passphrase of an
+        // encrypted pool
+        .in_arg(("passphrase_fd", "(bh)"))
+        // b: true if the upgrade should only be checked, not performed
+        .in_arg(("dry_run", "b"))
+        // In order from left to right:
+        // b: true if the pool was upgraded
+        // b: true if the pool is encrypted
+        // b: true if the pool has enough free space to be upgraded
+        // t: free space in bytes required by the upgrade
+        // t: free space in bytes available on the pool
+        .out_arg(("result", "(bbbtt)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn thin_meta_spare_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("ThinMetaSpare", (), thin_meta_spare)
+        // UUID of a stopped pool
+        .in_arg(("pool_uuid", "s"))
+        // One of "report", "validate", "swap", or "reprovision"
+        .in_arg(("action", "s"))
+        // In order from left to right:
+        // t: size of the spare thin metadata device in bytes
+        // t: size of the thin metadata device in bytes
+        // b: true if the spare has been validated since it last changed
+        // s: time at which the spare was last validated
+        .out_arg(("result", "(tt(bs))"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
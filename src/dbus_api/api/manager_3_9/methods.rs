@@ -0,0 +1,480 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{collections::HashSet, path::PathBuf, str::FromStr};
+
+use chrono::SecondsFormat;
+use dbus::{arg::OwnedFd, Message};
+use dbus_tree::{MTSync, MethodInfo, MethodResult};
+use futures::executor::block_on;
+
+use crate::{
+    dbus_api::{
+        blockdev::create_dbus_blockdev,
+        filesystem::create_dbus_filesystem,
+        job::start_job,
+        polkit::{ACTION_ENCRYPTION_BIND, ACTION_FILESYSTEM_CREATE, ACTION_POOL_CREATE},
+        pool::create_dbus_pool,
+        types::{DbusErrorEnum, TData, OK_STRING},
+        util::{engine_to_dbus_err_tuple, get_next_arg, tuple_to_option},
+    },
+    engine::{
+        apply_configuration, export_pool_config, plan_configuration, plan_pool_import,
+        BlockDevTier, ConfigAction, ConfigChange, PoolExport, PoolIdentifier, PoolUuid,
+        PoolsConfig, ThinMetaSpareAction,
+    },
+    stratis::StratisError,
+};
+
+pub fn create_pool_job(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let name: &str = get_next_arg(&mut iter, 0)?;
+
+    let return_message = message.method_return();
+    let default_return = dbus::Path::default();
+
+    // Check before the job is created so that an unauthorized caller is
+    // refused immediately; CreatePool checks again when the job runs.
+    check_polkit!(m; ACTION_POOL_CREATE; default_return; return_message);
+
+    start_job(m, &format!("Create pool {name}"), "CreatePool")
+}
+
+pub fn start_pool_job(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let id: &str = get_next_arg(&mut iter, 0)?;
+
+    start_job(m, &format!("Start pool {id}"), "StartPool")
+}
+
+/// Expose on the D-Bus the objects created and the properties changed by
+/// applying a configuration. Returns the object paths of the pools which were
+/// created.
+fn expose_changes(
+    m: &MethodInfo<'_, MTSync<TData>, TData>,
+    changes: &[ConfigChange],
+) -> Vec<dbus::Path<'static>> {
+    let base_path = m.path.get_name();
+    let dbus_context = m.tree.get_data();
+    let mut pool_paths = Vec::new();
+
+    // A pool created by the configuration is exposed in its final state, so
+    // later changes to it need not be exposed separately.
+    let created = changes
+        .iter()
+        .filter_map(|change| match change {
+            ConfigChange::PoolCreated(uuid) => Some(*uuid),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    for change in changes.iter() {
+        let pool_uuid = match change {
+            ConfigChange::PoolCreated(uuid)
+            | ConfigChange::BlockdevsAdded(uuid, _, _)
+            | ConfigChange::CacheInitialized(uuid, _)
+            | ConfigChange::KeyringBound(uuid)
+            | ConfigChange::ClevisBound(uuid)
+            | ConfigChange::OverprovModeChanged(uuid, _)
+            | ConfigChange::FsLimitChanged(uuid, _)
+            | ConfigChange::FilesystemsCreated(uuid, _)
+            | ConfigChange::FsSizeLimitChanged(uuid, _, _) => *uuid,
+        };
+        let guard = match block_on(
+            dbus_context
+                .engine
+                .get_pool(PoolIdentifier::Uuid(pool_uuid)),
+        ) {
+            Some(g) => g,
+            None => continue,
+        };
+        let (pool_name, _, pool) = guard.as_tuple();
+
+        if let ConfigChange::PoolCreated(_) = change {
+            let pool_path =
+                create_dbus_pool(dbus_context, base_path.clone(), &pool_name, pool_uuid, pool);
+            pool_paths.push(pool_path.clone());
+            for (bd_uuid, tier, bd) in pool.blockdevs() {
+                create_dbus_blockdev(dbus_context, pool_path.clone(), bd_uuid, tier, bd);
+            }
+            for (fs_name, fs_uuid, fs) in pool.filesystems() {
+                create_dbus_filesystem(
+                    dbus_context,
+                    pool_path.clone(),
+                    &pool_name,
+                    &fs_name,
+                    fs_uuid,
+                    fs,
+                );
+            }
+            continue;
+        }
+        if created.contains(&pool_uuid) {
+            continue;
+        }
+        let pool_path = match uuid_to_path!(m.tree, pool_uuid, Pool) {
+            Some(path) => path,
+            None => continue,
+        };
+
+        match change {
+            ConfigChange::PoolCreated(_) => (),
+            ConfigChange::BlockdevsAdded(_, tier, bd_uuids) => {
+                for bd_uuid in bd_uuids {
+                    if let Some((_, bd)) = pool.get_blockdev(*bd_uuid) {
+                        create_dbus_blockdev(dbus_context, pool_path.clone(), *bd_uuid, *tier, bd);
+                    }
+                }
+            }
+            ConfigChange::CacheInitialized(_, bd_uuids) => {
+                for bd_uuid in bd_uuids {
+                    if let Some((_, bd)) = pool.get_blockdev(*bd_uuid) {
+                        create_dbus_blockdev(
+                            dbus_context,
+                            pool_path.clone(),
+                            *bd_uuid,
+                            BlockDevTier::Cache,
+                            bd,
+                        );
+                    }
+                }
+                dbus_context.push_pool_cache_change(pool_path, true);
+            }
+            ConfigChange::KeyringBound(_) => {
+                dbus_context.push_pool_key_desc_change(
+                    pool_path,
+                    pool.encryption_info().map(|ei| ei.map_left(|e| (true, e))),
+                );
+            }
+            ConfigChange::ClevisBound(_) => {
+                dbus_context.push_pool_clevis_info_change(
+                    pool_path,
+                    pool.encryption_info().map(|ei| ei.map_left(|e| (true, e))),
+                );
+            }
+            ConfigChange::OverprovModeChanged(_, enabled) => {
+                dbus_context.push_pool_overprov_mode_change(pool_path, *enabled);
+            }
+            ConfigChange::FsLimitChanged(_, limit) => {
+                dbus_context.push_pool_fs_limit_change(pool_path, *limit);
+            }
+            ConfigChange::FilesystemsCreated(_, fs_uuids) => {
+                for fs_uuid in fs_uuids {
+                    if let Some((fs_name, fs)) = pool.get_filesystem(*fs_uuid) {
+                        create_dbus_filesystem(
+                            dbus_context,
+                            pool_path.clone(),
+                            &pool_name,
+                            &fs_name,
+                            *fs_uuid,
+                            fs,
+                        );
+                    }
+                }
+            }
+            ConfigChange::FsSizeLimitChanged(_, fs_uuid, limit) => {
+                if let Some(path) = uuid_to_path!(m.tree, *fs_uuid, Fs) {
+                    dbus_context.push_fs_size_limit_change(path, *limit);
+                }
+            }
+        }
+    }
+
+    pool_paths
+}
+
+pub fn apply_configuration(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let config_str: &str = get_next_arg(&mut iter, 0)?;
+    let dry_run: bool = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return: Vec<String> = Vec::new();
+
+    let actions = match PoolsConfig::parse(config_str)
+        .and_then(|config| block_on(plan_configuration(&*dbus_context.engine, &config)))
+    {
+        Ok(actions) => actions,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+    let plan = actions.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+    if dry_run {
+        return Ok(vec![return_message.append3(
+            plan,
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        )]);
+    }
+
+    if actions
+        .iter()
+        .any(|a| matches!(a, ConfigAction::CreatePool { .. }))
+    {
+        check_polkit!(m; ACTION_POOL_CREATE; default_return; return_message);
+    }
+    if actions
+        .iter()
+        .any(|a| matches!(a, ConfigAction::CreateFilesystems { .. }))
+    {
+        check_polkit!(m; ACTION_FILESYSTEM_CREATE; default_return; return_message);
+    }
+    if actions.iter().any(|a| {
+        matches!(
+            a,
+            ConfigAction::BindKeyring { .. } | ConfigAction::BindClevis { .. }
+        )
+    }) {
+        check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+    }
+
+    let (changes, res) = block_on(apply_configuration(&*dbus_context.engine, &actions));
+
+    expose_changes(m, &changes);
+
+    match res {
+        Ok(()) => Ok(vec![return_message.append3(
+            plan,
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        )]),
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            Ok(vec![return_message.append3(default_return, rc, rs)])
+        }
+    }
+}
+
+pub fn export_pool_config(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let pool_path: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return = String::new();
+
+    let pool_uuid = match m
+        .tree
+        .get(&pool_path)
+        .and_then(|op| op.get_data().as_ref())
+        .map(|d| &d.uuid)
+    {
+        Some(uuid) => *typed_uuid!(uuid; Pool; default_return; return_message),
+        None => {
+            let (rc, rs) = (
+                DbusErrorEnum::ERROR as u16,
+                format!("no pool found at object path {pool_path}"),
+            );
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let guard = match block_on(
+        dbus_context
+            .engine
+            .get_pool(PoolIdentifier::Uuid(pool_uuid)),
+    ) {
+        Some(g) => g,
+        None => {
+            let (rc, rs) = (
+                DbusErrorEnum::ERROR as u16,
+                format!("no pool found with UUID {pool_uuid}"),
+            );
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+    let (pool_name, _, pool) = guard.as_tuple();
+
+    match export_pool_config(&pool_name, pool).and_then(|export| {
+        serde_json::to_string(&export)
+            .map_err(|e| StratisError::Msg(format!("Failed to serialize pool export: {e}")))
+    }) {
+        Ok(json) => Ok(vec![return_message.append3(
+            json,
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        )]),
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            Ok(vec![return_message.append3(default_return, rc, rs)])
+        }
+    }
+}
+
+pub fn import_pool_config(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let export_str: &str = get_next_arg(&mut iter, 0)?;
+    let devs: Vec<&str> = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return = (false, dbus::Path::default());
+
+    check_polkit!(m; ACTION_POOL_CREATE; default_return; return_message);
+
+    let actions = match serde_json::from_str::<PoolExport>(export_str)
+        .map_err(|e| StratisError::Msg(format!("Failed to parse pool export: {e}")))
+        .and_then(|export| {
+            block_on(plan_pool_import(
+                &*dbus_context.engine,
+                &export,
+                devs.into_iter().map(PathBuf::from).collect(),
+            ))
+        }) {
+        Ok(actions) => actions,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    if actions
+        .iter()
+        .any(|a| matches!(a, ConfigAction::CreateFilesystems { .. }))
+    {
+        check_polkit!(m; ACTION_FILESYSTEM_CREATE; default_return; return_message);
+    }
+    if actions.iter().any(|a| {
+        matches!(
+            a,
+            ConfigAction::BindKeyring { .. } | ConfigAction::BindClevis { .. }
+        )
+    }) {
+        check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+    }
+
+    let (changes, res) = block_on(apply_configuration(&*dbus_context.engine, &actions));
+    let pool_paths = expose_changes(m, &changes);
+
+    match res {
+        Ok(()) => Ok(vec![return_message.append3(
+            pool_paths
+                .into_iter()
+                .next()
+                .map(|path| (true, path))
+                .unwrap_or(default_return),
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        )]),
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            Ok(vec![return_message.append3(default_return, rc, rs)])
+        }
+    }
+}
+
+pub fn upgrade_pool(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let pool_uuid_str: &str = get_next_arg(&mut iter, 0)?;
+    let fd_opt: (bool, OwnedFd) = get_next_arg(&mut iter, 1)?;
+    let dry_run: bool = get_next_arg(&mut iter, 2)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return = (false, false, false, 0u64, 0u64);
+
+    let pool_uuid = match PoolUuid::parse_str(pool_uuid_str) {
+        Ok(u) => u,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    if !dry_run {
+        check_polkit!(m; ACTION_POOL_CREATE; default_return; return_message);
+    }
+
+    let fd = tuple_to_option(fd_opt);
+    match block_on(dbus_context.engine.upgrade_pool(
+        PoolIdentifier::Uuid(pool_uuid),
+        fd.map(|f| f.into_fd()),
+        dry_run,
+    )) {
+        Ok(report) => {
+            if report.upgraded {
+                dbus_context.push_stopped_pools(block_on(dbus_context.engine.stopped_pools()));
+            }
+            Ok(vec![return_message.append3(
+                (
+                    report.upgraded,
+                    report.encrypted,
+                    report.sufficient_space,
+                    u64::try_from(*report.required.bytes()).unwrap_or(u64::MAX),
+                    u64::try_from(*report.available.bytes()).unwrap_or(u64::MAX),
+                ),
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )])
+        }
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            Ok(vec![return_message.append3(default_return, rc, rs)])
+        }
+    }
+}
+
+pub fn thin_meta_spare(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let pool_uuid_str: &str = get_next_arg(&mut iter, 0)?;
+    let action_str: &str = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return = (0u64, 0u64, (false, String::new()));
+
+    let parsed = PoolUuid::parse_str(pool_uuid_str).and_then(|uuid| {
+        ThinMetaSpareAction::from_str(action_str)
+            .map(|action| (uuid, action))
+            .map_err(|_| {
+                StratisError::Msg(format!(
+                    "Unknown thin metadata spare action \"{action_str}\""
+                ))
+            })
+    });
+    let (pool_uuid, action) = match parsed {
+        Ok(tup) => tup,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    if action != ThinMetaSpareAction::Report {
+        check_polkit!(m; ACTION_POOL_CREATE; default_return; return_message);
+    }
+
+    match block_on(
+        dbus_context
+            .engine
+            .thin_meta_spare(PoolIdentifier::Uuid(pool_uuid), action),
+    ) {
+        Ok(info) => Ok(vec![return_message.append3(
+            (
+                u64::try_from(*info.spare_size.bytes()).unwrap_or(u64::MAX),
+                u64::try_from(*info.meta_size.bytes()).unwrap_or(u64::MAX),
+                match info.last_validated {
+                    Some(time) => (true, time.to_rfc3339_opts(SecondsFormat::Secs, true)),
+                    None => (false, String::new()),
+                },
+            ),
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        )]),
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            Ok(vec![return_message.append3(default_return, rc, rs)])
+        }
+    }
+}
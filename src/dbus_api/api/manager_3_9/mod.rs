@@ -0,0 +1,11 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+mod api;
+mod methods;
+
+pub use api::{
+    apply_configuration_method, create_pool_job_method, export_pool_config_method,
+    import_pool_config_method, start_pool_job_method, thin_meta_spare_method, upgrade_pool_method,
+};
@@ -15,6 +15,7 @@ mod manager_3_4;
 mod manager_3_5;
 mod manager_3_6;
 mod manager_3_8;
+mod manager_3_9;
 pub mod prop_conv;
 mod report_3_0;
 mod shared;
@@ -164,6 +165,27 @@ pub fn get_base_tree<'a>(
                 .add_p(manager_3_0::version_property(&f))
                 .add_p(manager_3_8::stopped_pools_property(&f)),
         )
+        .add(
+            f.interface(consts::MANAGER_INTERFACE_NAME_3_9, ())
+                .add_m(manager_3_8::create_pool_method(&f))
+                .add_m(manager_3_9::create_pool_job_method(&f))
+                .add_m(manager_3_9::apply_configuration_method(&f))
+                .add_m(manager_3_9::export_pool_config_method(&f))
+                .add_m(manager_3_9::import_pool_config_method(&f))
+                .add_m(manager_3_9::upgrade_pool_method(&f))
+                .add_m(manager_3_9::thin_meta_spare_method(&f))
+                .add_m(manager_3_0::set_key_method(&f))
+                .add_m(manager_3_0::unset_key_method(&f))
+                .add_m(manager_3_0::list_keys_method(&f))
+                .add_m(manager_3_0::destroy_pool_method(&f))
+                .add_m(manager_3_0::engine_state_report_method(&f))
+                .add_m(manager_3_8::start_pool_method(&f))
+                .add_m(manager_3_9::start_pool_job_method(&f))
+                .add_m(manager_3_6::stop_pool_method(&f))
+                .add_m(manager_3_2::refresh_state_method(&f))
+                .add_p(manager_3_0::version_property(&f))
+                .add_p(manager_3_8::stopped_pools_property(&f)),
+        )
         .add(
             f.interface(consts::REPORT_INTERFACE_NAME_3_0, ())
                 .add_m(report_3_0::get_report_method(&f)),
@@ -199,6 +221,10 @@ pub fn get_base_tree<'a>(
         .add(
             f.interface(consts::REPORT_INTERFACE_NAME_3_8, ())
                 .add_m(report_3_0::get_report_method(&f)),
+        )
+        .add(
+            f.interface(consts::REPORT_INTERFACE_NAME_3_9, ())
+                .add_m(report_3_0::get_report_method(&f)),
         );
 
     let path = obj_path.get_name().to_owned();
@@ -15,11 +15,14 @@ use crate::{
         api::prop_conv::{self, StoppedOrLockedPools},
         blockdev::get_blockdev_properties,
         filesystem::get_fs_properties,
+        job::{get_job_properties, job_id_from_path},
         pool::get_pool_properties,
         types::{GetManagedObjects, InterfacesAddedThreadSafe, TData},
         util::thread_safe_to_dbus_sendable,
     },
-    engine::{AllLockReadGuard, DevUuid, Engine, FilesystemUuid, Pool, PoolUuid, StratisUuid},
+    engine::{
+        AllLockReadGuard, DevUuid, Engine, FilesystemUuid, JobManager, Pool, PoolUuid, StratisUuid,
+    },
 };
 
 pub type EncryptionParams = (Option<(bool, String)>, Option<(bool, (String, String))>);
@@ -80,6 +83,14 @@ pub fn get_managed_objects_method(
         })
     }
 
+    fn job_properties(path: &dbus::Path<'static>, jobs: &JobManager) -> Option<GetManagedObjects> {
+        job_id_from_path(path)
+            .and_then(|id| jobs.get(id))
+            .map(|job| {
+                properties_to_get_managed_objects(path.clone(), get_job_properties(&job.info()))
+            })
+    }
+
     fn parent_pool_uuid(op: Option<&ObjectPath<MTSync<TData>, TData>>) -> Option<PoolUuid> {
         op.and_then(|o| {
             o.get_data().as_ref().and_then(|data| match data.uuid {
@@ -93,12 +104,17 @@ pub fn get_managed_objects_method(
         let dbus_context = m.tree.get_data();
 
         let table = block_on(dbus_context.engine.pools());
+        let jobs = dbus_context.engine.jobs();
 
         let properties: GetManagedObjects = m
             .tree
             .iter()
             .filter_map(|op| {
-                op.get_data().as_ref().and_then(|data| match data.uuid {
+                let data = match op.get_data().as_ref() {
+                    Some(data) => data,
+                    None => return job_properties(op.get_name(), &jobs),
+                };
+                match data.uuid {
                     StratisUuid::Pool(uuid) => pool_properties(op.get_name(), &table, uuid),
                     StratisUuid::Fs(uuid) => fs_properties(
                         &data.parent,
@@ -116,7 +132,7 @@ pub fn get_managed_objects_method(
                             .expect("Parent must be present and be pool"),
                         uuid,
                     ),
-                })
+                }
             })
             .fold(HashMap::new(), |mut props, prop| {
                 props.extend(prop);
@@ -151,6 +151,19 @@ pub fn create_dbus_blockdev<'a>(
                 .add_p(blockdev_3_0::physical_path_property(&f))
                 .add_p(blockdev_3_0::size_property(&f))
                 .add_p(blockdev_3_3::new_size_property(&f)),
+        )
+        .add(
+            f.interface(consts::BLOCKDEV_INTERFACE_NAME_3_9, ())
+                .add_p(blockdev_3_0::devnode_property(&f))
+                .add_p(blockdev_3_0::hardware_info_property(&f))
+                .add_p(blockdev_3_0::initialization_time_property(&f))
+                .add_p(blockdev_3_0::pool_property(&f))
+                .add_p(blockdev_3_0::tier_property(&f))
+                .add_p(blockdev_3_3::user_info_property(&f))
+                .add_p(blockdev_3_0::uuid_property(&f))
+                .add_p(blockdev_3_0::physical_path_property(&f))
+                .add_p(blockdev_3_0::size_property(&f))
+                .add_p(blockdev_3_3::new_size_property(&f)),
         );
 
     let path = object_path.get_name().to_owned();
@@ -261,6 +274,18 @@ pub fn get_blockdev_properties(
             consts::BLOCKDEV_NEW_SIZE_PROP => shared::blockdev_new_size_prop(dev)
         },
         consts::BLOCKDEV_INTERFACE_NAME_3_8 => {
+            consts::BLOCKDEV_DEVNODE_PROP => shared::blockdev_devnode_prop(dev),
+            consts::BLOCKDEV_HARDWARE_INFO_PROP => shared::blockdev_hardware_info_prop(dev),
+            consts::BLOCKDEV_USER_INFO_PROP => shared::blockdev_user_info_prop(dev),
+            consts::BLOCKDEV_INIT_TIME_PROP => shared::blockdev_init_time_prop(dev),
+            consts::BLOCKDEV_POOL_PROP => parent.clone(),
+            consts::BLOCKDEV_UUID_PROP => uuid_to_string!(dev_uuid),
+            consts::BLOCKDEV_TIER_PROP => shared::blockdev_tier_prop(tier),
+            consts::BLOCKDEV_PHYSICAL_PATH_PROP => shared::blockdev_physical_path_prop(dev),
+            consts::BLOCKDEV_TOTAL_SIZE_PROP => shared::blockdev_size_prop(dev),
+            consts::BLOCKDEV_NEW_SIZE_PROP => shared::blockdev_new_size_prop(dev)
+        },
+        consts::BLOCKDEV_INTERFACE_NAME_3_9 => {
             consts::BLOCKDEV_DEVNODE_PROP => shared::blockdev_devnode_prop(dev),
             consts::BLOCKDEV_HARDWARE_INFO_PROP => shared::blockdev_hardware_info_prop(dev),
             consts::BLOCKDEV_USER_INFO_PROP => shared::blockdev_user_info_prop(dev),
@@ -15,7 +15,7 @@ use tokio::{
 };
 
 use crate::{
-    dbus_api::types::LockableTree,
+    dbus_api::{polkit, types::LockableTree},
     stratis::{StratisError, StratisResult},
 };
 
@@ -55,7 +55,11 @@ impl DbusConnectionHandler {
                 spawn_blocking(move || {
                     trace!("Starting D-Bus request handling");
                     let lock = cloned_tree.blocking_read();
-                    if let Some(msgs) = lock.handle(&msg) {
+                    let replies = match polkit::caller(&cloned_connection, &msg) {
+                        Some(caller) => caller.scope_sync(|| lock.handle(&msg)),
+                        None => lock.handle(&msg),
+                    };
+                    if let Some(msgs) = replies {
                         for msg in msgs {
                             if cloned_connection.send(msg).is_err() {
                                 warn!("Failed to send reply to D-Bus client");
@@ -16,6 +16,7 @@ pub const MANAGER_INTERFACE_NAME_3_5: &str = "org.storage.stratis3.Manager.r5";
 pub const MANAGER_INTERFACE_NAME_3_6: &str = "org.storage.stratis3.Manager.r6";
 pub const MANAGER_INTERFACE_NAME_3_7: &str = "org.storage.stratis3.Manager.r7";
 pub const MANAGER_INTERFACE_NAME_3_8: &str = "org.storage.stratis3.Manager.r8";
+pub const MANAGER_INTERFACE_NAME_3_9: &str = "org.storage.stratis3.Manager.r9";
 pub const REPORT_INTERFACE_NAME_3_0: &str = "org.storage.stratis3.Report.r0";
 pub const REPORT_INTERFACE_NAME_3_1: &str = "org.storage.stratis3.Report.r1";
 pub const REPORT_INTERFACE_NAME_3_2: &str = "org.storage.stratis3.Report.r2";
@@ -25,6 +26,7 @@ pub const REPORT_INTERFACE_NAME_3_5: &str = "org.storage.stratis3.Report.r5";
 pub const REPORT_INTERFACE_NAME_3_6: &str = "org.storage.stratis3.Report.r6";
 pub const REPORT_INTERFACE_NAME_3_7: &str = "org.storage.stratis3.Report.r7";
 pub const REPORT_INTERFACE_NAME_3_8: &str = "org.storage.stratis3.Report.r8";
+pub const REPORT_INTERFACE_NAME_3_9: &str = "org.storage.stratis3.Report.r9";
 
 pub const LOCKED_POOLS_PROP: &str = "LockedPools";
 pub const STOPPED_POOLS_PROP: &str = "StoppedPools";
@@ -38,6 +40,7 @@ pub const POOL_INTERFACE_NAME_3_5: &str = "org.storage.stratis3.pool.r5";
 pub const POOL_INTERFACE_NAME_3_6: &str = "org.storage.stratis3.pool.r6";
 pub const POOL_INTERFACE_NAME_3_7: &str = "org.storage.stratis3.pool.r7";
 pub const POOL_INTERFACE_NAME_3_8: &str = "org.storage.stratis3.pool.r8";
+pub const POOL_INTERFACE_NAME_3_9: &str = "org.storage.stratis3.pool.r9";
 pub const POOL_NAME_PROP: &str = "Name";
 pub const POOL_UUID_PROP: &str = "Uuid";
 pub const POOL_HAS_CACHE_PROP: &str = "HasCache";
@@ -64,6 +67,7 @@ pub const FILESYSTEM_INTERFACE_NAME_3_5: &str = "org.storage.stratis3.filesystem
 pub const FILESYSTEM_INTERFACE_NAME_3_6: &str = "org.storage.stratis3.filesystem.r6";
 pub const FILESYSTEM_INTERFACE_NAME_3_7: &str = "org.storage.stratis3.filesystem.r7";
 pub const FILESYSTEM_INTERFACE_NAME_3_8: &str = "org.storage.stratis3.filesystem.r8";
+pub const FILESYSTEM_INTERFACE_NAME_3_9: &str = "org.storage.stratis3.filesystem.r9";
 pub const FILESYSTEM_NAME_PROP: &str = "Name";
 pub const FILESYSTEM_UUID_PROP: &str = "Uuid";
 pub const FILESYSTEM_USED_PROP: &str = "Used";
@@ -72,6 +76,7 @@ pub const FILESYSTEM_POOL_PROP: &str = "Pool";
 pub const FILESYSTEM_CREATED_PROP: &str = "Created";
 pub const FILESYSTEM_SIZE_PROP: &str = "Size";
 pub const FILESYSTEM_SIZE_LIMIT_PROP: &str = "SizeLimit";
+pub const FILESYSTEM_RESERVATION_PROP: &str = "Reservation";
 pub const FILESYSTEM_ORIGIN_PROP: &str = "Origin";
 pub const FILESYSTEM_MERGE_SCHEDULED_PROP: &str = "MergeScheduled";
 
@@ -84,6 +89,7 @@ pub const BLOCKDEV_INTERFACE_NAME_3_5: &str = "org.storage.stratis3.blockdev.r5"
 pub const BLOCKDEV_INTERFACE_NAME_3_6: &str = "org.storage.stratis3.blockdev.r6";
 pub const BLOCKDEV_INTERFACE_NAME_3_7: &str = "org.storage.stratis3.blockdev.r7";
 pub const BLOCKDEV_INTERFACE_NAME_3_8: &str = "org.storage.stratis3.blockdev.r8";
+pub const BLOCKDEV_INTERFACE_NAME_3_9: &str = "org.storage.stratis3.blockdev.r9";
 pub const BLOCKDEV_DEVNODE_PROP: &str = "Devnode";
 pub const BLOCKDEV_HARDWARE_INFO_PROP: &str = "HardwareInfo";
 pub const BLOCKDEV_USER_INFO_PROP: &str = "UserInfo";
@@ -95,6 +101,14 @@ pub const BLOCKDEV_PHYSICAL_PATH_PROP: &str = "PhysicalPath";
 pub const BLOCKDEV_NEW_SIZE_PROP: &str = "NewPhysicalSize";
 pub const BLOCKDEV_TOTAL_SIZE_PROP: &str = "TotalPhysicalSize";
 
+pub const JOB_BASE_PATH: &str = "/org/storage/stratis3/job";
+pub const JOB_INTERFACE_NAME_3_9: &str = "org.storage.stratis3.job.r9";
+pub const JOB_DESCRIPTION_PROP: &str = "Description";
+pub const JOB_STATE_PROP: &str = "State";
+pub const JOB_PROGRESS_PROP: &str = "Progress";
+pub const JOB_ERROR_PROP: &str = "Error";
+pub const JOB_FINISHED_SIGNAL: &str = "Finished";
+
 /// Get a list of all the standard pool interfaces
 pub fn standard_pool_interfaces() -> Vec<String> {
     [
@@ -107,6 +121,7 @@ pub fn standard_pool_interfaces() -> Vec<String> {
         POOL_INTERFACE_NAME_3_6,
         POOL_INTERFACE_NAME_3_7,
         POOL_INTERFACE_NAME_3_8,
+        POOL_INTERFACE_NAME_3_9,
     ]
     .iter()
     .map(|s| (*s).to_string())
@@ -125,6 +140,7 @@ pub fn standard_filesystem_interfaces() -> Vec<String> {
         FILESYSTEM_INTERFACE_NAME_3_6,
         FILESYSTEM_INTERFACE_NAME_3_7,
         FILESYSTEM_INTERFACE_NAME_3_8,
+        FILESYSTEM_INTERFACE_NAME_3_9,
     ]
     .iter()
     .map(|s| (*s).to_string())
@@ -143,6 +159,7 @@ pub fn standard_blockdev_interfaces() -> Vec<String> {
         BLOCKDEV_INTERFACE_NAME_3_6,
         BLOCKDEV_INTERFACE_NAME_3_7,
         BLOCKDEV_INTERFACE_NAME_3_8,
+        BLOCKDEV_INTERFACE_NAME_3_9,
     ]
     .iter()
     .map(|s| (*s).to_string())
@@ -163,3 +180,8 @@ pub fn filesystem_interface_list() -> InterfacesRemoved {
 pub fn blockdev_interface_list() -> InterfacesRemoved {
     standard_blockdev_interfaces()
 }
+
+/// Get a list of all interfaces supported by a job object.
+pub fn job_interface_list() -> InterfacesRemoved {
+    vec![JOB_INTERFACE_NAME_3_9.to_string()]
+}
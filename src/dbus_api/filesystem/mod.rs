@@ -16,6 +16,7 @@ use crate::{
 mod filesystem_3_0;
 mod filesystem_3_6;
 mod filesystem_3_7;
+mod filesystem_3_9;
 pub mod prop_conv;
 mod shared;
 
@@ -142,6 +143,21 @@ pub fn create_dbus_filesystem<'a>(
                 .add_p(filesystem_3_6::size_limit_property(&f))
                 .add_p(filesystem_3_7::origin_property(&f))
                 .add_p(filesystem_3_7::merge_scheduled_property(&f)),
+        )
+        .add(
+            f.interface(consts::FILESYSTEM_INTERFACE_NAME_3_9, ())
+                .add_m(filesystem_3_0::rename_method(&f))
+                .add_p(filesystem_3_0::devnode_property(&f))
+                .add_p(filesystem_3_0::name_property(&f))
+                .add_p(filesystem_3_0::pool_property(&f))
+                .add_p(filesystem_3_0::uuid_property(&f))
+                .add_p(filesystem_3_0::created_property(&f))
+                .add_p(filesystem_3_0::size_property(&f))
+                .add_p(filesystem_3_0::used_property(&f))
+                .add_p(filesystem_3_6::size_limit_property(&f))
+                .add_p(filesystem_3_7::origin_property(&f))
+                .add_p(filesystem_3_7::merge_scheduled_property(&f))
+                .add_p(filesystem_3_9::reservation_property(&f)),
         );
 
     let path = object_path.get_name().to_owned();
@@ -239,13 +255,26 @@ pub fn get_fs_properties(
             consts::FILESYSTEM_NAME_PROP => shared::fs_name_prop(fs_name),
             consts::FILESYSTEM_UUID_PROP => uuid_to_string!(fs_uuid),
             consts::FILESYSTEM_DEVNODE_PROP => shared::fs_devnode_prop(fs, pool_name, fs_name),
-            consts::FILESYSTEM_POOL_PROP => parent,
+            consts::FILESYSTEM_POOL_PROP => parent.clone(),
             consts::FILESYSTEM_CREATED_PROP => shared::fs_created_prop(fs),
             consts::FILESYSTEM_SIZE_PROP => shared::fs_size_prop(fs),
             consts::FILESYSTEM_USED_PROP => shared::fs_used_prop(fs),
             consts::FILESYSTEM_SIZE_LIMIT_PROP => shared::fs_size_limit_prop(fs),
             consts::FILESYSTEM_ORIGIN_PROP => shared::fs_origin_prop(fs),
             consts::FILESYSTEM_MERGE_SCHEDULED_PROP => shared::fs_merge_scheduled_prop(fs)
+        },
+        consts::FILESYSTEM_INTERFACE_NAME_3_9 => {
+            consts::FILESYSTEM_NAME_PROP => shared::fs_name_prop(fs_name),
+            consts::FILESYSTEM_UUID_PROP => uuid_to_string!(fs_uuid),
+            consts::FILESYSTEM_DEVNODE_PROP => shared::fs_devnode_prop(fs, pool_name, fs_name),
+            consts::FILESYSTEM_POOL_PROP => parent,
+            consts::FILESYSTEM_CREATED_PROP => shared::fs_created_prop(fs),
+            consts::FILESYSTEM_SIZE_PROP => shared::fs_size_prop(fs),
+            consts::FILESYSTEM_USED_PROP => shared::fs_used_prop(fs),
+            consts::FILESYSTEM_SIZE_LIMIT_PROP => shared::fs_size_limit_prop(fs),
+            consts::FILESYSTEM_ORIGIN_PROP => shared::fs_origin_prop(fs),
+            consts::FILESYSTEM_MERGE_SCHEDULED_PROP => shared::fs_merge_scheduled_prop(fs),
+            consts::FILESYSTEM_RESERVATION_PROP => shared::fs_reservation_prop(fs)
         }
     }
 }
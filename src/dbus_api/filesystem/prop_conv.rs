@@ -24,6 +24,12 @@ pub fn fs_size_limit_to_prop(limit: Option<Sectors>) -> (bool, String) {
     option_to_tuple(limit.map(|u| (*u.bytes()).to_string()), String::new())
 }
 
+/// Generate D-Bus representation of filesystem reservation property.
+#[inline]
+pub fn fs_reservation_to_prop(reservation: Option<Sectors>) -> (bool, String) {
+    option_to_tuple(reservation.map(|u| (*u.bytes()).to_string()), String::new())
+}
+
 /// Generate D-Bus representation of filesystem origin property.
 #[inline]
 pub fn fs_origin_to_prop(origin: Option<FilesystemUuid>) -> (bool, String) {
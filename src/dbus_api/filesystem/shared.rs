@@ -173,6 +173,23 @@ pub fn set_fs_size_limit_prop(
         .map_err(|e| e.to_string())
 }
 
+/// Get the filesystem reservation for a given filesystem.
+#[inline]
+pub fn fs_reservation_prop(fs: &dyn Filesystem) -> (bool, String) {
+    prop_conv::fs_reservation_to_prop(fs.reservation())
+}
+
+/// Set the filesystem reservation for a given filesystem.
+#[inline]
+pub fn set_fs_reservation_prop(
+    uuid: FilesystemUuid,
+    pool: &mut dyn Pool,
+    reservation: Option<Bytes>,
+) -> Result<PropChangeAction<Option<Sectors>>, String> {
+    pool.set_fs_reservation(uuid, reservation)
+        .map_err(|e| e.to_string())
+}
+
 /// Generate D-Bus representation of name property.
 #[inline]
 pub fn fs_name_prop(name: &Name) -> String {
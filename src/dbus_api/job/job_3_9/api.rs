@@ -0,0 +1,66 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::{Access, EmitsChangedSignal, Factory, MTSync, Method, Property, Signal};
+
+use crate::dbus_api::{
+    consts,
+    job::job_3_9::{
+        methods::cancel,
+        props::{get_job_description, get_job_error, get_job_progress, get_job_state},
+    },
+    types::TData,
+};
+
+pub fn cancel_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    // A queued job is cancelled at once. A running job stops at the next
+    // point at which its operation can stop safely, and completes if there is
+    // none left.
+    f.method("Cancel", (), cancel)
+        // b: true if the job was or will be cancelled, false if it had
+        //    already finished
+        //
+        // Rust representation: bool
+        .out_arg(("result", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn finished_signal(f: &Factory<MTSync<TData>, TData>) -> Signal<TData> {
+    f.signal(consts::JOB_FINISHED_SIGNAL, ())
+        // s: the final state of the job
+        .sarg::<&str, _>("state")
+        // s: the reason the job failed, empty if it did not fail
+        .sarg::<&str, _>("error")
+}
+
+pub fn description_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<&str, _>(consts::JOB_DESCRIPTION_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_job_description)
+}
+
+pub fn state_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<&str, _>(consts::JOB_STATE_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_job_state)
+}
+
+/// The fraction of the job which is done, between 0 and 1, as reported by
+/// the operation while the job is running.
+pub fn progress_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<f64, _>(consts::JOB_PROGRESS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_job_progress)
+}
+
+pub fn error_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<&str, _>(consts::JOB_ERROR_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_job_error)
+}
@@ -0,0 +1,51 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus::Message;
+use dbus_tree::{MTSync, MethodInfo, MethodResult};
+
+use crate::dbus_api::{
+    job::shared::job_id_from_path,
+    types::{DbusErrorEnum, TData, OK_STRING},
+    util::engine_to_dbus_err_tuple,
+};
+
+pub fn cancel(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let id = match job_id_from_path(object_path) {
+        Some(id) => id,
+        None => {
+            return Ok(vec![return_message.append3(
+                default_return,
+                DbusErrorEnum::ERROR as u16,
+                format!("no job corresponds to object path {object_path}"),
+            )]);
+        }
+    };
+
+    let jobs = dbus_context.engine.jobs();
+    match jobs.cancel(id) {
+        Ok(cancelled) => {
+            if cancelled {
+                if let Some(job) = jobs.get(id) {
+                    dbus_context.push_job_change(object_path, job.info());
+                }
+            }
+            Ok(vec![return_message.append3(
+                cancelled,
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )])
+        }
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            Ok(vec![return_message.append3(default_return, rc, rs)])
+        }
+    }
+}
@@ -0,0 +1,12 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+mod api;
+mod methods;
+mod props;
+
+pub use api::{
+    cancel_method, description_property, error_property, finished_signal, progress_property,
+    state_property,
+};
@@ -0,0 +1,51 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::Factory;
+
+use crate::{
+    dbus_api::{
+        consts,
+        types::{DbusContext, InterfacesAddedThreadSafe},
+    },
+    engine::JobInfo,
+};
+
+mod job_3_9;
+mod shared;
+
+pub use self::shared::{job_error_prop, job_id_from_path, job_path, run_job, start_job};
+
+/// Create a D-Bus object for a job. Unlike pools, filesystems and blockdevs,
+/// a job object has no associated OPContext; the job is identified by the
+/// last component of its object path.
+pub fn create_dbus_job(dbus_context: &DbusContext, info: &JobInfo) -> dbus::Path<'static> {
+    let f = Factory::new_sync();
+
+    let object_path = f.object_path(job_path(info.id), None).introspectable().add(
+        f.interface(consts::JOB_INTERFACE_NAME_3_9, ())
+            .add_m(job_3_9::cancel_method(&f))
+            .add_s(job_3_9::finished_signal(&f))
+            .add_p(job_3_9::description_property(&f))
+            .add_p(job_3_9::state_property(&f))
+            .add_p(job_3_9::progress_property(&f))
+            .add_p(job_3_9::error_property(&f)),
+    );
+
+    let path = object_path.get_name().to_owned();
+    dbus_context.push_add(object_path, get_job_properties(info));
+    path
+}
+
+/// Get the initial state of all properties associated with a job object.
+pub fn get_job_properties(info: &JobInfo) -> InterfacesAddedThreadSafe {
+    initial_properties! {
+        consts::JOB_INTERFACE_NAME_3_9 => {
+            consts::JOB_DESCRIPTION_PROP => info.description.clone(),
+            consts::JOB_STATE_PROP => info.state.to_string(),
+            consts::JOB_PROGRESS_PROP => info.progress,
+            consts::JOB_ERROR_PROP => shared::job_error_prop(info)
+        }
+    }
+}
@@ -0,0 +1,175 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus::{arg::IterAppend, Message, Path};
+use dbus_tree::{MTSync, MethodErr, MethodInfo, MethodResult, PropInfo};
+
+use crate::{
+    dbus_api::{
+        consts,
+        job::create_dbus_job,
+        types::{DbusErrorEnum, JobCall, JobRun, LockableTree, TData, OK_STRING},
+    },
+    engine::{JobId, JobInfo},
+    stratis::Caller,
+};
+
+/// The object path of the job with the given ID.
+pub fn job_path(id: JobId) -> Path<'static> {
+    Path::from(format!("{}/{}", consts::JOB_BASE_PATH, id))
+}
+
+/// The ID of the job with the given object path, if it is the path of a job.
+pub fn job_id_from_path(path: &Path<'_>) -> Option<JobId> {
+    path.strip_prefix(consts::JOB_BASE_PATH)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .and_then(|id| id.parse::<JobId>().ok())
+}
+
+pub fn job_error_prop(info: &JobInfo) -> String {
+    info.error.clone().unwrap_or_default()
+}
+
+/// Get a job property and place it on the D-Bus.
+pub fn get_job_property<F, R>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+    getter: F,
+) -> Result<(), MethodErr>
+where
+    F: Fn(JobInfo) -> Result<R, String>,
+    R: dbus::arg::Append,
+{
+    let object_path = p.path.get_name();
+    let info = job_id_from_path(object_path)
+        .and_then(|id| p.tree.get_data().engine.jobs().get(id))
+        .map(|job| job.info())
+        .ok_or_else(|| {
+            MethodErr::failed(&format!("no job corresponds to object path {object_path}"))
+        })?;
+    i.append(getter(info).map_err(|ref e| MethodErr::failed(e))?);
+    Ok(())
+}
+
+/// Start a job which runs the method named method of the same interface
+/// and object as the method call m, with the same arguments. The job method
+/// must therefore take the same arguments as the method it runs.
+///
+/// Returns the object path of the job without waiting for it to start.
+pub fn start_job(
+    m: &MethodInfo<'_, MTSync<TData>, TData>,
+    description: &str,
+    method: &'static str,
+) -> MethodResult {
+    let message: &Message = m.msg;
+    let dbus_context = m.tree.get_data();
+    let return_message = message.method_return();
+    let default_return = Path::default();
+
+    let job_message = match message.duplicate() {
+        Ok(msg) => msg,
+        Err(e) => {
+            return Ok(vec![return_message.append3(
+                default_return,
+                DbusErrorEnum::ERROR as u16,
+                format!("Failed to copy method call for job: {e}"),
+            )]);
+        }
+    };
+
+    let job = dbus_context.engine.jobs().create(description);
+    let path = create_dbus_job(dbus_context, &job.info());
+    dbus_context.push_job_run(JobRun {
+        context: dbus_context.clone(),
+        job,
+        path: path.clone(),
+        call: JobCall {
+            object: m.path.get_name().clone(),
+            interface: m.iface.get_name().to_string(),
+            method,
+            message: job_message,
+            // The job may finish after the client has disconnected.
+            caller: Caller::current().map(Caller::resolve),
+        },
+    });
+
+    Ok(vec![return_message.append3(
+        path,
+        DbusErrorEnum::OK as u16,
+        OK_STRING.to_string(),
+    )])
+}
+
+/// Run the method call of a job and get its outcome from the return code in
+/// the reply. This must be called from a thread in which blocking is
+/// permitted, as the method holds a read lock on the tree while it runs.
+pub fn run_job(tree: &LockableTree, call: &JobCall) -> Result<(), String> {
+    let lock = tree.blocking_read();
+    let object = lock
+        .get(&call.object)
+        .ok_or_else(|| format!("object path {} no longer exists", call.object))?;
+    let iface = object
+        .iter()
+        .find(|i| &**i.get_name() == call.interface.as_str())
+        .ok_or_else(|| {
+            format!(
+                "object path {} has no interface {}",
+                call.object, call.interface
+            )
+        })?;
+    let method = iface
+        .iter_m()
+        .find(|m| &**m.get_name() == call.method)
+        .ok_or_else(|| format!("interface {} has no method {}", call.interface, call.method))?;
+
+    let info = MethodInfo {
+        msg: &call.message,
+        method,
+        iface,
+        path: object,
+        tree: &lock,
+    };
+    let replies = match call.caller.clone() {
+        Some(caller) => caller.scope_sync(|| method.call(&info)),
+        None => method.call(&info),
+    }
+    .map_err(|e| format!("{}: {}", e.errorname(), e.description()))?;
+
+    // Every method which may be run as a job returns a value followed by a
+    // return code and a return string.
+    let reply = replies
+        .first()
+        .ok_or_else(|| format!("method {} returned no reply", call.method))?;
+    let mut iter = reply.iter_init();
+    iter.next();
+    let rc: u16 = iter
+        .read()
+        .map_err(|e| format!("malformed reply from method {}: {e}", call.method))?;
+    let rs: String = iter
+        .read()
+        .map_err(|e| format!("malformed reply from method {}: {e}", call.method))?;
+    if rc == DbusErrorEnum::OK as u16 {
+        Ok(())
+    } else {
+        Err(rs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_path() {
+        assert_eq!(job_id_from_path(&job_path(12)), Some(12));
+        assert_eq!(
+            job_id_from_path(&Path::from(consts::STRATIS_BASE_PATH)),
+            None
+        );
+        assert_eq!(
+            job_id_from_path(&Path::from(format!("{}/12", consts::STRATIS_BASE_PATH))),
+            None
+        );
+    }
+}
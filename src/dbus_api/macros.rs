@@ -70,6 +70,24 @@ macro_rules! typed_uuid {
     };
 }
 
+/// Macro for early return with Ok dbus message if the sender of the method
+/// call is not authorized to perform the polkit action.
+macro_rules! check_polkit {
+    ($m:ident; $action:expr; $default:expr; $message:expr) => {
+        if let Err(message) = $crate::dbus_api::polkit::check_authorization(
+            $m.tree.get_data().connection(),
+            $m.msg,
+            $action,
+        ) {
+            let (rc, rs) = (
+                $crate::dbus_api::types::DbusErrorEnum::ERROR as u16,
+                message,
+            );
+            return Ok(vec![$message.append3($default, rc, rs)]);
+        }
+    };
+}
+
 /// Macro for early return with Ok dbus message on failure to get mutable pool.
 macro_rules! get_mut_pool {
     ($engine:expr; $uuid:ident; $default:expr; $message:expr) => {
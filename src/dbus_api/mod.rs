@@ -10,6 +10,8 @@ mod blockdev;
 mod connection;
 mod consts;
 mod filesystem;
+mod job;
+mod polkit;
 mod pool;
 mod tree;
 mod types;
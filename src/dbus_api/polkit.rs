@@ -0,0 +1,115 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use dbus::{
+    arg::{RefArg, Variant},
+    blocking::SyncConnection,
+    Message,
+};
+
+use crate::stratis::{Caller, LazyUid};
+
+const DBUS_BUS_NAME: &str = "org.freedesktop.DBus";
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+
+const POLKIT_BUS_NAME: &str = "org.freedesktop.PolicyKit1";
+const POLKIT_AUTHORITY_PATH: &str = "/org/freedesktop/PolicyKit1/Authority";
+const POLKIT_AUTHORITY_INTERFACE: &str = "org.freedesktop.PolicyKit1.Authority";
+
+const TIMEOUT: Duration = Duration::from_secs(25);
+
+/// Asks polkit to let the user authenticate, through their authentication
+/// agent, if the policy of the action requires it.
+const CHECK_AUTHORIZATION_ALLOW_USER_INTERACTION: u32 = 1;
+
+/// The time allowed for polkit to check authorization, which includes the
+/// time the user takes to authenticate.
+const AUTHORIZATION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Polkit action IDs, one for each family of D-Bus methods that may be
+/// delegated to unprivileged users. They are defined in
+/// org.storage.stratis3.policy.
+pub const ACTION_POOL_CREATE: &str = "org.storage.stratis3.pool.create";
+pub const ACTION_POOL_DESTROY: &str = "org.storage.stratis3.pool.destroy";
+pub const ACTION_FILESYSTEM_CREATE: &str = "org.storage.stratis3.filesystem.create";
+pub const ACTION_FILESYSTEM_SNAPSHOT: &str = "org.storage.stratis3.filesystem.snapshot";
+pub const ACTION_KEY_MANAGE: &str = "org.storage.stratis3.key.manage";
+pub const ACTION_ENCRYPTION_BIND: &str = "org.storage.stratis3.encryption.bind";
+
+/// Ask the bus for the user ID of the client with the given unique name.
+fn connection_unix_user(connection: &SyncConnection, sender: &str) -> Result<u32, String> {
+    let (uid,): (u32,) = connection
+        .with_proxy(DBUS_BUS_NAME, DBUS_PATH, TIMEOUT)
+        .method_call(DBUS_BUS_NAME, "GetConnectionUnixUser", (sender,))
+        .map_err(|e| format!("failed to get the user ID of {sender}: {e}"))?;
+    Ok(uid)
+}
+
+/// Identify the sender of a method call for the audit log. The bus is asked
+/// for the user ID of the sender only if an audit record names it.
+pub fn caller(connection: &Arc<SyncConnection>, message: &Message) -> Option<Caller> {
+    let sender = message.sender()?.to_string();
+    let connection = Arc::clone(connection);
+    let lookup_sender = sender.clone();
+    let uid = LazyUid::new(move || {
+        connection_unix_user(&connection, &lookup_sender)
+            .map_err(|e| warn!("Failed to identify caller for the audit log: {e}"))
+            .ok()
+    });
+    Some(Caller::DBus { sender, uid })
+}
+
+/// Check that the sender of a method call is authorized to perform action.
+///
+/// Root is always authorized, without consulting polkit, so that stratisd
+/// remains usable where polkit is not running, e.g., in the initrd. Any other
+/// sender must be authorized by polkit, which may ask the sender's user to
+/// authenticate. If polkit can not be reached, the sender is not authorized.
+pub fn check_authorization(
+    connection: &SyncConnection,
+    message: &Message,
+    action: &str,
+) -> Result<(), String> {
+    let sender = message
+        .sender()
+        .ok_or_else(|| "method call has no sender".to_string())?
+        .to_string();
+
+    let uid = connection_unix_user(connection, &sender)?;
+    if uid == 0 {
+        return Ok(());
+    }
+
+    let mut subject_details: HashMap<&str, Variant<Box<dyn RefArg>>> = HashMap::new();
+    subject_details.insert("name", Variant(Box::new(sender.clone())));
+    let details: HashMap<&str, &str> = HashMap::new();
+    let ((is_authorized, _, _),): ((bool, bool, HashMap<String, String>),) = connection
+        .with_proxy(
+            POLKIT_BUS_NAME,
+            POLKIT_AUTHORITY_PATH,
+            AUTHORIZATION_TIMEOUT,
+        )
+        .method_call(
+            POLKIT_AUTHORITY_INTERFACE,
+            "CheckAuthorization",
+            (
+                ("system-bus-name", subject_details),
+                action,
+                details,
+                CHECK_AUTHORIZATION_ALLOW_USER_INTERACTION,
+                "",
+            ),
+        )
+        .map_err(|e| format!("failed to check authorization for {action} with polkit: {e}"))?;
+
+    if is_authorized {
+        Ok(())
+    } else {
+        Err(format!(
+            "user with ID {uid} is not authorized to perform action {action}"
+        ))
+    }
+}
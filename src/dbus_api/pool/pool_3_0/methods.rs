@@ -15,6 +15,7 @@ use crate::{
     dbus_api::{
         consts::filesystem_interface_list,
         filesystem::create_dbus_filesystem,
+        polkit::{ACTION_ENCRYPTION_BIND, ACTION_FILESYSTEM_CREATE, ACTION_FILESYSTEM_SNAPSHOT},
         pool::shared::{add_blockdevs, BlockDevOp},
         types::{DbusErrorEnum, TData, OK_STRING},
         util::{engine_to_dbus_err_tuple, get_next_arg, tuple_to_option},
@@ -37,6 +38,8 @@ pub fn create_filesystems(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodRes
     let return_message = message.method_return();
     let default_return: (bool, Vec<(dbus::Path<'_>, &str)>) = (false, Vec::new());
 
+    check_polkit!(m; ACTION_FILESYSTEM_CREATE; default_return; return_message);
+
     if filesystems.count() > 1 {
         let error_message = "only 1 filesystem per request allowed";
         let (rc, rs) = (DbusErrorEnum::ERROR as u16, error_message);
@@ -217,6 +220,8 @@ pub fn snapshot_filesystem(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodRe
     let return_message = message.method_return();
     let default_return = (false, dbus::Path::default());
 
+    check_polkit!(m; ACTION_FILESYSTEM_SNAPSHOT; default_return; return_message);
+
     let pool_path = m
         .tree
         .get(object_path)
@@ -355,6 +360,8 @@ pub fn bind_clevis(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let return_message = message.method_return();
     let default_return = false;
 
+    check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+
     let pool_path = m
         .tree
         .get(object_path)
@@ -407,6 +414,8 @@ pub fn unbind_clevis(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let return_message = message.method_return();
     let default_return = false;
 
+    check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+
     let pool_path = m
         .tree
         .get(object_path)
@@ -450,6 +459,8 @@ pub fn bind_keyring(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let return_message = message.method_return();
     let default_return = false;
 
+    check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+
     let key_desc = match KeyDescription::try_from(key_desc_str) {
         Ok(kd) => kd,
         Err(e) => {
@@ -503,6 +514,8 @@ pub fn unbind_keyring(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult
     let return_message = message.method_return();
     let default_return = false;
 
+    check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+
     let pool_path = m
         .tree
         .get(object_path)
@@ -550,6 +563,8 @@ pub fn rebind_keyring(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult
     let return_message = message.method_return();
     let default_return = false;
 
+    check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+
     let key_desc = match KeyDescription::try_from(key_desc_str) {
         Ok(kd) => kd,
         Err(e) => {
@@ -610,6 +625,8 @@ pub fn rebind_clevis(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let return_message = message.method_return();
     let default_return = false;
 
+    check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+
     let pool_path = m
         .tree
         .get(object_path)
@@ -10,6 +10,7 @@ use devicemapper::Bytes;
 use crate::{
     dbus_api::{
         filesystem::create_dbus_filesystem,
+        polkit::ACTION_FILESYSTEM_CREATE,
         types::{DbusErrorEnum, TData, OK_STRING},
         util::{engine_to_dbus_err_tuple, get_next_arg, tuple_to_option},
     },
@@ -29,6 +30,8 @@ pub fn create_filesystems(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodRes
     let return_message = message.method_return();
     let default_return: (bool, Vec<(dbus::Path<'_>, &str)>) = (false, Vec::new());
 
+    check_polkit!(m; ACTION_FILESYSTEM_CREATE; default_return; return_message);
+
     if filesystems.count() > 1 {
         let error_message = "only 1 filesystem per request allowed";
         let (rc, rs) = (DbusErrorEnum::ERROR as u16, error_message);
@@ -8,6 +8,7 @@ use serde_json::Value;
 
 use crate::{
     dbus_api::{
+        polkit::ACTION_ENCRYPTION_BIND,
         types::{DbusErrorEnum, TData, OK_STRING},
         util::{engine_to_dbus_err_tuple, get_next_arg, tuple_to_option},
     },
@@ -30,6 +31,8 @@ pub fn bind_clevis(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let return_message = message.method_return();
     let default_return = false;
 
+    check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+
     let pool_path = m
         .tree
         .get(object_path)
@@ -100,6 +103,8 @@ pub fn unbind_clevis(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let return_message = message.method_return();
     let default_return = false;
 
+    check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+
     let token_slot = tuple_to_option(token_slot_tuple);
 
     let pool_path = m
@@ -159,6 +164,8 @@ pub fn bind_keyring(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let return_message = message.method_return();
     let default_return = false;
 
+    check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+
     let key_desc = match KeyDescription::try_from(key_desc_str) {
         Ok(kd) => kd,
         Err(e) => {
@@ -228,6 +235,8 @@ pub fn unbind_keyring(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult
     let return_message = message.method_return();
     let default_return = false;
 
+    check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+
     let token_slot = tuple_to_option(token_slot_tuple);
 
     let pool_path = m
@@ -287,6 +296,8 @@ pub fn rebind_keyring(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult
     let return_message = message.method_return();
     let default_return = false;
 
+    check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+
     let key_desc = match KeyDescription::try_from(key_desc_str) {
         Ok(kd) => kd,
         Err(e) => {
@@ -361,6 +372,8 @@ pub fn rebind_clevis(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let return_message = message.method_return();
     let default_return = false;
 
+    check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+
     let token_slot = tuple_to_option(token_slot_tuple);
 
     let pool_path = m
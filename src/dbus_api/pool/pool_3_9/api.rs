@@ -0,0 +1,98 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::{Factory, MTSync, Method};
+
+use crate::dbus_api::{
+    pool::pool_3_9::methods::{
+        apply_batch, clone_filesystem, compact, compact_job, get_layout, grow_physical_job,
+        init_cache_job,
+    },
+    types::TData,
+};
+
+pub fn clone_filesystem_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("CloneFilesystem", (), clone_filesystem)
+        .in_arg(("origin", "o"))
+        .in_arg(("name", "s"))
+        .in_arg(("preserve_uuid", "b"))
+        // b: false if no new filesystem was created
+        // o: Object path of new filesystem
+        //
+        // Rust representation: (bool, String)
+        .out_arg(("result", "(bo)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+/// Takes the same arguments as InitCache, which is run by the job.
+pub fn init_cache_job_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("InitCacheJob", (), init_cache_job)
+        .in_arg(("devices", "as"))
+        // o: Object path of the job which initializes the cache
+        .out_arg(("job", "o"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+/// Takes the same arguments as GrowPhysicalDevice, which is run by the job.
+pub fn grow_physical_device_job_method(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Method<MTSync<TData>, TData> {
+    f.method("GrowPhysicalDeviceJob", (), grow_physical_job)
+        // s: String representation of device UUID
+        .in_arg(("dev", "s"))
+        // o: Object path of the job which extends the device
+        .out_arg(("job", "o"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn apply_batch_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("ApplyBatch", (), apply_batch)
+        // JSON array of operations, each an object whose "op" member names
+        // the operation
+        .in_arg(("operations", "s"))
+        // as: Description of each change made
+        //
+        // Rust representation: Vec<String>
+        .out_arg(("results", "as"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn get_layout_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("GetLayout", (), get_layout)
+        // The format of the layout: "json", "ascii" or "svg"
+        .in_arg(("format", "s"))
+        // s: Which sectors of each block device of the pool hold what, in
+        // the requested format
+        //
+        // Rust representation: String
+        .out_arg(("layout", "s"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn compact_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("Compact", (), compact)
+        // t: Number of segments of the flex devices before compaction
+        // t: Number of segments of the flex devices after compaction
+        // s: Number of bytes moved
+        // s: Number of bytes added to the thin data device
+        //
+        // Rust representation: (u64, u64, String, String)
+        .out_arg(("results", "(ttss)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+/// Takes the same arguments as Compact, which is run by the job.
+pub fn compact_job_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("CompactJob", (), compact_job)
+        // o: Object path of the job which compacts the pool
+        .out_arg(("job", "o"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
@@ -0,0 +1,366 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus::Message;
+use dbus_tree::{MTSync, MethodInfo, MethodResult};
+use futures::executor::block_on;
+
+use crate::{
+    dbus_api::{
+        filesystem::create_dbus_filesystem,
+        job::start_job,
+        polkit::{ACTION_ENCRYPTION_BIND, ACTION_FILESYSTEM_CREATE, ACTION_FILESYSTEM_SNAPSHOT},
+        types::{DbusErrorEnum, TData, OK_STRING},
+        util::{engine_to_dbus_err_tuple, get_next_arg},
+    },
+    engine::{BatchChange, BatchOperation, CreateAction, Name},
+};
+
+/// The width in characters of the bars of the ASCII rendering of a pool's
+/// layout.
+const LAYOUT_ASCII_WIDTH: u64 = 72;
+
+/// Clone a filesystem from another pool into the pool on which the method is
+/// called.
+pub fn clone_filesystem(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let filesystem: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let name: &str = get_next_arg(&mut iter, 1)?;
+    let preserve_uuid: bool = get_next_arg(&mut iter, 2)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = (false, dbus::Path::default());
+
+    check_polkit!(m; ACTION_FILESYSTEM_SNAPSHOT; default_return; return_message);
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let (source_pool_uuid, fs_uuid) = match m.tree.get(&filesystem) {
+        Some(op) => {
+            let data = get_data!(op; default_return; return_message);
+            let source_pool_path = get_parent!(m; data; default_return; return_message);
+            (
+                typed_uuid!(
+                    get_data!(source_pool_path; default_return; return_message).uuid;
+                    Pool;
+                    default_return;
+                    return_message
+                ),
+                typed_uuid!(data.uuid; Fs; default_return; return_message),
+            )
+        }
+        None => {
+            let message = format!("no data for object path {filesystem}");
+            let (rc, rs) = (DbusErrorEnum::ERROR as u16, message);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let msg = match handle_action!(block_on(dbus_context.engine.clone_filesystem(
+        source_pool_uuid,
+        fs_uuid,
+        pool_uuid,
+        name,
+        preserve_uuid,
+    ))) {
+        Ok(CreateAction::Created(uuid)) => {
+            let guard = get_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+            let (pool_name, _, pool) = guard.as_tuple();
+            match pool.get_filesystem(uuid) {
+                Some((_, fs)) => {
+                    let fs_object_path: dbus::Path<'_> = create_dbus_filesystem(
+                        dbus_context,
+                        object_path.clone(),
+                        &pool_name,
+                        &Name::new(name.to_string()),
+                        uuid,
+                        fs,
+                    );
+                    return_message.append3(
+                        (true, fs_object_path),
+                        DbusErrorEnum::OK as u16,
+                        OK_STRING.to_string(),
+                    )
+                }
+                None => {
+                    let message = format!("engine does not know about filesystem with uuid {uuid}");
+                    return_message.append3(default_return, DbusErrorEnum::ERROR as u16, message)
+                }
+            }
+        }
+        Ok(CreateAction::Identity) => return_message.append3(
+            default_return,
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        ),
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+
+    Ok(vec![msg])
+}
+
+/// Initialize the cache of the pool in a job.
+pub fn init_cache_job(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    start_job(
+        m,
+        &format!("Initialize cache of pool {}", m.path.get_name()),
+        "InitCache",
+    )
+}
+
+/// Extend a device of the pool to its physical size in a job.
+pub fn grow_physical_job(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let dev_uuid_str: &str = get_next_arg(&mut iter, 0)?;
+
+    start_job(
+        m,
+        &format!("Extend device {dev_uuid_str} of pool {}", m.path.get_name()),
+        "GrowPhysicalDevice",
+    )
+}
+
+/// Apply a batch of operations, given as a JSON array, to the pool.
+pub fn apply_batch(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let ops_str: &str = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return: Vec<String> = Vec::new();
+
+    let ops = match serde_json::from_str::<Vec<BatchOperation>>(ops_str) {
+        Ok(ops) => ops,
+        Err(e) => {
+            let (rc, rs) = (
+                DbusErrorEnum::ERROR as u16,
+                format!("Could not parse batch of operations: {e}"),
+            );
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    if ops
+        .iter()
+        .any(|op| matches!(op, BatchOperation::CreateFilesystem { .. }))
+    {
+        check_polkit!(m; ACTION_FILESYSTEM_CREATE; default_return; return_message);
+    }
+    if ops
+        .iter()
+        .any(|op| matches!(op, BatchOperation::BindKeyring { .. }))
+    {
+        check_polkit!(m; ACTION_ENCRYPTION_BIND; default_return; return_message);
+    }
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let had_key_desc = pool
+        .encryption_info()
+        .and_then(|either| either.left())
+        .as_ref()
+        .and_then(|ei| ei.single_key_description())
+        .is_some();
+
+    let changes = match pool.apply_batch(&pool_name, pool_uuid, &ops) {
+        Ok(changes) => changes,
+        Err(e) => {
+            if let Some(state) = e.error_to_available_actions() {
+                dbus_context.push_pool_avail_actions(object_path, state);
+            }
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    for change in changes.iter() {
+        info!("Pool {}: {}", pool_name, change);
+        match change {
+            BatchChange::FilesystemCreated(name, uuid) => {
+                let filesystem = pool
+                    .get_filesystem(*uuid)
+                    .expect("just created by apply_batch")
+                    .1;
+                create_dbus_filesystem(
+                    dbus_context,
+                    object_path.clone(),
+                    &pool_name,
+                    name,
+                    *uuid,
+                    filesystem,
+                );
+            }
+            BatchChange::FilesystemRenamed(uuid, name) => {
+                if let Some(path) = uuid_to_path!(m.tree, *uuid, Fs) {
+                    dbus_context.push_filesystem_name_change(path, name);
+                }
+            }
+            BatchChange::FsSizeLimitChanged(uuid, limit) => {
+                if let Some(path) = uuid_to_path!(m.tree, *uuid, Fs) {
+                    dbus_context.push_fs_size_limit_change(path, *limit);
+                }
+            }
+            BatchChange::FsLimitChanged(limit) => {
+                dbus_context.push_pool_fs_limit_change(object_path, *limit);
+            }
+            BatchChange::OverprovModeChanged(enabled) => {
+                dbus_context.push_pool_overprov_mode_change(object_path, *enabled);
+            }
+            BatchChange::BlockdevUserInfoChanged(uuid, user_info) => {
+                if let Some(path) = uuid_to_path!(m.tree, *uuid, Dev) {
+                    dbus_context.push_blockdev_user_info_change(path, user_info.clone());
+                }
+            }
+            BatchChange::KeyringBound(_, _) => {
+                dbus_context.push_pool_key_desc_change(
+                    object_path,
+                    pool.encryption_info()
+                        .map(|ei| ei.map_left(|e| (!had_key_desc, e))),
+                );
+            }
+        }
+    }
+
+    Ok(vec![return_message.append3(
+        changes.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+        DbusErrorEnum::OK as u16,
+        OK_STRING.to_string(),
+    )])
+}
+
+/// Describe which sectors of each block device of the pool hold what, as
+/// JSON, as text, or as an SVG image.
+pub fn get_layout(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let format: &str = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = String::new();
+
+    if !["json", "ascii", "svg"].contains(&format) {
+        let (rc, rs) = (
+            DbusErrorEnum::ERROR as u16,
+            format!("Unknown layout format {format}; must be one of json, ascii or svg"),
+        );
+        return Ok(vec![return_message.append3(default_return, rc, rs)]);
+    }
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let guard = get_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_tuple();
+
+    let result = pool.layout(&pool_name).and_then(|layout| match format {
+        "json" => serde_json::to_string(&layout).map_err(|e| e.into()),
+        "ascii" => Ok(layout.to_ascii(LAYOUT_ASCII_WIDTH)),
+        _ => Ok(layout.to_svg()),
+    });
+
+    let msg = match result {
+        Ok(v) => return_message.append3(v, DbusErrorEnum::OK as u16, OK_STRING.to_string()),
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
+
+/// Relocate the segments of the pool's flex devices so that each occupies a
+/// single extent.
+pub fn compact(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = (0u64, 0u64, String::new(), String::new());
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let msg = match pool.compact(&pool_name, pool_uuid) {
+        Ok(report) => return_message.append3(
+            (
+                report.segments_before as u64,
+                report.segments_after as u64,
+                (*report.moved.bytes()).to_string(),
+                (*report.data_extension.bytes()).to_string(),
+            ),
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        ),
+        Err(e) => {
+            if let Some(state) = e.error_to_available_actions() {
+                dbus_context.push_pool_avail_actions(object_path, state);
+            }
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
+
+/// Compact the allocations of the pool's flex devices in a job.
+pub fn compact_job(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    start_job(
+        m,
+        &format!("Compact allocations of pool {}", m.path.get_name()),
+        "Compact",
+    )
+}
@@ -0,0 +1,11 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+mod api;
+mod methods;
+
+pub use api::{
+    apply_batch_method, clone_filesystem_method, compact_job_method, compact_method,
+    get_layout_method, grow_physical_device_job_method, init_cache_job_method,
+};
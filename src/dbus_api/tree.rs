@@ -15,11 +15,14 @@ use dbus::{
     },
     channel::Sender,
     message::SignalArgs,
-    Path,
+    Message, Path,
 };
 use dbus_tree::{MTSync, ObjectPath};
 use either::Either;
-use tokio::sync::{broadcast::Receiver, mpsc::UnboundedReceiver};
+use tokio::{
+    sync::{broadcast::Receiver, mpsc::UnboundedReceiver},
+    task,
+};
 
 use devicemapper::{Bytes, Sectors};
 
@@ -32,20 +35,22 @@ use crate::{
         },
         consts,
         filesystem::prop_conv::{
-            fs_origin_to_prop, fs_size_limit_to_prop, fs_size_to_prop, fs_used_to_prop,
+            fs_origin_to_prop, fs_reservation_to_prop, fs_size_limit_to_prop, fs_size_to_prop,
+            fs_used_to_prop,
         },
+        job::{job_error_prop, job_path, run_job},
         pool::prop_conv::{
             avail_actions_to_prop, clevis_info_to_prop, key_desc_to_prop, pool_alloc_to_prop,
             pool_size_to_prop, pool_used_to_prop,
         },
         types::{
-            DbusAction, InterfacesAddedThreadSafe, InterfacesRemoved, LockableTree, SignalChange,
-            TData, TreeReadLock, TreeWriteLock,
+            DbusAction, InterfacesAddedThreadSafe, InterfacesRemoved, JobRun, LockableTree,
+            SignalChange, TData, TreeReadLock, TreeWriteLock,
         },
         util::{poll_exit_and_future, thread_safe_to_dbus_sendable},
     },
     engine::{
-        ActionAvailability, DevUuid, EncryptionInfo, FilesystemUuid, LockedPoolsInfo,
+        ActionAvailability, DevUuid, EncryptionInfo, FilesystemUuid, JobInfo, LockedPoolsInfo,
         PoolEncryptionInfo, PoolUuid, StoppedPoolsInfo, StratisUuid,
     },
     stratis::{StratisError, StratisResult},
@@ -202,6 +207,11 @@ impl DbusTreeHandler {
                         vec![consts::FILESYSTEM_DEVNODE_PROP.into()],
                         consts::FILESYSTEM_NAME_PROP.to_string() =>
                         Variant(new_name.box_clone())
+                    },
+                    consts::FILESYSTEM_INTERFACE_NAME_3_9 => {
+                        vec![consts::FILESYSTEM_DEVNODE_PROP.into()],
+                        consts::FILESYSTEM_NAME_PROP.to_string() =>
+                        Variant(new_name.box_clone())
                     }
                 },
             )
@@ -225,6 +235,11 @@ impl DbusTreeHandler {
                         vec![],
                         consts::FILESYSTEM_ORIGIN_PROP.to_string() =>
                         box_variant!(fs_origin_to_prop(new_origin))
+                    },
+                    consts::FILESYSTEM_INTERFACE_NAME_3_9 => {
+                        vec![],
+                        consts::FILESYSTEM_ORIGIN_PROP.to_string() =>
+                        box_variant!(fs_origin_to_prop(new_origin))
                     }
                 },
             )
@@ -289,6 +304,11 @@ impl DbusTreeHandler {
                         Vec::new(),
                         consts::POOL_NAME_PROP.to_string() =>
                         Variant(new_name.box_clone())
+                    },
+                    consts::POOL_INTERFACE_NAME_3_9 => {
+                        Vec::new(),
+                        consts::POOL_NAME_PROP.to_string() =>
+                        Variant(new_name.box_clone())
                     }
                 },
             )
@@ -339,6 +359,9 @@ impl DbusTreeHandler {
                             },
                             consts::FILESYSTEM_INTERFACE_NAME_3_8 => {
                                 vec![consts::FILESYSTEM_DEVNODE_PROP.into()]
+                            },
+                            consts::FILESYSTEM_INTERFACE_NAME_3_9 => {
+                                vec![consts::FILESYSTEM_DEVNODE_PROP.into()]
                             }
                         },
                     )
@@ -402,6 +425,11 @@ impl DbusTreeHandler {
                         box_variant!(avail_prop.clone())
                     },
                     consts::POOL_INTERFACE_NAME_3_8 => {
+                        Vec::new(),
+                        consts::POOL_AVAIL_ACTIONS_PROP.to_string() =>
+                        box_variant!(avail_prop.clone())
+                    },
+                    consts::POOL_INTERFACE_NAME_3_9 => {
                         Vec::new(),
                         consts::POOL_AVAIL_ACTIONS_PROP.to_string() =>
                         box_variant!(avail_prop)
@@ -485,6 +513,11 @@ impl DbusTreeHandler {
                         Vec::new(),
                         consts::POOL_KEY_DESCS_PROP.to_string() =>
                         key_descs_to_prop(ei.clone().map(|either| either.map_left(|(_, ei)| ei)))
+                    },
+                    consts::POOL_INTERFACE_NAME_3_9 => {
+                        Vec::new(),
+                        consts::POOL_KEY_DESCS_PROP.to_string() =>
+                        key_descs_to_prop(ei.clone().map(|either| either.map_left(|(_, ei)| ei)))
                     }
                 },
             )
@@ -566,6 +599,11 @@ impl DbusTreeHandler {
                         Vec::new(),
                         consts::POOL_CLEVIS_INFOS_PROP.to_string() =>
                         clevis_infos_to_prop(ei.clone().map(|either| either.map_left(|(_, ei)| ei)))
+                    },
+                    consts::POOL_INTERFACE_NAME_3_9 => {
+                        Vec::new(),
+                        consts::POOL_CLEVIS_INFOS_PROP.to_string() =>
+                        clevis_infos_to_prop(ei.clone().map(|either| either.map_left(|(_, ei)| ei)))
                     }
                 },
             )
@@ -616,6 +654,10 @@ impl DbusTreeHandler {
                     consts::POOL_INTERFACE_NAME_3_8 => {
                         Vec::new(),
                         consts::POOL_HAS_CACHE_PROP.to_string() => box_variant!(b)
+                    },
+                    consts::POOL_INTERFACE_NAME_3_9 => {
+                        Vec::new(),
+                        consts::POOL_HAS_CACHE_PROP.to_string() => box_variant!(b)
                     }
                 },
             )
@@ -689,6 +731,11 @@ impl DbusTreeHandler {
                         Vec::new(),
                         consts::STOPPED_POOLS_PROP.to_string() =>
                         box_variant!(stopped_pools_to_prop(&stopped_pools, true))
+                    },
+                    consts::MANAGER_INTERFACE_NAME_3_9 => {
+                        Vec::new(),
+                        consts::STOPPED_POOLS_PROP.to_string() =>
+                        box_variant!(stopped_pools_to_prop(&stopped_pools, true))
                     }
                 },
             )
@@ -784,6 +831,14 @@ impl DbusTreeHandler {
                 consts::FILESYSTEM_SIZE_PROP.to_string(),
                 fs_size_to_prop,
                 new_size
+            },
+            consts::FILESYSTEM_INTERFACE_NAME_3_9 => {
+                consts::FILESYSTEM_USED_PROP.to_string(),
+                fs_used_to_prop,
+                new_used,
+                consts::FILESYSTEM_SIZE_PROP.to_string(),
+                fs_size_to_prop,
+                new_size
             }
         );
     }
@@ -899,6 +954,17 @@ impl DbusTreeHandler {
                 consts::POOL_NO_ALLOCABLE_SPACE_PROP.to_string(),
                 |x| x,
                 new_no_space
+            },
+            consts::POOL_INTERFACE_NAME_3_9 => {
+                consts::POOL_TOTAL_USED_PROP.to_string(),
+                pool_used_to_prop,
+                new_used,
+                consts::POOL_ALLOC_SIZE_PROP.to_string(),
+                pool_alloc_to_prop,
+                new_alloc,
+                consts::POOL_NO_ALLOCABLE_SPACE_PROP.to_string(),
+                |x| x,
+                new_no_space
             }
         );
     }
@@ -947,6 +1013,11 @@ impl DbusTreeHandler {
                     Vec::new(),
                     consts::POOL_FS_LIMIT_PROP.to_string() =>
                     box_variant!(new_fs_limit)
+                },
+                consts::POOL_INTERFACE_NAME_3_9 => {
+                    Vec::new(),
+                    consts::POOL_FS_LIMIT_PROP.to_string() =>
+                    box_variant!(new_fs_limit)
                 }
             ),
         ) {
@@ -974,6 +1045,11 @@ impl DbusTreeHandler {
                     box_variant!(size_limit.clone())
                 },
                 consts::FILESYSTEM_INTERFACE_NAME_3_8 => {
+                    Vec::new(),
+                    consts::FILESYSTEM_SIZE_LIMIT_PROP.to_string() =>
+                    box_variant!(size_limit.clone())
+                },
+                consts::FILESYSTEM_INTERFACE_NAME_3_9 => {
                     Vec::new(),
                     consts::FILESYSTEM_SIZE_LIMIT_PROP.to_string() =>
                     box_variant!(size_limit)
@@ -987,6 +1063,25 @@ impl DbusTreeHandler {
         }
     }
 
+    /// Send a signal indicating that the filesystem reservation has changed.
+    fn handle_fs_reservation_change(&self, path: Path<'static>, new_reservation: Option<Sectors>) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::FILESYSTEM_INTERFACE_NAME_3_9 => {
+                    Vec::new(),
+                    consts::FILESYSTEM_RESERVATION_PROP.to_string() =>
+                    box_variant!(fs_reservation_to_prop(new_reservation))
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating filesystem reservation change: {}",
+                e
+            );
+        }
+    }
+
     /// Send a signal indicating that the filesystem merge scheduled value has
     /// changed.
     fn handle_fs_merge_scheduled_change(&self, path: Path<'static>, new_scheduled: bool) {
@@ -1038,6 +1133,11 @@ impl DbusTreeHandler {
                     box_variant!(user_info_prop.clone())
                 },
                 consts::BLOCKDEV_INTERFACE_NAME_3_8 => {
+                    Vec::new(),
+                    consts::BLOCKDEV_USER_INFO_PROP.to_string() =>
+                    box_variant!(user_info_prop.clone())
+                },
+                consts::BLOCKDEV_INTERFACE_NAME_3_9 => {
                     Vec::new(),
                     consts::BLOCKDEV_USER_INFO_PROP.to_string() =>
                     box_variant!(user_info_prop)
@@ -1104,6 +1204,11 @@ impl DbusTreeHandler {
                     box_variant!(total_physical_size_prop.clone())
                 },
                 consts::BLOCKDEV_INTERFACE_NAME_3_8 => {
+                    Vec::new(),
+                    consts::BLOCKDEV_TOTAL_SIZE_PROP.to_string() =>
+                    box_variant!(total_physical_size_prop.clone())
+                },
+                consts::BLOCKDEV_INTERFACE_NAME_3_9 => {
                     Vec::new(),
                     consts::BLOCKDEV_TOTAL_SIZE_PROP.to_string() =>
                     box_variant!(total_physical_size_prop)
@@ -1161,6 +1266,11 @@ impl DbusTreeHandler {
                     Vec::new(),
                     consts::POOL_OVERPROV_PROP.to_string() =>
                     box_variant!(new_mode)
+                },
+                consts::POOL_INTERFACE_NAME_3_9 => {
+                    Vec::new(),
+                    consts::POOL_OVERPROV_PROP.to_string() =>
+                    box_variant!(new_mode)
                 }
             ),
         ) {
@@ -1306,6 +1416,20 @@ impl DbusTreeHandler {
                 consts::POOL_NO_ALLOCABLE_SPACE_PROP.to_string(),
                 |x| x,
                 new_no_space
+            },
+            consts::POOL_INTERFACE_NAME_3_9 => {
+                consts::POOL_TOTAL_USED_PROP.to_string(),
+                pool_used_to_prop,
+                new_used,
+                consts::POOL_ALLOC_SIZE_PROP.to_string(),
+                pool_alloc_to_prop,
+                new_alloc,
+                consts::POOL_TOTAL_SIZE_PROP.to_string(),
+                pool_size_to_prop,
+                new_size,
+                consts::POOL_NO_ALLOCABLE_SPACE_PROP.to_string(),
+                |x| x,
+                new_no_space
             }
         );
     }
@@ -1351,6 +1475,11 @@ impl DbusTreeHandler {
                 consts::BLOCKDEV_NEW_SIZE_PROP.to_string(),
                 blockdev_new_size_to_prop,
                 new_size
+            },
+            consts::BLOCKDEV_INTERFACE_NAME_3_9 => {
+                consts::BLOCKDEV_NEW_SIZE_PROP.to_string(),
+                blockdev_new_size_to_prop,
+                new_size
             }
         )
     }
@@ -1421,6 +1550,10 @@ impl DbusTreeHandler {
                 self.handle_fs_size_limit_change(path, new_limit);
                 Ok(true)
             }
+            DbusAction::FsReservationChange(path, new_reservation) => {
+                self.handle_fs_reservation_change(path, new_reservation);
+                Ok(true)
+            }
             DbusAction::FsMergeScheduledChange(path, new_scheduled) => {
                 self.handle_fs_merge_scheduled_change(path, new_scheduled);
                 Ok(true)
@@ -1482,6 +1615,99 @@ impl DbusTreeHandler {
                     new_size
                 }
             }
+            DbusAction::JobRun(run) => {
+                self.handle_job_run(run);
+                Ok(true)
+            }
+            DbusAction::JobChange(path, info) => {
+                self.handle_job_change(path, info);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Run a job in the background. The job waits for all earlier jobs to
+    /// finish, then runs its method call in a thread of its own, so that
+    /// the tree handler may continue to process actions.
+    fn handle_job_run(&self, run: JobRun) {
+        let tree = self.tree.clone();
+        tokio::spawn(async move {
+            let JobRun {
+                context,
+                job,
+                path,
+                call,
+            } = run;
+            let jobs = context.engine.jobs();
+            match jobs.start(&job).await {
+                Some(_permit) => {
+                    context.push_job_change(&path, job.info());
+                    let (watch_context, watch_path) = (context.clone(), path.clone());
+                    job.watch_progress(move |info| {
+                        watch_context.push_job_change(&watch_path, info.clone())
+                    });
+                    let running = Arc::clone(&job);
+                    let result = match task::spawn_blocking(move || {
+                        running.run_sync(|| run_job(&tree, &call))
+                    })
+                    .await
+                    {
+                        Ok(result) => result,
+                        Err(e) => Err(format!("job thread panicked: {e}")),
+                    };
+                    if let Err(ref e) = result {
+                        warn!("Job {} failed: {}", job.id(), e);
+                    }
+                    jobs.finish(&job, result);
+                    context.push_job_change(&path, job.info());
+                }
+                None => {
+                    info!("Job {} was cancelled before it started", job.id());
+                }
+            }
+            for id in jobs.prune() {
+                context.push_remove(&job_path(id), consts::job_interface_list());
+            }
+        });
+    }
+
+    /// Send a signal indicating that the state of a job has changed and, if
+    /// the job has finished, a signal indicating its outcome.
+    fn handle_job_change(&self, path: Path<'static>, info: JobInfo) {
+        let error = job_error_prop(&info);
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::JOB_INTERFACE_NAME_3_9 => {
+                    Vec::new(),
+                    consts::JOB_STATE_PROP.to_string() =>
+                    box_variant!(info.state.to_string()),
+                    consts::JOB_PROGRESS_PROP.to_string() =>
+                    box_variant!(info.progress),
+                    consts::JOB_ERROR_PROP.to_string() =>
+                    box_variant!(error.clone())
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating job state change: {}",
+                e
+            );
+        }
+
+        if info.state.is_finished() {
+            let msg = Message::signal(
+                &path,
+                &consts::JOB_INTERFACE_NAME_3_9.into(),
+                &consts::JOB_FINISHED_SIGNAL.into(),
+            )
+            .append2(info.state.to_string(), error);
+            if let Err(()) = self.connection.send(msg) {
+                warn!(
+                    "Failed to send a signal over D-Bus indicating that job {} finished",
+                    info.id
+                );
+            }
         }
     }
 
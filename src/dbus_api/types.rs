@@ -15,7 +15,7 @@ use std::{
 use dbus::{
     arg::{RefArg, Variant},
     blocking::SyncConnection,
-    Path,
+    Message, Path,
 };
 use dbus_tree::{DataType, MTSync, ObjectPath, Tree};
 use either::Either;
@@ -29,10 +29,11 @@ use crate::{
     dbus_api::{connection::DbusConnectionHandler, tree::DbusTreeHandler, udev::DbusUdevHandler},
     engine::{
         total_allocated, total_used, ActionAvailability, DevUuid, Diff, EncryptionInfo, Engine,
-        ExclusiveGuard, FilesystemUuid, Lockable, LockedPoolsInfo, PoolDiff, PoolEncryptionInfo,
-        PoolUuid, SharedGuard, StoppedPoolsInfo, StratBlockDevDiff, StratFilesystemDiff,
-        StratPoolDiff, StratisUuid, ThinPoolDiff,
+        ExclusiveGuard, FilesystemUuid, Job, JobInfo, Lockable, LockedPoolsInfo, PoolDiff,
+        PoolEncryptionInfo, PoolUuid, SharedGuard, StoppedPoolsInfo, StratBlockDevDiff,
+        StratFilesystemDiff, StratPoolDiff, StratisUuid, ThinPoolDiff,
     },
+    stratis::Caller,
 };
 
 /// Type for lockable D-Bus tree object.
@@ -115,6 +116,7 @@ pub enum DbusAction {
     BlockdevTotalPhysicalSizeChange(Path<'static>, Sectors),
     FsOriginChange(Path<'static>, Option<FilesystemUuid>),
     FsSizeLimitChange(Path<'static>, Option<Sectors>),
+    FsReservationChange(Path<'static>, Option<Sectors>),
     FsMergeScheduledChange(Path<'static>, bool),
     FsBackgroundChange(
         FilesystemUuid,
@@ -135,6 +137,30 @@ pub enum DbusAction {
         SignalChange<bool>,
     ),
     UdevBackgroundChange(DevUuid, SignalChange<Option<Sectors>>),
+    JobRun(JobRun),
+    JobChange(Path<'static>, JobInfo),
+}
+
+/// A method call to be run by a job. The method is looked up in the tree
+/// when the job starts and called with the arguments of message, on behalf
+/// of the caller that created the job.
+#[derive(Debug)]
+pub struct JobCall {
+    pub object: Path<'static>,
+    pub interface: String,
+    pub method: &'static str,
+    pub message: Message,
+    pub caller: Option<Caller>,
+}
+
+/// A job which has been created by a D-Bus method call and is waiting to
+/// be run.
+#[derive(Debug)]
+pub struct JobRun {
+    pub context: DbusContext,
+    pub job: Arc<Job>,
+    pub path: Path<'static>,
+    pub call: JobCall,
 }
 
 impl DbusAction {
@@ -148,11 +174,13 @@ impl DbusAction {
                         StratPoolDiff {
                             metadata_size,
                             out_of_alloc_space,
+                            ..
                         },
                     thin_pool:
                         ThinPoolDiff {
                             used,
                             allocated_size,
+                            ..
                         },
                 } = diff;
 
@@ -252,6 +280,10 @@ impl DbusContext {
         }
     }
 
+    pub fn connection(&self) -> &SyncConnection {
+        &self.connection
+    }
+
     /// Generates a new id for object paths.
     /// It is assumed that, while Stratisd is running, it will never generate
     /// more than 2^64 object paths. If it turns out that this is a bad
@@ -288,6 +320,29 @@ impl DbusContext {
         }
     }
 
+    /// Request that a job be run once its object has been added to the tree.
+    pub fn push_job_run(&self, run: JobRun) {
+        let path = run.path.clone();
+        if let Err(e) = self.sender.send(DbusAction::JobRun(run)) {
+            warn!(
+                "D-Bus job run event could not be sent to the processing thread; the job with \
+                path {} will never run: {}",
+                path, e,
+            )
+        }
+    }
+
+    /// Send changed signals for the State, Progress and Error properties of
+    /// a job and, if it has finished, the Finished signal.
+    pub fn push_job_change(&self, item: &Path<'static>, info: JobInfo) {
+        if let Err(e) = self.sender.send(DbusAction::JobChange(item.clone(), info)) {
+            warn!(
+                "D-Bus job change event could not be sent to the processing thread; no signal will be sent out for job with path {}: {}",
+                item, e,
+            )
+        }
+    }
+
     /// Send changed signal for Name property and invalidated signal for
     /// Devnode property.
     pub fn push_filesystem_name_change(&self, item: &Path<'static>, new_name: &str) {
@@ -392,6 +447,23 @@ impl DbusContext {
         }
     }
 
+    /// Send changed signal for filesystem Reservation property.
+    pub fn push_fs_reservation_change(
+        &self,
+        item: &Path<'static>,
+        new_reservation: Option<Sectors>,
+    ) {
+        if let Err(e) = self.sender.send(DbusAction::FsReservationChange(
+            item.clone(),
+            new_reservation,
+        )) {
+            warn!(
+                "D-Bus filesystem reservation change event could not be sent to the processing thread; no signal will be sent out for the reservation change of filesystem with path {}: {}",
+                item, e,
+            )
+        }
+    }
+
     /// Send changed signal for pool overprovisioning mode property.
     pub fn push_pool_overprov_mode_change(&self, item: &Path<'static>, new_mode: bool) {
         if let Err(e) = self
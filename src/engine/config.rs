@@ -0,0 +1,1011 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// A configuration describes the desired state of a set of pools. Applying it
+// compares the desired state with the pools which exist and performs only the
+// actions necessary to bring them into agreement. Applying the same
+// configuration twice therefore does nothing the second time.
+//
+// A configuration only ever adds to a pool: devices, filesystems and
+// encryption bindings which exist but are not mentioned are left alone, as
+// are pools which are not mentioned.
+//
+// An exported pool is the configuration of a single pool, recording the UUID
+// of each filesystem, from which the pool can be re-created on new devices.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+use either::Either;
+use serde_json::Value;
+
+use devicemapper::{Bytes, Sectors};
+
+use crate::{
+    engine::{
+        engine::{Engine, Pool},
+        structures::SomeLockWriteGuard,
+        types::{
+            BlockDevTier, DevUuid, EngineAction, FilesystemUuid, InputEncryptionInfo,
+            IntegritySpec, KeyDescription, Name, OptionalTokenSlotInput, PoolIdentifier, PoolUuid,
+            PropChangeAction, StratSigblockVersion,
+        },
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// The desired state of a set of pools.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolsConfig {
+    #[serde(default)]
+    pub pools: Vec<PoolConfig>,
+}
+
+/// The desired state of a single pool.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolConfig {
+    pub name: String,
+    /// Devices in the data tier.
+    pub blockdevs: Vec<PathBuf>,
+    /// Devices in the cache tier.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cache: Vec<PathBuf>,
+    /// Encryption can only be chosen when the pool is created, but further
+    /// bindings may be added to an encrypted pool later.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<EncryptionConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overprovisioning: Option<bool>,
+    /// The minimum filesystem limit; the limit is never lowered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fs_limit: Option<u64>,
+    #[serde(default)]
+    pub filesystems: Vec<FilesystemConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptionConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_desc: Option<KeyDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clevis: Option<ClevisConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClevisConfig {
+    pub pin: String,
+    pub config: Value,
+}
+
+/// The desired state of a filesystem; sizes are in bytes. The size and the
+/// UUID are only used when the filesystem is created; if no UUID is given,
+/// a new one is chosen.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilesystemConfig {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<FilesystemUuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_limit: Option<u64>,
+}
+
+impl PoolsConfig {
+    /// Parse a configuration written either as JSON or as TOML.
+    pub fn parse(text: &str) -> StratisResult<Self> {
+        if text.trim_start().starts_with('{') {
+            serde_json::from_str(text).map_err(|e| {
+                StratisError::Msg(format!("Failed to parse configuration as JSON: {e}"))
+            })
+        } else {
+            toml::from_str(text).map_err(|e| {
+                StratisError::Msg(format!("Failed to parse configuration as TOML: {e}"))
+            })
+        }
+    }
+
+    /// Check that no pool is described twice and that no filesystem is
+    /// described twice within a pool.
+    fn validate(&self) -> StratisResult<()> {
+        let mut pool_names = HashSet::new();
+        for pool in self.pools.iter() {
+            if !pool_names.insert(pool.name.as_str()) {
+                return Err(StratisError::Msg(format!(
+                    "Pool {} is described more than once",
+                    pool.name
+                )));
+            }
+            if pool.blockdevs.is_empty() {
+                return Err(StratisError::Msg(format!(
+                    "Pool {} must have at least one data device",
+                    pool.name
+                )));
+            }
+            let mut fs_names = HashSet::new();
+            let mut fs_uuids = HashSet::new();
+            for fs in pool.filesystems.iter() {
+                if !fs_names.insert(fs.name.as_str()) {
+                    return Err(StratisError::Msg(format!(
+                        "Filesystem {} in pool {} is described more than once",
+                        fs.name, pool.name
+                    )));
+                }
+                if let Some(uuid) = fs.uuid {
+                    if !fs_uuids.insert(uuid) {
+                        return Err(StratisError::Msg(format!(
+                            "UUID {uuid} is given to more than one filesystem in pool {}",
+                            pool.name
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl EncryptionConfig {
+    fn to_input(&self) -> StratisResult<Option<InputEncryptionInfo>> {
+        InputEncryptionInfo::new(
+            self.key_desc
+                .iter()
+                .map(|kd| (None, kd.clone()))
+                .collect::<Vec<_>>(),
+            self.clevis
+                .iter()
+                .map(|ci| (None, (ci.pin.clone(), ci.config.clone())))
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+/// An action which must be performed to bring the pools into agreement with
+/// a configuration.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConfigAction {
+    CreatePool {
+        name: String,
+        blockdevs: Vec<PathBuf>,
+        encryption: Option<EncryptionConfig>,
+    },
+    AddBlockdevs {
+        pool: String,
+        tier: BlockDevTier,
+        blockdevs: Vec<PathBuf>,
+    },
+    InitCache {
+        pool: String,
+        blockdevs: Vec<PathBuf>,
+    },
+    BindKeyring {
+        pool: String,
+        key_desc: KeyDescription,
+    },
+    BindClevis {
+        pool: String,
+        clevis: ClevisConfig,
+    },
+    SetOverprovMode {
+        pool: String,
+        enabled: bool,
+    },
+    SetFsLimit {
+        pool: String,
+        limit: u64,
+    },
+    CreateFilesystems {
+        pool: String,
+        filesystems: Vec<FilesystemConfig>,
+    },
+    SetFsSizeLimit {
+        pool: String,
+        filesystem: String,
+        limit: u64,
+    },
+}
+
+fn paths_to_string(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Display for ConfigAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigAction::CreatePool {
+                name,
+                blockdevs,
+                encryption,
+            } => write!(
+                f,
+                "create {}pool {name} on {}",
+                if encryption.is_some() {
+                    "encrypted "
+                } else {
+                    ""
+                },
+                paths_to_string(blockdevs)
+            ),
+            ConfigAction::AddBlockdevs {
+                pool,
+                tier,
+                blockdevs,
+            } => write!(
+                f,
+                "add {} devices {} to pool {pool}",
+                match tier {
+                    BlockDevTier::Data => "data",
+                    BlockDevTier::Cache => "cache",
+                },
+                paths_to_string(blockdevs)
+            ),
+            ConfigAction::InitCache { pool, blockdevs } => write!(
+                f,
+                "initialize cache of pool {pool} with {}",
+                paths_to_string(blockdevs)
+            ),
+            ConfigAction::BindKeyring { pool, key_desc } => write!(
+                f,
+                "bind pool {pool} to key description {}",
+                key_desc.as_application_str()
+            ),
+            ConfigAction::BindClevis { pool, clevis } => {
+                write!(f, "bind pool {pool} to Clevis pin {}", clevis.pin)
+            }
+            ConfigAction::SetOverprovMode { pool, enabled } => write!(
+                f,
+                "{} overprovisioning of pool {pool}",
+                if *enabled { "enable" } else { "disable" }
+            ),
+            ConfigAction::SetFsLimit { pool, limit } => {
+                write!(f, "set filesystem limit of pool {pool} to at least {limit}")
+            }
+            ConfigAction::CreateFilesystems { pool, filesystems } => write!(
+                f,
+                "create filesystems {} in pool {pool}",
+                filesystems
+                    .iter()
+                    .map(|fs| fs.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ConfigAction::SetFsSizeLimit {
+                pool,
+                filesystem,
+                limit,
+            } => write!(
+                f,
+                "set size limit of filesystem {filesystem} in pool {pool} to {limit} bytes"
+            ),
+        }
+    }
+}
+
+impl ConfigAction {
+    fn pool_name(&self) -> &str {
+        match self {
+            ConfigAction::CreatePool { name: pool, .. }
+            | ConfigAction::AddBlockdevs { pool, .. }
+            | ConfigAction::InitCache { pool, .. }
+            | ConfigAction::BindKeyring { pool, .. }
+            | ConfigAction::BindClevis { pool, .. }
+            | ConfigAction::SetOverprovMode { pool, .. }
+            | ConfigAction::SetFsLimit { pool, .. }
+            | ConfigAction::CreateFilesystems { pool, .. }
+            | ConfigAction::SetFsSizeLimit { pool, .. } => pool,
+        }
+    }
+}
+
+/// A change which was made by applying a configuration.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConfigChange {
+    PoolCreated(PoolUuid),
+    BlockdevsAdded(PoolUuid, BlockDevTier, Vec<DevUuid>),
+    CacheInitialized(PoolUuid, Vec<DevUuid>),
+    KeyringBound(PoolUuid),
+    ClevisBound(PoolUuid),
+    OverprovModeChanged(PoolUuid, bool),
+    FsLimitChanged(PoolUuid, u64),
+    FilesystemsCreated(PoolUuid, Vec<FilesystemUuid>),
+    FsSizeLimitChanged(PoolUuid, FilesystemUuid, Option<Sectors>),
+}
+
+/// Resolve symbolic links so that devices named differently in the
+/// configuration and by the engine can be matched. A path which can not be
+/// resolved is compared as given.
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+/// The key descriptions and Clevis pins to which a pool is bound.
+fn bindings(pool: &dyn Pool) -> StratisResult<(Vec<KeyDescription>, Vec<String>)> {
+    match pool.encryption_info() {
+        None => Ok((Vec::new(), Vec::new())),
+        Some(Either::Left(ei)) => Ok((
+            ei.all_key_descriptions()
+                .map(|(_, kd)| kd.clone())
+                .collect(),
+            ei.all_clevis_infos()
+                .map(|(_, (pin, _))| pin.clone())
+                .collect(),
+        )),
+        Some(Either::Right(pei)) => Ok((
+            pei.key_description()?.into_iter().cloned().collect(),
+            pei.clevis_info()?
+                .into_iter()
+                .map(|(pin, _)| pin.clone())
+                .collect(),
+        )),
+    }
+}
+
+/// Find the devices of a tier which are not yet in the pool. It is an error
+/// if a device is in the pool but in the other tier.
+fn missing_blockdevs(
+    pool_name: &str,
+    present: &HashMap<PathBuf, BlockDevTier>,
+    paths: &[PathBuf],
+    tier: BlockDevTier,
+) -> StratisResult<Vec<PathBuf>> {
+    let mut missing = Vec::new();
+    for path in paths {
+        match present.get(&canonical(path)) {
+            Some(t) if *t == tier => (),
+            Some(_) => {
+                return Err(StratisError::Msg(format!(
+                    "Device {} belongs to the {} tier of pool {pool_name}",
+                    path.display(),
+                    match tier {
+                        BlockDevTier::Data => "cache",
+                        BlockDevTier::Cache => "data",
+                    }
+                )))
+            }
+            None => missing.push(path.clone()),
+        }
+    }
+    Ok(missing)
+}
+
+fn plan_existing_pool(
+    pool: &dyn Pool,
+    config: &PoolConfig,
+    actions: &mut Vec<ConfigAction>,
+) -> StratisResult<()> {
+    let name = &config.name;
+    let present = pool
+        .blockdevs()
+        .into_iter()
+        .map(|(_, tier, bd)| (canonical(bd.devnode()), tier))
+        .collect::<HashMap<_, _>>();
+
+    let data = missing_blockdevs(name, &present, &config.blockdevs, BlockDevTier::Data)?;
+    if !data.is_empty() {
+        actions.push(ConfigAction::AddBlockdevs {
+            pool: name.clone(),
+            tier: BlockDevTier::Data,
+            blockdevs: data,
+        });
+    }
+
+    let cache = missing_blockdevs(name, &present, &config.cache, BlockDevTier::Cache)?;
+    if !cache.is_empty() {
+        actions.push(if pool.has_cache() {
+            ConfigAction::AddBlockdevs {
+                pool: name.clone(),
+                tier: BlockDevTier::Cache,
+                blockdevs: cache,
+            }
+        } else {
+            ConfigAction::InitCache {
+                pool: name.clone(),
+                blockdevs: cache,
+            }
+        });
+    }
+
+    if let Some(ref encryption) = config.encryption {
+        if !pool.is_encrypted() {
+            return Err(StratisError::Msg(format!(
+                "Pool {name} is not encrypted; encryption can only be chosen when a pool is created"
+            )));
+        }
+        let (key_descs, pins) = bindings(pool)?;
+        if let Some(ref key_desc) = encryption.key_desc {
+            if !key_descs.contains(key_desc) {
+                actions.push(ConfigAction::BindKeyring {
+                    pool: name.clone(),
+                    key_desc: key_desc.clone(),
+                });
+            }
+        }
+        if let Some(ref clevis) = encryption.clevis {
+            if !pins.contains(&clevis.pin) {
+                actions.push(ConfigAction::BindClevis {
+                    pool: name.clone(),
+                    clevis: clevis.clone(),
+                });
+            }
+        }
+    }
+
+    if let Some(enabled) = config.overprovisioning {
+        if enabled != pool.overprov_enabled() {
+            actions.push(ConfigAction::SetOverprovMode {
+                pool: name.clone(),
+                enabled,
+            });
+        }
+    }
+
+    if let Some(limit) = config.fs_limit {
+        if limit > pool.fs_limit() {
+            actions.push(ConfigAction::SetFsLimit {
+                pool: name.clone(),
+                limit,
+            });
+        }
+    }
+
+    let mut new_filesystems = Vec::new();
+    let mut size_limits = Vec::new();
+    for fs_config in config.filesystems.iter() {
+        match pool.get_filesystem_by_name(&Name::new(fs_config.name.clone())) {
+            Some((fs_uuid, fs)) => {
+                if let Some(uuid) = fs_config.uuid {
+                    if uuid != fs_uuid {
+                        return Err(StratisError::Msg(format!(
+                            "Filesystem {} in pool {name} has UUID {fs_uuid}, not {uuid}",
+                            fs_config.name
+                        )));
+                    }
+                }
+                if let Some(limit) = fs_config.size_limit {
+                    if fs.size_limit().map(|l| l.bytes()) != Some(Bytes::from(limit)) {
+                        size_limits.push(ConfigAction::SetFsSizeLimit {
+                            pool: name.clone(),
+                            filesystem: fs_config.name.clone(),
+                            limit,
+                        });
+                    }
+                }
+            }
+            None => new_filesystems.push(fs_config.clone()),
+        }
+    }
+    if !new_filesystems.is_empty() {
+        actions.push(ConfigAction::CreateFilesystems {
+            pool: name.clone(),
+            filesystems: new_filesystems,
+        });
+    }
+    actions.extend(size_limits);
+
+    Ok(())
+}
+
+fn plan_new_pool(config: &PoolConfig, actions: &mut Vec<ConfigAction>) {
+    let name = &config.name;
+    actions.push(ConfigAction::CreatePool {
+        name: name.clone(),
+        blockdevs: config.blockdevs.clone(),
+        encryption: config.encryption.clone(),
+    });
+    if !config.cache.is_empty() {
+        actions.push(ConfigAction::InitCache {
+            pool: name.clone(),
+            blockdevs: config.cache.clone(),
+        });
+    }
+    // Overprovisioning is enabled for a newly created pool.
+    if config.overprovisioning == Some(false) {
+        actions.push(ConfigAction::SetOverprovMode {
+            pool: name.clone(),
+            enabled: false,
+        });
+    }
+    if let Some(limit) = config.fs_limit {
+        actions.push(ConfigAction::SetFsLimit {
+            pool: name.clone(),
+            limit,
+        });
+    }
+    if !config.filesystems.is_empty() {
+        actions.push(ConfigAction::CreateFilesystems {
+            pool: name.clone(),
+            filesystems: config.filesystems.clone(),
+        });
+    }
+}
+
+/// Compute the actions which are necessary to bring the pools managed by
+/// engine into agreement with config, in the order in which they must be
+/// performed. No changes are made.
+pub async fn plan_configuration(
+    engine: &dyn Engine,
+    config: &PoolsConfig,
+) -> StratisResult<Vec<ConfigAction>> {
+    config.validate()?;
+
+    let stopped = engine.stopped_pools().await;
+    let mut actions = Vec::new();
+    for pool_config in config.pools.iter() {
+        let name = Name::new(pool_config.name.clone());
+        if stopped.name_to_uuid.contains_key(&name) {
+            return Err(StratisError::Msg(format!(
+                "Pool {name} is stopped; it must be started before a configuration can be applied to it"
+            )));
+        }
+        match engine.get_pool(PoolIdentifier::Name(name)).await {
+            Some(guard) => {
+                let (_, _, pool) = guard.as_tuple();
+                plan_existing_pool(pool, pool_config, &mut actions)?;
+            }
+            None => plan_new_pool(pool_config, &mut actions),
+        }
+    }
+    Ok(actions)
+}
+
+async fn get_mut_pool(
+    engine: &dyn Engine,
+    name: &str,
+) -> StratisResult<SomeLockWriteGuard<PoolUuid, dyn Pool>> {
+    engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))
+}
+
+async fn apply_action(
+    engine: &dyn Engine,
+    action: &ConfigAction,
+) -> StratisResult<Option<ConfigChange>> {
+    if let ConfigAction::CreatePool {
+        name,
+        blockdevs,
+        encryption,
+    } = action
+    {
+        let encryption_info = match encryption {
+            Some(ref ec) => ec.to_input()?,
+            None => None,
+        };
+        return Ok(engine
+            .create_pool(
+                name,
+                &blockdevs.iter().map(PathBuf::as_path).collect::<Vec<_>>(),
+                encryption_info.as_ref(),
+                IntegritySpec::default(),
+            )
+            .await?
+            .changed()
+            .map(ConfigChange::PoolCreated));
+    }
+
+    let mut guard = get_mut_pool(engine, action.pool_name()).await?;
+    let (pool_name, pool_uuid, pool) = guard.as_mut_tuple();
+    // Pools with metadata version 1 only permit a single binding of each
+    // kind, in the token slot reserved for it.
+    let token_slot = if pool.metadata_version() == StratSigblockVersion::V1 {
+        OptionalTokenSlotInput::Legacy
+    } else {
+        OptionalTokenSlotInput::None
+    };
+
+    match action {
+        ConfigAction::CreatePool { .. } => Ok(None),
+        ConfigAction::AddBlockdevs {
+            tier, blockdevs, ..
+        } => Ok(pool
+            .add_blockdevs(
+                pool_uuid,
+                &pool_name,
+                &blockdevs.iter().map(PathBuf::as_path).collect::<Vec<_>>(),
+                *tier,
+            )?
+            .0
+            .changed()
+            .map(|uuids| ConfigChange::BlockdevsAdded(pool_uuid, *tier, uuids))),
+        ConfigAction::InitCache { blockdevs, .. } => Ok(pool
+            .init_cache(
+                pool_uuid,
+                &pool_name,
+                &blockdevs.iter().map(PathBuf::as_path).collect::<Vec<_>>(),
+                true,
+            )?
+            .changed()
+            .map(|uuids| ConfigChange::CacheInitialized(pool_uuid, uuids))),
+        ConfigAction::BindKeyring { key_desc, .. } => Ok(pool
+            .bind_keyring(token_slot, key_desc)?
+            .is_changed()
+            .then_some(ConfigChange::KeyringBound(pool_uuid))),
+        ConfigAction::BindClevis { clevis, .. } => Ok(pool
+            .bind_clevis(token_slot, &clevis.pin, &clevis.config)?
+            .is_changed()
+            .then_some(ConfigChange::ClevisBound(pool_uuid))),
+        ConfigAction::SetOverprovMode { enabled, .. } => {
+            if pool.overprov_enabled() == *enabled {
+                Ok(None)
+            } else {
+                pool.set_overprov_mode(&pool_name, *enabled)?;
+                Ok(Some(ConfigChange::OverprovModeChanged(pool_uuid, *enabled)))
+            }
+        }
+        ConfigAction::SetFsLimit { limit, .. } => {
+            if pool.fs_limit() >= *limit {
+                Ok(None)
+            } else {
+                pool.set_fs_limit(&pool_name, pool_uuid, *limit)?;
+                Ok(Some(ConfigChange::FsLimitChanged(pool_uuid, *limit)))
+            }
+        }
+        ConfigAction::CreateFilesystems { filesystems, .. } => {
+            let mut created = pool
+                .create_filesystems(
+                    &pool_name,
+                    pool_uuid,
+                    &filesystems
+                        .iter()
+                        .filter(|fs| fs.uuid.is_none())
+                        .map(|fs| {
+                            (
+                                fs.name.as_str(),
+                                fs.size.map(Bytes::from),
+                                fs.size_limit.map(Bytes::from),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                )?
+                .changed()
+                .map(|c| c.into_iter().map(|(_, uuid, _)| uuid).collect::<Vec<_>>())
+                .unwrap_or_default();
+            for fs in filesystems.iter() {
+                if let Some(uuid) = fs.uuid {
+                    created.extend(
+                        pool.create_filesystem_with_uuid(
+                            &pool_name,
+                            pool_uuid,
+                            uuid,
+                            &fs.name,
+                            fs.size.map(Bytes::from),
+                            fs.size_limit.map(Bytes::from),
+                        )?
+                        .changed(),
+                    );
+                }
+            }
+            Ok(if created.is_empty() {
+                None
+            } else {
+                Some(ConfigChange::FilesystemsCreated(pool_uuid, created))
+            })
+        }
+        ConfigAction::SetFsSizeLimit {
+            filesystem, limit, ..
+        } => {
+            let (fs_uuid, _) = pool
+                .get_filesystem_by_name(&Name::new(filesystem.clone()))
+                .ok_or_else(|| {
+                    StratisError::Msg(format!(
+                        "No filesystem named {filesystem} found in pool {pool_name}"
+                    ))
+                })?;
+            match pool.set_fs_size_limit(fs_uuid, Some(Bytes::from(*limit)))? {
+                PropChangeAction::Identity => Ok(None),
+                PropChangeAction::NewValue(v) => Ok(Some(ConfigChange::FsSizeLimitChanged(
+                    pool_uuid, fs_uuid, v,
+                ))),
+            }
+        }
+    }
+}
+
+/// Perform the actions computed by plan_configuration in order, stopping at
+/// the first action which fails. Actions are not undone when a later action
+/// fails, so the changes which were made are returned along with the result.
+pub async fn apply_configuration(
+    engine: &dyn Engine,
+    actions: &[ConfigAction],
+) -> (Vec<ConfigChange>, StratisResult<()>) {
+    let mut changes = Vec::new();
+    for (index, action) in actions.iter().enumerate() {
+        match apply_action(engine, action).await {
+            Ok(change) => changes.extend(change),
+            Err(e) => {
+                return (
+                    changes,
+                    Err(StratisError::Chained(
+                        format!(
+                            "Failed to {action}; {index} of {} actions were completed",
+                            actions.len()
+                        ),
+                        Box::new(e),
+                    )),
+                );
+            }
+        }
+    }
+    (changes, Ok(()))
+}
+
+/// The version of the document produced by export_pool_config.
+pub const POOL_EXPORT_VERSION: u32 = 1;
+
+/// The configuration of a single pool, from which a new pool with the same
+/// filesystems may be created.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolExport {
+    pub version: u32,
+    pub pool: PoolConfig,
+}
+
+/// The first key description and Clevis binding of a pool, if any.
+fn encryption_config(pool: &dyn Pool) -> StratisResult<Option<EncryptionConfig>> {
+    let (key_desc, clevis) = match pool.encryption_info() {
+        None => return Ok(None),
+        Some(Either::Left(ei)) => (
+            ei.all_key_descriptions().map(|(_, kd)| kd.clone()).next(),
+            ei.all_clevis_infos()
+                .map(|(_, (pin, config))| ClevisConfig {
+                    pin: pin.clone(),
+                    config: config.clone(),
+                })
+                .next(),
+        ),
+        Some(Either::Right(pei)) => (
+            pei.key_description()?.cloned(),
+            pei.clevis_info()?.map(|(pin, config)| ClevisConfig {
+                pin: pin.clone(),
+                config: config.clone(),
+            }),
+        ),
+    };
+    Ok(Some(EncryptionConfig { key_desc, clevis }))
+}
+
+/// Describe an existing pool, including the UUID and size of each of its
+/// filesystems.
+pub fn export_pool_config(name: &Name, pool: &dyn Pool) -> StratisResult<PoolExport> {
+    let mut blockdevs = Vec::new();
+    let mut cache = Vec::new();
+    for (_, tier, bd) in pool.blockdevs() {
+        match tier {
+            BlockDevTier::Data => blockdevs.push(bd.devnode().to_owned()),
+            BlockDevTier::Cache => cache.push(bd.devnode().to_owned()),
+        }
+    }
+
+    let mut filesystems = pool
+        .filesystems()
+        .into_iter()
+        .map(|(fs_name, fs_uuid, fs)| {
+            Ok(FilesystemConfig {
+                name: fs_name.to_string(),
+                uuid: Some(fs_uuid),
+                size: Some(convert_int!(*fs.size(), u128, u64)?),
+                size_limit: fs
+                    .size_limit()
+                    .map(|l| convert_int!(*l.bytes(), u128, u64))
+                    .transpose()?,
+            })
+        })
+        .collect::<StratisResult<Vec<_>>>()?;
+    filesystems.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(PoolExport {
+        version: POOL_EXPORT_VERSION,
+        pool: PoolConfig {
+            name: name.to_string(),
+            blockdevs,
+            cache,
+            encryption: encryption_config(pool)?,
+            overprovisioning: Some(pool.overprov_enabled()),
+            fs_limit: Some(pool.fs_limit()),
+            filesystems,
+        },
+    })
+}
+
+/// Compute the actions which create a new pool from an exported pool on the
+/// given data devices. The new pool has no cache. It is an error if the pool
+/// already exists or if any filesystem UUID is already in use.
+pub async fn plan_pool_import(
+    engine: &dyn Engine,
+    export: &PoolExport,
+    blockdevs: Vec<PathBuf>,
+) -> StratisResult<Vec<ConfigAction>> {
+    if export.version != POOL_EXPORT_VERSION {
+        return Err(StratisError::Msg(format!(
+            "Unsupported pool export version {}; only version {POOL_EXPORT_VERSION} is supported",
+            export.version
+        )));
+    }
+
+    let config = PoolsConfig {
+        pools: vec![PoolConfig {
+            blockdevs,
+            cache: Vec::new(),
+            ..export.pool.clone()
+        }],
+    };
+    config.validate()?;
+
+    let name = Name::new(export.pool.name.clone());
+    if engine
+        .stopped_pools()
+        .await
+        .name_to_uuid
+        .contains_key(&name)
+        || engine
+            .get_pool(PoolIdentifier::Name(name.clone()))
+            .await
+            .is_some()
+    {
+        return Err(StratisError::Msg(format!("Pool {name} already exists")));
+    }
+
+    let guard = engine.pools().await;
+    for (pool_name, _, pool) in guard.iter() {
+        for fs_config in export.pool.filesystems.iter() {
+            if let Some(uuid) = fs_config.uuid {
+                if let Some((fs_name, _)) = pool.get_filesystem(uuid) {
+                    return Err(StratisError::Msg(format!(
+                        "UUID {uuid} is already in use by filesystem {fs_name} in pool {pool_name}"
+                    )));
+                }
+            }
+        }
+    }
+
+    let mut actions = Vec::new();
+    plan_new_pool(&config.pools[0], &mut actions);
+    Ok(actions)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::SimEngine;
+
+    use super::*;
+
+    fn test_config() -> PoolsConfig {
+        PoolsConfig::parse(
+            r#"
+            [[pools]]
+            name = "pool"
+            blockdevs = ["/dev/one", "/dev/two"]
+            overprovisioning = false
+            fs_limit = 20
+
+            [[pools.filesystems]]
+            name = "fs1"
+            size_limit = 2199023255552
+
+            [[pools.filesystems]]
+            name = "fs2"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    /// A configuration may be written as JSON or as TOML.
+    fn test_parse() {
+        let json = serde_json::to_string(&test_config()).unwrap();
+        assert_eq!(PoolsConfig::parse(&json).unwrap(), test_config());
+        assert!(PoolsConfig::parse("[[pools]]\nname = \"pool\"\nsize = 1\n").is_err());
+    }
+
+    #[test]
+    /// Applying a configuration creates what is missing, after which
+    /// applying it again does nothing.
+    fn test_apply_idempotent() {
+        let engine = SimEngine::default();
+        let actions = test_async!(plan_configuration(&engine, &test_config())).unwrap();
+        assert_eq!(actions.len(), 4);
+        let (changes, res) = test_async!(apply_configuration(&engine, &actions));
+        res.unwrap();
+        assert_eq!(changes.len(), 4);
+        assert!(test_async!(plan_configuration(&engine, &test_config()))
+            .unwrap()
+            .is_empty());
+
+        let mut config = test_config();
+        config.pools[0].blockdevs.push(PathBuf::from("/dev/three"));
+        config.pools[0].filesystems[0].size_limit = Some(4_398_046_511_104);
+        config.pools[0].filesystems.push(FilesystemConfig {
+            name: "fs3".to_string(),
+            uuid: None,
+            size: None,
+            size_limit: None,
+        });
+        let actions = test_async!(plan_configuration(&engine, &config)).unwrap();
+        assert_eq!(actions.len(), 3);
+        let (changes, res) = test_async!(apply_configuration(&engine, &actions));
+        res.unwrap();
+        assert_eq!(changes.len(), 3);
+        assert!(test_async!(plan_configuration(&engine, &config))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    /// A configuration which describes a pool or filesystem twice is
+    /// rejected, as is one which moves a device to another tier.
+    fn test_invalid() {
+        let mut config = test_config();
+        config.pools.push(config.pools[0].clone());
+        assert!(test_async!(plan_configuration(&SimEngine::default(), &config)).is_err());
+
+        let engine = SimEngine::default();
+        let actions = test_async!(plan_configuration(&engine, &test_config())).unwrap();
+        test_async!(apply_configuration(&engine, &actions))
+            .1
+            .unwrap();
+        let mut config = test_config();
+        config.pools[0].cache.push(PathBuf::from("/dev/one"));
+        assert!(test_async!(plan_configuration(&engine, &config)).is_err());
+    }
+
+    #[test]
+    /// Importing an exported pool re-creates its filesystems with the same
+    /// UUIDs; importing it a second time is rejected.
+    fn test_export_import() {
+        let engine = SimEngine::default();
+        let actions = test_async!(plan_configuration(&engine, &test_config())).unwrap();
+        test_async!(apply_configuration(&engine, &actions))
+            .1
+            .unwrap();
+
+        let mut export = {
+            let guard =
+                test_async!(engine.get_pool(PoolIdentifier::Name(Name::new("pool".to_string()))))
+                    .unwrap();
+            let (name, _, pool) = guard.as_tuple();
+            export_pool_config(&name, pool).unwrap()
+        };
+        let uuids = export
+            .pool
+            .filesystems
+            .iter()
+            .map(|fs| fs.uuid.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(uuids.len(), 2);
+        let json = serde_json::to_string(&export).unwrap();
+        assert_eq!(serde_json::from_str::<PoolExport>(&json).unwrap(), export);
+
+        let blockdevs = vec![PathBuf::from("/dev/three")];
+        export.pool.name = "restored".to_string();
+        assert!(test_async!(plan_pool_import(&engine, &export, blockdevs.clone())).is_err());
+
+        let engine = SimEngine::default();
+        let actions = test_async!(plan_pool_import(&engine, &export, blockdevs.clone())).unwrap();
+        test_async!(apply_configuration(&engine, &actions))
+            .1
+            .unwrap();
+        let guard =
+            test_async!(engine.get_pool(PoolIdentifier::Name(Name::new("restored".to_string()))))
+                .unwrap();
+        let (_, _, pool) = guard.as_tuple();
+        for uuid in uuids {
+            assert!(pool.get_filesystem(uuid).is_some());
+        }
+        assert!(test_async!(plan_pool_import(&engine, &export, blockdevs)).is_err());
+    }
+}
@@ -19,16 +19,18 @@ use devicemapper::{Bytes, Sectors};
 
 use crate::{
     engine::{
+        jobs::JobManager,
         structures::{AllLockReadGuard, AllLockWriteGuard, SomeLockReadGuard, SomeLockWriteGuard},
         types::{
-            ActionAvailability, BlockDevTier, Clevis, CreateAction, DeleteAction, DevUuid,
-            EncryptionInfo, FilesystemUuid, GrowAction, InputEncryptionInfo, IntegritySpec, Key,
-            KeyDescription, LockedPoolsInfo, MappingCreateAction, MappingDeleteAction, Name,
-            OptionalTokenSlotInput, PoolDiff, PoolEncryptionInfo, PoolIdentifier, PoolUuid,
-            PropChangeAction, RegenAction, RenameAction, ReportType, SetCreateAction,
-            SetDeleteAction, SetUnlockAction, StartAction, StopAction, StoppedPoolsInfo,
-            StratBlockDevDiff, StratFilesystemDiff, StratSigblockVersion, TokenUnlockMethod,
-            UdevEngineEvent, UnlockMethod,
+            ActionAvailability, BatchChange, BatchOperation, BlockDevTier, Clevis,
+            CompactionReport, CreateAction, DeleteAction, DevUuid, EncryptionInfo, FilesystemUuid,
+            GrowAction, InputEncryptionInfo, IntegritySpec, Key, KeyDescription, LockedPoolsInfo,
+            MappingCreateAction, MappingDeleteAction, Name, OptionalTokenSlotInput, PoolDiff,
+            PoolEncryptionInfo, PoolIdentifier, PoolLayout, PoolUuid, PropChangeAction,
+            RegenAction, RenameAction, ReportType, SetCreateAction, SetDeleteAction,
+            SetUnlockAction, StartAction, StopAction, StoppedPoolsInfo, StratBlockDevDiff,
+            StratFilesystemDiff, StratSigblockVersion, ThinMetaSpareAction, ThinMetaSpareInfo,
+            TokenUnlockMethod, UdevEngineEvent, UnlockMethod, UpgradeReport,
         },
     },
     stratis::StratisResult,
@@ -95,6 +97,9 @@ pub trait Filesystem: Debug {
     /// Get filesystem size limit.
     fn size_limit(&self) -> Option<Sectors>;
 
+    /// Get the amount of thin pool data space reserved for the filesystem.
+    fn reservation(&self) -> Option<Sectors>;
+
     /// Get filesystem snapshot origin.
     fn origin(&self) -> Option<FilesystemUuid>;
 
@@ -166,6 +171,20 @@ pub trait Pool: Debug + Send + Sync {
         specs: &[(&'b str, Option<Bytes>, Option<Bytes>)],
     ) -> StratisResult<SetCreateAction<(&'b str, FilesystemUuid, Sectors)>>;
 
+    /// Creates a filesystem with the given UUID, so that a filesystem can
+    /// keep its identity when the layout of a pool is restored elsewhere.
+    /// Returns an error if the name or the UUID is already in use by a
+    /// different filesystem in this pool.
+    fn create_filesystem_with_uuid(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        fs_uuid: FilesystemUuid,
+        name: &str,
+        size: Option<Bytes>,
+        size_limit: Option<Bytes>,
+    ) -> StratisResult<CreateAction<FilesystemUuid>>;
+
     /// Adds blockdevs specified by paths to pool.
     /// Returns a list of uuids corresponding to devices actually added.
     /// Returns an error if a blockdev can not be added because it is owned
@@ -388,6 +407,13 @@ pub trait Pool: Debug + Send + Sync {
         limit: Option<Bytes>,
     ) -> StratisResult<PropChangeAction<Option<Sectors>>>;
 
+    /// Set the amount of thin pool data space guaranteed to a filesystem.
+    fn set_fs_reservation(
+        &mut self,
+        fs: FilesystemUuid,
+        reservation: Option<Bytes>,
+    ) -> StratisResult<PropChangeAction<Option<Sectors>>>;
+
     /// Return the metadata that would be written if metadata were written.
     fn current_metadata(&self, pool_name: &Name) -> StratisResult<String>;
 
@@ -409,6 +435,28 @@ pub trait Pool: Debug + Send + Sync {
         fs: FilesystemUuid,
         new_scheduled: bool,
     ) -> StratisResult<PropChangeAction<bool>>;
+
+    /// Apply a batch of operations to the pool, in order. Either all
+    /// operations are applied or, if any operation fails, the operations
+    /// already applied are undone and an error is returned. The pool-level
+    /// metadata is written once, after all operations have been applied.
+    /// Returns the changes that were made.
+    fn apply_batch(
+        &mut self,
+        pool_name: &Name,
+        pool_uuid: PoolUuid,
+        ops: &[BatchOperation],
+    ) -> StratisResult<Vec<BatchChange>>;
+
+    /// Describe which sectors of each block device of the pool hold what.
+    fn layout(&self, pool_name: &Name) -> StratisResult<PoolLayout>;
+
+    /// Relocate the segments of the pool's flex devices so that each
+    /// occupies a single extent on the cap device. The pool remains in use
+    /// throughout; it is suspended only while each chunk is moved. Any
+    /// space left over is added to the thin data device.
+    fn compact(&mut self, pool_name: &Name, pool_uuid: PoolUuid)
+        -> StratisResult<CompactionReport>;
 }
 
 pub type HandleEvents<P> = (
@@ -462,6 +510,21 @@ pub trait Engine: Debug + Report + Send + Sync {
         token_slot: UnlockMethod,
     ) -> StratisResult<SetUnlockAction<DevUuid>>;
 
+    /// Clone the filesystem with UUID fs_uuid in the pool with UUID
+    /// source_pool into a new filesystem named new_name in the pool with UUID
+    /// target_pool. Only the provisioned blocks of the source are copied.
+    /// If preserve_uuid is true, the copy keeps the XFS UUID of the source,
+    /// otherwise it is given the UUID of the new filesystem.
+    /// Returns the UUID of the new filesystem.
+    async fn clone_filesystem(
+        &self,
+        source_pool: PoolUuid,
+        fs_uuid: FilesystemUuid,
+        target_pool: PoolUuid,
+        new_name: &str,
+        preserve_uuid: bool,
+    ) -> StratisResult<CreateAction<FilesystemUuid>>;
+
     /// Find the pool designated by name or UUID.
     async fn get_pool(
         &self,
@@ -502,6 +565,11 @@ pub trait Engine: Debug + Report + Send + Sync {
         pools: Option<&HashSet<PoolUuid>>,
     ) -> HashMap<FilesystemUuid, StratFilesystemDiff>;
 
+    /// Back up the thin pool metadata of every pool to its metadata volume.
+    /// Returns, for each pool, the path of the new backup or the reason that
+    /// no backup could be made.
+    async fn backup_thin_metadata(&self) -> HashMap<PoolUuid, StratisResult<PathBuf>>;
+
     /// Get the handler for kernel keyring operations.
     async fn get_key_handler(&self) -> Arc<dyn KeyActions>;
 
@@ -522,9 +590,30 @@ pub trait Engine: Debug + Report + Send + Sync {
         has_partially_constructed: bool,
     ) -> StratisResult<StopAction<PoolUuid>>;
 
+    /// Upgrade a stopped pool with legacy metadata to the current metadata
+    /// format, or, if dry_run is true, only report whether the upgrade is
+    /// possible.
+    async fn upgrade_pool(
+        &self,
+        pool_id: PoolIdentifier<PoolUuid>,
+        passphrase_fd: Option<RawFd>,
+        dry_run: bool,
+    ) -> StratisResult<UpgradeReport>;
+
+    /// Perform an operation on the spare thin metadata device of a stopped
+    /// pool and return the state of the spare afterwards.
+    async fn thin_meta_spare(
+        &self,
+        pool_id: PoolIdentifier<PoolUuid>,
+        action: ThinMetaSpareAction,
+    ) -> StratisResult<ThinMetaSpareInfo>;
+
     /// Refresh the state of all pools and liminal devices.
     async fn refresh_state(&self) -> StratisResult<()>;
 
+    /// Get the set of jobs tracking long-running operations.
+    fn jobs(&self) -> Arc<JobManager>;
+
     /// Return true if this engine is the simulator engine, otherwise false.
     fn is_sim(&self) -> bool;
 }
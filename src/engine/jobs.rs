@@ -0,0 +1,424 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Jobs track long-running operations which are started by a request but
+// complete after the request has returned. Jobs are run one at a time, in
+// the order in which they were created. A queued job is cancelled at once. A
+// running job is cancelled cooperatively: cancelling it only records the
+// request, and the operation it performs stops at its next checkpoint, a
+// point at which it can stop and leave the pool consistent. The operation
+// also reports its progress to the job.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::stratis::{StratisError, StratisResult};
+
+/// The number of finished jobs which are remembered, so that their outcome
+/// can still be queried after they complete.
+const FINISHED_JOBS_KEPT: usize = 32;
+
+pub type JobId = u64;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, strum_macros::Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    /// Whether a job in this state will never change state again.
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobState::Completed | JobState::Failed | JobState::Cancelled
+        )
+    }
+}
+
+/// A snapshot of the status of a job.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub description: String,
+    pub state: JobState,
+    /// The fraction of the job which is done, between 0 and 1.
+    pub progress: f64,
+    /// The reason the job failed, if it failed.
+    pub error: Option<String>,
+}
+
+/// A function called with the status of a job whenever its progress changes.
+type ProgressWatcher = Box<dyn Fn(&JobInfo) + Send + Sync>;
+
+pub struct Job {
+    info: Mutex<JobInfo>,
+    watcher: Mutex<Option<ProgressWatcher>>,
+    cancel_requested: AtomicBool,
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Job")
+            .field("info", &self.info)
+            .finish_non_exhaustive()
+    }
+}
+
+tokio::task_local! {
+    static CURRENT_JOB: Arc<Job>;
+}
+
+impl Job {
+    /// Run f as the work of this job, so that the operations f performs on
+    /// the current thread report their progress to the job.
+    pub fn run_sync<F, R>(self: Arc<Self>, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        CURRENT_JOB.sync_scope(self, f)
+    }
+
+    /// Run fut as the work of this job, so that the operations fut performs
+    /// report their progress to the job.
+    pub fn run<F>(self: Arc<Self>, fut: F) -> impl Future<Output = F::Output>
+    where
+        F: Future,
+    {
+        CURRENT_JOB.scope(self, fut)
+    }
+
+    /// The job which the current operation is performed for, if any.
+    pub fn current() -> Option<Arc<Job>> {
+        CURRENT_JOB.try_with(Arc::clone).ok()
+    }
+
+    /// Fail if cancellation of the job which the current operation is
+    /// performed for has been requested. Long-running operations call this at
+    /// each point at which they can stop and leave the pool consistent.
+    pub fn checkpoint() -> StratisResult<()> {
+        match Job::current() {
+            Some(job) => job.check_cancelled(),
+            None => Ok(()),
+        }
+    }
+
+    /// Fail if cancellation of this job has been requested.
+    pub fn check_cancelled(&self) -> StratisResult<()> {
+        if self.cancel_requested.load(Ordering::SeqCst) {
+            Err(StratisError::Msg(format!("Job {} was cancelled", self.id())))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Run f as the work of job, if there is one.
+    pub fn run_sync_opt<F, R>(job: Option<Arc<Job>>, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        match job {
+            Some(job) => job.run_sync(f),
+            None => f(),
+        }
+    }
+
+    pub fn id(&self) -> JobId {
+        self.info().id
+    }
+
+    pub fn info(&self) -> JobInfo {
+        self.info
+            .lock()
+            .expect("job lock is never poisoned")
+            .clone()
+    }
+
+    /// Call watcher with the status of the job whenever its progress
+    /// changes while it is running.
+    pub fn watch_progress<F>(&self, watcher: F)
+    where
+        F: Fn(&JobInfo) + Send + Sync + 'static,
+    {
+        *self.watcher.lock().expect("job lock is never poisoned") = Some(Box::new(watcher));
+    }
+
+    /// Record the fraction of the job which is done. Progress is ignored
+    /// unless the job is running.
+    pub fn set_progress(&self, progress: f64) {
+        let changed = {
+            let mut info = self.info.lock().expect("job lock is never poisoned");
+            let progress = progress.clamp(0.0, 1.0);
+            if info.state == JobState::Running && info.progress != progress {
+                info.progress = progress;
+                Some(info.clone())
+            } else {
+                None
+            }
+        };
+        if let Some(info) = changed {
+            if let Some(ref watcher) = *self.watcher.lock().expect("job lock is never poisoned") {
+                watcher(&info);
+            }
+        }
+    }
+
+    /// Record that done out of total units of the job's work are complete.
+    pub fn set_done(&self, done: u64, total: u64) {
+        if total > 0 {
+            // Progress is only an estimate, so precision does not matter.
+            #[allow(clippy::cast_precision_loss)]
+            let progress = done as f64 / total as f64;
+            self.set_progress(progress);
+        }
+    }
+
+    fn set_state(&self, state: JobState, error: Option<String>) {
+        let mut info = self.info.lock().expect("job lock is never poisoned");
+        info.state = state;
+        if state == JobState::Completed {
+            info.progress = 1.0;
+        }
+        info.error = error;
+    }
+}
+
+/// The set of jobs of an engine.
+#[derive(Debug)]
+pub struct JobManager {
+    next_id: AtomicU64,
+    jobs: Mutex<BTreeMap<JobId, Arc<Job>>>,
+    runner: Arc<Semaphore>,
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        JobManager {
+            next_id: AtomicU64::new(1),
+            jobs: Mutex::new(BTreeMap::new()),
+            runner: Arc::new(Semaphore::new(1)),
+        }
+    }
+}
+
+impl JobManager {
+    /// Create a new job in the queued state.
+    pub fn create(&self, description: &str) -> Arc<Job> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let job = Arc::new(Job {
+            info: Mutex::new(JobInfo {
+                id,
+                description: description.to_owned(),
+                state: JobState::Queued,
+                progress: 0.0,
+                error: None,
+            }),
+            watcher: Mutex::new(None),
+            cancel_requested: AtomicBool::new(false),
+        });
+        self.jobs
+            .lock()
+            .expect("job lock is never poisoned")
+            .insert(id, Arc::clone(&job));
+        job
+    }
+
+    pub fn get(&self, id: JobId) -> Option<Arc<Job>> {
+        self.jobs
+            .lock()
+            .expect("job lock is never poisoned")
+            .get(&id)
+            .cloned()
+    }
+
+    /// The status of all known jobs, ordered by ID.
+    pub fn list(&self) -> Vec<JobInfo> {
+        self.jobs
+            .lock()
+            .expect("job lock is never poisoned")
+            .values()
+            .map(|job| job.info())
+            .collect()
+    }
+
+    /// Cancel the job with the given ID. A queued job is cancelled at once; a
+    /// running job stops at the next checkpoint of its operation, and is
+    /// cancelled if it fails there, or completes if it has none left.
+    /// Returns true if the job was or will be cancelled, false if it had
+    /// already finished. Returns an error if there is no such job.
+    pub fn cancel(&self, id: JobId) -> StratisResult<bool> {
+        let job = self
+            .get(id)
+            .ok_or_else(|| StratisError::Msg(format!("No job with ID {id} exists")))?;
+        let mut info = job.info.lock().expect("job lock is never poisoned");
+        match info.state {
+            JobState::Queued => {
+                info.state = JobState::Cancelled;
+                Ok(true)
+            }
+            JobState::Running => {
+                job.cancel_requested.store(true, Ordering::SeqCst);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Wait until the job may run and mark it running. The job runs for as
+    /// long as the returned permit is held.
+    /// Returns None if the job was cancelled while it was queued.
+    pub async fn start(&self, job: &Job) -> Option<OwnedSemaphorePermit> {
+        let permit = Arc::clone(&self.runner)
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed");
+        let mut info = job.info.lock().expect("job lock is never poisoned");
+        if info.state == JobState::Cancelled {
+            return None;
+        }
+        info.state = JobState::Running;
+        Some(permit)
+    }
+
+    /// Record the outcome of a running job. A job whose cancellation was
+    /// requested and which failed is cancelled. The job's progress is no
+    /// longer watched.
+    pub fn finish(&self, job: &Job, result: Result<(), String>) {
+        match result {
+            Ok(()) => job.set_state(JobState::Completed, None),
+            Err(e) if job.cancel_requested.load(Ordering::SeqCst) => {
+                job.set_state(JobState::Cancelled, Some(e))
+            }
+            Err(e) => job.set_state(JobState::Failed, Some(e)),
+        }
+        *job.watcher.lock().expect("job lock is never poisoned") = None;
+    }
+
+    /// Forget the oldest finished jobs, keeping only the most recent ones.
+    /// Returns the IDs of the jobs which were forgotten.
+    pub fn prune(&self) -> Vec<JobId> {
+        let mut jobs = self.jobs.lock().expect("job lock is never poisoned");
+        let finished = jobs
+            .iter()
+            .filter(|(_, job)| job.info().state.is_finished())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        let excess = finished.len().saturating_sub(FINISHED_JOBS_KEPT);
+        let pruned = finished.into_iter().take(excess).collect::<Vec<_>>();
+        for id in pruned.iter() {
+            jobs.remove(id);
+        }
+        pruned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// A queued job may be cancelled, after which it never runs.
+    fn test_cancel_queued() {
+        let jobs = JobManager::default();
+        let job = jobs.create("test");
+        assert!(jobs.cancel(job.id()).unwrap());
+        assert_eq!(job.info().state, JobState::Cancelled);
+        assert!(!jobs.cancel(job.id()).unwrap());
+        assert!(futures::executor::block_on(jobs.start(&job)).is_none());
+        assert!(jobs.cancel(job.id() + 1).is_err());
+    }
+
+    #[test]
+    /// A running job records its progress and its outcome.
+    fn test_run() {
+        let jobs = JobManager::default();
+        let job = jobs.create("test");
+        let permit = futures::executor::block_on(jobs.start(&job));
+        assert!(permit.is_some());
+        assert_eq!(job.info().state, JobState::Running);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let watched = Arc::clone(&seen);
+        job.watch_progress(move |info| watched.lock().unwrap().push(info.progress));
+        job.set_progress(2.0);
+        assert_eq!(job.info().progress, 1.0);
+        job.set_done(1, 4);
+        assert_eq!(job.info().progress, 0.25);
+        job.set_done(1, 0);
+        assert_eq!(job.info().progress, 0.25);
+        assert_eq!(*seen.lock().unwrap(), vec![1.0, 0.25]);
+        jobs.finish(&job, Err("failed".to_string()));
+        assert_eq!(job.info().state, JobState::Failed);
+        assert_eq!(job.info().error.as_deref(), Some("failed"));
+    }
+
+    #[test]
+    /// A running job is cancelled at the next checkpoint of its operation.
+    fn test_cancel_running() {
+        let jobs = JobManager::default();
+        let job = jobs.create("test");
+        let permit = futures::executor::block_on(jobs.start(&job));
+        assert!(permit.is_some());
+        assert!(Arc::clone(&job).run_sync(Job::checkpoint).is_ok());
+        assert!(jobs.cancel(job.id()).unwrap());
+        assert_eq!(job.info().state, JobState::Running);
+        let result = Arc::clone(&job).run_sync(Job::checkpoint);
+        assert!(result.is_err());
+        assert!(Job::checkpoint().is_ok());
+        jobs.finish(&job, result.map_err(|e| e.to_string()));
+        assert_eq!(job.info().state, JobState::Cancelled);
+        assert!(!jobs.cancel(job.id()).unwrap());
+    }
+
+    #[test]
+    /// A running job which has no checkpoint left after its cancellation is
+    /// requested completes.
+    fn test_cancel_running_completes() {
+        let jobs = JobManager::default();
+        let job = jobs.create("test");
+        let permit = futures::executor::block_on(jobs.start(&job));
+        assert!(permit.is_some());
+        assert!(jobs.cancel(job.id()).unwrap());
+        jobs.finish(&job, Ok(()));
+        assert_eq!(job.info().state, JobState::Completed);
+    }
+
+    #[test]
+    /// The current job is only known within the scope in which it runs.
+    fn test_current() {
+        let jobs = JobManager::default();
+        let job = jobs.create("test");
+        assert!(Job::current().is_none());
+        let id = Arc::clone(&job).run_sync(|| Job::current().map(|j| j.id()));
+        assert_eq!(id, Some(job.id()));
+        assert!(Job::run_sync_opt(None, Job::current).is_none());
+    }
+
+    #[test]
+    /// Only the most recent finished jobs are kept.
+    fn test_prune() {
+        let jobs = JobManager::default();
+        let queued = jobs.create("queued");
+        for _ in 0..FINISHED_JOBS_KEPT + 2 {
+            let job = jobs.create("test");
+            jobs.cancel(job.id()).unwrap();
+        }
+        assert_eq!(jobs.prune(), vec![queued.id() + 1, queued.id() + 2]);
+        assert_eq!(jobs.list().len(), FINISHED_JOBS_KEPT + 1);
+        assert!(jobs.get(queued.id()).is_some());
+    }
+}
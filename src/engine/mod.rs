@@ -3,10 +3,18 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 #[cfg(feature = "extras")]
-pub use self::strat_engine::{pool_inspection, ProcessedPathInfos, StratPool};
+pub use self::strat_engine::{
+    fsck_pool, pool_inspection, upgrade_pool, FsckRepair, FsckReport, ProcessedPathInfos, StratPool,
+};
 
 pub use self::{
+    config::{
+        apply_configuration, export_pool_config, plan_configuration, plan_pool_import,
+        ClevisConfig, ConfigAction, ConfigChange, EncryptionConfig, FilesystemConfig, PoolConfig,
+        PoolExport, PoolsConfig, POOL_EXPORT_VERSION,
+    },
     engine::{BlockDev, Engine, Filesystem, KeyActions, Pool, Report},
+    jobs::{Job, JobId, JobInfo, JobManager, JobState},
     shared::{total_allocated, total_used},
     sim_engine::SimEngine,
     strat_engine::{
@@ -16,15 +24,18 @@ pub use self::{
     },
     structures::{AllLockReadGuard, ExclusiveGuard, SharedGuard, Table},
     types::{
-        ActionAvailability, BlockDevTier, ClevisInfo, CreateAction, DeleteAction, DevUuid, Diff,
-        EncryptionInfo, EngineAction, FilesystemUuid, GrowAction, InputEncryptionInfo,
-        IntegritySpec, IntegrityTagSpec, KeyDescription, Lockable, LockedPoolInfo, LockedPoolsInfo,
-        MappingCreateAction, MappingDeleteAction, MaybeInconsistent, Name, OptionalTokenSlotInput,
-        PoolDiff, PoolEncryptionInfo, PoolIdentifier, PoolUuid, PropChangeAction, RenameAction,
-        ReportType, SetCreateAction, SetDeleteAction, SetUnlockAction, StartAction, StopAction,
-        StoppedPoolInfo, StoppedPoolsInfo, StratBlockDevDiff, StratFilesystemDiff, StratPoolDiff,
-        StratSigblockVersion, StratisUuid, ThinPoolDiff, ToDisplay, TokenUnlockMethod,
-        UdevEngineEvent, UnlockMethod, ValidatedIntegritySpec, DEFAULT_INTEGRITY_JOURNAL_SIZE,
+        ActionAvailability, BatchChange, BatchOperation, BlockDevTier, CacheStats, ClevisInfo,
+        CompactionReport, CreateAction, DeleteAction, DevUuid, DeviceLayout, Diff, EncryptionInfo,
+        EngineAction, FilesystemUuid, GrowAction, InputEncryptionInfo, IntegritySpec,
+        IntegrityTagSpec, KeyDescription, LayoutExtent, LayoutUse, Lockable, LockedPoolInfo,
+        LockedPoolsInfo, MappingCreateAction, MappingDeleteAction, MaybeInconsistent, Name,
+        OptionalTokenSlotInput, PoolDiff, PoolEncryptionInfo, PoolIdentifier, PoolLayout, PoolUuid,
+        PropChangeAction, RenameAction, ReportType, SetCreateAction, SetDeleteAction,
+        SetUnlockAction, SizedKeyMemory, StartAction, StopAction, StoppedPoolInfo,
+        StoppedPoolsInfo, StratBlockDevDiff, StratFilesystemDiff, StratPoolDiff,
+        StratSigblockVersion, StratisUuid, ThinMetaSpareAction, ThinMetaSpareInfo, ThinPoolDiff,
+        ThinPoolUsageInfo, ToDisplay, TokenUnlockMethod, UdevEngineEvent, UnlockMethod,
+        UpgradeReport, ValidatedIntegritySpec, DEFAULT_INTEGRITY_JOURNAL_SIZE,
         DEFAULT_INTEGRITY_TAG_SPEC,
     },
 };
@@ -32,8 +43,10 @@ pub use self::{
 #[macro_use]
 mod macros;
 
+mod config;
 #[allow(clippy::module_inception)]
 mod engine;
+mod jobs;
 mod shared;
 mod sim_engine;
 mod strat_engine;
@@ -24,8 +24,8 @@ use crate::{
     engine::{
         engine::{Pool, MAX_STRATIS_PASS_SIZE},
         types::{
-            BlockDevTier, CreateAction, DevUuid, Diff, EncryptionInfo, MaybeInconsistent, Name,
-            PoolEncryptionInfo, PoolUuid, SetCreateAction,
+            BatchOperation, BlockDevTier, CreateAction, DevUuid, Diff, EncryptionInfo,
+            MaybeInconsistent, Name, PoolEncryptionInfo, PoolUuid, SetCreateAction,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -242,6 +242,29 @@ pub fn validate_filesystem_size(
         .transpose()
 }
 
+/// Validate a requested filesystem reservation and convert it to sectors.
+pub fn validate_filesystem_reservation(
+    name: &str,
+    reservation_opt: Option<Bytes>,
+) -> StratisResult<Option<Sectors>> {
+    reservation_opt
+        .map(|reservation| {
+            let reservation_sectors = reservation.sectors();
+            if reservation_sectors.bytes() != reservation {
+                Err(StratisError::Msg(format!(
+                    "Requested reservation of filesystem {name} must be divisible by {SECTOR_SIZE}"
+                )))
+            } else if reservation_sectors == Sectors(0) {
+                Err(StratisError::Msg(format!(
+                    "Requested reservation of filesystem {name} must be greater than zero"
+                )))
+            } else {
+                Ok(reservation_sectors)
+            }
+        })
+        .transpose()
+}
+
 pub fn validate_filesystem_size_specs<'a>(
     specs: &[(&'a str, Option<Bytes>, Option<Bytes>)],
 ) -> StratisResult<HashMap<&'a str, (Sectors, Option<Sectors>)>> {
@@ -283,6 +306,127 @@ where
 }
 
 /// Gather the encryption information from across multiple block devices.
+/// A view of the parts of a pool which operations in a batch may change, used
+/// to check that every operation in a batch is valid given the effects of the
+/// operations before it.
+struct BatchView {
+    filesystems: HashSet<String>,
+    fs_limit: u64,
+    blockdevs: HashSet<DevUuid>,
+    encrypted: bool,
+}
+
+impl BatchView {
+    fn new(pool: &dyn Pool) -> Self {
+        BatchView {
+            filesystems: pool
+                .filesystems()
+                .into_iter()
+                .map(|(name, _, _)| name.to_string())
+                .collect(),
+            fs_limit: pool.fs_limit(),
+            blockdevs: pool
+                .blockdevs()
+                .into_iter()
+                .map(|(uuid, _, _)| uuid)
+                .collect(),
+            encrypted: pool.is_encrypted(),
+        }
+    }
+
+    fn has_filesystem(&self, name: &str) -> StratisResult<()> {
+        if self.filesystems.contains(name) {
+            Ok(())
+        } else {
+            Err(StratisError::Msg(format!(
+                "Filesystem {name} does not exist"
+            )))
+        }
+    }
+
+    /// Check that op may be applied and record its effect.
+    fn apply(&mut self, op: &BatchOperation) -> StratisResult<()> {
+        match op {
+            BatchOperation::CreateFilesystem {
+                name,
+                size,
+                size_limit,
+            } => {
+                validate_name(name)?;
+                if self.filesystems.contains(name) {
+                    return Err(StratisError::Msg(format!(
+                        "Filesystem {name} already exists"
+                    )));
+                }
+                if convert_int!(self.filesystems.len(), usize, u64)? >= self.fs_limit {
+                    return Err(StratisError::Msg(format!(
+                        "Creating filesystem {name} would exceed the filesystem limit of {}",
+                        self.fs_limit
+                    )));
+                }
+                validate_filesystem_size(name, size.map(Bytes::from))?;
+                validate_filesystem_size(name, size_limit.map(Bytes::from))?;
+                self.filesystems.insert(name.to_owned());
+            }
+            BatchOperation::RenameFilesystem { name, new_name } => {
+                self.has_filesystem(name)?;
+                validate_name(new_name)?;
+                if name != new_name {
+                    if self.filesystems.contains(new_name) {
+                        return Err(StratisError::Msg(format!(
+                            "Filesystem {new_name} already exists"
+                        )));
+                    }
+                    self.filesystems.remove(name);
+                    self.filesystems.insert(new_name.to_owned());
+                }
+            }
+            BatchOperation::SetFsSizeLimit { name, limit } => {
+                self.has_filesystem(name)?;
+                validate_filesystem_size(name, limit.map(Bytes::from))?;
+            }
+            BatchOperation::SetFsLimit { limit } => {
+                if *limit < self.fs_limit {
+                    return Err(StratisError::Msg(format!(
+                        "New filesystem limit {limit} is less than the current limit {}",
+                        self.fs_limit
+                    )));
+                }
+                self.fs_limit = *limit;
+            }
+            BatchOperation::SetOverprovMode { .. } => (),
+            BatchOperation::SetBlockdevUserInfo { uuid, .. } => {
+                if !self.blockdevs.contains(uuid) {
+                    return Err(StratisError::Msg(format!(
+                        "Blockdev with UUID {uuid} does not exist"
+                    )));
+                }
+            }
+            BatchOperation::BindKeyring { .. } => {
+                if !self.encrypted {
+                    return Err(StratisError::Msg(
+                        "Pool is not encrypted; it can not be bound to a key".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check that all operations in a batch may be applied to the pool, in order,
+/// without changing the pool. Checks which depend on the state of the devices,
+/// e.g., available space, are left to the operations themselves.
+pub fn validate_batch(pool: &dyn Pool, ops: &[BatchOperation]) -> StratisResult<()> {
+    let mut view = BatchView::new(pool);
+    for (index, op) in ops.iter().enumerate() {
+        view.apply(op).map_err(|e| {
+            StratisError::Chained(format!("Operation {index} is invalid"), Box::new(e))
+        })?;
+    }
+    Ok(())
+}
+
 pub fn gather_encryption_info<'a, I>(
     len: usize,
     iterator: I,
@@ -370,6 +514,85 @@ pub fn now_to_timestamp() -> DateTime<Utc> {
 mod tests {
     use super::*;
 
+    use crate::engine::types::KeyDescription;
+
+    fn batch_view() -> BatchView {
+        BatchView {
+            filesystems: ["fs1".to_string()].into_iter().collect(),
+            fs_limit: 2,
+            blockdevs: HashSet::new(),
+            encrypted: false,
+        }
+    }
+
+    #[test]
+    /// Operations in a batch are validated against the effects of the
+    /// operations before them.
+    fn test_batch_view() {
+        let mut view = batch_view();
+        view.apply(&BatchOperation::RenameFilesystem {
+            name: "fs1".to_string(),
+            new_name: "fs2".to_string(),
+        })
+        .unwrap();
+        view.apply(&BatchOperation::SetFsSizeLimit {
+            name: "fs2".to_string(),
+            limit: None,
+        })
+        .unwrap();
+        assert_matches!(
+            view.apply(&BatchOperation::SetFsSizeLimit {
+                name: "fs1".to_string(),
+                limit: None,
+            }),
+            Err(_)
+        );
+        view.apply(&BatchOperation::CreateFilesystem {
+            name: "fs1".to_string(),
+            size: None,
+            size_limit: None,
+        })
+        .unwrap();
+        assert_matches!(
+            view.apply(&BatchOperation::CreateFilesystem {
+                name: "fs3".to_string(),
+                size: None,
+                size_limit: None,
+            }),
+            Err(_)
+        );
+        view.apply(&BatchOperation::SetFsLimit { limit: 3 })
+            .unwrap();
+        view.apply(&BatchOperation::CreateFilesystem {
+            name: "fs3".to_string(),
+            size: None,
+            size_limit: None,
+        })
+        .unwrap();
+        assert_matches!(view.apply(&BatchOperation::SetFsLimit { limit: 1 }), Err(_));
+    }
+
+    #[test]
+    /// Operations which require devices or encryption the pool does not have
+    /// are invalid.
+    fn test_batch_view_pool_properties() {
+        let mut view = batch_view();
+        assert_matches!(
+            view.apply(&BatchOperation::SetBlockdevUserInfo {
+                uuid: DevUuid::new_v4(),
+                user_info: None,
+            }),
+            Err(_)
+        );
+        assert_matches!(
+            view.apply(&BatchOperation::BindKeyring {
+                token_slot: None,
+                key_desc: KeyDescription::try_from("key".to_string()).unwrap(),
+            }),
+            Err(_)
+        );
+    }
+
     #[test]
     fn test_validate_name() {
         assert_matches!(validate_name(&'\u{0}'.to_string()), Err(_));
@@ -5,7 +5,7 @@
 use std::{
     collections::{hash_map::RandomState, HashMap, HashSet},
     os::fd::RawFd,
-    path::Path,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
@@ -16,7 +16,8 @@ use tokio::sync::RwLock;
 
 use crate::{
     engine::{
-        engine::{Engine, HandleEvents, KeyActions, Pool, Report},
+        engine::{Engine, Filesystem, HandleEvents, KeyActions, Pool, Report},
+        jobs::JobManager,
         shared::{create_pool_idempotent_or_err, validate_name, validate_paths},
         sim_engine::{keys::SimKeyActions, pool::SimPool},
         structures::{
@@ -24,11 +25,12 @@ use crate::{
             SomeLockWriteGuard, Table,
         },
         types::{
-            CreateAction, DeleteAction, DevUuid, EncryptionInfo, Features, FilesystemUuid,
-            InputEncryptionInfo, IntegritySpec, LockedPoolsInfo, Name, PoolDevice, PoolDiff,
-            PoolIdentifier, PoolUuid, RenameAction, ReportType, SetUnlockAction, StartAction,
-            StopAction, StoppedPoolInfo, StoppedPoolsInfo, StratFilesystemDiff, TokenUnlockMethod,
-            UdevEngineEvent, UnlockMechanism, UnlockMethod, ValidatedIntegritySpec,
+            CreateAction, DeleteAction, DevUuid, EncryptionInfo, EngineAction, Features,
+            FilesystemUuid, InputEncryptionInfo, IntegritySpec, LockedPoolsInfo, Name, PoolDevice,
+            PoolDiff, PoolIdentifier, PoolUuid, RenameAction, ReportType, SetUnlockAction,
+            StartAction, StopAction, StoppedPoolInfo, StoppedPoolsInfo, StratFilesystemDiff,
+            ThinMetaSpareAction, ThinMetaSpareInfo, TokenUnlockMethod, UdevEngineEvent,
+            UnlockMechanism, UnlockMethod, UpgradeReport, ValidatedIntegritySpec,
         },
         StratSigblockVersion,
     },
@@ -39,6 +41,7 @@ use crate::{
 pub struct SimEngine {
     pools: AllOrSomeLock<PoolUuid, SimPool>,
     key_handler: Arc<SimKeyActions>,
+    jobs: Arc<JobManager>,
     stopped_pools: Lockable<Arc<RwLock<Table<PoolUuid, SimPool>>>>,
 }
 
@@ -47,6 +50,7 @@ impl Default for SimEngine {
         SimEngine {
             pools: AllOrSomeLock::default(),
             key_handler: Arc::new(SimKeyActions::default()),
+            jobs: Arc::new(JobManager::default()),
             stopped_pools: Lockable::new_shared(Table::default()),
         }
     }
@@ -240,6 +244,51 @@ impl Engine for SimEngine {
         Ok(SetUnlockAction::empty())
     }
 
+    async fn clone_filesystem(
+        &self,
+        source_pool: PoolUuid,
+        fs_uuid: FilesystemUuid,
+        target_pool: PoolUuid,
+        new_name: &str,
+        _preserve_uuid: bool,
+    ) -> StratisResult<CreateAction<FilesystemUuid>> {
+        if source_pool == target_pool {
+            return Err(StratisError::Msg(
+                "The source and target pools of a filesystem clone must be different".to_string(),
+            ));
+        }
+
+        let (size, size_limit) = {
+            let guard = get_pool!(self; PoolIdentifier::Uuid(source_pool)).ok_or_else(|| {
+                StratisError::Msg(format!("No pool found with UUID {source_pool}"))
+            })?;
+            let (_, fs) = guard.get_filesystem(fs_uuid).ok_or_else(|| {
+                StratisError::Msg(format!("Filesystem with UUID {fs_uuid} could not be found"))
+            })?;
+            (fs.size(), fs.size_limit().map(|l| l.bytes()))
+        };
+
+        let mut guard = get_mut_pool!(self; PoolIdentifier::Uuid(target_pool))
+            .ok_or_else(|| StratisError::Msg(format!("No pool found with UUID {target_pool}")))?;
+        let (target_name, _, pool) = guard.as_mut_tuple();
+        if pool
+            .get_filesystem_by_name(&Name::new(new_name.to_owned()))
+            .is_some()
+        {
+            return Ok(CreateAction::Identity);
+        }
+        let created = pool.create_filesystems(
+            &target_name,
+            target_pool,
+            &[(new_name, Some(size), size_limit)],
+        )?;
+        Ok(created
+            .changed()
+            .and_then(|mut created| created.pop())
+            .map(|(_, uuid, _)| CreateAction::Created(uuid))
+            .unwrap_or(CreateAction::Identity))
+    }
+
     async fn get_pool(
         &self,
         key: PoolIdentifier<PoolUuid>,
@@ -310,6 +359,10 @@ impl Engine for SimEngine {
         HashMap::default()
     }
 
+    async fn backup_thin_metadata(&self) -> HashMap<PoolUuid, StratisResult<PathBuf>> {
+        HashMap::default()
+    }
+
     async fn get_key_handler(&self) -> Arc<dyn KeyActions> {
         Arc::clone(&self.key_handler) as Arc<dyn KeyActions>
     }
@@ -428,10 +481,53 @@ impl Engine for SimEngine {
         }
     }
 
+    async fn upgrade_pool(
+        &self,
+        pool_id: PoolIdentifier<PoolUuid>,
+        _passphrase_fd: Option<RawFd>,
+        _dry_run: bool,
+    ) -> StratisResult<UpgradeReport> {
+        let stopped_pools = self.stopped_pools.read().await;
+        let pool_uuid = match pool_id {
+            PoolIdentifier::Name(ref n) => stopped_pools.get_by_name(n).map(|(u, _)| u),
+            PoolIdentifier::Uuid(u) => stopped_pools.get_by_uuid(u).map(|_| u),
+        }
+        .ok_or_else(|| {
+            StratisError::Msg(format!(
+                "Pool {pool_id} was not found in stopped pools and cannot be upgraded"
+            ))
+        })?;
+        Err(StratisError::Msg(format!(
+            "Pool with UUID {pool_uuid} already uses the current metadata format"
+        )))
+    }
+
+    async fn thin_meta_spare(
+        &self,
+        pool_id: PoolIdentifier<PoolUuid>,
+        _action: ThinMetaSpareAction,
+    ) -> StratisResult<ThinMetaSpareInfo> {
+        let stopped_pools = self.stopped_pools.read().await;
+        let pool_uuid = match pool_id {
+            PoolIdentifier::Name(ref n) => stopped_pools.get_by_name(n).map(|(u, _)| u),
+            PoolIdentifier::Uuid(u) => stopped_pools.get_by_uuid(u).map(|_| u),
+        }
+        .ok_or_else(|| {
+            StratisError::Msg(format!("Pool {pool_id} was not found in stopped pools"))
+        })?;
+        Err(StratisError::Msg(format!(
+            "Pool with UUID {pool_uuid} has no thin metadata devices in the simulator"
+        )))
+    }
+
     async fn refresh_state(&self) -> StratisResult<()> {
         Ok(())
     }
 
+    fn jobs(&self) -> Arc<JobManager> {
+        Arc::clone(&self.jobs)
+    }
+
     fn is_sim(&self) -> bool {
         true
     }
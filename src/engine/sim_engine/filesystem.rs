@@ -26,6 +26,8 @@ pub struct FilesystemSave {
     #[serde(skip_serializing_if = "Option::is_none")]
     fs_size_limit: Option<Sectors>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    fs_reservation: Option<Sectors>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     origin: Option<FilesystemUuid>,
     #[serde(default)]
     merge: bool,
@@ -37,6 +39,7 @@ pub struct SimFilesystem {
     created: DateTime<Utc>,
     size: Sectors,
     size_limit: Option<Sectors>,
+    reservation: Option<Sectors>,
     origin: Option<FilesystemUuid>,
     merge_scheduled: bool,
 }
@@ -59,6 +62,7 @@ impl SimFilesystem {
             created: Utc::now(),
             size,
             size_limit,
+            reservation: None,
             origin,
             merge_scheduled: false,
         })
@@ -70,13 +74,16 @@ impl SimFilesystem {
 
     /// Set the size limit for the SimFilesystem.
     pub fn set_size_limit(&mut self, limit: Option<Sectors>) -> StratisResult<bool> {
-        match limit {
-            Some(lim) if self.size() > lim => Err(StratisError::Msg(format!(
+        match (limit, self.reservation) {
+            (Some(lim), _) if self.size() > lim => Err(StratisError::Msg(format!(
                 "Limit requested of {} is smaller than current filesystem size of {}",
                 lim,
                 self.size()
             ))),
-            Some(_) | None => {
+            (Some(lim), Some(res)) if res > lim => Err(StratisError::Msg(format!(
+                "Limit requested of {lim} is smaller than current filesystem reservation of {res}"
+            ))),
+            (Some(_), _) | (None, _) => {
                 if self.size_limit == limit {
                     Ok(false)
                 } else {
@@ -87,6 +94,23 @@ impl SimFilesystem {
         }
     }
 
+    /// Set the reservation for the SimFilesystem.
+    pub fn set_reservation(&mut self, reservation: Option<Sectors>) -> StratisResult<bool> {
+        match (reservation, self.size_limit) {
+            (Some(res), Some(lim)) if res > lim => Err(StratisError::Msg(format!(
+                "Reservation requested of {res} is larger than filesystem size limit of {lim}"
+            ))),
+            _ => {
+                if self.reservation == reservation {
+                    Ok(false)
+                } else {
+                    self.reservation = reservation;
+                    Ok(true)
+                }
+            }
+        }
+    }
+
     pub fn set_origin(&mut self, value: Option<FilesystemUuid>) -> bool {
         let changed = self.origin != value;
         self.origin = value;
@@ -100,6 +124,7 @@ impl SimFilesystem {
             size: self.size,
             created: self.created.timestamp() as u64,
             fs_size_limit: self.size_limit,
+            fs_reservation: self.reservation,
             origin: self.origin,
             merge: self.merge_scheduled,
         }
@@ -147,6 +172,10 @@ impl Filesystem for SimFilesystem {
         self.size_limit
     }
 
+    fn reservation(&self) -> Option<Sectors> {
+        self.reservation
+    }
+
     fn origin(&self) -> Option<FilesystemUuid> {
         self.origin
     }
@@ -177,6 +206,15 @@ impl Into<Value> for &SimFilesystem {
                     .unwrap_or_else(|| "Not set".to_string()),
             ),
         );
+        json.insert(
+            "reservation".to_string(),
+            Value::from(
+                self.reservation
+                    .as_ref()
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "Not set".to_string()),
+            ),
+        );
         json.insert(
             "origin".to_string(),
             Value::from(
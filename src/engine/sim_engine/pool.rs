@@ -3,6 +3,7 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::{
+    cmp::min,
     collections::{hash_map::RandomState, HashMap, HashSet},
     path::Path,
     vec::Vec,
@@ -18,23 +19,29 @@ use crate::{
     engine::{
         engine::{BlockDev, Filesystem, Pool},
         shared::{
-            init_cache_idempotent_or_err, validate_filesystem_size, validate_filesystem_size_specs,
-            validate_name, validate_paths,
+            init_cache_idempotent_or_err, validate_batch, validate_filesystem_reservation,
+            validate_filesystem_size, validate_filesystem_size_specs, validate_name,
+            validate_paths,
         },
         sim_engine::{blockdev::SimDev, filesystem::SimFilesystem},
         structures::Table,
         types::{
-            ActionAvailability, BlockDevTier, Clevis, CreateAction, DeleteAction, DevUuid,
-            EncryptionInfo, FilesystemUuid, GrowAction, Key, KeyDescription, Name,
-            OptionalTokenSlotInput, PoolDiff, PoolEncryptionInfo, PoolUuid, RegenAction,
-            RenameAction, SetCreateAction, SetDeleteAction, StratSigblockVersion, UnlockMechanism,
-            ValidatedIntegritySpec,
+            ActionAvailability, BatchChange, BatchOperation, BlockDevTier, Clevis,
+            CompactionReport, CreateAction, DeleteAction, DevUuid, DeviceLayout, EncryptionInfo,
+            EngineAction, FilesystemUuid, GrowAction, Key, KeyDescription, LayoutExtent, LayoutUse,
+            Name, OptionalTokenSlotInput, PoolDiff, PoolEncryptionInfo, PoolLayout, PoolUuid,
+            RegenAction, RenameAction, SetCreateAction, SetDeleteAction, StratSigblockVersion,
+            UnlockMechanism, ValidatedIntegritySpec,
         },
         PropChangeAction,
     },
     stratis::{StratisError, StratisResult},
 };
 
+/// The number of sectors at the start of each simulated block device which
+/// are shown as holding Stratis metadata in the pool's layout.
+const SIM_METADATA_SIZE: Sectors = Sectors(8192);
+
 #[derive(Debug)]
 pub struct SimPool {
     block_devs: HashMap<DevUuid, SimDev>,
@@ -114,6 +121,13 @@ impl SimPool {
         }
     }
 
+    fn batch_filesystem_uuid(&self, name: &str) -> StratisResult<FilesystemUuid> {
+        self.filesystems
+            .get_by_name(name)
+            .map(|(uuid, _)| uuid)
+            .ok_or_else(|| StratisError::Msg(format!("Filesystem {name} does not exist")))
+    }
+
     pub fn record(&self, name: &str) -> PoolSave {
         PoolSave {
             name: name.to_owned(),
@@ -257,6 +271,40 @@ impl Pool for SimPool {
         Ok(SetCreateAction::new(result))
     }
 
+    fn create_filesystem_with_uuid(
+        &mut self,
+        _pool_name: &str,
+        _pool_uuid: PoolUuid,
+        fs_uuid: FilesystemUuid,
+        name: &str,
+        size: Option<Bytes>,
+        size_limit: Option<Bytes>,
+    ) -> StratisResult<CreateAction<FilesystemUuid>> {
+        if let Some((existing_name, _)) = self.filesystems.get_by_uuid(fs_uuid) {
+            return if &*existing_name == name {
+                Ok(CreateAction::Identity)
+            } else {
+                Err(StratisError::Msg(format!(
+                    "UUID {fs_uuid} is already in use by filesystem {existing_name}"
+                )))
+            };
+        }
+
+        validate_name(name)?;
+        if self.filesystems.contains_name(name) {
+            return Err(StratisError::Msg(format!(
+                "A filesystem named {name} already exists"
+            )));
+        }
+        self.check_fs_limit(1)?;
+        let spec_map = validate_filesystem_size_specs(&[(name, size, size_limit)])?;
+        let (size, size_limit) = spec_map[&name];
+        let new_filesystem = SimFilesystem::new(size, size_limit, None)?;
+        self.filesystems
+            .insert(Name::new(name.to_owned()), fs_uuid, new_filesystem);
+        Ok(CreateAction::Created(fs_uuid))
+    }
+
     fn add_blockdevs(
         &mut self,
         _pool_uuid: PoolUuid,
@@ -870,6 +918,37 @@ impl Pool for SimPool {
         }
     }
 
+    fn set_fs_reservation(
+        &mut self,
+        fs_uuid: FilesystemUuid,
+        reservation: Option<Bytes>,
+    ) -> StratisResult<PropChangeAction<Option<Sectors>>> {
+        let total_physical_size = self.total_physical_size();
+        let others_reserved = self
+            .filesystems
+            .iter()
+            .filter(|(_, uuid, _)| **uuid != fs_uuid)
+            .filter_map(|(_, _, fs)| fs.reservation())
+            .sum::<Sectors>();
+        let (name, fs) = self.filesystems.get_mut_by_uuid(fs_uuid).ok_or_else(|| {
+            StratisError::Msg(format!("Filesystem with UUID {fs_uuid} not found"))
+        })?;
+        let reservation = validate_filesystem_reservation(&name, reservation)?;
+        if let Some(res) = reservation {
+            if others_reserved + res > total_physical_size {
+                return Err(StratisError::Msg(format!(
+                    "Reservation of {res} for filesystem {fs_uuid} together with existing reservations of {others_reserved} exceeds the size of the pool, {total_physical_size}"
+                )));
+            }
+        }
+        let changed = fs.set_reservation(reservation)?;
+        if changed {
+            Ok(PropChangeAction::NewValue(reservation))
+        } else {
+            Ok(PropChangeAction::Identity)
+        }
+    }
+
     fn current_metadata(&self, pool_name: &Name) -> StratisResult<String> {
         serde_json::to_string(&self.record(pool_name)).map_err(|e| e.into())
     }
@@ -976,6 +1055,205 @@ impl Pool for SimPool {
 
         Ok(PropChangeAction::NewValue(scheduled))
     }
+
+    fn apply_batch(
+        &mut self,
+        pool_name: &Name,
+        pool_uuid: PoolUuid,
+        ops: &[BatchOperation],
+    ) -> StratisResult<Vec<BatchChange>> {
+        type Undo = Box<dyn FnOnce(&mut SimPool) -> StratisResult<()>>;
+
+        validate_batch(self, ops)?;
+
+        let mut undo: Vec<Undo> = Vec::new();
+        let mut changes = Vec::new();
+        let res = ops.iter().try_for_each(|op| {
+            match op {
+                BatchOperation::CreateFilesystem {
+                    name,
+                    size,
+                    size_limit,
+                } => {
+                    let created = self.create_filesystems(
+                        pool_name,
+                        pool_uuid,
+                        &[(
+                            name.as_str(),
+                            size.map(Bytes::from),
+                            size_limit.map(Bytes::from),
+                        )],
+                    )?;
+                    for (name, uuid, _) in created.changed().unwrap_or_default() {
+                        let pool_name = pool_name.to_string();
+                        undo.push(Box::new(move |pool| {
+                            pool.destroy_filesystems(&pool_name, &HashSet::from([uuid]))
+                                .map(|_| ())
+                        }));
+                        changes.push(BatchChange::FilesystemCreated(
+                            Name::new(name.to_owned()),
+                            uuid,
+                        ));
+                    }
+                }
+                BatchOperation::RenameFilesystem { name, new_name } => {
+                    let uuid = self.batch_filesystem_uuid(name)?;
+                    if let RenameAction::Renamed(_) =
+                        self.rename_filesystem(pool_name, uuid, new_name)?
+                    {
+                        let (pool_name, name) = (pool_name.to_string(), name.to_owned());
+                        undo.push(Box::new(move |pool| {
+                            pool.rename_filesystem(&pool_name, uuid, &name).map(|_| ())
+                        }));
+                        changes.push(BatchChange::FilesystemRenamed(
+                            uuid,
+                            Name::new(new_name.to_owned()),
+                        ));
+                    }
+                }
+                BatchOperation::SetFsSizeLimit { name, limit } => {
+                    let uuid = self.batch_filesystem_uuid(name)?;
+                    let old = self
+                        .filesystems
+                        .get_by_uuid(uuid)
+                        .and_then(|(_, fs)| fs.size_limit());
+                    if let PropChangeAction::NewValue(new) =
+                        self.set_fs_size_limit(uuid, limit.map(Bytes::from))?
+                    {
+                        undo.push(Box::new(move |pool| {
+                            pool.set_fs_size_limit(uuid, old.map(|l| l.bytes()))
+                                .map(|_| ())
+                        }));
+                        changes.push(BatchChange::FsSizeLimitChanged(uuid, new));
+                    }
+                }
+                BatchOperation::SetFsLimit { limit } => {
+                    let old = self.fs_limit;
+                    if *limit != old {
+                        self.set_fs_limit(pool_name, pool_uuid, *limit)?;
+                        undo.push(Box::new(move |pool| {
+                            pool.fs_limit = old;
+                            Ok(())
+                        }));
+                        changes.push(BatchChange::FsLimitChanged(*limit));
+                    }
+                }
+                BatchOperation::SetOverprovMode { enabled } => {
+                    let old = self.enable_overprov;
+                    if *enabled != old {
+                        self.set_overprov_mode(pool_name, *enabled)?;
+                        undo.push(Box::new(move |pool| {
+                            pool.enable_overprov = old;
+                            Ok(())
+                        }));
+                        changes.push(BatchChange::OverprovModeChanged(*enabled));
+                    }
+                }
+                BatchOperation::SetBlockdevUserInfo { uuid, user_info } => {
+                    let uuid = *uuid;
+                    let old = self
+                        .get_blockdev(uuid)
+                        .and_then(|(_, bd)| bd.user_info().map(str::to_owned));
+                    if let RenameAction::Renamed(_) =
+                        self.set_blockdev_user_info(pool_name, uuid, user_info.as_deref())?
+                    {
+                        let pool_name = pool_name.to_string();
+                        undo.push(Box::new(move |pool| {
+                            pool.set_blockdev_user_info(&pool_name, uuid, old.as_deref())
+                                .map(|_| ())
+                        }));
+                        changes.push(BatchChange::BlockdevUserInfoChanged(
+                            uuid,
+                            user_info.clone(),
+                        ));
+                    }
+                }
+                BatchOperation::BindKeyring {
+                    token_slot,
+                    key_desc,
+                } => {
+                    let token_slot = token_slot
+                        .map_or(OptionalTokenSlotInput::None, OptionalTokenSlotInput::Some);
+                    if let CreateAction::Created((_, slot)) =
+                        self.bind_keyring(token_slot, key_desc)?
+                    {
+                        undo.push(Box::new(move |pool| {
+                            pool.unbind_keyring(Some(slot)).map(|_| ())
+                        }));
+                        changes.push(BatchChange::KeyringBound(slot, key_desc.clone()));
+                    }
+                }
+            }
+            Ok(())
+        });
+
+        match res {
+            Ok(()) => Ok(changes),
+            Err(causal_error) => {
+                let rollback = undo
+                    .into_iter()
+                    .rev()
+                    .map(|f| f(self))
+                    .fold(Ok(()), |acc, res| acc.and(res));
+                match rollback {
+                    Ok(()) => Err(causal_error),
+                    Err(rollback_error) => Err(StratisError::RollbackError {
+                        causal_error: Box::new(causal_error),
+                        rollback_error: Box::new(rollback_error),
+                        level: ActionAvailability::NoRequests,
+                    }),
+                }
+            }
+        }
+    }
+
+    fn layout(&self, pool_name: &Name) -> StratisResult<PoolLayout> {
+        let mut devices = self
+            .blockdevs()
+            .into_iter()
+            .map(|(uuid, tier, bd)| {
+                let size = bd.size();
+                let metadata = min(SIM_METADATA_SIZE, size);
+                let mut extents = vec![LayoutExtent {
+                    start: Sectors(0),
+                    length: metadata,
+                    usage: LayoutUse::StratisMetadata,
+                }];
+                if metadata < size {
+                    extents.push(LayoutExtent {
+                        start: metadata,
+                        length: size - metadata,
+                        usage: LayoutUse::Unused,
+                    });
+                }
+                DeviceLayout {
+                    uuid,
+                    tier,
+                    size: Some(size),
+                    extents,
+                }
+            })
+            .collect::<Vec<_>>();
+        devices.sort_by_key(|device| (device.tier == BlockDevTier::Cache, device.uuid.to_string()));
+        Ok(PoolLayout {
+            name: pool_name.to_string(),
+            encrypted: self.is_encrypted(),
+            devices,
+        })
+    }
+
+    fn compact(
+        &mut self,
+        _pool_name: &Name,
+        _pool_uuid: PoolUuid,
+    ) -> StratisResult<CompactionReport> {
+        Ok(CompactionReport {
+            segments_before: 0,
+            segments_after: 0,
+            moved: Sectors(0),
+            data_extension: Sectors(0),
+        })
+    }
 }
 
 #[cfg(test)]
@@ -1288,4 +1566,161 @@ mod tests {
             _ => false,
         });
     }
+
+    #[test]
+    /// All operations of a batch are applied and reported.
+    fn apply_batch_succeeds() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            None,
+            IntegritySpec::default(),
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let changes = pool
+            .apply_batch(
+                &Name::new(pool_name.to_string()),
+                uuid,
+                &[
+                    BatchOperation::CreateFilesystem {
+                        name: "fs1".to_string(),
+                        size: None,
+                        size_limit: None,
+                    },
+                    BatchOperation::RenameFilesystem {
+                        name: "fs1".to_string(),
+                        new_name: "fs2".to_string(),
+                    },
+                    BatchOperation::SetFsLimit { limit: 200 },
+                    BatchOperation::SetOverprovMode { enabled: true },
+                ],
+            )
+            .unwrap();
+        assert_eq!(changes.len(), 3);
+        assert!(pool
+            .get_filesystem_by_name(&Name::new("fs2".to_string()))
+            .is_some());
+        assert_eq!(pool.fs_limit(), 200);
+    }
+
+    #[test]
+    /// If an operation of a batch fails, the operations before it are undone.
+    fn apply_batch_rolls_back() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            None,
+            IntegritySpec::default(),
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        pool.create_filesystems(pool_name, uuid, &[("fs1", None, None)])
+            .unwrap();
+        let fs_limit = pool.fs_limit();
+        assert_matches!(
+            pool.apply_batch(
+                &Name::new(pool_name.to_string()),
+                uuid,
+                &[
+                    BatchOperation::SetFsLimit {
+                        limit: fs_limit + 1
+                    },
+                    BatchOperation::RenameFilesystem {
+                        name: "fs1".to_string(),
+                        new_name: "fs2".to_string(),
+                    },
+                    BatchOperation::CreateFilesystem {
+                        name: "fs3".to_string(),
+                        size: None,
+                        size_limit: None,
+                    },
+                    // Smaller than the filesystem, so fails when applied
+                    BatchOperation::SetFsSizeLimit {
+                        name: "fs2".to_string(),
+                        limit: Some(IEC::Gi),
+                    },
+                ],
+            ),
+            Err(_)
+        );
+        assert_eq!(pool.fs_limit(), fs_limit);
+        assert!(pool
+            .get_filesystem_by_name(&Name::new("fs1".to_string()))
+            .is_some());
+        assert!(pool
+            .get_filesystem_by_name(&Name::new("fs2".to_string()))
+            .is_none());
+        assert!(pool
+            .get_filesystem_by_name(&Name::new("fs3".to_string()))
+            .is_none());
+    }
+
+    #[test]
+    /// A batch which is invalid is rejected before any operation is applied.
+    fn apply_batch_invalid() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            None,
+            IntegritySpec::default(),
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        assert_matches!(
+            pool.apply_batch(
+                &Name::new(pool_name.to_string()),
+                uuid,
+                &[
+                    BatchOperation::CreateFilesystem {
+                        name: "fs1".to_string(),
+                        size: None,
+                        size_limit: None,
+                    },
+                    BatchOperation::RenameFilesystem {
+                        name: "fs2".to_string(),
+                        new_name: "fs3".to_string(),
+                    },
+                ],
+            ),
+            Err(_)
+        );
+        assert!(pool.filesystems().is_empty());
+    }
+
+    #[test]
+    /// The layout of a sim pool describes every block device from its first
+    /// sector to its last.
+    fn layout_covers_devices() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            None,
+            IntegritySpec::default(),
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let pool = test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let layout = pool.layout(&Name::new(pool_name.to_string())).unwrap();
+        assert_eq!(layout.devices.len(), 3);
+        for device in layout.devices.iter() {
+            assert_eq!(Some(device.length()), device.size);
+            assert_eq!(device.total(LayoutUse::StratisMetadata), SIM_METADATA_SIZE);
+        }
+    }
 }
@@ -27,6 +27,10 @@ pub trait InternalBackstore {
     /// added to cap.
     fn available_in_backstore(&self) -> Sectors;
 
+    /// The location on the cap device which follows all the space allocated
+    /// from it.
+    fn allocated_end(&self) -> Sectors;
+
     /// Satisfy a request for multiple segments. This request must
     /// always be satisfied exactly, None is returned if this can not
     /// be done.
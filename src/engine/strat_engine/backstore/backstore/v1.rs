@@ -10,7 +10,7 @@ use chrono::{DateTime, Utc};
 use serde_json::Value;
 use tempfile::TempDir;
 
-use devicemapper::{CacheDev, Device, DmDevice, LinearDev, Sectors};
+use devicemapper::{CacheDev, CacheDevStatus, Device, DmDevice, DmOptions, LinearDev, Sectors};
 
 use crate::{
     engine::{
@@ -35,8 +35,8 @@ use crate::{
             writing::wipe_sectors,
         },
         types::{
-            ActionAvailability, BlockDevTier, DevUuid, EncryptionInfo, InputEncryptionInfo,
-            KeyDescription, Name, PoolEncryptionInfo, PoolUuid,
+            ActionAvailability, BlockDevTier, CacheStats, DevUuid, EncryptionInfo,
+            InputEncryptionInfo, KeyDescription, Name, PoolEncryptionInfo, PoolUuid,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -129,6 +129,10 @@ impl InternalBackstore for Backstore {
         self.data_tier.usable_size() - self.next
     }
 
+    fn allocated_end(&self) -> Sectors {
+        self.next
+    }
+
     fn alloc(
         &mut self,
         pool_uuid: PoolUuid,
@@ -530,6 +534,22 @@ impl Backstore {
             .unwrap_or(Sectors(0))
     }
 
+    /// Query devicemapper for the current statistics of the cache device.
+    /// Returns None if the backstore has no cache or if the cache is not
+    /// in a working state.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache
+            .as_ref()
+            .and_then(|cache| match cache.status(get_dm(), DmOptions::default()) {
+                Ok(CacheDevStatus::Working(status)) => Some(CacheStats::from(&*status)),
+                Ok(_) => None,
+                Err(err) => {
+                    warn!("Failed to get status of cache device: {}", err);
+                    None
+                }
+            })
+    }
+
     /// Destroy the entire store.
     pub fn destroy(&mut self, pool_uuid: PoolUuid) -> StratisResult<()> {
         let devs = list_of_backstore_devices(pool_uuid);
@@ -609,7 +629,7 @@ impl Backstore {
 
     /// Write the given data to the data tier's devices.
     pub fn save_state(&mut self, metadata: &[u8]) -> StratisResult<()> {
-        self.data_tier.save_state(metadata)
+        self.data_tier.save_state(metadata, true)
     }
 
     /// Read the currently saved state from the data tier's devices.
@@ -11,8 +11,9 @@ use either::Either;
 use serde_json::Value;
 
 use devicemapper::{
-    CacheDev, CacheDevTargetTable, CacheTargetParams, DevId, Device, DmDevice, DmFlags, DmOptions,
-    LinearDev, LinearDevTargetParams, LinearTargetParams, Sectors, TargetLine, TargetTable,
+    CacheDev, CacheDevStatus, CacheDevTargetTable, CacheTargetParams, DevId, Device, DmDevice,
+    DmFlags, DmOptions, LinearDev, LinearDevTargetParams, LinearTargetParams, Sectors, TargetLine,
+    TargetTable,
 };
 
 use crate::{
@@ -33,9 +34,9 @@ use crate::{
             writing::wipe_sectors,
         },
         types::{
-            ActionAvailability, BlockDevTier, DevUuid, EncryptionInfo, InputEncryptionInfo,
-            KeyDescription, OptionalTokenSlotInput, PoolUuid, SizedKeyMemory, TokenUnlockMethod,
-            UnlockMechanism, ValidatedIntegritySpec,
+            ActionAvailability, BlockDevTier, CacheStats, DevUuid, EncryptionInfo,
+            InputEncryptionInfo, KeyDescription, OptionalTokenSlotInput, PoolUuid, SizedKeyMemory,
+            TokenUnlockMethod, UnlockMechanism, ValidatedIntegritySpec,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -178,6 +179,10 @@ impl InternalBackstore for Backstore {
         self.datatier_usable_size() - self.datatier_allocated_size()
     }
 
+    fn allocated_end(&self) -> Sectors {
+        self.calc_next_cap()
+    }
+
     fn alloc(
         &mut self,
         pool_uuid: PoolUuid,
@@ -860,6 +865,22 @@ impl Backstore {
             .unwrap_or(Sectors(0))
     }
 
+    /// Query devicemapper for the current statistics of the cache device.
+    /// Returns None if the backstore has no cache or if the cache is not
+    /// in a working state.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache
+            .as_ref()
+            .and_then(|cache| match cache.status(get_dm(), DmOptions::default()) {
+                Ok(CacheDevStatus::Working(status)) => Some(CacheStats::from(&*status)),
+                Ok(_) => None,
+                Err(err) => {
+                    warn!("Failed to get status of cache device: {}", err);
+                    None
+                }
+            })
+    }
+
     /// Destroy the entire store.
     pub fn destroy(&mut self, pool_uuid: PoolUuid) -> StratisResult<()> {
         if let Some(h) = self.enc.as_mut().and_then(|either| either.as_ref().right()) {
@@ -950,9 +971,10 @@ impl Backstore {
         self.datatier_crypt_meta_size() + self.data_tier.metadata_size()
     }
 
-    /// Write the given data to the data tier's devices.
-    pub fn save_state(&mut self, metadata: &[u8]) -> StratisResult<()> {
-        self.data_tier.save_state(metadata)
+    /// Write the given data to the data tier's devices, recording it in the
+    /// metadata history if record_history is true.
+    pub fn save_state(&mut self, metadata: &[u8], record_history: bool) -> StratisResult<()> {
+        self.data_tier.save_state(metadata, record_history)
     }
 
     /// Read the currently saved state from the data tier's devices.
@@ -996,6 +1018,21 @@ impl Backstore {
         self.cache_tier.is_some()
     }
 
+    /// The device node of the cap device, on which space is allocated to the
+    /// upper layer. Corresponds to InternalBackstore::device().
+    pub fn devnode(&self) -> Option<PathBuf> {
+        self.enc
+            .as_ref()
+            .and_then(|either| {
+                either
+                    .as_ref()
+                    .right()
+                    .map(|h| h.activated_device_path().to_owned())
+            })
+            .or_else(|| self.cache.as_ref().map(|c| c.devnode()))
+            .or_else(|| self.placeholder.as_ref().map(|lin| lin.devnode()))
+    }
+
     /// Get the encryption information for the backstore.
     pub fn encryption_info(&self) -> Option<&EncryptionInfo> {
         self.enc
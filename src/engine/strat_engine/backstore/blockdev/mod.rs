@@ -98,8 +98,14 @@ pub trait InternalBlockDev {
     /// Load the pool-level metadata for the given block device.
     fn load_state(&self) -> StratisResult<Option<(Vec<u8>, &DateTime<Utc>)>>;
 
-    /// Save the current metadata state to block device.
-    fn save_state(&mut self, time: &DateTime<Utc>, metadata: &[u8]) -> StratisResult<()>;
+    /// Save the current metadata state to block device, recording it in the
+    /// metadata history if record_history is true.
+    fn save_state(
+        &mut self,
+        time: &DateTime<Utc>,
+        metadata: &[u8],
+        record_history: bool,
+    ) -> StratisResult<()>;
 
     /// If a pool is encrypted, tear down the cryptsetup devicemapper devices on the
     /// physical device.
@@ -340,12 +340,22 @@ impl InternalBlockDev for StratBlockDev {
         }
     }
 
-    fn save_state(&mut self, time: &DateTime<Utc>, metadata: &[u8]) -> StratisResult<()> {
+    fn save_state(
+        &mut self,
+        time: &DateTime<Utc>,
+        metadata: &[u8],
+        record_history: bool,
+    ) -> StratisResult<()> {
         let mut f = OpenOptions::new()
             .read(true)
             .write(true)
             .open(&*self.devnode)?;
-        self.bda.save_state(time, metadata, &mut f)?;
+        if record_history {
+            self.bda.save_state(time, metadata, &mut f)?;
+        } else {
+            self.bda
+                .save_state_without_history(time, metadata, &mut f)?;
+        }
 
         f.rewind()?;
         let header = static_header(&mut f)?.ok_or_else(|| {
@@ -412,8 +412,9 @@ where
     /// metadata. If current time is not more recent than previously written
     /// time, use a time that is one nanosecond greater than that previously
     /// written. Randomly select no more than MAX_NUM_TO_WRITE blockdevs to
-    /// write to.
-    pub fn save_state(&mut self, metadata: &[u8]) -> StratisResult<()> {
+    /// write to. Record the data in the metadata history of the blockdevs
+    /// if record_history is true.
+    pub fn save_state(&mut self, metadata: &[u8], record_history: bool) -> StratisResult<()> {
         let stamp_time = self.last_update_time.next();
 
         let data_size = Bytes::from(metadata.len());
@@ -433,7 +434,7 @@ where
             .choose_multiple(&mut rng(), MAX_NUM_TO_WRITE)
             .iter_mut()
             .fold(false, |acc, b| {
-                acc | b.save_state(&stamp_time, metadata).is_ok()
+                acc | b.save_state(&stamp_time, metadata, record_history).is_ok()
             });
 
         if saved {
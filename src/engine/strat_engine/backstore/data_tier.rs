@@ -275,8 +275,8 @@ where
 
     /// Save the given state to the devices. This action bypasses the DM
     /// device entirely.
-    pub fn save_state(&mut self, metadata: &[u8]) -> StratisResult<()> {
-        self.block_mgr.save_state(metadata)
+    pub fn save_state(&mut self, metadata: &[u8], record_history: bool) -> StratisResult<()> {
+        self.block_mgr.save_state(metadata, record_history)
     }
 
     pub fn load_state(&self) -> StratisResult<Vec<u8>> {
@@ -22,6 +22,7 @@ use libblkid_rs::{BlkidCache, BlkidProbe};
 
 use crate::{
     engine::{
+        jobs::Job,
         strat_engine::{
             backstore::blockdev::{
                 v1::{self, UnderlyingDevice},
@@ -732,17 +733,29 @@ pub fn initialize_devices_legacy(
         encryption_info: Option<&InputEncryptionInfo>,
         sector_size: Option<u32>,
     ) -> StratisResult<Vec<v1::StratBlockDev>> {
+        // Each device initialized is reported as progress of the job which
+        // initializes them, if any. If the job is cancelled, the devices
+        // initialized so far are wiped, as on failure.
+        let job = Job::current();
+        let total = devices.inner.len() as u64;
         let mut initialized_blockdevs: Vec<v1::StratBlockDev> = Vec::new();
         for dev_info in devices.inner {
-            match initialize_one(
-                &dev_info,
-                pool_name.clone(),
-                pool_uuid,
-                mda_data_size,
-                encryption_info,
-                sector_size,
-            ) {
-                Ok(blockdev) => initialized_blockdevs.push(blockdev),
+            match Job::checkpoint().and_then(|()| {
+                initialize_one(
+                    &dev_info,
+                    pool_name.clone(),
+                    pool_uuid,
+                    mda_data_size,
+                    encryption_info,
+                    sector_size,
+                )
+            }) {
+                Ok(blockdev) => {
+                    initialized_blockdevs.push(blockdev);
+                    if let Some(ref job) = job {
+                        job.set_done(initialized_blockdevs.len() as u64, total);
+                    }
+                }
                 Err(err) => {
                     if let Err(err) = wipe_blockdevs(&mut initialized_blockdevs) {
                         warn!("Failed to clean up some devices after initialization of device {} for pool with UUID {} failed: {}",
@@ -906,10 +919,22 @@ pub fn initialize_devices(
         pool_uuid: PoolUuid,
         mda_data_size: MDADataSize,
     ) -> StratisResult<Vec<v2::StratBlockDev>> {
+        // Each device initialized is reported as progress of the job which
+        // initializes them, if any. If the job is cancelled, the devices
+        // initialized so far are wiped, as on failure.
+        let job = Job::current();
+        let total = devices.inner.len() as u64;
         let mut initialized_blockdevs: Vec<v2::StratBlockDev> = Vec::new();
         for dev_info in devices.inner {
-            match initialize_one(&dev_info, pool_uuid, mda_data_size) {
-                Ok(blockdev) => initialized_blockdevs.push(blockdev),
+            match Job::checkpoint()
+                .and_then(|()| initialize_one(&dev_info, pool_uuid, mda_data_size))
+            {
+                Ok(blockdev) => {
+                    initialized_blockdevs.push(blockdev);
+                    if let Some(ref job) = job {
+                        job.set_done(initialized_blockdevs.len() as u64, total);
+                    }
+                }
                 Err(err) => {
                     if let Err(err) = wipe_blockdevs(&mut initialized_blockdevs) {
                         warn!("Failed to clean up some devices after initialization of device {} for pool with UUID {} failed: {}",
@@ -15,6 +15,7 @@ mod shared;
 pub use self::{
     blockdev::v2::integrity_meta_space,
     devices::{find_stratis_devs_by_uuid, get_devno_from_path, ProcessedPathInfos, UnownedDevices},
+    range_alloc::RangeAllocator,
 };
 
 #[cfg(test)]
@@ -29,7 +29,7 @@ use once_cell::sync::Lazy;
 use semver::{Version, VersionReq};
 use serde_json::Value;
 
-use devicemapper::{MetaBlocks, Sectors};
+use devicemapper::{MetaBlocks, Sectors, ThinDevId};
 
 use crate::{
     engine::{
@@ -67,6 +67,8 @@ fn find_executable(name: &str) -> Option<PathBuf> {
 const MKFS_XFS: &str = "mkfs.xfs";
 const THIN_CHECK: &str = "thin_check";
 const THIN_REPAIR: &str = "thin_repair";
+const THIN_DUMP: &str = "thin_dump";
+const THIN_RESTORE: &str = "thin_restore";
 #[cfg(test)]
 const UDEVADM: &str = "udevadm";
 const THIN_METADATA_SIZE: &str = "thin_metadata_size";
@@ -124,6 +126,8 @@ static EXECUTABLES: Lazy<HashMap<String, Option<PathBuf>>> = Lazy::new(|| {
         (MKFS_XFS.to_string(), find_executable(MKFS_XFS)),
         (THIN_CHECK.to_string(), find_executable(THIN_CHECK)),
         (THIN_REPAIR.to_string(), find_executable(THIN_REPAIR)),
+        (THIN_DUMP.to_string(), find_executable(THIN_DUMP)),
+        (THIN_RESTORE.to_string(), find_executable(THIN_RESTORE)),
         #[cfg(test)]
         (UDEVADM.to_string(), find_executable(UDEVADM)),
         (XFS_DB.to_string(), find_executable(XFS_DB)),
@@ -342,6 +346,25 @@ pub fn set_uuid(devnode: &Path, uuid: FilesystemUuid) -> StratisResult<()> {
     )
 }
 
+/// Get the UUID of the XFS filesystem on the devnode.
+pub fn get_uuid(devnode: &Path) -> StratisResult<FilesystemUuid> {
+    let mut cmd = Command::new(get_executable(XFS_DB).as_os_str());
+    cmd.arg("-r").arg("-c").arg("uuid").arg(devnode);
+    let output = cmd.output()?;
+    handle_output(&mut cmd, output.clone())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .trim()
+        .strip_prefix("UUID = ")
+        .ok_or_else(|| {
+            StratisError::Msg(format!(
+                "Could not parse UUID from xfs_db output \"{}\"",
+                stdout.trim()
+            ))
+        })
+        .and_then(FilesystemUuid::parse_str)
+}
+
 /// Call thin_check on a thinpool
 pub fn thin_check(devnode: &Path) -> StratisResult<()> {
     execute_cmd(Command::new(get_executable(THIN_CHECK).as_os_str()).arg(devnode))
@@ -358,6 +381,55 @@ pub fn thin_repair(meta_dev: &Path, new_meta_dev: &Path) -> StratisResult<()> {
     )
 }
 
+/// Call thin_restore to write the XML description of a thinpool's metadata
+/// in the file at input to the metadata device new_meta_dev.
+pub fn thin_restore(input: &Path, new_meta_dev: &Path) -> StratisResult<()> {
+    execute_cmd(
+        Command::new(get_executable(THIN_RESTORE).as_os_str())
+            .arg("-i")
+            .arg(input)
+            .arg("-o")
+            .arg(new_meta_dev),
+    )
+}
+
+/// Call thin_dump on the metadata snapshot of a live thinpool and return the
+/// XML description of the mappings of the thin device with the given id, or
+/// of all thin devices if no id is given.
+/// The caller is responsible for reserving and releasing the metadata
+/// snapshot.
+pub fn thin_dump_metadata_snap(
+    meta_dev: &Path,
+    dev_id: Option<ThinDevId>,
+) -> StratisResult<String> {
+    let mut cmd = Command::new(get_executable(THIN_DUMP).as_os_str());
+    cmd.arg("--metadata-snap");
+    if let Some(dev_id) = dev_id {
+        cmd.arg("--dev-id").arg(dev_id.to_string());
+    }
+    cmd.arg(meta_dev);
+    let output = cmd.output()?;
+    handle_output(&mut cmd, output.clone())?;
+    String::from_utf8(output.stdout)
+        .map_err(|_| StratisError::Msg("thin_dump output is not valid UTF-8".to_string()))
+}
+
+/// Call thin_dump on the metadata device of a thinpool which is not active
+/// and return the XML description of its thin devices. If skip_mappings is
+/// true, the mappings of the thin devices are omitted.
+#[cfg(feature = "extras")]
+pub fn thin_dump(meta_dev: &Path, skip_mappings: bool) -> StratisResult<String> {
+    let mut cmd = Command::new(get_executable(THIN_DUMP).as_os_str());
+    if skip_mappings {
+        cmd.arg("--skip-mappings");
+    }
+    cmd.arg(meta_dev);
+    let output = cmd.output()?;
+    handle_output(&mut cmd, output.clone())?;
+    String::from_utf8(output.stdout)
+        .map_err(|_| StratisError::Msg("thin_dump output is not valid UTF-8".to_string()))
+}
+
 /// Call udevadm settle
 #[cfg(test)]
 pub fn udev_settle() -> StratisResult<()> {
@@ -4,6 +4,7 @@
 
 use std::{
     fmt::{self, Debug},
+    fs::{self, File},
     path::{Path, PathBuf},
 };
 
@@ -20,8 +21,8 @@ use devicemapper::{Bytes, Device, DmName, DmNameBuf, Sectors};
 use libcryptsetup_rs::{
     c_uint,
     consts::{
-        flags::{CryptActivate, CryptVolumeKey},
-        vals::{EncryptionFormat, KeyslotsSize, MetadataSize},
+        flags::{CryptActivate, CryptReencrypt, CryptVolumeKey},
+        vals::{CryptReencryptModeInfo, EncryptionFormat, KeyslotsSize, MetadataSize},
     },
     CryptDevice, CryptInit, CryptParamsLuks2, CryptParamsLuks2Ref, SafeMemHandle, TokenInput,
 };
@@ -45,7 +46,7 @@ use crate::{
                     check_luks2_token, clevis_decrypt, device_from_physical_path,
                     encryption_info_from_metadata, ensure_inactive, ensure_wiped,
                     get_keyslot_number, interpret_clevis_config, luks2_token_type_is_valid,
-                    read_key, wipe_fallback,
+                    read_key, reencrypt_params, reencryption_in_progress, wipe_fallback,
                 },
             },
             dm::DEVICEMAPPER_PATH,
@@ -429,6 +430,184 @@ fn setup_crypt_handle(
     )))
 }
 
+/// The unlock mechanisms of a legacy encrypted device together with the
+/// secrets required to bind them to new LUKS2 metadata.
+pub struct UnlockSecrets {
+    /// The token slot and key description of the keyring binding, with the
+    /// key.
+    pub key_description: Option<(u32, KeyDescription, SizedKeyMemory)>,
+    /// The token slot and token of the Clevis binding, with the passphrase
+    /// which the token protects.
+    pub clevis: Option<(u32, Value, SizedKeyMemory)>,
+}
+
+impl UnlockSecrets {
+    /// A passphrase which unlocks the device.
+    pub fn passphrase(&self) -> &SizedKeyMemory {
+        self.key_description
+            .as_ref()
+            .map(|(_, _, key)| key)
+            .or_else(|| self.clevis.as_ref().map(|(_, _, pass)| pass))
+            .expect("unlock_secrets() requires at least one unlock mechanism")
+    }
+}
+
+/// Read the unlock mechanisms of a legacy encrypted device and the secrets
+/// which they protect. If passphrase is specified, it is used as the key for
+/// the keyring binding instead of the key in the kernel keyring.
+pub fn unlock_secrets(
+    physical_path: &Path,
+    passphrase: Option<&SizedKeyMemory>,
+) -> StratisResult<UnlockSecrets> {
+    let mut device = acquire_crypt_device(physical_path)?;
+    let encryption_info = encryption_info_from_metadata(&mut device)?;
+
+    let key_description = match encryption_info.single_key_description() {
+        Some((token_slot, key_desc)) => {
+            let key = match passphrase {
+                Some(pass) => {
+                    let mut mem = SafeMemHandle::alloc(pass.as_ref().len())?;
+                    mem.as_mut().copy_from_slice(pass.as_ref());
+                    SizedKeyMemory::new(mem, pass.as_ref().len())
+                }
+                None => read_key(key_desc)?.ok_or_else(|| {
+                    StratisError::Msg(format!(
+                        "The key with key description \"{}\" is not set in the kernel keyring",
+                        key_desc.as_application_str()
+                    ))
+                })?,
+            };
+            Some((token_slot, key_desc.to_owned(), key))
+        }
+        None => None,
+    };
+    let clevis = match encryption_info.single_clevis_info() {
+        Some((token_slot, _)) => {
+            let token = device.token_handle().json_get(token_slot)?;
+            let pass = clevis_decrypt(&mut device, token_slot)?.ok_or_else(|| {
+                StratisError::Msg(format!(
+                    "Could not decrypt the Clevis passphrase of device {}",
+                    physical_path.display()
+                ))
+            })?;
+            Some((token_slot, token, pass))
+        }
+        None => None,
+    };
+
+    if key_description.is_none() && clevis.is_none() {
+        return Err(StratisError::Msg(format!(
+            "Device {} has no unlock mechanisms",
+            physical_path.display()
+        )));
+    }
+
+    Ok(UnlockSecrets {
+        key_description,
+        clevis,
+    })
+}
+
+/// Whether every data segment of the LUKS2 metadata is unencrypted.
+fn is_decrypted(device: &mut CryptDevice) -> StratisResult<bool> {
+    let json = device.status_handle().dump_json()?;
+    Ok(json
+        .get("segments")
+        .and_then(|segments| segments.as_object())
+        .map(|segments| {
+            segments.values().all(|segment| {
+                segment.get(TOKEN_TYPE_KEY).and_then(|t| t.as_str()) == Some("linear")
+            })
+        })
+        .unwrap_or(false))
+}
+
+/// Check that the copy of the LUKS2 header of a device at header_path can be
+/// loaded and belongs to the device.
+fn check_header_backup(device: &mut CryptDevice, header_path: &Path) -> StratisResult<()> {
+    let mut backup = CryptInit::init(header_path)?;
+    backup
+        .context_handle()
+        .load::<()>(Some(EncryptionFormat::Luks2), None)?;
+    let expected = device.status_handle().get_uuid()?;
+    let found = backup.status_handle().get_uuid()?;
+    if expected != found {
+        return Err(StratisError::Msg(format!(
+            "The copy of the LUKS2 header at {} has UUID {found}, but the header of the device \
+            has UUID {expected}",
+            header_path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Decrypt a legacy encrypted device in place, leaving the data at the offset
+/// at which it was encrypted.
+///
+/// The LUKS2 header is first copied to header_path and the progress of the
+/// decryption is recorded in the copy. The copy is synced and checked before
+/// it is moved to header_path, so that header_path only ever holds a
+/// complete copy. The header on the device is left intact so that it
+/// continues to identify the device, and an interrupted decryption is resumed
+/// by calling this method again with the same header path.
+pub fn decrypt_in_place(
+    physical_path: &Path,
+    header_path: &Path,
+    passphrase: &SizedKeyMemory,
+) -> StratisResult<()> {
+    if !header_path.exists() {
+        let tmp_path = header_path.with_extension("tmp");
+        if tmp_path.exists() {
+            fs::remove_file(&tmp_path)?;
+        }
+        let mut device = acquire_crypt_device(physical_path)?;
+        device
+            .backup_handle()
+            .header_backup(Some(EncryptionFormat::Luks2), &tmp_path)?;
+        File::open(&tmp_path)?.sync_all()?;
+        check_header_backup(&mut device, &tmp_path)?;
+        fs::rename(&tmp_path, header_path)?;
+        if let Some(dir) = header_path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+    }
+
+    let mut device = CryptInit::init_with_data_device(libcryptsetup_rs::Either::Right((
+        header_path,
+        physical_path,
+    )))?;
+    device
+        .context_handle()
+        .load::<()>(Some(EncryptionFormat::Luks2), None)?;
+    if is_decrypted(&mut device)? {
+        return Ok(());
+    }
+
+    let flags = if reencryption_in_progress(&mut device)? {
+        CryptReencrypt::RESUME_ONLY
+    } else {
+        CryptReencrypt::empty()
+    };
+    log_on_failure!(
+        device.reencrypt_handle().reencrypt_init_by_passphrase(
+            None,
+            passphrase.as_ref(),
+            None,
+            None,
+            None,
+            reencrypt_params(CryptReencryptModeInfo::Decrypt, flags),
+        ),
+        "Failed to initialize decryption of device {}",
+        physical_path.display()
+    );
+    log_on_failure!(
+        device.reencrypt_handle().reencrypt(None),
+        "Failed to decrypt device {}",
+        physical_path.display()
+    );
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct CryptMetadata {
     pub physical_path: DevicePath,
@@ -17,8 +17,8 @@ use devicemapper::{Device, DmName, DmNameBuf, Sectors};
 use libcryptsetup_rs::{
     c_uint,
     consts::{
-        flags::{CryptActivate, CryptVolumeKey},
-        vals::{EncryptionFormat, KeyslotsSize, MetadataSize},
+        flags::{CryptActivate, CryptReencrypt, CryptVolumeKey},
+        vals::{CryptReencryptModeInfo, EncryptionFormat, KeyslotsSize, MetadataSize},
     },
     CryptDevice, CryptInit, CryptParamsLuks2, CryptParamsLuks2Ref, SafeMemHandle, TokenInput,
 };
@@ -34,13 +34,15 @@ use crate::{
             crypt::{
                 consts::{
                     DEFAULT_CRYPT_DATA_OFFSET_V2, DEFAULT_CRYPT_KEYSLOTS_SIZE,
-                    DEFAULT_CRYPT_METADATA_SIZE_V2, STRATIS_MEK_SIZE,
+                    DEFAULT_CRYPT_METADATA_SIZE_V2, STRATIS_MEK_SIZE, TOKEN_KEYSLOTS_KEY,
                 },
+                handle::v1::UnlockSecrets,
                 shared::{
                     acquire_crypt_device, activate, activate_by_token, add_keyring_keyslot,
                     clevis_decrypt, clevis_info_from_json, device_from_physical_path,
                     encryption_info_from_metadata, ensure_wiped, get_keyslot_number,
-                    interpret_clevis_config, read_key, wipe_fallback,
+                    interpret_clevis_config, read_key, reencrypt_params, reencryption_in_progress,
+                    wipe_fallback,
                 },
             },
             device::blkdev_size,
@@ -209,6 +211,150 @@ fn get_passphrase(
     ))
 }
 
+/// Format a device holding unencrypted data with LUKS2 metadata in
+/// preparation for encrypting the data in place, and bind the unlock
+/// mechanisms of a legacy encrypted device to the new metadata.
+///
+/// The data must begin at DEFAULT_CRYPT_DATA_OFFSET_V2; the sectors before it
+/// are overwritten by the LUKS2 metadata.
+pub fn format_for_encryption_in_place(
+    physical_path: &Path,
+    secrets: &UnlockSecrets,
+) -> StratisResult<()> {
+    let mut device = log_on_failure!(
+        CryptInit::init(physical_path),
+        "Failed to acquire context for device {}",
+        physical_path.display()
+    );
+
+    device.settings_handle().set_metadata_size(
+        MetadataSize::try_from(convert_int!(*DEFAULT_CRYPT_METADATA_SIZE_V2, u128, u64)?)?,
+        KeyslotsSize::try_from(convert_int!(*DEFAULT_CRYPT_KEYSLOTS_SIZE, u128, u64)?)?,
+    )?;
+    device.set_data_offset(*DEFAULT_CRYPT_DATA_OFFSET_V2)?;
+
+    log_on_failure!(
+        device.context_handle().format::<CryptParamsLuks2Ref<'_>>(
+            EncryptionFormat::Luks2,
+            ("aes", "xts-plain64"),
+            None,
+            libcryptsetup_rs::Either::Right(STRATIS_MEK_SIZE),
+            None,
+        ),
+        "Failed to format device {} with LUKS2 header",
+        physical_path.display()
+    );
+
+    let mut keyslot = None;
+    if let Some((token_slot, key_desc, key)) = &secrets.key_description {
+        let new_keyslot = log_on_failure!(
+            device
+                .keyslot_handle()
+                .add_by_key(None, None, key.as_ref(), CryptVolumeKey::empty()),
+            "Failed to initialize keyslot with key for key description"
+        );
+        let new_token_slot = log_on_failure!(
+            device
+                .token_handle()
+                .luks2_keyring_set(Some(*token_slot), &key_desc.to_system_string()),
+            "Failed to initialize the LUKS2 token for driving keyring activation operations"
+        );
+        log_on_failure!(
+            device
+                .token_handle()
+                .assign_keyslot(new_token_slot, Some(new_keyslot)),
+            "Failed to assign the LUKS2 keyring token to the keyslot"
+        );
+        keyslot = Some(new_keyslot);
+    }
+    if let Some((token_slot, token, pass)) = &secrets.clevis {
+        let new_keyslot = log_on_failure!(
+            device
+                .keyslot_handle()
+                .add_by_key(None, None, pass.as_ref(), CryptVolumeKey::empty()),
+            "Failed to initialize keyslot with Clevis passphrase"
+        );
+        let mut token = token.clone();
+        if let Some(map) = token.as_object_mut() {
+            map.insert(
+                TOKEN_KEYSLOTS_KEY.to_string(),
+                Value::Array(vec![Value::from(new_keyslot.to_string())]),
+            );
+        }
+        log_on_failure!(
+            device
+                .token_handle()
+                .json_set(TokenInput::ReplaceToken(*token_slot, &token)),
+            "Failed to copy the Clevis token to the new LUKS2 metadata"
+        );
+        keyslot = keyslot.or(Some(new_keyslot));
+    }
+
+    log_on_failure!(
+        device.reencrypt_handle().reencrypt_init_by_passphrase(
+            None,
+            secrets.passphrase().as_ref(),
+            None,
+            keyslot,
+            Some(("aes", "xts-plain64")),
+            reencrypt_params(
+                CryptReencryptModeInfo::Encrypt,
+                CryptReencrypt::INITIALIZE_ONLY
+            ),
+        ),
+        "Failed to initialize encryption of device {}",
+        physical_path.display()
+    );
+    Ok(())
+}
+
+/// Encrypt the data of a device formatted by format_for_encryption_in_place(),
+/// resuming the encryption if it was interrupted. If passphrase is not
+/// specified, the device is unlocked with one of its unlock mechanisms.
+pub fn encrypt_in_place(
+    physical_path: &Path,
+    passphrase: Option<&SizedKeyMemory>,
+) -> StratisResult<()> {
+    let mut device = acquire_crypt_device(physical_path)?;
+    let key;
+    let passphrase = match passphrase {
+        Some(p) => p,
+        None => {
+            let encryption_info = encryption_info_from_metadata(&mut device)?;
+            key = get_passphrase(&mut device, &encryption_info)?.either(|(_, k)| k, |k| k);
+            &key
+        }
+    };
+
+    log_on_failure!(
+        device.reencrypt_handle().reencrypt_init_by_passphrase(
+            None,
+            passphrase.as_ref(),
+            None,
+            None,
+            None,
+            reencrypt_params(CryptReencryptModeInfo::Encrypt, CryptReencrypt::RESUME_ONLY),
+        ),
+        "Failed to resume encryption of device {}",
+        physical_path.display()
+    );
+    log_on_failure!(
+        device.reencrypt_handle().reencrypt(None),
+        "Failed to encrypt device {}",
+        physical_path.display()
+    );
+    Ok(())
+}
+
+/// Whether the device has LUKS2 metadata and an in place encryption of its
+/// data has not been completed.
+pub fn encryption_in_progress(physical_path: &Path) -> StratisResult<bool> {
+    match device_from_physical_path(physical_path)? {
+        Some(mut device) => reencryption_in_progress(&mut device),
+        None => Ok(false),
+    }
+}
+
 /// Handle for performing all operations on an encrypted device.
 ///
 /// `Clone` is derived for this data structure because `CryptHandle` acquires
@@ -511,7 +657,6 @@ impl CryptHandle {
     }
 
     /// Return the path of the activated devicemapper device.
-    #[cfg(test)]
     pub fn activated_device_path(&self) -> &Path {
         &self.metadata.activated_path
     }
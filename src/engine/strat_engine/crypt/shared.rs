@@ -21,12 +21,14 @@ use devicemapper::{DevId, DmName, DmOptions, Sectors, SECTOR_SIZE};
 use libcryptsetup_rs::{
     c_uint,
     consts::{
-        flags::{CryptActivate, CryptVolumeKey, CryptWipe},
+        flags::{CryptActivate, CryptReencrypt, CryptVolumeKey, CryptWipe},
         vals::{
-            CryptDebugLevel, CryptLogLevel, CryptStatusInfo, CryptWipePattern, EncryptionFormat,
+            CryptDebugLevel, CryptLogLevel, CryptReencryptDirectionInfo, CryptReencryptInfo,
+            CryptReencryptModeInfo, CryptStatusInfo, CryptWipePattern, EncryptionFormat,
         },
     },
-    register, set_debug_level, set_log_callback, CryptDevice, CryptInit,
+    register, set_debug_level, set_log_callback, CryptDevice, CryptInit, CryptParamsLuks2,
+    CryptParamsReencrypt,
 };
 
 use crate::{
@@ -939,6 +941,50 @@ pub fn clevis_decrypt(
     cmd::clevis_decrypt(&jwe).map(Some)
 }
 
+/// Parameters for an in place encryption or decryption of a device which
+/// leaves the data at its current offset.
+pub fn reencrypt_params(
+    mode: CryptReencryptModeInfo,
+    flags: CryptReencrypt,
+) -> CryptParamsReencrypt {
+    CryptParamsReencrypt {
+        mode,
+        direction: CryptReencryptDirectionInfo::Forward,
+        resilience: "checksum".to_string(),
+        hash: "sha256".to_string(),
+        data_shift: 0,
+        max_hotzone_size: 0,
+        device_size: 0,
+        luks2: CryptParamsLuks2 {
+            pbkdf: None,
+            integrity: None,
+            integrity_params: None,
+            data_alignment: 0,
+            data_device: None,
+            sector_size: convert_const!(SECTOR_SIZE, usize, u32),
+            label: None,
+            subsystem: None,
+        },
+        flags,
+    }
+}
+
+/// Whether an in place encryption or decryption of the device has been
+/// started and not yet completed.
+pub fn reencryption_in_progress(device: &mut CryptDevice) -> StratisResult<bool> {
+    let status = device.reencrypt_handle().status(reencrypt_params(
+        CryptReencryptModeInfo::Reencrypt,
+        CryptReencrypt::empty(),
+    ))?;
+    match status {
+        CryptReencryptInfo::None => Ok(false),
+        CryptReencryptInfo::Clean | CryptReencryptInfo::Crash => Ok(true),
+        CryptReencryptInfo::Invalid => Err(StratisError::Msg(
+            "The reencryption metadata on the device is invalid".to_string(),
+        )),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
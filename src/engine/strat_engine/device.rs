@@ -23,6 +23,7 @@ use crate::stratis::{StratisError, StratisResult};
 // https://github.com/stratis-storage/project/issues/533
 ioctl_read_bad!(blksszget, 0x1268, c_int);
 ioctl_read_bad!(blkpbszget, 0x127b, c_int);
+ioctl_none!(blkflsbuf, 0x12, 97);
 
 const BLK: Group = Group::new(0x12);
 
@@ -54,3 +55,11 @@ pub fn blkdev_physical_sector_size(file: &File) -> StratisResult<Bytes> {
     })?;
     Ok(Bytes::from(convert_int!(val, c_int, u16)?))
 }
+
+/// Write out and invalidate the buffers of the block device, so that reads
+/// through the page cache see what is on the device.
+pub fn blkdev_flush_buffers(file: &File) -> StratisResult<()> {
+    unsafe { blkflsbuf(file.as_raw_fd()) }
+        .map_err(|e| StratisError::Msg(format!("Error flushing buffers (BLKFLSBUF): {e}")))?;
+    Ok(())
+}
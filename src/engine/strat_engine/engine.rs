@@ -5,7 +5,7 @@
 use std::{
     collections::{HashMap, HashSet},
     os::fd::RawFd,
-    path::Path,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
@@ -17,11 +17,12 @@ use tokio::{
     task::{spawn_blocking, JoinHandle},
 };
 
-use devicemapper::DmNameBuf;
+use devicemapper::{DmNameBuf, Sectors};
 
 use crate::{
     engine::{
         engine::{HandleEvents, KeyActions},
+        jobs::JobManager,
         shared::{create_pool_idempotent_or_err, validate_name, validate_paths},
         strat_engine::{
             backstore::ProcessedPathInfos,
@@ -37,14 +38,15 @@ use crate::{
             SomeLockWriteGuard, Table,
         },
         types::{
-            CreateAction, DeleteAction, DevUuid, FilesystemUuid, InputEncryptionInfo,
+            CreateAction, DeleteAction, DevUuid, EngineAction, FilesystemUuid, InputEncryptionInfo,
             IntegritySpec, LockedPoolsInfo, PoolDiff, PoolIdentifier, RenameAction, ReportType,
             SetUnlockAction, StartAction, StopAction, StoppedPoolsInfo, StratFilesystemDiff,
-            TokenUnlockMethod, UdevEngineEvent, UnlockMethod, ValidatedIntegritySpec,
+            ThinMetaSpareAction, ThinMetaSpareInfo, TokenUnlockMethod, UdevEngineEvent,
+            UnlockMethod, UpgradeReport, ValidatedIntegritySpec,
         },
         Engine, Name, Pool, PoolUuid, Report,
     },
-    stratis::{StratisError, StratisResult},
+    stratis::{AuditRecord, StratisError, StratisResult},
 };
 
 type EventNumbers = HashMap<PoolUuid, HashMap<DmNameBuf, u32>>;
@@ -65,6 +67,9 @@ pub struct StratEngine {
     // Handler for key operations
     key_handler: Arc<StratKeyActions>,
 
+    // Jobs tracking long-running operations
+    jobs: Arc<JobManager>,
+
     // In memory filesystem for private namespace mounts.
     #[allow(dead_code)]
     fs: MemoryFilesystem,
@@ -95,6 +100,7 @@ impl StratEngine {
             liminal_devices: Lockable::new_shared(liminal_devices),
             watched_dev_last_event_nrs: Lockable::new_shared(HashMap::new()),
             key_handler: Arc::new(StratKeyActions),
+            jobs: Arc::new(JobManager::default()),
             fs,
         })
     }
@@ -426,70 +432,9 @@ impl Report for StratEngine {
     }
 }
 
-#[async_trait]
-impl Engine for StratEngine {
-    async fn handle_events(&self, events: Vec<UdevEngineEvent>) -> HandleEvents<dyn Pool> {
-        let mut ret_guards = Vec::new();
-        let mut diffs = HashMap::new();
-
-        // Acquire a write lock here so that no concurrent accesses can occur between
-        // udev event handling and insertion into the pool.
-        //
-        // Failing to do this can cause two identical pools to be registered in
-        // internal data structures.
-        let mut pools_write_all = self.pools.modify_all().await;
-        let mut ld_guard = self.liminal_devices.write().await;
-
-        match spawn_blocking!({
-            events
-                .iter()
-                .map(|event| {
-                    let uuid = if let Some((name, uuid, pool)) =
-                        ld_guard.block_evaluate(&pools_write_all, event)
-                    {
-                        pools_write_all.insert(name, uuid, pool);
-                        Some(uuid)
-                    } else {
-                        None
-                    };
-                    match LiminalDevices::block_evaluate_size(&mut pools_write_all, event) {
-                        Ok(Some((dev_uuid, diff))) => (uuid, Some((dev_uuid, diff))),
-                        Ok(None) => (uuid, None),
-                        Err(e) => {
-                            warn!("Ignoring device size change handling due to error: {}", e);
-                            (uuid, None)
-                        }
-                    }
-                })
-                .fold(
-                    (Vec::new(), HashMap::new()),
-                    |(mut uuids, mut diffs), (uuid, info)| {
-                        if let Some(u) = uuid {
-                            uuids.push(u);
-                        }
-                        if let Some((dev_uuid, diff)) = info {
-                            diffs.insert(dev_uuid, diff);
-                        }
-                        (uuids, diffs)
-                    },
-                )
-        }) {
-            Ok((uuids, diffs_thread)) => {
-                for uuid in uuids {
-                    if let Some(guard) = self.pools.read(PoolIdentifier::Uuid(uuid)).await {
-                        ret_guards.push(guard.into_dyn());
-                    }
-                }
-                diffs.extend(diffs_thread);
-            }
-            Err(e) => {
-                warn!("Failed to handle udev events: {}", e);
-            }
-        };
-
-        (ret_guards, diffs)
-    }
-
+/// The engine operations which are audited. The implementation of the Engine
+/// trait emits an audit record for the result of each.
+impl StratEngine {
     async fn create_pool(
         &self,
         name: &str,
@@ -693,6 +638,334 @@ impl Engine for StratEngine {
         }
     }
 
+    async fn clone_filesystem(
+        &self,
+        source_pool: PoolUuid,
+        fs_uuid: FilesystemUuid,
+        target_pool: PoolUuid,
+        new_name: &str,
+        preserve_uuid: bool,
+    ) -> StratisResult<CreateAction<FilesystemUuid>> {
+        if source_pool == target_pool {
+            return Err(StratisError::Msg(
+                "The source and target pools of a filesystem clone must be different; use a snapshot to copy a filesystem within a pool".to_string(),
+            ));
+        }
+
+        // Acquire the locks in a fixed order so that concurrent clones in
+        // opposite directions can not deadlock.
+        let mut guards = Vec::new();
+        for uuid in if source_pool.0 < target_pool.0 {
+            [source_pool, target_pool]
+        } else {
+            [target_pool, source_pool]
+        } {
+            guards.push(
+                self.pools
+                    .write(PoolIdentifier::Uuid(uuid))
+                    .await
+                    .ok_or_else(|| StratisError::Msg(format!("No pool found with UUID {uuid}")))?,
+            );
+        }
+        let (mut source_guard, mut target_guard) = {
+            let second = guards.pop().expect("two guards acquired");
+            let first = guards.pop().expect("two guards acquired");
+            if source_pool.0 < target_pool.0 {
+                (first, second)
+            } else {
+                (second, first)
+            }
+        };
+
+        let new_name = Name::new(new_name.to_owned());
+        if target_guard.get_filesystem_by_name(&new_name).is_some() {
+            return Ok(CreateAction::Identity);
+        }
+
+        spawn_blocking!({
+            let (source_name, _, source) = source_guard.as_mut_tuple();
+            let (target_name, _, target) = target_guard.as_mut_tuple();
+
+            let clone_source = source.prepare_clone_source(&source_name, source_pool, fs_uuid)?;
+            let mut reported = 0;
+            let res = target.clone_filesystem(
+                &target_name,
+                target_pool,
+                &new_name,
+                &clone_source,
+                preserve_uuid,
+                &mut |copied, total| {
+                    let percent = if total == Sectors(0) {
+                        100
+                    } else {
+                        *copied * 100 / *total
+                    };
+                    if percent / 10 > reported / 10 {
+                        reported = percent;
+                        info!(
+                            "Clone of filesystem {} into filesystem {} of pool {}: copied {} of {} ({}%)",
+                            fs_uuid, new_name, target_name, copied, total, percent
+                        );
+                    }
+                },
+            );
+            if let Err(err) = source.destroy_clone_source(&source_name, clone_source.snapshot_uuid)
+            {
+                warn!(
+                    "Failed to remove temporary snapshot {} of filesystem {} in pool {}: {}",
+                    clone_source.snapshot_uuid, fs_uuid, source_name, err
+                );
+            }
+            res.map(CreateAction::Created)
+        })?
+    }
+
+    async fn start_pool(
+        &self,
+        id: PoolIdentifier<PoolUuid>,
+        token_slot: TokenUnlockMethod,
+        passphrase_fd: Option<RawFd>,
+    ) -> StratisResult<StartAction<PoolUuid>> {
+        if let Some(lock) = self.pools.read(id.clone()).await {
+            let (_, pool_uuid, pool) = lock.as_tuple();
+            if !pool.is_encrypted() && token_slot.is_some() {
+                return Err(StratisError::Msg(format!(
+                    "Pool with UUID {pool_uuid} is not encrypted but an unlock method was provided"
+                )));
+            } else if !pool.is_encrypted() && passphrase_fd.is_some() {
+                return Err(StratisError::Msg(format!(
+                    "Pool with UUID {pool_uuid} is not encrypted but a passphrase was provided"
+                )));
+            } else {
+                Ok(StartAction::Identity)
+            }
+        } else {
+            let mut pools = self.pools.modify_all().await;
+            let mut liminal = self.liminal_devices.write().await;
+            let pool_uuid = spawn_blocking!({
+                let (name, pool_uuid, pool, _) =
+                    liminal.start_pool(&pools, id, token_slot, passphrase_fd)?;
+                pools.insert(name, pool_uuid, pool);
+                StratisResult::Ok(pool_uuid)
+            })??;
+            Ok(StartAction::Started(pool_uuid))
+        }
+    }
+
+    async fn stop_pool(
+        &self,
+        pool_id: PoolIdentifier<PoolUuid>,
+        has_partially_constructed: bool,
+    ) -> StratisResult<StopAction<PoolUuid>> {
+        let id_str = pool_id.to_string();
+
+        let stopped_pools = self.liminal_devices.read().await.stopped_pools();
+        let pool_uuid = match pool_id {
+            PoolIdentifier::Name(ref n) => stopped_pools.name_to_uuid.get(n).copied(),
+            PoolIdentifier::Uuid(ref u) => Some(*u),
+        };
+        if let Some(pool_uuid) = pool_uuid {
+            if has_partially_constructed {
+                if stopped_pools.stopped.contains_key(&pool_uuid) {
+                    return Ok(StopAction::Identity);
+                } else if stopped_pools.partially_constructed.contains_key(&pool_uuid) {
+                    let mut lim_devs = self.liminal_devices.write().await;
+                    spawn_blocking!(lim_devs.stop_partially_constructed_pool(pool_uuid))??;
+                    return Ok(StopAction::CleanedUp(pool_uuid));
+                }
+            } else if stopped_pools.stopped.contains_key(&pool_uuid)
+                || stopped_pools.partially_constructed.contains_key(&pool_uuid)
+            {
+                return Ok(StopAction::Identity);
+            }
+        }
+
+        let mut pools = self.pools.modify_all().await;
+        if let Some((name, pool_uuid, pool)) = match pool_id {
+            PoolIdentifier::Name(n) => pools.remove_by_name(&n).map(|(u, p)| (n, u, p)),
+            PoolIdentifier::Uuid(u) => pools.remove_by_uuid(u).map(|(n, p)| (n, u, p)),
+        } {
+            let mut lim_devs = self.liminal_devices.write().await;
+            if spawn_blocking!(lim_devs.stop_pool(&mut pools, name, pool_uuid, pool))?? {
+                Ok(StopAction::Stopped(pool_uuid))
+            } else {
+                Ok(StopAction::Partial(pool_uuid))
+            }
+        } else {
+            Err(StratisError::Msg(format!(
+                "Pool with UUID {id_str} could not be found and cannot be stopped"
+            )))
+        }
+    }
+
+    async fn upgrade_pool(
+        &self,
+        pool_id: PoolIdentifier<PoolUuid>,
+        passphrase_fd: Option<RawFd>,
+        dry_run: bool,
+    ) -> StratisResult<UpgradeReport> {
+        if self.pools.read(pool_id.clone()).await.is_some() {
+            return Err(StratisError::Msg(format!(
+                "Pool {pool_id} must be stopped to be upgraded"
+            )));
+        }
+        let mut liminal = self.liminal_devices.write().await;
+        spawn_blocking!(liminal.upgrade_pool(pool_id, passphrase_fd, dry_run))?
+    }
+
+    async fn thin_meta_spare(
+        &self,
+        pool_id: PoolIdentifier<PoolUuid>,
+        action: ThinMetaSpareAction,
+    ) -> StratisResult<ThinMetaSpareInfo> {
+        if self.pools.read(pool_id.clone()).await.is_some() {
+            return Err(StratisError::Msg(format!(
+                "Pool {pool_id} must be stopped to operate on its spare thin metadata device"
+            )));
+        }
+        let mut liminal = self.liminal_devices.write().await;
+        spawn_blocking!(liminal.thin_meta_spare(pool_id, action))?
+    }
+}
+
+#[async_trait]
+impl Engine for StratEngine {
+    async fn handle_events(&self, events: Vec<UdevEngineEvent>) -> HandleEvents<dyn Pool> {
+        let mut ret_guards = Vec::new();
+        let mut diffs = HashMap::new();
+
+        // Acquire a write lock here so that no concurrent accesses can occur between
+        // udev event handling and insertion into the pool.
+        //
+        // Failing to do this can cause two identical pools to be registered in
+        // internal data structures.
+        let mut pools_write_all = self.pools.modify_all().await;
+        let mut ld_guard = self.liminal_devices.write().await;
+
+        match spawn_blocking!({
+            events
+                .iter()
+                .map(|event| {
+                    let uuid = if let Some((name, uuid, pool)) =
+                        ld_guard.block_evaluate(&pools_write_all, event)
+                    {
+                        pools_write_all.insert(name, uuid, pool);
+                        Some(uuid)
+                    } else {
+                        None
+                    };
+                    match LiminalDevices::block_evaluate_size(&mut pools_write_all, event) {
+                        Ok(Some((dev_uuid, diff))) => (uuid, Some((dev_uuid, diff))),
+                        Ok(None) => (uuid, None),
+                        Err(e) => {
+                            warn!("Ignoring device size change handling due to error: {}", e);
+                            (uuid, None)
+                        }
+                    }
+                })
+                .fold(
+                    (Vec::new(), HashMap::new()),
+                    |(mut uuids, mut diffs), (uuid, info)| {
+                        if let Some(u) = uuid {
+                            uuids.push(u);
+                        }
+                        if let Some((dev_uuid, diff)) = info {
+                            diffs.insert(dev_uuid, diff);
+                        }
+                        (uuids, diffs)
+                    },
+                )
+        }) {
+            Ok((uuids, diffs_thread)) => {
+                for uuid in uuids {
+                    if let Some(guard) = self.pools.read(PoolIdentifier::Uuid(uuid)).await {
+                        ret_guards.push(guard.into_dyn());
+                    }
+                }
+                diffs.extend(diffs_thread);
+            }
+            Err(e) => {
+                warn!("Failed to handle udev events: {}", e);
+            }
+        };
+
+        (ret_guards, diffs)
+    }
+
+    async fn create_pool(
+        &self,
+        name: &str,
+        blockdev_paths: &[&Path],
+        encryption_info: Option<&InputEncryptionInfo>,
+        integrity_spec: IntegritySpec,
+    ) -> StratisResult<CreateAction<PoolUuid>> {
+        let res = self
+            .create_pool(name, blockdev_paths, encryption_info, integrity_spec)
+            .await;
+        AuditRecord::new("create_pool")
+            .pool_uuid(res.as_ref().ok().and_then(|a| match a {
+                CreateAction::Created(uuid) => Some(*uuid),
+                CreateAction::Identity => None,
+            }))
+            .pool_name(name)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
+    }
+
+    async fn destroy_pool(&self, uuid: PoolUuid) -> StratisResult<DeleteAction<PoolUuid>> {
+        let res = self.destroy_pool(uuid).await;
+        AuditRecord::new("destroy_pool")
+            .pool_uuid(Some(uuid))
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
+    }
+
+    async fn rename_pool(
+        &self,
+        uuid: PoolUuid,
+        new_name: &str,
+    ) -> StratisResult<RenameAction<PoolUuid>> {
+        let res = self.rename_pool(uuid, new_name).await;
+        AuditRecord::new("rename_pool")
+            .pool_uuid(Some(uuid))
+            .pool_name(new_name)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
+    }
+
+    async fn unlock_pool(
+        &self,
+        pool_uuid: PoolUuid,
+        unlock_method: UnlockMethod,
+    ) -> StratisResult<SetUnlockAction<DevUuid>> {
+        let res = self.unlock_pool(pool_uuid, unlock_method).await;
+        AuditRecord::new("unlock_pool")
+            .pool_uuid(Some(pool_uuid))
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
+    }
+
+    async fn clone_filesystem(
+        &self,
+        source_pool: PoolUuid,
+        fs_uuid: FilesystemUuid,
+        target_pool: PoolUuid,
+        new_name: &str,
+        preserve_uuid: bool,
+    ) -> StratisResult<CreateAction<FilesystemUuid>> {
+        let res = self
+            .clone_filesystem(source_pool, fs_uuid, target_pool, new_name, preserve_uuid)
+            .await;
+        let mut record = AuditRecord::new("clone_filesystem")
+            .pool_uuid(Some(target_pool))
+            .fs_uuid(fs_uuid);
+        if let Ok(CreateAction::Created(uuid)) = res {
+            record = record.fs_uuid(uuid);
+        }
+        record.emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
+    }
+
     async fn get_pool(
         &self,
         key: PoolIdentifier<PoolUuid>,
@@ -781,6 +1054,29 @@ impl Engine for StratEngine {
         }
     }
 
+    async fn backup_thin_metadata(&self) -> HashMap<PoolUuid, StratisResult<PathBuf>> {
+        let guards: Vec<SomeLockReadGuard<PoolUuid, AnyPool>> = self.pools.read_all().await.into();
+        join_all(guards.into_iter().map(|guard| {
+            spawn_blocking(move || {
+                let (_, uuid, pool) = guard.as_tuple();
+                (uuid, pool.backup_thin_metadata())
+            })
+        }))
+        .await
+        .into_iter()
+        .filter_map(|res| match res {
+            Ok(tup) => Some(tup),
+            Err(e) => {
+                warn!(
+                    "Failed to get status for thread handling thin metadata backup: {}",
+                    e
+                );
+                None
+            }
+        })
+        .collect()
+    }
+
     async fn get_key_handler(&self) -> Arc<dyn KeyActions> {
         Arc::clone(&self.key_handler) as Arc<dyn KeyActions>
     }
@@ -791,30 +1087,15 @@ impl Engine for StratEngine {
         token_slot: TokenUnlockMethod,
         passphrase_fd: Option<RawFd>,
     ) -> StratisResult<StartAction<PoolUuid>> {
-        if let Some(lock) = self.pools.read(id.clone()).await {
-            let (_, pool_uuid, pool) = lock.as_tuple();
-            if !pool.is_encrypted() && token_slot.is_some() {
-                return Err(StratisError::Msg(format!(
-                    "Pool with UUID {pool_uuid} is not encrypted but an unlock method was provided"
-                )));
-            } else if !pool.is_encrypted() && passphrase_fd.is_some() {
-                return Err(StratisError::Msg(format!(
-                    "Pool with UUID {pool_uuid} is not encrypted but a passphrase was provided"
-                )));
-            } else {
-                Ok(StartAction::Identity)
-            }
-        } else {
-            let mut pools = self.pools.modify_all().await;
-            let mut liminal = self.liminal_devices.write().await;
-            let pool_uuid = spawn_blocking!({
-                let (name, pool_uuid, pool, _) =
-                    liminal.start_pool(&pools, id, token_slot, passphrase_fd)?;
-                pools.insert(name, pool_uuid, pool);
-                StratisResult::Ok(pool_uuid)
-            })??;
-            Ok(StartAction::Started(pool_uuid))
-        }
+        let record = AuditRecord::new("start_pool").pool_id(&id);
+        let res = self.start_pool(id, token_slot, passphrase_fd).await;
+        record
+            .pool_uuid(match res {
+                Ok(StartAction::Started(uuid)) => Some(uuid),
+                _ => None,
+            })
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     async fn stop_pool(
@@ -822,45 +1103,55 @@ impl Engine for StratEngine {
         pool_id: PoolIdentifier<PoolUuid>,
         has_partially_constructed: bool,
     ) -> StratisResult<StopAction<PoolUuid>> {
-        let id_str = pool_id.to_string();
+        let record = AuditRecord::new("stop_pool").pool_id(&pool_id);
+        let res = self.stop_pool(pool_id, has_partially_constructed).await;
+        record
+            .pool_uuid(match res {
+                Ok(
+                    StopAction::Stopped(uuid)
+                    | StopAction::CleanedUp(uuid)
+                    | StopAction::Partial(uuid),
+                ) => Some(uuid),
+                _ => None,
+            })
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
+    }
 
-        let stopped_pools = self.liminal_devices.read().await.stopped_pools();
-        let pool_uuid = match pool_id {
-            PoolIdentifier::Name(ref n) => stopped_pools.name_to_uuid.get(n).copied(),
-            PoolIdentifier::Uuid(ref u) => Some(*u),
+    async fn upgrade_pool(
+        &self,
+        pool_id: PoolIdentifier<PoolUuid>,
+        passphrase_fd: Option<RawFd>,
+        dry_run: bool,
+    ) -> StratisResult<UpgradeReport> {
+        let record = AuditRecord::new("upgrade_pool").pool_id(&pool_id);
+        let res = self.upgrade_pool(pool_id, passphrase_fd, dry_run).await;
+        record
+            .pool_uuid(res.as_ref().ok().map(|report| report.pool_uuid))
+            .emit_result(res.as_ref().map(|report| report.upgraded));
+        res
+    }
+
+    async fn thin_meta_spare(
+        &self,
+        pool_id: PoolIdentifier<PoolUuid>,
+        action: ThinMetaSpareAction,
+    ) -> StratisResult<ThinMetaSpareInfo> {
+        let audit_action = match action {
+            ThinMetaSpareAction::Report => return self.thin_meta_spare(pool_id, action).await,
+            ThinMetaSpareAction::Validate => "validate_thin_meta_spare",
+            ThinMetaSpareAction::Swap => "swap_thin_meta_spare",
+            ThinMetaSpareAction::Reprovision => "reprovision_thin_meta_spare",
         };
-        if let Some(pool_uuid) = pool_uuid {
-            if has_partially_constructed {
-                if stopped_pools.stopped.contains_key(&pool_uuid) {
-                    return Ok(StopAction::Identity);
-                } else if stopped_pools.partially_constructed.contains_key(&pool_uuid) {
-                    let mut lim_devs = self.liminal_devices.write().await;
-                    spawn_blocking!(lim_devs.stop_partially_constructed_pool(pool_uuid))??;
-                    return Ok(StopAction::CleanedUp(pool_uuid));
-                }
-            } else if stopped_pools.stopped.contains_key(&pool_uuid)
-                || stopped_pools.partially_constructed.contains_key(&pool_uuid)
-            {
-                return Ok(StopAction::Identity);
-            }
-        }
 
-        let mut pools = self.pools.modify_all().await;
-        if let Some((name, pool_uuid, pool)) = match pool_id {
-            PoolIdentifier::Name(n) => pools.remove_by_name(&n).map(|(u, p)| (n, u, p)),
-            PoolIdentifier::Uuid(u) => pools.remove_by_uuid(u).map(|(n, p)| (n, u, p)),
-        } {
-            let mut lim_devs = self.liminal_devices.write().await;
-            if spawn_blocking!(lim_devs.stop_pool(&mut pools, name, pool_uuid, pool))?? {
-                Ok(StopAction::Stopped(pool_uuid))
-            } else {
-                Ok(StopAction::Partial(pool_uuid))
-            }
-        } else {
-            Err(StratisError::Msg(format!(
-                "Pool with UUID {id_str} could not be found and cannot be stopped"
-            )))
-        }
+        // Validation only reads the spare; swap and reprovision rewrite it.
+        let changes = !matches!(action, ThinMetaSpareAction::Validate);
+        let record = AuditRecord::new(audit_action).pool_id(&pool_id);
+        let res = self.thin_meta_spare(pool_id, action).await;
+        record
+            .pool_uuid(res.as_ref().ok().map(|info| info.pool_uuid))
+            .emit_result(res.as_ref().map(|_| changes));
+        res
     }
 
     async fn refresh_state(&self) -> StratisResult<()> {
@@ -875,6 +1166,10 @@ impl Engine for StratEngine {
         Ok(())
     }
 
+    fn jobs(&self) -> Arc<JobManager> {
+        Arc::clone(&self.jobs)
+    }
+
     fn is_sim(&self) -> bool {
         false
     }
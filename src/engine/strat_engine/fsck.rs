@@ -0,0 +1,546 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Offline check and repair of a stopped pool.
+//
+// The copies of the pool-level metadata on all the devices of the pool are
+// compared and the newest valid copy is chosen. The backstore of the pool is
+// then activated temporarily, without starting the pool, so that the thin pool
+// metadata and the filesystem records on the MDV can be checked. Nothing is
+// written unless a repair is requested explicitly.
+//
+// If both the thin metadata device and its spare are damaged, the thin pool
+// metadata can be restored from the most recent backup which stratisd made
+// to the MDV while the pool was running. Any changes to the thin pool made
+// after that backup are lost.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+    io::Write,
+    mem::swap,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use tempfile::NamedTempFile;
+
+use devicemapper::{Device, DmDevice, ThinDevId};
+
+use crate::{
+    engine::{
+        strat_engine::{
+            cmd::{thin_check, thin_dump, thin_repair, thin_restore, verify_executables},
+            dm::{get_dm, get_dm_init},
+            liminal::{find_all, LStratisInfo},
+            names::FlexRole,
+            ns::{unshare_mount_namespace, MemoryFilesystem},
+            offline::{
+                choose_copy, ensure_unused, read_copy, setup_flex_dev, sigblock_version, teardown,
+                write_metadata, ActiveBackstore,
+            },
+            pool::inspection::inspectors,
+            serde_structs::PoolSave,
+            thinpool::{thin_dump_devices, MetadataVol},
+        },
+        types::{DevUuid, PoolUuid},
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// A repair which fsck_pool() performs only if it is requested.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FsckRepair {
+    /// Write the newest valid copy of the pool-level metadata to every device
+    /// of the pool.
+    RewriteMda,
+    /// Repair the thin pool metadata into the spare thin metadata device,
+    /// which then becomes the thin metadata device.
+    SwapThinMetaSpare,
+    /// Restore the thin pool metadata from the most recent backup on the MDV
+    /// into the spare thin metadata device, which then becomes the thin
+    /// metadata device.
+    RestoreThinMetaBackup,
+    /// Remove or correct the filesystem records on the MDV which can not be
+    /// read or which are inconsistent with each other or with the thin pool
+    /// metadata.
+    RebuildMdv,
+}
+
+/// The result of checking a pool.
+#[derive(Debug)]
+pub struct FsckReport {
+    pub pool_uuid: PoolUuid,
+    /// The devices of the pool that were found.
+    pub devices: Vec<(DevUuid, PathBuf)>,
+    /// The time at which the copy of the pool-level metadata that was used
+    /// was written, if a valid copy was found.
+    pub metadata_time: Option<DateTime<Utc>>,
+    /// The problems found.
+    pub problems: Vec<String>,
+    /// The repairs performed.
+    pub repairs: Vec<String>,
+}
+
+impl FsckReport {
+    /// True if no problems were found.
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl Display for FsckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Pool UUID: {}", self.pool_uuid)?;
+        writeln!(f, "Devices:")?;
+        for (dev_uuid, devnode) in self.devices.iter() {
+            writeln!(f, "  {} {}", dev_uuid, devnode.display())?;
+        }
+        match self.metadata_time {
+            Some(time) => writeln!(f, "Metadata written at: {time}")?,
+            None => writeln!(f, "Metadata written at: none")?,
+        }
+        if self.problems.is_empty() {
+            writeln!(f, "No problems found")?;
+        } else {
+            writeln!(f, "Problems:")?;
+            for problem in self.problems.iter() {
+                writeln!(f, "  {problem}")?;
+            }
+        }
+        if !self.repairs.is_empty() {
+            writeln!(f, "Repairs performed:")?;
+            for repair in self.repairs.iter() {
+                writeln!(f, "  {repair}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Restore the most recent thin pool metadata backup on the MDV to the device
+/// new_meta_dev. Returns the path of the backup that was restored.
+fn restore_thin_meta_backup(mdv: &MetadataVol, new_meta_dev: &Path) -> StratisResult<PathBuf> {
+    let backup = mdv
+        .thin_meta_backups()?
+        .pop()
+        .ok_or_else(|| StratisError::Msg("No thin metadata backup was found".to_string()))?;
+    let mut xml_file = NamedTempFile::new()?;
+    xml_file.write_all(MetadataVol::read_thin_meta_backup(&backup)?.as_bytes())?;
+    xml_file.flush()?;
+    thin_restore(xml_file.path(), new_meta_dev)?;
+    Ok(backup)
+}
+
+/// Check the thin pool metadata, repairing it or restoring it from a backup
+/// into the spare thin metadata device if requested. Returns the ids of the
+/// thin devices in the thin pool metadata, if they could be found, and
+/// whether the pool-level metadata was changed.
+fn check_thin_meta(
+    pool_uuid: PoolUuid,
+    device: Device,
+    metadata: &mut PoolSave,
+    mdv: &MetadataVol,
+    repairs: &[FsckRepair],
+    report: &mut FsckReport,
+) -> StratisResult<(Option<Vec<ThinDevId>>, bool)> {
+    let restore = repairs.contains(&FsckRepair::RestoreThinMetaBackup);
+    let repair = repairs.contains(&FsckRepair::SwapThinMetaSpare);
+    if restore && repair {
+        return Err(StratisError::Msg(
+            "The thin pool metadata can not be both repaired and restored from a backup"
+                .to_string(),
+        ));
+    }
+
+    let mut meta_dev = setup_flex_dev(
+        pool_uuid,
+        FlexRole::ThinMeta,
+        device,
+        &metadata.flex_devs.thin_meta_dev,
+    )?;
+    let mut valid = match thin_check(&meta_dev.devnode()) {
+        Ok(()) => true,
+        Err(e) => {
+            let backups = match mdv.thin_meta_backups() {
+                Ok(backups) => match backups.last() {
+                    Some(latest) => format!(
+                        "{} thin metadata backups are available, the most recent is {}",
+                        backups.len(),
+                        latest.display()
+                    ),
+                    None => "no thin metadata backup is available".to_string(),
+                },
+                Err(err) => format!("thin metadata backups could not be listed: {err}"),
+            };
+            report.problems.push(format!(
+                "thin_check failed on the thin pool metadata: {e}; {backups}"
+            ));
+            false
+        }
+    };
+
+    let mut changed = false;
+    if restore || repair {
+        let spare_dev = setup_flex_dev(
+            pool_uuid,
+            FlexRole::ThinMetaSpare,
+            device,
+            &metadata.flex_devs.thin_meta_dev_spare,
+        )?;
+        let spare_devnode = spare_dev.devnode();
+        let description = if restore {
+            restore_thin_meta_backup(mdv, &spare_devnode)
+                .and_then(|backup| thin_check(&spare_devnode).map(|_| backup))
+                .map(|backup| format!(
+                    "Restored the thin pool metadata from backup {} into the spare thin metadata device, which is now the thin metadata device",
+                    backup.display()
+                ))
+                .map_err(|e| {
+                    StratisError::Chained(
+                        "Failed to restore the thin pool metadata from a backup into the spare thin metadata device"
+                            .to_string(),
+                        Box::new(e),
+                    )
+                })?
+        } else {
+            thin_repair(&meta_dev.devnode(), &spare_devnode)
+                .and_then(|_| thin_check(&spare_devnode))
+                .map(|_| "Repaired the thin pool metadata into the spare thin metadata device, which is now the thin metadata device".to_string())
+                .map_err(|e| {
+                    StratisError::Chained(
+                        "Failed to repair the thin pool metadata into the spare thin metadata device"
+                            .to_string(),
+                        Box::new(e),
+                    )
+                })?
+        };
+        meta_dev.teardown(get_dm())?;
+        meta_dev = spare_dev;
+        let flex_devs = &mut metadata.flex_devs;
+        swap(
+            &mut flex_devs.thin_meta_dev,
+            &mut flex_devs.thin_meta_dev_spare,
+        );
+        report.repairs.push(description);
+        valid = true;
+        changed = true;
+    }
+
+    let thin_ids = if valid {
+        match thin_dump(&meta_dev.devnode(), true).and_then(|xml| thin_dump_devices(&xml)) {
+            Ok(ids) => Some(ids),
+            Err(e) => {
+                report.problems.push(format!(
+                    "Thin devices could not be listed from the thin pool metadata: {e}"
+                ));
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    meta_dev.teardown(get_dm())?;
+    Ok((thin_ids, changed))
+}
+
+/// Check the filesystem records on the MDV, correcting them if rebuild is
+/// true. If thin_ids is None, the records can not be checked against the thin
+/// pool metadata.
+fn check_mdv(
+    mdv: &MetadataVol,
+    thin_ids: Option<&[ThinDevId]>,
+    rebuild: bool,
+    report: &mut FsckReport,
+) -> StratisResult<()> {
+    let mut records = Vec::new();
+    for (path, record) in mdv.records()? {
+        match record {
+            Ok(record) => records.push((path, record)),
+            Err(e) => {
+                report.problems.push(format!(
+                    "Filesystem record {} can not be read: {e}",
+                    path.display()
+                ));
+                if rebuild {
+                    mdv.rm_record(&path)?;
+                    report.repairs.push(format!(
+                        "Removed unreadable filesystem record {}",
+                        path.display()
+                    ));
+                }
+            }
+        }
+    }
+
+    match thin_ids {
+        Some(thin_ids) => {
+            let mut kept = Vec::new();
+            for (path, record) in records {
+                if thin_ids.contains(&record.thin_id) {
+                    kept.push((path, record));
+                    continue;
+                }
+                report.problems.push(format!(
+                    "Filesystem {} with UUID {} refers to thin device {}, which does not exist in the thin pool metadata",
+                    record.name, record.uuid, record.thin_id
+                ));
+                if rebuild {
+                    mdv.rm_record(&path)?;
+                    report.repairs.push(format!(
+                        "Removed the record of filesystem {} with UUID {}",
+                        record.name, record.uuid
+                    ));
+                } else {
+                    kept.push((path, record));
+                }
+            }
+            records = kept;
+
+            let recorded = records
+                .iter()
+                .map(|(_, record)| record.thin_id)
+                .collect::<HashSet<_>>();
+            for thin_id in thin_ids.iter().filter(|id| !recorded.contains(id)) {
+                report.problems.push(format!(
+                    "Thin device {thin_id} has no filesystem record; it can not be recovered automatically"
+                ));
+            }
+        }
+        None => report.problems.push(
+            "Filesystem records were not checked against the thin pool metadata, which could not be read".to_string(),
+        ),
+    }
+
+    for (path, record) in records.iter() {
+        let expected = format!("{}.json", uuid_to_string!(record.uuid));
+        if path.file_name().and_then(|n| n.to_str()) != Some(expected.as_str()) {
+            report.problems.push(format!(
+                "Record of filesystem {} with UUID {} is stored in {}",
+                record.name,
+                record.uuid,
+                path.display()
+            ));
+            if rebuild {
+                mdv.save_record(record)?;
+                mdv.rm_record(path)?;
+                report.repairs.push(format!(
+                    "Moved the record of filesystem {} with UUID {} to {}",
+                    record.name, record.uuid, expected
+                ));
+            }
+        }
+    }
+
+    let mut thin_id_owners = HashMap::new();
+    for (_, record) in records.iter() {
+        if let Some(other) = thin_id_owners.insert(record.thin_id, record.uuid) {
+            report.problems.push(format!(
+                "Filesystems with UUIDs {} and {} both refer to thin device {}; they must be resolved by hand",
+                other, record.uuid, record.thin_id
+            ));
+        }
+    }
+
+    // The oldest of the filesystems sharing a name keeps it.
+    records.sort_by_key(|(_, record)| record.created);
+    let mut names = HashSet::new();
+    for (_, record) in records.iter_mut() {
+        if names.insert(record.name.clone()) {
+            continue;
+        }
+        let new_name = format!("{}-{}", record.name, uuid_to_string!(record.uuid));
+        report.problems.push(format!(
+            "Filesystem name {} is used by more than one filesystem, including the filesystem with UUID {}",
+            record.name, record.uuid
+        ));
+        if rebuild {
+            report.repairs.push(format!(
+                "Renamed filesystem {} with UUID {} to {}",
+                record.name, record.uuid, new_name
+            ));
+            record.name = new_name;
+            mdv.save_record(record)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Activate the backstore of the pool and check the thin pool metadata and
+/// the filesystem records on the MDV. Returns true if the pool-level metadata
+/// was changed by a repair.
+fn check_activated(
+    pool_uuid: PoolUuid,
+    time: DateTime<Utc>,
+    metadata: &mut PoolSave,
+    infos: Vec<LStratisInfo>,
+    repairs: &[FsckRepair],
+    report: &mut FsckReport,
+) -> StratisResult<bool> {
+    let dev_uuids = infos
+        .iter()
+        .map(|info| info.bda.dev_uuid())
+        .collect::<Vec<_>>();
+    let version = match sigblock_version(&infos) {
+        Some(version) => version,
+        None => {
+            report.problems.push(
+                "The devices of the pool have different metadata versions; the pool can not be activated".to_string(),
+            );
+            return Ok(false);
+        }
+    };
+
+    let res =
+        ActiveBackstore::setup(pool_uuid, time, metadata, version, infos).and_then(|backstore| {
+            let device = backstore.device().ok_or_else(|| {
+                StratisError::Msg("The backstore of the pool has no cap device".to_string())
+            })?;
+            let mdv_dev = setup_flex_dev(
+                pool_uuid,
+                FlexRole::MetadataVolume,
+                device,
+                &metadata.flex_devs.meta_dev,
+            )?;
+            let mut mdv = MetadataVol::setup(pool_uuid, mdv_dev)?;
+            let res = check_thin_meta(pool_uuid, device, metadata, &mdv, repairs, report).and_then(
+                |(thin_ids, changed)| {
+                    check_mdv(
+                        &mdv,
+                        thin_ids.as_deref(),
+                        repairs.contains(&FsckRepair::RebuildMdv),
+                        report,
+                    )
+                    .map(|_| changed)
+                },
+            );
+            mdv.teardown(pool_uuid)?;
+            res
+        });
+
+    // Remove all the devices which were activated, whether or not the checks
+    // succeeded.
+    let cleanup = teardown(pool_uuid, version, &dev_uuids);
+    let changed = res?;
+    cleanup?;
+    Ok(changed)
+}
+
+/// Check a stopped pool identified by its UUID and perform the repairs
+/// requested.
+///
+/// The mount namespace of the calling process is unshared, so that the MDV
+/// is not visible outside of it while it is mounted.
+///
+/// Pools with per-device encryption can not be checked, as their devices
+/// must be unlocked before their metadata can be read.
+pub fn fsck_pool(pool_uuid: PoolUuid, repairs: &[FsckRepair]) -> StratisResult<FsckReport> {
+    verify_executables()?;
+    get_dm_init()?;
+
+    let (luks_infos, mut stratis_infos) = find_all()?;
+    if luks_infos.contains_key(&pool_uuid) {
+        return Err(StratisError::Msg(format!(
+            "Pool with UUID {pool_uuid} uses per-device encryption; it must be upgraded to the current metadata format to be checked"
+        )));
+    }
+    let infos = stratis_infos
+        .remove(&pool_uuid)
+        .unwrap_or_default()
+        .into_iter()
+        .map(LStratisInfo::from)
+        .collect::<Vec<_>>();
+    if infos.is_empty() {
+        return Err(StratisError::Msg(format!(
+            "No devices belonging to the pool with UUID {pool_uuid} were found"
+        )));
+    }
+    for info in infos.iter() {
+        ensure_unused(&info.dev_info.devnode)?;
+    }
+
+    let mut report = FsckReport {
+        pool_uuid,
+        devices: infos
+            .iter()
+            .map(|info| (info.bda.dev_uuid(), info.dev_info.devnode.clone()))
+            .collect(),
+        metadata_time: None,
+        problems: Vec::new(),
+        repairs: Vec::new(),
+    };
+
+    let copies = infos.iter().map(read_copy).collect::<Vec<_>>();
+    let (time, mut data, mut metadata) = match choose_copy(copies, &mut report.problems) {
+        Some(chosen) => chosen,
+        None if repairs.is_empty() => return Ok(report),
+        None => {
+            return Err(StratisError::Msg(format!(
+                "No valid metadata found for the pool with UUID {pool_uuid}; no repairs can be performed"
+            )))
+        }
+    };
+    report.metadata_time = Some(time);
+
+    let expected = inspectors::blockdev_uuids(&metadata)
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let found = report
+        .devices
+        .iter()
+        .map(|(dev_uuid, _)| *dev_uuid)
+        .collect::<HashSet<_>>();
+    for dev_uuid in expected.difference(&found) {
+        report.problems.push(format!(
+            "Device with UUID {dev_uuid} is recorded in the metadata but was not found"
+        ));
+    }
+    for (dev_uuid, devnode) in report.devices.iter() {
+        if !expected.contains(dev_uuid) {
+            report.problems.push(format!(
+                "Device {} with UUID {} is not recorded in the metadata",
+                devnode.display(),
+                dev_uuid
+            ));
+        }
+    }
+
+    let mut rewrite = repairs.contains(&FsckRepair::RewriteMda);
+    if expected.is_subset(&found) {
+        unshare_mount_namespace()?;
+        let _memfs = MemoryFilesystem::new()?;
+        let infos = infos
+            .into_iter()
+            .filter(|info| expected.contains(&info.bda.dev_uuid()))
+            .collect::<Vec<_>>();
+        if check_activated(pool_uuid, time, &mut metadata, infos, repairs, &mut report)? {
+            data = serde_json::to_vec(&metadata)?;
+            rewrite = true;
+        }
+    } else {
+        report.problems.push(
+            "The thin pool metadata and the filesystem records were not checked because devices are missing".to_string(),
+        );
+    }
+
+    if rewrite {
+        let now = Utc::now();
+        let devnodes = report
+            .devices
+            .iter()
+            .filter(|(dev_uuid, _)| expected.contains(dev_uuid))
+            .map(|(_, devnode)| devnode.clone())
+            .collect::<Vec<_>>();
+        for devnode in devnodes.iter() {
+            write_metadata(devnode, &now, &data)?;
+        }
+        report
+            .repairs
+            .push(format!("Wrote the metadata to {} devices", devnodes.len()));
+    }
+
+    Ok(report)
+}
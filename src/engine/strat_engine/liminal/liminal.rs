@@ -22,7 +22,9 @@ use crate::{
         engine::{DumpState, Pool, StateDiff, MAX_STRATIS_PASS_SIZE},
         shared::read_key_shared,
         strat_engine::{
-            backstore::{blockdev::InternalBlockDev, find_stratis_devs_by_uuid},
+            backstore::{
+                blockdev::InternalBlockDev, find_stratis_devs_by_uuid, get_devno_from_path,
+            },
             crypt::handle::v1::CryptHandle,
             dm::{
                 has_leftover_devices, has_leftover_devices_legacy, stop_partially_constructed_pool,
@@ -43,13 +45,16 @@ use crate::{
             pool::{v1, v2, AnyPool},
             serde_structs::{PoolFeatures, PoolSave},
             shared::tiers_to_bdas,
+            spare::thin_meta_spare,
             types::BDARecordResult,
+            upgrade::upgrade_pool,
         },
         structures::Table,
         types::{
             DevUuid, LockedPoolsInfo, MaybeInconsistent, Name, PoolEncryptionInfo, PoolIdentifier,
             PoolUuid, SizedKeyMemory, StoppedPoolsInfo, StratBlockDevDiff, StratSigblockVersion,
-            TokenUnlockMethod, UdevEngineEvent, UuidOrConflict,
+            ThinMetaSpareAction, ThinMetaSpareInfo, TokenUnlockMethod, UdevEngineEvent,
+            UpgradeReport, UuidOrConflict,
         },
         BlockDevTier,
     },
@@ -482,6 +487,112 @@ impl LiminalDevices {
         }
     }
 
+    /// Upgrade a stopped pool with legacy metadata to the current metadata
+    /// format, or, if dry_run is true, only report whether the upgrade is
+    /// possible. After an upgrade, the devices of the pool are read again so
+    /// that the pool is recorded with its new metadata.
+    pub fn upgrade_pool(
+        &mut self,
+        id: PoolIdentifier<PoolUuid>,
+        passphrase_fd: Option<RawFd>,
+        dry_run: bool,
+    ) -> StratisResult<UpgradeReport> {
+        let pool_uuid = match id {
+            PoolIdentifier::Uuid(u) => u,
+            PoolIdentifier::Name(ref n) => self
+                .name_to_uuid
+                .get(n)
+                .ok_or_else(|| StratisError::Msg(format!("Could not find a pool with name {n}")))
+                .and_then(|uc| uc.to_result())?,
+        };
+        let device_set = self.stopped_pools.get(&pool_uuid).ok_or_else(|| {
+            StratisError::Msg(format!(
+                "Requested pool with UUID {pool_uuid} was not found in stopped pools"
+            ))
+        })?;
+        let devnodes = device_set
+            .iter()
+            .map(|(_, info)| match info {
+                LInfo::Stratis(info) => info
+                    .luks
+                    .as_ref()
+                    .map(|luks| luks.dev_info.devnode.clone())
+                    .unwrap_or_else(|| info.dev_info.devnode.clone()),
+                LInfo::Luks(info) => info.dev_info.devnode.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let passphrase = if let Some(fd) = passphrase_fd {
+            let mut memory = SafeMemHandle::alloc(MAX_STRATIS_PASS_SIZE)?;
+            let len = read_key_shared(fd, memory.as_mut())?;
+            Some(SizedKeyMemory::new(memory, len))
+        } else {
+            None
+        };
+
+        let report = upgrade_pool(&devnodes, passphrase.as_ref(), dry_run)?;
+        if report.upgraded {
+            self.replace_stopped_pool(pool_uuid, read_stratis_infos(pool_uuid, &devnodes));
+        }
+
+        Ok(report)
+    }
+
+    /// Perform an operation on the spare thin metadata device of a stopped
+    /// pool. If the pool-level metadata was changed, the devices of the pool
+    /// are read again so that the pool is recorded with its new metadata.
+    pub fn thin_meta_spare(
+        &mut self,
+        id: PoolIdentifier<PoolUuid>,
+        action: ThinMetaSpareAction,
+    ) -> StratisResult<ThinMetaSpareInfo> {
+        let pool_uuid = match id {
+            PoolIdentifier::Uuid(u) => u,
+            PoolIdentifier::Name(ref n) => self
+                .name_to_uuid
+                .get(n)
+                .ok_or_else(|| StratisError::Msg(format!("Could not find a pool with name {n}")))
+                .and_then(|uc| uc.to_result())?,
+        };
+        let device_set = self.stopped_pools.get(&pool_uuid).ok_or_else(|| {
+            StratisError::Msg(format!(
+                "Requested pool with UUID {pool_uuid} was not found in stopped pools"
+            ))
+        })?;
+        let devnodes = device_set
+            .iter()
+            .map(|(_, info)| match info {
+                LInfo::Stratis(info) if info.luks.is_none() => Ok(info.dev_info.devnode.clone()),
+                _ => Err(StratisError::Msg(format!(
+                    "Pool with UUID {pool_uuid} uses per-device encryption; it must be upgraded to the current metadata format first"
+                ))),
+            })
+            .collect::<StratisResult<Vec<_>>>()?;
+
+        let infos = read_stratis_infos(pool_uuid, &devnodes);
+        let (info, changed) = thin_meta_spare(
+            pool_uuid,
+            infos.into_iter().map(LStratisInfo::from).collect(),
+            action,
+        )?;
+        if changed {
+            self.replace_stopped_pool(pool_uuid, read_stratis_infos(pool_uuid, &devnodes));
+        }
+
+        Ok(info)
+    }
+
+    /// Record a stopped pool with the devices given, replacing the devices
+    /// which were previously recorded for it.
+    fn replace_stopped_pool(&mut self, pool_uuid: PoolUuid, infos: Vec<StratisInfo>) {
+        let mut device_set = DeviceSet::new();
+        for info in infos {
+            device_set.process_info_add(DeviceInfo::Stratis(info));
+        }
+        self.stopped_pools.remove(&pool_uuid);
+        self.handle_stopped_pool(pool_uuid, device_set);
+    }
+
     /// Stop a pool, tear down the devicemapper devices, and store the pool information
     /// in an internal data structure for later starting.
     /// Returns true if the pool was torn down entirely, false if the pool is
@@ -1221,6 +1332,33 @@ impl Into<Value> for &LiminalDevices {
     }
 }
 
+/// Read the signature blocks of the given devices of a stopped pool. Devices
+/// whose signature blocks can not be read are omitted.
+fn read_stratis_infos(pool_uuid: PoolUuid, devnodes: &[PathBuf]) -> Vec<StratisInfo> {
+    devnodes
+        .iter()
+        .filter_map(
+            |devnode| match (bda_wrapper(devnode), get_devno_from_path(devnode)) {
+                (Ok(Ok(Some(bda))), Ok(device_number)) => Some(StratisInfo {
+                    dev_info: StratisDevInfo {
+                        device_number,
+                        devnode: devnode.clone(),
+                    },
+                    bda,
+                }),
+                _ => {
+                    warn!(
+                        "Failed to read BDA of device {} of pool with UUID {}; ignoring",
+                        devnode.display(),
+                        pool_uuid
+                    );
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
 /// Read the BDA and MDA information for a set of devices that has been
 /// determined to be a part of the same pool.
 fn load_stratis_metadata(
@@ -8,4 +8,9 @@ mod identify;
 mod liminal;
 mod setup;
 
-pub use self::{device_info::DeviceSet, identify::find_all, liminal::LiminalDevices};
+pub use self::{
+    device_info::{DeviceSet, LStratisDevInfo, LStratisInfo},
+    identify::find_all,
+    liminal::LiminalDevices,
+    setup::{get_blockdevs, get_blockdevs_legacy},
+};
@@ -6,10 +6,13 @@ use std::io::{Read, Seek};
 
 use chrono::{DateTime, Utc};
 
+use devicemapper::Bytes;
+
 use crate::{
     engine::{
         strat_engine::{
             metadata::{
+                history::MetadataHistory,
                 mda,
                 sizes::{BDAExtendedSize, BlockdevSize, MDADataSize, STATIC_HEADER_SIZE},
                 static_header::{MetadataLocation, StaticHeader, StratisIdentifiers},
@@ -25,6 +28,7 @@ use crate::{
 pub struct BDA {
     pub(in super::super) header: StaticHeader,
     regions: mda::MDARegions,
+    history: MetadataHistory,
 }
 
 impl Default for BDA {
@@ -56,8 +60,13 @@ impl BDA {
         );
 
         let regions = mda::MDARegions::new(header.mda_size);
+        let history = MetadataHistory::new(header.reserved_size);
 
-        BDA { header, regions }
+        BDA {
+            header,
+            regions,
+            history,
+        }
     }
 
     /// Initialize a blockdev with a Stratis BDA.
@@ -70,6 +79,8 @@ impl BDA {
         self.regions
             .initialize(STATIC_HEADER_SIZE.sectors().bytes(), f)?;
 
+        self.history.initialize(self.history_offset(), f)?;
+
         Ok(())
     }
 
@@ -88,17 +99,57 @@ impl BDA {
         // error or invalid MDA headers.
         let regions =
             mda::MDARegions::load(STATIC_HEADER_SIZE.sectors().bytes(), header.mda_size, f)?;
-
-        Ok(Some(BDA { header, regions }))
+        let history = MetadataHistory::load(
+            header.mda_size.bda_size().sectors().bytes(),
+            header.reserved_size,
+            f,
+        )?;
+
+        Ok(Some(BDA {
+            header,
+            regions,
+            history,
+        }))
     }
 
-    /// Save metadata to the disk
+    /// Save metadata to the disk and record it in the metadata history
     pub fn save_state<F>(
         &mut self,
         time: &DateTime<Utc>,
         metadata: &[u8],
         f: &mut F,
     ) -> StratisResult<()>
+    where
+        F: Seek + SyncAll,
+    {
+        self.save_state_without_history(time, metadata, f)?;
+
+        // The history is a convenience for recovery; failing to record a
+        // generation must not cause the metadata update itself to fail.
+        let offset = self.history_offset();
+        let max_size = self.history.max_data_size();
+        if Bytes::from(metadata.len()) > max_size {
+            warn!(
+                "Metadata of {} bytes written to device {} exceeds the {} bytes that a slot of the metadata history can hold; it is not recorded in the history",
+                metadata.len(),
+                self.dev_uuid(),
+                *max_size
+            );
+        } else if let Err(e) = self.history.save(offset, time, metadata, f) {
+            warn!("Failed to record metadata generation in history: {}", e);
+        }
+
+        Ok(())
+    }
+
+    /// Save metadata to the disk without recording it in the metadata
+    /// history
+    pub fn save_state_without_history<F>(
+        &mut self,
+        time: &DateTime<Utc>,
+        metadata: &[u8],
+        f: &mut F,
+    ) -> StratisResult<()>
     where
         F: Seek + SyncAll,
     {
@@ -115,12 +166,66 @@ impl BDA {
             .load_state(STATIC_HEADER_SIZE.sectors().bytes(), &mut f)
     }
 
+    /// Read the metadata that was current before the latest metadata was
+    /// written from the disk
+    pub fn load_previous_state<F>(&self, mut f: &mut F) -> StratisResult<Option<Vec<u8>>>
+    where
+        F: Read + Seek,
+    {
+        self.regions
+            .load_previous_state(STATIC_HEADER_SIZE.sectors().bytes(), &mut f)
+    }
+
+    /// The offset of the metadata history, which occupies the space reserved
+    /// after the BDA proper.
+    fn history_offset(&self) -> Bytes {
+        self.header.mda_size.bda_size().sectors().bytes()
+    }
+
+    /// The times at which the generations of metadata retained in the
+    /// metadata history were written, newest first.
+    pub fn metadata_generations(&self) -> Vec<DateTime<Utc>> {
+        self.history.generations()
+    }
+
+    /// The maximum size of a generation of metadata that the metadata history
+    /// can hold. Larger generations are not recorded.
+    pub fn max_generation_size(&self) -> Bytes {
+        self.history.max_data_size()
+    }
+
+    /// Read the generation of metadata written at the given time from the
+    /// metadata history. Returns None if the history does not contain it.
+    pub fn load_generation<F>(
+        &self,
+        time: &DateTime<Utc>,
+        f: &mut F,
+    ) -> StratisResult<Option<Vec<u8>>>
+    where
+        F: Read + Seek,
+    {
+        self.history.load_generation(self.history_offset(), time, f)
+    }
+
     /// The time when the most recent metadata was written to the BDA,
     /// if any.
     pub fn last_update_time(&self) -> Option<&DateTime<Utc>> {
         self.regions.last_update_time()
     }
 
+    /// The time when the metadata preceding the most recent metadata was
+    /// written to the BDA, if any.
+    pub fn previous_update_time(&self) -> Option<&DateTime<Utc>> {
+        self.regions.previous_update_time()
+    }
+
+    /// The headers of the primary MDA regions. None indicates that no
+    /// variable length metadata has been written to a region.
+    #[cfg(feature = "extras")]
+    pub fn mda_headers(&self) -> &[Option<mda::MDAHeader>] {
+        self.regions.headers()
+    }
+
     /// The UUID of the device.
     pub fn dev_uuid(&self) -> DevUuid {
         self.header.identifiers.device_uuid
@@ -259,6 +364,7 @@ mod tests {
         /// Reload BDA and verify that new BDA has correct update time.
         /// Load state using new BDA and verify correct state.
         /// Save metadata again, and reload one more time, verifying new timestamp.
+        /// Verify that the previous state and its timestamp are retained.
         fn check_state(
             ref sh in static_header_strategy(),
             ref state in vec(num::u8::ANY, 1..100),
@@ -287,12 +393,18 @@ mod tests {
             prop_assert!(loaded_state.map(|s| &s == state).unwrap_or(false));
             prop_assert!(bda.last_update_time().map(|t| t == &current_time).unwrap_or(false));
 
+            prop_assert!(bda.load_previous_state(&mut buf).unwrap().is_none());
+
+            let previous_time = current_time;
             let current_time = Utc::now();
             bda.save_state(&current_time, next_state, &mut buf)
                 .unwrap();
             let loaded_state = bda.load_state(&mut buf).unwrap();
             prop_assert!(loaded_state.map(|s| &s == next_state).unwrap_or(false));
             prop_assert!(bda.last_update_time().map(|t| t == &current_time).unwrap_or(false));
+            let previous_state = bda.load_previous_state(&mut buf).unwrap();
+            prop_assert!(previous_state.map(|s| &s == state).unwrap_or(false));
+            prop_assert!(bda.previous_update_time().map(|t| t == &previous_time).unwrap_or(false));
 
         }
     }
@@ -0,0 +1,292 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Management of the metadata history, a ring of the most recently written
+// generations of the variable length metadata, kept in the space reserved
+// immediately after the BDA proper.
+//
+// The reserved space is divided evenly between the slots of the ring. With
+// the 3 MiB reserved on every device, each slot holds at most 384 KiB of
+// metadata, less the size of its header. A generation of metadata which is
+// larger than that is not recorded in the history.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use chrono::{DateTime, Utc};
+
+use devicemapper::{Bytes, Sectors};
+
+use crate::{
+    engine::strat_engine::{
+        metadata::{
+            mda::MDAHeader,
+            sizes::{mda_size, ReservedSize},
+        },
+        writing::SyncAll,
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// The number of generations of metadata retained in the history.
+pub const NUM_HISTORY_SLOTS: usize = 8;
+
+/// Manages the slots of the metadata history. Each slot holds a single
+/// generation of the variable length metadata, preceded by a header with
+/// the same format as an MDA region header.
+#[derive(Debug)]
+pub struct MetadataHistory {
+    /// The size of a single slot.
+    slot_size: Sectors,
+    /// The headers of the slots. A value of None indicates that the slot
+    /// holds no valid generation of metadata.
+    headers: Vec<Option<MDAHeader>>,
+}
+
+impl MetadataHistory {
+    /// Construct an empty metadata history occupying the reserved space.
+    pub fn new(reserved_size: ReservedSize) -> MetadataHistory {
+        MetadataHistory {
+            slot_size: reserved_size.sectors() / NUM_HISTORY_SLOTS,
+            headers: (0..NUM_HISTORY_SLOTS).map(|_| None).collect(),
+        }
+    }
+
+    /// Calculate the offset from start of device of a slot.
+    fn slot_offset(offset: Bytes, index: usize, slot_size: Sectors) -> StratisResult<u64> {
+        convert_int!(*(offset + slot_size.bytes() * index), u128, u64)
+    }
+
+    /// The maximum size of a generation of metadata that a slot can hold.
+    pub fn max_data_size(&self) -> Bytes {
+        let slot_size = self.slot_size.bytes();
+        let hdr_size = Bytes::from(mda_size::_MDA_REGION_HDR_SIZE);
+        if slot_size > hdr_size {
+            slot_size - hdr_size
+        } else {
+            Bytes(0)
+        }
+    }
+
+    /// Initialize the headers of all the slots, so that the history is
+    /// empty. offset is the location of the reserved space on the device.
+    pub fn initialize<F>(&self, offset: Bytes, f: &mut F) -> StratisResult<()>
+    where
+        F: Seek + SyncAll,
+    {
+        if self.max_data_size() == Bytes(0) {
+            return Ok(());
+        }
+
+        let hdr_buf = MDAHeader::default().to_buf();
+        for index in 0..NUM_HISTORY_SLOTS {
+            f.seek(SeekFrom::Start(Self::slot_offset(
+                offset,
+                index,
+                self.slot_size,
+            )?))?;
+            f.write_all(&hdr_buf)?;
+        }
+
+        f.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Construct a MetadataHistory from data on the disk. A slot with an
+    /// invalid header is treated as empty, since the reserved space on a
+    /// device that was initialized before the history was introduced may
+    /// contain arbitrary data.
+    ///
+    /// Returns an error only if there is an I/O error.
+    pub fn load<F>(
+        offset: Bytes,
+        reserved_size: ReservedSize,
+        f: &mut F,
+    ) -> StratisResult<MetadataHistory>
+    where
+        F: Read + Seek,
+    {
+        let mut history = MetadataHistory::new(reserved_size);
+        if history.max_data_size() == Bytes(0) {
+            return Ok(history);
+        }
+
+        for index in 0..NUM_HISTORY_SLOTS {
+            let mut hdr_buf = [0u8; mda_size::_MDA_REGION_HDR_SIZE];
+            f.seek(SeekFrom::Start(Self::slot_offset(
+                offset,
+                index,
+                history.slot_size,
+            )?))?;
+            f.read_exact(&mut hdr_buf)?;
+            history.headers[index] = MDAHeader::from_buf(&hdr_buf)
+                .map_err(|e| {
+                    debug!("Ignoring invalid metadata history slot {}: {}", index, e);
+                })
+                .ok()
+                .flatten();
+        }
+
+        Ok(history)
+    }
+
+    /// Write a generation of metadata to the history, overwriting the oldest
+    /// generation if all slots are in use.
+    ///
+    /// Returns an error if the metadata does not fit in a slot or if there
+    /// is an error when writing the data.
+    pub fn save<F>(
+        &mut self,
+        offset: Bytes,
+        time: &DateTime<Utc>,
+        data: &[u8],
+        f: &mut F,
+    ) -> StratisResult<()>
+    where
+        F: Seek + SyncAll,
+    {
+        let used = Bytes::from(data.len());
+        let max_available = self.max_data_size();
+        if used > max_available {
+            return Err(StratisError::Msg(format!(
+                "metadata length {used} exceeds history slot available {max_available}"
+            )));
+        }
+
+        let index = self
+            .headers
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, h)| h.as_ref().map(|h| *h.last_updated()))
+            .map(|(index, _)| index)
+            .expect("NUM_HISTORY_SLOTS > 0");
+
+        let header = MDAHeader::new(*time, data);
+        f.seek(SeekFrom::Start(Self::slot_offset(
+            offset,
+            index,
+            self.slot_size,
+        )?))?;
+        f.write_all(&header.to_buf())?;
+        f.write_all(data)?;
+        f.sync_all()?;
+
+        self.headers[index] = Some(header);
+
+        Ok(())
+    }
+
+    /// The times at which the generations of metadata in the history were
+    /// written, newest first.
+    pub fn generations(&self) -> Vec<DateTime<Utc>> {
+        let mut times = self
+            .headers
+            .iter()
+            .flatten()
+            .map(|h| *h.last_updated())
+            .collect::<Vec<_>>();
+        times.sort_unstable_by(|a, b| b.cmp(a));
+        times
+    }
+
+    /// Load the generation of metadata written at the given time.
+    /// Returns None if the history contains no such generation.
+    pub fn load_generation<F>(
+        &self,
+        offset: Bytes,
+        time: &DateTime<Utc>,
+        f: &mut F,
+    ) -> StratisResult<Option<Vec<u8>>>
+    where
+        F: Read + Seek,
+    {
+        match self.headers.iter().enumerate().find_map(|(index, h)| {
+            h.as_ref()
+                .filter(|h| h.last_updated() == time)
+                .map(|h| (index, h))
+        }) {
+            Some((index, header)) => {
+                f.seek(SeekFrom::Start(
+                    Self::slot_offset(offset, index, self.slot_size)?
+                        + mda_size::_MDA_REGION_HDR_SIZE as u64,
+                ))?;
+                header.load_region(f).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use chrono::Duration;
+
+    use crate::engine::strat_engine::metadata::sizes::ReservedSize;
+
+    use super::*;
+
+    const RESERVED: Sectors = Sectors(6144);
+
+    #[test]
+    /// Verify that only the newest NUM_HISTORY_SLOTS generations are
+    /// retained and that each can be read back.
+    fn test_history_ring() {
+        let offset = Bytes(4096);
+        let buf_length = convert_test!(*(offset + RESERVED.bytes()), u128, usize);
+        let mut buf = Cursor::new(vec![0xffu8; buf_length]);
+
+        let history = MetadataHistory::load(offset, ReservedSize::new(RESERVED), &mut buf).unwrap();
+        assert!(history.generations().is_empty());
+
+        let mut history = MetadataHistory::new(ReservedSize::new(RESERVED));
+        history.initialize(offset, &mut buf).unwrap();
+
+        let start = Utc::now();
+        let times = (0..NUM_HISTORY_SLOTS + 3)
+            .map(|i| start + Duration::seconds(convert_test!(i, usize, i64)))
+            .collect::<Vec<_>>();
+        for (i, time) in times.iter().enumerate() {
+            history
+                .save(offset, time, format!("generation {i}").as_bytes(), &mut buf)
+                .unwrap();
+        }
+
+        let history = MetadataHistory::load(offset, ReservedSize::new(RESERVED), &mut buf).unwrap();
+        let generations = history.generations();
+        assert_eq!(generations.len(), NUM_HISTORY_SLOTS);
+        assert_eq!(generations[0], times[times.len() - 1]);
+        assert_eq!(
+            generations[NUM_HISTORY_SLOTS - 1],
+            times[times.len() - NUM_HISTORY_SLOTS]
+        );
+        assert_eq!(
+            history
+                .load_generation(offset, &times[times.len() - 1], &mut buf)
+                .unwrap(),
+            Some(format!("generation {}", times.len() - 1).into_bytes())
+        );
+        assert_eq!(
+            history
+                .load_generation(offset, &times[0], &mut buf)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    /// Verify that metadata too large for a slot is rejected.
+    fn test_history_too_large() {
+        let offset = Bytes(0);
+        let buf_length = convert_test!(*RESERVED.bytes(), u128, usize);
+        let mut buf = Cursor::new(vec![0u8; buf_length]);
+
+        let mut history = MetadataHistory::new(ReservedSize::new(RESERVED));
+        history.initialize(offset, &mut buf).unwrap();
+        let data = vec![0u8; buf_length];
+        assert_matches!(history.save(offset, &Utc::now(), &data, &mut buf), Err(_));
+        assert!(history.generations().is_empty());
+    }
+}
@@ -169,11 +169,7 @@ impl MDARegions {
             return Err(StratisError::Msg(err_msg));
         }
 
-        let header = MDAHeader {
-            last_updated: *time,
-            used: MetaDataSize::new(used),
-            data_crc: CASTAGNOLI.checksum(data),
-        };
+        let header = MDAHeader::new(*time, data);
         let hdr_buf = header.to_buf();
 
         // Write data to a region specified by index.
@@ -210,8 +206,36 @@ impl MDARegions {
     where
         F: Read + Seek,
     {
-        let newer_region = self.newer();
-        let mda = match self.mda_headers[newer_region] {
+        self.load_region_state(header_size, self.newer(), f)
+    }
+
+    /// Load metadata from the older MDA region, i.e., the metadata that was
+    /// current before the most recent update.
+    /// In case there is no record of metadata in the older region, return
+    /// None.
+    pub fn load_previous_state<F>(
+        &self,
+        header_size: Bytes,
+        f: &mut F,
+    ) -> StratisResult<Option<Vec<u8>>>
+    where
+        F: Read + Seek,
+    {
+        self.load_region_state(header_size, self.older(), f)
+    }
+
+    /// Load metadata from the MDA region with the given index, falling back
+    /// to its backup copy if the primary copy can not be read.
+    fn load_region_state<F>(
+        &self,
+        header_size: Bytes,
+        index: usize,
+        f: &mut F,
+    ) -> StratisResult<Option<Vec<u8>>>
+    where
+        F: Read + Seek,
+    {
+        let mda = match self.mda_headers[index] {
             None => return Ok(None),
             Some(ref mda) => mda,
         };
@@ -228,8 +252,8 @@ impl MDARegions {
 
         // TODO: Figure out if there is an action to take if the
         // first read returns an error.
-        load_region(newer_region)
-            .or_else(|_| load_region(newer_region + mda_size::NUM_PRIMARY_MDA_REGIONS))
+        load_region(index)
+            .or_else(|_| load_region(index + mda_size::NUM_PRIMARY_MDA_REGIONS))
             .map(Some)
     }
 
@@ -261,6 +285,20 @@ impl MDARegions {
             .map(|h| &h.last_updated)
     }
 
+    /// The time of the update before the last update for these MDA regions
+    pub fn previous_update_time(&self) -> Option<&DateTime<Utc>> {
+        self.mda_headers[self.older()]
+            .as_ref()
+            .map(|h| &h.last_updated)
+    }
+
+    /// The MDA headers of the primary regions. None indicates that no
+    /// variable length metadata has been written to a region.
+    #[cfg(feature = "extras")]
+    pub fn headers(&self) -> &[Option<MDAHeader>] {
+        &self.mda_headers
+    }
+
     #[cfg(test)]
     /// An invariant on MDARegions structs.
     /// 1. If an MDAHeader in the regions is not None, then its used
@@ -319,6 +357,32 @@ impl Default for MDAHeader {
 }
 
 impl MDAHeader {
+    /// Construct an MDAHeader describing the given data written at time.
+    pub(super) fn new(last_updated: DateTime<Utc>, data: &[u8]) -> MDAHeader {
+        MDAHeader {
+            last_updated,
+            used: MetaDataSize::new(Bytes::from(data.len())),
+            data_crc: CASTAGNOLI.checksum(data),
+        }
+    }
+
+    /// The time at which the data described by this header was written.
+    pub fn last_updated(&self) -> &DateTime<Utc> {
+        &self.last_updated
+    }
+
+    /// The size of the data described by this header.
+    #[cfg(feature = "extras")]
+    pub fn used(&self) -> Bytes {
+        self.used.bytes()
+    }
+
+    /// The CRC of the data described by this header.
+    #[cfg(feature = "extras")]
+    pub fn data_crc(&self) -> u32 {
+        self.data_crc
+    }
+
     /// Parse a valid MDAHeader from buf.
     /// If the amount used by the variable length metadata is 0, return None,
     /// as this means that no variable length metadata has been written.
@@ -343,7 +407,9 @@ impl MDAHeader {
     /// Return an error for a bad checksum.
     /// Return None if there is no MDAHeader to be read. This is detected if the
     /// timestamp region in the buffer is 0.
-    fn from_buf(buf: &[u8; mda_size::_MDA_REGION_HDR_SIZE]) -> StratisResult<Option<MDAHeader>> {
+    pub(super) fn from_buf(
+        buf: &[u8; mda_size::_MDA_REGION_HDR_SIZE],
+    ) -> StratisResult<Option<MDAHeader>> {
         if LittleEndian::read_u32(&buf[..4]) != CASTAGNOLI.checksum(&buf[4..]) {
             return Err(StratisError::Msg("MDA region header CRC".into()));
         }
@@ -367,7 +433,7 @@ impl MDAHeader {
         MDAHeader::parse_buf(buf).transpose()
     }
 
-    fn to_buf(&self) -> [u8; mda_size::_MDA_REGION_HDR_SIZE] {
+    pub(super) fn to_buf(&self) -> [u8; mda_size::_MDA_REGION_HDR_SIZE] {
         // Unsigned casts are always safe, as sec and nsec values are never negative
         assert!(self.last_updated.timestamp() >= 0);
 
@@ -393,7 +459,7 @@ impl MDAHeader {
     /// Return an error if the data can not be read, since the existence
     /// of the MDAHeader implies that the data must be available.
     // MDAHeader cannot seek because it doesn't know which region it's in
-    fn load_region<F>(&self, f: &mut F) -> StratisResult<Vec<u8>>
+    pub(super) fn load_region<F>(&self, f: &mut F) -> StratisResult<Vec<u8>>
     where
         F: Read,
     {
@@ -11,6 +11,7 @@ macro_rules! bytes {
 }
 
 mod bda;
+mod history;
 mod mda;
 mod sizes;
 mod static_header;
@@ -9,23 +9,30 @@ mod device;
 mod devlinks;
 mod dm;
 mod engine;
+#[cfg(feature = "extras")]
+mod fsck;
 mod keys;
 mod liminal;
 mod metadata;
 mod names;
 mod ns;
+mod offline;
 mod pool;
 mod serde_structs;
 mod shared;
+mod spare;
 mod thinpool;
 mod types;
 mod udev;
+mod upgrade;
 mod writing;
 
 #[cfg(feature = "extras")]
 pub use self::{
     backstore::ProcessedPathInfos,
+    fsck::{fsck_pool, FsckRepair, FsckReport},
     pool::{inspection as pool_inspection, v1::StratPool},
+    upgrade::upgrade_pool,
 };
 
 pub use self::{
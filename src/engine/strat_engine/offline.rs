@@ -0,0 +1,355 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Operations shared by the tools which read, check, and change stopped pools:
+// choosing among the copies of the pool-level metadata on the devices of the
+// pool, writing the metadata back, and activating the backstore and flex
+// devices of the pool without starting it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::OpenOptions,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+
+use devicemapper::{Device, LinearDev, Sectors};
+
+use crate::{
+    engine::{
+        strat_engine::{
+            backstore::backstore::{v1, v2, InternalBackstore},
+            dm::{get_dm, stop_partially_constructed_pool, stop_partially_constructed_pool_legacy},
+            liminal::{get_blockdevs, get_blockdevs_legacy, LStratisDevInfo, LStratisInfo},
+            metadata::{static_header, BDA},
+            names::{format_flex_ids, FlexRole},
+            pool::inspection::inspectors,
+            serde_structs::{BackstoreSave, PoolSave, Recordable},
+            thinpool::linear_table,
+        },
+        types::{DevUuid, PoolUuid, StratSigblockVersion, TokenUnlockMethod},
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// A copy of the pool-level metadata read from one device. The contents are
+/// an error if the copy could not be read, parsed, or did not pass
+/// inspection.
+pub struct MdaCopy {
+    devnode: PathBuf,
+    time: Option<DateTime<Utc>>,
+    contents: Result<(Vec<u8>, PoolSave), String>,
+}
+
+/// Fail if the device is in use, e.g., because its pool is started.
+pub fn ensure_unused(devnode: &Path) -> StratisResult<()> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_EXCL)
+        .open(devnode)
+        .map(|_| ())
+        .map_err(|e| {
+            StratisError::Msg(format!(
+                "Device {} is in use; the pool must be stopped: {e}",
+                devnode.display()
+            ))
+        })
+}
+
+/// Read the copy of the pool-level metadata on a device.
+pub fn read_copy(info: &LStratisInfo) -> MdaCopy {
+    let devnode = info.dev_info.devnode.clone();
+    let time = info.bda.last_update_time().copied();
+    let contents = if time.is_none() {
+        Err("no metadata has been written".to_string())
+    } else {
+        OpenOptions::new()
+            .read(true)
+            .open(&devnode)
+            .map_err(|e| format!("device could not be opened: {e}"))
+            .and_then(|mut f| {
+                info.bda
+                    .load_state(&mut f)
+                    .map_err(|e| format!("metadata could not be read: {e}"))
+            })
+            .and_then(|data| data.ok_or_else(|| "no metadata found".to_string()))
+            .and_then(|data| {
+                let metadata = serde_json::from_slice(&data)
+                    .map_err(|e| format!("metadata could not be parsed: {e}"))?;
+                inspectors::check(&metadata)
+                    .map_err(|e| format!("metadata did not pass inspection: {e}"))?;
+                Ok((data, metadata))
+            })
+    };
+    MdaCopy {
+        devnode,
+        time,
+        contents,
+    }
+}
+
+/// Choose the newest valid copy of the pool-level metadata. Record a problem
+/// for every copy which is not valid, which is older than the chosen copy, or
+/// which differs from it.
+pub fn choose_copy(
+    mut copies: Vec<MdaCopy>,
+    problems: &mut Vec<String>,
+) -> Option<(DateTime<Utc>, Vec<u8>, PoolSave)> {
+    let chosen = copies
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| match (c.time, &c.contents) {
+            (Some(time), Ok(_)) => Some((i, time)),
+            _ => None,
+        })
+        .max_by_key(|(_, time)| *time);
+
+    for copy in copies.iter() {
+        match (&copy.contents, chosen) {
+            (Err(e), _) => problems.push(format!(
+                "Metadata on {} can not be used: {e}",
+                copy.devnode.display()
+            )),
+            (Ok((data, _)), Some((i, time))) => {
+                if copy.time < Some(time) {
+                    problems.push(format!(
+                        "Metadata on {} is older than the newest valid metadata, written at {time}",
+                        copy.devnode.display()
+                    ));
+                } else if copies[i]
+                    .contents
+                    .as_ref()
+                    .map(|(chosen_data, _)| chosen_data != data)
+                    .unwrap_or(false)
+                {
+                    problems.push(format!(
+                        "Metadata on {} differs from the newest valid metadata, written at {time}",
+                        copy.devnode.display()
+                    ));
+                }
+            }
+            (Ok(_), None) => (),
+        }
+    }
+
+    match chosen {
+        Some((i, time)) => copies
+            .swap_remove(i)
+            .contents
+            .ok()
+            .map(|(data, metadata)| (time, data, metadata)),
+        None => {
+            problems.push("No valid metadata found on any device".to_string());
+            None
+        }
+    }
+}
+
+/// Write the pool-level metadata to a device.
+pub fn write_metadata(devnode: &Path, time: &DateTime<Utc>, data: &[u8]) -> StratisResult<()> {
+    let mut f = OpenOptions::new().read(true).write(true).open(devnode)?;
+    let header = static_header(&mut f)?.ok_or_else(|| {
+        StratisError::Msg(format!(
+            "No Stratis signature found on {}",
+            devnode.display()
+        ))
+    })?;
+    let mut bda = BDA::load(header, &mut f)?.ok_or_else(|| {
+        StratisError::Msg(format!("No Stratis BDA found on {}", devnode.display()))
+    })?;
+    bda.save_state(time, data, &mut f)
+}
+
+/// The backstore of a pool, activated without starting the pool.
+pub enum ActiveBackstore {
+    Legacy(v1::Backstore),
+    Current(v2::Backstore),
+}
+
+impl ActiveBackstore {
+    /// Activate the backstore described by the metadata on the given
+    /// devices. Encrypted pools are unlocked using any of their tokens.
+    pub fn setup(
+        pool_uuid: PoolUuid,
+        time: DateTime<Utc>,
+        metadata: &PoolSave,
+        version: StratSigblockVersion,
+        infos: Vec<LStratisInfo>,
+    ) -> StratisResult<ActiveBackstore> {
+        let (infos, bdas): (HashMap<_, _>, HashMap<_, _>) = infos
+            .into_iter()
+            .map(|info| {
+                let dev_uuid = info.bda.dev_uuid();
+                let (dev_info, bda) = <(LStratisDevInfo, BDA)>::from(info);
+                ((dev_uuid, Box::new(dev_info)), (dev_uuid, bda))
+            })
+            .unzip();
+        match version {
+            StratSigblockVersion::V1 => {
+                let (datadevs, cachedevs) =
+                    get_blockdevs_legacy(&metadata.backstore, &infos, bdas).map_err(|(e, _)| e)?;
+                v1::Backstore::setup(pool_uuid, &metadata.backstore, datadevs, cachedevs, time)
+                    .map(ActiveBackstore::Legacy)
+                    .map_err(|(e, _)| e)
+            }
+            StratSigblockVersion::V2 => {
+                let (datadevs, cachedevs) =
+                    get_blockdevs(&metadata.backstore, &infos, bdas).map_err(|(e, _)| e)?;
+                v2::Backstore::setup(
+                    pool_uuid,
+                    metadata,
+                    datadevs,
+                    cachedevs,
+                    time,
+                    TokenUnlockMethod::Any,
+                    None,
+                )
+                .map(ActiveBackstore::Current)
+                .map_err(|(e, _)| e)
+            }
+        }
+    }
+
+    pub fn device(&self) -> Option<Device> {
+        match self {
+            ActiveBackstore::Legacy(backstore) => backstore.device(),
+            ActiveBackstore::Current(backstore) => backstore.device(),
+        }
+    }
+
+    /// Allocate segments of the given sizes from the backstore.
+    pub fn alloc(
+        &mut self,
+        pool_uuid: PoolUuid,
+        sizes: &[Sectors],
+    ) -> StratisResult<Option<Vec<(Sectors, Sectors)>>> {
+        match self {
+            ActiveBackstore::Legacy(backstore) => backstore.alloc(pool_uuid, sizes),
+            ActiveBackstore::Current(backstore) => backstore.alloc(pool_uuid, sizes),
+        }
+    }
+
+    /// The metadata describing the backstore in its current state.
+    pub fn record(&self) -> BackstoreSave {
+        match self {
+            ActiveBackstore::Legacy(backstore) => backstore.record(),
+            ActiveBackstore::Current(backstore) => backstore.record(),
+        }
+    }
+}
+
+/// The signature block version shared by all the given devices, or None if
+/// the devices have different versions.
+pub fn sigblock_version(infos: &[LStratisInfo]) -> Option<StratSigblockVersion> {
+    let versions = infos
+        .iter()
+        .map(|info| info.bda.sigblock_version())
+        .collect::<HashSet<_>>();
+    match versions.into_iter().collect::<Vec<_>>().as_slice() {
+        [version] => Some(*version),
+        _ => None,
+    }
+}
+
+/// Remove all the devices which were activated for the pool without starting
+/// it.
+pub fn teardown(
+    pool_uuid: PoolUuid,
+    version: StratSigblockVersion,
+    dev_uuids: &[DevUuid],
+) -> StratisResult<()> {
+    match version {
+        StratSigblockVersion::V1 => stop_partially_constructed_pool_legacy(pool_uuid, dev_uuids),
+        StratSigblockVersion::V2 => stop_partially_constructed_pool(pool_uuid),
+    }
+}
+
+/// Set up a linear device with the given role over segments of the cap
+/// device.
+pub fn setup_flex_dev(
+    pool_uuid: PoolUuid,
+    role: FlexRole,
+    device: Device,
+    segments: &[(Sectors, Sectors)],
+) -> StratisResult<LinearDev> {
+    let (dm_name, dm_uuid) = format_flex_ids(pool_uuid, role);
+    Ok(LinearDev::setup(
+        get_dm(),
+        &dm_name,
+        Some(&dm_uuid),
+        linear_table::segs_to_table(device, segments),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn copy(devnode: &str, time: Option<i64>, data: Option<&str>) -> MdaCopy {
+        MdaCopy {
+            devnode: PathBuf::from(devnode),
+            time: time.map(|t| Utc.timestamp_opt(t, 0).unwrap()),
+            contents: match data {
+                Some(d) => Ok((
+                    d.as_bytes().to_vec(),
+                    serde_json::from_value(json!({
+                        "name": "pool",
+                        "backstore": {
+                            "data_tier": {"blockdev": {"allocs": [[]], "devs": []}},
+                            "cap": {"allocs": []},
+                        },
+                        "flex_devs": {
+                            "meta_dev": [],
+                            "thin_meta_dev": [],
+                            "thin_data_dev": [],
+                            "thin_meta_dev_spare": [],
+                        },
+                        "thinpool_dev": {"data_block_size": 2048},
+                    }))
+                    .unwrap(),
+                )),
+                None => Err("corrupt".to_string()),
+            },
+        }
+    }
+
+    #[test]
+    /// Verify that the newest valid copy is chosen and that older, differing,
+    /// and invalid copies are reported.
+    fn test_choose_copy() {
+        let mut problems = Vec::new();
+        let (time, data, _) = choose_copy(
+            vec![
+                copy("/dev/a", Some(20), None),
+                copy("/dev/b", Some(10), Some("new")),
+                copy("/dev/c", Some(10), Some("other")),
+                copy("/dev/d", Some(5), Some("old")),
+            ],
+            &mut problems,
+        )
+        .unwrap();
+        assert_eq!(time, Utc.timestamp_opt(10, 0).unwrap());
+        assert!(data == b"new" || data == b"other");
+        assert_eq!(problems.len(), 3);
+        assert!(problems.iter().any(|p| p.contains("/dev/a")));
+        assert!(problems.iter().any(|p| p.contains("/dev/d")));
+    }
+
+    #[test]
+    /// Verify that no copy is chosen if none is valid.
+    fn test_choose_copy_none_valid() {
+        let mut problems = Vec::new();
+        assert!(choose_copy(
+            vec![copy("/dev/a", Some(20), None), copy("/dev/b", None, None)],
+            &mut problems,
+        )
+        .is_none());
+        assert_eq!(problems.len(), 3);
+    }
+}
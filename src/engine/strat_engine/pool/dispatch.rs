@@ -2,7 +2,10 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::{collections::HashSet, path::Path};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
 
 use either::Either;
 use serde_json::Value;
@@ -12,15 +15,19 @@ use devicemapper::{Bytes, Sectors};
 use crate::{
     engine::{
         engine::{BlockDev, Filesystem, Pool},
-        strat_engine::pool::{v1, v2},
+        strat_engine::{
+            pool::{v1, v2},
+            thinpool::CloneSource,
+        },
         types::{
-            ActionAvailability, BlockDevTier, Clevis, CreateAction, DeleteAction, DevUuid,
-            EncryptionInfo, FilesystemUuid, GrowAction, Key, KeyDescription, Name,
-            OptionalTokenSlotInput, PoolDiff, PoolEncryptionInfo, PoolUuid, PropChangeAction,
-            RegenAction, RenameAction, SetCreateAction, SetDeleteAction, StratSigblockVersion,
+            ActionAvailability, BatchChange, BatchOperation, BlockDevTier, Clevis,
+            CompactionReport, CreateAction, DeleteAction, DevUuid, EncryptionInfo, EngineAction,
+            FilesystemUuid, GrowAction, Key, KeyDescription, Name, OptionalTokenSlotInput,
+            PoolDiff, PoolEncryptionInfo, PoolLayout, PoolUuid, PropChangeAction, RegenAction,
+            RenameAction, SetCreateAction, SetDeleteAction, StratSigblockVersion,
         },
     },
-    stratis::StratisResult,
+    stratis::{AuditRecord, StratisResult},
 };
 
 #[derive(Debug)]
@@ -29,6 +36,71 @@ pub enum AnyPool {
     V2(v2::StratPool),
 }
 
+impl AnyPool {
+    /// The UUID of the pool as recorded on its block devices.
+    fn pool_uuid(&self) -> Option<PoolUuid> {
+        match self {
+            AnyPool::V1(p) => p.blockdevs().first().map(|(_, _, bd)| bd.pool_uuid()),
+            AnyPool::V2(p) => p.blockdevs().first().map(|(_, _, bd)| bd.pool_uuid()),
+        }
+    }
+
+    /// Take a temporary snapshot of a filesystem in this pool which is to be
+    /// cloned into another pool.
+    pub fn prepare_clone_source(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        fs_uuid: FilesystemUuid,
+    ) -> StratisResult<CloneSource> {
+        match self {
+            AnyPool::V1(p) => p.prepare_clone_source(pool_name, pool_uuid, fs_uuid),
+            AnyPool::V2(p) => p.prepare_clone_source(pool_name, pool_uuid, fs_uuid),
+        }
+    }
+
+    /// Back up the thin pool metadata of this pool to its MDV.
+    pub fn backup_thin_metadata(&self) -> StratisResult<PathBuf> {
+        match self {
+            AnyPool::V1(p) => p.backup_thin_metadata(),
+            AnyPool::V2(p) => p.backup_thin_metadata(),
+        }
+    }
+
+    /// Remove the temporary snapshot taken by prepare_clone_source().
+    pub fn destroy_clone_source(
+        &mut self,
+        pool_name: &str,
+        snapshot_uuid: FilesystemUuid,
+    ) -> StratisResult<()> {
+        match self {
+            AnyPool::V1(p) => p.destroy_clone_source(pool_name, snapshot_uuid),
+            AnyPool::V2(p) => p.destroy_clone_source(pool_name, snapshot_uuid),
+        }
+    }
+
+    /// Create a filesystem in this pool which is a copy of a filesystem in
+    /// another pool.
+    pub fn clone_filesystem(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        name: &str,
+        source: &CloneSource,
+        preserve_uuid: bool,
+        progress: &mut dyn FnMut(Sectors, Sectors),
+    ) -> StratisResult<FilesystemUuid> {
+        match self {
+            AnyPool::V1(p) => {
+                p.clone_filesystem(pool_name, pool_uuid, name, source, preserve_uuid, progress)
+            }
+            AnyPool::V2(p) => {
+                p.clone_filesystem(pool_name, pool_uuid, name, source, preserve_uuid, progress)
+            }
+        }
+    }
+}
+
 impl Pool for AnyPool {
     fn init_cache(
         &mut self,
@@ -37,10 +109,15 @@ impl Pool for AnyPool {
         blockdevs: &[&Path],
         supports_encrypted: bool,
     ) -> StratisResult<SetCreateAction<DevUuid>> {
-        match self {
+        let res = match self {
             AnyPool::V1(p) => p.init_cache(pool_uuid, pool_name, blockdevs, supports_encrypted),
             AnyPool::V2(p) => p.init_cache(pool_uuid, pool_name, blockdevs, supports_encrypted),
-        }
+        };
+        AuditRecord::new("init_cache")
+            .pool_uuid(Some(pool_uuid))
+            .pool_name(pool_name)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn bind_clevis(
@@ -49,10 +126,15 @@ impl Pool for AnyPool {
         pin: &str,
         clevis_info: &Value,
     ) -> StratisResult<CreateAction<(Clevis, u32)>> {
-        match self {
+        let pool_uuid = self.pool_uuid();
+        let res = match self {
             AnyPool::V1(p) => p.bind_clevis(token_slot, pin, clevis_info),
             AnyPool::V2(p) => p.bind_clevis(token_slot, pin, clevis_info),
-        }
+        };
+        AuditRecord::new("bind_clevis")
+            .pool_uuid(pool_uuid)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn bind_keyring(
@@ -60,10 +142,15 @@ impl Pool for AnyPool {
         token_slot: OptionalTokenSlotInput,
         key_description: &KeyDescription,
     ) -> StratisResult<CreateAction<(Key, u32)>> {
-        match self {
+        let pool_uuid = self.pool_uuid();
+        let res = match self {
             AnyPool::V1(p) => p.bind_keyring(token_slot, key_description),
             AnyPool::V2(p) => p.bind_keyring(token_slot, key_description),
-        }
+        };
+        AuditRecord::new("bind_keyring")
+            .pool_uuid(pool_uuid)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn rebind_keyring(
@@ -71,31 +158,51 @@ impl Pool for AnyPool {
         token_slot: Option<u32>,
         new_key_desc: &KeyDescription,
     ) -> StratisResult<RenameAction<Key>> {
-        match self {
+        let pool_uuid = self.pool_uuid();
+        let res = match self {
             AnyPool::V1(p) => p.rebind_keyring(token_slot, new_key_desc),
             AnyPool::V2(p) => p.rebind_keyring(token_slot, new_key_desc),
-        }
+        };
+        AuditRecord::new("rebind_keyring")
+            .pool_uuid(pool_uuid)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn rebind_clevis(&mut self, token_slot: Option<u32>) -> StratisResult<RegenAction> {
-        match self {
+        let pool_uuid = self.pool_uuid();
+        let res = match self {
             AnyPool::V1(p) => p.rebind_clevis(token_slot),
             AnyPool::V2(p) => p.rebind_clevis(token_slot),
-        }
+        };
+        AuditRecord::new("rebind_clevis")
+            .pool_uuid(pool_uuid)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn unbind_keyring(&mut self, token_slot: Option<u32>) -> StratisResult<DeleteAction<Key>> {
-        match self {
+        let pool_uuid = self.pool_uuid();
+        let res = match self {
             AnyPool::V1(p) => p.unbind_keyring(token_slot),
             AnyPool::V2(p) => p.unbind_keyring(token_slot),
-        }
+        };
+        AuditRecord::new("unbind_keyring")
+            .pool_uuid(pool_uuid)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn unbind_clevis(&mut self, token_slot: Option<u32>) -> StratisResult<DeleteAction<Clevis>> {
-        match self {
+        let pool_uuid = self.pool_uuid();
+        let res = match self {
             AnyPool::V1(p) => p.unbind_clevis(token_slot),
             AnyPool::V2(p) => p.unbind_clevis(token_slot),
-        }
+        };
+        AuditRecord::new("unbind_clevis")
+            .pool_uuid(pool_uuid)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn create_filesystems<'a>(
@@ -104,10 +211,44 @@ impl Pool for AnyPool {
         pool_uuid: PoolUuid,
         specs: &[(&'a str, Option<Bytes>, Option<Bytes>)],
     ) -> StratisResult<SetCreateAction<(&'a str, FilesystemUuid, Sectors)>> {
-        match self {
+        let res = match self {
             AnyPool::V1(p) => p.create_filesystems(pool_name, pool_uuid, specs),
             AnyPool::V2(p) => p.create_filesystems(pool_name, pool_uuid, specs),
-        }
+        };
+        AuditRecord::new("create_filesystems")
+            .pool_uuid(Some(pool_uuid))
+            .pool_name(pool_name)
+            .fs_uuids(
+                res.iter()
+                    .flat_map(|a| a.created())
+                    .map(|(_, uuid, _)| uuid),
+            )
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
+    }
+
+    fn create_filesystem_with_uuid(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        fs_uuid: FilesystemUuid,
+        name: &str,
+        size: Option<Bytes>,
+        size_limit: Option<Bytes>,
+    ) -> StratisResult<CreateAction<FilesystemUuid>> {
+        let res = match self {
+            AnyPool::V1(p) => {
+                p.create_filesystem_with_uuid(pool_name, pool_uuid, fs_uuid, name, size, size_limit)
+            }
+            AnyPool::V2(p) => {
+                p.create_filesystem_with_uuid(pool_name, pool_uuid, fs_uuid, name, size, size_limit)
+            }
+        };
+        AuditRecord::new("create_filesystem_with_uuid")
+            .pool_uuid(Some(pool_uuid))
+            .pool_name(pool_name)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn add_blockdevs(
@@ -117,10 +258,15 @@ impl Pool for AnyPool {
         paths: &[&Path],
         tier: BlockDevTier,
     ) -> StratisResult<(SetCreateAction<DevUuid>, Option<PoolDiff>)> {
-        match self {
+        let res = match self {
             AnyPool::V1(p) => p.add_blockdevs(pool_uuid, pool_name, paths, tier),
             AnyPool::V2(p) => p.add_blockdevs(pool_uuid, pool_name, paths, tier),
-        }
+        };
+        AuditRecord::new("add_blockdevs")
+            .pool_uuid(Some(pool_uuid))
+            .pool_name(pool_name)
+            .emit_result(res.as_ref().map(|(a, _)| a.is_changed()));
+        res
     }
 
     fn destroy_filesystems(
@@ -129,10 +275,17 @@ impl Pool for AnyPool {
         fs_uuids: &HashSet<FilesystemUuid>,
     ) -> StratisResult<SetDeleteAction<FilesystemUuid, (FilesystemUuid, Option<FilesystemUuid>)>>
     {
-        match self {
+        let pool_uuid = self.pool_uuid();
+        let res = match self {
             AnyPool::V1(p) => p.destroy_filesystems(pool_name, fs_uuids),
             AnyPool::V2(p) => p.destroy_filesystems(pool_name, fs_uuids),
-        }
+        };
+        AuditRecord::new("destroy_filesystems")
+            .pool_uuid(pool_uuid)
+            .pool_name(pool_name)
+            .fs_uuids(fs_uuids)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn rename_filesystem(
@@ -141,10 +294,17 @@ impl Pool for AnyPool {
         uuid: FilesystemUuid,
         new_name: &str,
     ) -> StratisResult<RenameAction<FilesystemUuid>> {
-        match self {
+        let pool_uuid = self.pool_uuid();
+        let res = match self {
             AnyPool::V1(p) => p.rename_filesystem(pool_name, uuid, new_name),
             AnyPool::V2(p) => p.rename_filesystem(pool_name, uuid, new_name),
-        }
+        };
+        AuditRecord::new("rename_filesystem")
+            .pool_uuid(pool_uuid)
+            .pool_name(pool_name)
+            .fs_uuid(uuid)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn snapshot_filesystem<'a>(
@@ -154,14 +314,24 @@ impl Pool for AnyPool {
         origin_uuid: FilesystemUuid,
         snapshot_name: &str,
     ) -> StratisResult<CreateAction<(FilesystemUuid, &'a mut dyn Filesystem)>> {
-        match self {
+        let res = match self {
             AnyPool::V1(p) => {
                 p.snapshot_filesystem(pool_name, pool_uuid, origin_uuid, snapshot_name)
             }
             AnyPool::V2(p) => {
                 p.snapshot_filesystem(pool_name, pool_uuid, origin_uuid, snapshot_name)
             }
-        }
+        };
+        AuditRecord::new("snapshot_filesystem")
+            .pool_uuid(Some(pool_uuid))
+            .pool_name(pool_name)
+            .fs_uuid(origin_uuid)
+            .fs_uuids(res.iter().filter_map(|a| match a {
+                CreateAction::Created((uuid, _)) => Some(uuid),
+                CreateAction::Identity => None,
+            }))
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn total_physical_size(&self) -> Sectors {
@@ -236,10 +406,17 @@ impl Pool for AnyPool {
         uuid: DevUuid,
         user_info: Option<&str>,
     ) -> StratisResult<RenameAction<DevUuid>> {
-        match self {
+        let pool_uuid = self.pool_uuid();
+        let res = match self {
             AnyPool::V1(p) => p.set_blockdev_user_info(pool_name, uuid, user_info),
             AnyPool::V2(p) => p.set_blockdev_user_info(pool_name, uuid, user_info),
-        }
+        };
+        AuditRecord::new("set_blockdev_user_info")
+            .pool_uuid(pool_uuid)
+            .pool_name(pool_name)
+            .dev_uuid(uuid)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn has_cache(&self) -> bool {
@@ -290,10 +467,16 @@ impl Pool for AnyPool {
         pool_uuid: PoolUuid,
         new_limit: u64,
     ) -> StratisResult<()> {
-        match self {
+        let prev_limit = self.fs_limit();
+        let res = match self {
             AnyPool::V1(p) => p.set_fs_limit(pool_name, pool_uuid, new_limit),
             AnyPool::V2(p) => p.set_fs_limit(pool_name, pool_uuid, new_limit),
-        }
+        };
+        AuditRecord::new("set_fs_limit")
+            .pool_uuid(Some(pool_uuid))
+            .pool_name(pool_name)
+            .emit_result(res.as_ref().map(|()| self.fs_limit() != prev_limit));
+        res
     }
 
     fn overprov_enabled(&self) -> bool {
@@ -304,10 +487,20 @@ impl Pool for AnyPool {
     }
 
     fn set_overprov_mode(&mut self, pool_name: &Name, enabled: bool) -> StratisResult<()> {
-        match self {
+        let pool_uuid = self.pool_uuid();
+        let prev_enabled = self.overprov_enabled();
+        let res = match self {
             AnyPool::V1(p) => p.set_overprov_mode(pool_name, enabled),
             AnyPool::V2(p) => p.set_overprov_mode(pool_name, enabled),
-        }
+        };
+        AuditRecord::new("set_overprov_mode")
+            .pool_uuid(pool_uuid)
+            .pool_name(pool_name)
+            .emit_result(
+                res.as_ref()
+                    .map(|()| self.overprov_enabled() != prev_enabled),
+            );
+        res
     }
 
     fn out_of_alloc_space(&self) -> bool {
@@ -323,10 +516,16 @@ impl Pool for AnyPool {
         pool_uuid: PoolUuid,
         device: DevUuid,
     ) -> StratisResult<(GrowAction<(PoolUuid, DevUuid)>, Option<PoolDiff>)> {
-        match self {
+        let res = match self {
             AnyPool::V1(p) => p.grow_physical(name, pool_uuid, device),
             AnyPool::V2(p) => p.grow_physical(name, pool_uuid, device),
-        }
+        };
+        AuditRecord::new("grow_physical")
+            .pool_uuid(Some(pool_uuid))
+            .pool_name(name)
+            .dev_uuid(device)
+            .emit_result(res.as_ref().map(|(a, _)| a.is_changed()));
+        res
     }
 
     fn set_fs_size_limit(
@@ -334,10 +533,33 @@ impl Pool for AnyPool {
         fs: FilesystemUuid,
         limit: Option<Bytes>,
     ) -> StratisResult<PropChangeAction<Option<Sectors>>> {
-        match self {
+        let pool_uuid = self.pool_uuid();
+        let res = match self {
             AnyPool::V1(p) => p.set_fs_size_limit(fs, limit),
             AnyPool::V2(p) => p.set_fs_size_limit(fs, limit),
-        }
+        };
+        AuditRecord::new("set_fs_size_limit")
+            .pool_uuid(pool_uuid)
+            .fs_uuid(fs)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
+    }
+
+    fn set_fs_reservation(
+        &mut self,
+        fs: FilesystemUuid,
+        reservation: Option<Bytes>,
+    ) -> StratisResult<PropChangeAction<Option<Sectors>>> {
+        let pool_uuid = self.pool_uuid();
+        let res = match self {
+            AnyPool::V1(p) => p.set_fs_reservation(fs, reservation),
+            AnyPool::V2(p) => p.set_fs_reservation(fs, reservation),
+        };
+        AuditRecord::new("set_fs_reservation")
+            .pool_uuid(pool_uuid)
+            .fs_uuid(fs)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
     }
 
     fn current_metadata(&self, pool_name: &Name) -> StratisResult<String> {
@@ -380,9 +602,55 @@ impl Pool for AnyPool {
         fs_uuid: FilesystemUuid,
         new_scheduled: bool,
     ) -> StratisResult<PropChangeAction<bool>> {
-        match self {
+        let pool_uuid = self.pool_uuid();
+        let res = match self {
             AnyPool::V1(p) => p.set_fs_merge_scheduled(fs_uuid, new_scheduled),
             AnyPool::V2(p) => p.set_fs_merge_scheduled(fs_uuid, new_scheduled),
+        };
+        AuditRecord::new("set_fs_merge_scheduled")
+            .pool_uuid(pool_uuid)
+            .fs_uuid(fs_uuid)
+            .emit_result(res.as_ref().map(|a| a.is_changed()));
+        res
+    }
+
+    fn apply_batch(
+        &mut self,
+        pool_name: &Name,
+        pool_uuid: PoolUuid,
+        ops: &[BatchOperation],
+    ) -> StratisResult<Vec<BatchChange>> {
+        let res = match self {
+            AnyPool::V1(p) => p.apply_batch(pool_name, pool_uuid, ops),
+            AnyPool::V2(p) => p.apply_batch(pool_name, pool_uuid, ops),
+        };
+        AuditRecord::new("apply_batch")
+            .pool_uuid(Some(pool_uuid))
+            .pool_name(pool_name)
+            .emit_result(res.as_ref().map(|changes| !changes.is_empty()));
+        res
+    }
+
+    fn layout(&self, pool_name: &Name) -> StratisResult<PoolLayout> {
+        match self {
+            AnyPool::V1(p) => p.layout(pool_name),
+            AnyPool::V2(p) => p.layout(pool_name),
         }
     }
+
+    fn compact(
+        &mut self,
+        pool_name: &Name,
+        pool_uuid: PoolUuid,
+    ) -> StratisResult<CompactionReport> {
+        let res = match self {
+            AnyPool::V1(p) => p.compact(pool_name, pool_uuid),
+            AnyPool::V2(p) => p.compact(pool_name, pool_uuid),
+        };
+        AuditRecord::new("compact")
+            .pool_uuid(Some(pool_uuid))
+            .pool_name(pool_name)
+            .emit_result(res.as_ref().map(|report| report.moved > Sectors(0)));
+        res
+    }
 }
@@ -3,14 +3,19 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use indexmap::map::{Entry, IndexMap};
-use std::fmt;
+use std::{cmp::max, fmt};
 
 use devicemapper::Sectors;
 
+#[cfg(feature = "extras")]
+use crate::engine::strat_engine::serde_structs::{BaseDevSave, BlockDevSave};
 use crate::{
     engine::{
         strat_engine::{crypt::DEFAULT_CRYPT_DATA_OFFSET_V2, serde_structs::PoolSave},
-        types::{DevUuid, IntegrityTagSpec, ValidatedIntegritySpec},
+        types::{
+            BlockDevTier, DevUuid, DeviceLayout, IntegrityTagSpec, LayoutExtent, LayoutUse,
+            PoolLayout, ValidatedIntegritySpec,
+        },
     },
     stratis::{StratisError, StratisResult},
 };
@@ -124,25 +129,75 @@ where
     Ok(())
 }
 
-// Check whether any extent overlaps with another.
-fn check_overlap<U>(extents: &IndexMap<Sectors, (U, Sectors)>, start_offset: Sectors) -> Vec<String>
+// Check whether any extent overlaps with another or begins before
+// start_offset.
+fn check_overlap<U>(
+    extents: &IndexMap<Sectors, (U, Sectors)>,
+    start_offset: Sectors,
+    component: InspectedComponent,
+    device: Option<DevUuid>,
+) -> Vec<InspectionFinding>
 where
     U: Use,
 {
-    let mut errors = vec![];
-    let mut current_offset = start_offset;
+    let mut findings = vec![];
+    let mut previous_end = None;
     let mut starts: Vec<&Sectors> = extents.keys().collect();
     starts.sort();
 
     for &start in starts {
         let (used, length) = extents[&start];
-        if start < current_offset {
-            errors.push(format!("allocation ({start}, {length}) for {used} overlaps with previous allocation which extends to {current_offset}"))
+        match previous_end {
+            None if start < start_offset => findings.push(InspectionFinding::OutOfBounds {
+                component,
+                device,
+                usage: used.to_string(),
+                extent: (start, length),
+                start: start_offset,
+                end: None,
+            }),
+            Some(end) if start < end => findings.push(InspectionFinding::Overlap {
+                component,
+                device,
+                usage: used.to_string(),
+                extent: (start, length),
+                previous_end: end,
+            }),
+            _ => (),
         }
-        current_offset = start + length;
+        previous_end = Some(start + length);
     }
 
-    errors
+    findings
+}
+
+// Check that the extents, which should be contiguous, leave no space unused
+// between start_offset and the end of the last extent.
+fn check_gaps<U>(
+    extents: &IndexMap<Sectors, (U, Sectors)>,
+    start_offset: Sectors,
+    component: InspectedComponent,
+) -> Vec<InspectionFinding>
+where
+    U: Use,
+{
+    let mut findings = vec![];
+    let mut current_offset = start_offset;
+    let mut starts: Vec<&Sectors> = extents.keys().collect();
+    starts.sort();
+
+    for &start in starts {
+        let (_, length) = extents[&start];
+        if start > current_offset {
+            findings.push(InspectionFinding::Gap {
+                component,
+                extent: (current_offset, start - current_offset),
+            });
+        }
+        current_offset = max(current_offset, start + length);
+    }
+
+    findings
 }
 
 #[derive(strum_macros::Display)]
@@ -176,10 +231,29 @@ impl CapDevice {
         Ok(())
     }
 
-    fn check(&self) -> Vec<String> {
-        let mut errors = Vec::new();
-        errors.extend(check_overlap(&self.extents, self.offset()));
-        errors
+    // The end of the last allocation.
+    fn end(&self) -> Sectors {
+        self.extents
+            .iter()
+            .map(|(&start, &(_, length))| start + length)
+            .max()
+            .unwrap_or_else(|| self.offset())
+    }
+
+    fn check(&self) -> Vec<InspectionFinding> {
+        let mut findings = Vec::new();
+        findings.extend(check_overlap(
+            &self.extents,
+            self.offset(),
+            InspectedComponent::CapDevice,
+            None,
+        ));
+        findings.extend(check_gaps(
+            &self.extents,
+            self.offset(),
+            InspectedComponent::CapDevice,
+        ));
+        findings
     }
 }
 
@@ -252,36 +326,41 @@ impl DataDevice {
         Ok(())
     }
 
-    fn _check_integrity_meta_round(&self) -> Vec<String> {
-        let mut errors = Vec::new();
+    fn _check_integrity_meta_round(&self, uuid: DevUuid) -> Vec<InspectionFinding> {
+        let mut findings = Vec::new();
 
-        for (_, &(_, length)) in self
+        for (&start, &(_, length)) in self
             .extents
             .iter()
             .filter(|(_, &(used, _))| used == DataDeviceUse::IntegrityMetadata)
         {
             if length % Sectors(8) != Sectors(0) {
-                errors.push(format!(
-                    "Allocation {length} for integrity meta data not a multiple of 4KiB"
-                ));
+                findings.push(InspectionFinding::UnalignedIntegrityAllocation {
+                    device: uuid,
+                    extent: (start, length),
+                });
             }
         }
 
-        errors
+        findings
     }
 
-    fn _check_integrity(&self, integrity_spec: Option<ValidatedIntegritySpec>) -> Vec<String> {
+    fn _check_integrity(
+        &self,
+        uuid: DevUuid,
+        integrity_spec: Option<ValidatedIntegritySpec>,
+    ) -> Vec<InspectionFinding> {
         if let Some(integrity_spec) = integrity_spec {
+            let allocated = self.sum(&[DataDeviceUse::IntegrityMetadata]);
             if !integrity_spec.allocate_superblock
                 && integrity_spec.journal_size == Sectors(0)
                 && integrity_spec.tag_spec == IntegrityTagSpec::B0
-                && self.sum(&[DataDeviceUse::IntegrityMetadata]) > Sectors(0)
+                && allocated > Sectors(0)
             {
-                vec![
-                    format!(
-                        "Integrity specification should resolve to 0 allocations for integrity, but data device has space allocated for integrity."
-                    )
-                ]
+                vec![InspectionFinding::UnexpectedIntegrityAllocation {
+                    device: uuid,
+                    allocated,
+                }]
             } else {
                 vec![]
             }
@@ -290,12 +369,21 @@ impl DataDevice {
         }
     }
 
-    fn check(&self, integrity_spec: Option<ValidatedIntegritySpec>) -> Vec<String> {
-        let mut errors = Vec::new();
-        errors.extend(check_overlap(&self.extents, self.offset()));
-        errors.extend(self._check_integrity_meta_round());
-        errors.extend(self._check_integrity(integrity_spec));
-        errors
+    fn check(
+        &self,
+        uuid: DevUuid,
+        integrity_spec: Option<ValidatedIntegritySpec>,
+    ) -> Vec<InspectionFinding> {
+        let mut findings = Vec::new();
+        findings.extend(check_overlap(
+            &self.extents,
+            self.offset(),
+            InspectedComponent::DataDevice,
+            Some(uuid),
+        ));
+        findings.extend(self._check_integrity_meta_round(uuid));
+        findings.extend(self._check_integrity(uuid, integrity_spec));
+        findings
     }
 }
 
@@ -365,10 +453,13 @@ impl CacheDevice {
         Ok(())
     }
 
-    fn check(&self) -> Vec<String> {
-        let mut errors = Vec::new();
-        errors.extend(check_overlap(&self.extents, self.offset()));
-        errors
+    fn check(&self, uuid: DevUuid) -> Vec<InspectionFinding> {
+        check_overlap(
+            &self.extents,
+            self.offset(),
+            InspectedComponent::CacheDevice,
+            Some(uuid),
+        )
     }
 }
 
@@ -421,35 +512,26 @@ impl CryptAllocs {
         Ok(())
     }
 
-    fn check(&self) -> Vec<String> {
-        let mut errors = vec![];
-
-        if self.extents.is_empty() {
-            errors.push("No allocations for crypt metadata".into());
-        }
-
-        if self.extents.len() > 1 {
-            errors.push("Multiple allocations for crypt metadata".into());
-        }
-
-        let (&start, &(_, length)) = self
+    fn check(&self) -> Vec<InspectionFinding> {
+        let extents = self
             .extents
             .iter()
-            .collect::<Vec<_>>()
-            .pop()
-            .expect("Exactly one extents in the extent map");
-
-        if start != Sectors(0) {
-            errors.push(format!("Crypt meta allocs offset, {start} is not 0"));
-        }
-
-        if length != DEFAULT_CRYPT_DATA_OFFSET_V2 {
-            errors.push(format!(
-                "Crypt meta allocs entry has unexpected length {length}"
-            ));
+            .map(|(&start, &(_, length))| (start, length))
+            .collect::<Vec<_>>();
+
+        match extents.as_slice() {
+            [] => vec![InspectionFinding::MissingCryptAllocation],
+            &[(start, length)] => {
+                if start != Sectors(0) || length != DEFAULT_CRYPT_DATA_OFFSET_V2 {
+                    vec![InspectionFinding::MisplacedCryptAllocation {
+                        extent: (start, length),
+                    }]
+                } else {
+                    vec![]
+                }
+            }
+            _ => vec![InspectionFinding::MultipleCryptAllocations { extents }],
         }
-
-        errors
     }
 }
 
@@ -513,21 +595,48 @@ impl FlexDevice {
 
     // Verify that both thin meta devices, the one currently in use and the
     // spare, are the same size.
-    fn _check_thin_metas_equal(&self) -> Vec<String> {
+    fn _check_thin_metas_equal(&self) -> Vec<InspectionFinding> {
         let thin_meta_total = self.sum(&[FlexDeviceUse::ThinMetaDev]);
         let thin_meta_spare_total = self.sum(&[FlexDeviceUse::ThinMetaDevSpare]);
         if thin_meta_total == thin_meta_spare_total {
             vec![]
         } else {
-            vec![format!("The sum of the allocations for the thin meta device, {thin_meta_total}, does not equal the sum of the allocations for the thin meta spare device, {thin_meta_spare_total}.")]
+            vec![InspectionFinding::MismatchedThinMetaSizes {
+                thin_meta: thin_meta_total,
+                thin_meta_spare: thin_meta_spare_total,
+            }]
         }
     }
 
-    fn check(&self) -> Vec<String> {
-        let mut errors = Vec::new();
-        errors.extend(self._check_thin_metas_equal());
-        errors.extend(check_overlap(&self.extents, self.offset()));
-        errors
+    // Verify that every allocation lies within the space allocated from the
+    // cap device.
+    fn _check_bounds(&self, cap_device: &CapDevice) -> Vec<InspectionFinding> {
+        let limit = cap_device.end();
+        self.extents
+            .iter()
+            .filter(|(&start, &(_, length))| start + length > limit)
+            .map(|(&start, &(used, length))| InspectionFinding::OutOfBounds {
+                component: InspectedComponent::FlexDevice,
+                device: None,
+                usage: used.to_string(),
+                extent: (start, length),
+                start: self.offset(),
+                end: Some(limit),
+            })
+            .collect()
+    }
+
+    fn check(&self, cap_device: &CapDevice) -> Vec<InspectionFinding> {
+        let mut findings = Vec::new();
+        findings.extend(self._check_thin_metas_equal());
+        findings.extend(check_overlap(
+            &self.extents,
+            self.offset(),
+            InspectedComponent::FlexDevice,
+            None,
+        ));
+        findings.extend(self._check_bounds(cap_device));
+        findings
     }
 }
 
@@ -680,52 +789,888 @@ fn cap_device(metadata: &PoolSave, encrypted: bool) -> StratisResult<CapDevice>
     Ok(cap_device)
 }
 
+impl Use for LayoutUse {}
+
+// The use shown in the layout of a part of a data device. Allocations from the
+// data device are not shown as such; they are shown as the uses of the parts
+// of the pool they are allocated to.
+fn data_device_layout_use(used: DataDeviceUse) -> Option<LayoutUse> {
+    match used {
+        DataDeviceUse::StratisMetadata => Some(LayoutUse::StratisMetadata),
+        DataDeviceUse::IntegrityMetadata => Some(LayoutUse::IntegrityMetadata),
+        DataDeviceUse::Allocated | DataDeviceUse::Unused => None,
+    }
+}
+
+fn cache_device_layout_use(used: CacheDeviceUse) -> Option<LayoutUse> {
+    match used {
+        CacheDeviceUse::StratisMetadata => Some(LayoutUse::StratisMetadata),
+        CacheDeviceUse::CacheMetadata => Some(LayoutUse::CacheMetadata),
+        CacheDeviceUse::CacheData => Some(LayoutUse::CacheData),
+        CacheDeviceUse::Unused => None,
+    }
+}
+
+fn flex_device_layout_use(used: FlexDeviceUse) -> LayoutUse {
+    match used {
+        FlexDeviceUse::MetaDev => LayoutUse::Mdv,
+        FlexDeviceUse::ThinDataDev => LayoutUse::ThinData,
+        FlexDeviceUse::ThinMetaDev => LayoutUse::ThinMeta,
+        FlexDeviceUse::ThinMetaDevSpare => LayoutUse::ThinMetaSpare,
+        FlexDeviceUse::Unused => LayoutUse::PoolUnused,
+    }
+}
+
+// Order the extents of a device and mark the space between them, and after
+// them up to the size of the device if it is known, as unused.
+fn device_layout(
+    uuid: DevUuid,
+    tier: BlockDevTier,
+    mut extents: Vec<LayoutExtent>,
+    size: Option<Sectors>,
+) -> DeviceLayout {
+    extents.sort_by_key(|extent| extent.start);
+
+    let mut result = Vec::new();
+    let mut current_offset = Sectors(0);
+    for extent in extents {
+        if extent.start > current_offset {
+            result.push(LayoutExtent {
+                start: current_offset,
+                length: extent.start - current_offset,
+                usage: LayoutUse::Unused,
+            });
+        }
+        current_offset = max(current_offset, extent.start + extent.length);
+        result.push(extent);
+    }
+    if let Some(size) = size {
+        if size > current_offset {
+            result.push(LayoutExtent {
+                start: current_offset,
+                length: size - current_offset,
+                usage: LayoutUse::Unused,
+            });
+        }
+    }
+
+    DeviceLayout {
+        uuid,
+        tier,
+        size,
+        extents: result,
+    }
+}
+
+// Describe the items which are in new but not in old as added and the
+// items which are in old but not in new as removed.
+#[cfg(feature = "extras")]
+fn item_changes<T, F>(what: &str, old: &[T], new: &[T], fmt: F) -> Vec<String>
+where
+    T: PartialEq,
+    F: Fn(&T) -> String,
+{
+    old.iter()
+        .filter(|item| !new.contains(item))
+        .map(|item| format!("{what}: removed {}", fmt(item)))
+        .chain(
+            new.iter()
+                .filter(|item| !old.contains(item))
+                .map(|item| format!("{what}: added {}", fmt(item))),
+        )
+        .collect()
+}
+
+// Describe a change in a single value, if there is one.
+#[cfg(feature = "extras")]
+fn value_change<T>(what: &str, old: &T, new: &T) -> Option<String>
+where
+    T: PartialEq + fmt::Debug,
+{
+    if old == new {
+        None
+    } else {
+        Some(format!("{what}: changed from {old:?} to {new:?}"))
+    }
+}
+
+// Describe the changes in a list of items in which the order of the items is
+// significant, such as the segments of a device.
+#[cfg(feature = "extras")]
+fn ordered_item_changes<T, F>(what: &str, old: &[T], new: &[T], fmt: F) -> Vec<String>
+where
+    T: PartialEq,
+    F: Fn(&T) -> String,
+{
+    if old == new {
+        return Vec::new();
+    }
+    let changes = item_changes(what, old, new, fmt);
+    if changes.is_empty() {
+        vec![format!("{what}: reordered")]
+    } else {
+        changes
+    }
+}
+
+#[cfg(feature = "extras")]
+fn fmt_segment(&(start, length): &(Sectors, Sectors)) -> String {
+    format!("segment at {start} of length {length}")
+}
+
+#[cfg(feature = "extras")]
+fn fmt_base_dev(dev: &BaseDevSave) -> String {
+    format!(
+        "segment on {} at {} of length {}",
+        dev.parent, dev.start, dev.length
+    )
+}
+
+// Describe the changes in the devices of a tier and in the allocations made
+// from them.
+#[cfg(feature = "extras")]
+fn blockdev_changes(tier: &str, old: &BlockDevSave, new: &BlockDevSave) -> Vec<String> {
+    let old_uuids = old.devs.iter().map(|dev| dev.uuid).collect::<Vec<_>>();
+    let new_uuids = new.devs.iter().map(|dev| dev.uuid).collect::<Vec<_>>();
+    let mut changes = item_changes(&format!("{tier} devices"), &old_uuids, &new_uuids, |u| {
+        u.to_string()
+    });
+
+    for new_dev in new.devs.iter() {
+        if let Some(old_dev) = old.devs.iter().find(|dev| dev.uuid == new_dev.uuid) {
+            let what = format!("{tier} device {}", new_dev.uuid);
+            changes.extend(item_changes(
+                &format!("{what} integrity metadata"),
+                &old_dev.integrity_meta_allocs,
+                &new_dev.integrity_meta_allocs,
+                fmt_segment,
+            ));
+            changes.extend(value_change(
+                &format!("{what} user info"),
+                &old_dev.user_info,
+                &new_dev.user_info,
+            ));
+            changes.extend(value_change(
+                &format!("{what} hardware info"),
+                &old_dev.hardware_info,
+                &new_dev.hardware_info,
+            ));
+        }
+    }
+
+    let old_allocs = old.allocs.iter().flatten().collect::<Vec<_>>();
+    let new_allocs = new.allocs.iter().flatten().collect::<Vec<_>>();
+    changes.extend(item_changes(
+        &format!("{tier} allocations"),
+        &old_allocs,
+        &new_allocs,
+        |dev| fmt_base_dev(dev),
+    ));
+
+    changes
+}
+
+/// The part of the pool's layout in which a problem was found.
+#[derive(strum_macros::Display)]
+#[strum(serialize_all = "snake_case")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InspectedComponent {
+    DataDevice,
+    CacheDevice,
+    CryptAllocs,
+    CapDevice,
+    FlexDevice,
+}
+
+/// How serious a problem found by inspection is. Metadata with any finding of
+/// severity Error fails the check.
+#[derive(strum_macros::Display)]
+#[strum(serialize_all = "snake_case")]
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found by inspecting a pool's metadata. Extents are pairs of start
+/// and length.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InspectionFinding {
+    /// An allocation begins before the end of the allocation preceding it.
+    Overlap {
+        component: InspectedComponent,
+        device: Option<DevUuid>,
+        usage: String,
+        extent: (Sectors, Sectors),
+        previous_end: Sectors,
+    },
+    /// Space is left unallocated between allocations that should be
+    /// contiguous.
+    Gap {
+        component: InspectedComponent,
+        extent: (Sectors, Sectors),
+    },
+    /// An allocation lies outside the space from which it may be allocated,
+    /// which begins at start and, if known, ends at end.
+    OutOfBounds {
+        component: InspectedComponent,
+        device: Option<DevUuid>,
+        usage: String,
+        extent: (Sectors, Sectors),
+        start: Sectors,
+        end: Option<Sectors>,
+    },
+    /// There is no allocation for the crypt metadata.
+    MissingCryptAllocation,
+    /// There is more than one allocation for the crypt metadata.
+    MultipleCryptAllocations { extents: Vec<(Sectors, Sectors)> },
+    /// The allocation for the crypt metadata is not at the start of the
+    /// device or is not of the expected length.
+    MisplacedCryptAllocation { extent: (Sectors, Sectors) },
+    /// An allocation for integrity metadata is not a multiple of 4 KiB.
+    UnalignedIntegrityAllocation {
+        device: DevUuid,
+        extent: (Sectors, Sectors),
+    },
+    /// Space is allocated for integrity metadata, although the integrity
+    /// specification requires none.
+    UnexpectedIntegrityAllocation { device: DevUuid, allocated: Sectors },
+    /// The thin meta device and its spare are of different sizes.
+    MismatchedThinMetaSizes {
+        thin_meta: Sectors,
+        thin_meta_spare: Sectors,
+    },
+}
+
+impl InspectionFinding {
+    /// The part of the layout in which the problem was found.
+    #[cfg(feature = "extras")]
+    pub fn component(&self) -> InspectedComponent {
+        match self {
+            InspectionFinding::Overlap { component, .. }
+            | InspectionFinding::Gap { component, .. }
+            | InspectionFinding::OutOfBounds { component, .. } => *component,
+            InspectionFinding::MissingCryptAllocation
+            | InspectionFinding::MultipleCryptAllocations { .. }
+            | InspectionFinding::MisplacedCryptAllocation { .. } => InspectedComponent::CryptAllocs,
+            InspectionFinding::UnalignedIntegrityAllocation { .. }
+            | InspectionFinding::UnexpectedIntegrityAllocation { .. } => {
+                InspectedComponent::DataDevice
+            }
+            InspectionFinding::MismatchedThinMetaSizes { .. } => InspectedComponent::FlexDevice,
+        }
+    }
+
+    /// The block device on which the problem was found, if the problem is
+    /// specific to a single block device.
+    pub fn device(&self) -> Option<DevUuid> {
+        match self {
+            InspectionFinding::Overlap { device, .. }
+            | InspectionFinding::OutOfBounds { device, .. } => *device,
+            InspectionFinding::UnalignedIntegrityAllocation { device, .. }
+            | InspectionFinding::UnexpectedIntegrityAllocation { device, .. } => Some(*device),
+            InspectionFinding::Gap { .. }
+            | InspectionFinding::MissingCryptAllocation
+            | InspectionFinding::MultipleCryptAllocations { .. }
+            | InspectionFinding::MisplacedCryptAllocation { .. }
+            | InspectionFinding::MismatchedThinMetaSizes { .. } => None,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            InspectionFinding::Gap { .. }
+            | InspectionFinding::UnexpectedIntegrityAllocation { .. } => Severity::Warning,
+            InspectionFinding::Overlap { .. }
+            | InspectionFinding::OutOfBounds { .. }
+            | InspectionFinding::MissingCryptAllocation
+            | InspectionFinding::MultipleCryptAllocations { .. }
+            | InspectionFinding::MisplacedCryptAllocation { .. }
+            | InspectionFinding::UnalignedIntegrityAllocation { .. }
+            | InspectionFinding::MismatchedThinMetaSizes { .. } => Severity::Error,
+        }
+    }
+
+    /// A change to the metadata that resolves the problem, if there is one
+    /// that can be made without knowledge beyond the metadata itself.
+    #[cfg(feature = "extras")]
+    pub fn fix(&self) -> Option<InspectionFix> {
+        match self {
+            InspectionFinding::MissingCryptAllocation
+            | InspectionFinding::MultipleCryptAllocations { .. }
+            | InspectionFinding::MisplacedCryptAllocation { .. } => {
+                Some(InspectionFix::SetCryptMetaAllocs {
+                    extent: (Sectors(0), DEFAULT_CRYPT_DATA_OFFSET_V2),
+                })
+            }
+            InspectionFinding::UnexpectedIntegrityAllocation { device, .. } => {
+                Some(InspectionFix::RemoveIntegrityMetaAllocs { device: *device })
+            }
+            InspectionFinding::Overlap { .. }
+            | InspectionFinding::Gap { .. }
+            | InspectionFinding::OutOfBounds { .. }
+            | InspectionFinding::UnalignedIntegrityAllocation { .. }
+            | InspectionFinding::MismatchedThinMetaSizes { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for InspectionFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(uuid) = self.device() {
+            write!(f, "Device {uuid}: ")?;
+        }
+        match self {
+            InspectionFinding::Overlap {
+                usage,
+                extent: (start, length),
+                previous_end,
+                ..
+            } => write!(f, "allocation ({start}, {length}) for {usage} overlaps with previous allocation which extends to {previous_end}"),
+            InspectionFinding::Gap {
+                component,
+                extent: (start, length),
+            } => write!(
+                f,
+                "{component} has unallocated space ({start}, {length}) between allocations which should be contiguous"
+            ),
+            InspectionFinding::OutOfBounds {
+                usage,
+                extent: (extent_start, length),
+                start,
+                end,
+                ..
+            } => {
+                write!(f, "allocation ({extent_start}, {length}) for {usage} lies outside the space available, which begins at {start}")?;
+                if let Some(end) = end {
+                    write!(f, " and ends at {end}")?;
+                }
+                Ok(())
+            }
+            InspectionFinding::MissingCryptAllocation => {
+                write!(f, "No allocations for crypt metadata")
+            }
+            InspectionFinding::MultipleCryptAllocations { extents } => write!(
+                f,
+                "Multiple allocations for crypt metadata: {}",
+                extents
+                    .iter()
+                    .map(|(start, length)| format!("({start}, {length})"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            InspectionFinding::MisplacedCryptAllocation {
+                extent: (start, length),
+            } => write!(
+                f,
+                "Crypt meta allocs entry ({start}, {length}) is not at offset 0 with length {DEFAULT_CRYPT_DATA_OFFSET_V2}"
+            ),
+            InspectionFinding::UnalignedIntegrityAllocation {
+                extent: (_, length),
+                ..
+            } => write!(
+                f,
+                "Allocation {length} for integrity meta data not a multiple of 4KiB"
+            ),
+            InspectionFinding::UnexpectedIntegrityAllocation { allocated, .. } => write!(
+                f,
+                "Integrity specification should resolve to 0 allocations for integrity, but data device has {allocated} allocated for integrity"
+            ),
+            InspectionFinding::MismatchedThinMetaSizes {
+                thin_meta,
+                thin_meta_spare,
+            } => write!(
+                f,
+                "The sum of the allocations for the thin meta device, {thin_meta}, does not equal the sum of the allocations for the thin meta spare device, {thin_meta_spare}."
+            ),
+        }
+    }
+}
+
+/// A change to the metadata which resolves a problem found by inspection.
+#[cfg(feature = "extras")]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum InspectionFix {
+    /// Replace the allocations for the crypt metadata with the single
+    /// allocation given.
+    SetCryptMetaAllocs { extent: (Sectors, Sectors) },
+    /// Remove the allocations for integrity metadata from the data device.
+    RemoveIntegrityMetaAllocs { device: DevUuid },
+}
+
+#[cfg(feature = "extras")]
+impl InspectionFix {
+    /// Make the change to the metadata.
+    pub fn apply(&self, metadata: &mut PoolSave) -> StratisResult<()> {
+        match self {
+            InspectionFix::SetCryptMetaAllocs { extent } => {
+                metadata.backstore.cap.crypt_meta_allocs = vec![*extent];
+            }
+            InspectionFix::RemoveIntegrityMetaAllocs { device } => {
+                metadata
+                    .backstore
+                    .data_tier
+                    .blockdev
+                    .devs
+                    .iter_mut()
+                    .find(|dev| dev.uuid == *device)
+                    .ok_or_else(|| {
+                        StratisError::Msg(format!("No data device with UUID {device} in metadata"))
+                    })?
+                    .integrity_meta_allocs
+                    .clear();
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Some ways of inspecting the pool-level metadata.
 pub mod inspectors {
+    use std::{
+        cmp::{max, min},
+        collections::HashMap,
+    };
+
+    use devicemapper::Sectors;
+
     use super::{
-        cache_devices, cap_device, crypt_allocs, data_devices, flex_device, PoolSave, StratisResult,
+        add, cache_device_layout_use, cache_devices, cap_device, crypt_allocs,
+        data_device_layout_use, data_devices, device_layout, filled, flex_device,
+        flex_device_layout_use, Allocator, BlockDevTier, DevUuid, IndexMap, InspectionFinding,
+        LayoutExtent, LayoutUse, PoolLayout, PoolSave, Severity, StratisResult,
+    };
+    #[cfg(feature = "extras")]
+    use super::{
+        blockdev_changes, fmt_base_dev, fmt_segment, item_changes, ordered_item_changes,
+        value_change,
     };
 
     use crate::{engine::strat_engine::serde_structs::PoolFeatures, stratis::StratisError};
 
-    /// Check that the metadata is well-formed.
-    pub fn check(metadata: &PoolSave) -> StratisResult<()> {
-        let mut errors = Vec::new();
+    /// Inspect the metadata, returning every problem found. An empty list
+    /// means that the metadata is well-formed.
+    pub fn findings(metadata: &PoolSave) -> StratisResult<Vec<InspectionFinding>> {
+        let mut findings = Vec::new();
 
         let encrypted = metadata.features.contains(&PoolFeatures::Encryption);
 
         let (data_devices, integrity_spec) = data_devices(metadata)?;
         for (uuid, data_device) in data_devices.iter() {
-            errors.extend(
-                data_device
-                    .check(integrity_spec)
-                    .iter()
-                    .map(|s| format!("Device {uuid}: {s}")),
-            );
+            findings.extend(data_device.check(*uuid, integrity_spec));
         }
 
         let cache_devices = cache_devices(metadata)?;
-        for cache_device in cache_devices.values() {
-            errors.extend(cache_device.check());
+        for (uuid, cache_device) in cache_devices.iter() {
+            findings.extend(cache_device.check(*uuid));
         }
 
         let crypt_allocs = crypt_allocs(metadata)?;
-        errors.extend(crypt_allocs.check());
+        findings.extend(crypt_allocs.check());
 
         let cap_device = cap_device(metadata, encrypted)?;
-        errors.extend(cap_device.check());
+        findings.extend(cap_device.check());
 
         let flex_device = flex_device(metadata, encrypted)?;
-        errors.extend(flex_device.check());
+        findings.extend(flex_device.check(&cap_device));
+
+        Ok(findings)
+    }
+
+    /// Check that the metadata is well-formed. Findings of severity Warning
+    /// are logged but do not cause the check to fail.
+    pub fn check(metadata: &PoolSave) -> StratisResult<()> {
+        let (errors, warnings): (Vec<_>, Vec<_>) = findings(metadata)?
+            .into_iter()
+            .partition(|finding| finding.severity() == Severity::Error);
+
+        for warning in warnings {
+            warn!("Pool metadata for pool {}: {}", metadata.name, warning);
+        }
 
         if !errors.is_empty() {
-            Err(StratisError::Msg(errors.join("\n")))
+            Err(StratisError::Msg(
+                errors
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ))
         } else {
             Ok(())
         }
     }
 
+    /// Describe which sectors of each block device of the pool hold what, as
+    /// recorded in the metadata. device_sizes holds the sizes of the block
+    /// devices, where they are known; the layout of any other device ends
+    /// with its last allocation.
+    pub fn layout(
+        metadata: &PoolSave,
+        device_sizes: &HashMap<DevUuid, Sectors>,
+    ) -> StratisResult<PoolLayout> {
+        let encrypted = metadata.features.contains(&PoolFeatures::Encryption);
+
+        let (data_devices, _) = data_devices(metadata)?;
+        let cache_devices = cache_devices(metadata)?;
+        let flex_device = flex_device(metadata, encrypted)?;
+
+        // The uses of the sectors of the device made up of the allocations
+        // from the data tier, in order. If the pool is encrypted, the flex
+        // device allocations are made from the encrypted device, which
+        // begins after the crypt metadata.
+        let crypt_meta_allocs = &metadata.backstore.cap.crypt_meta_allocs;
+        let shift = if encrypted {
+            crypt_meta_allocs
+                .iter()
+                .map(|&(start, length)| start + length)
+                .max()
+                .unwrap_or(Sectors(0))
+        } else {
+            Sectors(0)
+        };
+        let mut backing = IndexMap::new();
+        add(&mut backing, crypt_meta_allocs, LayoutUse::CryptMetadata)?;
+        for (&start, &(used, length)) in flex_device.extents() {
+            add(
+                &mut backing,
+                &[(start + shift, length)],
+                flex_device_layout_use(used),
+            )?;
+        }
+        let backing = filled(&backing, LayoutUse::PoolUnused, Sectors(0));
+
+        let mut extents = data_devices
+            .iter()
+            .map(|(uuid, data_device)| {
+                (
+                    *uuid,
+                    data_device
+                        .extents()
+                        .iter()
+                        .filter_map(|(&start, &(used, length))| {
+                            data_device_layout_use(used).map(|usage| LayoutExtent {
+                                start,
+                                length,
+                                usage,
+                            })
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<IndexMap<_, _>>();
+
+        let mut offset = Sectors(0);
+        for segment in &metadata.backstore.data_tier.blockdev.allocs[0] {
+            let device_extents = extents.get_mut(&segment.parent).ok_or_else(|| {
+                StratisError::Msg(format!(
+                    "No device in devs for uuid {} in blockdevs",
+                    segment.parent
+                ))
+            })?;
+            let segment_end = offset + segment.length;
+            let mut covered = offset;
+            for (&start, &(usage, length)) in backing.iter() {
+                let (overlap_start, overlap_end) =
+                    (max(start, offset), min(start + length, segment_end));
+                if overlap_start < overlap_end {
+                    device_extents.push(LayoutExtent {
+                        start: segment.start + (overlap_start - offset),
+                        length: overlap_end - overlap_start,
+                        usage,
+                    });
+                    covered = max(covered, overlap_end);
+                }
+            }
+            if covered < segment_end {
+                device_extents.push(LayoutExtent {
+                    start: segment.start + (covered - offset),
+                    length: segment_end - covered,
+                    usage: LayoutUse::PoolUnused,
+                });
+            }
+            offset = segment_end;
+        }
+
+        let devices = extents
+            .into_iter()
+            .map(|(uuid, device_extents)| {
+                device_layout(
+                    uuid,
+                    BlockDevTier::Data,
+                    device_extents,
+                    device_sizes.get(&uuid).copied(),
+                )
+            })
+            .chain(cache_devices.iter().map(|(uuid, cache_device)| {
+                device_layout(
+                    *uuid,
+                    BlockDevTier::Cache,
+                    cache_device
+                        .extents()
+                        .iter()
+                        .filter_map(|(&start, &(used, length))| {
+                            cache_device_layout_use(used).map(|usage| LayoutExtent {
+                                start,
+                                length,
+                                usage,
+                            })
+                        })
+                        .collect(),
+                    device_sizes.get(uuid).copied(),
+                )
+            }))
+            .collect();
+
+        Ok(PoolLayout {
+            name: metadata.name.clone(),
+            encrypted,
+            devices,
+        })
+    }
+
+    /// The UUIDs of all the block devices, in both the data and the cache
+    /// tier, that the metadata describes.
+    pub fn blockdev_uuids(metadata: &PoolSave) -> Vec<DevUuid> {
+        metadata
+            .backstore
+            .data_tier
+            .blockdev
+            .devs
+            .iter()
+            .chain(
+                metadata
+                    .backstore
+                    .cache_tier
+                    .iter()
+                    .flat_map(|cache_tier| cache_tier.blockdev.devs.iter()),
+            )
+            .map(|dev| dev.uuid)
+            .collect()
+    }
+
+    /// Describe the differences between two versions of the metadata of a
+    /// pool: devices added or removed, allocations made or released, and
+    /// changes in encryption and in the thin pool's settings. Returns an
+    /// empty list if the versions do not differ.
+    #[cfg(feature = "extras")]
+    pub fn diff(old: &PoolSave, new: &PoolSave) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        changes.extend(value_change("Pool name", &old.name, &new.name));
+        changes.extend(value_change("Pool started", &old.started, &new.started));
+
+        let old_encrypted = old.features.contains(&PoolFeatures::Encryption);
+        let new_encrypted = new.features.contains(&PoolFeatures::Encryption);
+        if old_encrypted != new_encrypted {
+            changes.push(if new_encrypted {
+                "Encryption: enabled".to_string()
+            } else {
+                "Encryption: disabled".to_string()
+            });
+        }
+        let old_features = old
+            .features
+            .iter()
+            .filter(|f| **f != PoolFeatures::Encryption)
+            .collect::<Vec<_>>();
+        let new_features = new
+            .features
+            .iter()
+            .filter(|f| **f != PoolFeatures::Encryption)
+            .collect::<Vec<_>>();
+        changes.extend(item_changes(
+            "Pool features",
+            &old_features,
+            &new_features,
+            |f| format!("{f:?}"),
+        ));
+
+        let (old_backstore, new_backstore) = (&old.backstore, &new.backstore);
+        changes.extend(value_change(
+            "Data tier integrity specification",
+            &old_backstore.data_tier.integrity_spec,
+            &new_backstore.data_tier.integrity_spec,
+        ));
+        changes.extend(blockdev_changes(
+            "Data tier",
+            &old_backstore.data_tier.blockdev,
+            &new_backstore.data_tier.blockdev,
+        ));
+        match (&old_backstore.cache_tier, &new_backstore.cache_tier) {
+            (None, Some(_)) => changes.push("Cache tier: added".to_string()),
+            (Some(_), None) => changes.push("Cache tier: removed".to_string()),
+            _ => (),
+        }
+        if let (Some(old_cache), Some(new_cache)) =
+            (&old_backstore.cache_tier, &new_backstore.cache_tier)
+        {
+            changes.extend(blockdev_changes(
+                "Cache tier",
+                &old_cache.blockdev,
+                &new_cache.blockdev,
+            ));
+        }
+        changes.extend(item_changes(
+            "Cap device allocations",
+            &old_backstore.cap.allocs,
+            &new_backstore.cap.allocs,
+            fmt_segment,
+        ));
+        changes.extend(item_changes(
+            "Crypt metadata allocations",
+            &old_backstore.cap.crypt_meta_allocs,
+            &new_backstore.cap.crypt_meta_allocs,
+            fmt_segment,
+        ));
+
+        let (old_flex, new_flex) = (&old.flex_devs, &new.flex_devs);
+        for (what, old_segs, new_segs) in [
+            ("MDV", &old_flex.meta_dev, &new_flex.meta_dev),
+            (
+                "Thin metadata device",
+                &old_flex.thin_meta_dev,
+                &new_flex.thin_meta_dev,
+            ),
+            (
+                "Thin data device",
+                &old_flex.thin_data_dev,
+                &new_flex.thin_data_dev,
+            ),
+            (
+                "Spare thin metadata device",
+                &old_flex.thin_meta_dev_spare,
+                &new_flex.thin_meta_dev_spare,
+            ),
+        ] {
+            changes.extend(item_changes(what, old_segs, new_segs, fmt_segment));
+        }
+
+        let (old_thinpool, new_thinpool) = (&old.thinpool_dev, &new.thinpool_dev);
+        changes.extend(value_change(
+            "Thin pool data block size",
+            &old_thinpool.data_block_size,
+            &new_thinpool.data_block_size,
+        ));
+        changes.extend(value_change(
+            "Thin pool feature args",
+            &old_thinpool.feature_args,
+            &new_thinpool.feature_args,
+        ));
+        changes.extend(value_change(
+            "Filesystem limit",
+            &old_thinpool.fs_limit,
+            &new_thinpool.fs_limit,
+        ));
+        changes.extend(value_change(
+            "Overprovisioning enabled",
+            &old_thinpool.enable_overprov,
+            &new_thinpool.enable_overprov,
+        ));
+
+        changes
+    }
+
+    /// Describe how the allocations recorded in two versions of the metadata
+    /// of a pool differ: the space allocated from each block device and the
+    /// segments of the cap device and of the flex devices. The device-mapper
+    /// tables of a pool are built from these, so of two versions whose
+    /// allocations differ at most one describes where the pool's data is.
+    /// Returns an empty list if the allocations do not differ.
+    #[cfg(feature = "extras")]
+    pub fn allocation_changes(old: &PoolSave, new: &PoolSave) -> Vec<String> {
+        let (old_backstore, new_backstore) = (&old.backstore, &new.backstore);
+        let mut changes = Vec::new();
+
+        for (tier, old_blockdev, new_blockdev) in [
+            (
+                "Data tier",
+                Some(&old_backstore.data_tier.blockdev),
+                Some(&new_backstore.data_tier.blockdev),
+            ),
+            (
+                "Cache tier",
+                old_backstore.cache_tier.as_ref().map(|t| &t.blockdev),
+                new_backstore.cache_tier.as_ref().map(|t| &t.blockdev),
+            ),
+        ] {
+            let old_allocs = old_blockdev
+                .iter()
+                .flat_map(|b| b.allocs.iter().flatten())
+                .collect::<Vec<_>>();
+            let new_allocs = new_blockdev
+                .iter()
+                .flat_map(|b| b.allocs.iter().flatten())
+                .collect::<Vec<_>>();
+            changes.extend(ordered_item_changes(
+                &format!("{tier} allocations"),
+                &old_allocs,
+                &new_allocs,
+                |dev| fmt_base_dev(dev),
+            ));
+        }
+
+        for new_dev in new_backstore.data_tier.blockdev.devs.iter() {
+            if let Some(old_dev) = old_backstore
+                .data_tier
+                .blockdev
+                .devs
+                .iter()
+                .find(|dev| dev.uuid == new_dev.uuid)
+            {
+                changes.extend(ordered_item_changes(
+                    &format!("Data tier device {} integrity metadata", new_dev.uuid),
+                    &old_dev.integrity_meta_allocs,
+                    &new_dev.integrity_meta_allocs,
+                    fmt_segment,
+                ));
+            }
+        }
+
+        changes.extend(ordered_item_changes(
+            "Cap device allocations",
+            &old_backstore.cap.allocs,
+            &new_backstore.cap.allocs,
+            fmt_segment,
+        ));
+        changes.extend(ordered_item_changes(
+            "Crypt metadata allocations",
+            &old_backstore.cap.crypt_meta_allocs,
+            &new_backstore.cap.crypt_meta_allocs,
+            fmt_segment,
+        ));
+
+        let (old_flex, new_flex) = (&old.flex_devs, &new.flex_devs);
+        for (what, old_segs, new_segs) in [
+            ("MDV", &old_flex.meta_dev, &new_flex.meta_dev),
+            (
+                "Thin metadata device",
+                &old_flex.thin_meta_dev,
+                &new_flex.thin_meta_dev,
+            ),
+            (
+                "Thin data device",
+                &old_flex.thin_data_dev,
+                &new_flex.thin_data_dev,
+            ),
+            (
+                "Spare thin metadata device",
+                &old_flex.thin_meta_dev_spare,
+                &new_flex.thin_meta_dev_spare,
+            ),
+        ] {
+            changes.extend(ordered_item_changes(what, old_segs, new_segs, fmt_segment));
+        }
+
+        changes
+    }
+
     /// Print a human-useful representation of the metadata's meaning.
+    #[cfg(feature = "extras")]
     pub fn print(metadata: &PoolSave) -> StratisResult<()> {
         let encrypted = metadata.features.contains(&PoolFeatures::Encryption);
 
@@ -799,3 +1744,174 @@ pub mod inspectors {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "extras")]
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    /// Verify that overlapping extents and extents before the start of the
+    /// device are found.
+    fn test_check_overlap() {
+        let mut cap_device = CapDevice::new(false);
+        let offset = DEFAULT_CRYPT_DATA_OFFSET_V2;
+        cap_device
+            .add(Some(&[
+                (Sectors(0), Sectors(8)),
+                (offset, Sectors(16)),
+                (offset + Sectors(8), Sectors(16)),
+            ]))
+            .unwrap();
+
+        let findings = cap_device.check();
+        assert_eq!(findings.len(), 2);
+        assert!(matches!(
+            findings[0],
+            InspectionFinding::OutOfBounds { start, end: None, .. } if start == offset
+        ));
+        assert!(matches!(
+            findings[1],
+            InspectionFinding::Overlap { previous_end, .. } if previous_end == offset + Sectors(16)
+        ));
+        assert!(findings
+            .iter()
+            .all(|finding| finding.severity() == Severity::Error));
+    }
+
+    #[test]
+    /// Verify that a gap between cap device allocations is found and is only
+    /// a warning.
+    fn test_cap_device_gap() {
+        let mut cap_device = CapDevice::new(true);
+        cap_device
+            .add(Some(&[
+                (Sectors(0), Sectors(16)),
+                (Sectors(32), Sectors(16)),
+            ]))
+            .unwrap();
+
+        let findings = cap_device.check();
+        assert_eq!(
+            findings,
+            vec![InspectionFinding::Gap {
+                component: InspectedComponent::CapDevice,
+                extent: (Sectors(16), Sectors(16)),
+            }]
+        );
+        assert_eq!(findings[0].severity(), Severity::Warning);
+        assert_eq!(cap_device.end(), Sectors(48));
+    }
+
+    #[test]
+    /// Verify that flex device allocations beyond the space allocated from
+    /// the cap device are found.
+    fn test_flex_device_bounds() {
+        let mut cap_device = CapDevice::new(true);
+        cap_device.add(Some(&[(Sectors(0), Sectors(64))])).unwrap();
+
+        let mut flex_device = FlexDevice::new(true);
+        flex_device
+            .add(
+                Some(&vec![(Sectors(0), Sectors(8))]),
+                Some(&vec![(Sectors(8), Sectors(8))]),
+                Some(&vec![(Sectors(16), Sectors(16))]),
+                Some(&vec![(Sectors(32), Sectors(64))]),
+            )
+            .unwrap();
+
+        assert_eq!(
+            flex_device.check(&cap_device),
+            vec![InspectionFinding::OutOfBounds {
+                component: InspectedComponent::FlexDevice,
+                device: None,
+                usage: FlexDeviceUse::ThinDataDev.to_string(),
+                extent: (Sectors(32), Sectors(64)),
+                start: Sectors(0),
+                end: Some(Sectors(64)),
+            }]
+        );
+    }
+
+    #[test]
+    /// Verify the findings for the allocations for crypt metadata, and that
+    /// the inspection does not fail if there are none.
+    fn test_crypt_allocs() {
+        let crypt_allocs = CryptAllocs::new();
+        assert_eq!(
+            crypt_allocs.check(),
+            vec![InspectionFinding::MissingCryptAllocation]
+        );
+
+        let mut crypt_allocs = CryptAllocs::new();
+        crypt_allocs
+            .add(Some(&vec![(Sectors(0), DEFAULT_CRYPT_DATA_OFFSET_V2)]))
+            .unwrap();
+        assert!(crypt_allocs.check().is_empty());
+
+        let mut crypt_allocs = CryptAllocs::new();
+        crypt_allocs
+            .add(Some(&vec![(Sectors(8), DEFAULT_CRYPT_DATA_OFFSET_V2)]))
+            .unwrap();
+        assert_eq!(
+            crypt_allocs.check(),
+            vec![InspectionFinding::MisplacedCryptAllocation {
+                extent: (Sectors(8), DEFAULT_CRYPT_DATA_OFFSET_V2)
+            }]
+        );
+    }
+
+    #[cfg(feature = "extras")]
+    /// Metadata of a pool with a single data device from which size sectors
+    /// are allocated, all of them to the thin data device except for the
+    /// first 4096.
+    fn pool_with_size(dev: DevUuid, size: u64) -> PoolSave {
+        serde_json::from_value(json!({
+            "name": "pool",
+            "backstore": {
+                "data_tier": {
+                    "blockdev": {
+                        "allocs": [[{"parent": dev, "start": 8192, "length": size}]],
+                        "devs": [{"uuid": dev}],
+                    },
+                },
+                "cap": {"allocs": [[0, size]]},
+            },
+            "flex_devs": {
+                "meta_dev": [[0, 1024]],
+                "thin_meta_dev": [[1024, 1024]],
+                "thin_data_dev": [[4096, size - 4096]],
+                "thin_meta_dev_spare": [[2048, 1024]],
+            },
+            "thinpool_dev": {"data_block_size": 2048},
+        }))
+        .expect("valid pool metadata")
+    }
+
+    #[cfg(feature = "extras")]
+    #[test]
+    /// Verify that the allocations recorded before the data device of a pool
+    /// was extended are found to differ from those recorded after, so that
+    /// metadata from before the extension is not restored.
+    fn test_allocation_changes_extension() {
+        let dev = DevUuid::new_v4();
+        let before = pool_with_size(dev, 100_000);
+        let after = pool_with_size(dev, 200_000);
+
+        assert!(inspectors::allocation_changes(&before, &before).is_empty());
+
+        let changes = inspectors::allocation_changes(&after, &before);
+        assert!(changes
+            .iter()
+            .any(|change| change.starts_with("Data tier allocations")));
+        assert!(changes
+            .iter()
+            .any(|change| change.starts_with("Cap device allocations")));
+        assert!(changes
+            .iter()
+            .any(|change| change.starts_with("Thin data device")));
+        assert!(!changes.iter().any(|change| change.starts_with("MDV")));
+    }
+}
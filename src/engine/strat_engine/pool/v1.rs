@@ -5,7 +5,7 @@
 use std::{
     cmp::max,
     collections::{HashMap, HashSet},
-    path::Path,
+    path::{Path, PathBuf},
     vec::Vec,
 };
 
@@ -29,8 +29,9 @@ use crate::{
     engine::{
         engine::{BlockDev, DumpState, Filesystem, Pool, StateDiff},
         shared::{
-            init_cache_idempotent_or_err, validate_filesystem_size, validate_filesystem_size_specs,
-            validate_name, validate_paths,
+            init_cache_idempotent_or_err, validate_filesystem_reservation,
+            validate_filesystem_size, validate_filesystem_size_specs, validate_name,
+            validate_paths,
         },
         strat_engine::{
             backstore::{
@@ -43,13 +44,14 @@ use crate::{
             metadata::BDA,
             serde_structs::{FlexDevsSave, PoolSave, Recordable},
             shared::tiers_to_bdas,
-            thinpool::{StratFilesystem, ThinPool},
+            thinpool::{CloneSource, StratFilesystem, ThinPool},
             types::BDARecordResult,
         },
         types::{
-            ActionAvailability, BlockDevTier, Clevis, Compare, CreateAction, DeleteAction, DevUuid,
-            Diff, FilesystemUuid, GrowAction, Key, KeyDescription, Name, OptionalTokenSlotInput,
-            PoolDiff, PoolEncryptionInfo, PoolUuid, RegenAction, RenameAction, SetCreateAction,
+            ActionAvailability, BatchChange, BatchOperation, BlockDevTier, CacheStats, Clevis,
+            CompactionReport, Compare, CreateAction, DeleteAction, DevUuid, Diff, FilesystemUuid,
+            GrowAction, Key, KeyDescription, Name, OptionalTokenSlotInput, PoolDiff,
+            PoolEncryptionInfo, PoolLayout, PoolUuid, RegenAction, RenameAction, SetCreateAction,
             SetDeleteAction, StratFilesystemDiff, StratPoolDiff, StratSigblockVersion,
         },
         EncryptionInfo, PropChangeAction,
@@ -155,6 +157,19 @@ fn check_metadata(metadata: &PoolSave) -> StratisResult<()> {
     Ok(())
 }
 
+/// Inspect the layout recorded in metadata which is about to be written.
+/// Writing metadata which records an invalid layout is a bug, so this is
+/// done in debug builds only.
+#[cfg(debug_assertions)]
+fn inspect_metadata(metadata: &PoolSave) {
+    if let Err(err) = super::inspection::inspectors::check(metadata) {
+        panic!(
+            "Metadata to be written for pool {} is not valid: {}",
+            metadata.name, err
+        );
+    }
+}
+
 // Takes a set of information determined about the pool in liminal devices and
 // determines what the state of the pool should be when it is set up.
 fn get_pool_state(info: Option<PoolEncryptionInfo>, backstore: &Backstore) -> ActionAvailability {
@@ -177,6 +192,7 @@ pub struct StratPool {
     thin_pool: ThinPool<Backstore>,
     action_avail: ActionAvailability,
     metadata_size: Sectors,
+    cache_stats: Option<CacheStats>,
 }
 
 #[strat_pool_impl_gen]
@@ -243,6 +259,7 @@ impl StratPool {
             thin_pool: thinpool,
             action_avail: ActionAvailability::Full,
             metadata_size,
+            cache_stats: None,
         };
 
         pool.write_metadata(&Name::new(name.to_owned()))?;
@@ -307,6 +324,7 @@ impl StratPool {
             thin_pool: thinpool,
             action_avail,
             metadata_size,
+            cache_stats: None,
         };
 
         // The value of the started field in the pool metadata needs to be
@@ -345,7 +363,10 @@ impl StratPool {
     /// Write current metadata to pool members.
     #[pool_mutating_action("NoPoolChanges")]
     pub fn write_metadata(&mut self, name: &str) -> StratisResult<()> {
-        let data = serde_json::to_vec(&self.record(name))?;
+        let record = self.record(name);
+        #[cfg(debug_assertions)]
+        inspect_metadata(&record);
+        let data = serde_json::to_vec(&record)?;
         self.backstore.save_state(&data)
     }
 
@@ -389,6 +410,69 @@ impl StratPool {
         self.thin_pool.check_fs(pool_uuid, &self.backstore)
     }
 
+    /// Take a temporary snapshot of a filesystem in this pool which is to be
+    /// cloned into another pool.
+    #[pool_mutating_action("NoRequests")]
+    pub fn prepare_clone_source(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        fs_uuid: FilesystemUuid,
+    ) -> StratisResult<CloneSource> {
+        self.thin_pool
+            .prepare_clone_source(pool_name, pool_uuid, fs_uuid)
+    }
+
+    /// Back up the thin pool metadata of this pool to its MDV.
+    pub fn backup_thin_metadata(&self) -> StratisResult<PathBuf> {
+        self.thin_pool.backup_metadata()
+    }
+
+    /// Remove the temporary snapshot taken by prepare_clone_source().
+    pub fn destroy_clone_source(
+        &mut self,
+        pool_name: &str,
+        snapshot_uuid: FilesystemUuid,
+    ) -> StratisResult<()> {
+        self.thin_pool
+            .destroy_clone_source(pool_name, snapshot_uuid)
+    }
+
+    /// Create a filesystem in this pool which is a copy of a filesystem in
+    /// another pool.
+    #[pool_mutating_action("NoRequests")]
+    pub fn clone_filesystem(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        name: &str,
+        source: &CloneSource,
+        preserve_uuid: bool,
+        progress: &mut dyn FnMut(Sectors, Sectors),
+    ) -> StratisResult<FilesystemUuid> {
+        self.check_fs_limit(1)?;
+        validate_name(name)?;
+        self.check_overprov(source.size)?;
+        self.thin_pool.check_reservations(
+            &self.backstore,
+            source.ranges.iter().map(|(_, length)| *length).sum(),
+        )?;
+
+        let (extended, res) = self.thin_pool.clone_filesystem(
+            pool_name,
+            pool_uuid,
+            &mut self.backstore,
+            name,
+            source,
+            preserve_uuid,
+            progress,
+        );
+        if extended {
+            self.write_metadata(pool_name)?;
+        }
+        res
+    }
+
     pub fn record(&self, name: &str) -> PoolSave {
         PoolSave {
             name: name.to_owned(),
@@ -455,6 +539,8 @@ impl StratPool {
         self.thin_pool.teardown(pool_uuid)?;
         let mut data = self.record(pool_name);
         data.started = Some(false);
+        #[cfg(debug_assertions)]
+        inspect_metadata(&data);
         let json = serde_json::to_vec(&data).map_err(|e| (StratisError::from(e), false))?;
         self.backstore.save_state(&json).map_err(|e| (e, false))?;
         self.backstore.teardown(pool_uuid).map_err(|e| (e, false))?;
@@ -820,6 +906,13 @@ impl Pool for StratPool {
             .copied()
             .sum::<Sectors>();
         self.check_overprov(increase)?;
+        self.thin_pool.check_reservations(
+            &self.backstore,
+            spec_map
+                .values()
+                .map(|(size, _)| self.thin_pool.new_fs_footprint(*size))
+                .sum(),
+        )?;
 
         spec_map.iter().try_fold((), |_, (name, (size, _))| {
             validate_name(name)
@@ -855,6 +948,39 @@ impl Pool for StratPool {
         Ok(SetCreateAction::new(result))
     }
 
+    #[pool_mutating_action("NoRequests")]
+    fn create_filesystem_with_uuid(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        fs_uuid: FilesystemUuid,
+        name: &str,
+        size: Option<Bytes>,
+        size_limit: Option<Bytes>,
+    ) -> StratisResult<CreateAction<FilesystemUuid>> {
+        if let Some((existing_name, _)) = self.thin_pool.get_filesystem_by_uuid(fs_uuid) {
+            return if &*existing_name == name {
+                Ok(CreateAction::Identity)
+            } else {
+                Err(StratisError::Msg(format!(
+                    "UUID {fs_uuid} is already in use by filesystem {existing_name}"
+                )))
+            };
+        }
+
+        validate_name(name)?;
+        self.check_fs_limit(1)?;
+        let spec_map = validate_filesystem_size_specs(&[(name, size, size_limit)])?;
+        let (size, size_limit) = spec_map[&name];
+        self.check_overprov(size)?;
+        self.thin_pool
+            .check_reservations(&self.backstore, self.thin_pool.new_fs_footprint(size))?;
+
+        self.thin_pool
+            .create_filesystem_with_uuid(pool_name, pool_uuid, fs_uuid, name, size, size_limit)
+            .map(CreateAction::Created)
+    }
+
     #[pool_mutating_action("NoRequests")]
     fn add_blockdevs(
         &mut self,
@@ -1100,6 +1226,8 @@ impl Pool for StratPool {
                 .1
                 .thindev_size(),
         )?;
+        self.thin_pool
+            .check_reservations(&self.backstore, self.thin_pool.snapshot_footprint())?;
 
         if self
             .thin_pool
@@ -1293,6 +1421,26 @@ impl Pool for StratPool {
         }
     }
 
+    #[pool_mutating_action("NoRequests")]
+    fn set_fs_reservation(
+        &mut self,
+        fs_uuid: FilesystemUuid,
+        reservation: Option<Bytes>,
+    ) -> StratisResult<PropChangeAction<Option<Sectors>>> {
+        let (name, _) = self.get_filesystem(fs_uuid).ok_or_else(|| {
+            StratisError::Msg(format!("Filesystem with UUID {fs_uuid} not found"))
+        })?;
+        let reservation = validate_filesystem_reservation(&name, reservation)?;
+        if self
+            .thin_pool
+            .set_fs_reservation(&self.backstore, fs_uuid, reservation)?
+        {
+            Ok(PropChangeAction::NewValue(reservation))
+        } else {
+            Ok(PropChangeAction::Identity)
+        }
+    }
+
     fn current_metadata(&self, pool_name: &Name) -> StratisResult<String> {
         serde_json::to_string(&self.record(pool_name)).map_err(|e| e.into())
     }
@@ -1331,11 +1479,39 @@ impl Pool for StratPool {
             Ok(PropChangeAction::Identity)
         }
     }
+
+    fn apply_batch(
+        &mut self,
+        _pool_name: &Name,
+        _pool_uuid: PoolUuid,
+        _ops: &[BatchOperation],
+    ) -> StratisResult<Vec<BatchChange>> {
+        Err(StratisError::Msg(
+            "Batches of operations are only supported by pools with metadata version 2".into(),
+        ))
+    }
+
+    fn layout(&self, _pool_name: &Name) -> StratisResult<PoolLayout> {
+        Err(StratisError::Msg(
+            "Pool layouts are only available for pools with metadata version 2".into(),
+        ))
+    }
+
+    fn compact(
+        &mut self,
+        _pool_name: &Name,
+        _pool_uuid: PoolUuid,
+    ) -> StratisResult<CompactionReport> {
+        Err(StratisError::Msg(
+            "Compaction is only supported by pools with metadata version 2".into(),
+        ))
+    }
 }
 
 pub struct StratPoolState {
     metadata_size: Bytes,
     out_of_alloc_space: bool,
+    cache: Option<CacheStats>,
 }
 
 impl StateDiff for StratPoolState {
@@ -1345,6 +1521,7 @@ impl StateDiff for StratPoolState {
         StratPoolDiff {
             metadata_size: self.metadata_size.compare(&other.metadata_size),
             out_of_alloc_space: self.out_of_alloc_space.compare(&other.out_of_alloc_space),
+            cache: self.cache.compare(&other.cache),
         }
     }
 
@@ -1352,6 +1529,7 @@ impl StateDiff for StratPoolState {
         StratPoolDiff {
             metadata_size: Diff::Unchanged(self.metadata_size),
             out_of_alloc_space: Diff::Unchanged(self.out_of_alloc_space),
+            cache: Diff::Unchanged(self.cache),
         }
     }
 }
@@ -1364,14 +1542,17 @@ impl DumpState<'_> for StratPool {
         StratPoolState {
             metadata_size: self.metadata_size.bytes(),
             out_of_alloc_space: self.thin_pool.out_of_alloc_space(),
+            cache: self.cache_stats,
         }
     }
 
     fn dump(&mut self, _: Self::DumpInput) -> Self::State {
         self.metadata_size = self.backstore.datatier_metadata_size();
+        self.cache_stats = self.backstore.cache_stats();
         StratPoolState {
             metadata_size: self.metadata_size.bytes(),
             out_of_alloc_space: self.thin_pool.out_of_alloc_space(),
+            cache: self.cache_stats,
         }
     }
 }
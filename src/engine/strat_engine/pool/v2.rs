@@ -4,7 +4,7 @@
 
 use std::{
     collections::{HashMap, HashSet},
-    path::Path,
+    path::{Path, PathBuf},
     vec::Vec,
 };
 
@@ -18,9 +18,11 @@ use stratisd_proc_macros::strat_pool_impl_gen;
 use crate::{
     engine::{
         engine::{BlockDev, DumpState, Filesystem, Pool, StateDiff},
+        jobs::Job,
         shared::{
-            init_cache_idempotent_or_err, validate_filesystem_size, validate_filesystem_size_specs,
-            validate_name, validate_paths,
+            init_cache_idempotent_or_err, validate_batch, validate_filesystem_reservation,
+            validate_filesystem_size, validate_filesystem_size_specs, validate_name,
+            validate_paths,
         },
         strat_engine::{
             backstore::{
@@ -32,13 +34,17 @@ use crate::{
             metadata::{MDADataSize, BDA},
             serde_structs::{FlexDevsSave, PoolFeatures, PoolSave, Recordable},
             shared::tiers_to_bdas,
-            thinpool::{StratFilesystem, ThinPool, ThinPoolSizeParams, DATA_BLOCK_SIZE},
+            thinpool::{
+                CloneSource, Relocation, StratFilesystem, ThinPool, ThinPoolSizeParams,
+                DATA_BLOCK_SIZE,
+            },
             types::BDARecordResult,
         },
         types::{
-            ActionAvailability, BlockDevTier, Clevis, Compare, CreateAction, DeleteAction, DevUuid,
-            Diff, EncryptionInfo, FilesystemUuid, GrowAction, InputEncryptionInfo, Key,
-            KeyDescription, Name, OptionalTokenSlotInput, PoolDiff, PoolEncryptionInfo, PoolUuid,
+            ActionAvailability, BatchChange, BatchOperation, BlockDevTier, CacheStats, Clevis,
+            CompactionReport, Compare, CreateAction, DeleteAction, DevUuid, Diff, EncryptionInfo,
+            EngineAction, FilesystemUuid, GrowAction, InputEncryptionInfo, Key, KeyDescription,
+            Name, OptionalTokenSlotInput, PoolDiff, PoolEncryptionInfo, PoolLayout, PoolUuid,
             PropChangeAction, RegenAction, RenameAction, SetCreateAction, SetDeleteAction,
             SizedKeyMemory, StratFilesystemDiff, StratPoolDiff, StratSigblockVersion,
             TokenUnlockMethod, ValidatedIntegritySpec,
@@ -67,6 +73,17 @@ fn next_index(flex_devs: &FlexDevsSave) -> Sectors {
     .sum()
 }
 
+/// The inverse of a change made while applying a batch.
+enum BatchUndo {
+    Create(FilesystemUuid),
+    Rename(FilesystemUuid, Name),
+    FsSizeLimit(FilesystemUuid, Option<Sectors>),
+    FsLimit(u64),
+    OverprovMode(bool),
+    BlockdevUserInfo(DevUuid, Option<String>),
+    Keyring(u32),
+}
+
 /// Check the metadata of an individual pool for consistency.
 /// Precondition: This method is called only when setting up a pool, which
 /// ensures that the flex devs metadata lists are all non-empty.
@@ -137,12 +154,26 @@ fn check_metadata(metadata: &PoolSave) -> StratisResult<()> {
     Ok(())
 }
 
+/// Inspect the layout recorded in metadata which is about to be written.
+/// Writing metadata which records an invalid layout is a bug, so this is
+/// done in debug builds only.
+#[cfg(debug_assertions)]
+fn inspect_metadata(metadata: &PoolSave) {
+    if let Err(err) = super::inspection::inspectors::check(metadata) {
+        panic!(
+            "Metadata to be written for pool {} is not valid: {}",
+            metadata.name, err
+        );
+    }
+}
+
 #[derive(Debug)]
 pub struct StratPool {
     backstore: Backstore,
     thin_pool: ThinPool<Backstore>,
     action_avail: ActionAvailability,
     metadata_size: Sectors,
+    cache_stats: Option<CacheStats>,
 }
 
 #[strat_pool_impl_gen]
@@ -209,6 +240,7 @@ impl StratPool {
             thin_pool: thinpool,
             action_avail: ActionAvailability::Full,
             metadata_size,
+            cache_stats: None,
         };
 
         pool.write_metadata(&Name::new(name.to_owned()))?;
@@ -275,6 +307,7 @@ impl StratPool {
             thin_pool: thinpool,
             action_avail,
             metadata_size,
+            cache_stats: None,
         };
 
         // The value of the started field in the pool metadata needs to be
@@ -313,8 +346,20 @@ impl StratPool {
     /// Write current metadata to pool members.
     #[pool_mutating_action("NoPoolChanges")]
     pub fn write_metadata(&mut self, name: &str) -> StratisResult<()> {
-        let data = serde_json::to_string(&self.record(name))?;
-        self.backstore.save_state(data.as_bytes())
+        self.save_metadata(name, true)
+    }
+
+    /// Write current metadata to pool members, recording it in the metadata
+    /// history if record_history is true. An operation which writes the
+    /// metadata at every one of many steps records only its final state, so
+    /// that it does not push the generations from before it out of the
+    /// history.
+    fn save_metadata(&mut self, name: &str, record_history: bool) -> StratisResult<()> {
+        let record = self.record(name);
+        #[cfg(debug_assertions)]
+        inspect_metadata(&record);
+        let data = serde_json::to_string(&record)?;
+        self.backstore.save_state(data.as_bytes(), record_history)
     }
 
     /// Teardown a pool.
@@ -357,6 +402,69 @@ impl StratPool {
         self.thin_pool.check_fs(pool_uuid, &self.backstore)
     }
 
+    /// Take a temporary snapshot of a filesystem in this pool which is to be
+    /// cloned into another pool.
+    #[pool_mutating_action("NoRequests")]
+    pub fn prepare_clone_source(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        fs_uuid: FilesystemUuid,
+    ) -> StratisResult<CloneSource> {
+        self.thin_pool
+            .prepare_clone_source(pool_name, pool_uuid, fs_uuid)
+    }
+
+    /// Back up the thin pool metadata of this pool to its MDV.
+    pub fn backup_thin_metadata(&self) -> StratisResult<PathBuf> {
+        self.thin_pool.backup_metadata()
+    }
+
+    /// Remove the temporary snapshot taken by prepare_clone_source().
+    pub fn destroy_clone_source(
+        &mut self,
+        pool_name: &str,
+        snapshot_uuid: FilesystemUuid,
+    ) -> StratisResult<()> {
+        self.thin_pool
+            .destroy_clone_source(pool_name, snapshot_uuid)
+    }
+
+    /// Create a filesystem in this pool which is a copy of a filesystem in
+    /// another pool.
+    #[pool_mutating_action("NoRequests")]
+    pub fn clone_filesystem(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        name: &str,
+        source: &CloneSource,
+        preserve_uuid: bool,
+        progress: &mut dyn FnMut(Sectors, Sectors),
+    ) -> StratisResult<FilesystemUuid> {
+        self.check_fs_limit(1)?;
+        validate_name(name)?;
+        self.check_overprov(source.size)?;
+        self.thin_pool.check_reservations(
+            &self.backstore,
+            source.ranges.iter().map(|(_, length)| *length).sum(),
+        )?;
+
+        let (extended, res) = self.thin_pool.clone_filesystem(
+            pool_name,
+            pool_uuid,
+            &mut self.backstore,
+            name,
+            source,
+            preserve_uuid,
+            progress,
+        );
+        if extended {
+            self.write_metadata(pool_name)?;
+        }
+        res
+    }
+
     pub fn record(&self, name: &str) -> PoolSave {
         let mut features = vec![];
         if self.is_encrypted() {
@@ -427,9 +535,11 @@ impl StratPool {
         self.thin_pool.teardown(pool_uuid)?;
         let mut data = self.record(pool_name);
         data.started = Some(false);
+        #[cfg(debug_assertions)]
+        inspect_metadata(&data);
         let json = serde_json::to_string(&data).map_err(|e| (StratisError::from(e), false))?;
         self.backstore
-            .save_state(json.as_bytes())
+            .save_state(json.as_bytes(), true)
             .map_err(|e| (e, false))?;
         self.backstore.teardown(pool_uuid).map_err(|e| (e, false))?;
         let bds = self.backstore.drain_bds();
@@ -446,6 +556,267 @@ impl StratPool {
         self.backstore.drain_bds()
     }
 
+    /// Apply a single operation of a batch, recording how to undo it.
+    fn apply_batch_operation(
+        &mut self,
+        pool_name: &Name,
+        pool_uuid: PoolUuid,
+        op: &BatchOperation,
+        undo: &mut Vec<BatchUndo>,
+        changes: &mut Vec<BatchChange>,
+    ) -> StratisResult<()> {
+        match op {
+            BatchOperation::CreateFilesystem {
+                name,
+                size,
+                size_limit,
+            } => {
+                let created = self.create_filesystems(
+                    pool_name,
+                    pool_uuid,
+                    &[(
+                        name.as_str(),
+                        size.map(Bytes::from),
+                        size_limit.map(Bytes::from),
+                    )],
+                )?;
+                for (name, uuid, _) in created.changed().unwrap_or_default() {
+                    undo.push(BatchUndo::Create(uuid));
+                    changes.push(BatchChange::FilesystemCreated(
+                        Name::new(name.to_owned()),
+                        uuid,
+                    ));
+                }
+            }
+            BatchOperation::RenameFilesystem { name, new_name } => {
+                let uuid = self.batch_filesystem_uuid(name)?;
+                if let RenameAction::Renamed(_) =
+                    self.rename_filesystem(pool_name, uuid, new_name)?
+                {
+                    undo.push(BatchUndo::Rename(uuid, Name::new(name.to_owned())));
+                    changes.push(BatchChange::FilesystemRenamed(
+                        uuid,
+                        Name::new(new_name.to_owned()),
+                    ));
+                }
+            }
+            BatchOperation::SetFsSizeLimit { name, limit } => {
+                let uuid = self.batch_filesystem_uuid(name)?;
+                let old = self
+                    .thin_pool
+                    .get_filesystem_by_uuid(uuid)
+                    .and_then(|(_, fs)| fs.size_limit());
+                if let PropChangeAction::NewValue(new) =
+                    self.set_fs_size_limit(uuid, limit.map(Bytes::from))?
+                {
+                    undo.push(BatchUndo::FsSizeLimit(uuid, old));
+                    changes.push(BatchChange::FsSizeLimitChanged(uuid, new));
+                }
+            }
+            BatchOperation::SetFsLimit { limit } => {
+                let old = self.thin_pool.fs_limit();
+                if *limit != old {
+                    let (_, res) =
+                        self.thin_pool
+                            .set_fs_limit(pool_uuid, &mut self.backstore, *limit);
+                    res?;
+                    undo.push(BatchUndo::FsLimit(old));
+                    changes.push(BatchChange::FsLimitChanged(*limit));
+                }
+            }
+            BatchOperation::SetOverprovMode { enabled } => {
+                let old = self.thin_pool.overprov_enabled();
+                let (changed, res) = self.thin_pool.set_overprov_mode(&self.backstore, *enabled);
+                res?;
+                if changed {
+                    undo.push(BatchUndo::OverprovMode(old));
+                    changes.push(BatchChange::OverprovModeChanged(*enabled));
+                }
+            }
+            BatchOperation::SetBlockdevUserInfo { uuid, user_info } => {
+                let old = self
+                    .backstore
+                    .get_blockdev_by_uuid(*uuid)
+                    .and_then(|(_, bd)| bd.user_info().map(str::to_owned));
+                if self
+                    .backstore
+                    .set_blockdev_user_info(*uuid, user_info.as_deref())?
+                    .is_some()
+                {
+                    undo.push(BatchUndo::BlockdevUserInfo(*uuid, old));
+                    changes.push(BatchChange::BlockdevUserInfoChanged(
+                        *uuid,
+                        user_info.clone(),
+                    ));
+                }
+            }
+            BatchOperation::BindKeyring {
+                token_slot,
+                key_desc,
+            } => {
+                let token_slot =
+                    token_slot.map_or(OptionalTokenSlotInput::None, OptionalTokenSlotInput::Some);
+                if let Some(slot) = self.backstore.bind_keyring(token_slot, key_desc)? {
+                    undo.push(BatchUndo::Keyring(slot));
+                    changes.push(BatchChange::KeyringBound(slot, key_desc.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn batch_filesystem_uuid(&self, name: &str) -> StratisResult<FilesystemUuid> {
+        self.thin_pool
+            .get_filesystem_by_name(name)
+            .map(|(uuid, _)| uuid)
+            .ok_or_else(|| StratisError::Msg(format!("Filesystem {name} does not exist")))
+    }
+
+    /// Undo the changes made while applying a batch, most recent first.
+    /// Every change is attempted; the first error encountered is returned.
+    fn undo_batch(&mut self, pool_name: &Name, undo: Vec<BatchUndo>) -> StratisResult<()> {
+        let mut result = Ok(());
+        for item in undo.into_iter().rev() {
+            let res = match item {
+                BatchUndo::Create(uuid) => self
+                    .thin_pool
+                    .destroy_filesystems(pool_name, &HashSet::from([uuid]))
+                    .map(|_| ()),
+                BatchUndo::Rename(uuid, name) => self
+                    .thin_pool
+                    .rename_filesystem(pool_name, uuid, &name)
+                    .map(|_| ()),
+                BatchUndo::FsSizeLimit(uuid, limit) => {
+                    self.thin_pool.set_fs_size_limit(uuid, limit).map(|_| ())
+                }
+                BatchUndo::FsLimit(limit) => {
+                    self.thin_pool.restore_fs_limit(limit);
+                    Ok(())
+                }
+                BatchUndo::OverprovMode(enabled) => {
+                    self.thin_pool.set_overprov_mode(&self.backstore, enabled).1
+                }
+                BatchUndo::BlockdevUserInfo(uuid, user_info) => self
+                    .backstore
+                    .set_blockdev_user_info(uuid, user_info.as_deref())
+                    .map(|_| ()),
+                BatchUndo::Keyring(slot) => self.backstore.unbind_keyring(Some(slot)).map(|_| ()),
+            };
+            if let Err(e) = res {
+                warn!(
+                    "Failed to undo a change made by a batch of operations: {}",
+                    e
+                );
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Carry out a step of the compaction of the flex devices. The thin pool
+    /// remains suspended until the new location of the relocated sectors has
+    /// been recorded in the pool-level metadata, so that no write to them can
+    /// be lost if stratisd stops in the meantime. The intermediate metadata
+    /// is not recorded in the metadata history.
+    fn relocate(
+        &mut self,
+        pool_name: &Name,
+        devnode: &Path,
+        relocation: &Relocation,
+    ) -> StratisResult<()> {
+        self.thin_pool.suspend()?;
+        let res = self
+            .thin_pool
+            .relocate(devnode, relocation)
+            .and_then(|old_segments| {
+                self.save_metadata(pool_name, false)
+                    .or_else(|causal_error| {
+                        match self
+                            .thin_pool
+                            .set_flex_segments(relocation.dev, old_segments)
+                        {
+                            Ok(()) => Err(causal_error),
+                            Err(rollback_error) => Err(StratisError::RollbackError {
+                                causal_error: Box::new(causal_error),
+                                rollback_error: Box::new(rollback_error),
+                                level: ActionAvailability::NoRequests,
+                            }),
+                        }
+                    })
+            });
+        let resumed = self.thin_pool.resume();
+        res.and(resumed)
+    }
+
+    /// Compact the flex devices, carrying out each step with step.
+    ///
+    /// The space allocated for the compaction is recorded in the pool-level
+    /// metadata before the first step, so that if a step fails, the space
+    /// remains allocated to the pool, and the next compaction, which treats
+    /// it as free, resumes where this one left off. Only the final state is
+    /// recorded in the metadata history, so that the generations from before
+    /// the compaction are kept.
+    fn compact_with<F>(
+        &mut self,
+        pool_name: &Name,
+        pool_uuid: PoolUuid,
+        mut step: F,
+    ) -> StratisResult<CompactionReport>
+    where
+        F: FnMut(&mut Self, &Path, &Relocation) -> StratisResult<()>,
+    {
+        let devnode = self
+            .backstore
+            .devnode()
+            .expect("pool has a thin pool, so the backstore must have a cap device");
+        let allocated = self.backstore.datatier_allocated_size();
+        let mut compaction = self
+            .thin_pool
+            .plan_compaction(pool_uuid, &mut self.backstore)?;
+        let topped_up = self.backstore.datatier_allocated_size() != allocated;
+        if topped_up {
+            self.save_metadata(pool_name, false)?;
+        }
+        let segments_before = compaction.segment_count();
+
+        let job = Job::current();
+        let misplaced = *compaction.misplaced();
+        let mut moved = Sectors(0);
+        while let Some(relocation) = compaction.next_step() {
+            // The pool is consistent between steps, and a compaction which is
+            // cancelled is resumed by the next one.
+            if let Some(ref job) = job {
+                job.check_cancelled()?;
+            }
+            step(self, &devnode, &relocation)?;
+            compaction.apply(&relocation);
+            moved += relocation.length;
+            if let Some(ref job) = job {
+                let placed = misplaced.saturating_sub(*compaction.misplaced());
+                job.set_done(placed, misplaced);
+            }
+        }
+
+        let data_extension = self.thin_pool.finish_compaction(&compaction)?;
+        if topped_up || moved > Sectors(0) || data_extension > Sectors(0) {
+            self.write_metadata(pool_name)?;
+        }
+
+        let report = CompactionReport {
+            segments_before,
+            segments_after: compaction.segment_count(),
+            moved,
+            data_extension,
+        };
+        info!(
+            "Compacted the flex devices of pool {}: {}",
+            pool_name, report
+        );
+        Ok(report)
+    }
+
     #[cfg(test)]
     #[pool_mutating_action("NoRequests")]
     #[pool_rollback]
@@ -727,6 +1098,13 @@ impl Pool for StratPool {
             .copied()
             .sum::<Sectors>();
         self.check_overprov(increase)?;
+        self.thin_pool.check_reservations(
+            &self.backstore,
+            spec_map
+                .values()
+                .map(|(size, _)| self.thin_pool.new_fs_footprint(*size))
+                .sum(),
+        )?;
 
         spec_map.iter().try_fold((), |_, (name, (size, _))| {
             validate_name(name)
@@ -762,6 +1140,39 @@ impl Pool for StratPool {
         Ok(SetCreateAction::new(result))
     }
 
+    #[pool_mutating_action("NoRequests")]
+    fn create_filesystem_with_uuid(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        fs_uuid: FilesystemUuid,
+        name: &str,
+        size: Option<Bytes>,
+        size_limit: Option<Bytes>,
+    ) -> StratisResult<CreateAction<FilesystemUuid>> {
+        if let Some((existing_name, _)) = self.thin_pool.get_filesystem_by_uuid(fs_uuid) {
+            return if &*existing_name == name {
+                Ok(CreateAction::Identity)
+            } else {
+                Err(StratisError::Msg(format!(
+                    "UUID {fs_uuid} is already in use by filesystem {existing_name}"
+                )))
+            };
+        }
+
+        validate_name(name)?;
+        self.check_fs_limit(1)?;
+        let spec_map = validate_filesystem_size_specs(&[(name, size, size_limit)])?;
+        let (size, size_limit) = spec_map[&name];
+        self.check_overprov(size)?;
+        self.thin_pool
+            .check_reservations(&self.backstore, self.thin_pool.new_fs_footprint(size))?;
+
+        self.thin_pool
+            .create_filesystem_with_uuid(pool_name, pool_uuid, fs_uuid, name, size, size_limit)
+            .map(CreateAction::Created)
+    }
+
     #[pool_mutating_action("NoRequests")]
     fn add_blockdevs(
         &mut self,
@@ -971,6 +1382,8 @@ impl Pool for StratPool {
                 .1
                 .thindev_size(),
         )?;
+        self.thin_pool
+            .check_reservations(&self.backstore, self.thin_pool.snapshot_footprint())?;
 
         if self
             .thin_pool
@@ -1165,6 +1578,26 @@ impl Pool for StratPool {
         }
     }
 
+    #[pool_mutating_action("NoRequests")]
+    fn set_fs_reservation(
+        &mut self,
+        fs_uuid: FilesystemUuid,
+        reservation: Option<Bytes>,
+    ) -> StratisResult<PropChangeAction<Option<Sectors>>> {
+        let (name, _) = self.get_filesystem(fs_uuid).ok_or_else(|| {
+            StratisError::Msg(format!("Filesystem with UUID {fs_uuid} not found"))
+        })?;
+        let reservation = validate_filesystem_reservation(&name, reservation)?;
+        if self
+            .thin_pool
+            .set_fs_reservation(&self.backstore, fs_uuid, reservation)?
+        {
+            Ok(PropChangeAction::NewValue(reservation))
+        } else {
+            Ok(PropChangeAction::Identity)
+        }
+    }
+
     fn current_metadata(&self, pool_name: &Name) -> StratisResult<String> {
         serde_json::to_string(&self.record(pool_name)).map_err(|e| e.into())
     }
@@ -1203,11 +1636,71 @@ impl Pool for StratPool {
             Ok(PropChangeAction::Identity)
         }
     }
+
+    #[pool_mutating_action("NoRequests")]
+    #[pool_rollback]
+    fn apply_batch(
+        &mut self,
+        pool_name: &Name,
+        pool_uuid: PoolUuid,
+        ops: &[BatchOperation],
+    ) -> StratisResult<Vec<BatchChange>> {
+        validate_batch(self, ops)?;
+
+        let mut undo = Vec::new();
+        let mut changes = Vec::new();
+        let res = ops
+            .iter()
+            .try_for_each(|op| {
+                self.apply_batch_operation(pool_name, pool_uuid, op, &mut undo, &mut changes)
+            })
+            .and_then(|()| {
+                if changes.is_empty() {
+                    Ok(())
+                } else {
+                    self.write_metadata(pool_name)
+                }
+            });
+
+        match res {
+            Ok(()) => Ok(changes),
+            Err(causal_error) => match self.undo_batch(pool_name, undo) {
+                Ok(()) => Err(causal_error),
+                Err(rollback_error) => Err(StratisError::RollbackError {
+                    causal_error: Box::new(causal_error),
+                    rollback_error: Box::new(rollback_error),
+                    level: ActionAvailability::NoRequests,
+                }),
+            },
+        }
+    }
+
+    fn layout(&self, pool_name: &Name) -> StratisResult<PoolLayout> {
+        let device_sizes = self
+            .blockdevs()
+            .into_iter()
+            .map(|(uuid, _, bd)| (uuid, bd.size()))
+            .collect::<HashMap<_, _>>();
+        super::inspection::inspectors::layout(&self.record(pool_name), &device_sizes)
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    #[pool_rollback]
+    fn compact(
+        &mut self,
+        pool_name: &Name,
+        pool_uuid: PoolUuid,
+    ) -> StratisResult<CompactionReport> {
+        self.compact_with(pool_name, pool_uuid, |pool, devnode, relocation| {
+            pool.relocate(pool_name, devnode, relocation)
+        })
+    }
 }
 
 pub struct StratPoolState {
     metadata_size: Bytes,
     out_of_alloc_space: bool,
+    cache: Option<CacheStats>,
 }
 
 impl StateDiff for StratPoolState {
@@ -1217,6 +1710,7 @@ impl StateDiff for StratPoolState {
         StratPoolDiff {
             metadata_size: self.metadata_size.compare(&other.metadata_size),
             out_of_alloc_space: self.out_of_alloc_space.compare(&other.out_of_alloc_space),
+            cache: self.cache.compare(&other.cache),
         }
     }
 
@@ -1224,6 +1718,7 @@ impl StateDiff for StratPoolState {
         StratPoolDiff {
             metadata_size: Diff::Unchanged(self.metadata_size),
             out_of_alloc_space: Diff::Unchanged(self.out_of_alloc_space),
+            cache: Diff::Unchanged(self.cache),
         }
     }
 }
@@ -1236,14 +1731,17 @@ impl DumpState<'_> for StratPool {
         StratPoolState {
             metadata_size: self.metadata_size.bytes(),
             out_of_alloc_space: self.thin_pool.out_of_alloc_space(),
+            cache: self.cache_stats,
         }
     }
 
     fn dump(&mut self, _: Self::DumpInput) -> Self::State {
         self.metadata_size = self.backstore.datatier_metadata_size();
+        self.cache_stats = self.backstore.cache_stats();
         StratPoolState {
             metadata_size: self.metadata_size.bytes(),
             out_of_alloc_space: self.thin_pool.out_of_alloc_space(),
+            cache: self.cache_stats,
         }
     }
 }
@@ -1705,6 +2203,181 @@ mod tests {
         );
     }
 
+    /// Test that compaction leaves each flex device in a single extent and
+    /// that data written to a filesystem before compaction can be read
+    /// afterwards.
+    fn test_compact(paths: &[&Path]) {
+        assert!(paths.len() == 1);
+
+        let pool_name = Name::new("pool".to_string());
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let (pool_uuid, mut pool) = StratPool::initialize(
+            &pool_name,
+            unowned_devices,
+            None,
+            ValidatedIntegritySpec::default(),
+        )
+        .unwrap();
+
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(&pool_name, pool_uuid, &[("stratis-filesystem", None, None)])
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .unwrap();
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let new_file = tmp_dir.path().join("stratis_test.txt");
+        let bytestring = b"some bytes";
+        let devnode = pool.get_filesystem(fs_uuid).unwrap().1.devnode();
+        mount(
+            Some(&devnode),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None as Option<&str>,
+        )
+        .unwrap();
+        {
+            let mut f = OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(&new_file)
+                .unwrap();
+            f.write_all(bytestring).unwrap();
+            f.sync_all().unwrap();
+        }
+
+        // Extending the thin metadata device splits it and its spare.
+        pool.set_fs_limit(&pool_name, pool_uuid, 500).unwrap();
+        invariant(&pool, &pool_name);
+        assert!(pool.record(&pool_name).flex_devs.thin_meta_dev.len() > 1);
+
+        let report = pool.compact(&pool_name, pool_uuid).unwrap();
+        invariant(&pool, &pool_name);
+        assert!(report.moved > Sectors(0));
+        assert_eq!(report.segments_after, 4);
+        let flex_devs = pool.record(&pool_name).flex_devs;
+        for segments in [
+            &flex_devs.meta_dev,
+            &flex_devs.thin_meta_dev,
+            &flex_devs.thin_meta_dev_spare,
+            &flex_devs.thin_data_dev,
+        ] {
+            assert_eq!(segments.len(), 1);
+        }
+
+        let report = pool.compact(&pool_name, pool_uuid).unwrap();
+        assert_eq!(report.moved, Sectors(0));
+
+        umount(tmp_dir.path()).unwrap();
+        mount(
+            Some(&devnode),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None as Option<&str>,
+        )
+        .unwrap();
+        let mut buf = [0u8; 10];
+        OpenOptions::new()
+            .read(true)
+            .open(&new_file)
+            .unwrap()
+            .read_exact(&mut buf)
+            .unwrap();
+        assert_eq!(&buf, bytestring);
+
+        umount(tmp_dir.path()).unwrap();
+        pool.teardown(pool_uuid).unwrap();
+    }
+
+    #[test]
+    fn loop_test_compact() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(1, Some(Sectors(10 * IEC::Mi))),
+            test_compact,
+        );
+    }
+
+    #[test]
+    fn real_test_compact() {
+        real::test_with_spec(
+            &real::DeviceLimits::Exactly(1, Some(Sectors(10 * IEC::Mi)), None),
+            test_compact,
+        );
+    }
+
+    /// Test that a compaction which fails partway leaves the pool
+    /// consistent, and that the next compaction takes up the space allocated
+    /// for the first one and finishes it.
+    fn test_compact_interrupted(paths: &[&Path]) {
+        assert!(paths.len() == 1);
+
+        let pool_name = Name::new("pool".to_string());
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let (pool_uuid, mut pool) = StratPool::initialize(
+            &pool_name,
+            unowned_devices,
+            None,
+            ValidatedIntegritySpec::default(),
+        )
+        .unwrap();
+
+        // Extending the thin metadata device splits it and its spare.
+        pool.set_fs_limit(&pool_name, pool_uuid, 500).unwrap();
+        invariant(&pool, &pool_name);
+
+        let mut steps = 0;
+        assert!(pool
+            .compact_with(&pool_name, pool_uuid, |pool, devnode, relocation| {
+                if steps == 1 {
+                    return Err(StratisError::Msg("Injected failure".into()));
+                }
+                steps += 1;
+                pool.relocate(&pool_name, devnode, relocation)
+            })
+            .is_err());
+        invariant(&pool, &pool_name);
+        let allocated = pool.backstore.datatier_allocated_size();
+
+        let report = pool.compact(&pool_name, pool_uuid).unwrap();
+        invariant(&pool, &pool_name);
+        assert!(report.moved > Sectors(0));
+        assert_eq!(report.segments_after, 4);
+        assert_eq!(pool.backstore.datatier_allocated_size(), allocated);
+
+        pool.teardown(pool_uuid).unwrap();
+    }
+
+    #[test]
+    fn loop_test_compact_interrupted() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(1, Some(Sectors(10 * IEC::Mi))),
+            test_compact_interrupted,
+        );
+    }
+
+    #[test]
+    fn real_test_compact_interrupted() {
+        real::test_with_spec(
+            &real::DeviceLimits::Exactly(1, Some(Sectors(10 * IEC::Mi)), None),
+            test_compact_interrupted,
+        );
+    }
+
     /// Set up for testing physical device growth.
     fn test_grow_physical_pre_grow(paths: &[&Path]) {
         let pool_name = Name::new("pool".to_string());
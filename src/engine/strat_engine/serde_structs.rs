@@ -167,6 +167,11 @@ pub struct FlexDevsSave {
     pub thin_meta_dev: Vec<(Sectors, Sectors)>,
     pub thin_data_dev: Vec<(Sectors, Sectors)>,
     pub thin_meta_dev_spare: Vec<(Sectors, Sectors)>,
+    /// The time, in seconds since the Unix epoch, at which the thin pool
+    /// metadata was last repaired into the spare successfully.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub thin_meta_dev_spare_validated: Option<i64>,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -197,6 +202,8 @@ pub struct FilesystemSave {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fs_size_limit: Option<Sectors>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub fs_reservation: Option<Sectors>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub origin: Option<FilesystemUuid>,
     #[serde(default)]
     pub merge: bool,
@@ -51,6 +51,7 @@ pub fn merge(origin: &FilesystemSave, snap: &FilesystemSave) -> FilesystemSave {
         size: snap.size,
         created: origin.created,
         fs_size_limit: snap.fs_size_limit,
+        fs_reservation: origin.fs_reservation,
         origin: origin.origin,
         merge: origin.merge,
     }
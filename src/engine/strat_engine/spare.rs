@@ -0,0 +1,220 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Operations on the spare thin metadata device of a stopped pool.
+//
+// The spare is only used when thin_check fails on the thin metadata device
+// while the pool is being started, in which case the thin pool metadata is
+// repaired into it. These operations allow an administrator to perform that
+// repair on demand, to confirm beforehand that it would succeed, and to
+// prepare a spare which has been used for a repair for use again.
+
+use std::{collections::HashSet, mem::swap};
+
+use chrono::{TimeZone, Utc};
+
+use devicemapper::{DmDevice, Sectors};
+
+use crate::{
+    engine::{
+        strat_engine::{
+            cmd::{thin_check, thin_repair},
+            dm::get_dm,
+            liminal::LStratisInfo,
+            names::FlexRole,
+            offline::{
+                choose_copy, ensure_unused, read_copy, setup_flex_dev, sigblock_version, teardown,
+                write_metadata, ActiveBackstore,
+            },
+            pool::inspection::inspectors,
+            serde_structs::{FlexDevsSave, PoolSave},
+            thinpool::coalesce_segs,
+            writing::wipe_sectors,
+        },
+        types::{PoolUuid, ThinMetaSpareAction, ThinMetaSpareInfo},
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// The state of the spare as recorded in the pool-level metadata.
+fn spare_info(pool_uuid: PoolUuid, flex_devs: &FlexDevsSave) -> ThinMetaSpareInfo {
+    ThinMetaSpareInfo {
+        pool_uuid,
+        spare_size: flex_devs.thin_meta_dev_spare.iter().map(|s| s.1).sum(),
+        meta_size: flex_devs.thin_meta_dev.iter().map(|s| s.1).sum(),
+        last_validated: flex_devs
+            .thin_meta_dev_spare_validated
+            .and_then(|t| Utc.timestamp_opt(t, 0).single()),
+    }
+}
+
+/// Perform the operation on the spare, using the activated backstore of the
+/// pool, and record the result in the pool-level metadata.
+fn apply(
+    pool_uuid: PoolUuid,
+    backstore: &mut ActiveBackstore,
+    metadata: &mut PoolSave,
+    action: ThinMetaSpareAction,
+) -> StratisResult<()> {
+    let device = backstore.device().ok_or_else(|| {
+        StratisError::Msg("The backstore of the pool has no cap device".to_string())
+    })?;
+
+    match action {
+        ThinMetaSpareAction::Report => (),
+        ThinMetaSpareAction::Validate | ThinMetaSpareAction::Swap => {
+            let mut meta_dev = setup_flex_dev(
+                pool_uuid,
+                FlexRole::ThinMeta,
+                device,
+                &metadata.flex_devs.thin_meta_dev,
+            )?;
+            let mut spare_dev = setup_flex_dev(
+                pool_uuid,
+                FlexRole::ThinMetaSpare,
+                device,
+                &metadata.flex_devs.thin_meta_dev_spare,
+            )?;
+            thin_repair(&meta_dev.devnode(), &spare_dev.devnode())
+                .and_then(|_| thin_check(&spare_dev.devnode()))
+                .map_err(|e| {
+                    StratisError::Chained(
+                        "Failed to repair the thin pool metadata into the spare thin metadata device"
+                            .to_string(),
+                        Box::new(e),
+                    )
+                })?;
+            meta_dev.teardown(get_dm())?;
+            spare_dev.teardown(get_dm())?;
+
+            let flex_devs = &mut metadata.flex_devs;
+            if action == ThinMetaSpareAction::Swap {
+                swap(
+                    &mut flex_devs.thin_meta_dev,
+                    &mut flex_devs.thin_meta_dev_spare,
+                );
+                flex_devs.thin_meta_dev_spare_validated = None;
+            } else {
+                flex_devs.thin_meta_dev_spare_validated = Some(Utc::now().timestamp());
+            }
+        }
+        ThinMetaSpareAction::Reprovision => {
+            let spare_size = metadata
+                .flex_devs
+                .thin_meta_dev_spare
+                .iter()
+                .map(|s| s.1)
+                .sum::<Sectors>();
+            let meta_size = metadata
+                .flex_devs
+                .thin_meta_dev
+                .iter()
+                .map(|s| s.1)
+                .sum::<Sectors>();
+            if spare_size < meta_size {
+                let segments = backstore
+                    .alloc(pool_uuid, &[meta_size - spare_size])?
+                    .ok_or_else(|| {
+                        StratisError::Msg(format!(
+                            "Not enough free space in the pool to extend the spare thin metadata device by {}",
+                            (meta_size - spare_size).bytes()
+                        ))
+                    })?;
+                let flex_devs = &mut metadata.flex_devs;
+                flex_devs.thin_meta_dev_spare =
+                    coalesce_segs(&flex_devs.thin_meta_dev_spare, &segments);
+                metadata.backstore = backstore.record();
+            }
+
+            let mut spare_dev = setup_flex_dev(
+                pool_uuid,
+                FlexRole::ThinMetaSpare,
+                device,
+                &metadata.flex_devs.thin_meta_dev_spare,
+            )?;
+            wipe_sectors(spare_dev.devnode(), Sectors(0), spare_dev.size())?;
+            spare_dev.teardown(get_dm())?;
+            metadata.flex_devs.thin_meta_dev_spare_validated = None;
+        }
+    }
+
+    Ok(())
+}
+
+/// Perform an operation on the spare thin metadata device of the stopped pool
+/// whose devices are given and return the state of the spare afterwards.
+/// Also returns true if the pool-level metadata was written to the devices
+/// of the pool, in which case their signature blocks must be read again.
+///
+/// The backstore of the pool is activated for any operation other than
+/// reporting and is always torn down again before this function returns.
+pub fn thin_meta_spare(
+    pool_uuid: PoolUuid,
+    infos: Vec<LStratisInfo>,
+    action: ThinMetaSpareAction,
+) -> StratisResult<(ThinMetaSpareInfo, bool)> {
+    for info in infos.iter() {
+        ensure_unused(&info.dev_info.devnode)?;
+    }
+
+    let mut problems = Vec::new();
+    let (time, _, mut metadata) = choose_copy(infos.iter().map(read_copy).collect(), &mut problems)
+        .ok_or_else(|| {
+            StratisError::Msg(format!(
+                "No valid metadata found for the pool with UUID {pool_uuid}: {}",
+                problems.join("; ")
+            ))
+        })?;
+    if action == ThinMetaSpareAction::Report {
+        return Ok((spare_info(pool_uuid, &metadata.flex_devs), false));
+    }
+    if !problems.is_empty() {
+        return Err(StratisError::Msg(format!(
+            "The metadata of the pool with UUID {pool_uuid} is not consistent across its devices: {}",
+            problems.join("; ")
+        )));
+    }
+
+    let expected = inspectors::blockdev_uuids(&metadata)
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let infos = infos
+        .into_iter()
+        .filter(|info| expected.contains(&info.bda.dev_uuid()))
+        .collect::<Vec<_>>();
+    if infos.len() != expected.len() {
+        return Err(StratisError::Msg(format!(
+            "Not all devices of the pool with UUID {pool_uuid} were found"
+        )));
+    }
+    let version = sigblock_version(&infos).ok_or_else(|| {
+        StratisError::Msg(format!(
+            "The devices of the pool with UUID {pool_uuid} have different metadata versions"
+        ))
+    })?;
+    let dev_uuids = infos
+        .iter()
+        .map(|info| info.bda.dev_uuid())
+        .collect::<Vec<_>>();
+    let devnodes = infos
+        .iter()
+        .map(|info| info.dev_info.devnode.clone())
+        .collect::<Vec<_>>();
+
+    let res = ActiveBackstore::setup(pool_uuid, time, &metadata, version, infos)
+        .and_then(|mut backstore| apply(pool_uuid, &mut backstore, &mut metadata, action));
+    // Remove all the devices which were activated, whether or not the
+    // operation succeeded.
+    let cleanup = teardown(pool_uuid, version, &dev_uuids);
+    res?;
+    cleanup?;
+
+    let data = serde_json::to_vec(&metadata)?;
+    let now = Utc::now();
+    for devnode in devnodes.iter() {
+        write_metadata(devnode, &now, &data)?;
+    }
+
+    Ok((spare_info(pool_uuid, &metadata.flex_devs), true))
+}
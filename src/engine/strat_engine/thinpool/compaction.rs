@@ -0,0 +1,519 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Relocation of the segments of the flex devices on the cap device, so that
+// each flex device ends up occupying a single extent. Compaction plans the
+// relocation as a sequence of steps; copy_run copies the sectors of a single
+// step, leaving the suspension of the thin pool and the recording of the new
+// segments to the caller.
+
+use std::{
+    cmp::min,
+    fs::OpenOptions,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use devicemapper::{Sectors, IEC};
+
+use crate::{engine::strat_engine::device::blkdev_flush_buffers, stratis::StratisResult};
+
+/// The largest number of sectors relocated in a single step, during which
+/// the thin pool is suspended. Compaction also tops up the free space with
+/// which it proceeds to this amount.
+pub const COMPACTION_CHUNK_SIZE: Sectors = Sectors(256 * IEC::Ki); // 128 MiB
+
+const COPY_CHUNK_SIZE: Sectors = Sectors(2 * IEC::Ki); // 1 MiB
+
+/// The devices whose segments are allocated from the cap device.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FlexDev {
+    ThinData,
+    ThinMeta,
+    ThinMetaSpare,
+    Mdv,
+}
+
+/// The order of the extents in a compacted layout, where None stands for the
+/// free space. The thin data device, which is by far the largest, comes
+/// first and is followed by the free space, which can then be added to it;
+/// later compactions only have to move what was allocated since and the
+/// small devices which follow.
+const ORDER: [Option<FlexDev>; 5] = [
+    Some(FlexDev::ThinData),
+    None,
+    Some(FlexDev::ThinMeta),
+    Some(FlexDev::ThinMetaSpare),
+    Some(FlexDev::Mdv),
+];
+
+/// A run of sectors of a flex device to be copied from one place on the cap
+/// device to another.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Relocation {
+    pub dev: FlexDev,
+    /// The offset of the run within the flex device.
+    pub offset: Sectors,
+    /// The location of the run on the cap device.
+    pub from: Sectors,
+    /// The location on the cap device to which the run is to be copied.
+    pub to: Sectors,
+    pub length: Sectors,
+}
+
+fn total(segments: &[(Sectors, Sectors)]) -> Sectors {
+    segments.iter().map(|(_, length)| *length).sum()
+}
+
+/// Sort the segments and merge those which are adjacent.
+fn merged(mut segments: Vec<(Sectors, Sectors)>) -> Vec<(Sectors, Sectors)> {
+    segments.sort();
+    coalesced(segments)
+}
+
+/// Merge each segment with the one before it if the two are adjacent.
+fn coalesced(segments: impl IntoIterator<Item = (Sectors, Sectors)>) -> Vec<(Sectors, Sectors)> {
+    let mut result: Vec<(Sectors, Sectors)> = Vec::new();
+    for (start, length) in segments {
+        match result.last_mut() {
+            Some(last) if last.0 + last.1 == start => last.1 += length,
+            _ => result.push((start, length)),
+        }
+    }
+    result
+}
+
+/// The segments of a flex device after the relocation of a run of its
+/// sectors, with those segments which become adjacent merged.
+/// Precondition: the run lies within a single segment of the device.
+pub fn relocated(
+    segments: &[(Sectors, Sectors)],
+    relocation: &Relocation,
+) -> Vec<(Sectors, Sectors)> {
+    let (start, end) = (relocation.offset, relocation.offset + relocation.length);
+    let mut pieces = Vec::with_capacity(segments.len() + 2);
+
+    let mut offset = Sectors(0);
+    for &(seg_start, length) in segments {
+        let seg_end = offset + length;
+        if seg_end <= start || offset >= end {
+            pieces.push((seg_start, length));
+        } else {
+            if offset < start {
+                pieces.push((seg_start, start - offset));
+            }
+            if offset <= start {
+                pieces.push((relocation.to, relocation.length));
+            }
+            if seg_end > end {
+                pieces.push((seg_start + (end - offset), seg_end - end));
+            }
+        }
+        offset = seg_end;
+    }
+    coalesced(pieces)
+}
+
+/// Copy the run of sectors to be relocated from its location on the cap
+/// device at devnode to its new location.
+pub fn copy_run(devnode: &Path, relocation: &Relocation) -> StratisResult<()> {
+    let mut f = OpenOptions::new().read(true).write(true).open(devnode)?;
+    // The cap device is written through the DM devices stacked on it, which
+    // bypass its page cache, so anything in the page cache may be stale.
+    blkdev_flush_buffers(&f)?;
+
+    let mut buf = vec![0u8; convert_int!(*COPY_CHUNK_SIZE.bytes(), u128, usize)?];
+    let mut copied = Sectors(0);
+    while copied < relocation.length {
+        let chunk = min(relocation.length - copied, COPY_CHUNK_SIZE);
+        let chunk_buf = &mut buf[..convert_int!(*chunk.bytes(), u128, usize)?];
+        f.seek(SeekFrom::Start(convert_int!(
+            *(relocation.from + copied).bytes(),
+            u128,
+            u64
+        )?))?;
+        f.read_exact(chunk_buf)?;
+        f.seek(SeekFrom::Start(convert_int!(
+            *(relocation.to + copied).bytes(),
+            u128,
+            u64
+        )?))?;
+        f.write_all(chunk_buf)?;
+        copied += chunk;
+    }
+    f.sync_all()?;
+    Ok(())
+}
+
+/// The layout of the flex devices on the cap device while they are being
+/// compacted. Each step relocates a run of sectors into free space at the
+/// location which the run has in the compacted layout; only when all the
+/// free space is where it belongs in the compacted layout is a run moved
+/// into free space elsewhere, to break a cycle. No run is ever moved away
+/// from its location in the compacted layout, so compaction terminates.
+#[derive(Debug)]
+pub struct Compaction {
+    /// The segments of each flex device, in the order of the compacted
+    /// layout.
+    devs: Vec<(FlexDev, Vec<(Sectors, Sectors)>)>,
+    /// The ranges of the cap device which lie among the segments of the flex
+    /// devices but belong to none of them, in order.
+    free: Vec<(Sectors, Sectors)>,
+    /// The location on the cap device at which the compacted layout begins.
+    start: Sectors,
+    /// The largest number of sectors copied by a single step.
+    max_length: Sectors,
+}
+
+impl Compaction {
+    /// Precondition: the segments of all the devices and the free ranges
+    /// together cover a single range of the cap device without overlapping.
+    pub fn new(
+        mut devs: Vec<(FlexDev, Vec<(Sectors, Sectors)>)>,
+        free: Vec<(Sectors, Sectors)>,
+        max_length: Sectors,
+    ) -> Compaction {
+        let start = devs
+            .iter()
+            .flat_map(|(_, segments)| segments.iter())
+            .chain(free.iter())
+            .map(|(start, _)| *start)
+            .min()
+            .unwrap_or_default();
+        devs.sort_by_key(|(dev, _)| ORDER.iter().position(|d| *d == Some(*dev)));
+        Compaction {
+            devs: devs
+                .into_iter()
+                .map(|(dev, segments)| (dev, coalesced(segments)))
+                .collect(),
+            free: merged(free),
+            start,
+            max_length,
+        }
+    }
+
+    fn segments(&self, dev: FlexDev) -> &[(Sectors, Sectors)] {
+        self.devs
+            .iter()
+            .find(|(d, _)| *d == dev)
+            .map(|(_, segments)| segments.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The extents of the compacted layout, in order.
+    fn targets(&self) -> Vec<(Option<FlexDev>, Sectors, Sectors)> {
+        let mut next = self.start;
+        ORDER
+            .iter()
+            .map(|item| {
+                let length = match item {
+                    Some(dev) => total(self.segments(*dev)),
+                    None => total(&self.free),
+                };
+                let target = (*item, next, length);
+                next += length;
+                target
+            })
+            .collect()
+    }
+
+    /// The location on the cap device of the sector at offset within the
+    /// device, and the number of sectors from there to the end of the
+    /// segment which holds it.
+    fn locate(&self, dev: FlexDev, offset: Sectors) -> Option<(Sectors, Sectors)> {
+        let mut seg_offset = Sectors(0);
+        for &(start, length) in self.segments(dev) {
+            if offset < seg_offset + length {
+                return Some((
+                    start + (offset - seg_offset),
+                    length - (offset - seg_offset),
+                ));
+            }
+            seg_offset += length;
+        }
+        None
+    }
+
+    /// The next step of the compaction, or None if compaction is complete
+    /// or there is no free space with which to proceed.
+    pub fn next_step(&self) -> Option<Relocation> {
+        let targets = self.targets();
+
+        for &(free_start, free_length) in self.free.iter() {
+            let free_end = free_start + free_length;
+            for &(item, target_start, target_length) in targets.iter() {
+                let target_end = target_start + target_length;
+                let (start, end) = (free_start.max(target_start), min(free_end, target_end));
+                if start >= end {
+                    continue;
+                }
+                if let Some(dev) = item {
+                    let offset = start - target_start;
+                    let (from, available) = self
+                        .locate(dev, offset)
+                        .expect("offset lies within the device");
+                    return Some(Relocation {
+                        dev,
+                        offset,
+                        from,
+                        to: start,
+                        length: min(min(end - start, available), self.max_length),
+                    });
+                }
+            }
+        }
+
+        let &(free_start, free_length) = self.free.first()?;
+        targets
+            .iter()
+            .filter_map(|(item, target_start, _)| item.map(|dev| (dev, *target_start)))
+            .find_map(|(dev, target_start)| {
+                let mut offset = Sectors(0);
+                for &(start, length) in self.segments(dev) {
+                    if start != target_start + offset {
+                        return Some(Relocation {
+                            dev,
+                            offset,
+                            from: start,
+                            to: free_start,
+                            length: min(min(length, free_length), self.max_length),
+                        });
+                    }
+                    offset += length;
+                }
+                None
+            })
+    }
+
+    /// Record that a step of the compaction has been carried out.
+    pub fn apply(&mut self, relocation: &Relocation) {
+        if let Some((_, segments)) = self.devs.iter_mut().find(|(d, _)| *d == relocation.dev) {
+            *segments = relocated(segments, relocation);
+        }
+
+        let (to_start, to_end) = (relocation.to, relocation.to + relocation.length);
+        let mut free = Vec::with_capacity(self.free.len() + 2);
+        for &(start, length) in self.free.iter() {
+            let end = start + length;
+            if end <= to_start || start >= to_end {
+                free.push((start, length));
+            } else {
+                if start < to_start {
+                    free.push((start, to_start - start));
+                }
+                if end > to_end {
+                    free.push((to_end, end - to_end));
+                }
+            }
+        }
+        free.push((relocation.from, relocation.length));
+        self.free = merged(free);
+    }
+
+    /// Whether every device occupies a single extent at its location in the
+    /// compacted layout.
+    pub fn is_complete(&self) -> bool {
+        self.targets()
+            .iter()
+            .all(|(item, target_start, _)| match item {
+                Some(dev) => match self.segments(*dev) {
+                    [] => true,
+                    [(start, _)] => start == target_start,
+                    _ => false,
+                },
+                None => self.free.len() <= 1,
+            })
+    }
+
+    /// The number of sectors of the devices which are not yet at their
+    /// location in the compacted layout.
+    pub fn misplaced(&self) -> Sectors {
+        self.targets()
+            .into_iter()
+            .filter_map(|(item, target_start, _)| item.map(|dev| (dev, target_start)))
+            .map(|(dev, target_start)| {
+                let mut offset = Sectors(0);
+                let mut misplaced = Sectors(0);
+                for &(start, length) in self.segments(dev) {
+                    if start != target_start + offset {
+                        misplaced += length;
+                    }
+                    offset += length;
+                }
+                misplaced
+            })
+            .sum()
+    }
+
+    /// The total number of segments of all the devices.
+    pub fn segment_count(&self) -> usize {
+        self.devs.iter().map(|(_, segments)| segments.len()).sum()
+    }
+
+    /// The free space, which follows the thin data device once compaction
+    /// is complete.
+    pub fn free(&self) -> &[(Sectors, Sectors)] {
+        &self.free
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a compaction to completion, checking after every step that no
+    /// two segments overlap.
+    fn compact(compaction: &mut Compaction) -> usize {
+        let mut steps = 0;
+        while let Some(relocation) = compaction.next_step() {
+            compaction.apply(&relocation);
+            steps += 1;
+
+            let mut all = compaction
+                .devs
+                .iter()
+                .flat_map(|(_, segments)| segments.iter().copied())
+                .chain(compaction.free.iter().copied())
+                .collect::<Vec<_>>();
+            all.sort();
+            for window in all.windows(2) {
+                assert_eq!(window[0].0 + window[0].1, window[1].0);
+            }
+        }
+        steps
+    }
+
+    #[test]
+    /// Verify that relocating a run within a segment splits the segment and
+    /// that relocating a run next to its neighbor merges them.
+    fn test_relocated() {
+        let segments = [(Sectors(0), Sectors(10)), (Sectors(20), Sectors(10))];
+        assert_eq!(
+            relocated(
+                &segments,
+                &Relocation {
+                    dev: FlexDev::ThinData,
+                    offset: Sectors(2),
+                    from: Sectors(2),
+                    to: Sectors(100),
+                    length: Sectors(3),
+                }
+            ),
+            vec![
+                (Sectors(0), Sectors(2)),
+                (Sectors(100), Sectors(3)),
+                (Sectors(5), Sectors(5)),
+                (Sectors(20), Sectors(10)),
+            ]
+        );
+        assert_eq!(
+            relocated(
+                &segments,
+                &Relocation {
+                    dev: FlexDev::ThinData,
+                    offset: Sectors(10),
+                    from: Sectors(20),
+                    to: Sectors(10),
+                    length: Sectors(10),
+                }
+            ),
+            vec![(Sectors(0), Sectors(20))]
+        );
+    }
+
+    #[test]
+    /// Verify that the typical layout of a pool which has grown is
+    /// compacted so that each device occupies a single extent, the data
+    /// device first, followed by the free space.
+    fn test_compaction() {
+        let mut compaction = Compaction::new(
+            vec![
+                (FlexDev::ThinMeta, vec![(Sectors(0), Sectors(4))]),
+                (FlexDev::ThinMetaSpare, vec![(Sectors(4), Sectors(4))]),
+                (
+                    FlexDev::ThinData,
+                    vec![(Sectors(8), Sectors(16)), (Sectors(28), Sectors(32))],
+                ),
+                (FlexDev::Mdv, vec![(Sectors(24), Sectors(4))]),
+            ],
+            vec![(Sectors(60), Sectors(8))],
+            Sectors(8),
+        );
+        assert_eq!(compaction.segment_count(), 5);
+        assert!(!compaction.is_complete());
+        assert_eq!(compaction.misplaced(), Sectors(60));
+
+        compact(&mut compaction);
+
+        assert!(compaction.is_complete());
+        assert_eq!(compaction.misplaced(), Sectors(0));
+        assert_eq!(compaction.segment_count(), 4);
+        assert_eq!(
+            compaction.segments(FlexDev::ThinData),
+            &[(Sectors(0), Sectors(48))]
+        );
+        assert_eq!(compaction.free(), &[(Sectors(48), Sectors(8))]);
+        assert_eq!(
+            compaction.segments(FlexDev::ThinMeta),
+            &[(Sectors(56), Sectors(4))]
+        );
+        assert_eq!(
+            compaction.segments(FlexDev::ThinMetaSpare),
+            &[(Sectors(60), Sectors(4))]
+        );
+        assert_eq!(
+            compaction.segments(FlexDev::Mdv),
+            &[(Sectors(64), Sectors(4))]
+        );
+    }
+
+    #[test]
+    /// Verify that a layout in which the free space is already where it
+    /// belongs, but the devices are not, is compacted nonetheless.
+    fn test_compaction_cycle() {
+        let mut compaction = Compaction::new(
+            vec![
+                (FlexDev::ThinData, vec![(Sectors(10), Sectors(10))]),
+                (FlexDev::ThinMeta, vec![(Sectors(0), Sectors(10))]),
+            ],
+            vec![],
+            Sectors(4),
+        );
+        assert_eq!(compaction.next_step(), None);
+
+        let mut compaction = Compaction::new(
+            vec![
+                (FlexDev::ThinData, vec![(Sectors(20), Sectors(10))]),
+                (FlexDev::ThinMeta, vec![(Sectors(0), Sectors(10))]),
+            ],
+            vec![(Sectors(10), Sectors(10))],
+            Sectors(4),
+        );
+        compact(&mut compaction);
+        assert!(compaction.is_complete());
+        assert_eq!(
+            compaction.segments(FlexDev::ThinData),
+            &[(Sectors(0), Sectors(10))]
+        );
+        assert_eq!(
+            compaction.segments(FlexDev::ThinMeta),
+            &[(Sectors(20), Sectors(10))]
+        );
+    }
+
+    #[test]
+    /// Verify that a compacted layout requires no steps.
+    fn test_compaction_complete() {
+        let mut compaction = Compaction::new(
+            vec![
+                (FlexDev::ThinData, vec![(Sectors(0), Sectors(10))]),
+                (FlexDev::ThinMeta, vec![(Sectors(14), Sectors(2))]),
+            ],
+            vec![(Sectors(10), Sectors(4))],
+            Sectors(4),
+        );
+        assert!(compaction.is_complete());
+        assert_eq!(compact(&mut compaction), 0);
+    }
+}
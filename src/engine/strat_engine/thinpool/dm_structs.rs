@@ -41,7 +41,7 @@ impl From<&ThinPoolStatus> for ThinPoolStatusDigest {
 }
 
 pub mod thin_pool_status_parser {
-    use devicemapper::{DataBlocks, MetaBlocks, ThinPoolStatus};
+    use devicemapper::{DataBlocks, MetaBlocks, ThinPoolStatus, ThinPoolUsage};
 
     /// Convert the thin pool status to the metadata low water mark.
     pub fn meta_lowater(status: &ThinPoolStatus) -> Option<MetaBlocks> {
@@ -60,6 +60,15 @@ pub mod thin_pool_status_parser {
             None
         }
     }
+
+    /// Convert the thin pool status to the used and total information.
+    pub fn usage(status: &ThinPoolStatus) -> Option<ThinPoolUsage> {
+        if let ThinPoolStatus::Working(w) = status {
+            Some(w.usage.clone())
+        } else {
+            None
+        }
+    }
 }
 
 pub mod thin_table {
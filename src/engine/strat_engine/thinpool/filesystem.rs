@@ -5,7 +5,7 @@
 use std::{
     cmp::min,
     fs::{File, OpenOptions},
-    io::{Read, Write},
+    io::{Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
 };
 
@@ -16,7 +16,7 @@ use serde_json::{Map, Value};
 
 use devicemapper::{
     Bytes, DevId, DmDevice, DmName, DmOptions, DmUuid, Sectors, ThinDev, ThinDevId, ThinPoolDev,
-    ThinStatus,
+    ThinStatus, IEC,
 };
 
 use nix::{
@@ -45,12 +45,16 @@ use crate::{
 
 const TEMP_MNT_POINT_PREFIX: &str = "stratis_mp_";
 
+// Amount of data to copy at once when copying a filesystem.
+const COPY_CHUNK_SIZE: Sectors = Sectors(2 * IEC::Ki); // 1 MiB
+
 #[derive(Debug)]
 pub struct StratFilesystem {
     thin_dev: ThinDev,
     created: DateTime<Utc>,
     used: Option<Bytes>,
     size_limit: Option<Sectors>,
+    reservation: Option<Sectors>,
     origin: Option<FilesystemUuid>,
     merge_scheduled: bool,
 }
@@ -68,15 +72,77 @@ fn init_used(thin_dev: &ThinDev) -> Option<Bytes> {
         })
 }
 
+/// Destroy a newly created thin device after a failure to initialize the
+/// filesystem on it.
+fn cleanup_thin_dev(thin_dev: &mut ThinDev, thinpool_dev: &ThinPoolDev, op: &str) {
+    if let Err(err) = retry_with_index(Fixed::from_millis(100).take(4), |i| {
+        trace!(
+            "Cleanup new thin device after failed {}() attempt {}",
+            op,
+            i
+        );
+        thin_dev.destroy(get_dm(), thinpool_dev)
+    }) {
+        error!(
+            "While handling {} error, thin_dev.destroy() failed: {}",
+            op, err
+        );
+        // This will result in a dangling DM device that will prevent
+        // the thinpool from being destroyed, and wasted space in the
+        // thinpool.
+    }
+}
+
+/// Copy the given (offset, length) ranges from the device at source to the
+/// device at target. Ranges are clipped to size.
+fn copy_ranges(
+    source: &Path,
+    target: &Path,
+    ranges: &[(Sectors, Sectors)],
+    size: Sectors,
+    progress: &mut dyn FnMut(Sectors, Sectors),
+) -> StratisResult<()> {
+    let ranges = ranges
+        .iter()
+        .filter(|(start, _)| *start < size)
+        .map(|(start, length)| (*start, min(*length, size - *start)))
+        .collect::<Vec<_>>();
+    let total = ranges.iter().map(|(_, length)| *length).sum::<Sectors>();
+
+    let mut src = File::open(source)?;
+    let mut dst = OpenOptions::new().write(true).open(target)?;
+    let mut buf = vec![0u8; convert_int!(*COPY_CHUNK_SIZE.bytes(), u128, usize)?];
+    let mut copied = Sectors(0);
+    for (start, length) in ranges {
+        let offset = convert_int!(*start.bytes(), u128, u64)?;
+        src.seek(SeekFrom::Start(offset))?;
+        dst.seek(SeekFrom::Start(offset))?;
+        let mut remaining = length;
+        while remaining > Sectors(0) {
+            let chunk = min(remaining, COPY_CHUNK_SIZE);
+            let chunk_buf = &mut buf[..convert_int!(*chunk.bytes(), u128, usize)?];
+            src.read_exact(chunk_buf)?;
+            dst.write_all(chunk_buf)?;
+            remaining -= chunk;
+            copied += chunk;
+            progress(copied, total);
+        }
+    }
+    dst.sync_all()?;
+    Ok(())
+}
+
 impl StratFilesystem {
-    /// Create a StratFilesystem on top of the given ThinDev.
+    /// Create a StratFilesystem with UUID fs_uuid on top of the given ThinDev.
+    /// The XFS UUID of the filesystem is also set to fs_uuid.
     pub fn initialize(
         pool_uuid: PoolUuid,
         thinpool_dev: &ThinPoolDev,
+        fs_uuid: FilesystemUuid,
         size: Sectors,
         size_limit: Option<Sectors>,
         id: ThinDevId,
-    ) -> StratisResult<(FilesystemUuid, StratFilesystem)> {
+    ) -> StratisResult<StratFilesystem> {
         if let Some(limit) = size_limit {
             if limit < size {
                 return Err(StratisError::Msg(format!(
@@ -85,27 +151,55 @@ impl StratFilesystem {
             }
         }
 
-        let fs_uuid = FilesystemUuid::new_v4();
         let (dm_name, dm_uuid) = format_thin_ids(pool_uuid, ThinRole::Filesystem(fs_uuid));
         let mut thin_dev =
             ThinDev::new(get_dm(), &dm_name, Some(&dm_uuid), size, thinpool_dev, id)?;
 
         if let Err(err) = create_fs(&thin_dev.devnode(), Some(StratisUuid::Fs(fs_uuid))) {
-            if let Err(err2) = retry_with_index(Fixed::from_millis(100).take(4), |i| {
-                trace!(
-                    "Cleanup new thin device after failed create_fs() attempt {}",
-                    i
-                );
-                thin_dev.destroy(get_dm(), thinpool_dev)
-            }) {
-                error!(
-                    "While handling create_fs error, thin_dev.destroy() failed: {}",
-                    err2
-                );
-                // This will result in a dangling DM device that will prevent
-                // the thinpool from being destroyed, and wasted space in the
-                // thinpool.
-            }
+            cleanup_thin_dev(&mut thin_dev, thinpool_dev, "create_fs");
+            return Err(err);
+        }
+
+        Ok(StratFilesystem {
+            used: init_used(&thin_dev),
+            thin_dev,
+            created: Utc::now(),
+            size_limit,
+            reservation: None,
+            origin: None,
+            merge_scheduled: false,
+        })
+    }
+
+    /// Create a StratFilesystem on a new ThinDev which is a block copy of
+    /// the device at source. Since unprovisioned regions of a thin device
+    /// read as zeroes, only the provisioned ranges of the source, given as
+    /// (offset, length) pairs, are copied. The XFS UUID of the copy is set
+    /// to xfs_uuid if specified, otherwise to the UUID of the new filesystem.
+    ///
+    /// progress is called after each chunk is copied with the amount copied
+    /// so far and the total amount to copy.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_copy(
+        pool_uuid: PoolUuid,
+        thinpool_dev: &ThinPoolDev,
+        source: &Path,
+        ranges: &[(Sectors, Sectors)],
+        size: Sectors,
+        size_limit: Option<Sectors>,
+        id: ThinDevId,
+        xfs_uuid: Option<FilesystemUuid>,
+        progress: &mut dyn FnMut(Sectors, Sectors),
+    ) -> StratisResult<(FilesystemUuid, StratFilesystem)> {
+        let fs_uuid = FilesystemUuid::new_v4();
+        let (dm_name, dm_uuid) = format_thin_ids(pool_uuid, ThinRole::Filesystem(fs_uuid));
+        let mut thin_dev =
+            ThinDev::new(get_dm(), &dm_name, Some(&dm_uuid), size, thinpool_dev, id)?;
+
+        if let Err(err) = copy_ranges(source, &thin_dev.devnode(), ranges, size, progress)
+            .and_then(|_| set_uuid(&thin_dev.devnode(), xfs_uuid.unwrap_or(fs_uuid)))
+        {
+            cleanup_thin_dev(&mut thin_dev, thinpool_dev, "copy");
             return Err(err);
         }
 
@@ -116,6 +210,7 @@ impl StratFilesystem {
                 thin_dev,
                 created: Utc::now(),
                 size_limit,
+                reservation: None,
                 origin: None,
                 merge_scheduled: false,
             },
@@ -144,6 +239,7 @@ impl StratFilesystem {
             thin_dev,
             created,
             size_limit: fssave.fs_size_limit,
+            reservation: fssave.fs_reservation,
             origin: fssave.origin,
             merge_scheduled: fssave.merge,
         })
@@ -196,6 +292,9 @@ impl StratFilesystem {
     ///
     /// As of the introduction of filesystem size limits, snapshots inherit the origin size limit
     /// but the limit can be changed or removed through the API.
+    ///
+    /// Snapshots do not inherit the reservation of the origin; a reservation
+    /// must be explicitly requested for the snapshot.
     #[allow(clippy::too_many_arguments)]
     pub fn snapshot(
         &self,
@@ -252,6 +351,7 @@ impl StratFilesystem {
                     thin_dev,
                     created: Utc::now(),
                     size_limit: self.size_limit,
+                    reservation: None,
                     origin: Some(origin_uuid),
                     merge_scheduled: false,
                 })
@@ -393,6 +493,7 @@ impl StratFilesystem {
             size: self.thin_dev.size(),
             created: self.created.timestamp() as u64,
             fs_size_limit: self.size_limit,
+            fs_reservation: self.reservation,
             origin: self.origin,
             merge: self.merge_scheduled,
         }
@@ -429,13 +530,16 @@ impl StratFilesystem {
     }
 
     pub fn set_size_limit(&mut self, limit: Option<Sectors>) -> StratisResult<bool> {
-        match limit {
-            Some(lim) if self.thindev_size() > lim => Err(StratisError::Msg(format!(
+        match (limit, self.reservation) {
+            (Some(lim), _) if self.thindev_size() > lim => Err(StratisError::Msg(format!(
                 "Limit requested of {} is smaller than current filesystem size of {}",
                 lim,
                 self.thindev_size()
             ))),
-            Some(_) | None => {
+            (Some(lim), Some(res)) if res > lim => Err(StratisError::Msg(format!(
+                "Limit requested of {lim} is smaller than current filesystem reservation of {res}"
+            ))),
+            (Some(_), _) | (None, _) => {
                 if self.size_limit == limit {
                     Ok(false)
                 } else {
@@ -446,6 +550,40 @@ impl StratFilesystem {
         }
     }
 
+    /// Set the amount of thin pool data space reserved for the filesystem.
+    /// The reservation may not exceed the filesystem's size limit.
+    pub fn set_reservation(&mut self, reservation: Option<Sectors>) -> StratisResult<bool> {
+        match (reservation, self.size_limit) {
+            (Some(res), Some(lim)) if res > lim => Err(StratisError::Msg(format!(
+                "Reservation requested of {res} is larger than filesystem size limit of {lim}"
+            ))),
+            _ => {
+                if self.reservation == reservation {
+                    Ok(false)
+                } else {
+                    self.reservation = reservation;
+                    Ok(true)
+                }
+            }
+        }
+    }
+
+    /// The portion of the given reservation that the filesystem has not yet
+    /// consumed, based on the last cached value for the space used by the
+    /// filesystem.
+    pub fn unclaimed(&self, reservation: Sectors) -> Sectors {
+        let used = self.used.map(|u| u.sectors()).unwrap_or(Sectors(0));
+        Sectors(reservation.saturating_sub(*used))
+    }
+
+    /// The portion of the filesystem's reservation that it has not yet
+    /// consumed.
+    pub fn unclaimed_reservation(&self) -> Sectors {
+        self.reservation
+            .map(|res| self.unclaimed(res))
+            .unwrap_or(Sectors(0))
+    }
+
     pub fn thindev_size(&self) -> Sectors {
         self.thin_dev.size()
     }
@@ -513,6 +651,10 @@ impl Filesystem for StratFilesystem {
         self.size_limit
     }
 
+    fn reservation(&self) -> Option<Sectors> {
+        self.reservation
+    }
+
     fn origin(&self) -> Option<FilesystemUuid> {
         self.origin
     }
@@ -606,6 +748,15 @@ impl Into<Value> for &StratFilesystem {
                     .unwrap_or_else(|| "Not set".to_string()),
             ),
         );
+        json.insert(
+            "reservation".to_string(),
+            Value::from(
+                self.reservation
+                    .as_ref()
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "Not set".to_string()),
+            ),
+        );
         json.insert(
             "origin".to_string(),
             Value::from(
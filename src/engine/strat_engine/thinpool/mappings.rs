@@ -0,0 +1,151 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Parse the XML output of thin_dump to find which thin devices exist and
+// which blocks of a thin device are provisioned.
+
+use devicemapper::ThinDevId;
+
+use crate::stratis::{StratisError, StratisResult};
+
+/// A contiguous range of provisioned blocks of a thin device. Both values are
+/// in units of thin pool data blocks, relative to the start of the thin
+/// device.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ThinMapping {
+    pub origin_begin: u64,
+    pub length: u64,
+}
+
+/// Get the value of the attribute with the given name from a single XML
+/// element.
+fn attribute(line: &str, name: &str) -> StratisResult<u64> {
+    let prefix = format!(" {name}=\"");
+    let start = line.find(&prefix).ok_or_else(|| {
+        StratisError::Msg(format!(
+            "Attribute {name} not found in thin_dump element \"{line}\""
+        ))
+    })? + prefix.len();
+    let value = line[start..].split('"').next().unwrap_or("");
+    value.parse::<u64>().map_err(|e| {
+        StratisError::Msg(format!(
+            "Failed to parse value \"{value}\" of attribute {name} in thin_dump output: {e}"
+        ))
+    })
+}
+
+/// Parse the output of thin_dump and return the provisioned ranges of the
+/// thin device with the given id, sorted by offset and with adjacent ranges
+/// merged.
+pub fn parse_thin_dump(xml: &str, dev_id: ThinDevId) -> StratisResult<Vec<ThinMapping>> {
+    let dev_id = u64::from(u32::from(dev_id));
+    let mut found = false;
+    let mut in_device = false;
+    let mut mappings = Vec::new();
+    for line in xml.lines().map(|l| l.trim()) {
+        if line.starts_with("<device ") {
+            in_device = attribute(line, "dev_id")? == dev_id;
+            found |= in_device;
+        } else if line.starts_with("</device>") {
+            in_device = false;
+        } else if in_device && line.starts_with("<single_mapping ") {
+            mappings.push(ThinMapping {
+                origin_begin: attribute(line, "origin_block")?,
+                length: 1,
+            });
+        } else if in_device && line.starts_with("<range_mapping ") {
+            mappings.push(ThinMapping {
+                origin_begin: attribute(line, "origin_begin")?,
+                length: attribute(line, "length")?,
+            });
+        }
+    }
+
+    if !found {
+        return Err(StratisError::Msg(format!(
+            "No thin device with id {dev_id} found in thin_dump output"
+        )));
+    }
+
+    mappings.sort_by_key(|m| m.origin_begin);
+    Ok(mappings.into_iter().fold(Vec::new(), |mut acc, next| {
+        match acc.last_mut() {
+            Some(last) if last.origin_begin + last.length == next.origin_begin => {
+                last.length += next.length;
+            }
+            _ => acc.push(next),
+        }
+        acc
+    }))
+}
+
+/// Parse the output of thin_dump and return the ids of all the thin devices
+/// it describes.
+pub fn thin_dump_devices(xml: &str) -> StratisResult<Vec<ThinDevId>> {
+    xml.lines()
+        .map(|l| l.trim())
+        .filter(|l| l.starts_with("<device "))
+        .map(|l| {
+            attribute(l, "dev_id").and_then(|id| ThinDevId::new_u64(id).map_err(StratisError::from))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THIN_DUMP: &str = r#"<superblock uuid="" time="1" transaction="4" version="2" data_block_size="2048" nr_data_blocks="10240">
+  <device dev_id="1" mapped_blocks="6" transaction="0" creation_time="0" snap_time="1">
+    <range_mapping origin_begin="0" data_begin="0" length="3" time="0"/>
+    <single_mapping origin_block="3" data_block="8" time="1"/>
+    <single_mapping origin_block="10" data_block="9" time="1"/>
+    <range_mapping origin_begin="11" data_begin="12" length="1" time="1"/>
+  </device>
+  <device dev_id="2" mapped_blocks="1" transaction="1" creation_time="1" snap_time="1">
+    <single_mapping origin_block="7" data_block="3" time="1"/>
+  </device>
+</superblock>
+"#;
+
+    #[test]
+    /// Verify that mappings are found only for the requested device and that
+    /// adjacent mappings are merged.
+    fn test_parse_thin_dump() {
+        assert_eq!(
+            parse_thin_dump(THIN_DUMP, ThinDevId::new_u64(1).unwrap()).unwrap(),
+            vec![
+                ThinMapping {
+                    origin_begin: 0,
+                    length: 4
+                },
+                ThinMapping {
+                    origin_begin: 10,
+                    length: 2
+                },
+            ]
+        );
+        assert_eq!(
+            parse_thin_dump(THIN_DUMP, ThinDevId::new_u64(2).unwrap()).unwrap(),
+            vec![ThinMapping {
+                origin_begin: 7,
+                length: 1
+            }]
+        );
+        assert!(parse_thin_dump(THIN_DUMP, ThinDevId::new_u64(3).unwrap()).is_err());
+    }
+
+    #[test]
+    /// Verify that the ids of all thin devices are found.
+    fn test_thin_dump_devices() {
+        assert_eq!(
+            thin_dump_devices(THIN_DUMP).unwrap(),
+            vec![
+                ThinDevId::new_u64(1).unwrap(),
+                ThinDevId::new_u64(2).unwrap()
+            ]
+        );
+        assert_eq!(thin_dump_devices("").unwrap(), Vec::new());
+    }
+}
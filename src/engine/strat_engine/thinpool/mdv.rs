@@ -13,6 +13,8 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use nix::{
     mount::{mount, umount, MsFlags},
     sys::stat::stat,
@@ -38,6 +40,8 @@ use crate::{
 };
 
 const FILESYSTEM_DIR: &str = "filesystems";
+const THIN_META_BACKUP_DIR: &str = "thin-meta-backups";
+const THIN_META_BACKUP_SUFFIX: &str = ".xml.gz";
 
 #[derive(Debug)]
 pub struct MetadataVol {
@@ -101,20 +105,26 @@ impl MetadataVol {
 
     /// Save info on a new filesystem to persistent storage, or update
     /// the existing info on a filesystem.
-    // Write to a temp file and then rename to actual filename, to
-    // ensure file contents are not truncated if operation is
-    // interrupted.
     pub fn save_fs(
         &self,
         name: &Name,
         uuid: FilesystemUuid,
         fs: &StratFilesystem,
     ) -> StratisResult<()> {
-        let data = serde_json::to_vec(&fs.record(name, uuid))?;
+        self.save_record(&fs.record(name, uuid))
+    }
+
+    /// Save a filesystem record to persistent storage, replacing any existing
+    /// record for the same filesystem.
+    // Write to a temp file and then rename to actual filename, to
+    // ensure file contents are not truncated if operation is
+    // interrupted.
+    pub fn save_record(&self, record: &FilesystemSave) -> StratisResult<()> {
+        let data = serde_json::to_vec(record)?;
         let path = self
             .mount_pt
             .join(FILESYSTEM_DIR)
-            .join(uuid_to_string!(uuid))
+            .join(uuid_to_string!(record.uuid))
             .with_extension("json");
 
         let temp_path = path.with_extension("temp");
@@ -145,13 +155,18 @@ impl MetadataVol {
 
     /// Remove info on a filesystem from persistent storage.
     pub fn rm_fs(&self, fs_uuid: FilesystemUuid) -> StratisResult<()> {
-        let fs_path = self
-            .mount_pt
-            .join(FILESYSTEM_DIR)
-            .join(uuid_to_string!(fs_uuid))
-            .with_extension("json");
+        self.rm_record(
+            &self
+                .mount_pt
+                .join(FILESYSTEM_DIR)
+                .join(uuid_to_string!(fs_uuid))
+                .with_extension("json"),
+        )
+    }
 
-        let needs_fsync = if let Err(err) = remove_file(fs_path) {
+    /// Remove the record file at the given path, as returned by records().
+    pub fn rm_record(&self, path: &Path) -> StratisResult<()> {
+        let needs_fsync = if let Err(err) = remove_file(path) {
             if err.kind() == ErrorKind::NotFound {
                 false
             } else {
@@ -190,6 +205,122 @@ impl MetadataVol {
         Ok(filesystems)
     }
 
+    /// Get every filesystem record stored on the MDV, with the path of the
+    /// file which contains it. Unlike filesystems(), a record which can not
+    /// be read or parsed does not cause the whole operation to fail.
+    #[cfg(feature = "extras")]
+    pub fn records(&self) -> StratisResult<Vec<(PathBuf, StratisResult<FilesystemSave>)>> {
+        let mut records = Vec::new();
+
+        for dir_e in read_dir(self.mount_pt.join(FILESYSTEM_DIR))? {
+            let path = dir_e?.path();
+
+            if path.extension().and_then(|e| e.to_str()) == Some("temp") {
+                continue;
+            }
+
+            let record = OpenOptions::new()
+                .read(true)
+                .open(&path)
+                .and_then(|mut f| {
+                    let mut data = Vec::new();
+                    f.read_to_end(&mut data).map(|_| data)
+                })
+                .map_err(StratisError::from)
+                .and_then(|data| serde_json::from_slice(&data).map_err(StratisError::from));
+            records.push((path, record));
+        }
+
+        Ok(records)
+    }
+
+    /// Save a compressed copy of the XML description of the thin pool
+    /// metadata made at the given time, then remove the oldest copies so
+    /// that no more than keep copies remain.
+    pub fn save_thin_meta_backup(
+        &self,
+        time: DateTime<Utc>,
+        xml: &str,
+        keep: usize,
+    ) -> StratisResult<PathBuf> {
+        let backup_dir = self.mount_pt.join(THIN_META_BACKUP_DIR);
+        if let Err(err) = create_dir(&backup_dir) {
+            if err.kind() != ErrorKind::AlreadyExists {
+                return Err(From::from(err));
+            }
+        }
+
+        let path = backup_dir.join(thin_meta_backup_name(time));
+        let temp_path = backup_dir.join("backup.temp");
+
+        {
+            let backup_file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&temp_path)?;
+
+            let mut encoder = GzEncoder::new(backup_file, Compression::default());
+            encoder.write_all(xml.as_bytes())?;
+            encoder.finish()?.sync_all()?;
+        }
+
+        rename(temp_path, &path)?;
+        File::open(&backup_dir)?.sync_all()?;
+
+        let backups = self.thin_meta_backups()?;
+        let excess = backups.len().saturating_sub(keep);
+        for old in backups.iter().take(excess) {
+            if let Err(err) = remove_file(old) {
+                warn!(
+                    "Could not remove old thin metadata backup {}: {}",
+                    old.display(),
+                    err
+                );
+            }
+        }
+        if excess > 0 {
+            File::open(&backup_dir)?.sync_all()?;
+        }
+
+        Ok(path)
+    }
+
+    /// Get the paths of the thin metadata backups stored on the MDV, oldest
+    /// first.
+    pub fn thin_meta_backups(&self) -> StratisResult<Vec<PathBuf>> {
+        let backup_dir = self.mount_pt.join(THIN_META_BACKUP_DIR);
+        let entries = match read_dir(&backup_dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(From::from(err)),
+        };
+
+        let mut backups = Vec::new();
+        for dir_e in entries {
+            let path = dir_e?.path();
+            if path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.ends_with(THIN_META_BACKUP_SUFFIX))
+            {
+                backups.push(path);
+            }
+        }
+        // Backup names are timestamps which sort in chronological order.
+        backups.sort();
+
+        Ok(backups)
+    }
+
+    /// Read and decompress the thin metadata backup at the given path, as
+    /// returned by thin_meta_backups().
+    pub fn read_thin_meta_backup(path: &Path) -> StratisResult<String> {
+        let mut xml = String::new();
+        GzDecoder::new(File::open(path)?).read_to_string(&mut xml)?;
+        Ok(xml)
+    }
+
     /// Tear down a Metadata Volume.
     pub fn teardown(&mut self, pool_uuid: PoolUuid) -> StratisResult<()> {
         let mtpt_stat = match stat(&self.mount_pt) {
@@ -310,6 +441,15 @@ impl Drop for MetadataVol {
     }
 }
 
+/// The file name of a thin metadata backup made at the given time.
+fn thin_meta_backup_name(time: DateTime<Utc>) -> String {
+    format!(
+        "{}{}",
+        time.format("%Y%m%dT%H%M%S%.6fZ"),
+        THIN_META_BACKUP_SUFFIX
+    )
+}
+
 /// Remove temp files from the designated directory.
 /// Returns an error if the directory can not be read.
 /// Persists if an individual directory entry can not be read due to an
@@ -330,3 +470,38 @@ fn remove_temp_files(dir: &Path) -> StratisResult<(u64, Vec<PathBuf>)> {
     }
     Ok((found, failed))
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    #[test]
+    /// Verify that the names of thin metadata backups sort in the order in
+    /// which the backups were made.
+    fn test_thin_meta_backup_name_order() {
+        let first = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let times = [
+            first,
+            first + Duration::microseconds(1),
+            first + Duration::seconds(9),
+            first + Duration::seconds(10),
+            first + Duration::days(400),
+        ];
+        let mut names = times
+            .iter()
+            .rev()
+            .map(|t| thin_meta_backup_name(*t))
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            times
+                .iter()
+                .map(|t| thin_meta_backup_name(*t))
+                .collect::<Vec<_>>()
+        );
+        assert!(names.iter().all(|n| n.ends_with(THIN_META_BACKUP_SUFFIX)));
+    }
+}
@@ -2,8 +2,10 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+mod compaction;
 mod dm_structs;
 mod filesystem;
+mod mappings;
 mod mdv;
 mod thinids;
 #[allow(clippy::module_inception)]
@@ -12,6 +14,10 @@ mod thinpool;
 #[cfg(test)]
 pub use self::dm_structs::ThinPoolStatusDigest;
 pub use self::{
+    compaction::Relocation,
+    dm_structs::linear_table,
     filesystem::StratFilesystem,
-    thinpool::{ThinPool, ThinPoolSizeParams, DATA_BLOCK_SIZE},
+    thinpool::{coalesce_segs, CloneSource, ThinPool, ThinPoolSizeParams, DATA_BLOCK_SIZE},
 };
+#[cfg(feature = "extras")]
+pub use self::{mappings::thin_dump_devices, mdv::MetadataVol};
@@ -8,16 +8,19 @@ use std::{
     cmp::{max, min, Ordering},
     collections::{hash_map::Entry, HashMap, HashSet},
     marker::PhantomData,
+    path::{Path, PathBuf},
     thread::scope,
 };
 
+use chrono::Utc;
 use itertools::Itertools;
 use retry::{delay::Fixed, retry_with_index};
 use serde_json::{Map, Value};
 
 use devicemapper::{
     device_exists, message, Bytes, DataBlocks, Device, DmDevice, DmName, DmNameBuf, DmOptions,
-    LinearDev, MetaBlocks, Sectors, ThinDevId, ThinPoolDev, ThinPoolStatus, IEC,
+    LinearDev, MetaBlocks, Sectors, ThinDevId, ThinPoolDev, ThinPoolStatus, ThinPoolStatusSummary,
+    IEC,
 };
 
 use crate::{
@@ -25,7 +28,10 @@ use crate::{
         engine::{DumpState, Filesystem, StateDiff},
         strat_engine::{
             backstore::backstore::{v1, v2, InternalBackstore},
-            cmd::{set_uuid, thin_check, thin_metadata_size, thin_repair},
+            cmd::{
+                get_uuid, set_uuid, thin_check, thin_dump_metadata_snap, thin_metadata_size,
+                thin_repair,
+            },
             dm::{get_dm, list_of_thin_pool_devices, remove_optional_devices},
             names::{
                 format_flex_ids, format_thin_ids, format_thinpool_ids, FlexRole, ThinPoolRole,
@@ -34,10 +40,14 @@ use crate::{
             serde_structs::{FlexDevsSave, Recordable, ThinPoolDevSave},
             shared::merge,
             thinpool::{
+                compaction::{
+                    copy_run, relocated, Compaction, FlexDev, Relocation, COMPACTION_CHUNK_SIZE,
+                },
                 dm_structs::{
                     linear_table, thin_pool_status_parser, thin_table, ThinPoolStatusDigest,
                 },
                 filesystem::StratFilesystem,
+                mappings::{parse_thin_dump, thin_dump_devices},
                 mdv::MetadataVol,
                 thinids::ThinDevIdPool,
             },
@@ -46,7 +56,7 @@ use crate::{
         structures::Table,
         types::{
             Compare, Diff, FilesystemUuid, Name, PoolUuid, SetDeleteAction, StratFilesystemDiff,
-            ThinPoolDiff,
+            ThinPoolDiff, ThinPoolUsageInfo,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -62,6 +72,13 @@ pub const DATA_BLOCK_SIZE: Sectors = Sectors(2 * IEC::Ki);
 // 512 MiB
 const INITIAL_MDV_SIZE: Sectors = Sectors(IEC::Mi);
 
+// Number of thin metadata backups kept in the MDV.
+const THIN_META_BACKUPS_KEPT: usize = 3;
+
+// Prefix of the name of the temporary snapshot taken of a filesystem while
+// it is being cloned into another pool.
+const CLONE_SNAPSHOT_PREFIX: &str = "stratis-clone-";
+
 // Use different constants for testing and application builds.
 use self::consts::{DATA_ALLOC_SIZE, DATA_LOWATER};
 #[cfg(not(test))]
@@ -115,7 +132,7 @@ fn thin_pool_identifiers(thin_pool: &ThinPoolDev) -> String {
 /// second argument, merge those two together.
 /// Postcondition: left.len() + right.len() - 1 <= result.len()
 /// Postcondition: result.len() <= left.len() + right.len()
-fn coalesce_segs(
+pub fn coalesce_segs(
     left: &[(Sectors, Sectors)],
     right: &[(Sectors, Sectors)],
 ) -> Vec<(Sectors, Sectors)> {
@@ -154,10 +171,47 @@ fn coalesce_segs(
 struct Segments {
     meta_segments: Vec<(Sectors, Sectors)>,
     meta_spare_segments: Vec<(Sectors, Sectors)>,
+    /// The time at which the spare was last validated, as recorded in
+    /// FlexDevsSave.
+    meta_spare_validated: Option<i64>,
     data_segments: Vec<(Sectors, Sectors)>,
     mdv_segments: Vec<(Sectors, Sectors)>,
 }
 
+impl Segments {
+    /// The segments of the given flex device.
+    fn flex_dev(&self, dev: FlexDev) -> &Vec<(Sectors, Sectors)> {
+        match dev {
+            FlexDev::ThinData => &self.data_segments,
+            FlexDev::ThinMeta => &self.meta_segments,
+            FlexDev::ThinMetaSpare => &self.meta_spare_segments,
+            FlexDev::Mdv => &self.mdv_segments,
+        }
+    }
+
+    fn flex_dev_mut(&mut self, dev: FlexDev) -> &mut Vec<(Sectors, Sectors)> {
+        match dev {
+            FlexDev::ThinData => &mut self.data_segments,
+            FlexDev::ThinMeta => &mut self.meta_segments,
+            FlexDev::ThinMetaSpare => &mut self.meta_spare_segments,
+            FlexDev::Mdv => &mut self.mdv_segments,
+        }
+    }
+
+    /// The segments of all the flex devices.
+    fn flex_devs(&self) -> Vec<(FlexDev, Vec<(Sectors, Sectors)>)> {
+        [
+            FlexDev::ThinData,
+            FlexDev::ThinMeta,
+            FlexDev::ThinMetaSpare,
+            FlexDev::Mdv,
+        ]
+        .into_iter()
+        .map(|dev| (dev, self.flex_dev(dev).clone()))
+        .collect()
+    }
+}
+
 /// Calculate the room available for data that is not taken up by metadata.
 fn room_for_data(usable_size: Sectors, meta_size: Sectors) -> Sectors {
     Sectors(
@@ -218,6 +272,22 @@ fn calc_total_physical_used(data_used: Option<Sectors>, segments: &Segments) ->
     Some(data_dev_used + spare_total + meta_total + mdv_total)
 }
 
+/// A point-in-time copy of a filesystem which is to be cloned into another
+/// pool.
+#[derive(Debug)]
+pub struct CloneSource {
+    /// UUID of the temporary snapshot of the filesystem being cloned
+    pub snapshot_uuid: FilesystemUuid,
+    /// Device node of the temporary snapshot
+    pub devnode: PathBuf,
+    pub size: Sectors,
+    pub size_limit: Option<Sectors>,
+    /// XFS UUID of the filesystem being cloned
+    pub xfs_uuid: FilesystemUuid,
+    /// Provisioned (offset, length) ranges of the temporary snapshot
+    pub ranges: Vec<(Sectors, Sectors)>,
+}
+
 /// A ThinPool struct contains the thinpool itself, the spare
 /// segments for its metadata device, and the filesystems and filesystem
 /// metadata associated with it.
@@ -256,6 +326,20 @@ impl<B> ThinPool<B> {
             .map(|(d, m)| (datablocks_to_sectors(d), m))
     }
 
+    /// Get the last cached value for the used and total space on the data
+    /// and metadata devices of the thin pool.
+    fn usage_info(&self) -> Option<ThinPoolUsageInfo> {
+        self.thin_pool_status
+            .as_ref()
+            .and_then(thin_pool_status_parser::usage)
+            .map(|usage| ThinPoolUsageInfo {
+                data_used: datablocks_to_sectors(usage.used_data).bytes(),
+                data_total: datablocks_to_sectors(usage.total_data).bytes(),
+                meta_used: usage.used_meta.sectors().bytes(),
+                meta_total: usage.total_meta.sectors().bytes(),
+            })
+    }
+
     /// Sum the logical size of all filesystems on the pool.
     pub fn filesystem_logical_size_sum(&self) -> StratisResult<Sectors> {
         Ok(self
@@ -423,16 +507,9 @@ impl<B> ThinPool<B> {
             .collect()
     }
 
-    /// Create a filesystem within the thin pool. Given name must not
-    /// already be in use.
-    pub fn create_filesystem(
-        &mut self,
-        pool_name: &str,
-        pool_uuid: PoolUuid,
-        name: &str,
-        size: Sectors,
-        size_limit: Option<Sectors>,
-    ) -> StratisResult<FilesystemUuid> {
+    /// Return an error if the given name is already recorded as the name of
+    /// a filesystem in this thin pool.
+    fn check_fs_name_unused(&self, pool_name: &str, name: &str) -> StratisResult<()> {
         if self
             .mdv
             .filesystems()?
@@ -445,14 +522,18 @@ impl<B> ThinPool<B> {
                 "Pool {pool_name} already has a record of filesystem name {name}"
             )));
         }
+        Ok(())
+    }
 
-        let (fs_uuid, mut new_filesystem) = StratFilesystem::initialize(
-            pool_uuid,
-            &self.thin_pool,
-            size,
-            size_limit,
-            self.id_gen.new_id()?,
-        )?;
+    /// Save and register a newly initialized filesystem. If the filesystem
+    /// metadata can not be saved, the filesystem is destroyed.
+    fn add_new_filesystem(
+        &mut self,
+        pool_name: &str,
+        name: &str,
+        fs_uuid: FilesystemUuid,
+        mut new_filesystem: StratFilesystem,
+    ) -> StratisResult<FilesystemUuid> {
         let name = Name::new(name.to_owned());
         if let Err(err) = self.mdv.save_fs(&name, fs_uuid, &new_filesystem) {
             if let Err(err2) = retry_with_index(Fixed::from_millis(100).take(4), |i| {
@@ -479,6 +560,55 @@ impl<B> ThinPool<B> {
         Ok(fs_uuid)
     }
 
+    /// Create a filesystem within the thin pool. Given name must not
+    /// already be in use.
+    pub fn create_filesystem(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        name: &str,
+        size: Sectors,
+        size_limit: Option<Sectors>,
+    ) -> StratisResult<FilesystemUuid> {
+        self.create_filesystem_with_uuid(
+            pool_name,
+            pool_uuid,
+            FilesystemUuid::new_v4(),
+            name,
+            size,
+            size_limit,
+        )
+    }
+
+    /// Create a filesystem with the given UUID within the thin pool. Neither
+    /// the given name nor the given UUID may already be in use.
+    pub fn create_filesystem_with_uuid(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        fs_uuid: FilesystemUuid,
+        name: &str,
+        size: Sectors,
+        size_limit: Option<Sectors>,
+    ) -> StratisResult<FilesystemUuid> {
+        self.check_fs_name_unused(pool_name, name)?;
+        if let Some((existing_name, _)) = self.get_filesystem_by_uuid(fs_uuid) {
+            return Err(StratisError::Msg(format!(
+                "UUID {fs_uuid} is already in use by filesystem {existing_name} in pool {pool_name}"
+            )));
+        }
+
+        let new_filesystem = StratFilesystem::initialize(
+            pool_uuid,
+            &self.thin_pool,
+            fs_uuid,
+            size,
+            size_limit,
+            self.id_gen.new_id()?,
+        )?;
+        self.add_new_filesystem(pool_name, name, fs_uuid, new_filesystem)
+    }
+
     /// Create a filesystem snapshot of the origin.  Given origin_uuid
     /// must exist.  Returns the Uuid of the new filesystem.
     pub fn snapshot_filesystem(
@@ -563,6 +693,140 @@ impl<B> ThinPool<B> {
         }
     }
 
+    /// Find the provisioned ranges of the thin device with the given id as
+    /// (offset, length) pairs, using a snapshot of the thin pool metadata.
+    fn provisioned_ranges(&self, thin_id: ThinDevId) -> StratisResult<Vec<(Sectors, Sectors)>> {
+        message(get_dm(), &self.thin_pool, "reserve_metadata_snap")?;
+        let res = thin_dump_metadata_snap(&self.thin_pool.meta_dev().devnode(), Some(thin_id))
+            .and_then(|xml| parse_thin_dump(&xml, thin_id));
+        if let Err(e) = message(get_dm(), &self.thin_pool, "release_metadata_snap") {
+            warn!(
+                "Failed to release metadata snapshot of thinpool device with \"{}\": {}",
+                thin_pool_identifiers(&self.thin_pool),
+                e
+            );
+        }
+
+        let block_size = self.thin_pool.data_block_size();
+        Ok(res?
+            .into_iter()
+            .map(|m| (block_size * m.origin_begin, block_size * m.length))
+            .collect())
+    }
+
+    /// Back up the thin pool metadata to the MDV while the pool is in use.
+    /// The backup is made from a metadata snapshot, so that the pool need not
+    /// be suspended, and is only stored if every filesystem of the pool is
+    /// found in it. Older backups are rotated out.
+    /// Returns the path of the new backup.
+    pub fn backup_metadata(&self) -> StratisResult<PathBuf> {
+        match self.thin_pool.status(get_dm(), DmOptions::default())? {
+            ThinPoolStatus::Working(ref status)
+                if !status.needs_check
+                    && status.held_metadata_root.is_none()
+                    && status.summary != ThinPoolStatusSummary::ReadOnly => {}
+            _ => {
+                return Err(StratisError::Msg(format!(
+                    "Metadata of thinpool device with \"{}\" can not be backed up in its current state",
+                    thin_pool_identifiers(&self.thin_pool)
+                )));
+            }
+        }
+
+        let time = Utc::now();
+        message(get_dm(), &self.thin_pool, "reserve_metadata_snap")?;
+        let res = thin_dump_metadata_snap(&self.thin_pool.meta_dev().devnode(), None);
+        if let Err(e) = message(get_dm(), &self.thin_pool, "release_metadata_snap") {
+            warn!(
+                "Failed to release metadata snapshot of thinpool device with \"{}\": {}",
+                thin_pool_identifiers(&self.thin_pool),
+                e
+            );
+        }
+        let xml = res?;
+
+        let thin_ids = thin_dump_devices(&xml)?.into_iter().collect::<HashSet<_>>();
+        let missing = self
+            .filesystems
+            .iter()
+            .filter(|(_, _, fs)| !thin_ids.contains(&fs.thin_id()))
+            .map(|(name, uuid, _)| format!("{name} ({uuid})"))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(StratisError::Msg(format!(
+                "Metadata snapshot of thinpool device with \"{}\" does not describe filesystems {}; not saving backup",
+                thin_pool_identifiers(&self.thin_pool),
+                missing.join(", ")
+            )));
+        }
+
+        self.mdv
+            .save_thin_meta_backup(time, &xml, THIN_META_BACKUPS_KEPT)
+    }
+
+    /// Take a temporary snapshot of a filesystem that is to be cloned into
+    /// another pool and find the ranges of the snapshot that must be copied.
+    /// The snapshot must be removed with destroy_clone_source() once the
+    /// copy is complete.
+    pub fn prepare_clone_source(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        fs_uuid: FilesystemUuid,
+    ) -> StratisResult<CloneSource> {
+        let xfs_uuid = match self.get_filesystem_by_uuid(fs_uuid) {
+            Some((_, fs)) => get_uuid(&fs.devnode())?,
+            None => {
+                return Err(StratisError::Msg(format!(
+                    "Filesystem with UUID {fs_uuid} could not be found"
+                )));
+            }
+        };
+
+        let snapshot_name = format!("{CLONE_SNAPSHOT_PREFIX}{fs_uuid}");
+        if self.get_filesystem_by_name(&snapshot_name).is_some() {
+            return Err(StratisError::Msg(format!(
+                "A clone of filesystem with UUID {fs_uuid} is already in progress"
+            )));
+        }
+        let (snapshot_uuid, snapshot) =
+            self.snapshot_filesystem(pool_name, pool_uuid, fs_uuid, &snapshot_name)?;
+        let devnode = snapshot.devnode();
+        let size = snapshot.thindev_size();
+        let size_limit = snapshot.size_limit();
+        let thin_id = snapshot.thin_id();
+
+        match self.provisioned_ranges(thin_id) {
+            Ok(ranges) => Ok(CloneSource {
+                snapshot_uuid,
+                devnode,
+                size,
+                size_limit,
+                xfs_uuid,
+                ranges,
+            }),
+            Err(err) => {
+                if let Err(err2) = self.destroy_clone_source(pool_name, snapshot_uuid) {
+                    warn!(
+                        "Failed to remove temporary snapshot {} of filesystem {}: {}",
+                        snapshot_uuid, fs_uuid, err2
+                    );
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Remove the temporary snapshot created by prepare_clone_source().
+    pub fn destroy_clone_source(
+        &mut self,
+        pool_name: &str,
+        snapshot_uuid: FilesystemUuid,
+    ) -> StratisResult<()> {
+        self.destroy_filesystem(pool_name, snapshot_uuid)
+            .map(|_| ())
+    }
+
     #[cfg(test)]
     pub fn state(&self) -> Option<ThinPoolStatusDigest> {
         self.thin_pool_status.as_ref().map(|s| s.into())
@@ -648,6 +912,79 @@ impl<B> ThinPool<B> {
         Ok(())
     }
 
+    /// Make the given segments those of the flex device, reloading the table
+    /// of its DM device if it has one. The thin pool must be suspended.
+    pub fn set_flex_segments(
+        &mut self,
+        dev: FlexDev,
+        segments: Vec<(Sectors, Sectors)>,
+    ) -> StratisResult<()> {
+        let table = linear_table::segs_to_table(self.backstore_device, &segments);
+        match dev {
+            FlexDev::ThinData => self.thin_pool.set_data_table(get_dm(), table)?,
+            FlexDev::ThinMeta => self.thin_pool.set_meta_table(get_dm(), table)?,
+            // The spare is not in use, so it has no DM device.
+            FlexDev::ThinMetaSpare => (),
+            FlexDev::Mdv => self.mdv.set_table(table)?,
+        }
+        *self.segments.flex_dev_mut(dev) = segments;
+        Ok(())
+    }
+
+    /// Copy a run of sectors of a flex device to its new location on the cap
+    /// device at devnode and switch the flex device over to it. The thin pool
+    /// must be suspended.
+    ///
+    /// Returns the segments of the flex device from before the relocation,
+    /// with which it can be undone.
+    pub fn relocate(
+        &mut self,
+        devnode: &Path,
+        relocation: &Relocation,
+    ) -> StratisResult<Vec<(Sectors, Sectors)>> {
+        copy_run(devnode, relocation)?;
+        let old_segments = self.segments.flex_dev(relocation.dev).clone();
+        self.set_flex_segments(relocation.dev, relocated(&old_segments, relocation))?;
+        Ok(old_segments)
+    }
+
+    /// Once compaction is complete, add the free space which follows the thin
+    /// data device to it, in whole data blocks.
+    ///
+    /// This method returns the extension size as Ok(data_extension).
+    pub fn finish_compaction(&mut self, compaction: &Compaction) -> StratisResult<Sectors> {
+        let data_end = self
+            .segments
+            .data_segments
+            .last()
+            .map(|(start, length)| *start + *length)
+            .expect("thin pool data device has at least one segment");
+        let data_extension = match compaction.free() {
+            [(start, length)] if compaction.is_complete() && *start == data_end => {
+                datablocks_to_sectors(sectors_to_datablocks(*length))
+            }
+            _ => Sectors(0),
+        };
+        if data_extension == Sectors(0) {
+            return Ok(Sectors(0));
+        }
+
+        let segments = coalesce_segs(&self.segments.data_segments, &[(data_end, data_extension)]);
+        self.thin_pool.suspend(get_dm(), DmOptions::default())?;
+        // Leaves data device suspended
+        let res = self.thin_pool.set_data_table(
+            get_dm(),
+            linear_table::segs_to_table(self.backstore_device, &segments),
+        );
+        if res.is_ok() {
+            self.segments.data_segments = segments;
+        }
+        self.thin_pool.resume(get_dm())?;
+        res?;
+
+        Ok(data_extension)
+    }
+
     pub fn fs_limit(&self) -> u64 {
         self.fs_limit
     }
@@ -657,6 +994,24 @@ impl<B> ThinPool<B> {
         self.enable_overprov
     }
 
+    /// Sum the portions of all filesystem reservations that have not yet been
+    /// consumed by the filesystems holding them, excluding the filesystem with
+    /// the given UUID, if any.
+    fn unclaimed_reservations(&self, exclude: Option<FilesystemUuid>) -> Sectors {
+        self.filesystems
+            .iter()
+            .filter(|(_, uuid, _)| Some(**uuid) != exclude)
+            .map(|(_, _, fs)| fs.unclaimed_reservation())
+            .sum()
+    }
+
+    /// Returns true if any filesystem on the pool has a reservation.
+    pub fn has_reservations(&self) -> bool {
+        self.filesystems
+            .iter()
+            .any(|(_, _, fs)| fs.reservation().is_some())
+    }
+
     /// Indicate to the pool that it may now have more room for metadata growth.
     pub fn clear_out_of_meta_flag(&mut self) {
         self.out_of_meta_space = false;
@@ -802,6 +1157,7 @@ impl ThinPool<v1::Backstore> {
         let segments = Segments {
             meta_segments: vec![meta_segments],
             meta_spare_segments: vec![spare_segments],
+            meta_spare_validated: None,
             data_segments: vec![data_segments],
             mdv_segments: vec![mdv_segments],
         };
@@ -945,6 +1301,7 @@ impl ThinPool<v2::Backstore> {
         let segments = Segments {
             meta_segments: vec![meta_segments],
             meta_spare_segments: vec![spare_segments],
+            meta_spare_validated: None,
             data_segments: vec![data_segments],
             mdv_segments: vec![mdv_segments],
         };
@@ -1097,6 +1454,13 @@ where
             meta_segments,
             spare_segments,
         )?;
+        // If the thin pool metadata was repaired into the spare during setup,
+        // the spare is now the previous thin metadata device.
+        let meta_spare_validated = if meta_segments == flex_devs.thin_meta_dev {
+            flex_devs.thin_meta_dev_spare_validated
+        } else {
+            None
+        };
 
         let (dm_name, dm_uuid) = format_flex_ids(pool_uuid, FlexRole::ThinData);
         let data_dev = LinearDev::setup(
@@ -1222,6 +1586,7 @@ where
         let segments = Segments {
             meta_segments,
             meta_spare_segments: spare_segments,
+            meta_spare_validated,
             data_segments,
             mdv_segments,
         };
@@ -1337,9 +1702,15 @@ where
                 )
                 .saturating_sub(*sum),
             ))
+        } else if self.has_reservations() {
+            // When overprovisioning, filesystems that have consumed their
+            // reservation, or have none, may only grow into space that is not
+            // reserved for other filesystems.
+            Some(self.unreserved_space(backstore, None).unwrap_or(Sectors(0)))
         } else {
             None
         };
+        let enable_overprov = self.enable_overprov;
 
         scope(|s| {
             // This collect is needed to ensure all threads are spawned in
@@ -1351,7 +1722,12 @@ where
                 .filesystems
                 .iter_mut()
                 .filter_map(|(name, uuid, fs)| {
-                    fs.visit_values(remaining_space.as_mut())
+                    let remaining = if enable_overprov && fs.unclaimed_reservation() > Sectors(0) {
+                        None
+                    } else {
+                        remaining_space.as_mut()
+                    };
+                    fs.visit_values(remaining)
                         .map(|(mt_pt, extend_size)| (name, *uuid, fs, mt_pt, extend_size))
                 })
                 .map(|(name, uuid, fs, mt_pt, extend_size)| {
@@ -1412,9 +1788,15 @@ where
         });
 
         if remaining_space == Some(Sectors(0)) {
-            warn!(
-                "Overprovisioning protection must be disabled or more space must be added to the pool to extend the filesystem further"
-            );
+            if self.enable_overprov {
+                warn!(
+                    "Remaining space in the pool is reserved for other filesystems; filesystem reservations must be reduced or more space must be added to the pool to extend the filesystem further"
+                );
+            } else {
+                warn!(
+                    "Overprovisioning protection must be disabled or more space must be added to the pool to extend the filesystem further"
+                );
+            }
         }
 
         Ok(updated)
@@ -1514,6 +1896,185 @@ where
         }
     }
 
+    /// Plan the compaction of the flex devices, so that each occupies a
+    /// single extent. The free space among their segments is topped up to
+    /// COMPACTION_CHUNK_SIZE, if possible, with space allocated from the
+    /// backstore.
+    ///
+    /// Any space allocated from the cap device after the flex devices belongs
+    /// to none of them and so is free, too. Such space is left behind by a
+    /// compaction which did not complete, and is taken up by the next one.
+    ///
+    /// If the flex devices already occupy a single extent each, the
+    /// compaction has no steps.
+    pub fn plan_compaction(
+        &mut self,
+        pool_uuid: PoolUuid,
+        backstore: &mut B,
+    ) -> StratisResult<Compaction> {
+        let devs = self.segments.flex_devs();
+        if devs.iter().all(|(_, segments)| segments.len() <= 1) {
+            return Ok(Compaction::new(devs, Vec::new(), COMPACTION_CHUNK_SIZE));
+        }
+
+        let mut used = devs
+            .iter()
+            .flat_map(|(_, segments)| segments.iter().copied())
+            .collect::<Vec<_>>();
+        used.sort();
+        let mut free = used
+            .windows(2)
+            .filter_map(|window| {
+                let end = window[0].0 + window[0].1;
+                if end < window[1].0 {
+                    Some((end, window[1].0 - end))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let used_end = used
+            .last()
+            .map(|(start, length)| *start + *length)
+            .expect("thin pool has flex devices");
+        let free_size = free.iter().map(|(_, length)| *length).sum::<Sectors>()
+            + Sectors(backstore.allocated_end().saturating_sub(*used_end));
+        let request = datablocks_to_sectors(sectors_to_datablocks(min(
+            Sectors(COMPACTION_CHUNK_SIZE.saturating_sub(*free_size)),
+            backstore.available_in_backstore(),
+        )));
+        if request > Sectors(0) {
+            if let Some((start, _)) = backstore
+                .alloc(pool_uuid, &[request])?
+                .and_then(|segs| segs.first().copied())
+            {
+                if start < used_end {
+                    return Err(StratisError::Msg(format!(
+                        "Space allocated for compaction at {start} lies among the flex devices, which end at {used_end}"
+                    )));
+                }
+            }
+        }
+        let allocated_end = backstore.allocated_end();
+        if allocated_end > used_end {
+            free.push((used_end, allocated_end - used_end));
+        }
+
+        if free.is_empty() {
+            return Err(StratisError::OutOfSpaceError(
+                "No unallocated space is available with which to compact the pool".to_string(),
+            ));
+        }
+
+        Ok(Compaction::new(devs, free, COMPACTION_CHUNK_SIZE))
+    }
+
+    /// Ensure that the thinpool's data dev has at least required free space,
+    /// extending it as necessary.
+    ///
+    /// The bool in the return value indicates whether the data dev was
+    /// extended and the pool metadata must therefore be saved.
+    fn ensure_data_space(
+        &mut self,
+        pool_uuid: PoolUuid,
+        backstore: &mut B,
+        required: Sectors,
+    ) -> (bool, StratisResult<()>) {
+        match self.thin_pool.status(get_dm(), DmOptions::default()) {
+            Ok(status) => self.set_state(Some(status)),
+            Err(e) => return (false, Err(StratisError::from(e))),
+        }
+        let mut free = match self.used() {
+            Some((data_used, _)) => self.thin_pool.data_dev().size() - data_used,
+            None => {
+                return (
+                    false,
+                    Err(StratisError::Msg(
+                        "Unable to determine the amount of free space in the thin pool".to_string(),
+                    )),
+                )
+            }
+        };
+
+        let available = free + backstore.available_in_backstore();
+        if available < required {
+            return (
+                false,
+                Err(StratisError::OutOfSpaceError(format!(
+                    "{required} of free space is required but only {available} is available"
+                ))),
+            );
+        }
+
+        let mut extended = false;
+        while free < required {
+            match self.extend_thin_data_device(pool_uuid, backstore) {
+                (changed, Ok(Sectors(0))) => {
+                    return (
+                        extended || changed,
+                        Err(StratisError::OutOfSpaceError(format!(
+                            "Unable to extend thin pool data device to hold {required}"
+                        ))),
+                    );
+                }
+                (changed, Ok(extend_size)) => {
+                    extended |= changed;
+                    free += extend_size;
+                }
+                (changed, Err(e)) => return (extended || changed, Err(e)),
+            }
+        }
+
+        (extended, Ok(()))
+    }
+
+    /// Create a filesystem within the thin pool that is a copy of the
+    /// filesystem described by source, which may belong to another pool.
+    /// The XFS UUID of the source is preserved if preserve_uuid is true.
+    /// The data dev is extended beforehand if necessary, so that it can
+    /// hold all the data to be copied.
+    ///
+    /// The bool in the return value indicates whether the data dev was
+    /// extended and the pool metadata must therefore be saved.
+    #[allow(clippy::too_many_arguments)]
+    pub fn clone_filesystem(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        backstore: &mut B,
+        name: &str,
+        source: &CloneSource,
+        preserve_uuid: bool,
+        progress: &mut dyn FnMut(Sectors, Sectors),
+    ) -> (bool, StratisResult<FilesystemUuid>) {
+        if let Err(e) = self.check_fs_name_unused(pool_name, name) {
+            return (false, Err(e));
+        }
+
+        let required = source.ranges.iter().map(|(_, length)| *length).sum();
+        let (extended, res) = self.ensure_data_space(pool_uuid, backstore, required);
+        let res = res.and_then(|_| {
+            let (fs_uuid, new_filesystem) = StratFilesystem::initialize_copy(
+                pool_uuid,
+                &self.thin_pool,
+                &source.devnode,
+                &source.ranges,
+                source.size,
+                source.size_limit,
+                self.id_gen.new_id()?,
+                if preserve_uuid {
+                    Some(source.xfs_uuid)
+                } else {
+                    None
+                },
+                progress,
+            )?;
+            self.add_new_filesystem(pool_name, name, fs_uuid, new_filesystem)
+        });
+        (extended, res)
+    }
+
     /// Extend thinpool's meta dev.
     ///
     /// If is_lowater is true, it was determined that the low water mark has been
@@ -1708,6 +2269,12 @@ where
         }
     }
 
+    /// Restore a filesystem limit which was in effect before a call to
+    /// set_fs_limit. The thin metadata device is not shrunk.
+    pub fn restore_fs_limit(&mut self, fs_limit: u64) {
+        self.fs_limit = fs_limit;
+    }
+
     /// Return the limit for total size of all filesystems when overprovisioning
     /// is disabled.
     pub fn total_fs_limit(&self, backstore: &B) -> Sectors {
@@ -1717,6 +2284,59 @@ where
         )
     }
 
+    /// Return the amount of data space available to the thin pool that is
+    /// neither in use nor held back by the reservation of any filesystem other
+    /// than the filesystem with the given UUID. Returns None if the usage of
+    /// the thin pool is not known.
+    fn unreserved_space(&self, backstore: &B, exclude: Option<FilesystemUuid>) -> Option<Sectors> {
+        self.used().map(|(data_used, _)| {
+            Sectors(
+                self.total_fs_limit(backstore)
+                    .saturating_sub(*data_used)
+                    .saturating_sub(*self.unclaimed_reservations(exclude)),
+            )
+        })
+    }
+
+    /// The data space that creating a filesystem of the given size is
+    /// expected to consume before anything is written to it. This is at
+    /// least one data block, and at least the XFS log, which mkfs.xfs writes
+    /// in full and which by default is 1/2048 of the size of the filesystem.
+    pub fn new_fs_footprint(&self, size: Sectors) -> Sectors {
+        let block_size = self.thin_pool.data_block_size();
+        let log_size = Sectors(*size / 2048);
+        max(
+            block_size,
+            Sectors(log_size.div_ceil(*block_size) * *block_size),
+        )
+    }
+
+    /// The data space that creating a snapshot is expected to consume before
+    /// anything is written to it. The snapshot shares the data of its origin,
+    /// but its new XFS UUID is written to it, which takes a data block.
+    pub fn snapshot_footprint(&self) -> Sectors {
+        self.thin_pool.data_block_size()
+    }
+
+    /// Verify that creating a new filesystem or snapshot, which is expected
+    /// to consume footprint of data space at once, will not encroach on space
+    /// reserved for existing filesystems.
+    pub fn check_reservations(&self, backstore: &B, footprint: Sectors) -> StratisResult<()> {
+        if !self.has_reservations() {
+            return Ok(());
+        }
+        match self.unreserved_space(backstore, None) {
+            Some(unreserved) if unreserved < footprint => Err(StratisError::Msg(format!(
+                "Only {unreserved} of free space in the pool is not reserved for existing filesystems ({} unclaimed) but the new filesystem is expected to consume {footprint}; unwilling to create a filesystem that would consume reserved space",
+                self.unclaimed_reservations(None)
+            ))),
+            Some(_) => Ok(()),
+            None => Err(StratisError::Msg(
+                "Unable to determine thin pool usage; unwilling to create a filesystem while filesystem reservations can not be verified".to_string(),
+            )),
+        }
+    }
+
     /// Set the overprovisioning mode to either enabled or disabled based on the boolean
     /// provided as an input and return an error if changing this property fails.
     pub fn set_overprov_mode(&mut self, backstore: &B, enabled: bool) -> (bool, StratisResult<()>) {
@@ -1767,6 +2387,47 @@ where
         Ok(changed)
     }
 
+    /// Set the reservation for filesystem with given UUID. The portion of the
+    /// reservation not yet consumed by the filesystem must fit in the data
+    /// space that is neither in use nor reserved for other filesystems.
+    pub fn set_fs_reservation(
+        &mut self,
+        backstore: &B,
+        fs_uuid: FilesystemUuid,
+        reservation: Option<Sectors>,
+    ) -> StratisResult<bool> {
+        if let Some(res) = reservation {
+            let (_, fs) = self.get_filesystem_by_uuid(fs_uuid).ok_or_else(|| {
+                StratisError::Msg(format!("No filesystem with UUID {fs_uuid} found"))
+            })?;
+            let unclaimed = fs.unclaimed(res);
+            let available = self.unreserved_space(backstore, Some(fs_uuid)).ok_or_else(|| {
+                StratisError::Msg(
+                    "Unable to determine thin pool usage; can not verify that the reservation can be satisfied".to_string(),
+                )
+            })?;
+            if unclaimed > available {
+                return Err(StratisError::Msg(format!(
+                    "Reservation of {res} for filesystem {fs_uuid} requires {unclaimed} of additional space but only {available} is unused and unreserved"
+                )));
+            }
+        }
+
+        let changed = {
+            let (_, fs) = self.get_mut_filesystem_by_uuid(fs_uuid).ok_or_else(|| {
+                StratisError::Msg(format!("No filesystem with UUID {fs_uuid} found"))
+            })?;
+            fs.set_reservation(reservation)?
+        };
+        let (name, fs) = self
+            .get_filesystem_by_uuid(fs_uuid)
+            .ok_or_else(|| StratisError::Msg(format!("No filesystem with UUID {fs_uuid} found")))?;
+        if changed {
+            self.mdv.save_fs(&name, fs_uuid, fs)?;
+        }
+        Ok(changed)
+    }
+
     /// Set the filesystem merge scheduled value for filesystem with given UUID
     /// Returns true if the value was changed from the filesystem's, previous
     /// value, otherwise false.
@@ -1949,6 +2610,7 @@ impl<B> Into<Value> for &ThinPool<B> {
 pub struct ThinPoolState {
     allocated_size: Bytes,
     used: Option<Bytes>,
+    usage: Option<ThinPoolUsageInfo>,
 }
 
 impl StateDiff for ThinPoolState {
@@ -1958,6 +2620,7 @@ impl StateDiff for ThinPoolState {
         ThinPoolDiff {
             allocated_size: self.allocated_size.compare(&new_state.allocated_size),
             used: self.used.compare(&new_state.used),
+            usage: self.usage.compare(&new_state.usage),
         }
     }
 
@@ -1965,6 +2628,7 @@ impl StateDiff for ThinPoolState {
         ThinPoolDiff {
             allocated_size: Diff::Unchanged(self.allocated_size),
             used: Diff::Unchanged(self.used),
+            usage: Diff::Unchanged(self.usage),
         }
     }
 }
@@ -1980,6 +2644,7 @@ where
         ThinPoolState {
             allocated_size: self.allocated_size.bytes(),
             used: self.total_physical_used().map(|u| u.bytes()),
+            usage: self.usage_info(),
         }
     }
 
@@ -1990,6 +2655,7 @@ where
         ThinPoolState {
             allocated_size: self.allocated_size.bytes(),
             used: self.total_physical_used().map(|u| u.bytes()),
+            usage: self.usage_info(),
         }
     }
 }
@@ -2001,6 +2667,7 @@ impl Recordable<FlexDevsSave> for Segments {
             thin_meta_dev: self.meta_segments.to_vec(),
             thin_data_dev: self.data_segments.to_vec(),
             thin_meta_dev_spare: self.meta_spare_segments.to_vec(),
+            thin_meta_dev_spare_validated: self.meta_spare_validated,
         }
     }
 }
@@ -4035,6 +4702,199 @@ mod tests {
             );
         }
 
+        /// Set up thinpool and backstore. Set up two filesystems and reserve
+        /// all the unused space in the pool for one of them. Verify that a
+        /// larger reservation is rejected, that no new filesystem or snapshot
+        /// may be created while all free space is reserved, and that snapshots
+        /// do not inherit the reservation of their origin.
+        fn test_fs_reservation(paths: &[&Path]) {
+            let pool_name = "pool";
+            let pool_uuid = PoolUuid::new_v4();
+
+            let devices = get_devices(paths).unwrap();
+
+            let mut backstore = backstore::v2::Backstore::initialize(
+                pool_uuid,
+                devices,
+                MDADataSize::default(),
+                None,
+                ValidatedIntegritySpec::default(),
+            )
+            .unwrap();
+            let mut pool = ThinPool::<backstore::v2::Backstore>::new(
+                pool_uuid,
+                &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+                DATA_BLOCK_SIZE,
+                &mut backstore,
+            )
+            .unwrap();
+
+            let reserved_uuid = pool
+                .create_filesystem(
+                    pool_name,
+                    pool_uuid,
+                    "reserved",
+                    DEFAULT_THIN_DEV_SIZE,
+                    None,
+                )
+                .unwrap();
+            let other_uuid = pool
+                .create_filesystem(pool_name, pool_uuid, "other", DEFAULT_THIN_DEV_SIZE, None)
+                .unwrap();
+            pool.check(pool_uuid, &mut backstore).unwrap();
+            pool.check_reservations(&backstore, pool.new_fs_footprint(DEFAULT_THIN_DEV_SIZE))
+                .unwrap();
+
+            let available = pool.unreserved_space(&backstore, None).unwrap();
+            let reservation = {
+                let (_, fs) = pool.get_filesystem_by_uuid(reserved_uuid).unwrap();
+                let used = DEFAULT_THIN_DEV_SIZE - fs.unclaimed(DEFAULT_THIN_DEV_SIZE);
+                available + used
+            };
+
+            assert!(pool
+                .set_fs_reservation(&backstore, reserved_uuid, Some(reservation + Sectors(1)))
+                .is_err());
+            assert!(pool
+                .set_fs_reservation(&backstore, reserved_uuid, Some(reservation))
+                .unwrap());
+            assert!(!pool
+                .set_fs_reservation(&backstore, reserved_uuid, Some(reservation))
+                .unwrap());
+            assert_eq!(pool.unreserved_space(&backstore, None), Some(Sectors(0)));
+            assert!(pool
+                .check_reservations(&backstore, pool.snapshot_footprint())
+                .is_err());
+            assert!(pool
+                .set_fs_reservation(&backstore, other_uuid, Some(DEFAULT_THIN_DEV_SIZE))
+                .is_err());
+
+            pool.set_fs_reservation(&backstore, reserved_uuid, Some(DATA_BLOCK_SIZE))
+                .unwrap();
+            pool.check_reservations(&backstore, pool.snapshot_footprint())
+                .unwrap();
+            {
+                let (_, fs) = pool
+                    .snapshot_filesystem(pool_name, pool_uuid, reserved_uuid, "snapshot")
+                    .unwrap();
+                assert_eq!(fs.reservation(), None);
+            }
+
+            pool.set_fs_reservation(&backstore, reserved_uuid, None)
+                .unwrap();
+            assert_eq!(
+                pool.get_filesystem_by_uuid(reserved_uuid)
+                    .unwrap()
+                    .1
+                    .reservation(),
+                None
+            );
+        }
+
+        #[test]
+        fn loop_test_fs_reservation() {
+            loopbacked::test_with_spec(
+                &loopbacked::DeviceLimits::Range(1, 3, None),
+                test_fs_reservation,
+            );
+        }
+
+        #[test]
+        fn real_test_fs_reservation() {
+            real::test_with_spec(
+                &real::DeviceLimits::AtLeast(1, None, None),
+                test_fs_reservation,
+            );
+        }
+
+        /// Set up thinpool and backstore. Set up a filesystem and reserve
+        /// all but the expected footprint of a new filesystem for it. Verify
+        /// that the new filesystem is allowed if its footprint fits in the
+        /// unreserved space exactly, and refused if it is one sector larger
+        /// than the unreserved space.
+        fn test_fs_reservation_boundary(paths: &[&Path]) {
+            let pool_name = "pool";
+            let pool_uuid = PoolUuid::new_v4();
+
+            let devices = get_devices(paths).unwrap();
+
+            let mut backstore = backstore::v2::Backstore::initialize(
+                pool_uuid,
+                devices,
+                MDADataSize::default(),
+                None,
+                ValidatedIntegritySpec::default(),
+            )
+            .unwrap();
+            let mut pool = ThinPool::<backstore::v2::Backstore>::new(
+                pool_uuid,
+                &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+                DATA_BLOCK_SIZE,
+                &mut backstore,
+            )
+            .unwrap();
+
+            assert_eq!(pool.new_fs_footprint(Sectors(0)), DATA_BLOCK_SIZE);
+            assert_eq!(
+                pool.new_fs_footprint(Sectors(*DATA_BLOCK_SIZE * 2048 * 2 + 2048)),
+                Sectors(*DATA_BLOCK_SIZE * 3)
+            );
+            let footprint = pool.new_fs_footprint(Sectors(*DATA_BLOCK_SIZE * 2048 * 2));
+            assert_eq!(footprint, Sectors(*DATA_BLOCK_SIZE * 2));
+
+            let reserved_uuid = pool
+                .create_filesystem(
+                    pool_name,
+                    pool_uuid,
+                    "reserved",
+                    DEFAULT_THIN_DEV_SIZE,
+                    None,
+                )
+                .unwrap();
+            pool.check(pool_uuid, &mut backstore).unwrap();
+
+            let available = pool.unreserved_space(&backstore, None).unwrap();
+            let reservation = {
+                let (_, fs) = pool.get_filesystem_by_uuid(reserved_uuid).unwrap();
+                let used = DEFAULT_THIN_DEV_SIZE - fs.unclaimed(DEFAULT_THIN_DEV_SIZE);
+                available + used
+            };
+
+            assert!(pool
+                .set_fs_reservation(&backstore, reserved_uuid, Some(reservation - footprint))
+                .unwrap());
+            assert_eq!(pool.unreserved_space(&backstore, None), Some(footprint));
+            pool.check_reservations(&backstore, footprint).unwrap();
+            assert!(pool
+                .check_reservations(&backstore, footprint + Sectors(1))
+                .is_err());
+
+            assert!(pool
+                .set_fs_reservation(
+                    &backstore,
+                    reserved_uuid,
+                    Some(reservation - footprint + Sectors(1))
+                )
+                .unwrap());
+            assert!(pool.check_reservations(&backstore, footprint).is_err());
+        }
+
+        #[test]
+        fn loop_test_fs_reservation_boundary() {
+            loopbacked::test_with_spec(
+                &loopbacked::DeviceLimits::Range(1, 3, None),
+                test_fs_reservation_boundary,
+            );
+        }
+
+        #[test]
+        fn real_test_fs_reservation_boundary() {
+            real::test_with_spec(
+                &real::DeviceLimits::AtLeast(1, None, None),
+                test_fs_reservation_boundary,
+            );
+        }
+
         /// Verify that destroy_filesystems handles origin and merge
         /// scheduled properties correctly when destroying filesystems.
         fn test_thindev_with_origins(paths: &[&Path]) {
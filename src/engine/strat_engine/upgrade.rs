@@ -0,0 +1,1229 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Upgrade of stopped pools with legacy metadata, i.e., pools whose devices
+// have version 1 signature blocks, to the current metadata format.
+//
+// An unencrypted legacy pool has the same on-disk layout as a pool in the
+// current format, so only its metadata is rewritten, one device at a time. An
+// encrypted legacy pool has a LUKS2 device on each of its block devices, while
+// a pool in the current format has a single crypt device above its data tier.
+// Each device of the pool is decrypted in place, and then the whole data tier
+// is encrypted in place. Every step can be resumed, if it is interrupted, by
+// running the upgrade again.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    iter::once,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+
+use devicemapper::{
+    DmDevice, LinearDev, LinearDevTargetParams, LinearTargetParams, Sectors, TargetLine,
+};
+
+use crate::{
+    engine::{
+        strat_engine::{
+            backstore::{get_devno_from_path, integrity_meta_space, RangeAllocator},
+            crypt::{
+                crypt_metadata_size,
+                handle::{
+                    v1::{self, decrypt_in_place, unlock_secrets, UnlockSecrets},
+                    v2::{
+                        self, encrypt_in_place, encryption_in_progress,
+                        format_for_encryption_in_place,
+                    },
+                },
+                manual_wipe, DEFAULT_CRYPT_DATA_OFFSET_V2,
+            },
+            dm::get_dm,
+            metadata::{static_header, BlockdevSize, MetadataLocation, StaticHeader, BDA},
+            names::{format_backstore_ids, CacheRole},
+            serde_structs::{BaseDevSave, PoolFeatures, PoolSave},
+            writing::SyncAll,
+        },
+        types::{
+            DevUuid, PoolUuid, SizedKeyMemory, StratSigblockVersion, TokenUnlockMethod,
+            UpgradeReport, ValidatedIntegritySpec,
+        },
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// The directory in which the LUKS2 headers of legacy encrypted devices are
+/// kept while the devices are being converted.
+const UPGRADE_HEADER_DIR: &str = "/var/lib/stratisd/upgrade";
+
+/// The path of the copy of the LUKS2 header of a legacy encrypted device.
+fn header_path(dev_uuid: DevUuid) -> PathBuf {
+    Path::new(UPGRADE_HEADER_DIR).join(format!("{dev_uuid}.luks2"))
+}
+
+/// A device on which offsets are relative to the start of the legacy Stratis
+/// metadata, which, on a decrypted legacy encrypted device, follows the space
+/// which was occupied by the LUKS2 metadata.
+struct OffsetDevice {
+    file: File,
+    offset: u64,
+}
+
+impl OffsetDevice {
+    fn open(devnode: &Path, offset: Sectors) -> StratisResult<OffsetDevice> {
+        Ok(OffsetDevice {
+            file: OpenOptions::new().read(true).open(devnode)?,
+            offset: convert_int!(*offset.bytes(), u128, u64)?,
+        })
+    }
+}
+
+impl Read for OffsetDevice {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for OffsetDevice {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for OffsetDevice {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => SeekFrom::Start(offset + self.offset),
+            pos => pos,
+        };
+        self.file
+            .seek(pos)
+            .map(|offset| offset.saturating_sub(self.offset))
+    }
+}
+
+impl SyncAll for OffsetDevice {
+    fn sync_all(&mut self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+}
+
+/// The state of a device of a pool which is being upgraded.
+#[derive(Debug)]
+enum DeviceState {
+    /// An unencrypted legacy device.
+    Plain,
+    /// A legacy encrypted device which has not yet been decrypted.
+    Encrypted,
+    /// A legacy encrypted device whose decryption has been started, with the
+    /// path of the copy of its LUKS2 header.
+    Decrypting(PathBuf),
+    /// A formerly encrypted device which has the current metadata, but whose
+    /// data has not yet been encrypted again, with the path of the copy of
+    /// its LUKS2 header.
+    Converted(PathBuf),
+    /// A device with the current metadata.
+    Current,
+}
+
+impl DeviceState {
+    fn is_legacy(&self) -> bool {
+        !matches!(self, DeviceState::Current)
+    }
+
+    fn is_encrypted(&self) -> bool {
+        matches!(
+            self,
+            DeviceState::Encrypted | DeviceState::Decrypting(_) | DeviceState::Converted(_)
+        )
+    }
+
+    /// The offset of the legacy Stratis metadata on the device.
+    fn offset(&self) -> Sectors {
+        if self.is_encrypted() {
+            crypt_metadata_size().sectors()
+        } else {
+            Sectors(0)
+        }
+    }
+}
+
+/// A device of a pool which is being upgraded.
+struct UpgradeDevice {
+    devnode: PathBuf,
+    state: DeviceState,
+    bda: BDA,
+}
+
+/// Fail if the device is in use, e.g., because its pool is started.
+fn ensure_unused(devnode: &Path) -> StratisResult<()> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_EXCL)
+        .open(devnode)
+        .map(|_| ())
+        .map_err(|e| {
+            StratisError::Msg(format!(
+                "Device {} is in use; the pool must be stopped to be upgraded: {e}",
+                devnode.display()
+            ))
+        })
+}
+
+/// Identify a device and determine its state.
+fn identify(devnode: &Path) -> StratisResult<(PoolUuid, DevUuid, DeviceState)> {
+    ensure_unused(devnode)?;
+
+    if let Some(metadata) = v1::CryptHandle::load_metadata(devnode)? {
+        let ids = metadata.identifiers;
+        let path = header_path(ids.device_uuid);
+        let state = if path.exists() {
+            DeviceState::Decrypting(path)
+        } else {
+            DeviceState::Encrypted
+        };
+        return Ok((ids.pool_uuid, ids.device_uuid, state));
+    }
+
+    let header = static_header(&mut File::open(devnode)?)?.ok_or_else(|| {
+        StratisError::Msg(format!(
+            "Device {} is not a Stratis device",
+            devnode.display()
+        ))
+    })?;
+    let ids = header.identifiers;
+    let state = match header.sigblock_version {
+        StratSigblockVersion::V1 => DeviceState::Plain,
+        StratSigblockVersion::V2 => {
+            let path = header_path(ids.device_uuid);
+            if path.exists() {
+                DeviceState::Converted(path)
+            } else {
+                DeviceState::Current
+            }
+        }
+    };
+    Ok((ids.pool_uuid, ids.device_uuid, state))
+}
+
+/// Read the BDA and the pool-level metadata which it holds.
+fn read_metadata<F>(f: &mut F) -> StratisResult<(BDA, PoolSave)>
+where
+    F: Read + Seek + SyncAll,
+{
+    let header = static_header(f)?
+        .ok_or_else(|| StratisError::Msg("No Stratis static header found".to_string()))?;
+    let bda = BDA::load(header, f)?
+        .ok_or_else(|| StratisError::Msg("No Stratis metadata found".to_string()))?;
+    let data = bda
+        .load_state(f)?
+        .ok_or_else(|| StratisError::Msg("No pool-level metadata found".to_string()))?;
+    Ok((bda, serde_json::from_slice(&data)?))
+}
+
+/// Read the metadata of a legacy encrypted device which has not yet been
+/// decrypted by activating the device.
+fn read_encrypted_metadata(
+    devnode: &Path,
+    passphrase: Option<&SizedKeyMemory>,
+) -> StratisResult<(BDA, PoolSave)> {
+    let handle =
+        v1::CryptHandle::setup(devnode, TokenUnlockMethod::Any, passphrase)?.ok_or_else(|| {
+            StratisError::Msg(format!(
+                "Device {} is not a legacy encrypted Stratis device",
+                devnode.display()
+            ))
+        })?;
+    let res = File::open(handle.activated_device_path())
+        .map_err(StratisError::from)
+        .and_then(|mut f| read_metadata(&mut f));
+    if let Err(e) = handle.deactivate() {
+        warn!(
+            "Failed to deactivate encrypted device {}: {e}",
+            devnode.display()
+        );
+    }
+    res
+}
+
+/// The sizes of a legacy device which determine its layout after the upgrade.
+#[derive(Clone, Copy, Debug)]
+struct DeviceLayout {
+    /// The offset of the legacy Stratis metadata on the device.
+    offset: Sectors,
+    /// The size of the device as recorded in the legacy metadata.
+    size: Sectors,
+    /// The size of the Stratis metadata at the start of the device.
+    bda_size: Sectors,
+}
+
+/// The pool-level metadata and device sizes of a pool after the upgrade.
+#[derive(Debug)]
+struct UpgradePlan {
+    pool_save: PoolSave,
+    sizes: HashMap<DevUuid, Sectors>,
+    required: Sectors,
+    available: Sectors,
+    sufficient_space: bool,
+}
+
+/// Compute the pool-level metadata of a pool in the current format from the
+/// metadata of a legacy pool.
+///
+/// The current format reserves space for integrity metadata on each data
+/// device and, for encrypted pools, space for the LUKS2 metadata of the crypt
+/// device at the start of the data tier. Allocations on legacy encrypted
+/// devices are relative to the LUKS2 device, so they are moved by the size of
+/// the LUKS2 metadata. The legacy Stratis metadata, which remains in place
+/// until the upgrade is complete, is not allocated from.
+///
+/// Computing the plan from its own result changes nothing, so an interrupted
+/// upgrade is resumed with the same plan.
+fn plan_upgrade(
+    mut pool_save: PoolSave,
+    layouts: &HashMap<DevUuid, DeviceLayout>,
+    encrypted: bool,
+) -> StratisResult<UpgradePlan> {
+    let layout_of = |uuid: &DevUuid| {
+        layouts.get(uuid).copied().ok_or_else(|| {
+            StratisError::Msg(format!("No device with UUID {uuid} was found for the pool"))
+        })
+    };
+
+    if encrypted && pool_save.backstore.cache_tier.is_some() {
+        return Err(StratisError::Msg(
+            "Upgrading an encrypted pool with a cache is not supported; remove the cache first"
+                .to_string(),
+        ));
+    }
+
+    let integrity_spec = *pool_save
+        .backstore
+        .data_tier
+        .integrity_spec
+        .get_or_insert_with(ValidatedIntegritySpec::default);
+
+    let tiers = [
+        Some(&mut pool_save.backstore.data_tier.blockdev),
+        pool_save
+            .backstore
+            .cache_tier
+            .as_mut()
+            .map(|ct| &mut ct.blockdev),
+    ];
+    for blockdev in tiers.into_iter().flatten() {
+        for seg in blockdev.allocs.iter_mut().flatten() {
+            let offset = layout_of(&seg.parent)?.offset;
+            seg.start += offset;
+        }
+    }
+
+    let mut allocators = HashMap::new();
+    let mut sizes = HashMap::new();
+    let mut available = Sectors(0);
+    let data_devs = &pool_save.backstore.data_tier.blockdev;
+    let cache_devs = pool_save
+        .backstore
+        .cache_tier
+        .as_ref()
+        .map(|ct| &ct.blockdev);
+    for blockdev in once(data_devs).chain(cache_devs) {
+        for dev in blockdev.devs.iter() {
+            let layout = layout_of(&dev.uuid)?;
+            let size = layout.size + layout.offset;
+            let mut used = vec![(Sectors(0), layout.bda_size)];
+            if layout.offset != Sectors(0) {
+                used.push((layout.offset, layout.bda_size));
+            }
+            used.extend(
+                blockdev
+                    .allocs
+                    .iter()
+                    .flatten()
+                    .filter(|seg| seg.parent == dev.uuid)
+                    .map(|seg| (seg.start, seg.length)),
+            );
+            used.extend(dev.integrity_meta_allocs.iter().copied());
+            let allocator = RangeAllocator::new(BlockdevSize::new(size), &used)?;
+            available += allocator.available();
+            sizes.insert(dev.uuid, size);
+            allocators.insert(dev.uuid, allocator);
+        }
+    }
+
+    let mut required = Sectors(0);
+    let mut sufficient_space = true;
+    for dev in pool_save.backstore.data_tier.blockdev.devs.iter_mut() {
+        if !dev.integrity_meta_allocs.is_empty() {
+            continue;
+        }
+        let amount = integrity_meta_space(sizes[&dev.uuid], integrity_spec);
+        let allocator = allocators
+            .get_mut(&dev.uuid)
+            .expect("allocator exists for every device");
+        let segs = allocator.alloc_back(amount);
+        sufficient_space &= segs.sum() == amount;
+        required += amount;
+        dev.integrity_meta_allocs
+            .extend(segs.iter().map(|(start, len)| (*start, *len)));
+    }
+
+    if encrypted && pool_save.backstore.cap.crypt_meta_allocs.is_empty() {
+        let mut needed = DEFAULT_CRYPT_DATA_OFFSET_V2;
+        let mut crypt_meta_segs = Vec::new();
+        for dev in pool_save.backstore.data_tier.blockdev.devs.iter() {
+            let allocator = allocators
+                .get_mut(&dev.uuid)
+                .expect("allocator exists for every device");
+            let segs = allocator.alloc_front(needed.min(allocator.available()));
+            for (start, len) in segs.iter() {
+                crypt_meta_segs.push(BaseDevSave {
+                    parent: dev.uuid,
+                    start: *start,
+                    length: *len,
+                });
+            }
+            needed -= segs.sum();
+        }
+        sufficient_space &= needed == Sectors(0);
+        required += DEFAULT_CRYPT_DATA_OFFSET_V2;
+
+        let allocs = &mut pool_save.backstore.data_tier.blockdev.allocs;
+        if allocs.is_empty() {
+            allocs.push(Vec::new());
+        }
+        allocs[0].splice(0..0, crypt_meta_segs);
+        pool_save.backstore.cap.crypt_meta_allocs =
+            vec![(Sectors(0), DEFAULT_CRYPT_DATA_OFFSET_V2)];
+    }
+
+    if encrypted && !pool_save.features.contains(&PoolFeatures::Encryption) {
+        pool_save.features.push(PoolFeatures::Encryption);
+    }
+
+    Ok(UpgradePlan {
+        pool_save,
+        sizes,
+        required,
+        available,
+        sufficient_space,
+    })
+}
+
+/// The plan of an encrypted pool whose upgrade was interrupted after some of
+/// its devices were given the current metadata, which holds the metadata of
+/// the upgraded pool. Nothing remains to be allocated.
+fn finished_plan(pool_save: PoolSave, layouts: &HashMap<DevUuid, DeviceLayout>) -> UpgradePlan {
+    UpgradePlan {
+        pool_save,
+        sizes: layouts
+            .iter()
+            .map(|(uuid, layout)| (*uuid, layout.size + layout.offset))
+            .collect(),
+        required: Sectors(0),
+        available: Sectors(0),
+        sufficient_space: true,
+    }
+}
+
+/// Check that the devices are exactly the devices of the pool.
+fn check_devices(pool_save: &PoolSave, found: &HashSet<DevUuid>) -> StratisResult<()> {
+    let expected = pool_save
+        .backstore
+        .data_tier
+        .blockdev
+        .devs
+        .iter()
+        .chain(
+            pool_save
+                .backstore
+                .cache_tier
+                .iter()
+                .flat_map(|ct| ct.blockdev.devs.iter()),
+        )
+        .map(|dev| dev.uuid)
+        .collect::<HashSet<_>>();
+
+    let missing = expected.difference(found).collect::<Vec<_>>();
+    let unexpected = found.difference(&expected).collect::<Vec<_>>();
+    if !missing.is_empty() || !unexpected.is_empty() {
+        return Err(StratisError::Msg(format!(
+            "The devices specified are not the devices of the pool; missing: {missing:?}, \
+            not in the pool: {unexpected:?}"
+        )));
+    }
+    Ok(())
+}
+
+/// Set up a linear device over the data tier as described by the metadata,
+/// which becomes the device beneath the crypt device of the pool.
+fn setup_placeholder(
+    pool_uuid: PoolUuid,
+    pool_save: &PoolSave,
+    devices: &[UpgradeDevice],
+) -> StratisResult<LinearDev> {
+    let devnos = devices
+        .iter()
+        .map(|dev| Ok((dev.bda.dev_uuid(), get_devno_from_path(&dev.devnode)?)))
+        .collect::<StratisResult<HashMap<_, _>>>()?;
+
+    let mut table = Vec::new();
+    let mut logical_start = Sectors(0);
+    for seg in pool_save
+        .backstore
+        .data_tier
+        .blockdev
+        .allocs
+        .iter()
+        .flatten()
+    {
+        let device = devnos.get(&seg.parent).ok_or_else(|| {
+            StratisError::Msg(format!(
+                "No device with UUID {} was found for the pool",
+                seg.parent
+            ))
+        })?;
+        table.push(TargetLine::new(
+            logical_start,
+            seg.length,
+            LinearDevTargetParams::Linear(LinearTargetParams::new(*device, seg.start)),
+        ));
+        logical_start += seg.length;
+    }
+
+    let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::Cache);
+    Ok(LinearDev::setup(get_dm(), &dm_name, Some(&dm_uuid), table)?)
+}
+
+/// Rewrite the metadata of an unencrypted device in the current format. The
+/// pool-level metadata must already have been saved to the device.
+fn write_current_header(dev: &UpgradeDevice, size: Sectors) -> StratisResult<()> {
+    let mut f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&dev.devnode)?;
+    StaticHeader::write_header(
+        &mut f,
+        StaticHeader::new(
+            StratSigblockVersion::V2,
+            dev.bda.identifiers(),
+            dev.bda.max_data_size(),
+            BlockdevSize::new(size),
+            dev.bda.initialization_time(),
+        ),
+        MetadataLocation::Both,
+    )?;
+    Ok(())
+}
+
+/// Write new metadata in the current format at the start of a decrypted
+/// device, overwriting its legacy LUKS2 metadata.
+fn write_current_bda(
+    dev: &UpgradeDevice,
+    size: Sectors,
+    time: &DateTime<Utc>,
+    metadata: &[u8],
+) -> StratisResult<()> {
+    manual_wipe(&dev.devnode, Sectors(0), dev.bda.extended_size().sectors())?;
+    let mut f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&dev.devnode)?;
+    let mut bda = BDA::new(
+        StratSigblockVersion::V2,
+        dev.bda.identifiers(),
+        dev.bda.max_data_size(),
+        BlockdevSize::new(size),
+        *time,
+    );
+    bda.initialize(&mut f)?;
+    bda.save_state(time, metadata, &mut f)
+}
+
+/// Upgrade an unencrypted pool by rewriting its metadata.
+///
+/// Each device is upgraded in turn: its pool-level metadata is saved before
+/// its static header is rewritten, so that a device which has the current
+/// header always has the current metadata. If the upgrade is interrupted,
+/// running it again upgrades the devices which still have the legacy header.
+fn upgrade_unencrypted(devices: &[UpgradeDevice], plan: &UpgradePlan) -> StratisResult<()> {
+    let time = Utc::now();
+    let data = serde_json::to_string(&plan.pool_save)?;
+    for dev in devices
+        .iter()
+        .filter(|dev| matches!(dev.state, DeviceState::Plain))
+    {
+        let mut f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&dev.devnode)?;
+        let mut bda = BDA::load(
+            static_header(&mut f)?.ok_or_else(|| {
+                StratisError::Msg(format!(
+                    "No Stratis static header found on device {}",
+                    dev.devnode.display()
+                ))
+            })?,
+            &mut f,
+        )?
+        .ok_or_else(|| {
+            StratisError::Msg(format!(
+                "No Stratis metadata found on device {}",
+                dev.devnode.display()
+            ))
+        })?;
+        bda.save_state(&time, data.as_bytes(), &mut f)?;
+        write_current_header(dev, plan.sizes[&dev.bda.dev_uuid()])?;
+    }
+    Ok(())
+}
+
+/// Upgrade an encrypted pool by decrypting each device and then encrypting the
+/// data tier as a whole.
+fn upgrade_encrypted(
+    pool_uuid: PoolUuid,
+    devices: &mut [UpgradeDevice],
+    plan: &UpgradePlan,
+    secrets: Option<&UnlockSecrets>,
+) -> StratisResult<()> {
+    for dev in devices.iter_mut() {
+        if let DeviceState::Encrypted = dev.state {
+            let secrets = secrets.expect("read from the LUKS2 metadata of encrypted devices");
+            let path = header_path(dev.bda.dev_uuid());
+            fs::create_dir_all(UPGRADE_HEADER_DIR)?;
+            decrypt_in_place(&dev.devnode, &path, secrets.passphrase())?;
+            dev.state = DeviceState::Decrypting(path);
+        }
+    }
+
+    let mut placeholder = setup_placeholder(pool_uuid, &plan.pool_save, devices)?;
+    let res = (|| {
+        let path = placeholder.devnode();
+        if v2::setup_crypt_device(&path)?.is_none() {
+            let secrets = secrets.ok_or_else(|| {
+                StratisError::Msg(
+                    "The unlock mechanisms of the pool could not be read from any device"
+                        .to_string(),
+                )
+            })?;
+            format_for_encryption_in_place(&path, secrets)?;
+        }
+
+        let time = Utc::now();
+        let data = serde_json::to_string(&plan.pool_save)?;
+        for dev in devices.iter() {
+            if let DeviceState::Decrypting(_) = dev.state {
+                write_current_bda(dev, plan.sizes[&dev.bda.dev_uuid()], &time, data.as_bytes())?;
+            }
+        }
+
+        if encryption_in_progress(&path)? {
+            encrypt_in_place(&path, secrets.map(|s| s.passphrase()))?;
+        }
+        Ok(())
+    })();
+    if let Err(e) = placeholder.teardown(get_dm()) {
+        warn!("Failed to tear down device used for the upgrade: {e}");
+    }
+    res?;
+
+    for dev in devices.iter() {
+        if let DeviceState::Decrypting(ref path) | DeviceState::Converted(ref path) = dev.state {
+            if let Err(e) = fs::remove_file(path) {
+                warn!(
+                    "Failed to remove copy of LUKS2 header {}: {e}",
+                    path.display()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A stopped pool whose devices have been identified and whose upgrade has
+/// been planned.
+struct PreparedUpgrade {
+    pool_uuid: PoolUuid,
+    encrypted: bool,
+    devices: Vec<UpgradeDevice>,
+    plan: UpgradePlan,
+    secrets: Option<UnlockSecrets>,
+}
+
+/// Identify the devices of a stopped pool, read their metadata, and plan the
+/// upgrade of the pool, resuming an interrupted upgrade.
+fn prepare_upgrade(
+    devnodes: &[PathBuf],
+    passphrase: Option<&SizedKeyMemory>,
+    dry_run: bool,
+) -> StratisResult<PreparedUpgrade> {
+    let mut identified = Vec::new();
+    for devnode in devnodes {
+        identified.push((devnode.to_owned(), identify(devnode)?));
+    }
+
+    let pool_uuid = match identified.first() {
+        Some((_, (pool_uuid, _, _))) => *pool_uuid,
+        None => {
+            return Err(StratisError::Msg(
+                "No devices were specified for the upgrade".to_string(),
+            ))
+        }
+    };
+    if let Some((devnode, (other, _, _))) = identified
+        .iter()
+        .find(|(_, (uuid, _, _))| *uuid != pool_uuid)
+    {
+        return Err(StratisError::Msg(format!(
+            "Device {} belongs to pool {other}, not to pool {pool_uuid}",
+            devnode.display()
+        )));
+    }
+
+    if !identified
+        .iter()
+        .any(|(_, (_, _, state))| state.is_legacy())
+    {
+        return Err(StratisError::Msg(format!(
+            "Pool {pool_uuid} already uses the current metadata format"
+        )));
+    }
+    let encrypted = identified
+        .iter()
+        .any(|(_, (_, _, state))| state.is_encrypted());
+    if encrypted
+        && identified
+            .iter()
+            .any(|(_, (_, _, state))| matches!(state, DeviceState::Plain))
+    {
+        return Err(StratisError::Msg(format!(
+            "Pool {pool_uuid} has both encrypted and unencrypted devices"
+        )));
+    }
+    // The copy of the LUKS2 header of a device is removed only once the data
+    // tier has been encrypted, so an encrypted pool which has devices with
+    // the current metadata was interrupted while the copies were removed.
+    let finishing = encrypted
+        && identified
+            .iter()
+            .any(|(_, (_, _, state))| matches!(state, DeviceState::Current));
+    if finishing
+        && identified.iter().any(|(_, (_, _, state))| {
+            matches!(state, DeviceState::Encrypted | DeviceState::Decrypting(_))
+        })
+    {
+        return Err(StratisError::Msg(format!(
+            "Pool {pool_uuid} has devices which have been upgraded and devices which have \
+            not been decrypted; the copies of the LUKS2 headers in {UPGRADE_HEADER_DIR} may \
+            be missing"
+        )));
+    }
+    let in_progress = identified.iter().any(|(_, (_, _, state))| {
+        matches!(
+            state,
+            DeviceState::Decrypting(_) | DeviceState::Converted(_) | DeviceState::Current
+        )
+    });
+    if dry_run && in_progress {
+        return Err(StratisError::Msg(format!(
+            "An upgrade of pool {pool_uuid} has already been started; run the upgrade again \
+            to complete it"
+        )));
+    }
+
+    let secrets = if encrypted && !dry_run {
+        identified
+            .iter()
+            .find(|(_, (_, _, state))| {
+                matches!(state, DeviceState::Encrypted | DeviceState::Decrypting(_))
+            })
+            .map(|(devnode, _)| unlock_secrets(devnode, passphrase))
+            .transpose()?
+    } else {
+        None
+    };
+
+    let mut devices = Vec::new();
+    let mut saves = Vec::new();
+    for (devnode, (_, _, state)) in identified {
+        let (bda, pool_save) = match state {
+            DeviceState::Plain | DeviceState::Current => {
+                read_metadata(&mut OffsetDevice::open(&devnode, Sectors(0))?)?
+            }
+            DeviceState::Encrypted => read_encrypted_metadata(&devnode, passphrase)?,
+            DeviceState::Decrypting(ref path) => {
+                let secrets = secrets
+                    .as_ref()
+                    .expect("read from the LUKS2 metadata of decrypting devices");
+                decrypt_in_place(&devnode, path, secrets.passphrase())?;
+                read_metadata(&mut OffsetDevice::open(&devnode, state.offset())?)?
+            }
+            DeviceState::Converted(_) => {
+                read_metadata(&mut OffsetDevice::open(&devnode, state.offset())?)?
+            }
+        };
+        saves.push((bda.last_update_time().copied(), pool_save));
+        devices.push(UpgradeDevice {
+            devnode,
+            state,
+            bda,
+        });
+    }
+    // The devices with the current metadata, if any, hold the metadata of
+    // the upgraded pool; the others hold the legacy metadata.
+    let pool_save = saves
+        .into_iter()
+        .zip(devices.iter())
+        .filter(|(_, dev)| !finishing || matches!(dev.state, DeviceState::Current))
+        .max_by_key(|((time, _), _)| *time)
+        .map(|((_, pool_save), _)| pool_save)
+        .expect("at least one device");
+
+    check_devices(
+        &pool_save,
+        &devices.iter().map(|dev| dev.bda.dev_uuid()).collect(),
+    )?;
+
+    let layouts = devices
+        .iter()
+        .map(|dev| {
+            (
+                dev.bda.dev_uuid(),
+                DeviceLayout {
+                    offset: dev.state.offset(),
+                    size: dev.bda.dev_size().sectors(),
+                    bda_size: dev.bda.extended_size().sectors(),
+                },
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    let plan = if finishing {
+        finished_plan(pool_save, &layouts)
+    } else {
+        plan_upgrade(pool_save, &layouts, encrypted)?
+    };
+
+    Ok(PreparedUpgrade {
+        pool_uuid,
+        encrypted,
+        devices,
+        plan,
+        secrets,
+    })
+}
+
+/// Upgrade a stopped pool with legacy metadata, given the device nodes of all
+/// of its devices, to the current metadata format, or, if dry_run is true,
+/// only report whether the upgrade is possible.
+///
+/// The passphrase, if specified, is used instead of the key in the kernel
+/// keyring to unlock the devices of an encrypted pool.
+///
+/// If the upgrade is interrupted, it must be completed by running it again.
+/// An unencrypted pool whose upgrade was interrupted has devices with the
+/// legacy and devices with the current metadata. An encrypted pool is left
+/// unusable while it is being upgraded; the copies of the LUKS2 headers kept
+/// in /var/lib/stratisd/upgrade are needed to complete its upgrade.
+pub fn upgrade_pool(
+    devnodes: &[PathBuf],
+    passphrase: Option<&SizedKeyMemory>,
+    dry_run: bool,
+) -> StratisResult<UpgradeReport> {
+    let PreparedUpgrade {
+        pool_uuid,
+        encrypted,
+        mut devices,
+        plan,
+        secrets,
+    } = prepare_upgrade(devnodes, passphrase, dry_run)?;
+
+    let report = UpgradeReport {
+        pool_uuid,
+        encrypted,
+        required: plan.required,
+        available: plan.available,
+        sufficient_space: plan.sufficient_space,
+        upgraded: false,
+    };
+    if dry_run {
+        return Ok(report);
+    }
+    if !plan.sufficient_space {
+        return Err(StratisError::Msg(format!(
+            "Pool {pool_uuid} does not have enough free space for the upgrade: {} required, \
+            {} available",
+            plan.required.bytes(),
+            plan.available.bytes()
+        )));
+    }
+
+    if encrypted {
+        upgrade_encrypted(pool_uuid, &mut devices, &plan, secrets.as_ref())?;
+    } else {
+        upgrade_unencrypted(&devices, &plan)?;
+    }
+
+    Ok(UpgradeReport {
+        upgraded: true,
+        ..report
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::mount::{mount, umount, MsFlags};
+    use serde_json::json;
+
+    use crate::engine::{
+        strat_engine::{
+            pool::AnyPool,
+            tests::{crypt, loopbacked, real},
+        },
+        types::{FilesystemUuid, InputEncryptionInfo, KeyDescription, PoolIdentifier},
+        unshare_mount_namespace, Engine, Pool, StratEngine,
+    };
+
+    use super::*;
+
+    const FILE_NAME: &str = "stratis_test.txt";
+    const FILE_CONTENTS: &[u8] = b"some bytes";
+
+    /// A legacy pool with two data devices with allocations in the middle of
+    /// each device.
+    fn legacy_pool(dev1: DevUuid, dev2: DevUuid) -> PoolSave {
+        serde_json::from_value(json!({
+            "name": "pool",
+            "backstore": {
+                "data_tier": {
+                    "blockdev": {
+                        "allocs": [[
+                            {"parent": dev1, "start": 8192, "length": 1_000_000},
+                            {"parent": dev2, "start": 8192, "length": 500_000},
+                        ]],
+                        "devs": [{"uuid": dev1}, {"uuid": dev2}],
+                    },
+                },
+                "cap": {"allocs": [[0, 1_500_000]]},
+            },
+            "flex_devs": {
+                "meta_dev": [],
+                "thin_meta_dev": [],
+                "thin_data_dev": [],
+                "thin_meta_dev_spare": [],
+            },
+            "thinpool_dev": {"data_block_size": 2048},
+        }))
+        .expect("valid pool metadata")
+    }
+
+    fn layouts(
+        dev1: DevUuid,
+        dev2: DevUuid,
+        offset: Sectors,
+        size: Sectors,
+    ) -> HashMap<DevUuid, DeviceLayout> {
+        [dev1, dev2]
+            .into_iter()
+            .map(|uuid| {
+                (
+                    uuid,
+                    DeviceLayout {
+                        offset,
+                        size,
+                        bda_size: Sectors(8192),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    /// Verify that upgrading an unencrypted pool reserves integrity metadata
+    /// space without moving any allocations, and that planning the upgrade
+    /// again changes nothing.
+    fn test_plan_unencrypted() {
+        let (dev1, dev2) = (DevUuid::new_v4(), DevUuid::new_v4());
+        let layouts = layouts(dev1, dev2, Sectors(0), Sectors(4_000_000));
+        let plan = plan_upgrade(legacy_pool(dev1, dev2), &layouts, false).unwrap();
+
+        assert!(plan.sufficient_space);
+        assert!(plan.pool_save.backstore.data_tier.integrity_spec.is_some());
+        assert!(plan.pool_save.backstore.cap.crypt_meta_allocs.is_empty());
+        assert!(!plan.pool_save.features.contains(&PoolFeatures::Encryption));
+        let allocs = &plan.pool_save.backstore.data_tier.blockdev.allocs[0];
+        assert_eq!(allocs.len(), 2);
+        assert!(allocs.iter().all(|seg| seg.start == Sectors(8192)));
+        for dev in plan.pool_save.backstore.data_tier.blockdev.devs.iter() {
+            assert!(!dev.integrity_meta_allocs.is_empty());
+            assert!(dev
+                .integrity_meta_allocs
+                .iter()
+                .all(|(start, len)| *start + *len <= Sectors(4_000_000)));
+        }
+        assert_eq!(plan.sizes[&dev1], Sectors(4_000_000));
+
+        let again = plan_upgrade(plan.pool_save, &layouts, false).unwrap();
+        assert_eq!(again.required, Sectors(0));
+        assert!(again.sufficient_space);
+    }
+
+    #[test]
+    /// Verify that upgrading an encrypted pool moves allocations past the
+    /// legacy LUKS2 metadata, places the crypt metadata at the start of the
+    /// data tier, and leaves the legacy Stratis metadata unallocated.
+    fn test_plan_encrypted() {
+        let (dev1, dev2) = (DevUuid::new_v4(), DevUuid::new_v4());
+        let offset = crypt_metadata_size().sectors();
+        let layouts = layouts(dev1, dev2, offset, Sectors(4_000_000));
+        let plan = plan_upgrade(legacy_pool(dev1, dev2), &layouts, true).unwrap();
+
+        assert!(plan.sufficient_space);
+        assert!(plan.pool_save.features.contains(&PoolFeatures::Encryption));
+        assert_eq!(
+            plan.pool_save.backstore.cap.crypt_meta_allocs,
+            vec![(Sectors(0), DEFAULT_CRYPT_DATA_OFFSET_V2)]
+        );
+        assert_eq!(plan.sizes[&dev1], Sectors(4_000_000) + offset);
+
+        // The crypt metadata precedes the data; neither overlaps the new or
+        // the legacy Stratis metadata.
+        let allocs = &plan.pool_save.backstore.data_tier.blockdev.allocs[0];
+        let (crypt_meta, data) = allocs.split_at(allocs.len() - 2);
+        assert_eq!(
+            crypt_meta
+                .iter()
+                .fold(Sectors(0), |acc, seg| acc + seg.length),
+            DEFAULT_CRYPT_DATA_OFFSET_V2
+        );
+        assert!(crypt_meta.iter().all(|seg| seg.start >= Sectors(8192)
+            && (seg.start + seg.length <= offset || seg.start >= offset + Sectors(8192))));
+        assert!(data.iter().all(|seg| seg.start == offset + Sectors(8192)));
+
+        let again = plan_upgrade(plan.pool_save, &layouts, true).unwrap();
+        assert_eq!(again.required, Sectors(0));
+    }
+
+    #[test]
+    /// Verify that the plan reports insufficient space when a device is
+    /// fully allocated.
+    fn test_plan_insufficient_space() {
+        let (dev1, dev2) = (DevUuid::new_v4(), DevUuid::new_v4());
+        let layouts = layouts(dev1, dev2, Sectors(0), Sectors(1_008_192));
+        let plan = plan_upgrade(legacy_pool(dev1, dev2), &layouts, false).unwrap();
+        assert!(!plan.sufficient_space);
+        assert!(plan.required > Sectors(0));
+    }
+
+    #[test]
+    /// Verify that encrypted pools with a cache are refused.
+    fn test_plan_encrypted_cache() {
+        let (dev1, dev2) = (DevUuid::new_v4(), DevUuid::new_v4());
+        let mut pool_save = legacy_pool(dev1, dev2);
+        pool_save.backstore.cache_tier =
+            Some(serde_json::from_value(json!({"blockdev": {"allocs": [], "devs": []}})).unwrap());
+        let layouts = layouts(
+            dev1,
+            dev2,
+            crypt_metadata_size().sectors(),
+            Sectors(4_000_000),
+        );
+        assert!(plan_upgrade(pool_save, &layouts, true).is_err());
+    }
+
+    /// Mount the filesystem at devnode while f runs on the mount point.
+    fn with_mounted<F>(devnode: &Path, f: F)
+    where
+        F: FnOnce(&Path),
+    {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        mount(
+            Some(devnode),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None as Option<&str>,
+        )
+        .unwrap();
+        f(tmp_dir.path());
+        umount(tmp_dir.path()).unwrap();
+    }
+
+    /// Create a legacy pool with a filesystem which holds a file, and stop
+    /// the pool.
+    fn create_legacy_pool(
+        paths: &[&Path],
+        encryption_info: Option<&InputEncryptionInfo>,
+    ) -> (PoolUuid, FilesystemUuid) {
+        unshare_mount_namespace().unwrap();
+        let engine = StratEngine::initialize().unwrap();
+        let pool_uuid = test_async!(engine.create_pool_legacy("pool", paths, encryption_info))
+            .unwrap()
+            .changed()
+            .unwrap();
+        let fs_uuid = {
+            let mut guard =
+                test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(pool_uuid))).unwrap();
+            let (pool_name, _, pool) = guard.as_mut_tuple();
+            let (_, fs_uuid, _) = pool
+                .create_filesystems(&pool_name, pool_uuid, &[("fs", None, None)])
+                .unwrap()
+                .changed()
+                .unwrap()
+                .pop()
+                .unwrap();
+            let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
+            with_mounted(&fs.devnode(), |dir| {
+                fs::write(dir.join(FILE_NAME), FILE_CONTENTS).unwrap()
+            });
+            fs_uuid
+        };
+        test_async!(engine.stop_pool(PoolIdentifier::Uuid(pool_uuid), true)).unwrap();
+        engine.teardown().unwrap();
+        (pool_uuid, fs_uuid)
+    }
+
+    /// Start the upgraded pool and check that it uses the current metadata
+    /// format and that the file written before the upgrade can be read.
+    fn check_upgraded(
+        pool_uuid: PoolUuid,
+        fs_uuid: FilesystemUuid,
+        unlock_method: TokenUnlockMethod,
+    ) {
+        let engine = StratEngine::initialize().unwrap();
+        test_async!(engine.start_pool(PoolIdentifier::Uuid(pool_uuid), unlock_method, None))
+            .unwrap();
+        {
+            let guard = test_async!(engine.get_pool(PoolIdentifier::Uuid(pool_uuid))).unwrap();
+            let (_, _, pool) = guard.as_tuple();
+            assert_matches!(pool, AnyPool::V2(_));
+            let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
+            with_mounted(&fs.devnode(), |dir| {
+                assert_eq!(fs::read(dir.join(FILE_NAME)).unwrap(), FILE_CONTENTS)
+            });
+        }
+        engine.teardown().unwrap();
+    }
+
+    /// Verify that an upgrade of an unencrypted pool which was interrupted
+    /// after some of its devices were upgraded is completed by running it
+    /// again, and that the upgraded pool can be started.
+    fn test_upgrade_unencrypted(paths: &[&Path]) {
+        let (pool_uuid, fs_uuid) = create_legacy_pool(paths, None);
+        let devnodes = paths.iter().map(|p| p.to_path_buf()).collect::<Vec<_>>();
+
+        let report = upgrade_pool(&devnodes, None, true).unwrap();
+        assert!(report.sufficient_space);
+        assert!(!report.upgraded);
+
+        let prepared = prepare_upgrade(&devnodes, None, false).unwrap();
+        upgrade_unencrypted(&prepared.devices[..1], &prepared.plan).unwrap();
+        assert_matches!(identify(&devnodes[0]).unwrap().2, DeviceState::Current);
+        assert_matches!(identify(&devnodes[1]).unwrap().2, DeviceState::Plain);
+
+        let report = upgrade_pool(&devnodes, None, false).unwrap();
+        assert!(report.upgraded);
+        assert!(!report.encrypted);
+        for devnode in devnodes.iter() {
+            assert_matches!(identify(devnode).unwrap().2, DeviceState::Current);
+        }
+
+        check_upgraded(pool_uuid, fs_uuid, TokenUnlockMethod::None);
+    }
+
+    #[test]
+    fn loop_test_upgrade_unencrypted() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(2, None),
+            test_upgrade_unencrypted,
+        );
+    }
+
+    #[test]
+    fn real_test_upgrade_unencrypted() {
+        real::test_with_spec(
+            &real::DeviceLimits::Exactly(2, None, None),
+            test_upgrade_unencrypted,
+        );
+    }
+
+    /// Verify that an upgrade of an encrypted pool which was interrupted
+    /// after one of its devices was decrypted, and one which was interrupted
+    /// while the copies of the LUKS2 headers were removed, are completed by
+    /// running the upgrade again, and that the data on the pool survives the
+    /// decryption and encryption of its devices.
+    fn test_upgrade_encrypted(paths: &[&Path]) {
+        fn test(paths: &[&Path], key_desc: &KeyDescription) {
+            let (pool_uuid, fs_uuid) = create_legacy_pool(
+                paths,
+                InputEncryptionInfo::new_legacy(Some(key_desc.clone()), None).as_ref(),
+            );
+            let devnodes = paths.iter().map(|p| p.to_path_buf()).collect::<Vec<_>>();
+
+            let prepared = prepare_upgrade(&devnodes, None, false).unwrap();
+            let first = &prepared.devices[0];
+            let path = header_path(first.bda.dev_uuid());
+            fs::create_dir_all(UPGRADE_HEADER_DIR).unwrap();
+            decrypt_in_place(
+                &first.devnode,
+                &path,
+                prepared.secrets.as_ref().unwrap().passphrase(),
+            )
+            .unwrap();
+            assert_matches!(
+                identify(&devnodes[0]).unwrap().2,
+                DeviceState::Decrypting(_)
+            );
+            assert_matches!(identify(&devnodes[1]).unwrap().2, DeviceState::Encrypted);
+            let saved = tempfile::NamedTempFile::new().unwrap();
+            fs::copy(&path, saved.path()).unwrap();
+
+            let report = upgrade_pool(&devnodes, None, false).unwrap();
+            assert!(report.upgraded);
+            assert!(report.encrypted);
+            assert!(!path.exists());
+
+            fs::copy(saved.path(), &path).unwrap();
+            assert_matches!(identify(&devnodes[0]).unwrap().2, DeviceState::Converted(_));
+            assert_matches!(identify(&devnodes[1]).unwrap().2, DeviceState::Current);
+            assert!(upgrade_pool(&devnodes, None, true).is_err());
+            assert!(upgrade_pool(&devnodes, None, false).unwrap().upgraded);
+            assert!(!path.exists());
+            for devnode in devnodes.iter() {
+                assert_matches!(identify(devnode).unwrap().2, DeviceState::Current);
+            }
+
+            check_upgraded(pool_uuid, fs_uuid, TokenUnlockMethod::Any);
+        }
+
+        crypt::insert_and_cleanup_key(paths, test)
+    }
+
+    #[test]
+    fn loop_test_upgrade_encrypted() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(2, None),
+            test_upgrade_encrypted,
+        );
+    }
+
+    #[test]
+    fn real_test_upgrade_encrypted() {
+        real::test_with_spec(
+            &real::DeviceLimits::Exactly(2, None, None),
+            test_upgrade_encrypted,
+        );
+    }
+}
@@ -315,6 +315,11 @@ impl<T> SetCreateAction<T> {
     pub fn empty() -> Self {
         SetCreateAction { changed: vec![] }
     }
+
+    /// The things created, without consuming the action.
+    pub fn created(&self) -> &[T] {
+        &self.changed
+    }
 }
 
 impl<T> EngineAction for SetCreateAction<T> {
@@ -636,6 +641,19 @@ impl Display for SetDeleteAction<FilesystemUuid, (FilesystemUuid, Option<Filesys
 /// Action indicating a Clevis binding regeneration
 pub struct RegenAction;
 
+impl EngineAction for RegenAction {
+    type Return = ();
+
+    /// A regeneration always rewrites the bindings.
+    fn is_changed(&self) -> bool {
+        true
+    }
+
+    fn changed(self) -> Option<()> {
+        Some(())
+    }
+}
+
 impl Display for RegenAction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -0,0 +1,97 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt::{self, Display};
+
+use devicemapper::Sectors;
+
+use crate::engine::types::{DevUuid, FilesystemUuid, KeyDescription, Name};
+
+/// A single change to a pool which may be applied as part of a batch.
+///
+/// Filesystem destruction is deliberately absent: it can not be undone, so
+/// a batch containing it could not be rolled back.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    /// Create a filesystem; sizes are in bytes.
+    CreateFilesystem {
+        name: String,
+        size: Option<u64>,
+        size_limit: Option<u64>,
+    },
+    RenameFilesystem {
+        name: String,
+        new_name: String,
+    },
+    /// Set or clear the size limit of a filesystem; the limit is in bytes.
+    SetFsSizeLimit {
+        name: String,
+        limit: Option<u64>,
+    },
+    SetFsLimit {
+        limit: u64,
+    },
+    SetOverprovMode {
+        enabled: bool,
+    },
+    SetBlockdevUserInfo {
+        uuid: DevUuid,
+        user_info: Option<String>,
+    },
+    /// Bind the pool to a key description; if token_slot is None, any
+    /// free token slot is used.
+    BindKeyring {
+        token_slot: Option<u32>,
+        key_desc: KeyDescription,
+    },
+}
+
+/// A change which was made by applying a batch. Operations which turned out
+/// to require no change are not reported.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BatchChange {
+    FilesystemCreated(Name, FilesystemUuid),
+    FilesystemRenamed(FilesystemUuid, Name),
+    FsSizeLimitChanged(FilesystemUuid, Option<Sectors>),
+    FsLimitChanged(u64),
+    OverprovModeChanged(bool),
+    BlockdevUserInfoChanged(DevUuid, Option<String>),
+    KeyringBound(u32, KeyDescription),
+}
+
+impl Display for BatchChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchChange::FilesystemCreated(name, uuid) => {
+                write!(f, "created filesystem {name} with UUID {uuid}")
+            }
+            BatchChange::FilesystemRenamed(uuid, name) => {
+                write!(f, "renamed filesystem with UUID {uuid} to {name}")
+            }
+            BatchChange::FsSizeLimitChanged(uuid, Some(limit)) => write!(
+                f,
+                "set size limit of filesystem with UUID {uuid} to {}",
+                limit.bytes()
+            ),
+            BatchChange::FsSizeLimitChanged(uuid, None) => {
+                write!(f, "removed size limit of filesystem with UUID {uuid}")
+            }
+            BatchChange::FsLimitChanged(limit) => write!(f, "set filesystem limit to {limit}"),
+            BatchChange::OverprovModeChanged(enabled) => write!(
+                f,
+                "{} overprovisioning",
+                if *enabled { "enabled" } else { "disabled" }
+            ),
+            BatchChange::BlockdevUserInfoChanged(uuid, _) => {
+                write!(f, "set user info of blockdev with UUID {uuid}")
+            }
+            BatchChange::KeyringBound(token_slot, key_desc) => write!(
+                f,
+                "bound token slot {token_slot} to key description {}",
+                key_desc.as_application_str()
+            ),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt::{self, Display};
+
+use devicemapper::Sectors;
+
+/// The result of compacting the allocations of a pool's flex devices.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompactionReport {
+    /// The number of segments of the flex devices before compaction.
+    pub segments_before: usize,
+    /// The number of segments of the flex devices after compaction.
+    pub segments_after: usize,
+    /// The amount of data copied from one place to another.
+    pub moved: Sectors,
+    /// The amount of space added to the thin data device.
+    pub data_extension: Sectors,
+}
+
+impl Display for CompactionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} segments reduced to {}, {} moved",
+            self.segments_before,
+            self.segments_after,
+            self.moved.bytes(),
+        )?;
+        if self.data_extension > Sectors(0) {
+            write!(
+                f,
+                ", data device extended by {}",
+                self.data_extension.bytes()
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@
 
 use std::ops::{Deref, DerefMut};
 
-use devicemapper::{Bytes, Sectors};
+use devicemapper::{Bytes, CacheDevWorkingStatus, Sectors};
 
 /// This interface defines a generic way to compare whether two values of
 /// the same type have changed or remained the same.
@@ -73,12 +73,56 @@ impl<T> DerefMut for Diff<T> {
     }
 }
 
+/// Usage of the data and metadata devices of the thin pool as last reported
+/// by devicemapper.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ThinPoolUsageInfo {
+    pub data_used: Bytes,
+    pub data_total: Bytes,
+    pub meta_used: Bytes,
+    pub meta_total: Bytes,
+}
+
+/// Statistics for the cache device of a pool as last reported by
+/// devicemapper.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CacheStats {
+    pub used: Bytes,
+    pub total: Bytes,
+    pub read_hits: u64,
+    pub read_misses: u64,
+    pub write_hits: u64,
+    pub write_misses: u64,
+    pub demotions: u64,
+    pub promotions: u64,
+    pub dirty: u64,
+}
+
+impl From<&CacheDevWorkingStatus> for CacheStats {
+    fn from(status: &CacheDevWorkingStatus) -> Self {
+        let usage = &status.usage;
+        let perf = &status.performance;
+        CacheStats {
+            used: (*usage.used_cache * usage.cache_block_size).bytes(),
+            total: (*usage.total_cache * usage.cache_block_size).bytes(),
+            read_hits: perf.read_hits,
+            read_misses: perf.read_misses,
+            write_hits: perf.write_hits,
+            write_misses: perf.write_misses,
+            demotions: perf.demotions,
+            promotions: perf.promotions,
+            dirty: perf.dirty,
+        }
+    }
+}
+
 /// Change in attributes of the thin pool that may need to be reported to the
 /// IPC layer.
 #[derive(Debug)]
 pub struct ThinPoolDiff {
     pub allocated_size: Diff<Bytes>,
     pub used: Diff<Option<Bytes>>,
+    pub usage: Diff<Option<ThinPoolUsageInfo>>,
 }
 
 /// Change in attributes of a Stratis pool that may need to be reported to the
@@ -87,6 +131,7 @@ pub struct ThinPoolDiff {
 pub struct StratPoolDiff {
     pub metadata_size: Diff<Bytes>,
     pub out_of_alloc_space: Diff<bool>,
+    pub cache: Diff<Option<CacheStats>>,
 }
 
 /// Represents the difference between two dumped states for a filesystem.
@@ -0,0 +1,331 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt::Write;
+
+use strum::IntoEnumIterator;
+use strum_macros::{self, EnumIter};
+
+use devicemapper::Sectors;
+
+use crate::engine::types::{BlockDevTier, DevUuid};
+
+/// What a range of sectors on a block device holds.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, EnumIter, strum_macros::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum LayoutUse {
+    /// The signature blocks and the metadata area of the block device.
+    StratisMetadata,
+    IntegrityMetadata,
+    /// The LUKS2 metadata of an encrypted pool, or the space reserved for it
+    /// in an unencrypted pool.
+    CryptMetadata,
+    /// The metadata volume, which holds the filesystem metadata.
+    Mdv,
+    ThinMeta,
+    ThinMetaSpare,
+    ThinData,
+    CacheMetadata,
+    CacheData,
+    /// Allocated to the pool but not yet used by any of its components.
+    PoolUnused,
+    /// Not allocated to the pool.
+    Unused,
+}
+
+impl LayoutUse {
+    /// The character which represents this use in the ASCII rendering.
+    fn symbol(self) -> char {
+        match self {
+            LayoutUse::StratisMetadata => 'S',
+            LayoutUse::IntegrityMetadata => 'I',
+            LayoutUse::CryptMetadata => 'C',
+            LayoutUse::Mdv => 'M',
+            LayoutUse::ThinMeta => 't',
+            LayoutUse::ThinMetaSpare => 's',
+            LayoutUse::ThinData => 'D',
+            LayoutUse::CacheMetadata => 'm',
+            LayoutUse::CacheData => 'c',
+            LayoutUse::PoolUnused => '+',
+            LayoutUse::Unused => '.',
+        }
+    }
+
+    /// The color which represents this use in the SVG rendering.
+    fn color(self) -> &'static str {
+        match self {
+            LayoutUse::StratisMetadata => "#d62728",
+            LayoutUse::IntegrityMetadata => "#9467bd",
+            LayoutUse::CryptMetadata => "#8c564b",
+            LayoutUse::Mdv => "#e377c2",
+            LayoutUse::ThinMeta => "#ff7f0e",
+            LayoutUse::ThinMetaSpare => "#ffbb78",
+            LayoutUse::ThinData => "#1f77b4",
+            LayoutUse::CacheMetadata => "#2ca02c",
+            LayoutUse::CacheData => "#98df8a",
+            LayoutUse::PoolUnused => "#c7c7c7",
+            LayoutUse::Unused => "#ffffff",
+        }
+    }
+}
+
+/// A range of sectors on a block device and what it holds.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct LayoutExtent {
+    pub start: Sectors,
+    pub length: Sectors,
+    pub usage: LayoutUse,
+}
+
+/// The layout of a single block device of a pool.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct DeviceLayout {
+    pub uuid: DevUuid,
+    pub tier: BlockDevTier,
+    /// The size of the device, if known. If not known, the layout ends with
+    /// the last extent allocated on the device.
+    pub size: Option<Sectors>,
+    /// The extents of the device, in order, covering the device from its
+    /// first sector without gaps.
+    pub extents: Vec<LayoutExtent>,
+}
+
+impl DeviceLayout {
+    /// The number of sectors the layout describes.
+    pub fn length(&self) -> Sectors {
+        self.extents
+            .last()
+            .map(|extent| extent.start + extent.length)
+            .unwrap_or_default()
+    }
+
+    /// The total number of sectors with the given use.
+    pub fn total(&self, usage: LayoutUse) -> Sectors {
+        self.extents
+            .iter()
+            .filter(|extent| extent.usage == usage)
+            .map(|extent| extent.length)
+            .sum()
+    }
+
+    /// The use which covers most of the sectors from start to end.
+    fn dominant_use(&self, start: Sectors, end: Sectors) -> LayoutUse {
+        let mut covered: Vec<(LayoutUse, Sectors)> = Vec::new();
+        for extent in self.extents.iter() {
+            let overlap_start = extent.start.max(start);
+            let overlap_end = (extent.start + extent.length).min(end);
+            if overlap_start < overlap_end {
+                match covered.iter_mut().find(|(usage, _)| *usage == extent.usage) {
+                    Some((_, total)) => *total += overlap_end - overlap_start,
+                    None => covered.push((extent.usage, overlap_end - overlap_start)),
+                }
+            }
+        }
+        covered
+            .into_iter()
+            .max_by_key(|(_, total)| *total)
+            .map(|(usage, _)| usage)
+            .unwrap_or(LayoutUse::Unused)
+    }
+}
+
+/// Which sectors of every block device of a pool hold what.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct PoolLayout {
+    pub name: String,
+    pub encrypted: bool,
+    pub devices: Vec<DeviceLayout>,
+}
+
+// The width in pixels of the bar which represents a device in the SVG
+// rendering, and the height of each row.
+const SVG_BAR_WIDTH: u64 = 1000;
+const SVG_ROW_HEIGHT: u64 = 40;
+const SVG_LABEL_HEIGHT: u64 = 14;
+
+impl PoolLayout {
+    /// Render the layout as text: one bar of width characters for each
+    /// device, in which each character shows the use of most of the sectors
+    /// it stands for, followed by the total for each use and a legend. All
+    /// bars are drawn to the same scale, that of the largest device.
+    pub fn to_ascii(&self, width: u64) -> String {
+        let scale = self
+            .devices
+            .iter()
+            .map(DeviceLayout::length)
+            .max()
+            .unwrap_or_default();
+        let width = width.max(1);
+
+        let mut result = format!(
+            "Pool {}{}\n",
+            self.name,
+            if self.encrypted { " (encrypted)" } else { "" }
+        );
+        for device in self.devices.iter() {
+            let _ = writeln!(
+                result,
+                "\n{:?} device {}, {}",
+                device.tier,
+                device.uuid,
+                device.size.map_or_else(
+                    || format!("at least {}", device.length()),
+                    |size| size.to_string()
+                )
+            );
+            let length = device.length();
+            let bar = (0..width)
+                .map(|column| {
+                    let start = Sectors(*scale * column / width);
+                    let end = Sectors(*scale * (column + 1) / width);
+                    if start >= length {
+                        ' '
+                    } else {
+                        device.dominant_use(start, end).symbol()
+                    }
+                })
+                .collect::<String>();
+            let _ = writeln!(result, "|{}|", bar.trim_end());
+            for usage in LayoutUse::iter() {
+                let total = device.total(usage);
+                if total != Sectors(0) {
+                    let _ = writeln!(result, "  {} {usage}: {total}", usage.symbol());
+                }
+            }
+        }
+
+        let _ = writeln!(
+            result,
+            "\nLegend: {}",
+            LayoutUse::iter()
+                .map(|usage| format!("{} {usage}", usage.symbol()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        result
+    }
+
+    /// Render the layout as an SVG image: one bar for each device, all drawn
+    /// to the scale of the largest device, in which each extent is a
+    /// rectangle colored by its use and titled with its use, start and
+    /// length. Extents too small to be seen at this scale are drawn one
+    /// pixel wide.
+    pub fn to_svg(&self) -> String {
+        let scale = self
+            .devices
+            .iter()
+            .map(DeviceLayout::length)
+            .max()
+            .unwrap_or_default()
+            .max(Sectors(1));
+        let uses = LayoutUse::iter().collect::<Vec<_>>();
+        let devices = self.devices.len() as u64;
+        let height = SVG_ROW_HEIGHT * (devices + 1) + SVG_LABEL_HEIGHT * (uses.len() as u64);
+
+        let mut result = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{height}\" font-family=\"monospace\" font-size=\"12\">\n",
+            SVG_BAR_WIDTH + 20,
+        );
+        let _ = writeln!(
+            result,
+            "<text x=\"10\" y=\"{SVG_LABEL_HEIGHT}\">Pool {}{}</text>",
+            xml_escape(&self.name),
+            if self.encrypted { " (encrypted)" } else { "" }
+        );
+        for (row, device) in (0u64..).zip(self.devices.iter()) {
+            let y = SVG_ROW_HEIGHT * row + 2 * SVG_LABEL_HEIGHT;
+            let _ = writeln!(
+                result,
+                "<text x=\"10\" y=\"{y}\">{:?} device {}</text>",
+                device.tier, device.uuid
+            );
+            for extent in device.extents.iter() {
+                let x = *extent.start * SVG_BAR_WIDTH / *scale;
+                let width = (*extent.length * SVG_BAR_WIDTH / *scale).max(1);
+                let _ = writeln!(
+                    result,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{width}\" height=\"{}\" fill=\"{}\" stroke=\"#000000\" stroke-width=\"0.2\"><title>{}: {} + {}</title></rect>",
+                    x + 10,
+                    y + 4,
+                    SVG_ROW_HEIGHT - SVG_LABEL_HEIGHT - 8,
+                    extent.usage.color(),
+                    extent.usage,
+                    extent.start,
+                    extent.length,
+                );
+            }
+        }
+        let legend_y = SVG_ROW_HEIGHT * devices + 2 * SVG_LABEL_HEIGHT;
+        for (index, usage) in (0u64..).zip(uses.iter()) {
+            let y = legend_y + SVG_LABEL_HEIGHT * index;
+            let _ = writeln!(
+                result,
+                "<rect x=\"10\" y=\"{}\" width=\"10\" height=\"10\" fill=\"{}\" stroke=\"#000000\" stroke-width=\"0.5\"/><text x=\"26\" y=\"{y}\">{usage}</text>",
+                y - 10,
+                usage.color(),
+            );
+        }
+        result.push_str("</svg>\n");
+        result
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout() -> PoolLayout {
+        PoolLayout {
+            name: "pool".to_string(),
+            encrypted: false,
+            devices: vec![DeviceLayout {
+                uuid: DevUuid::new_v4(),
+                tier: BlockDevTier::Data,
+                size: Some(Sectors(400)),
+                extents: vec![
+                    LayoutExtent {
+                        start: Sectors(0),
+                        length: Sectors(100),
+                        usage: LayoutUse::StratisMetadata,
+                    },
+                    LayoutExtent {
+                        start: Sectors(100),
+                        length: Sectors(200),
+                        usage: LayoutUse::ThinData,
+                    },
+                    LayoutExtent {
+                        start: Sectors(300),
+                        length: Sectors(100),
+                        usage: LayoutUse::Unused,
+                    },
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    /// Verify that each character of the bar shows the use of most of the
+    /// sectors it stands for.
+    fn test_to_ascii() {
+        let ascii = layout().to_ascii(4);
+        assert!(ascii.contains("|SDD.|"));
+        assert!(ascii.contains("D thin_data: 200 sectors"));
+    }
+
+    #[test]
+    /// Verify that every extent is drawn.
+    fn test_to_svg() {
+        let svg = layout().to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<rect").count(), 3 + 11);
+    }
+}
@@ -32,15 +32,20 @@ pub use crate::{
                 RenameAction, SetCreateAction, SetDeleteAction, SetUnlockAction, StartAction,
                 StopAction, ToDisplay,
             },
+            batch::{BatchChange, BatchOperation},
+            compaction::CompactionReport,
             diff::{
-                Compare, Diff, PoolDiff, StratBlockDevDiff, StratFilesystemDiff, StratPoolDiff,
-                ThinPoolDiff,
+                CacheStats, Compare, Diff, PoolDiff, StratBlockDevDiff, StratFilesystemDiff,
+                StratPoolDiff, ThinPoolDiff, ThinPoolUsageInfo,
             },
             keys::{
                 EncryptionInfo, InputEncryptionInfo, KeyDescription, OptionalTokenSlotInput,
                 PoolEncryptionInfo, SizedKeyMemory, TokenUnlockMethod, UnlockMechanism,
                 UnlockMethod,
             },
+            layout::{DeviceLayout, LayoutExtent, LayoutUse, PoolLayout},
+            spare::{ThinMetaSpareAction, ThinMetaSpareInfo},
+            upgrade::UpgradeReport,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -51,8 +56,13 @@ pub const DEFAULT_INTEGRITY_BLOCK_SIZE: Bytes = Bytes(4 * IEC::Ki as u128);
 pub const DEFAULT_INTEGRITY_TAG_SPEC: IntegrityTagSpec = IntegrityTagSpec::B512;
 
 mod actions;
+mod batch;
+mod compaction;
 mod diff;
 mod keys;
+mod layout;
+mod spare;
+mod upgrade;
 
 macro_rules! uuid {
     ($vis:vis $ident:ident) => {
@@ -145,7 +155,8 @@ impl Display for StratisUuid {
 
 /// Blockdev tier. Used to distinguish between blockdevs used for
 /// data and blockdevs used for a cache.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BlockDevTier {
     Data = 0,
     Cache = 1,
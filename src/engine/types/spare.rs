@@ -0,0 +1,70 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt::{self, Display};
+
+use chrono::{DateTime, Utc};
+use strum_macros::{self, EnumString, VariantNames};
+
+use devicemapper::Sectors;
+
+use crate::engine::types::PoolUuid;
+
+/// An operation on the spare thin metadata device of a stopped pool.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Serialize,
+    EnumString,
+    VariantNames,
+    strum_macros::Display,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum ThinMetaSpareAction {
+    /// Report the state of the spare without changing it.
+    Report,
+    /// Repair the thin pool metadata into the spare and check the result, so
+    /// that the spare is known to be usable for a repair.
+    Validate,
+    /// Repair the thin pool metadata into the spare, which then becomes the
+    /// thin metadata device. The previous thin metadata device becomes the
+    /// spare.
+    Swap,
+    /// Zero the spare, first extending it to the size of the thin metadata
+    /// device if it is smaller.
+    Reprovision,
+}
+
+/// The state of the spare thin metadata device of a pool.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ThinMetaSpareInfo {
+    pub pool_uuid: PoolUuid,
+    /// The size of the spare thin metadata device.
+    pub spare_size: Sectors,
+    /// The size of the thin metadata device.
+    pub meta_size: Sectors,
+    /// The time at which the thin pool metadata was last repaired into the
+    /// spare successfully, if the spare has not changed since.
+    pub last_validated: Option<DateTime<Utc>>,
+}
+
+impl Display for ThinMetaSpareInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Pool {}: spare thin metadata device of {}, thin metadata device of {}, ",
+            self.pool_uuid,
+            self.spare_size.bytes(),
+            self.meta_size.bytes(),
+        )?;
+        match self.last_validated {
+            Some(time) => write!(f, "last validated at {time}"),
+            None => write!(f, "not validated"),
+        }
+    }
+}
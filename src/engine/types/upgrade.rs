@@ -0,0 +1,47 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt::{self, Display};
+
+use devicemapper::Sectors;
+
+use crate::engine::types::PoolUuid;
+
+/// The result of upgrading a pool with legacy metadata to the current
+/// metadata format, or of checking whether the upgrade is possible.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UpgradeReport {
+    pub pool_uuid: PoolUuid,
+    /// Whether the pool is encrypted, in which case the per-device encryption
+    /// is replaced by encryption of the whole pool.
+    pub encrypted: bool,
+    /// The space which must be free on the pool's devices for the upgrade.
+    pub required: Sectors,
+    /// The space which is free on the pool's devices.
+    pub available: Sectors,
+    /// Whether the free space is sufficient for the upgrade.
+    pub sufficient_space: bool,
+    /// Whether the pool was changed; false for a dry run.
+    pub upgraded: bool,
+}
+
+impl Display for UpgradeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Pool {}: {}encrypted, requires {} of free space, {} available",
+            self.pool_uuid,
+            if self.encrypted { "" } else { "not " },
+            self.required.bytes(),
+            self.available.bytes(),
+        )?;
+        if self.upgraded {
+            write!(f, "; upgraded")
+        } else if self.sufficient_space {
+            write!(f, "; can be upgraded")
+        } else {
+            write!(f, "; can not be upgraded")
+        }
+    }
+}
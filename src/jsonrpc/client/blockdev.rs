@@ -0,0 +1,35 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::{engine::DevUuid, jsonrpc::client::utils::to_suffix_repr, stratis::StratisResult};
+
+// stratis-min blockdev [list]
+pub fn blockdev_list() -> StratisResult<()> {
+    let (pool_names, paths, tiers, sizes, user_infos, uuids) = do_request!(BlockdevList);
+    let devices_formatted: Vec<_> = paths.into_iter().map(|p| p.display().to_string()).collect();
+    let sizes_formatted: Vec<_> = sizes.into_iter().map(to_suffix_repr).collect();
+    let user_infos_formatted: Vec<_> = user_infos
+        .into_iter()
+        .map(|u| u.unwrap_or_default())
+        .collect();
+    let uuids_formatted: Vec<_> = uuids.into_iter().map(|u| u.to_string()).collect();
+    print_table!(
+        "Pool Name", pool_names, "<";
+        "Device Node", devices_formatted, "<";
+        "Physical Size", sizes_formatted, ">";
+        "Tier", tiers, ">";
+        "User Info", user_infos_formatted, "<";
+        "UUID", uuids_formatted, "<"
+    );
+    Ok(())
+}
+
+// stratis-min blockdev set-user-info
+pub fn blockdev_set_user_info(
+    pool_name: String,
+    dev_uuid: DevUuid,
+    user_info: Option<String>,
+) -> StratisResult<()> {
+    do_request_standard!(BlockdevSetUserInfo, pool_name, dev_uuid, user_info)
+}
@@ -15,7 +15,10 @@ use nix::sys::socket::{sendmsg, ControlMessage, MsgFlags, UnixAddr};
 use serde::{de::DeserializeOwned, Serialize};
 
 use crate::{
-    jsonrpc::interface::{IpcResult, StratisParams, StratisRet},
+    jsonrpc::{
+        interface::{IpcResult, StratisParams, StratisRet},
+        protocol::{VersionedRequest, VersionedResponse},
+    },
     stratis::StratisResult,
 };
 
@@ -61,4 +64,12 @@ impl StratisClient {
     pub fn request(&mut self, params: StratisParams) -> StratisResult<IpcResult<StratisRet>> {
         send_request(&mut self.0, &params.type_, params.fd_opt)
     }
+
+    pub fn request_versioned(
+        &mut self,
+        request: &VersionedRequest,
+        fd_opt: Option<RawFd>,
+    ) -> StratisResult<VersionedResponse> {
+        send_request(&mut self.0, request, fd_opt)
+    }
 }
@@ -0,0 +1,48 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::path::PathBuf;
+
+use crate::{
+    engine::{PoolExport, PoolsConfig},
+    stratis::{StratisError, StratisResult},
+};
+
+// stratis-min apply
+pub fn config_apply(config: PoolsConfig, dry_run: bool) -> StratisResult<()> {
+    let (actions, rc, rs) = do_request!(ApplyConfiguration, config, dry_run);
+    if rc != 0 {
+        return Err(StratisError::Msg(rs));
+    }
+    if actions.is_empty() {
+        println!("The pools already match the configuration");
+    } else {
+        println!(
+            "{}",
+            if dry_run {
+                "Actions which would be performed:"
+            } else {
+                "Actions performed:"
+            }
+        );
+        for action in actions {
+            println!("  {action}");
+        }
+    }
+    Ok(())
+}
+
+// stratis-min pool export-config
+pub fn pool_export_config(name: String) -> StratisResult<PoolExport> {
+    let (export, rc, rs) = do_request!(PoolExportConfig, name);
+    match export {
+        Some(export) if rc == 0 => Ok(export),
+        _ => Err(StratisError::Msg(rs)),
+    }
+}
+
+// stratis-min pool import-config
+pub fn pool_import_config(export: PoolExport, blockdevs: Vec<PathBuf>) -> StratisResult<()> {
+    do_request_standard!(PoolImportConfig, export, blockdevs)
+}
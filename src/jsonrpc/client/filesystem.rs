@@ -59,3 +59,113 @@ pub fn filesystem_origin(pool_name: String, filesystem_name: String) -> StratisR
         Ok(origin.unwrap_or_else(|| "None".to_string()))
     }
 }
+
+// stratis-min filesystem info
+pub fn filesystem_info(pool_name: String, filesystem_name: String) -> StratisResult<()> {
+    let (info, rc, rs) = do_request!(FsInfo, pool_name, filesystem_name);
+    if rc != 0 {
+        return Err(StratisError::Msg(rs));
+    }
+    let (size, used, size_limit, reservation, origin, merge_scheduled, created, devnode, mount) =
+        info.ok_or_else(|| StratisError::Msg("No filesystem information returned".to_string()))?;
+    println!("Size: {}", to_suffix_repr(size));
+    println!(
+        "Used: {}",
+        used.map(to_suffix_repr)
+            .unwrap_or_else(|| "FAILURE".to_string())
+    );
+    println!(
+        "Size Limit: {}",
+        size_limit
+            .map(to_suffix_repr)
+            .unwrap_or_else(|| "None".to_string())
+    );
+    println!(
+        "Reservation: {}",
+        reservation
+            .map(to_suffix_repr)
+            .unwrap_or_else(|| "None".to_string())
+    );
+    println!(
+        "Origin: {}",
+        origin
+            .map(|u| u.to_string())
+            .unwrap_or_else(|| "None".to_string())
+    );
+    println!(
+        "Revert Scheduled: {}",
+        if merge_scheduled { "Yes" } else { "No" }
+    );
+    println!("Created: {created}");
+    println!("Device: {}", devnode.display());
+    println!("Mount Path: {}", mount.display());
+    Ok(())
+}
+
+// stratis-min filesystem snapshot
+pub fn filesystem_snapshot(
+    pool_name: String,
+    origin_name: String,
+    snapshot_name: String,
+) -> StratisResult<()> {
+    do_request_standard!(FsSnapshot, pool_name, origin_name, snapshot_name)
+}
+
+// stratis-min filesystem clone
+pub fn filesystem_clone(
+    pool_name: String,
+    filesystem_name: String,
+    target_pool_name: String,
+    new_name: String,
+    preserve_uuid: bool,
+) -> StratisResult<()> {
+    do_request_standard!(
+        FsClone,
+        pool_name,
+        filesystem_name,
+        target_pool_name,
+        new_name,
+        preserve_uuid
+    )
+}
+
+// stratis-min filesystem set-size-limit, filesystem unset-size-limit
+pub fn filesystem_set_size_limit(
+    pool_name: String,
+    filesystem_name: String,
+    limit: Option<u128>,
+) -> StratisResult<()> {
+    do_request_standard!(FsSetSizeLimit, pool_name, filesystem_name, limit)
+}
+
+// stratis-min filesystem set-reservation, filesystem unset-reservation
+pub fn filesystem_set_reservation(
+    pool_name: String,
+    filesystem_name: String,
+    reservation: Option<u128>,
+) -> StratisResult<()> {
+    do_request_standard!(FsSetReservation, pool_name, filesystem_name, reservation)
+}
+
+// stratis-min filesystem schedule-revert, filesystem cancel-revert
+pub fn filesystem_set_merge_scheduled(
+    pool_name: String,
+    filesystem_name: String,
+    scheduled: bool,
+) -> StratisResult<()> {
+    do_request_standard!(FsSetMergeScheduled, pool_name, filesystem_name, scheduled)
+}
+
+// stratis-min filesystem metadata
+pub fn filesystem_metadata(
+    pool_name: String,
+    filesystem_name: Option<String>,
+    current: bool,
+) -> StratisResult<String> {
+    let (metadata, rc, rs) = do_request!(FsMetadata, pool_name, filesystem_name, current);
+    if rc != 0 {
+        Err(StratisError::Msg(rs))
+    } else {
+        Ok(metadata)
+    }
+}
@@ -0,0 +1,62 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::{
+    engine::{JobId, JobInfo},
+    print_table,
+    stratis::{StratisError, StratisResult},
+};
+
+/// Report the ID of a job started by a request.
+pub fn job_started((id, rc, rs): (JobId, u16, String)) -> StratisResult<()> {
+    if rc != 0 {
+        Err(StratisError::Msg(rs))
+    } else {
+        println!("Started job {id}");
+        Ok(())
+    }
+}
+
+// stratis-min job [list]
+pub fn job_list() -> StratisResult<()> {
+    let jobs: Vec<JobInfo> = do_request!(JobList);
+    let ids = jobs.iter().map(|j| j.id.to_string()).collect::<Vec<_>>();
+    let descriptions = jobs
+        .iter()
+        .map(|j| j.description.clone())
+        .collect::<Vec<_>>();
+    let states = jobs.iter().map(|j| j.state.to_string()).collect::<Vec<_>>();
+    let progress = jobs
+        .iter()
+        .map(|j| format!("{:.0}%", j.progress * 100.0))
+        .collect::<Vec<_>>();
+    print_table!(
+        "ID", ids, ">";
+        "Description", descriptions, "<";
+        "State", states, "<";
+        "Progress", progress, ">"
+    );
+    Ok(())
+}
+
+// stratis-min job status
+pub fn job_status(id: JobId) -> StratisResult<()> {
+    let (info, rc, rs) = do_request!(JobStatus, id);
+    if rc != 0 {
+        return Err(StratisError::Msg(rs));
+    }
+    let info = info.ok_or_else(|| StratisError::Msg(format!("No job with ID {id} exists")))?;
+    println!("Description: {}", info.description);
+    println!("State: {}", info.state);
+    println!("Progress: {:.0}%", info.progress * 100.0);
+    if let Some(error) = info.error {
+        println!("Error: {error}");
+    }
+    Ok(())
+}
+
+// stratis-min job cancel
+pub fn job_cancel(id: JobId) -> StratisResult<()> {
+    do_request_standard!(JobCancel, id)
+}
@@ -5,11 +5,15 @@
 #[macro_use]
 pub mod utils;
 
+pub mod blockdev;
 #[allow(clippy::module_inception)]
 mod client;
+pub mod config;
 pub mod filesystem;
+pub mod job;
 pub mod key;
 pub mod pool;
+pub mod protocol;
 pub mod report;
 
 pub use self::client::StratisClient;
@@ -2,7 +2,14 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::{os::fd::AsRawFd, path::PathBuf};
+use std::{
+    os::fd::AsRawFd,
+    path::PathBuf,
+    process::Command,
+    str,
+    thread::sleep,
+    time::{Duration, Instant},
+};
 
 use nix::unistd::{pipe, write};
 
@@ -10,10 +17,13 @@ use serde_json::Value;
 
 use crate::{
     engine::{
-        InputEncryptionInfo, KeyDescription, OptionalTokenSlotInput, PoolIdentifier, PoolUuid,
-        TokenUnlockMethod,
+        BatchOperation, DevUuid, InputEncryptionInfo, KeyDescription, OptionalTokenSlotInput,
+        PoolIdentifier, PoolUuid, ThinMetaSpareAction, TokenUnlockMethod,
+    },
+    jsonrpc::client::{
+        job::job_started,
+        utils::{prompt_password, to_suffix_repr},
     },
-    jsonrpc::client::utils::{prompt_password, to_suffix_repr},
     print_table,
     stratis::{StratisError, StratisResult},
 };
@@ -23,8 +33,13 @@ pub fn pool_create(
     name: String,
     blockdevs: Vec<PathBuf>,
     enc_info: Option<InputEncryptionInfo>,
+    background: bool,
 ) -> StratisResult<()> {
-    do_request_standard!(PoolCreate, name, blockdevs, enc_info)
+    if background {
+        job_started(do_request!(PoolCreateJob, name, blockdevs, enc_info))
+    } else {
+        do_request_standard!(PoolCreate, name, blockdevs, enc_info)
+    }
 }
 
 // stratis-min pool start
@@ -36,25 +51,128 @@ pub fn pool_start(
     if prompt {
         let password = prompt_password()?
             .ok_or_else(|| StratisError::Msg("Password provided was empty".to_string()))?;
-
-        let (read_end, write_end) = pipe()?;
-        write(write_end, password.as_bytes())?;
-        do_request_standard!(PoolStart, id, unlock_method; {
-            read_end.as_raw_fd()
-        })
+        pool_start_with_passphrase(id, unlock_method, &password)
     } else {
         do_request_standard!(PoolStart, id, unlock_method)
     }
 }
 
+fn pool_start_with_passphrase(
+    id: PoolIdentifier<PoolUuid>,
+    unlock_method: TokenUnlockMethod,
+    password: &str,
+) -> StratisResult<()> {
+    let (read_end, write_end) = pipe()?;
+    write(write_end, password.as_bytes())?;
+    do_request_standard!(PoolStart, id, unlock_method; {
+        read_end.as_raw_fd()
+    })
+}
+
+/// How stratis-min pool setup unlocks an encrypted pool.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SetupUnlockMethod {
+    /// Use a token if the pool is bound, falling back to a passphrase.
+    Auto,
+    /// Use any token.
+    Token,
+    /// Ask for a passphrase through systemd-ask-password.
+    Passphrase,
+    /// The pool is not encrypted.
+    None,
+}
+
+/// Obtain a passphrase through the systemd password agents, which work
+/// during boot when no terminal is attached.
+fn ask_password(id: &PoolIdentifier<PoolUuid>) -> StratisResult<String> {
+    let output = Command::new("systemd-ask-password")
+        .arg(format!("Enter passphrase for Stratis pool with {id}"))
+        .output()?;
+    if !output.status.success() {
+        return Err(StratisError::Msg(format!(
+            "systemd-ask-password failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    let password = str::from_utf8(&output.stdout)?.trim_end_matches('\n');
+    if password.is_empty() {
+        Err(StratisError::Msg("Password provided was empty".to_string()))
+    } else {
+        Ok(password.to_string())
+    }
+}
+
+// stratis-min pool setup
+pub fn pool_setup(
+    id: PoolIdentifier<PoolUuid>,
+    timeout: Duration,
+    unlock: SetupUnlockMethod,
+) -> StratisResult<()> {
+    // The devices of a pool may appear some time after stratisd-min starts,
+    // so wait until the pool is known before trying to start it.
+    let deadline = Instant::now() + timeout;
+    let stopped = loop {
+        match pool_is_stopped(id.clone()) {
+            Ok(stopped) => break stopped,
+            Err(e) if Instant::now() >= deadline => {
+                return Err(StratisError::Chained(
+                    format!(
+                        "Pool with {id} did not appear within {} seconds",
+                        timeout.as_secs()
+                    ),
+                    Box::new(e),
+                ));
+            }
+            Err(_) => {
+                println!("Waiting on pool with {id}...");
+                sleep(Duration::from_secs(1));
+            }
+        }
+    };
+    if !stopped {
+        return Ok(());
+    }
+
+    match unlock {
+        SetupUnlockMethod::None => pool_start(id, TokenUnlockMethod::None, false),
+        SetupUnlockMethod::Token => pool_start(id, TokenUnlockMethod::Any, false),
+        SetupUnlockMethod::Passphrase => {
+            let password = ask_password(&id)?;
+            pool_start_with_passphrase(id, TokenUnlockMethod::Any, &password)
+        }
+        SetupUnlockMethod::Auto => {
+            if pool_is_bound(id.clone())? {
+                match pool_start(id.clone(), TokenUnlockMethod::Any, false) {
+                    Ok(()) => return Ok(()),
+                    Err(e) => eprintln!("Failed to start pool with {id} using a token: {e}"),
+                }
+            }
+            if pool_has_passphrase(id.clone())? {
+                let password = ask_password(&id)?;
+                return pool_start_with_passphrase(id, TokenUnlockMethod::Any, &password);
+            }
+            if !pool_is_encrypted(id.clone())? {
+                return pool_start(id, TokenUnlockMethod::None, false);
+            }
+            Err(StratisError::Msg(format!(
+                "No method of unlocking pool with {id} succeeded"
+            )))
+        }
+    }
+}
+
 // stratis-min pool stop
 pub fn pool_stop(id: PoolIdentifier<PoolUuid>) -> StratisResult<()> {
     do_request_standard!(PoolStop, id)
 }
 
 // stratis-min pool init-cache
-pub fn pool_init_cache(name: String, paths: Vec<PathBuf>) -> StratisResult<()> {
-    do_request_standard!(PoolInitCache, name, paths)
+pub fn pool_init_cache(name: String, paths: Vec<PathBuf>, background: bool) -> StratisResult<()> {
+    if background {
+        job_started(do_request!(PoolInitCacheJob, name, paths))
+    } else {
+        do_request_standard!(PoolInitCache, name, paths)
+    }
 }
 
 // stratis-min pool init-cache
@@ -209,3 +327,135 @@ pub fn pool_rebind_clevis(
 ) -> StratisResult<()> {
     do_request_standard!(PoolRebindClevis, id, token_slot)
 }
+
+// stratis-min pool info
+pub fn pool_info(name: String) -> StratisResult<()> {
+    let (info, rc, rs) = do_request!(PoolInfo, name);
+    if rc != 0 {
+        return Err(StratisError::Msg(rs));
+    }
+    let (
+        total_size,
+        allocated,
+        used,
+        fs_limit,
+        overprov_enabled,
+        no_alloc_space,
+        avail_actions,
+        metadata_version,
+    ) = info.ok_or_else(|| StratisError::Msg("No pool information returned".to_string()))?;
+    println!("Total Physical Size: {}", to_suffix_repr(total_size));
+    println!("Total Allocated Size: {}", to_suffix_repr(allocated));
+    println!(
+        "Total Physical Used: {}",
+        used.map(to_suffix_repr)
+            .unwrap_or_else(|| "FAILURE".to_string())
+    );
+    println!("Filesystem Limit: {fs_limit}");
+    println!(
+        "Allows Overprovisioning: {}",
+        if overprov_enabled { "Yes" } else { "No" }
+    );
+    println!(
+        "Out of Allocation Space: {}",
+        if no_alloc_space { "Yes" } else { "No" }
+    );
+    println!("Available Actions: {avail_actions}");
+    println!("Metadata Version: {metadata_version}");
+    Ok(())
+}
+
+// stratis-min pool thin-meta-spare
+pub fn pool_thin_meta_spare(
+    id: PoolIdentifier<PoolUuid>,
+    action: ThinMetaSpareAction,
+) -> StratisResult<()> {
+    let (spare, rc, rs) = do_request!(PoolThinMetaSpare, id, action);
+    if rc != 0 {
+        return Err(StratisError::Msg(rs));
+    }
+    let (spare_size, meta_size, last_validated) = spare.ok_or_else(|| {
+        StratisError::Msg("No spare thin metadata information returned".to_string())
+    })?;
+    println!("Spare Size: {}", to_suffix_repr(spare_size));
+    println!("Thin Metadata Size: {}", to_suffix_repr(meta_size));
+    println!(
+        "Last Validated: {}",
+        last_validated.unwrap_or_else(|| "Never".to_string())
+    );
+    Ok(())
+}
+
+// stratis-min pool extend-data
+pub fn pool_extend_data(
+    name: String,
+    device: Option<DevUuid>,
+    background: bool,
+) -> StratisResult<()> {
+    if background {
+        job_started(do_request!(PoolGrowPhysicalJob, name, device))
+    } else {
+        do_request_standard!(PoolGrowPhysical, name, device)
+    }
+}
+
+// stratis-min pool set-fs-limit
+pub fn pool_set_fs_limit(name: String, limit: u64) -> StratisResult<()> {
+    do_request_standard!(PoolSetFsLimit, name, limit)
+}
+
+// stratis-min pool overprovision
+pub fn pool_overprovision(name: String, enabled: bool) -> StratisResult<()> {
+    do_request_standard!(PoolSetOverprovMode, name, enabled)
+}
+
+// stratis-min pool metadata
+pub fn pool_metadata(name: String, current: bool) -> StratisResult<String> {
+    let (metadata, rc, rs) = do_request!(PoolMetadata, name, current);
+    if rc != 0 {
+        Err(StratisError::Msg(rs))
+    } else {
+        Ok(metadata)
+    }
+}
+
+// stratis-min pool layout
+pub fn pool_layout(name: String) -> StratisResult<String> {
+    let (layout, rc, rs) = do_request!(PoolLayout, name);
+    if rc != 0 {
+        Err(StratisError::Msg(rs))
+    } else {
+        Ok(layout)
+    }
+}
+
+// stratis-min pool compact
+pub fn pool_compact(name: String, background: bool) -> StratisResult<()> {
+    if background {
+        return job_started(do_request!(PoolCompactJob, name));
+    }
+    let (compaction, rc, rs) = do_request!(PoolCompact, name);
+    if rc != 0 {
+        return Err(StratisError::Msg(rs));
+    }
+    let (segments_before, segments_after, moved, data_extension) = compaction
+        .ok_or_else(|| StratisError::Msg("No compaction information returned".to_string()))?;
+    println!("Segments Before: {segments_before}");
+    println!("Segments After: {segments_after}");
+    println!("Moved: {}", to_suffix_repr(moved));
+    println!("Thin Data Extension: {}", to_suffix_repr(data_extension));
+    Ok(())
+}
+
+// stratis-min pool apply-batch
+pub fn pool_apply_batch(name: String, ops: Vec<BatchOperation>) -> StratisResult<()> {
+    let (changes, rc, rs) = do_request!(PoolApplyBatch, name, ops);
+    if rc != 0 {
+        Err(StratisError::Msg(rs))
+    } else {
+        for change in changes {
+            println!("{change}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use serde_json::{Map, Value};
+
+use crate::{
+    jsonrpc::{
+        client::StratisClient,
+        consts::RPC_SOCKADDR,
+        protocol::{VersionedRequest, LIST_METHODS},
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+// stratis-min list-methods
+pub fn list_methods() -> StratisResult<Value> {
+    let mut client = StratisClient::connect(RPC_SOCKADDR)?;
+    let response =
+        client.request_versioned(&VersionedRequest::new(LIST_METHODS, Map::new()), None)?;
+    match (response.result, response.error) {
+        (_, Some(e)) => Err(StratisError::Msg(format!(
+            "{} (code {})",
+            e.message, e.code
+        ))),
+        (Some(result), None) => Ok(Value::from(result)),
+        (None, None) => Err(StratisError::Msg(
+            "Response contained neither a result nor an error".to_string(),
+        )),
+    }
+}
@@ -8,3 +8,23 @@ pub const OP_ERR: u16 = 1;
 pub const OP_OK_STR: &str = "OK";
 
 pub const RPC_SOCKADDR: &str = "/run/stratisd/stratisd-min-jsonrpc";
+pub const RPC_ACCESS_CONFIG: &str = "/etc/stratis/jsonrpc-access.json";
+
+// Stable error codes reported in responses of the versioned protocol. OP_ERR
+// is reported for errors which have no more specific code, and for every
+// error in the legacy encoding.
+pub const OP_ERR_ACTION_DISABLED: u16 = 2;
+pub const OP_ERR_OUT_OF_SPACE: u16 = 3;
+pub const OP_ERR_ROLLBACK: u16 = 4;
+pub const OP_ERR_IO: u16 = 5;
+pub const OP_ERR_DM: u16 = 6;
+pub const OP_ERR_CRYPT: u16 = 7;
+pub const OP_ERR_INVALID_INPUT: u16 = 8;
+pub const OP_ERR_UNKNOWN_METHOD: u16 = 9;
+pub const OP_ERR_INVALID_PARAMS: u16 = 10;
+pub const OP_ERR_UNSUPPORTED_VERSION: u16 = 11;
+pub const OP_ERR_REQUEST: u16 = 12;
+pub const OP_ERR_PERMISSION_DENIED: u16 = 13;
+
+// The versions of the versioned protocol supported, oldest first.
+pub const RPC_PROTOCOL_VERSIONS: &[u32] = &[1];
@@ -9,8 +9,9 @@ use std::{os::unix::io::RawFd, path::PathBuf};
 use serde_json::Value;
 
 use crate::engine::{
-    FilesystemUuid, InputEncryptionInfo, KeyDescription, OptionalTokenSlotInput, PoolIdentifier,
-    PoolUuid, TokenUnlockMethod,
+    BatchOperation, DevUuid, FilesystemUuid, InputEncryptionInfo, JobId, JobInfo, KeyDescription,
+    OptionalTokenSlotInput, PoolExport, PoolIdentifier, PoolUuid, PoolsConfig, ThinMetaSpareAction,
+    TokenUnlockMethod,
 };
 
 pub type PoolListType = (
@@ -30,6 +31,37 @@ pub type FsListType = (
     Vec<PathBuf>,
     Vec<FilesystemUuid>,
 );
+// Total physical size, total allocated size, total used size, filesystem
+// limit, overprovisioning enabled, out of allocation space, available
+// actions, metadata version.
+pub type PoolInfoType = (u128, u128, Option<u128>, u64, bool, bool, String, u64);
+// Size, used, size limit, reservation, origin, merge scheduled, created,
+// device, mount path.
+pub type FsInfoType = (
+    u128,
+    Option<u128>,
+    Option<u128>,
+    Option<u128>,
+    Option<FilesystemUuid>,
+    bool,
+    String,
+    PathBuf,
+    PathBuf,
+);
+// Spare thin metadata device size, thin metadata device size, time of last
+// validation.
+pub type ThinMetaSpareType = (u128, u128, Option<String>);
+// Segments of the flex devices before compaction, segments after compaction,
+// amount of data moved, amount of space added to the thin data device.
+pub type CompactionType = (u64, u64, u128, u128);
+pub type BlockdevListType = (
+    Vec<String>,
+    Vec<PathBuf>,
+    Vec<String>,
+    Vec<u128>,
+    Vec<Option<String>>,
+    Vec<DevUuid>,
+);
 
 #[derive(Serialize, Deserialize)]
 pub enum StratisParamType {
@@ -64,14 +96,108 @@ pub enum StratisParamType {
     PoolIsStopped(PoolIdentifier<PoolUuid>),
     PoolIsBound(PoolIdentifier<PoolUuid>),
     PoolHasPassphrase(PoolIdentifier<PoolUuid>),
+    PoolInfo(String),
+    PoolGrowPhysical(String, Option<DevUuid>),
+    PoolSetFsLimit(String, u64),
+    PoolSetOverprovMode(String, bool),
+    PoolMetadata(String, bool),
+    PoolLayout(String),
+    PoolCompact(String),
+    PoolApplyBatch(String, Vec<BatchOperation>),
+    PoolCreateJob(String, Vec<PathBuf>, Option<InputEncryptionInfo>),
+    PoolInitCacheJob(String, Vec<PathBuf>),
+    PoolGrowPhysicalJob(String, Option<DevUuid>),
+    PoolCompactJob(String),
+    PoolThinMetaSpare(PoolIdentifier<PoolUuid>, ThinMetaSpareAction),
     FsCreate(String, String),
     FsDestroy(String, String),
     FsRename(String, String, String),
     FsOrigin(String, String),
     FsList,
+    FsInfo(String, String),
+    FsSnapshot(String, String, String),
+    FsClone(String, String, String, String, bool),
+    FsSetSizeLimit(String, String, Option<u128>),
+    FsSetReservation(String, String, Option<u128>),
+    FsSetMergeScheduled(String, String, bool),
+    FsMetadata(String, Option<String>, bool),
+    BlockdevList,
+    BlockdevSetUserInfo(String, DevUuid, Option<String>),
+    JobList,
+    JobStatus(JobId),
+    JobCancel(JobId),
+    ApplyConfiguration(PoolsConfig, bool),
+    PoolExportConfig(String),
+    PoolImportConfig(PoolExport, Vec<PathBuf>),
     Report,
 }
 
+impl StratisParamType {
+    /// Whether the request only queries state. Read-only requests may be
+    /// permitted to unprivileged users, all other requests require root.
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            StratisParamType::KeyList
+            | StratisParamType::PoolList
+            | StratisParamType::PoolIsEncrypted(_)
+            | StratisParamType::PoolIsStopped(_)
+            | StratisParamType::PoolIsBound(_)
+            | StratisParamType::PoolHasPassphrase(_)
+            | StratisParamType::PoolInfo(_)
+            | StratisParamType::PoolMetadata(..)
+            | StratisParamType::PoolLayout(_)
+            | StratisParamType::FsOrigin(..)
+            | StratisParamType::FsList
+            | StratisParamType::FsInfo(..)
+            | StratisParamType::FsMetadata(..)
+            | StratisParamType::BlockdevList
+            | StratisParamType::JobList
+            | StratisParamType::JobStatus(_)
+            | StratisParamType::PoolExportConfig(_)
+            | StratisParamType::PoolThinMetaSpare(_, ThinMetaSpareAction::Report)
+            | StratisParamType::Report => true,
+            StratisParamType::KeySet(_)
+            | StratisParamType::KeyUnset(_)
+            | StratisParamType::PoolCreate(..)
+            | StratisParamType::PoolRename(..)
+            | StratisParamType::PoolAddData(..)
+            | StratisParamType::PoolInitCache(..)
+            | StratisParamType::PoolAddCache(..)
+            | StratisParamType::PoolDestroy(_)
+            | StratisParamType::PoolStart(..)
+            | StratisParamType::PoolStop(_)
+            | StratisParamType::PoolBindKeyring(..)
+            | StratisParamType::PoolBindClevis(..)
+            | StratisParamType::PoolUnbindKeyring(..)
+            | StratisParamType::PoolUnbindClevis(..)
+            | StratisParamType::PoolRebindKeyring(..)
+            | StratisParamType::PoolRebindClevis(..)
+            | StratisParamType::PoolGrowPhysical(..)
+            | StratisParamType::PoolSetFsLimit(..)
+            | StratisParamType::PoolApplyBatch(..)
+            | StratisParamType::PoolSetOverprovMode(..)
+            | StratisParamType::PoolCreateJob(..)
+            | StratisParamType::PoolInitCacheJob(..)
+            | StratisParamType::PoolGrowPhysicalJob(..)
+            | StratisParamType::PoolCompact(_)
+            | StratisParamType::PoolCompactJob(_)
+            | StratisParamType::PoolThinMetaSpare(..)
+            | StratisParamType::FsCreate(..)
+            | StratisParamType::FsDestroy(..)
+            | StratisParamType::FsRename(..)
+            | StratisParamType::FsSnapshot(..)
+            | StratisParamType::FsClone(..)
+            | StratisParamType::FsSetSizeLimit(..)
+            | StratisParamType::FsSetReservation(..)
+            | StratisParamType::FsSetMergeScheduled(..)
+            | StratisParamType::BlockdevSetUserInfo(..)
+            | StratisParamType::JobCancel(_)
+            | StratisParamType::ApplyConfiguration(..)
+            | StratisParamType::PoolImportConfig(..) => false,
+        }
+    }
+}
+
 pub struct StratisParams {
     pub type_: StratisParamType,
     pub fd_opt: Option<RawFd>,
@@ -103,10 +229,38 @@ pub enum StratisRet {
     PoolIsStopped((bool, u16, String)),
     PoolHasPassphrase((bool, u16, String)),
     PoolIsBound((bool, u16, String)),
+    PoolInfo((Option<PoolInfoType>, u16, String)),
+    PoolGrowPhysical((bool, u16, String)),
+    PoolSetFsLimit((bool, u16, String)),
+    PoolSetOverprovMode((bool, u16, String)),
+    PoolMetadata((String, u16, String)),
+    PoolLayout((String, u16, String)),
+    PoolCompact((Option<CompactionType>, u16, String)),
+    PoolApplyBatch((Vec<String>, u16, String)),
+    PoolCreateJob((JobId, u16, String)),
+    PoolInitCacheJob((JobId, u16, String)),
+    PoolGrowPhysicalJob((JobId, u16, String)),
+    PoolCompactJob((JobId, u16, String)),
+    PoolThinMetaSpare((Option<ThinMetaSpareType>, u16, String)),
     FsCreate((bool, u16, String)),
     FsList(FsListType),
     FsDestroy((bool, u16, String)),
     FsRename((bool, u16, String)),
     FsOrigin((Option<String>, u16, String)),
+    FsInfo((Option<FsInfoType>, u16, String)),
+    FsSnapshot((bool, u16, String)),
+    FsClone((bool, u16, String)),
+    FsSetSizeLimit((bool, u16, String)),
+    FsSetReservation((bool, u16, String)),
+    FsSetMergeScheduled((bool, u16, String)),
+    FsMetadata((String, u16, String)),
+    BlockdevList(BlockdevListType),
+    BlockdevSetUserInfo((bool, u16, String)),
+    JobList(Vec<JobInfo>),
+    JobStatus((Option<JobInfo>, u16, String)),
+    JobCancel((bool, u16, String)),
+    ApplyConfiguration((Vec<String>, u16, String)),
+    PoolExportConfig((Option<PoolExport>, u16, String)),
+    PoolImportConfig((bool, u16, String)),
     Report(Value),
 }
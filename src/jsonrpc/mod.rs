@@ -5,6 +5,8 @@
 pub mod client;
 mod consts;
 mod interface;
+pub(crate) mod protocol;
 mod server;
 
+pub(crate) use self::server::{is_read_only_named, process_named, AccessPolicy, PeerCreds};
 pub use self::{consts::*, server::run_server};
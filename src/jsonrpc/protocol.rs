@@ -0,0 +1,1010 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// The versioned protocol wraps the serde encoding of StratisParamType and
+// StratisRet, which is positional and specific to Rust, in a stable envelope
+// with named parameters and results. A versioned request is a JSON object of
+// the form {"version": <u32>, "method": <string>, "params": {<name>: <value>}}
+// and is answered with {"version": <u32>, "result": {<name>: <value>}} on
+// success or {"version": <u32>, "error": {"code": <u16>, "message": <string>}}
+// on failure. Requests which do not contain a version are interpreted using
+// the legacy encoding.
+
+use serde_json::{Map, Value};
+
+use crate::{
+    jsonrpc::{
+        consts::{
+            OP_ERR, OP_ERR_ACTION_DISABLED, OP_ERR_CRYPT, OP_ERR_DM, OP_ERR_INVALID_INPUT,
+            OP_ERR_INVALID_PARAMS, OP_ERR_IO, OP_ERR_OUT_OF_SPACE, OP_ERR_REQUEST, OP_ERR_ROLLBACK,
+            OP_ERR_UNKNOWN_METHOD, OP_ERR_UNSUPPORTED_VERSION, OP_OK, RPC_PROTOCOL_VERSIONS,
+        },
+        interface::{IpcResult, StratisParamType, StratisRet},
+    },
+    stratis::StratisError,
+};
+
+/// Negotiate the protocol version. Takes the list of versions supported by
+/// the client and returns the newest version supported by both along with
+/// the available methods and capabilities of the server.
+pub const HELLO: &str = "Hello";
+/// Describe all methods, their parameters and results.
+pub const LIST_METHODS: &str = "ListMethods";
+
+/// Optional features of the server which a client may check for in the
+/// response to Hello.
+const CAPABILITIES: &[&str] = &["fd-passing", "legacy-encoding"];
+
+/// Descriptions of the JSON encodings of the types named in method schemas.
+const TYPES: &[(&str, &str)] = &[
+    ("string", "a JSON string"),
+    ("boolean", "a JSON boolean"),
+    ("u32", "an unsigned 32 bit integer"),
+    ("u64", "an unsigned 64 bit integer"),
+    ("u128", "an unsigned integer, in bytes where it denotes a size"),
+    ("uuid", "a UUID string, hyphenated or simple"),
+    ("path", "an absolute path string"),
+    ("json", "an arbitrary JSON value"),
+    ("pool_id", "{\"Name\": <string>} or {\"Uuid\": <uuid>}"),
+    ("key_description", "a kernel keyring key description string"),
+    (
+        "token_slot_input",
+        "\"Legacy\", \"None\" or {\"Some\": <u32>}",
+    ),
+    (
+        "token_unlock_method",
+        "\"None\", \"Any\" or {\"Token\": <u32>}",
+    ),
+    (
+        "encryption_info",
+        "{\"encryption_infos\": [[<u32 or null>, {\"KeyDesc\": <key_description>} or {\"ClevisInfo\": [<string>, <json>]}]]}",
+    ),
+    (
+        "job_info",
+        "{\"id\": <u64>, \"description\": <string>, \"state\": \"queued\", \"running\", \"completed\", \"failed\" or \"cancelled\", \"progress\": <a number between 0 and 1>, \"error\": <string or null>}; progress is updated while the job runs; a running job which JobCancel cancels stops at the next point at which it can stop safely, and completes if there is none left",
+    ),
+    (
+        "batch_operation",
+        "{\"op\": <string>, ...}; one of create_filesystem (name, size?, size_limit?), rename_filesystem (name, new_name), set_fs_size_limit (name, limit?), set_fs_limit (limit), set_overprov_mode (enabled), set_blockdev_user_info (uuid, user_info?) or bind_keyring (token_slot?, key_desc); sizes are in bytes",
+    ),
+    (
+        "pools_config",
+        "{\"pools\": [{\"name\": <string>, \"blockdevs\": [<path>], \"cache\"?: [<path>], \"encryption\"?: {\"key_desc\"?: <key_description>, \"clevis\"?: {\"pin\": <string>, \"config\": <json>}}, \"overprovisioning\"?: <boolean>, \"fs_limit\"?: <u64>, \"filesystems\"?: [{\"name\": <string>, \"uuid\"?: <uuid>, \"size\"?: <u64>, \"size_limit\"?: <u64>}]}]}; sizes are in bytes",
+    ),
+    (
+        "pool_export",
+        "{\"version\": <u32>, \"pool\": <an element of the pools of a pools_config>}",
+    ),
+    (
+        "thin_meta_spare_action",
+        "\"Report\", \"Validate\", \"Swap\" or \"Reprovision\"",
+    ),
+    (
+        "thin_meta_spare",
+        "[<u128: spare size>, <u128: thin metadata size>, <RFC 3339 time of last validation or null>]",
+    ),
+    (
+        "compaction",
+        "[<u64: flex device segments before>, <u64: flex device segments after>, <u128: amount moved>, <u128: amount added to the thin data device>]",
+    ),
+    ("[T]", "a JSON array of elements of type T"),
+    ("T?", "a value of type T or null"),
+];
+
+/// How a method uses a file descriptor passed with the request.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FdUse {
+    None,
+    Required,
+    Optional,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ParamSchema {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub type_: &'static str,
+    pub optional: bool,
+}
+
+const fn req(name: &'static str, type_: &'static str) -> ParamSchema {
+    ParamSchema {
+        name,
+        type_,
+        optional: false,
+    }
+}
+
+const fn opt(name: &'static str, type_: &'static str) -> ParamSchema {
+    ParamSchema {
+        name,
+        type_,
+        optional: true,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MethodSchema {
+    pub name: &'static str,
+    pub params: &'static [ParamSchema],
+    pub returns: &'static [ParamSchema],
+    pub fd: FdUse,
+    /// Whether the legacy encoding of the result carries a return code and
+    /// string after the value.
+    #[serde(skip)]
+    pub has_status: bool,
+}
+
+const fn method(
+    name: &'static str,
+    params: &'static [ParamSchema],
+    returns: &'static [ParamSchema],
+) -> MethodSchema {
+    MethodSchema {
+        name,
+        params,
+        returns,
+        fd: FdUse::None,
+        has_status: true,
+    }
+}
+
+const fn method_with_fd(
+    name: &'static str,
+    params: &'static [ParamSchema],
+    returns: &'static [ParamSchema],
+    fd: FdUse,
+) -> MethodSchema {
+    MethodSchema {
+        name,
+        params,
+        returns,
+        fd,
+        has_status: true,
+    }
+}
+
+const fn listing(name: &'static str, returns: &'static [ParamSchema]) -> MethodSchema {
+    MethodSchema {
+        name,
+        params: &[],
+        returns,
+        fd: FdUse::None,
+        has_status: false,
+    }
+}
+
+const CHANGED: &[ParamSchema] = &[req("changed", "boolean")];
+/// Methods which start a job return its ID rather than waiting for it.
+const JOB_STARTED: &[ParamSchema] = &[req("job_id", "u64")];
+
+/// The schemas of all methods of the versioned protocol other than Hello and
+/// ListMethods. The parameters are listed in the order of the fields of the
+/// StratisParamType variant of the same name.
+pub const METHODS: &[MethodSchema] = &[
+    method_with_fd(
+        "KeySet",
+        &[req("key_desc", "key_description")],
+        &[opt("changed", "boolean")],
+        FdUse::Required,
+    ),
+    method("KeyUnset", &[req("key_desc", "key_description")], CHANGED),
+    method("KeyList", &[], &[req("key_descs", "[key_description]")]),
+    method(
+        "PoolCreate",
+        &[
+            req("name", "string"),
+            req("blockdevs", "[path]"),
+            opt("encryption_info", "encryption_info"),
+        ],
+        CHANGED,
+    ),
+    method(
+        "PoolRename",
+        &[req("name", "string"), req("new_name", "string")],
+        CHANGED,
+    ),
+    method(
+        "PoolAddData",
+        &[req("name", "string"), req("blockdevs", "[path]")],
+        CHANGED,
+    ),
+    method(
+        "PoolInitCache",
+        &[req("name", "string"), req("blockdevs", "[path]")],
+        CHANGED,
+    ),
+    method(
+        "PoolAddCache",
+        &[req("name", "string"), req("blockdevs", "[path]")],
+        CHANGED,
+    ),
+    method("PoolDestroy", &[req("name", "string")], CHANGED),
+    method_with_fd(
+        "PoolStart",
+        &[
+            req("id", "pool_id"),
+            req("unlock_method", "token_unlock_method"),
+        ],
+        CHANGED,
+        FdUse::Optional,
+    ),
+    method("PoolStop", &[req("id", "pool_id")], CHANGED),
+    listing(
+        "PoolList",
+        &[
+            req("names", "[string]"),
+            req("sizes", "[[u128, u128?]]"),
+            req("properties", "[[boolean, boolean]]"),
+            req("uuids", "[uuid]"),
+        ],
+    ),
+    method(
+        "PoolBindKeyring",
+        &[
+            req("id", "pool_id"),
+            req("token_slot", "token_slot_input"),
+            req("key_desc", "key_description"),
+        ],
+        CHANGED,
+    ),
+    method(
+        "PoolBindClevis",
+        &[
+            req("id", "pool_id"),
+            req("token_slot", "token_slot_input"),
+            req("pin", "string"),
+            req("clevis_info", "json"),
+        ],
+        CHANGED,
+    ),
+    method(
+        "PoolUnbindKeyring",
+        &[req("id", "pool_id"), opt("token_slot", "u32")],
+        CHANGED,
+    ),
+    method(
+        "PoolUnbindClevis",
+        &[req("id", "pool_id"), opt("token_slot", "u32")],
+        CHANGED,
+    ),
+    method(
+        "PoolRebindKeyring",
+        &[
+            req("id", "pool_id"),
+            opt("token_slot", "u32"),
+            req("key_desc", "key_description"),
+        ],
+        CHANGED,
+    ),
+    method(
+        "PoolRebindClevis",
+        &[req("id", "pool_id"), opt("token_slot", "u32")],
+        CHANGED,
+    ),
+    method(
+        "PoolIsEncrypted",
+        &[req("id", "pool_id")],
+        &[req("is_encrypted", "boolean")],
+    ),
+    method(
+        "PoolIsStopped",
+        &[req("id", "pool_id")],
+        &[req("is_stopped", "boolean")],
+    ),
+    method(
+        "PoolIsBound",
+        &[req("id", "pool_id")],
+        &[req("is_bound", "boolean")],
+    ),
+    method(
+        "PoolHasPassphrase",
+        &[req("id", "pool_id")],
+        &[req("has_passphrase", "boolean")],
+    ),
+    method(
+        "PoolInfo",
+        &[req("name", "string")],
+        &[
+            req("total_physical_size", "u128"),
+            req("total_allocated_size", "u128"),
+            opt("total_physical_used", "u128"),
+            req("fs_limit", "u64"),
+            req("overprov_enabled", "boolean"),
+            req("out_of_alloc_space", "boolean"),
+            req("avail_actions", "string"),
+            req("metadata_version", "u64"),
+        ],
+    ),
+    method(
+        "PoolGrowPhysical",
+        &[req("name", "string"), opt("dev_uuid", "uuid")],
+        CHANGED,
+    ),
+    method(
+        "PoolSetFsLimit",
+        &[req("name", "string"), req("limit", "u64")],
+        CHANGED,
+    ),
+    method(
+        "PoolSetOverprovMode",
+        &[req("name", "string"), req("enabled", "boolean")],
+        CHANGED,
+    ),
+    method(
+        "PoolMetadata",
+        &[req("name", "string"), req("current", "boolean")],
+        &[req("metadata", "string")],
+    ),
+    method(
+        "PoolLayout",
+        &[req("name", "string")],
+        &[req("layout", "string")],
+    ),
+    method(
+        "PoolCompact",
+        &[req("name", "string")],
+        &[opt("compaction", "compaction")],
+    ),
+    method(
+        "PoolApplyBatch",
+        &[
+            req("name", "string"),
+            req("operations", "[batch_operation]"),
+        ],
+        &[req("changes", "[string]")],
+    ),
+    method(
+        "PoolCreateJob",
+        &[
+            req("name", "string"),
+            req("blockdevs", "[path]"),
+            opt("encryption_info", "encryption_info"),
+        ],
+        JOB_STARTED,
+    ),
+    method(
+        "PoolInitCacheJob",
+        &[req("name", "string"), req("blockdevs", "[path]")],
+        JOB_STARTED,
+    ),
+    method(
+        "PoolGrowPhysicalJob",
+        &[req("name", "string"), opt("dev_uuid", "uuid")],
+        JOB_STARTED,
+    ),
+    method("PoolCompactJob", &[req("name", "string")], JOB_STARTED),
+    method(
+        "PoolThinMetaSpare",
+        &[
+            req("id", "pool_id"),
+            req("action", "thin_meta_spare_action"),
+        ],
+        &[opt("spare", "thin_meta_spare")],
+    ),
+    method(
+        "FsCreate",
+        &[req("pool_name", "string"), req("name", "string")],
+        CHANGED,
+    ),
+    method(
+        "FsDestroy",
+        &[req("pool_name", "string"), req("name", "string")],
+        CHANGED,
+    ),
+    method(
+        "FsRename",
+        &[
+            req("pool_name", "string"),
+            req("name", "string"),
+            req("new_name", "string"),
+        ],
+        CHANGED,
+    ),
+    method(
+        "FsOrigin",
+        &[req("pool_name", "string"), req("name", "string")],
+        &[opt("origin", "uuid")],
+    ),
+    listing(
+        "FsList",
+        &[
+            req("pool_names", "[string]"),
+            req("names", "[string]"),
+            req("used", "[u128?]"),
+            req("created", "[string]"),
+            req("devnodes", "[path]"),
+            req("uuids", "[uuid]"),
+        ],
+    ),
+    method(
+        "FsInfo",
+        &[req("pool_name", "string"), req("name", "string")],
+        &[
+            req("size", "u128"),
+            opt("used", "u128"),
+            opt("size_limit", "u128"),
+            opt("reservation", "u128"),
+            opt("origin", "uuid"),
+            req("merge_scheduled", "boolean"),
+            req("created", "string"),
+            req("devnode", "path"),
+            req("mount_path", "path"),
+        ],
+    ),
+    method(
+        "FsSnapshot",
+        &[
+            req("pool_name", "string"),
+            req("origin_name", "string"),
+            req("snapshot_name", "string"),
+        ],
+        CHANGED,
+    ),
+    method(
+        "FsClone",
+        &[
+            req("pool_name", "string"),
+            req("name", "string"),
+            req("target_pool_name", "string"),
+            req("new_name", "string"),
+            req("preserve_uuid", "boolean"),
+        ],
+        CHANGED,
+    ),
+    method(
+        "FsSetSizeLimit",
+        &[
+            req("pool_name", "string"),
+            req("name", "string"),
+            opt("limit", "u128"),
+        ],
+        CHANGED,
+    ),
+    method(
+        "FsSetReservation",
+        &[
+            req("pool_name", "string"),
+            req("name", "string"),
+            opt("reservation", "u128"),
+        ],
+        CHANGED,
+    ),
+    method(
+        "FsSetMergeScheduled",
+        &[
+            req("pool_name", "string"),
+            req("name", "string"),
+            req("scheduled", "boolean"),
+        ],
+        CHANGED,
+    ),
+    method(
+        "FsMetadata",
+        &[
+            req("pool_name", "string"),
+            opt("name", "string"),
+            req("current", "boolean"),
+        ],
+        &[req("metadata", "string")],
+    ),
+    listing(
+        "BlockdevList",
+        &[
+            req("pool_names", "[string]"),
+            req("devnodes", "[path]"),
+            req("tiers", "[string]"),
+            req("sizes", "[u128]"),
+            req("user_infos", "[string?]"),
+            req("uuids", "[uuid]"),
+        ],
+    ),
+    method(
+        "BlockdevSetUserInfo",
+        &[
+            req("pool_name", "string"),
+            req("dev_uuid", "uuid"),
+            opt("user_info", "string"),
+        ],
+        CHANGED,
+    ),
+    listing("JobList", &[req("jobs", "[job_info]")]),
+    method("JobStatus", &[req("id", "u64")], &[req("job", "job_info")]),
+    method(
+        "JobCancel",
+        &[req("id", "u64")],
+        &[req("cancelled", "boolean")],
+    ),
+    method(
+        "ApplyConfiguration",
+        &[req("config", "pools_config"), req("dry_run", "boolean")],
+        &[req("actions", "[string]")],
+    ),
+    method(
+        "PoolExportConfig",
+        &[req("name", "string")],
+        &[opt("export", "pool_export")],
+    ),
+    method(
+        "PoolImportConfig",
+        &[req("export", "pool_export"), req("blockdevs", "[path]")],
+        CHANGED,
+    ),
+    listing("Report", &[req("report", "json")]),
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionedRequest {
+    pub version: u32,
+    pub method: String,
+    #[serde(default)]
+    pub params: Map<String, Value>,
+}
+
+impl VersionedRequest {
+    pub fn new(method: &str, params: Map<String, Value>) -> VersionedRequest {
+        VersionedRequest {
+            version: newest_version(),
+            method: method.to_string(),
+            params,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: u16,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: u16, message: String) -> RpcError {
+        RpcError { code, message }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionedResponse {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Map<String, Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl VersionedResponse {
+    pub fn new(version: u32, result: Result<Map<String, Value>, RpcError>) -> VersionedResponse {
+        match result {
+            Ok(r) => VersionedResponse {
+                version,
+                result: Some(r),
+                error: None,
+            },
+            Err(e) => VersionedResponse {
+                version,
+                result: None,
+                error: Some(e),
+            },
+        }
+    }
+}
+
+fn newest_version() -> u32 {
+    *RPC_PROTOCOL_VERSIONS
+        .last()
+        .expect("at least one protocol version is supported")
+}
+
+/// Map an error to the stable error code reported for it.
+pub fn error_code(err: &StratisError) -> u16 {
+    match err {
+        StratisError::Chained(_, cause) => error_code(cause),
+        StratisError::RollbackError { .. } | StratisError::NoActionRollbackError { .. } => {
+            OP_ERR_ROLLBACK
+        }
+        StratisError::ActionDisabled(_) => OP_ERR_ACTION_DISABLED,
+        StratisError::OutOfSpaceError(_) => OP_ERR_OUT_OF_SPACE,
+        StratisError::Io(_) | StratisError::Nix(_) => OP_ERR_IO,
+        StratisError::DM(_) => OP_ERR_DM,
+        StratisError::Crypt(_) => OP_ERR_CRYPT,
+        StratisError::Uuid(_)
+        | StratisError::Utf8(_)
+        | StratisError::Serde(_)
+        | StratisError::Decode(_)
+        | StratisError::Null(_) => OP_ERR_INVALID_INPUT,
+        StratisError::Msg(_)
+        | StratisError::BestEffortError(..)
+        | StratisError::Recv(_)
+        | StratisError::Join(_)
+        | StratisError::Blkid(_)
+        | StratisError::Udev(_) => OP_ERR,
+        #[cfg(feature = "dbus_enabled")]
+        StratisError::Dbus(_) => OP_ERR,
+    }
+}
+
+pub fn find_method(name: &str) -> Option<&'static MethodSchema> {
+    METHODS.iter().find(|m| m.name == name)
+}
+
+/// Check that the version of a request other than Hello is supported.
+pub fn check_version(version: u32) -> Result<(), RpcError> {
+    if RPC_PROTOCOL_VERSIONS.contains(&version) {
+        Ok(())
+    } else {
+        Err(RpcError::new(
+            OP_ERR_UNSUPPORTED_VERSION,
+            format!(
+                "Protocol version {version} is not supported; supported versions are {RPC_PROTOCOL_VERSIONS:?}"
+            ),
+        ))
+    }
+}
+
+/// Handle a Hello request. Returns the negotiated version with the result.
+pub fn hello(params: &Map<String, Value>) -> (u32, Result<Map<String, Value>, RpcError>) {
+    let versions = match params
+        .get("versions")
+        .map(|v| serde_json::from_value::<Vec<u32>>(v.clone()))
+    {
+        Some(Ok(versions)) => versions,
+        Some(Err(e)) => {
+            return (
+                newest_version(),
+                Err(RpcError::new(
+                    OP_ERR_INVALID_PARAMS,
+                    format!("Invalid parameter versions for method {HELLO}: {e}"),
+                )),
+            )
+        }
+        None => {
+            return (
+                newest_version(),
+                Err(RpcError::new(
+                    OP_ERR_INVALID_PARAMS,
+                    format!("Missing required parameter versions for method {HELLO}"),
+                )),
+            )
+        }
+    };
+
+    match RPC_PROTOCOL_VERSIONS
+        .iter()
+        .rev()
+        .find(|v| versions.contains(v))
+    {
+        Some(version) => {
+            let mut result = Map::new();
+            result.insert("version".to_string(), Value::from(*version));
+            result.insert(
+                "methods".to_string(),
+                Value::from(
+                    [HELLO, LIST_METHODS]
+                        .into_iter()
+                        .chain(METHODS.iter().map(|m| m.name))
+                        .collect::<Vec<_>>(),
+                ),
+            );
+            result.insert("capabilities".to_string(), Value::from(CAPABILITIES));
+            (*version, Ok(result))
+        }
+        None => (
+            newest_version(),
+            Err(RpcError::new(
+                OP_ERR_UNSUPPORTED_VERSION,
+                format!(
+                    "None of the requested protocol versions {versions:?} are supported; supported versions are {RPC_PROTOCOL_VERSIONS:?}"
+                ),
+            )),
+        ),
+    }
+}
+
+/// Handle a ListMethods request.
+pub fn list_methods() -> Result<Map<String, Value>, RpcError> {
+    let mut result = Map::new();
+    result.insert(
+        "methods".to_string(),
+        serde_json::to_value(METHODS).map_err(|e| RpcError::new(OP_ERR, e.to_string()))?,
+    );
+    result.insert(
+        "types".to_string(),
+        Value::from(
+            TYPES
+                .iter()
+                .map(|(name, desc)| ((*name).to_string(), Value::from(*desc)))
+                .collect::<Map<_, _>>(),
+        ),
+    );
+    Ok(result)
+}
+
+/// Convert the named parameters of a request into the legacy encoding.
+pub fn to_legacy_params(
+    schema: &MethodSchema,
+    mut params: Map<String, Value>,
+) -> Result<StratisParamType, RpcError> {
+    let mut values = Vec::new();
+    for param in schema.params {
+        match params.remove(param.name) {
+            Some(v) => values.push(v),
+            None if param.optional => values.push(Value::Null),
+            None => {
+                return Err(RpcError::new(
+                    OP_ERR_INVALID_PARAMS,
+                    format!(
+                        "Missing required parameter {} for method {}",
+                        param.name, schema.name
+                    ),
+                ))
+            }
+        }
+    }
+    if let Some(name) = params.keys().next() {
+        return Err(RpcError::new(
+            OP_ERR_INVALID_PARAMS,
+            format!("Unknown parameter {} for method {}", name, schema.name),
+        ));
+    }
+
+    // serde encodes a unit variant as its name, a newtype variant as a map
+    // from its name to its value and a tuple variant as a map from its name
+    // to an array of its values.
+    let encoded = if values.is_empty() {
+        Value::from(schema.name)
+    } else {
+        let mut map = Map::new();
+        map.insert(
+            schema.name.to_string(),
+            if values.len() == 1 {
+                values.remove(0)
+            } else {
+                Value::from(values)
+            },
+        );
+        Value::from(map)
+    };
+    serde_json::from_value(encoded).map_err(|e| {
+        RpcError::new(
+            OP_ERR_INVALID_PARAMS,
+            format!("Invalid parameters for method {}: {}", schema.name, e),
+        )
+    })
+}
+
+/// Convert the legacy encoding of the result of a request into named
+/// results.
+pub fn from_legacy_ret(
+    schema: &MethodSchema,
+    ret: IpcResult<StratisRet>,
+) -> Result<Map<String, Value>, RpcError> {
+    let ret = ret.map_err(|e| RpcError::new(OP_ERR_REQUEST, e))?;
+    let payload = match serde_json::to_value(&ret) {
+        Ok(Value::Object(mut map)) => map.remove(schema.name),
+        Ok(_) => None,
+        Err(e) => return Err(RpcError::new(OP_ERR, e.to_string())),
+    }
+    .ok_or_else(|| {
+        RpcError::new(
+            OP_ERR,
+            format!("Result did not match method {}", schema.name),
+        )
+    })?;
+
+    let value = if schema.has_status {
+        match payload {
+            Value::Array(mut status) if status.len() == 3 => {
+                let message = status.pop().expect("length checked");
+                let code = status.pop().expect("length checked");
+                let value = status.pop().expect("length checked");
+                let code = code
+                    .as_u64()
+                    .and_then(|c| u16::try_from(c).ok())
+                    .ok_or_else(|| RpcError::new(OP_ERR, "Invalid return code".to_string()))?;
+                if code != OP_OK {
+                    return Err(RpcError::new(
+                        code,
+                        message.as_str().unwrap_or_default().to_string(),
+                    ));
+                }
+                value
+            }
+            _ => {
+                return Err(RpcError::new(
+                    OP_ERR,
+                    format!("Malformed result for method {}", schema.name),
+                ))
+            }
+        }
+    } else {
+        payload
+    };
+
+    if let [ret] = schema.returns {
+        let mut result = Map::new();
+        result.insert(ret.name.to_string(), value);
+        Ok(result)
+    } else {
+        match value {
+            Value::Array(values) if values.len() == schema.returns.len() => Ok(schema
+                .returns
+                .iter()
+                .map(|r| r.name.to_string())
+                .zip(values)
+                .collect()),
+            _ => Err(RpcError::new(
+                OP_ERR,
+                format!("Malformed result for method {}", schema.name),
+            )),
+        }
+    }
+}
+
+/// The error returned for a request naming a method that does not exist.
+pub fn unknown_method(name: &str) -> RpcError {
+    RpcError::new(OP_ERR_UNKNOWN_METHOD, format!("Unknown method {name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn params(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => panic!("params must be an object"),
+        }
+    }
+
+    #[test]
+    /// Verify that every variant of StratisParamType has a schema and that
+    /// every schema names a variant.
+    fn test_methods_match_param_type() {
+        let source = include_str!("interface.rs");
+        let start = source
+            .find("pub enum StratisParamType {")
+            .expect("StratisParamType is defined in interface.rs");
+        let body = &source[start..];
+        let end = body.find("\n}\n").expect("definition is closed");
+        let variants = body[..end]
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.strip_prefix("    "))
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_uppercase()))
+            .map(|line| {
+                line.split(['(', ','])
+                    .next()
+                    .expect("split always yields one item")
+            })
+            .collect::<Vec<_>>();
+        assert!(variants.contains(&"PoolCreate"));
+
+        for variant in &variants {
+            assert!(
+                find_method(variant).is_some(),
+                "No schema for request {variant}"
+            );
+        }
+        for method in METHODS {
+            assert!(
+                variants.contains(&method.name),
+                "Schema {} does not name a request",
+                method.name
+            );
+        }
+    }
+
+    #[test]
+    /// Verify conversion of named parameters for unit, newtype and tuple
+    /// variants, including omitted optional parameters.
+    fn test_to_legacy_params() {
+        let schema = find_method("PoolList").unwrap();
+        assert!(matches!(
+            to_legacy_params(schema, Map::new()),
+            Ok(StratisParamType::PoolList)
+        ));
+
+        let schema = find_method("PoolDestroy").unwrap();
+        assert!(matches!(
+            to_legacy_params(schema, params(json!({"name": "pn"}))),
+            Ok(StratisParamType::PoolDestroy(ref n)) if n == "pn"
+        ));
+
+        let schema = find_method("FsMetadata").unwrap();
+        assert!(matches!(
+            to_legacy_params(schema, params(json!({"pool_name": "pn", "current": true}))),
+            Ok(StratisParamType::FsMetadata(ref p, None, true)) if p == "pn"
+        ));
+
+        let schema = find_method("PoolRename").unwrap();
+        assert_eq!(
+            to_legacy_params(schema, params(json!({"name": "pn"})))
+                .err()
+                .map(|e| e.code),
+            Some(OP_ERR_INVALID_PARAMS)
+        );
+        assert_eq!(
+            to_legacy_params(
+                schema,
+                params(json!({"name": "pn", "new_name": "nn", "extra": 1}))
+            )
+            .err()
+            .map(|e| e.code),
+            Some(OP_ERR_INVALID_PARAMS)
+        );
+    }
+
+    #[test]
+    /// Verify conversion of results with and without a return code.
+    fn test_from_legacy_ret() {
+        let schema = find_method("PoolRename").unwrap();
+        assert_eq!(
+            from_legacy_ret(
+                schema,
+                Ok(StratisRet::PoolRename((true, OP_OK, "OK".into())))
+            ),
+            Ok(params(json!({"changed": true})))
+        );
+        assert_eq!(
+            from_legacy_ret(
+                schema,
+                Ok(StratisRet::PoolRename((false, OP_ERR_IO, "failed".into())))
+            ),
+            Err(RpcError::new(OP_ERR_IO, "failed".into()))
+        );
+        assert_eq!(
+            from_legacy_ret(schema, Err("no fd".into())).map_err(|e| e.code),
+            Err(OP_ERR_REQUEST)
+        );
+
+        let schema = find_method("PoolList").unwrap();
+        assert_eq!(
+            from_legacy_ret(
+                schema,
+                Ok(StratisRet::PoolList((
+                    vec!["pn".into()],
+                    vec![(1, None)],
+                    vec![(false, true)],
+                    vec![],
+                )))
+            ),
+            Ok(params(json!({
+                "names": ["pn"],
+                "sizes": [[1, null]],
+                "properties": [[false, true]],
+                "uuids": [],
+            })))
+        );
+    }
+
+    #[test]
+    /// Verify version negotiation.
+    fn test_hello() {
+        let (version, result) = hello(&params(json!({"versions": [0, 1, 1000]})));
+        assert_eq!(version, 1);
+        assert!(result.unwrap()["methods"]
+            .as_array()
+            .unwrap()
+            .contains(&Value::from(LIST_METHODS)));
+
+        let (_, result) = hello(&params(json!({"versions": [1000]})));
+        assert_eq!(result.map_err(|e| e.code), Err(OP_ERR_UNSUPPORTED_VERSION));
+    }
+
+    #[test]
+    /// Verify that the code of a chained error is that of its cause.
+    fn test_error_code() {
+        assert_eq!(error_code(&StratisError::Msg("e".into())), OP_ERR);
+        assert_eq!(
+            error_code(&StratisError::Chained(
+                "context".into(),
+                Box::new(StratisError::OutOfSpaceError("e".into()))
+            )),
+            OP_ERR_OUT_OF_SPACE
+        );
+    }
+}
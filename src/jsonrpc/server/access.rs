@@ -0,0 +1,157 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    os::unix::io::{BorrowedFd, RawFd},
+    path::Path,
+};
+
+use nix::{
+    sys::socket::{getsockopt, sockopt::PeerCredentials},
+    unistd::Group,
+};
+
+use crate::stratis::{StratisError, StratisResult};
+
+/// The contents of the JSON-RPC access configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccessConfig {
+    /// Groups whose members may make read-only requests.
+    #[serde(default)]
+    read_only_groups: Vec<String>,
+}
+
+/// The credentials of the process on the other end of a connection.
+#[derive(Clone, Debug)]
+pub struct PeerCreds {
+    uid: u32,
+    gids: Vec<u32>,
+}
+
+impl PeerCreds {
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// Get the credentials of the peer of a connected Unix socket. The
+    /// supplementary groups of the peer are read from /proc, as SO_PEERCRED
+    /// only reports the primary group.
+    pub fn from_socket(fd: RawFd) -> StratisResult<PeerCreds> {
+        let creds = getsockopt(&unsafe { BorrowedFd::borrow_raw(fd) }, PeerCredentials)?;
+        let mut gids = vec![creds.gid()];
+        match fs::read_to_string(format!("/proc/{}/status", creds.pid())) {
+            Ok(status) => gids.extend(parse_groups(&status)),
+            Err(e) => warn!(
+                "Failed to read supplementary groups of process {}: {}",
+                creds.pid(),
+                e
+            ),
+        }
+        Ok(PeerCreds {
+            uid: creds.uid(),
+            gids,
+        })
+    }
+}
+
+/// Parse the supplementary groups from the contents of /proc/<pid>/status.
+fn parse_groups(status: &str) -> Vec<u32> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Groups:"))
+        .map(|groups| {
+            groups
+                .split_whitespace()
+                .filter_map(|gid| gid.parse::<u32>().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The policy deciding which peers may make which requests. Requests which
+/// change state are only permitted to root. Read-only requests are also
+/// permitted to members of the configured groups.
+#[derive(Debug, Default)]
+pub struct AccessPolicy {
+    read_only_gids: Vec<u32>,
+}
+
+impl AccessPolicy {
+    /// Load the policy from the configuration file at path. A missing file
+    /// is equivalent to an empty configuration, which permits only root.
+    pub fn load<P>(path: P) -> StratisResult<AccessPolicy>
+    where
+        P: AsRef<Path>,
+    {
+        let config = match fs::read_to_string(path.as_ref()) {
+            Ok(contents) => serde_json::from_str::<AccessConfig>(&contents).map_err(|e| {
+                StratisError::Chained(
+                    format!(
+                        "Failed to parse JSON-RPC access configuration at {}",
+                        path.as_ref().display()
+                    ),
+                    Box::new(StratisError::from(e)),
+                )
+            })?,
+            Err(e) if e.kind() == ErrorKind::NotFound => AccessConfig::default(),
+            Err(e) => return Err(StratisError::from(e)),
+        };
+
+        let mut read_only_gids = Vec::new();
+        for name in config.read_only_groups {
+            match Group::from_name(&name)? {
+                Some(group) => read_only_gids.push(group.gid.as_raw()),
+                None => warn!(
+                    "Group {} in the JSON-RPC access configuration does not exist; ignoring",
+                    name
+                ),
+            }
+        }
+        Ok(AccessPolicy { read_only_gids })
+    }
+
+    /// Whether the peer with the given credentials may make a request.
+    pub fn permits(&self, peer: &PeerCreds, read_only: bool) -> bool {
+        peer.uid == 0 || (read_only && peer.gids.iter().any(|g| self.read_only_gids.contains(g)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_groups() {
+        let status = "Name:\tcat\nUid:\t1000\t1000\t1000\t1000\nGroups:\t10 1000 \nNgid:\t0\n";
+        assert_eq!(parse_groups(status), vec![10, 1000]);
+        assert_eq!(parse_groups("Name:\tcat\n"), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_permits() {
+        let policy = AccessPolicy {
+            read_only_gids: vec![10],
+        };
+        let root = PeerCreds {
+            uid: 0,
+            gids: vec![0],
+        };
+        let member = PeerCreds {
+            uid: 1000,
+            gids: vec![1000, 10],
+        };
+        let other = PeerCreds {
+            uid: 1001,
+            gids: vec![1001],
+        };
+        assert!(policy.permits(&root, false));
+        assert!(policy.permits(&member, true));
+        assert!(!policy.permits(&member, false));
+        assert!(!policy.permits(&other, true));
+        assert!(!AccessPolicy::default().permits(&member, true));
+    }
+}
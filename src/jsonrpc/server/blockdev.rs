@@ -0,0 +1,69 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::sync::Arc;
+
+use tokio::task::block_in_place;
+
+use crate::{
+    engine::{BlockDevTier, DevUuid, Engine, EngineAction, Name, PoolIdentifier},
+    jsonrpc::interface::BlockdevListType,
+    stratis::{StratisError, StratisResult},
+};
+
+// stratis-min blockdev [list]
+pub async fn blockdev_list(engine: Arc<dyn Engine>) -> BlockdevListType {
+    let guard = engine.pools().await;
+    guard.iter().fold(
+        (
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        ),
+        |mut acc, (name, _, pool)| {
+            for (uuid, tier, bd) in pool.blockdevs() {
+                acc.0.push(name.to_string());
+                acc.1.push(bd.devnode().to_path_buf());
+                acc.2.push(
+                    match tier {
+                        BlockDevTier::Data => "Data",
+                        BlockDevTier::Cache => "Cache",
+                    }
+                    .to_string(),
+                );
+                acc.3.push(*bd.size().bytes());
+                acc.4.push(bd.user_info().map(|s| s.to_owned()));
+                acc.5.push(uuid);
+            }
+            acc
+        },
+    )
+}
+
+// stratis-min blockdev set-user-info
+pub async fn blockdev_set_user_info<'a>(
+    engine: Arc<dyn Engine>,
+    pool_name: &'a str,
+    dev_uuid: DevUuid,
+    user_info: Option<&'a str>,
+) -> StratisResult<bool> {
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+    let (_, _, pool) = guard.as_mut_tuple();
+    if pool.get_blockdev(dev_uuid).is_none() {
+        return Err(StratisError::Msg(format!(
+            "No blockdev with UUID {dev_uuid} found in pool {pool_name}"
+        )));
+    }
+    block_in_place(|| {
+        Ok(pool
+            .set_blockdev_user_info(pool_name, dev_uuid, user_info)?
+            .is_changed())
+    })
+}
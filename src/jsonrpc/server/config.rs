@@ -0,0 +1,51 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{path::PathBuf, sync::Arc};
+
+use crate::{
+    engine::{
+        apply_configuration, export_pool_config, plan_configuration, plan_pool_import, Engine,
+        Name, PoolExport, PoolIdentifier, PoolsConfig,
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+// stratis-min apply
+pub async fn config_apply(
+    engine: Arc<dyn Engine>,
+    config: PoolsConfig,
+    dry_run: bool,
+) -> StratisResult<Vec<String>> {
+    let actions = plan_configuration(&*engine, &config).await?;
+    if !dry_run {
+        apply_configuration(&*engine, &actions).await.1?;
+    }
+    Ok(actions.iter().map(|a| a.to_string()).collect())
+}
+
+// stratis-min pool export-config
+pub async fn pool_export_config(
+    engine: Arc<dyn Engine>,
+    name: &str,
+) -> StratisResult<Option<PoolExport>> {
+    let name = Name::new(name.to_owned());
+    let guard = engine
+        .get_pool(PoolIdentifier::Name(name.clone()))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (_, _, pool) = guard.as_tuple();
+    export_pool_config(&name, pool).map(Some)
+}
+
+// stratis-min pool import-config
+pub async fn pool_import_config(
+    engine: Arc<dyn Engine>,
+    export: &PoolExport,
+    blockdevs: Vec<PathBuf>,
+) -> StratisResult<bool> {
+    let actions = plan_pool_import(&*engine, export, blockdevs).await?;
+    apply_configuration(&*engine, &actions).await.1?;
+    Ok(true)
+}
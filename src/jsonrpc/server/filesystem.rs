@@ -5,11 +5,12 @@
 use std::sync::Arc;
 
 use chrono::SecondsFormat;
+use devicemapper::Bytes;
 use tokio::task::block_in_place;
 
 use crate::{
-    engine::{Engine, EngineAction, Name, PoolIdentifier},
-    jsonrpc::interface::FsListType,
+    engine::{Engine, EngineAction, Name, PoolIdentifier, PropChangeAction},
+    jsonrpc::interface::{FsInfoType, FsListType},
     stratis::{StratisError, StratisResult},
 };
 
@@ -114,3 +115,166 @@ pub async fn filesystem_origin<'a>(
         .ok_or_else(|| StratisError::Msg(format!("No filesystem named {fs_name} found")))?;
     Ok(fs.origin().map(|u| u.as_simple().to_string()))
 }
+
+// stratis-min filesystem info
+pub async fn filesystem_info<'a>(
+    engine: Arc<dyn Engine>,
+    pool_name: &'a str,
+    fs_name: &'a str,
+) -> StratisResult<Option<FsInfoType>> {
+    let pool = engine
+        .get_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+    let (_, fs) = pool
+        .get_filesystem_by_name(&Name::new(fs_name.to_string()))
+        .ok_or_else(|| StratisError::Msg(format!("No filesystem named {fs_name} found")))?;
+    Ok(Some((
+        *fs.size(),
+        fs.used().ok().map(|u| *u),
+        fs.size_limit().map(|l| *l.bytes()),
+        fs.reservation().map(|r| *r.bytes()),
+        fs.origin(),
+        fs.merge_scheduled(),
+        fs.created().to_rfc3339_opts(SecondsFormat::Secs, true),
+        fs.devnode(),
+        fs.path_to_mount_filesystem(pool_name, fs_name),
+    )))
+}
+
+// stratis-min filesystem snapshot
+pub async fn filesystem_snapshot<'a>(
+    engine: Arc<dyn Engine>,
+    pool_name: &'a str,
+    origin_name: &'a str,
+    snapshot_name: &'a str,
+) -> StratisResult<bool> {
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+    let (_, pool_uuid, pool) = guard.as_mut_tuple();
+    let (origin_uuid, _) = pool
+        .get_filesystem_by_name(&Name::new(origin_name.to_string()))
+        .ok_or_else(|| StratisError::Msg(format!("No filesystem named {origin_name} found")))?;
+    block_in_place(|| {
+        Ok(pool
+            .snapshot_filesystem(pool_name, pool_uuid, origin_uuid, snapshot_name)?
+            .is_changed())
+    })
+}
+
+// stratis-min filesystem clone
+pub async fn filesystem_clone<'a>(
+    engine: Arc<dyn Engine>,
+    pool_name: &'a str,
+    fs_name: &'a str,
+    target_pool_name: &'a str,
+    new_name: &'a str,
+    preserve_uuid: bool,
+) -> StratisResult<bool> {
+    let (source_pool, fs_uuid) = {
+        let guard = engine
+            .get_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+            .await
+            .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+        let (_, pool_uuid, pool) = guard.as_tuple();
+        let (fs_uuid, _) = pool
+            .get_filesystem_by_name(&Name::new(fs_name.to_string()))
+            .ok_or_else(|| StratisError::Msg(format!("No filesystem named {fs_name} found")))?;
+        (pool_uuid, fs_uuid)
+    };
+    let target_pool = engine
+        .get_pool(PoolIdentifier::Name(Name::new(target_pool_name.to_owned())))
+        .await
+        .map(|g| g.as_tuple().1)
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {target_pool_name} found")))?;
+    Ok(engine
+        .clone_filesystem(source_pool, fs_uuid, target_pool, new_name, preserve_uuid)
+        .await?
+        .is_changed())
+}
+
+// stratis-min filesystem set-size-limit, filesystem unset-size-limit
+pub async fn filesystem_set_size_limit<'a>(
+    engine: Arc<dyn Engine>,
+    pool_name: &'a str,
+    fs_name: &'a str,
+    limit: Option<u128>,
+) -> StratisResult<bool> {
+    let mut pool = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+    let (uuid, _) = pool
+        .get_filesystem_by_name(&Name::new(fs_name.to_string()))
+        .ok_or_else(|| StratisError::Msg(format!("No filesystem named {fs_name} found")))?;
+    block_in_place(|| {
+        Ok(matches!(
+            pool.set_fs_size_limit(uuid, limit.map(Bytes))?,
+            PropChangeAction::NewValue(_)
+        ))
+    })
+}
+
+// stratis-min filesystem set-reservation, filesystem unset-reservation
+pub async fn filesystem_set_reservation<'a>(
+    engine: Arc<dyn Engine>,
+    pool_name: &'a str,
+    fs_name: &'a str,
+    reservation: Option<u128>,
+) -> StratisResult<bool> {
+    let mut pool = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+    let (uuid, _) = pool
+        .get_filesystem_by_name(&Name::new(fs_name.to_string()))
+        .ok_or_else(|| StratisError::Msg(format!("No filesystem named {fs_name} found")))?;
+    block_in_place(|| {
+        Ok(matches!(
+            pool.set_fs_reservation(uuid, reservation.map(Bytes))?,
+            PropChangeAction::NewValue(_)
+        ))
+    })
+}
+
+// stratis-min filesystem schedule-revert, filesystem cancel-revert
+pub async fn filesystem_set_merge_scheduled<'a>(
+    engine: Arc<dyn Engine>,
+    pool_name: &'a str,
+    fs_name: &'a str,
+    scheduled: bool,
+) -> StratisResult<bool> {
+    let mut pool = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+    let (uuid, _) = pool
+        .get_filesystem_by_name(&Name::new(fs_name.to_string()))
+        .ok_or_else(|| StratisError::Msg(format!("No filesystem named {fs_name} found")))?;
+    block_in_place(|| {
+        Ok(matches!(
+            pool.set_fs_merge_scheduled(uuid, scheduled)?,
+            PropChangeAction::NewValue(_)
+        ))
+    })
+}
+
+// stratis-min filesystem metadata
+pub async fn filesystem_metadata<'a>(
+    engine: Arc<dyn Engine>,
+    pool_name: &'a str,
+    fs_name: Option<&'a str>,
+    current: bool,
+) -> StratisResult<String> {
+    let pool = engine
+        .get_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+    if current {
+        pool.current_fs_metadata(fs_name)
+    } else {
+        pool.last_fs_metadata(fs_name)
+    }
+}
@@ -0,0 +1,102 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{future::Future, sync::Arc};
+
+use crate::{
+    engine::{Engine, JobId, JobInfo},
+    stratis::{Caller, StratisError, StratisResult},
+};
+
+/// Start a job which performs work once all earlier jobs have finished.
+/// The job is performed on behalf of the caller that started it, and its
+/// outcome is the result of work.
+/// Returns the ID of the job without waiting for it to start.
+pub fn job_start<F, T>(engine: &Arc<dyn Engine>, description: &str, work: F) -> JobId
+where
+    F: Future<Output = StratisResult<T>> + Send + 'static,
+{
+    let jobs = engine.jobs();
+    let job = jobs.create(description);
+    let id = job.id();
+    let caller = Caller::current();
+    tokio::spawn(async move {
+        let _permit = match jobs.start(&job).await {
+            Some(permit) => permit,
+            None => {
+                info!("Job {} was cancelled before it started", job.id());
+                return;
+            }
+        };
+        let work = Arc::clone(&job).run(work);
+        let result = match caller {
+            Some(caller) => caller.scope(work).await,
+            None => work.await,
+        }
+        .map(|_| ())
+        .map_err(|e| e.to_string());
+        if let Err(ref e) = result {
+            warn!("Job {} failed: {}", job.id(), e);
+        }
+        jobs.finish(&job, result);
+        jobs.prune();
+    });
+    id
+}
+
+// stratis-min job list
+pub fn job_list(engine: Arc<dyn Engine>) -> Vec<JobInfo> {
+    engine.jobs().list()
+}
+
+// stratis-min job status
+pub fn job_status(engine: Arc<dyn Engine>, id: JobId) -> StratisResult<Option<JobInfo>> {
+    engine
+        .jobs()
+        .get(id)
+        .map(|job| Some(job.info()))
+        .ok_or_else(|| StratisError::Msg(format!("No job with ID {id} exists")))
+}
+
+// stratis-min job cancel
+pub fn job_cancel(engine: Arc<dyn Engine>, id: JobId) -> StratisResult<bool> {
+    engine.jobs().cancel(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::engine::{JobState, SimEngine};
+
+    /// Wait for the job with the given ID to finish and get its status.
+    async fn wait_for(engine: &Arc<dyn Engine>, id: JobId) -> JobInfo {
+        loop {
+            let info = job_status(Arc::clone(engine), id).unwrap().unwrap();
+            if info.state.is_finished() {
+                return info;
+            }
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[test]
+    /// The outcome of a job is the result of the work it performs.
+    fn test_job_start() {
+        test_async!(async {
+            let engine: Arc<dyn Engine> = Arc::new(SimEngine::default());
+            let id = job_start(&engine, "succeeds", async { Ok(false) });
+            let info = wait_for(&engine, id).await;
+            assert_eq!(info.state, JobState::Completed);
+            assert_eq!(info.error, None);
+
+            let id = job_start(&engine, "fails", async {
+                Err::<bool, _>(StratisError::Msg("failed".to_string()))
+            });
+            let info = wait_for(&engine, id).await;
+            assert_eq!(info.state, JobState::Failed);
+            assert_eq!(info.error.as_deref(), Some("failed"));
+        });
+    }
+}
@@ -5,11 +5,16 @@
 #[macro_use]
 mod utils;
 
+mod access;
+mod blockdev;
+mod config;
 mod filesystem;
+mod job;
 mod key;
 mod pool;
 mod report;
 #[allow(clippy::module_inception)]
 mod server;
 
-pub use server::run_server;
+pub use access::{AccessPolicy, PeerCreds};
+pub use server::{is_read_only_named, process_named, run_server};
@@ -4,17 +4,18 @@
 
 use std::{os::unix::io::RawFd, path::Path, sync::Arc};
 
+use chrono::SecondsFormat;
 use either::Either;
 use serde_json::Value;
 use tokio::task::block_in_place;
 
 use crate::{
     engine::{
-        BlockDevTier, CreateAction, DeleteAction, Engine, EngineAction, InputEncryptionInfo,
-        IntegritySpec, KeyDescription, Name, OptionalTokenSlotInput, PoolIdentifier, PoolUuid,
-        RenameAction, TokenUnlockMethod,
+        BatchOperation, BlockDevTier, CreateAction, DeleteAction, DevUuid, Engine, EngineAction,
+        InputEncryptionInfo, IntegritySpec, Job, KeyDescription, Name, OptionalTokenSlotInput,
+        PoolIdentifier, PoolUuid, RenameAction, ThinMetaSpareAction, TokenUnlockMethod,
     },
-    jsonrpc::interface::PoolListType,
+    jsonrpc::interface::{CompactionType, PoolInfoType, PoolListType, ThinMetaSpareType},
     stratis::{StratisError, StratisResult},
 };
 
@@ -376,3 +377,186 @@ pub async fn pool_has_passphrase(
         Err(StratisError::Msg(format!("Pool with {id} not found")))
     }
 }
+
+// stratis-min pool info
+pub async fn pool_info(engine: Arc<dyn Engine>, name: &str) -> StratisResult<Option<PoolInfoType>> {
+    let guard = engine
+        .get_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (_, _, pool) = guard.as_tuple();
+    Ok(Some((
+        *pool.total_physical_size().bytes(),
+        *pool.total_allocated_size().bytes(),
+        pool.total_physical_used().map(|u| *u.bytes()),
+        pool.fs_limit(),
+        pool.overprov_enabled(),
+        pool.out_of_alloc_space(),
+        pool.avail_actions().to_string(),
+        pool.metadata_version() as u64,
+    )))
+}
+
+// stratis-min pool thin-meta-spare
+pub async fn pool_thin_meta_spare(
+    engine: Arc<dyn Engine>,
+    id: PoolIdentifier<PoolUuid>,
+    action: ThinMetaSpareAction,
+) -> StratisResult<Option<ThinMetaSpareType>> {
+    let info = engine.thin_meta_spare(id, action).await?;
+    Ok(Some((
+        *info.spare_size.bytes(),
+        *info.meta_size.bytes(),
+        info.last_validated
+            .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true)),
+    )))
+}
+
+// stratis-min pool extend-data
+pub async fn pool_grow_physical(
+    engine: Arc<dyn Engine>,
+    name: &str,
+    device: Option<DevUuid>,
+) -> StratisResult<bool> {
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (pool_name, pool_uuid, pool) = guard.as_mut_tuple();
+    let devices = match device {
+        Some(dev_uuid) => vec![dev_uuid],
+        None => pool
+            .blockdevs()
+            .into_iter()
+            .filter(|(_, tier, _)| *tier == BlockDevTier::Data)
+            .map(|(dev_uuid, _, _)| dev_uuid)
+            .collect::<Vec<_>>(),
+    };
+    // Each device grown is reported as progress of the job which grows
+    // them, if any. If the job is cancelled, the devices not yet grown are
+    // left as they are.
+    let job = Job::current();
+    let total = devices.len() as u64;
+    block_in_place(|| {
+        devices
+            .into_iter()
+            .enumerate()
+            .try_fold(false, |changed, (index, dev_uuid)| {
+                if let Some(ref job) = job {
+                    job.check_cancelled()?;
+                }
+                let grown = pool
+                    .grow_physical(&pool_name, pool_uuid, dev_uuid)?
+                    .0
+                    .is_changed();
+                if let Some(ref job) = job {
+                    job.set_done(index as u64 + 1, total);
+                }
+                Ok(grown || changed)
+            })
+    })
+}
+
+// stratis-min pool set-fs-limit
+pub async fn pool_set_fs_limit(
+    engine: Arc<dyn Engine>,
+    name: &str,
+    new_limit: u64,
+) -> StratisResult<bool> {
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (pool_name, pool_uuid, pool) = guard.as_mut_tuple();
+    if pool.fs_limit() == new_limit {
+        return Ok(false);
+    }
+    block_in_place(|| {
+        pool.set_fs_limit(&pool_name, pool_uuid, new_limit)?;
+        Ok(true)
+    })
+}
+
+// stratis-min pool apply-batch
+pub async fn pool_apply_batch(
+    engine: Arc<dyn Engine>,
+    name: &str,
+    ops: Vec<BatchOperation>,
+) -> StratisResult<Vec<String>> {
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (pool_name, pool_uuid, pool) = guard.as_mut_tuple();
+    block_in_place(|| {
+        let changes = pool.apply_batch(&pool_name, pool_uuid, &ops)?;
+        Ok(changes.iter().map(|c| c.to_string()).collect())
+    })
+}
+
+// stratis-min pool overprovision
+pub async fn pool_set_overprov_mode(
+    engine: Arc<dyn Engine>,
+    name: &str,
+    enabled: bool,
+) -> StratisResult<bool> {
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+    if pool.overprov_enabled() == enabled {
+        return Ok(false);
+    }
+    block_in_place(|| {
+        pool.set_overprov_mode(&pool_name, enabled)?;
+        Ok(true)
+    })
+}
+
+// stratis-min pool metadata
+pub async fn pool_metadata(
+    engine: Arc<dyn Engine>,
+    name: &str,
+    current: bool,
+) -> StratisResult<String> {
+    let guard = engine
+        .get_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (pool_name, _, pool) = guard.as_tuple();
+    if current {
+        pool.current_metadata(&pool_name)
+    } else {
+        pool.last_metadata()
+    }
+}
+
+// stratis-min pool layout
+pub async fn pool_layout(engine: Arc<dyn Engine>, name: &str) -> StratisResult<String> {
+    let guard = engine
+        .get_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (pool_name, _, pool) = guard.as_tuple();
+    Ok(serde_json::to_string(&pool.layout(&pool_name)?)?)
+}
+
+// stratis-min pool compact
+pub async fn pool_compact(
+    engine: Arc<dyn Engine>,
+    name: &str,
+) -> StratisResult<Option<CompactionType>> {
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (pool_name, pool_uuid, pool) = guard.as_mut_tuple();
+    let report = block_in_place(|| pool.compact(&pool_name, pool_uuid))?;
+    Ok(Some((
+        report.segments_before as u64,
+        report.segments_after as u64,
+        *report.moved.bytes(),
+        *report.data_extension.bytes(),
+    )))
+}
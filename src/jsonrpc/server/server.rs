@@ -5,10 +5,13 @@
 #[cfg(feature = "systemd_compat")]
 use std::collections::HashMap;
 use std::{
-    fs::{create_dir_all, remove_file},
+    fs::{create_dir_all, remove_file, set_permissions, Permissions},
     future::Future,
     io::{IoSlice, IoSliceMut},
-    os::unix::io::{AsRawFd, OwnedFd, RawFd},
+    os::unix::{
+        fs::PermissionsExt,
+        io::{AsRawFd, OwnedFd, RawFd},
+    },
     path::Path,
     pin::Pin,
     sync::Arc,
@@ -28,6 +31,7 @@ use nix::{
     unistd::close,
 };
 use serde::Serialize;
+use serde_json::{Map, Value};
 use tokio::{io::unix::AsyncFd, task::JoinHandle};
 
 #[cfg(feature = "systemd_compat")]
@@ -35,19 +39,34 @@ use crate::systemd;
 use crate::{
     engine::Engine,
     jsonrpc::{
-        consts::RPC_SOCKADDR,
+        consts::{OP_ERR_PERMISSION_DENIED, OP_OK, OP_OK_STR, RPC_ACCESS_CONFIG, RPC_SOCKADDR},
         interface::{IpcResult, StratisParamType, StratisParams, StratisRet},
-        server::{filesystem, key, pool, report, utils::stratis_result_to_return},
+        protocol::{
+            check_version, find_method, from_legacy_ret, hello, list_methods, to_legacy_params,
+            unknown_method, MethodSchema, RpcError, VersionedRequest, VersionedResponse, HELLO,
+            LIST_METHODS,
+        },
+        server::{
+            access::{AccessPolicy, PeerCreds},
+            blockdev, config, filesystem, job, key, pool, report,
+            utils::{stratis_result_to_return, ReturnCodes},
+        },
     },
-    stratis::{StratisError, StratisResult},
+    stratis::{Caller, StratisError, StratisResult},
 };
 
 impl StratisParams {
-    async fn process(self, engine: Arc<dyn Engine>) -> IpcResult<StratisRet> {
+    /// Process the request, reporting errors with the given return codes.
+    pub(super) async fn process(
+        self,
+        engine: Arc<dyn Engine>,
+        codes: ReturnCodes,
+    ) -> IpcResult<StratisRet> {
         match self.type_ {
             StratisParamType::KeySet(key_desc) => {
                 let fd = expects_fd!(self.fd_opt, true);
                 Ok(StratisRet::KeySet(stratis_result_to_return(
+                    codes,
                     key::key_set(engine, &key_desc, fd).await,
                     None,
                 )))
@@ -55,6 +74,7 @@ impl StratisParams {
             StratisParamType::KeyUnset(key_desc) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::KeyUnset(stratis_result_to_return(
+                    codes,
                     key::key_unset(engine, &key_desc).await,
                     false,
                 )))
@@ -62,6 +82,7 @@ impl StratisParams {
             StratisParamType::KeyList => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::KeyList(stratis_result_to_return(
+                    codes,
                     key::key_list(engine).await,
                     Vec::new(),
                 )))
@@ -70,6 +91,7 @@ impl StratisParams {
                 expects_fd!(self.fd_opt, false);
                 let path_ref: Vec<_> = paths.iter().map(|p| p.as_path()).collect();
                 Ok(StratisRet::PoolCreate(stratis_result_to_return(
+                    codes,
                     pool::pool_create(
                         engine,
                         name.as_str(),
@@ -83,6 +105,7 @@ impl StratisParams {
             StratisParamType::PoolRename(name, new_name) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolRename(stratis_result_to_return(
+                    codes,
                     pool::pool_rename(engine, name.as_str(), new_name.as_str()).await,
                     false,
                 )))
@@ -91,6 +114,7 @@ impl StratisParams {
                 expects_fd!(self.fd_opt, false);
                 let path_ref: Vec<_> = paths.iter().map(|p| p.as_path()).collect();
                 Ok(StratisRet::PoolAddData(stratis_result_to_return(
+                    codes,
                     pool::pool_add_data(engine, name.as_str(), path_ref.as_slice()).await,
                     false,
                 )))
@@ -99,6 +123,7 @@ impl StratisParams {
                 expects_fd!(self.fd_opt, false);
                 let path_ref: Vec<_> = paths.iter().map(|p| p.as_path()).collect();
                 Ok(StratisRet::PoolInitCache(stratis_result_to_return(
+                    codes,
                     pool::pool_init_cache(engine, name.as_str(), path_ref.as_slice()).await,
                     false,
                 )))
@@ -107,6 +132,7 @@ impl StratisParams {
                 expects_fd!(self.fd_opt, false);
                 let path_ref: Vec<_> = paths.iter().map(|p| p.as_path()).collect();
                 Ok(StratisRet::PoolAddCache(stratis_result_to_return(
+                    codes,
                     pool::pool_add_cache(engine, name.as_str(), path_ref.as_slice()).await,
                     false,
                 )))
@@ -114,12 +140,14 @@ impl StratisParams {
             StratisParamType::PoolDestroy(name) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolDestroy(stratis_result_to_return(
+                    codes,
                     pool::pool_destroy(engine, name.as_str()).await,
                     false,
                 )))
             }
             StratisParamType::PoolStart(id, unlock_method) => {
                 Ok(StratisRet::PoolStart(stratis_result_to_return(
+                    codes,
                     pool::pool_start(engine, id, unlock_method, self.fd_opt).await,
                     false,
                 )))
@@ -127,6 +155,7 @@ impl StratisParams {
             StratisParamType::PoolStop(id) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolStop(stratis_result_to_return(
+                    codes,
                     pool::pool_stop(engine, id).await,
                     false,
                 )))
@@ -138,6 +167,7 @@ impl StratisParams {
             StratisParamType::PoolBindKeyring(id, token_slot, key_desc) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolBindKeyring(stratis_result_to_return(
+                    codes,
                     pool::pool_bind_keyring(engine, id, token_slot, &key_desc).await,
                     false,
                 )))
@@ -145,6 +175,7 @@ impl StratisParams {
             StratisParamType::PoolBindClevis(id, token_slot, pin, clevis_info) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolBindClevis(stratis_result_to_return(
+                    codes,
                     pool::pool_bind_clevis(engine, id, token_slot, &pin, &clevis_info).await,
                     false,
                 )))
@@ -152,6 +183,7 @@ impl StratisParams {
             StratisParamType::PoolUnbindKeyring(id, token_slot) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolUnbindKeyring(stratis_result_to_return(
+                    codes,
                     pool::pool_unbind_keyring(engine, id, token_slot).await,
                     false,
                 )))
@@ -159,6 +191,7 @@ impl StratisParams {
             StratisParamType::PoolUnbindClevis(id, token_slot) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolUnbindClevis(stratis_result_to_return(
+                    codes,
                     pool::pool_unbind_clevis(engine, id, token_slot).await,
                     false,
                 )))
@@ -166,6 +199,7 @@ impl StratisParams {
             StratisParamType::PoolRebindKeyring(id, token_slot, key_desc) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolRebindKeyring(stratis_result_to_return(
+                    codes,
                     pool::pool_rebind_keyring(engine, id, token_slot, key_desc).await,
                     false,
                 )))
@@ -173,6 +207,7 @@ impl StratisParams {
             StratisParamType::PoolRebindClevis(id, token_slot) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolRebindClevis(stratis_result_to_return(
+                    codes,
                     pool::pool_rebind_clevis(engine, id, token_slot).await,
                     false,
                 )))
@@ -180,6 +215,7 @@ impl StratisParams {
             StratisParamType::PoolIsEncrypted(id) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolIsEncrypted(stratis_result_to_return(
+                    codes,
                     pool::pool_is_encrypted(engine, id).await,
                     false,
                 )))
@@ -187,6 +223,7 @@ impl StratisParams {
             StratisParamType::PoolIsStopped(id) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolIsStopped(stratis_result_to_return(
+                    codes,
                     pool::pool_is_stopped(engine, id).await,
                     false,
                 )))
@@ -194,6 +231,7 @@ impl StratisParams {
             StratisParamType::PoolIsBound(id) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolIsBound(stratis_result_to_return(
+                    codes,
                     pool::pool_is_bound(engine, id).await,
                     false,
                 )))
@@ -201,13 +239,151 @@ impl StratisParams {
             StratisParamType::PoolHasPassphrase(id) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolHasPassphrase(stratis_result_to_return(
+                    codes,
                     pool::pool_has_passphrase(engine, id).await,
                     false,
                 )))
             }
+            StratisParamType::PoolInfo(name) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolInfo(stratis_result_to_return(
+                    codes,
+                    pool::pool_info(engine, &name).await,
+                    None,
+                )))
+            }
+            StratisParamType::PoolGrowPhysical(name, dev_uuid) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolGrowPhysical(stratis_result_to_return(
+                    codes,
+                    pool::pool_grow_physical(engine, &name, dev_uuid).await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolCreateJob(name, paths, encryption_info) => {
+                expects_fd!(self.fd_opt, false);
+                let description = format!("Create pool {name}");
+                let work = {
+                    let engine = Arc::clone(&engine);
+                    async move {
+                        let path_ref: Vec<_> = paths.iter().map(|p| p.as_path()).collect();
+                        pool::pool_create(
+                            engine,
+                            name.as_str(),
+                            path_ref.as_slice(),
+                            encryption_info.as_ref(),
+                        )
+                        .await
+                    }
+                };
+                Ok(StratisRet::PoolCreateJob((
+                    job::job_start(&engine, &description, work),
+                    OP_OK,
+                    OP_OK_STR.to_string(),
+                )))
+            }
+            StratisParamType::PoolInitCacheJob(name, paths) => {
+                expects_fd!(self.fd_opt, false);
+                let description = format!("Initialize cache of pool {name}");
+                let work = {
+                    let engine = Arc::clone(&engine);
+                    async move {
+                        let path_ref: Vec<_> = paths.iter().map(|p| p.as_path()).collect();
+                        pool::pool_init_cache(engine, name.as_str(), path_ref.as_slice()).await
+                    }
+                };
+                Ok(StratisRet::PoolInitCacheJob((
+                    job::job_start(&engine, &description, work),
+                    OP_OK,
+                    OP_OK_STR.to_string(),
+                )))
+            }
+            StratisParamType::PoolGrowPhysicalJob(name, dev_uuid) => {
+                expects_fd!(self.fd_opt, false);
+                let description = format!("Grow physical devices of pool {name}");
+                let work = {
+                    let engine = Arc::clone(&engine);
+                    async move { pool::pool_grow_physical(engine, &name, dev_uuid).await }
+                };
+                Ok(StratisRet::PoolGrowPhysicalJob((
+                    job::job_start(&engine, &description, work),
+                    OP_OK,
+                    OP_OK_STR.to_string(),
+                )))
+            }
+            StratisParamType::PoolCompactJob(name) => {
+                expects_fd!(self.fd_opt, false);
+                let description = format!("Compact allocations of pool {name}");
+                let work = {
+                    let engine = Arc::clone(&engine);
+                    async move { pool::pool_compact(engine, &name).await }
+                };
+                Ok(StratisRet::PoolCompactJob((
+                    job::job_start(&engine, &description, work),
+                    OP_OK,
+                    OP_OK_STR.to_string(),
+                )))
+            }
+            StratisParamType::PoolThinMetaSpare(id, action) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolThinMetaSpare(stratis_result_to_return(
+                    codes,
+                    pool::pool_thin_meta_spare(engine, id, action).await,
+                    None,
+                )))
+            }
+            StratisParamType::PoolSetFsLimit(name, new_limit) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolSetFsLimit(stratis_result_to_return(
+                    codes,
+                    pool::pool_set_fs_limit(engine, &name, new_limit).await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolSetOverprovMode(name, enabled) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolSetOverprovMode(stratis_result_to_return(
+                    codes,
+                    pool::pool_set_overprov_mode(engine, &name, enabled).await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolMetadata(name, current) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolMetadata(stratis_result_to_return(
+                    codes,
+                    pool::pool_metadata(engine, &name, current).await,
+                    String::new(),
+                )))
+            }
+            StratisParamType::PoolLayout(name) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolLayout(stratis_result_to_return(
+                    codes,
+                    pool::pool_layout(engine, &name).await,
+                    String::new(),
+                )))
+            }
+            StratisParamType::PoolCompact(name) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolCompact(stratis_result_to_return(
+                    codes,
+                    pool::pool_compact(engine, &name).await,
+                    None,
+                )))
+            }
+            StratisParamType::PoolApplyBatch(name, ops) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolApplyBatch(stratis_result_to_return(
+                    codes,
+                    pool::pool_apply_batch(engine, &name, ops).await,
+                    Vec::new(),
+                )))
+            }
             StratisParamType::FsCreate(pool_name, fs_name) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::FsCreate(stratis_result_to_return(
+                    codes,
                     filesystem::filesystem_create(engine, &pool_name, &fs_name).await,
                     false,
                 )))
@@ -221,6 +397,7 @@ impl StratisParams {
             StratisParamType::FsDestroy(pool_name, fs_name) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::FsDestroy(stratis_result_to_return(
+                    codes,
                     filesystem::filesystem_destroy(engine, &pool_name, &fs_name).await,
                     false,
                 )))
@@ -228,6 +405,7 @@ impl StratisParams {
             StratisParamType::FsRename(pool_name, fs_name, new_fs_name) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::FsRename(stratis_result_to_return(
+                    codes,
                     filesystem::filesystem_rename(engine, &pool_name, &fs_name, &new_fs_name).await,
                     false,
                 )))
@@ -235,10 +413,167 @@ impl StratisParams {
             StratisParamType::FsOrigin(pool_name, fs_name) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::FsOrigin(stratis_result_to_return(
+                    codes,
                     filesystem::filesystem_origin(engine, &pool_name, &fs_name).await,
                     None,
                 )))
             }
+            StratisParamType::FsInfo(pool_name, fs_name) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::FsInfo(stratis_result_to_return(
+                    codes,
+                    filesystem::filesystem_info(engine, &pool_name, &fs_name).await,
+                    None,
+                )))
+            }
+            StratisParamType::FsSnapshot(pool_name, origin_name, snapshot_name) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::FsSnapshot(stratis_result_to_return(
+                    codes,
+                    filesystem::filesystem_snapshot(
+                        engine,
+                        &pool_name,
+                        &origin_name,
+                        &snapshot_name,
+                    )
+                    .await,
+                    false,
+                )))
+            }
+            StratisParamType::FsClone(
+                pool_name,
+                fs_name,
+                target_pool_name,
+                new_name,
+                preserve_uuid,
+            ) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::FsClone(stratis_result_to_return(
+                    codes,
+                    filesystem::filesystem_clone(
+                        engine,
+                        &pool_name,
+                        &fs_name,
+                        &target_pool_name,
+                        &new_name,
+                        preserve_uuid,
+                    )
+                    .await,
+                    false,
+                )))
+            }
+            StratisParamType::FsSetSizeLimit(pool_name, fs_name, limit) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::FsSetSizeLimit(stratis_result_to_return(
+                    codes,
+                    filesystem::filesystem_set_size_limit(engine, &pool_name, &fs_name, limit)
+                        .await,
+                    false,
+                )))
+            }
+            StratisParamType::FsSetReservation(pool_name, fs_name, reservation) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::FsSetReservation(stratis_result_to_return(
+                    codes,
+                    filesystem::filesystem_set_reservation(
+                        engine,
+                        &pool_name,
+                        &fs_name,
+                        reservation,
+                    )
+                    .await,
+                    false,
+                )))
+            }
+            StratisParamType::FsSetMergeScheduled(pool_name, fs_name, scheduled) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::FsSetMergeScheduled(stratis_result_to_return(
+                    codes,
+                    filesystem::filesystem_set_merge_scheduled(
+                        engine, &pool_name, &fs_name, scheduled,
+                    )
+                    .await,
+                    false,
+                )))
+            }
+            StratisParamType::FsMetadata(pool_name, fs_name, current) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::FsMetadata(stratis_result_to_return(
+                    codes,
+                    filesystem::filesystem_metadata(
+                        engine,
+                        &pool_name,
+                        fs_name.as_deref(),
+                        current,
+                    )
+                    .await,
+                    String::new(),
+                )))
+            }
+            StratisParamType::BlockdevList => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::BlockdevList(
+                    blockdev::blockdev_list(engine).await,
+                ))
+            }
+            StratisParamType::BlockdevSetUserInfo(pool_name, dev_uuid, user_info) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::BlockdevSetUserInfo(stratis_result_to_return(
+                    codes,
+                    blockdev::blockdev_set_user_info(
+                        engine,
+                        &pool_name,
+                        dev_uuid,
+                        user_info.as_deref(),
+                    )
+                    .await,
+                    false,
+                )))
+            }
+            StratisParamType::JobList => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::JobList(job::job_list(engine)))
+            }
+            StratisParamType::JobStatus(id) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::JobStatus(stratis_result_to_return(
+                    codes,
+                    job::job_status(engine, id),
+                    None,
+                )))
+            }
+            StratisParamType::JobCancel(id) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::JobCancel(stratis_result_to_return(
+                    codes,
+                    job::job_cancel(engine, id),
+                    false,
+                )))
+            }
+            StratisParamType::ApplyConfiguration(config, dry_run) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::ApplyConfiguration(stratis_result_to_return(
+                    codes,
+                    config::config_apply(engine, config, dry_run).await,
+                    Vec::new(),
+                )))
+            }
+            StratisParamType::PoolExportConfig(name) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolExportConfig(stratis_result_to_return(
+                    codes,
+                    config::pool_export_config(engine, &name).await,
+                    None,
+                )))
+            }
+            StratisParamType::PoolImportConfig(export, blockdevs) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolImportConfig(stratis_result_to_return(
+                    codes,
+                    config::pool_import_config(engine, &export, blockdevs).await,
+                    false,
+                )))
+            }
             StratisParamType::Report => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::Report(report::report(engine).await))
@@ -247,9 +582,151 @@ impl StratisParams {
     }
 }
 
+/// A request in either the legacy or the versioned encoding.
+pub enum StratisRequest {
+    Legacy(StratisParams),
+    Versioned(VersionedRequest, Option<RawFd>),
+}
+
+impl StratisRequest {
+    /// Whether the request only queries state. A versioned request which can
+    /// not be converted to a legacy request will fail without reaching the
+    /// engine, so it is treated as read-only.
+    fn is_read_only(&self) -> bool {
+        match self {
+            StratisRequest::Legacy(params) => params.type_.is_read_only(),
+            StratisRequest::Versioned(request, _) => {
+                if request.method == HELLO || request.method == LIST_METHODS {
+                    return true;
+                }
+                find_method(&request.method)
+                    .map(|schema| is_read_only_named(schema, &request.params))
+                    .unwrap_or(true)
+            }
+        }
+    }
+
+    /// Refuse the request because the peer is not permitted to make it.
+    fn deny(self) -> StratisResponse {
+        let message = "Permission denied: this request may only be made by root".to_string();
+        match self {
+            StratisRequest::Legacy(params) => {
+                discard_fd(params.fd_opt);
+                StratisResponse::Legacy(Err(message))
+            }
+            StratisRequest::Versioned(request, fd_opt) => {
+                discard_fd(fd_opt);
+                StratisResponse::Versioned(VersionedResponse::new(
+                    request.version,
+                    Err(RpcError::new(OP_ERR_PERMISSION_DENIED, message)),
+                ))
+            }
+        }
+    }
+
+    async fn process(self, engine: Arc<dyn Engine>) -> StratisResponse {
+        match self {
+            StratisRequest::Legacy(params) => {
+                StratisResponse::Legacy(params.process(engine, ReturnCodes::Legacy).await)
+            }
+            StratisRequest::Versioned(request, fd_opt) => {
+                StratisResponse::Versioned(process_versioned(request, fd_opt, engine).await)
+            }
+        }
+    }
+}
+
+/// A response, encoded in the same way as the request it answers.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum StratisResponse {
+    Legacy(IpcResult<StratisRet>),
+    Versioned(VersionedResponse),
+}
+
+/// Close a file descriptor received with a request that will not be
+/// processed.
+fn discard_fd(fd_opt: Option<RawFd>) {
+    if let Some(fd) = fd_opt {
+        if let Err(e) = close(fd) {
+            warn!(
+                "Failed to close file descriptor {}: {}; a file descriptor may have been leaked",
+                fd, e
+            );
+        }
+    }
+}
+
+async fn process_versioned(
+    request: VersionedRequest,
+    fd_opt: Option<RawFd>,
+    engine: Arc<dyn Engine>,
+) -> VersionedResponse {
+    let VersionedRequest {
+        version,
+        method,
+        params,
+    } = request;
+
+    if method == HELLO {
+        discard_fd(fd_opt);
+        let (version, result) = hello(&params);
+        return VersionedResponse::new(version, result);
+    }
+    if let Err(e) = check_version(version) {
+        discard_fd(fd_opt);
+        return VersionedResponse::new(version, Err(e));
+    }
+    if method == LIST_METHODS {
+        discard_fd(fd_opt);
+        return VersionedResponse::new(version, list_methods());
+    }
+
+    let result = match find_method(&method) {
+        Some(schema) => process_named(schema, params, fd_opt, engine).await,
+        None => {
+            discard_fd(fd_opt);
+            Err(unknown_method(&method))
+        }
+    };
+    VersionedResponse::new(version, result)
+}
+
+/// Whether a call to the method described by schema with named parameters
+/// only queries state. A call whose parameters do not match the schema will
+/// fail without reaching the engine, so it is treated as read-only.
+pub fn is_read_only_named(schema: &MethodSchema, params: &Map<String, Value>) -> bool {
+    to_legacy_params(schema, params.clone())
+        .map(|type_| type_.is_read_only())
+        .unwrap_or(true)
+}
+
+/// Process a call to the method described by schema with named parameters.
+/// This is shared by the versioned JSON-RPC protocol and any other interface
+/// which describes its methods with the same schemas.
+pub async fn process_named(
+    schema: &MethodSchema,
+    params: Map<String, Value>,
+    fd_opt: Option<RawFd>,
+    engine: Arc<dyn Engine>,
+) -> Result<Map<String, Value>, RpcError> {
+    let type_ = match to_legacy_params(schema, params) {
+        Ok(type_) => type_,
+        Err(e) => {
+            discard_fd(fd_opt);
+            return Err(e);
+        }
+    };
+    let ret = StratisParams { type_, fd_opt }
+        .process(engine, ReturnCodes::Stable)
+        .await;
+    from_legacy_ret(schema, ret)
+}
+
 pub struct StratisServer {
     engine: Arc<dyn Engine>,
     listener: StratisUnixListener,
+    policy: Arc<AccessPolicy>,
 }
 
 impl StratisServer {
@@ -257,9 +734,17 @@ impl StratisServer {
     where
         P: AsRef<Path>,
     {
+        let policy = AccessPolicy::load(RPC_ACCESS_CONFIG).unwrap_or_else(|e| {
+            warn!(
+                "Failed to load JSON-RPC access configuration: {}; only root will be permitted to make requests",
+                e
+            );
+            AccessPolicy::default()
+        });
         let server = StratisServer {
             engine,
             listener: StratisUnixListener::bind(path)?,
+            policy: Arc::new(policy),
         };
         #[cfg(feature = "systemd_compat")]
         systemd::notify(
@@ -278,16 +763,28 @@ impl StratisServer {
             None => return Ok(false),
         };
         let engine = self.engine.clone();
+        let policy = Arc::clone(&self.policy);
         tokio::spawn(async move {
             let fd = Arc::clone(&request_handler.fd);
-            let params = match request_handler.await {
-                Ok(p) => p,
+            let peer = request_handler.peer.clone();
+            let request = match request_handler.await {
+                Ok(r) => r,
                 Err(e) => {
                     warn!("Failed to receive request from connection: {}", e);
                     return;
                 }
             };
-            let ret = params.process(engine).await;
+            let ret = if policy.permits(&peer, request.is_read_only()) {
+                Caller::Socket { uid: peer.uid() }
+                    .scope(request.process(engine))
+                    .await
+            } else {
+                warn!(
+                    "Refused request from process with UID {} which is not permitted to make it",
+                    peer.uid()
+                );
+                request.deny()
+            };
             if let Err(e) = StratisUnixResponse::new(fd, ret).await {
                 warn!("Failed to respond to request: {}", e);
             }
@@ -341,7 +838,7 @@ fn handle_cmsgs(cmsgs: Vec<ControlMessageOwned>) -> StratisResult<Option<RawFd>>
     }
 }
 
-fn try_recvmsg(fd: RawFd) -> StratisResult<StratisParams> {
+fn try_recvmsg(fd: RawFd) -> StratisResult<StratisRequest> {
     let mut cmsg_space = cmsg_space!([RawFd; 1]);
     let mut vec = vec![0; 65536];
     let (cmsgs, bytes) = {
@@ -352,9 +849,26 @@ fn try_recvmsg(fd: RawFd) -> StratisResult<StratisParams> {
 
     let fd_opt = handle_cmsgs(cmsgs)?;
     vec.truncate(bytes);
-    serde_json::from_slice(vec.as_slice())
-        .map(|type_| StratisParams { type_, fd_opt })
-        .map_err(StratisError::from)
+    let value = serde_json::from_slice::<Value>(vec.as_slice()).map_err(|e| {
+        discard_fd(fd_opt);
+        StratisError::from(e)
+    })?;
+    // Only requests in the versioned encoding carry a version.
+    if value.get("version").is_some() {
+        serde_json::from_value(value)
+            .map(|request| StratisRequest::Versioned(request, fd_opt))
+            .map_err(|e| {
+                discard_fd(fd_opt);
+                StratisError::from(e)
+            })
+    } else {
+        serde_json::from_value(value)
+            .map(|type_| StratisRequest::Legacy(StratisParams { type_, fd_opt }))
+            .map_err(|e| {
+                discard_fd(fd_opt);
+                StratisError::from(e)
+            })
+    }
 }
 
 fn try_sendmsg<S>(fd: RawFd, ret: &S) -> StratisResult<()>
@@ -374,10 +888,11 @@ where
 
 pub struct StratisUnixRequest {
     fd: Arc<AsyncFd<RawFd>>,
+    peer: PeerCreds,
 }
 
 impl Future for StratisUnixRequest {
-    type Output = StratisResult<StratisParams>;
+    type Output = StratisResult<StratisRequest>;
 
     fn poll(self: Pin<&mut Self>, ctxt: &mut Context<'_>) -> Poll<Self::Output> {
         let poll_res = ready!(self.fd.poll_read_ready(ctxt));
@@ -393,11 +908,11 @@ impl Future for StratisUnixRequest {
 
 pub struct StratisUnixResponse {
     fd: Arc<AsyncFd<RawFd>>,
-    ret: IpcResult<StratisRet>,
+    ret: StratisResponse,
 }
 
 impl StratisUnixResponse {
-    pub fn new(fd: Arc<AsyncFd<RawFd>>, ret: IpcResult<StratisRet>) -> StratisUnixResponse {
+    pub fn new(fd: Arc<AsyncFd<RawFd>>, ret: StratisResponse) -> StratisUnixResponse {
         StratisUnixResponse { fd, ret }
     }
 }
@@ -444,6 +959,9 @@ impl StratisUnixListener {
             })?;
         fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(flags | OFlag::O_NONBLOCK))?;
         bind(fd.as_raw_fd(), &UnixAddr::new(path.as_ref())?)?;
+        // Any user may connect; whether a request is permitted is decided
+        // by the credentials of the peer.
+        set_permissions(path.as_ref(), Permissions::from_mode(0o666))?;
         listen(&fd, Backlog::new(0).expect("0 is always valid"))?;
         Ok(StratisUnixListener {
             fd: AsyncFd::new(fd)?,
@@ -457,8 +975,18 @@ fn try_accept(fd: RawFd) -> StratisResult<StratisUnixRequest> {
         StratisError::Msg("Unrecognized flag types returned from fcntl".to_string())
     })?;
     fcntl(fd, FcntlArg::F_SETFL(flags | OFlag::O_NONBLOCK))?;
+    let peer = match PeerCreds::from_socket(fd) {
+        Ok(peer) => peer,
+        Err(e) => {
+            if let Err(e) = close(fd) {
+                warn!("Failed to close connection {}: {}", fd, e);
+            }
+            return Err(e);
+        }
+    };
     Ok(StratisUnixRequest {
         fd: Arc::new(AsyncFd::new(fd)?),
+        peer,
     })
 }
 
@@ -491,3 +1019,125 @@ pub fn run_server(engine: Arc<dyn Engine>) -> JoinHandle<()> {
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    /// The requests through which each method of the Pool trait is made
+    /// available to JSON-RPC clients.
+    const POOL_METHOD_REQUESTS: &[(&str, &[&str])] = &[
+        ("init_cache", &["PoolInitCache", "PoolInitCacheJob"]),
+        ("create_filesystems", &["FsCreate"]),
+        ("add_blockdevs", &["PoolAddData", "PoolAddCache"]),
+        ("bind_clevis", &["PoolBindClevis"]),
+        ("bind_keyring", &["PoolBindKeyring"]),
+        ("rebind_keyring", &["PoolRebindKeyring"]),
+        ("rebind_clevis", &["PoolRebindClevis"]),
+        ("unbind_keyring", &["PoolUnbindKeyring"]),
+        ("unbind_clevis", &["PoolUnbindClevis"]),
+        ("destroy_filesystems", &["FsDestroy"]),
+        ("rename_filesystem", &["FsRename"]),
+        ("snapshot_filesystem", &["FsSnapshot"]),
+        ("total_physical_size", &["PoolList", "PoolInfo"]),
+        ("total_allocated_size", &["PoolInfo"]),
+        ("total_physical_used", &["PoolList", "PoolInfo"]),
+        ("filesystems", &["FsList"]),
+        ("get_filesystem_by_name", &["FsInfo", "FsOrigin"]),
+        ("blockdevs", &["BlockdevList", "PoolGrowPhysical"]),
+        ("get_blockdev", &["BlockdevSetUserInfo"]),
+        ("set_blockdev_user_info", &["BlockdevSetUserInfo"]),
+        ("has_cache", &["PoolList"]),
+        ("is_encrypted", &["PoolList", "PoolIsEncrypted"]),
+        ("encryption_info", &["PoolIsBound", "PoolHasPassphrase"]),
+        ("avail_actions", &["PoolInfo"]),
+        ("fs_limit", &["PoolInfo", "PoolSetFsLimit"]),
+        ("set_fs_limit", &["PoolSetFsLimit"]),
+        ("overprov_enabled", &["PoolInfo", "PoolSetOverprovMode"]),
+        ("set_overprov_mode", &["PoolSetOverprovMode"]),
+        ("out_of_alloc_space", &["PoolInfo"]),
+        (
+            "grow_physical",
+            &["PoolGrowPhysical", "PoolGrowPhysicalJob"],
+        ),
+        ("set_fs_size_limit", &["FsSetSizeLimit"]),
+        ("set_fs_reservation", &["FsSetReservation"]),
+        ("current_metadata", &["PoolMetadata"]),
+        ("last_metadata", &["PoolMetadata"]),
+        ("metadata_version", &["PoolInfo"]),
+        ("current_fs_metadata", &["FsMetadata"]),
+        ("last_fs_metadata", &["FsMetadata"]),
+        ("set_fs_merge_scheduled", &["FsSetMergeScheduled"]),
+        ("apply_batch", &["PoolApplyBatch"]),
+        ("layout", &["PoolLayout"]),
+        ("compact", &["PoolCompact", "PoolCompactJob"]),
+        (
+            "create_filesystem_with_uuid",
+            &["PoolImportConfig", "ApplyConfiguration"],
+        ),
+    ];
+
+    /// Pool methods which are accessors subsumed by another method in the
+    /// JSON-RPC API and so need no request of their own.
+    const POOL_METHODS_EXEMPT: &[&str] = &[
+        // Filesystems are identified by name over JSON-RPC.
+        "get_filesystem",
+        // Blockdevs are only modified through set_blockdev_user_info.
+        "get_mut_blockdev",
+        // Superseded by encryption_info.
+        "encryption_info_legacy",
+    ];
+
+    /// Get the names of the methods of the Pool trait from its definition.
+    fn pool_trait_methods() -> Vec<&'static str> {
+        let source = include_str!("../../engine/engine.rs");
+        let start = source
+            .find("pub trait Pool:")
+            .expect("Pool trait is defined in engine.rs");
+        let body = &source[start..];
+        let end = body.find("\n}\n").expect("Pool trait definition is closed");
+        body[..end]
+            .lines()
+            .filter_map(|line| line.trim_start().strip_prefix("fn "))
+            .map(|sig| {
+                sig.split(['(', '<'])
+                    .next()
+                    .expect("split always yields one item")
+            })
+            .collect()
+    }
+
+    #[test]
+    /// Verify that every method of the Pool trait is exposed through some
+    /// JSON-RPC request and that every request named is handled by the
+    /// server.
+    fn test_pool_methods_have_requests() {
+        let methods = pool_trait_methods();
+        assert!(methods.contains(&"init_cache"));
+
+        let unmapped = methods
+            .iter()
+            .filter(|m| {
+                !POOL_METHODS_EXEMPT.contains(m)
+                    && !POOL_METHOD_REQUESTS.iter().any(|(name, _)| name == *m)
+            })
+            .collect::<Vec<_>>();
+        assert!(
+            unmapped.is_empty(),
+            "Pool methods with no JSON-RPC request: {unmapped:?}"
+        );
+
+        let server = include_str!("server.rs");
+        for (method, requests) in POOL_METHOD_REQUESTS {
+            assert!(
+                methods.contains(method),
+                "{method} is not a method of the Pool trait"
+            );
+            for request in *requests {
+                assert!(
+                    server.contains(&format!("StratisParamType::{request}("))
+                        || server.contains(&format!("StratisParamType::{request} =>")),
+                    "Request {request} for {method} is not handled by the server"
+                );
+            }
+        }
+    }
+}
@@ -3,10 +3,22 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use crate::{
-    jsonrpc::consts::{OP_ERR, OP_OK, OP_OK_STR},
+    jsonrpc::{
+        consts::{OP_ERR, OP_OK, OP_OK_STR},
+        protocol::error_code,
+    },
     stratis::StratisResult,
 };
 
+/// The return codes with which a response reports errors.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReturnCodes {
+    /// The legacy encoding reports OP_ERR for every error.
+    Legacy,
+    /// The versioned protocol reports a stable code for each kind of error.
+    Stable,
+}
+
 macro_rules! expects_fd {
     ($fd_opt:expr, true) => {
         match $fd_opt {
@@ -34,9 +46,19 @@ macro_rules! expects_fd {
     };
 }
 
-pub fn stratis_result_to_return<T>(result: StratisResult<T>, default_value: T) -> (T, u16, String) {
+pub fn stratis_result_to_return<T>(
+    codes: ReturnCodes,
+    result: StratisResult<T>,
+    default_value: T,
+) -> (T, u16, String) {
     match result {
         Ok(r) => (r, OP_OK, OP_OK_STR.to_string()),
-        Err(e) => (default_value, OP_ERR, e.to_string()),
+        Err(e) => {
+            let code = match codes {
+                ReturnCodes::Legacy => OP_ERR,
+                ReturnCodes::Stable => error_code(&e),
+            };
+            (default_value, code, e.to_string())
+        }
     }
 }
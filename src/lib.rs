@@ -47,5 +47,8 @@ pub mod stratis;
 #[cfg(feature = "min")]
 pub mod jsonrpc;
 
+#[cfg(feature = "varlink")]
+pub mod varlink;
+
 #[cfg(feature = "systemd_compat")]
 pub mod systemd;
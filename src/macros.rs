@@ -14,11 +14,14 @@ macro_rules! test_async {
     };
 }
 
+/// Run expr in a blocking thread. If the calling task is running a job, the
+/// blocking work reports its progress to the same job.
 #[cfg(feature = "engine")]
 macro_rules! spawn_blocking {
-    ($expr:expr) => {
-        tokio::task::spawn_blocking(move || $expr)
+    ($expr:expr) => {{
+        let job = $crate::engine::Job::current();
+        tokio::task::spawn_blocking(move || $crate::engine::Job::run_sync_opt(job, move || $expr))
             .await
             .map_err($crate::stratis::StratisError::from)
-    };
+    }};
 }
@@ -14,6 +14,8 @@ use tokio::{io::unix::AsyncFd, task::spawn};
 
 #[cfg(feature = "dbus_enabled")]
 use crate::dbus_api::DbusAction;
+#[cfg(feature = "metrics")]
+use crate::stratis::metrics::METRICS;
 use crate::{
     engine::{get_dm, get_dm_init, Engine},
     stratis::errors::{StratisError, StratisResult},
@@ -45,14 +47,25 @@ pub async fn dm_event_thread(
 
         // NOTE: May need to change order of pool_evented() and fs_evented()
 
+        #[cfg(feature = "metrics")]
+        METRICS.record_dm_event();
+
         #[cfg(any(feature = "min", not(feature = "dbus_enabled")))]
         {
-            let _ = engine.pool_evented(Some(&evented)).await;
-            let _ = engine.fs_evented(Some(&evented)).await;
+            #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+            let pool_diffs = engine.pool_evented(Some(&evented)).await;
+            #[cfg(feature = "metrics")]
+            METRICS.record_pool_diffs(&pool_diffs);
+            #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+            let fs_diffs = engine.fs_evented(Some(&evented)).await;
+            #[cfg(feature = "metrics")]
+            METRICS.record_fs_diffs(&fs_diffs);
         }
         #[cfg(feature = "dbus_enabled")]
         {
             let pool_diffs = engine.pool_evented(Some(&evented)).await;
+            #[cfg(feature = "metrics")]
+            METRICS.record_pool_diffs(&pool_diffs);
             for action in DbusAction::from_pool_diffs(pool_diffs) {
                 if let Err(e) = sender.send(action) {
                     warn!(
@@ -62,6 +75,8 @@ pub async fn dm_event_thread(
                 }
             }
             let fs_diffs = engine.fs_evented(Some(&evented)).await;
+            #[cfg(feature = "metrics")]
+            METRICS.record_fs_diffs(&fs_diffs);
             for action in DbusAction::from_fs_diffs(fs_diffs) {
                 if let Err(e) = sender.send(action) {
                     warn!(
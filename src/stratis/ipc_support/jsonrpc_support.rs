@@ -51,6 +51,8 @@ pub async fn setup(
     recv: UnboundedReceiver<UdevEngineEvent>,
 ) -> StratisResult<()> {
     let mut udev_join = handle_udev(engine.clone(), recv);
+    #[cfg(feature = "varlink")]
+    crate::varlink::run_server(engine.clone());
     let mut server_join = run_server(engine);
 
     select! {
@@ -0,0 +1,554 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Structured logging for stratisd.
+//!
+//! stratisd can send its log messages to journald using the journald native
+//! protocol rather than writing them to stderr. When logging to journald,
+//! each message carries the source location of the log statement as
+//! additional journal fields.
+//!
+//! Independently of the log level, every state-changing engine operation
+//! emits an audit record. When the journald backend is in use, the audit
+//! record is sent as a journal entry with the fields STRATIS_ACTION,
+//! STRATIS_RESULT and, where applicable, STRATIS_POOL_UUID,
+//! STRATIS_POOL_NAME, STRATIS_FS_UUID and STRATIS_DEV_UUID. If the
+//! operation was requested by a client, the record also identifies the
+//! client with the fields STRATIS_CALLER, which is either "dbus" or
+//! "socket", STRATIS_CALLER_SENDER, the unique bus name of a D-Bus client,
+//! and STRATIS_CALLER_UID, the user ID of the client. Otherwise the
+//! audit record is logged as a single line of JSON with the target
+//! "stratisd::audit" at the info level. If the log level filters out that
+//! message, the line is written to stderr directly, prefixed with the
+//! target.
+
+use std::{
+    fmt::{self, Display},
+    future::Future,
+    os::unix::net::UnixDatagram,
+    str::FromStr,
+    sync::Arc,
+};
+
+use env_logger::{Builder, Logger};
+use log::{Level, Log, Metadata, Record};
+use once_cell::sync::OnceCell;
+use serde_json::{Map, Value};
+
+use crate::{
+    engine::{DevUuid, FilesystemUuid, PoolIdentifier, PoolUuid},
+    stratis::{StratisError, StratisResult},
+};
+
+/// The socket on which journald listens for messages in its native protocol.
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+const SYSLOG_IDENTIFIER: &str = "stratisd";
+
+const AUDIT_TARGET: &str = "stratisd::audit";
+
+/// Socket to journald; set only if the journald logging backend has been
+/// successfully initialized.
+static JOURNAL: OnceCell<UnixDatagram> = OnceCell::new();
+
+/// The destination for log messages.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LogTarget {
+    Stderr,
+    Journald,
+}
+
+impl FromStr for LogTarget {
+    type Err = StratisError;
+
+    fn from_str(s: &str) -> StratisResult<Self> {
+        match s {
+            "stderr" => Ok(LogTarget::Stderr),
+            "journald" => Ok(LogTarget::Journald),
+            _ => Err(StratisError::Msg(format!("Unknown log target {s}"))),
+        }
+    }
+}
+
+/// Append a single field to a message in journald native protocol format.
+/// Values containing a newline must be length prefixed; all others are
+/// written as KEY=VALUE.
+fn append_field(buf: &mut Vec<u8>, key: &str, value: &[u8]) {
+    buf.extend_from_slice(key.as_bytes());
+    if value.contains(&b'\n') {
+        buf.push(b'\n');
+        buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        buf.push(b'=');
+    }
+    buf.extend_from_slice(value);
+    buf.push(b'\n');
+}
+
+/// Map a log level to the corresponding syslog priority.
+fn priority(level: Level) -> &'static str {
+    match level {
+        Level::Error => "3",
+        Level::Warn => "4",
+        Level::Info => "6",
+        Level::Debug | Level::Trace => "7",
+    }
+}
+
+/// Send an already encoded message to journald. If that fails, the message
+/// can not be logged anywhere else useful, so print the failure on stderr.
+fn send_to_journal(socket: &UnixDatagram, buf: &[u8]) {
+    if let Err(e) = socket.send(buf) {
+        eprintln!("Failed to send log message to journald: {e}");
+    }
+}
+
+/// A logger which sends messages to journald. Filtering is delegated to
+/// the env_logger configuration that stratisd uses for logging to stderr,
+/// so that the log-level argument and RUST_LOG behave identically for
+/// both targets.
+struct JournaldLogger {
+    filter: Logger,
+    socket: UnixDatagram,
+}
+
+impl Log for JournaldLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.filter.matches(record) {
+            return;
+        }
+
+        let mut buf = Vec::new();
+        append_field(&mut buf, "MESSAGE", record.args().to_string().as_bytes());
+        append_field(&mut buf, "PRIORITY", priority(record.level()).as_bytes());
+        append_field(&mut buf, "SYSLOG_IDENTIFIER", SYSLOG_IDENTIFIER.as_bytes());
+        append_field(&mut buf, "TARGET", record.target().as_bytes());
+        if let Some(file) = record.file() {
+            append_field(&mut buf, "CODE_FILE", file.as_bytes());
+        }
+        if let Some(line) = record.line() {
+            append_field(&mut buf, "CODE_LINE", line.to_string().as_bytes());
+        }
+        if let Some(module) = record.module_path() {
+            append_field(&mut buf, "CODE_MODULE", module.as_bytes());
+        }
+        send_to_journal(&self.socket, &buf);
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install a logger that sends all messages that pass the filters configured
+/// in builder to journald.
+///
+/// Returns an error if the journald socket can not be connected to or if
+/// a logger has already been installed.
+pub fn init_journald_log(mut builder: Builder) -> StratisResult<()> {
+    let socket = UnixDatagram::unbound()?;
+    socket.connect(JOURNALD_SOCKET).map_err(|e| {
+        StratisError::Chained(
+            format!("Failed to connect to journald socket at {JOURNALD_SOCKET}"),
+            Box::new(StratisError::from(e)),
+        )
+    })?;
+
+    let filter = builder.build();
+    let max_level = filter.filter();
+    let audit_socket = socket.try_clone()?;
+
+    log::set_boxed_logger(Box::new(JournaldLogger { filter, socket }))
+        .map_err(|e| StratisError::Msg(format!("Failed to install journald logger: {e}")))?;
+    log::set_max_level(max_level);
+
+    // Can only fail if called twice, but in that case set_boxed_logger
+    // would have already returned an error.
+    let _ = JOURNAL.set(audit_socket);
+    Ok(())
+}
+
+/// The user ID of a D-Bus client, which is looked up only when it is first
+/// needed, i.e., when an audit record of an operation performed on behalf of
+/// the client is emitted. Most method calls change no state, so looking it
+/// up for every call would be wasted work.
+#[derive(Clone)]
+pub struct LazyUid {
+    uid: Arc<OnceCell<Option<u32>>>,
+    lookup: Arc<dyn Fn() -> Option<u32> + Send + Sync>,
+}
+
+impl LazyUid {
+    /// Create a user ID which is looked up by calling lookup. lookup returns
+    /// None if the user ID could not be determined.
+    pub fn new<F>(lookup: F) -> Self
+    where
+        F: Fn() -> Option<u32> + Send + Sync + 'static,
+    {
+        LazyUid {
+            uid: Arc::new(OnceCell::new()),
+            lookup: Arc::new(lookup),
+        }
+    }
+
+    /// The user ID, looked up on the first call.
+    pub fn get(&self) -> Option<u32> {
+        *self.uid.get_or_init(|| (self.lookup)())
+    }
+}
+
+impl fmt::Debug for LazyUid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyUid")
+            .field("uid", &self.uid.get())
+            .finish()
+    }
+}
+
+impl PartialEq for LazyUid {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.uid, &other.uid)
+    }
+}
+
+impl Eq for LazyUid {}
+
+/// The client on whose behalf an engine operation is performed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Caller {
+    /// A D-Bus client, identified by its unique bus name and, if it could be
+    /// determined, its user ID.
+    DBus { sender: String, uid: LazyUid },
+    /// A client of the JSON-RPC or varlink socket, identified by the user ID
+    /// of the peer.
+    Socket { uid: u32 },
+}
+
+tokio::task_local! {
+    static CALLER: Caller;
+}
+
+impl Caller {
+    /// Run f with self recorded as the caller of every audited operation
+    /// that f performs on the current thread.
+    pub fn scope_sync<F, R>(self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        CALLER.sync_scope(self, f)
+    }
+
+    /// Run fut with self recorded as the caller of every audited operation
+    /// that fut performs.
+    pub fn scope<F>(self, fut: F) -> impl Future<Output = F::Output>
+    where
+        F: Future,
+    {
+        CALLER.scope(self, fut)
+    }
+
+    /// The caller of the operation currently being performed, if any.
+    pub fn current() -> Option<Caller> {
+        CALLER.try_with(Caller::clone).ok()
+    }
+
+    /// Look up any part of the identity of the caller which is otherwise
+    /// looked up only when it is needed. This is necessary if the caller may
+    /// be gone by the time it is needed.
+    pub fn resolve(self) -> Self {
+        if let Caller::DBus { ref uid, .. } = self {
+            uid.get();
+        }
+        self
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Caller::DBus { sender, uid } => {
+                let mut fields = vec![
+                    ("STRATIS_CALLER", "dbus".to_string()),
+                    ("STRATIS_CALLER_SENDER", sender.clone()),
+                ];
+                if let Some(uid) = uid.get() {
+                    fields.push(("STRATIS_CALLER_UID", uid.to_string()));
+                }
+                fields
+            }
+            Caller::Socket { uid } => vec![
+                ("STRATIS_CALLER", "socket".to_string()),
+                ("STRATIS_CALLER_UID", uid.to_string()),
+            ],
+        }
+    }
+}
+
+/// The result of an audited engine operation.
+#[derive(Clone, Copy, Debug)]
+pub enum AuditResult<'a> {
+    /// The operation succeeded and changed state.
+    Changed,
+    /// The operation succeeded but nothing needed to be changed.
+    Unchanged,
+    /// The operation failed.
+    Failed(&'a StratisError),
+}
+
+impl<'a> AuditResult<'a> {
+    /// Convert the result of an engine operation, where the Ok value
+    /// indicates whether the operation changed state.
+    pub fn from_changed(res: Result<bool, &'a StratisError>) -> Self {
+        match res {
+            Ok(true) => AuditResult::Changed,
+            Ok(false) => AuditResult::Unchanged,
+            Err(e) => AuditResult::Failed(e),
+        }
+    }
+}
+
+impl Display for AuditResult<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditResult::Changed => write!(f, "changed"),
+            AuditResult::Unchanged => write!(f, "unchanged"),
+            AuditResult::Failed(_) => write!(f, "failed"),
+        }
+    }
+}
+
+/// A record of a single state-changing engine operation.
+#[derive(Debug)]
+pub struct AuditRecord {
+    action: &'static str,
+    pool_uuid: Option<PoolUuid>,
+    pool_name: Option<String>,
+    fs_uuids: Vec<FilesystemUuid>,
+    dev_uuid: Option<DevUuid>,
+    caller: Option<Caller>,
+}
+
+impl AuditRecord {
+    /// Start a record of action, performed on behalf of the current caller.
+    pub fn new(action: &'static str) -> Self {
+        AuditRecord {
+            action,
+            pool_uuid: None,
+            pool_name: None,
+            fs_uuids: Vec::new(),
+            dev_uuid: None,
+            caller: Caller::current(),
+        }
+    }
+
+    /// Set the pool UUID, if it is known. Does not unset a UUID that has
+    /// already been set.
+    pub fn pool_uuid(mut self, pool_uuid: Option<PoolUuid>) -> Self {
+        if pool_uuid.is_some() {
+            self.pool_uuid = pool_uuid;
+        }
+        self
+    }
+
+    pub fn pool_name(mut self, pool_name: &str) -> Self {
+        self.pool_name = Some(pool_name.to_string());
+        self
+    }
+
+    pub fn pool_id(self, pool_id: &PoolIdentifier<PoolUuid>) -> Self {
+        match pool_id {
+            PoolIdentifier::Name(name) => self.pool_name(name),
+            PoolIdentifier::Uuid(uuid) => self.pool_uuid(Some(*uuid)),
+        }
+    }
+
+    pub fn fs_uuid(mut self, fs_uuid: FilesystemUuid) -> Self {
+        self.fs_uuids.push(fs_uuid);
+        self
+    }
+
+    pub fn fs_uuids<'a, I>(mut self, fs_uuids: I) -> Self
+    where
+        I: IntoIterator<Item = &'a FilesystemUuid>,
+    {
+        self.fs_uuids.extend(fs_uuids);
+        self
+    }
+
+    pub fn dev_uuid(mut self, dev_uuid: DevUuid) -> Self {
+        self.dev_uuid = Some(dev_uuid);
+        self
+    }
+
+    /// The identifying fields of the record as (journal field, value) pairs.
+    /// A field may occur more than once.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![("STRATIS_ACTION", self.action.to_string())];
+        if let Some(uuid) = self.pool_uuid {
+            fields.push(("STRATIS_POOL_UUID", uuid.to_string()));
+        }
+        if let Some(ref name) = self.pool_name {
+            fields.push(("STRATIS_POOL_NAME", name.clone()));
+        }
+        for uuid in &self.fs_uuids {
+            fields.push(("STRATIS_FS_UUID", uuid.to_string()));
+        }
+        if let Some(uuid) = self.dev_uuid {
+            fields.push(("STRATIS_DEV_UUID", uuid.to_string()));
+        }
+        if let Some(ref caller) = self.caller {
+            fields.extend(caller.fields());
+        }
+        fields
+    }
+
+    /// Emit the audit record with the given result.
+    pub fn emit(self, result: AuditResult<'_>) {
+        let mut fields = self.fields();
+        fields.push(("STRATIS_RESULT", result.to_string()));
+        if let AuditResult::Failed(e) = result {
+            fields.push(("STRATIS_ERROR", e.to_string()));
+        }
+
+        match JOURNAL.get() {
+            Some(socket) => {
+                let mut buf = Vec::new();
+                let message = match result {
+                    AuditResult::Failed(e) => format!("{} failed: {e}", self.action),
+                    _ => format!("{} {result}", self.action),
+                };
+                append_field(&mut buf, "MESSAGE", message.as_bytes());
+                append_field(&mut buf, "PRIORITY", priority(Level::Info).as_bytes());
+                append_field(&mut buf, "SYSLOG_IDENTIFIER", SYSLOG_IDENTIFIER.as_bytes());
+                append_field(&mut buf, "TARGET", AUDIT_TARGET.as_bytes());
+                for (key, value) in fields {
+                    append_field(&mut buf, key, value.as_bytes());
+                }
+                send_to_journal(socket, &buf);
+            }
+            None => {
+                // Fields that occur more than once become a JSON array.
+                let mut json = Map::new();
+                for (key, value) in fields {
+                    match json.get_mut(key) {
+                        Some(Value::Array(values)) => values.push(Value::from(value)),
+                        Some(prev) => {
+                            let first = prev.take();
+                            *prev = Value::Array(vec![first, Value::from(value)]);
+                        }
+                        None => {
+                            json.insert(key.to_string(), Value::from(value));
+                        }
+                    }
+                }
+                // Audit records must not be dropped by the level filter,
+                // so write them to stderr directly if it would drop them.
+                let json = Value::Object(json);
+                if log_enabled!(target: AUDIT_TARGET, Level::Info) {
+                    info!(target: AUDIT_TARGET, "{}", json);
+                } else {
+                    eprintln!("{AUDIT_TARGET}: {json}");
+                }
+            }
+        }
+    }
+
+    /// Emit the audit record for the result of an engine operation, where
+    /// the Ok value indicates whether the operation changed state.
+    pub fn emit_result(self, res: Result<bool, &StratisError>) {
+        self.emit(AuditResult::from_changed(res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    /// Verify that single line values are encoded as KEY=VALUE and values
+    /// containing a newline are length prefixed.
+    fn test_append_field() {
+        let mut buf = Vec::new();
+        append_field(&mut buf, "MESSAGE", b"simple");
+        assert_eq!(buf, b"MESSAGE=simple\n");
+
+        let mut buf = Vec::new();
+        append_field(&mut buf, "MESSAGE", b"two\nlines");
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&9u64.to_le_bytes());
+        expected.extend_from_slice(b"two\nlines\n");
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    /// Verify that the identifying fields of an audit record are present
+    /// only when they have been set.
+    fn test_audit_fields() {
+        let pool_uuid = PoolUuid::new_v4();
+        let fs_uuid = FilesystemUuid::new_v4();
+        let record = AuditRecord::new("create_filesystem")
+            .pool_uuid(Some(pool_uuid))
+            .fs_uuid(fs_uuid);
+        assert_eq!(
+            record.fields(),
+            vec![
+                ("STRATIS_ACTION", "create_filesystem".to_string()),
+                ("STRATIS_POOL_UUID", pool_uuid.to_string()),
+                ("STRATIS_FS_UUID", fs_uuid.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    /// Verify that an audit record identifies the caller in whose scope it
+    /// was created.
+    fn test_audit_caller_fields() {
+        let record = Caller::Socket { uid: 1000 }.scope_sync(|| AuditRecord::new("stop_pool"));
+        assert_eq!(
+            record.fields(),
+            vec![
+                ("STRATIS_ACTION", "stop_pool".to_string()),
+                ("STRATIS_CALLER", "socket".to_string()),
+                ("STRATIS_CALLER_UID", "1000".to_string()),
+            ]
+        );
+
+        let caller = Caller::DBus {
+            sender: ":1.42".to_string(),
+            uid: LazyUid::new(|| None),
+        };
+        let record = caller.scope_sync(|| AuditRecord::new("stop_pool"));
+        assert_eq!(
+            record.fields(),
+            vec![
+                ("STRATIS_ACTION", "stop_pool".to_string()),
+                ("STRATIS_CALLER", "dbus".to_string()),
+                ("STRATIS_CALLER_SENDER", ":1.42".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    /// The user ID of a D-Bus caller is looked up only when an audit record
+    /// is emitted, and only once.
+    fn test_lazy_uid() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&lookups);
+        let caller = Caller::DBus {
+            sender: ":1.42".to_string(),
+            uid: LazyUid::new(move || {
+                counted.fetch_add(1, Ordering::SeqCst);
+                Some(1000)
+            }),
+        };
+        let record = caller.scope_sync(|| AuditRecord::new("stop_pool"));
+        assert_eq!(lookups.load(Ordering::SeqCst), 0);
+        let fields = record.fields();
+        assert!(fields.contains(&("STRATIS_CALLER_UID", "1000".to_string())));
+        record.fields();
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+    }
+}
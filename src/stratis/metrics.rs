@@ -0,0 +1,620 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Metrics endpoint in the Prometheus text exposition format.
+//!
+//! The values are updated from the pool and filesystem diffs that are
+//! generated by the timer and by devicemapper event handling, and from a
+//! snapshot of the engine's pools that the timer takes on every tick. The
+//! endpoint is served over plain HTTP, without authentication, on the
+//! address given to stratisd, which is a localhost only address by default.
+
+use std::{
+    collections::HashMap,
+    fmt::{Display, Write as _},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    sync::{Arc, Mutex},
+};
+
+use once_cell::sync::Lazy;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    spawn,
+};
+
+use devicemapper::{Bytes, Sectors};
+
+use crate::{
+    engine::{
+        BlockDevTier, CacheStats, DevUuid, Engine, FilesystemUuid, PoolDiff, PoolUuid,
+        StratFilesystemDiff, ThinPoolUsageInfo,
+    },
+    stratis::StratisResult,
+};
+
+/// The address on which the metrics endpoint listens unless another address
+/// is specified.
+pub const DEFAULT_METRICS_ADDRESS: SocketAddr =
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 9849));
+
+/// The maximum size of an HTTP request header that will be read.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// The metrics shared between the timer, the devicemapper event handler and
+/// the metrics endpoint.
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::default);
+
+struct BlockDevMetrics {
+    uuid: DevUuid,
+    tier: BlockDevTier,
+    size: Sectors,
+}
+
+struct PoolMetrics {
+    name: String,
+    total_physical_size: Sectors,
+    fs_limit: u64,
+    overprov_enabled: bool,
+    blockdevs: Vec<BlockDevMetrics>,
+    // Values obtained from the most recent pool diff.
+    allocated_size: Option<Bytes>,
+    used: Option<Bytes>,
+    metadata_size: Option<Bytes>,
+    out_of_alloc_space: Option<bool>,
+    usage: Option<ThinPoolUsageInfo>,
+    cache: Option<CacheStats>,
+    changes: u64,
+}
+
+struct FilesystemMetrics {
+    pool_uuid: PoolUuid,
+    name: String,
+    size: Bytes,
+    size_limit: Option<Sectors>,
+    // Values obtained from the most recent filesystem diff.
+    used: Option<Bytes>,
+    changes: u64,
+}
+
+#[derive(Default)]
+struct MetricsState {
+    pools: HashMap<PoolUuid, PoolMetrics>,
+    filesystems: HashMap<FilesystemUuid, FilesystemMetrics>,
+    timer_checks: u64,
+    dm_events: u64,
+}
+
+#[derive(Default)]
+pub struct Metrics(Mutex<MetricsState>);
+
+impl Metrics {
+    fn lock(&self) -> std::sync::MutexGuard<'_, MetricsState> {
+        // The state is always consistent, so a poisoned lock can be used.
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Take a snapshot of the pools, filesystems and block devices that the
+    /// engine currently manages. Entries for pools and filesystems that no
+    /// longer exist are dropped.
+    pub async fn refresh(&self, engine: &Arc<dyn Engine>) {
+        let guard = engine.pools().await;
+        let mut state = self.lock();
+        let mut pools = HashMap::new();
+        let mut filesystems = HashMap::new();
+        for (pool_name, pool_uuid, pool) in guard.iter() {
+            let prev = state.pools.remove(pool_uuid);
+            pools.insert(
+                *pool_uuid,
+                PoolMetrics {
+                    name: pool_name.to_string(),
+                    total_physical_size: pool.total_physical_size(),
+                    fs_limit: pool.fs_limit(),
+                    overprov_enabled: pool.overprov_enabled(),
+                    blockdevs: pool
+                        .blockdevs()
+                        .into_iter()
+                        .map(|(uuid, tier, bd)| BlockDevMetrics {
+                            uuid,
+                            tier,
+                            size: bd.size(),
+                        })
+                        .collect(),
+                    allocated_size: prev.as_ref().and_then(|p| p.allocated_size),
+                    used: prev.as_ref().and_then(|p| p.used),
+                    metadata_size: prev.as_ref().and_then(|p| p.metadata_size),
+                    out_of_alloc_space: prev.as_ref().and_then(|p| p.out_of_alloc_space),
+                    usage: prev.as_ref().and_then(|p| p.usage),
+                    cache: prev.as_ref().and_then(|p| p.cache),
+                    changes: prev.as_ref().map_or(0, |p| p.changes),
+                },
+            );
+            for (fs_name, fs_uuid, fs) in pool.filesystems() {
+                let prev = state.filesystems.remove(&fs_uuid);
+                filesystems.insert(
+                    fs_uuid,
+                    FilesystemMetrics {
+                        pool_uuid: *pool_uuid,
+                        name: fs_name.to_string(),
+                        size: fs.size(),
+                        size_limit: fs.size_limit(),
+                        used: prev.as_ref().and_then(|f| f.used),
+                        changes: prev.as_ref().map_or(0, |f| f.changes),
+                    },
+                );
+            }
+        }
+        state.pools = pools;
+        state.filesystems = filesystems;
+    }
+
+    /// Record the values from a set of pool diffs. Diffs for pools that
+    /// have not yet been seen by refresh() are ignored.
+    pub fn record_pool_diffs(&self, diffs: &HashMap<PoolUuid, PoolDiff>) {
+        let mut state = self.lock();
+        for (uuid, diff) in diffs {
+            if let Some(pool) = state.pools.get_mut(uuid) {
+                pool.allocated_size = Some(*diff.thin_pool.allocated_size);
+                pool.used = *diff.thin_pool.used;
+                pool.usage = *diff.thin_pool.usage;
+                pool.metadata_size = Some(*diff.pool.metadata_size);
+                pool.out_of_alloc_space = Some(*diff.pool.out_of_alloc_space);
+                pool.cache = *diff.pool.cache;
+                if diff.thin_pool.allocated_size.is_changed()
+                    || diff.thin_pool.used.is_changed()
+                    || diff.pool.metadata_size.is_changed()
+                    || diff.pool.out_of_alloc_space.is_changed()
+                {
+                    pool.changes += 1;
+                }
+            }
+        }
+    }
+
+    /// Record the values from a set of filesystem diffs. Diffs for
+    /// filesystems that have not yet been seen by refresh() are ignored.
+    pub fn record_fs_diffs(&self, diffs: &HashMap<FilesystemUuid, StratFilesystemDiff>) {
+        let mut state = self.lock();
+        for (uuid, diff) in diffs {
+            if let Some(fs) = state.filesystems.get_mut(uuid) {
+                fs.size = *diff.size;
+                fs.used = *diff.used;
+                if diff.size.is_changed() || diff.used.is_changed() {
+                    fs.changes += 1;
+                }
+            }
+        }
+    }
+
+    /// Record that the timer has run its pool and filesystem checks.
+    pub fn record_timer_check(&self) {
+        self.lock().timer_checks += 1;
+    }
+
+    /// Record that a devicemapper event has been handled.
+    pub fn record_dm_event(&self) {
+        self.lock().dm_events += 1;
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let state = self.lock();
+        let mut w = MetricsWriter::default();
+
+        let pool_label = |uuid: &PoolUuid| vec![("pool_uuid", uuid.to_string())];
+        let fs_label = |uuid: &FilesystemUuid| vec![("filesystem_uuid", uuid.to_string())];
+
+        w.family(
+            "stratis_pool_info",
+            "gauge",
+            "Pool name; the value is always 1.",
+            state.pools.iter().map(|(uuid, p)| {
+                (
+                    vec![("pool_uuid", uuid.to_string()), ("name", p.name.clone())],
+                    1u128,
+                )
+            }),
+        );
+        w.family(
+            "stratis_pool_total_physical_size_bytes",
+            "gauge",
+            "Total size of all data devices in the pool.",
+            state
+                .pools
+                .iter()
+                .map(|(uuid, p)| (pool_label(uuid), *p.total_physical_size.bytes())),
+        );
+        w.family(
+            "stratis_pool_allocated_bytes",
+            "gauge",
+            "Space allocated from the data devices of the pool.",
+            state
+                .pools
+                .iter()
+                .filter_map(|(uuid, p)| p.allocated_size.map(|a| (pool_label(uuid), *a))),
+        );
+        w.family(
+            "stratis_pool_used_bytes",
+            "gauge",
+            "Space used on the thin pool, including thin pool metadata.",
+            state
+                .pools
+                .iter()
+                .filter_map(|(uuid, p)| p.used.map(|u| (pool_label(uuid), *u))),
+        );
+        w.family(
+            "stratis_pool_metadata_size_bytes",
+            "gauge",
+            "Space used by Stratis metadata on the data devices of the pool.",
+            state
+                .pools
+                .iter()
+                .filter_map(|(uuid, p)| p.metadata_size.map(|m| (pool_label(uuid), *m))),
+        );
+        w.family(
+            "stratis_pool_out_of_alloc_space",
+            "gauge",
+            "Whether the pool has run out of space to allocate.",
+            state.pools.iter().filter_map(|(uuid, p)| {
+                p.out_of_alloc_space
+                    .map(|o| (pool_label(uuid), u128::from(o)))
+            }),
+        );
+        w.family(
+            "stratis_pool_fs_limit",
+            "gauge",
+            "Maximum number of filesystems in the pool.",
+            state
+                .pools
+                .iter()
+                .map(|(uuid, p)| (pool_label(uuid), u128::from(p.fs_limit))),
+        );
+        w.family(
+            "stratis_pool_overprovisioning_enabled",
+            "gauge",
+            "Whether overprovisioning is enabled for the pool.",
+            state
+                .pools
+                .iter()
+                .map(|(uuid, p)| (pool_label(uuid), u128::from(p.overprov_enabled))),
+        );
+
+        type UsageField = fn(&ThinPoolUsageInfo) -> Bytes;
+        let usage_families: [(&str, &str, UsageField); 4] = [
+            (
+                "stratis_thinpool_data_used_bytes",
+                "Space used on the thin pool data device.",
+                |u| u.data_used,
+            ),
+            (
+                "stratis_thinpool_data_total_bytes",
+                "Size of the thin pool data device.",
+                |u| u.data_total,
+            ),
+            (
+                "stratis_thinpool_metadata_used_bytes",
+                "Space used on the thin pool metadata device.",
+                |u| u.meta_used,
+            ),
+            (
+                "stratis_thinpool_metadata_total_bytes",
+                "Size of the thin pool metadata device.",
+                |u| u.meta_total,
+            ),
+        ];
+        for (name, help, field) in usage_families {
+            w.family(
+                name,
+                "gauge",
+                help,
+                state.pools.iter().filter_map(|(uuid, p)| {
+                    p.usage.as_ref().map(|u| (pool_label(uuid), *field(u)))
+                }),
+            );
+        }
+
+        type CacheField = fn(&CacheStats) -> u128;
+        let cache_families: [(&str, &str, &str, CacheField); 9] = [
+            (
+                "stratis_cache_used_bytes",
+                "gauge",
+                "Space used on the cache device.",
+                |c| *c.used,
+            ),
+            (
+                "stratis_cache_total_bytes",
+                "gauge",
+                "Size of the cache device.",
+                |c| *c.total,
+            ),
+            (
+                "stratis_cache_read_hits_total",
+                "counter",
+                "Cache read hits.",
+                |c| u128::from(c.read_hits),
+            ),
+            (
+                "stratis_cache_read_misses_total",
+                "counter",
+                "Cache read misses.",
+                |c| u128::from(c.read_misses),
+            ),
+            (
+                "stratis_cache_write_hits_total",
+                "counter",
+                "Cache write hits.",
+                |c| u128::from(c.write_hits),
+            ),
+            (
+                "stratis_cache_write_misses_total",
+                "counter",
+                "Cache write misses.",
+                |c| u128::from(c.write_misses),
+            ),
+            (
+                "stratis_cache_demotions_total",
+                "counter",
+                "Blocks demoted from the cache.",
+                |c| u128::from(c.demotions),
+            ),
+            (
+                "stratis_cache_promotions_total",
+                "counter",
+                "Blocks promoted to the cache.",
+                |c| u128::from(c.promotions),
+            ),
+            (
+                "stratis_cache_dirty_blocks",
+                "gauge",
+                "Dirty blocks in the cache.",
+                |c| u128::from(c.dirty),
+            ),
+        ];
+        for (name, kind, help, field) in cache_families {
+            w.family(
+                name,
+                kind,
+                help,
+                state
+                    .pools
+                    .iter()
+                    .filter_map(|(uuid, p)| p.cache.as_ref().map(|c| (pool_label(uuid), field(c)))),
+            );
+        }
+
+        w.family(
+            "stratis_blockdev_size_bytes",
+            "gauge",
+            "Size of the block device.",
+            state.pools.iter().flat_map(|(pool_uuid, p)| {
+                p.blockdevs.iter().map(move |bd| {
+                    (
+                        vec![
+                            ("pool_uuid", pool_uuid.to_string()),
+                            ("blockdev_uuid", bd.uuid.to_string()),
+                            (
+                                "tier",
+                                match bd.tier {
+                                    BlockDevTier::Data => "data",
+                                    BlockDevTier::Cache => "cache",
+                                }
+                                .to_string(),
+                            ),
+                        ],
+                        *bd.size.bytes(),
+                    )
+                })
+            }),
+        );
+
+        w.family(
+            "stratis_filesystem_info",
+            "gauge",
+            "Filesystem name and pool; the value is always 1.",
+            state.filesystems.iter().map(|(uuid, f)| {
+                (
+                    vec![
+                        ("filesystem_uuid", uuid.to_string()),
+                        ("pool_uuid", f.pool_uuid.to_string()),
+                        ("name", f.name.clone()),
+                    ],
+                    1u128,
+                )
+            }),
+        );
+        w.family(
+            "stratis_filesystem_size_bytes",
+            "gauge",
+            "Logical size of the filesystem.",
+            state
+                .filesystems
+                .iter()
+                .map(|(uuid, f)| (fs_label(uuid), *f.size)),
+        );
+        w.family(
+            "stratis_filesystem_used_bytes",
+            "gauge",
+            "Space used by the filesystem on the thin pool.",
+            state
+                .filesystems
+                .iter()
+                .filter_map(|(uuid, f)| f.used.map(|u| (fs_label(uuid), *u))),
+        );
+        w.family(
+            "stratis_filesystem_size_limit_bytes",
+            "gauge",
+            "Maximum logical size of the filesystem, if set.",
+            state
+                .filesystems
+                .iter()
+                .filter_map(|(uuid, f)| f.size_limit.map(|l| (fs_label(uuid), *l.bytes()))),
+        );
+
+        w.family(
+            "stratis_pool_changes_total",
+            "counter",
+            "Changes in pool usage observed while handling events.",
+            state
+                .pools
+                .iter()
+                .map(|(uuid, p)| (pool_label(uuid), u128::from(p.changes))),
+        );
+        w.family(
+            "stratis_filesystem_changes_total",
+            "counter",
+            "Changes in filesystem usage observed while handling events.",
+            state
+                .filesystems
+                .iter()
+                .map(|(uuid, f)| (fs_label(uuid), u128::from(f.changes))),
+        );
+        w.family(
+            "stratis_timer_checks_total",
+            "counter",
+            "Timed pool and filesystem checks run.",
+            [(Vec::new(), u128::from(state.timer_checks))],
+        );
+        w.family(
+            "stratis_dm_events_total",
+            "counter",
+            "Devicemapper events handled.",
+            [(Vec::new(), u128::from(state.dm_events))],
+        );
+
+        w.out
+    }
+}
+
+/// Accumulates metric families in the Prometheus text exposition format.
+#[derive(Default)]
+struct MetricsWriter {
+    out: String,
+}
+
+impl MetricsWriter {
+    fn family<I, V>(&mut self, name: &str, kind: &str, help: &str, samples: I)
+    where
+        I: IntoIterator<Item = (Vec<(&'static str, String)>, V)>,
+        V: Display,
+    {
+        let mut samples = samples.into_iter().peekable();
+        if samples.peek().is_none() {
+            return;
+        }
+        // Writing to a String can not fail.
+        let _ = writeln!(self.out, "# HELP {name} {help}");
+        let _ = writeln!(self.out, "# TYPE {name} {kind}");
+        for (labels, value) in samples {
+            if labels.is_empty() {
+                let _ = writeln!(self.out, "{name} {value}");
+            } else {
+                let labels = labels
+                    .iter()
+                    .map(|(key, value)| format!("{key}=\"{}\"", escape_label_value(value)))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let _ = writeln!(self.out, "{name}{{{labels}}} {value}");
+            }
+        }
+    }
+}
+
+/// Escape a label value as required by the text exposition format.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Read the request line of an HTTP request and write the response.
+async fn handle_connection(mut stream: TcpStream) -> StratisResult<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < MAX_REQUEST_SIZE {
+        let len = stream.read(&mut chunk).await?;
+        if len == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..len]);
+    }
+
+    let request = String::from_utf8_lossy(&buf);
+    let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+    let response = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = METRICS.render();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        }
+        (Some("GET"), _) => {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        }
+        _ => "HTTP/1.1 405 Method Not Allowed\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            .to_string(),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Serve the metrics endpoint on address.
+pub async fn serve_metrics(address: SocketAddr) -> StratisResult<()> {
+    let listener = TcpListener::bind(address).await?;
+    info!("Serving metrics on http://{}/metrics", address);
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                spawn(async move {
+                    if let Err(e) = handle_connection(stream).await {
+                        warn!("Failed to handle metrics request: {}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                warn!("Failed to accept connection to metrics endpoint: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify the format of a metric family with and without labels.
+    fn test_metrics_writer() {
+        let mut w = MetricsWriter::default();
+        w.family(
+            "stratis_test",
+            "gauge",
+            "A test metric.",
+            [(vec![("name", "a\"b".to_string())], 3u128)],
+        );
+        w.family(
+            "stratis_test_total",
+            "counter",
+            "A test counter.",
+            [(Vec::new(), 1u128)],
+        );
+        w.family(
+            "stratis_empty",
+            "gauge",
+            "Omitted if there are no samples.",
+            Vec::<(Vec<(&'static str, String)>, u128)>::new(),
+        );
+        assert_eq!(
+            w.out,
+            "# HELP stratis_test A test metric.\n\
+             # TYPE stratis_test gauge\n\
+             stratis_test{name=\"a\\\"b\"} 3\n\
+             # HELP stratis_test_total A test counter.\n\
+             # TYPE stratis_test_total counter\n\
+             stratis_test_total 1\n"
+        );
+    }
+}
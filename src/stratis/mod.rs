@@ -4,13 +4,20 @@
 
 pub use self::{
     errors::{StratisError, StratisResult},
+    logging::{init_journald_log, AuditRecord, AuditResult, Caller, LazyUid, LogTarget},
     run::run,
     stratis::VERSION,
 };
 
+#[cfg(feature = "metrics")]
+pub use self::metrics::DEFAULT_METRICS_ADDRESS;
+
 mod dm;
 mod errors;
 mod ipc_support;
+mod logging;
+#[cfg(feature = "metrics")]
+mod metrics;
 mod run;
 #[allow(clippy::module_inception)]
 mod stratis;
@@ -4,6 +4,8 @@
 
 //! Main loop
 
+#[cfg(feature = "metrics")]
+use std::net::SocketAddr;
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc,
@@ -20,6 +22,8 @@ use nix::unistd::getpid;
 
 #[cfg(feature = "dbus_enabled")]
 use crate::dbus_api::DbusAction;
+#[cfg(feature = "metrics")]
+use crate::stratis::metrics::serve_metrics;
 use crate::{
     engine::{
         register_clevis_token, set_up_crypt_logging, unshare_mount_namespace, Engine, SimEngine,
@@ -48,8 +52,12 @@ async fn signal_thread() -> StratisResult<()> {
 /// Initialize the engine and keep it running until a signal is received
 /// or a fatal error is encountered.
 /// If sim is true, start the sim engine rather than the real engine.
+/// The metrics endpoint, if built, listens on metrics_address.
 /// Always check for devicemapper context.
-pub fn run(sim: bool) -> StratisResult<()> {
+pub fn run(
+    sim: bool,
+    #[cfg(feature = "metrics")] metrics_address: SocketAddr,
+) -> StratisResult<()> {
     if !sim {
         // If stratisd is running as PID 1, then it is definitely in a container and the
         // unshare_mount_namespace() command method will fail silently, since the mount namespace will
@@ -89,7 +97,11 @@ pub fn run(sim: bool) -> StratisResult<()> {
         })
         .build()?;
     runtime.block_on(async move {
-        async fn start_threads(engine: Arc<dyn Engine>, sim: bool) -> StratisResult<()> {
+        async fn start_threads(
+            engine: Arc<dyn Engine>,
+            sim: bool,
+            #[cfg(feature = "metrics")] metrics_address: SocketAddr,
+        ) -> StratisResult<()> {
             let (trigger, should_exit) = channel(1);
             let (udev_sender, udev_receiver) = unbounded_channel::<UdevEngineEvent>();
             #[cfg(feature = "dbus_enabled")]
@@ -114,6 +126,12 @@ pub fn run(sim: bool) -> StratisResult<()> {
                 #[cfg(feature = "dbus_enabled")]
                 dbus_sender.clone(),
             );
+            #[cfg(feature = "metrics")]
+            task::spawn(async move {
+                if let Err(e) = serve_metrics(metrics_address).await {
+                    warn!("The metrics endpoint exited with an error: {}", e);
+                }
+            });
             let join_timer = run_timers(
                 engine,
                 #[cfg(feature = "dbus_enabled")]
@@ -163,7 +181,13 @@ pub fn run(sim: bool) -> StratisResult<()> {
         info!("stratis daemon version {} started", VERSION);
         if sim {
             info!("Using SimEngine");
-            start_threads(Arc::new(SimEngine::default()), sim).await
+            start_threads(
+                Arc::new(SimEngine::default()),
+                sim,
+                #[cfg(feature = "metrics")]
+                metrics_address,
+            )
+            .await
         } else {
             info!("Using StratEngine");
             start_threads(
@@ -174,7 +198,9 @@ pub fn run(sim: bool) -> StratisResult<()> {
                         return Err(e);
                     }
                 }),
-                sim
+                sim,
+                #[cfg(feature = "metrics")]
+                metrics_address,
             ).await
         }
     })?;
@@ -10,8 +10,13 @@ use tokio::{task::spawn, time::sleep};
 
 #[cfg(feature = "dbus_enabled")]
 use crate::dbus_api::DbusAction;
+#[cfg(feature = "metrics")]
+use crate::stratis::metrics::METRICS;
 use crate::{engine::Engine, stratis::errors::StratisResult};
 
+// Interval between backups of the thin pool metadata of each pool.
+const THIN_META_BACKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 /// Runs checks on thin pool usage and filesystem usage to determine whether either
 /// need to be extended.
 async fn check_pool_and_fs(
@@ -22,14 +27,27 @@ async fn check_pool_and_fs(
         engine: &Arc<dyn Engine>,
         #[cfg(feature = "dbus_enabled")] sender: &UnboundedSender<DbusAction>,
     ) -> StratisResult<()> {
+        #[cfg(feature = "metrics")]
+        {
+            METRICS.record_timer_check();
+            METRICS.refresh(engine).await;
+        }
         #[cfg(any(feature = "min", not(feature = "dbus_enabled")))]
         {
-            let _ = engine.pool_evented(None).await;
-            let _ = engine.fs_evented(None).await;
+            #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+            let pool_diffs = engine.pool_evented(None).await;
+            #[cfg(feature = "metrics")]
+            METRICS.record_pool_diffs(&pool_diffs);
+            #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+            let fs_diffs = engine.fs_evented(None).await;
+            #[cfg(feature = "metrics")]
+            METRICS.record_fs_diffs(&fs_diffs);
         }
         #[cfg(feature = "dbus_enabled")]
         {
             let pool_diffs = engine.pool_evented(None).await;
+            #[cfg(feature = "metrics")]
+            METRICS.record_pool_diffs(&pool_diffs);
             for action in DbusAction::from_pool_diffs(pool_diffs) {
                 if let Err(e) = sender.send(action) {
                     warn!(
@@ -39,6 +57,8 @@ async fn check_pool_and_fs(
                 }
             }
             let fs_diffs = engine.fs_evented(None).await;
+            #[cfg(feature = "metrics")]
+            METRICS.record_fs_diffs(&fs_diffs);
             for action in DbusAction::from_fs_diffs(fs_diffs) {
                 if let Err(e) = sender.send(action) {
                     warn!(
@@ -67,18 +87,43 @@ async fn check_pool_and_fs(
     }
 }
 
+/// Periodically backs up the thin pool metadata of every pool.
+async fn backup_thin_metadata(engine: Arc<dyn Engine>) {
+    loop {
+        sleep(THIN_META_BACKUP_INTERVAL).await;
+        trace!("Starting timed thin metadata backups");
+        for (uuid, res) in engine.backup_thin_metadata().await {
+            match res {
+                Ok(path) => info!(
+                    "Backed up thin metadata of pool with UUID {} to {}",
+                    uuid,
+                    path.display()
+                ),
+                Err(e) => warn!(
+                    "Failed to back up thin metadata of pool with UUID {}: {}",
+                    uuid, e
+                ),
+            }
+        }
+        trace!("Timed thin metadata backups finished");
+    }
+}
+
 /// Run all timed background tasks.
 ///
-/// Currently runs a timer to check thin pool and filesystem usage.
+/// Currently runs a timer to check thin pool and filesystem usage and a
+/// timer to back up thin pool metadata.
 pub async fn run_timers(
     engine: Arc<dyn Engine>,
     #[cfg(feature = "dbus_enabled")] sender: UnboundedSender<DbusAction>,
 ) -> StratisResult<()> {
+    let backups = spawn(backup_thin_metadata(Arc::clone(&engine)));
     spawn(check_pool_and_fs(
         engine,
         #[cfg(feature = "dbus_enabled")]
         sender,
     ))
     .await?;
+    backups.await?;
     Ok(())
 }
@@ -0,0 +1,8 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// The socket on which the Varlink interfaces are served. By convention a
+/// Varlink socket is named after the interfaces it serves, so that a client
+/// like varlinkctl can be pointed at it directly.
+pub const VARLINK_SOCKADDR: &str = "/run/stratisd/org.storage.stratis1";
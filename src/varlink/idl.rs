@@ -0,0 +1,303 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Varlink interface definitions, generated from the method schemas of the
+// versioned JSON-RPC protocol so that both interfaces always expose the same
+// methods with the same parameters.
+
+use crate::jsonrpc::protocol::{FdUse, MethodSchema, ParamSchema, METHODS};
+
+/// The interface implemented by every Varlink service.
+pub const VARLINK_SERVICE: &str = "org.varlink.service";
+
+/// The description of the org.varlink.service interface, as given by the
+/// Varlink specification.
+const VARLINK_SERVICE_IDL: &str = "\
+# The Varlink Service Interface is provided by every varlink service. It
+# describes the service and the interfaces it implements.
+interface org.varlink.service
+
+# Get a list of all the interfaces a service provides and information
+# about the implementation.
+method GetInfo() -> (
+  vendor: string,
+  product: string,
+  version: string,
+  url: string,
+  interfaces: []string
+)
+
+# Get the description of an interface that is implemented by this service.
+method GetInterfaceDescription(interface: string) -> (description: string)
+
+# The requested interface was not found.
+error InterfaceNotFound (interface: string)
+
+# The requested method was not found
+error MethodNotFound (method: string)
+
+# The interface defines the requested method, but the service does not
+# implement it.
+error MethodNotImplemented (method: string)
+
+# One of the passed parameters is invalid.
+error InvalidParameter (parameter: string)
+
+# Client is denied access
+error PermissionDenied ()
+
+# Method is expected to be called with 'more' set to true, but wasn't
+error ExpectedMore ()
+";
+
+/// A Varlink interface of stratisd. The methods of the interface are those
+/// methods of the versioned JSON-RPC protocol whose names begin with prefix,
+/// with the prefix removed.
+pub struct Interface {
+    pub name: &'static str,
+    prefix: &'static str,
+    doc: &'static str,
+}
+
+/// The interfaces of stratisd. A method belongs to the first interface whose
+/// prefix it matches, so the catch-all Manager interface must come last.
+pub const INTERFACES: &[Interface] = &[
+    Interface {
+        name: "org.storage.stratis1.Pool",
+        prefix: "Pool",
+        doc: "Create, start, stop and manage Stratis pools.",
+    },
+    Interface {
+        name: "org.storage.stratis1.Filesystem",
+        prefix: "Fs",
+        doc: "Create, destroy and manage filesystems in Stratis pools.",
+    },
+    Interface {
+        name: "org.storage.stratis1.Blockdev",
+        prefix: "Blockdev",
+        doc: "List and manage the block devices of Stratis pools.",
+    },
+    Interface {
+        name: "org.storage.stratis1.Key",
+        prefix: "Key",
+        doc: "Manage keys in the kernel keyring used to unlock encrypted pools.",
+    },
+    Interface {
+        name: "org.storage.stratis1.Job",
+        prefix: "Job",
+        doc: "Query and cancel jobs running long operations in the background.",
+    },
+    Interface {
+        name: "org.storage.stratis1.Manager",
+        prefix: "",
+        doc: "Query the state of the Stratis daemon.",
+    },
+];
+
+/// The name of the error returned by all methods of an interface. It carries
+/// the same numeric code and message as a JSON-RPC error.
+pub const ERROR: &str = "Error";
+
+/// If the method may be called over Varlink, the interface it belongs to and
+/// its name within that interface. Methods which require a file descriptor
+/// can not be called over Varlink.
+pub fn varlink_name(schema: &MethodSchema) -> Option<(&'static Interface, &'static str)> {
+    if let FdUse::Required = schema.fd {
+        return None;
+    }
+    INTERFACES.iter().find_map(|iface| {
+        schema
+            .name
+            .strip_prefix(iface.prefix)
+            .filter(|rest| rest.starts_with(|c: char| c.is_ascii_uppercase()))
+            .map(|rest| (iface, rest))
+    })
+}
+
+/// Look up the schema of method in the interface named interface.
+pub fn find_method(interface: &str, method: &str) -> Option<&'static MethodSchema> {
+    METHODS.iter().find(|schema| {
+        varlink_name(schema)
+            .map(|(iface, name)| iface.name == interface && name == method)
+            .unwrap_or(false)
+    })
+}
+
+/// Whether an interface with the given name is implemented by stratisd.
+pub fn has_interface(interface: &str) -> bool {
+    interface == VARLINK_SERVICE || INTERFACES.iter().any(|iface| iface.name == interface)
+}
+
+/// Split a comma separated list of types at the top level only.
+fn split_types(types: &str) -> Vec<&str> {
+    let mut depth = 0;
+    let mut start = 0;
+    let mut elements = Vec::new();
+    for (i, c) in types.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                elements.push(types[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    elements.push(types[start..].trim());
+    elements
+}
+
+/// Translate a type name used in the method schemas to a Varlink type.
+///
+/// A tuple becomes an array of the type of its elements if they all have the
+/// same type. Types which Varlink can not express precisely, like the
+/// externally tagged enums of the JSON-RPC protocol, become object and must
+/// be passed in the same encoding as over JSON-RPC.
+pub fn varlink_type(type_: &str) -> String {
+    if let Some(inner) = type_.strip_suffix('?') {
+        return format!("?{}", varlink_type(inner));
+    }
+    if let Some(inner) = type_.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        let elements = split_types(inner)
+            .into_iter()
+            .map(varlink_type)
+            .collect::<Vec<_>>();
+        if let [element] = elements.as_slice() {
+            return format!("[]{element}");
+        }
+        let nullable = elements.iter().any(|e| e.starts_with('?'));
+        let bases = elements
+            .iter()
+            .map(|e| e.trim_start_matches('?'))
+            .collect::<Vec<_>>();
+        return if bases.iter().all(|b| *b == bases[0]) {
+            format!("[]{}{}", if nullable { "?" } else { "" }, bases[0])
+        } else {
+            "[]object".to_string()
+        };
+    }
+    match type_ {
+        "string" | "uuid" | "path" | "key_description" => "string",
+        "boolean" => "bool",
+        "u32" | "u64" | "u128" => "int",
+        "pool_id" => "PoolId",
+        _ => "object",
+    }
+    .to_string()
+}
+
+fn fields(params: &[ParamSchema]) -> String {
+    params
+        .iter()
+        .map(|p| {
+            let type_ = varlink_type(p.type_);
+            if p.optional && !type_.starts_with('?') {
+                format!("{}: ?{}", p.name, type_)
+            } else {
+                format!("{}: {}", p.name, type_)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Generate the description of the interface named interface.
+pub fn description(interface: &str) -> Option<String> {
+    if interface == VARLINK_SERVICE {
+        return Some(VARLINK_SERVICE_IDL.to_string());
+    }
+    let iface = INTERFACES.iter().find(|iface| iface.name == interface)?;
+    let methods = METHODS
+        .iter()
+        .filter_map(|schema| {
+            varlink_name(schema)
+                .filter(|(i, _)| i.name == iface.name)
+                .map(|(_, name)| (name, schema))
+        })
+        .collect::<Vec<_>>();
+
+    let mut idl = format!("# {}\ninterface {}\n", iface.doc, iface.name);
+    if methods
+        .iter()
+        .any(|(_, s)| s.params.iter().any(|p| p.type_ == "pool_id"))
+    {
+        idl.push_str(
+            "\n# A pool, identified by exactly one of its name or its UUID.\n\
+             type PoolId (Name: ?string, Uuid: ?string)\n",
+        );
+    }
+    for (name, schema) in methods {
+        idl.push_str(&format!(
+            "\nmethod {}({}) -> ({})\n",
+            name,
+            fields(schema.params),
+            fields(schema.returns)
+        ));
+    }
+    idl.push_str(&format!(
+        "\n# The request failed; code and message are those of the equivalent\n\
+         # JSON-RPC error.\nerror {ERROR} (code: int, message: string)\n"
+    ));
+    Some(idl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Every method which does not require a file descriptor is reachable
+    /// over Varlink under exactly one interface.
+    fn test_methods_reachable() {
+        for schema in METHODS {
+            match varlink_name(schema) {
+                Some((iface, name)) => {
+                    assert!(std::ptr::eq(find_method(iface.name, name).unwrap(), schema));
+                }
+                None => assert!(matches!(schema.fd, FdUse::Required)),
+            }
+        }
+        assert_eq!(
+            find_method("org.storage.stratis1.Pool", "Create").map(|s| s.name),
+            Some("PoolCreate")
+        );
+        assert_eq!(
+            find_method("org.storage.stratis1.Manager", "Report").map(|s| s.name),
+            Some("Report")
+        );
+        assert_eq!(
+            find_method("org.storage.stratis1.Job", "Status").map(|s| s.name),
+            Some("JobStatus")
+        );
+        assert!(find_method("org.storage.stratis1.Manager", "PoolCreate").is_none());
+    }
+
+    #[test]
+    fn test_varlink_type() {
+        assert_eq!(varlink_type("uuid"), "string");
+        assert_eq!(varlink_type("u128?"), "?int");
+        assert_eq!(varlink_type("[path]"), "[]string");
+        assert_eq!(varlink_type("[u128?]"), "[]?int");
+        assert_eq!(varlink_type("[[u128, u128?]]"), "[][]?int");
+        assert_eq!(varlink_type("[[boolean, boolean]]"), "[][]bool");
+        assert_eq!(varlink_type("[[string, u128]]"), "[][]object");
+        assert_eq!(varlink_type("pool_id"), "PoolId");
+        assert_eq!(varlink_type("token_slot_input"), "object");
+    }
+
+    #[test]
+    /// Every interface has a description which declares its methods and no
+    /// doubly nullable types.
+    fn test_description() {
+        for iface in INTERFACES {
+            let idl = description(iface.name).unwrap();
+            assert!(idl.contains(&format!("interface {}\n", iface.name)));
+            assert!(idl.contains("\nmethod "));
+            assert!(!idl.contains("??"));
+        }
+        assert!(description(VARLINK_SERVICE).is_some());
+        assert!(description("org.storage.stratis2.Pool").is_none());
+    }
+}
@@ -0,0 +1,15 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A Varlink service exposing the same pool, filesystem, blockdev and key
+//! operations as the JSON-RPC server. Unlike the JSON-RPC protocol it needs
+//! no stratisd specific client, so it can be used from minimal environments
+//! like the initrd with a generic tool such as varlinkctl. Calls are subject
+//! to the same access policy as JSON-RPC requests.
+
+mod consts;
+mod idl;
+mod server;
+
+pub use self::{consts::*, server::run_server};
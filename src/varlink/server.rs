@@ -0,0 +1,388 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{
+    fs::{create_dir_all, remove_file, set_permissions, Permissions},
+    os::unix::{fs::PermissionsExt, io::AsRawFd},
+    path::Path,
+    sync::Arc,
+};
+
+use serde_json::{Map, Value};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    task::JoinHandle,
+};
+
+use crate::{
+    engine::Engine,
+    jsonrpc::{is_read_only_named, process_named, AccessPolicy, PeerCreds, RPC_ACCESS_CONFIG},
+    stratis::{Caller, StratisError, StratisResult, VERSION},
+    varlink::{
+        consts::VARLINK_SOCKADDR,
+        idl::{description, find_method, has_interface, ERROR, INTERFACES, VARLINK_SERVICE},
+    },
+};
+
+/// The largest message accepted from a Varlink client, including its
+/// terminating NUL byte; the same as the largest request accepted by the
+/// JSON-RPC server.
+const MAX_MESSAGE_SIZE: u64 = 65536;
+
+/// A method call as sent by a Varlink client.
+#[derive(Debug, Deserialize)]
+pub struct VarlinkCall {
+    pub method: String,
+    #[serde(default)]
+    pub parameters: Map<String, Value>,
+    #[serde(default)]
+    pub oneway: bool,
+    #[serde(default)]
+    pub more: bool,
+}
+
+/// A reply to a method call. An error reply carries the qualified name of
+/// the error in addition to its parameters.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct VarlinkReply {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub parameters: Map<String, Value>,
+}
+
+impl VarlinkReply {
+    fn ok(parameters: Map<String, Value>) -> VarlinkReply {
+        VarlinkReply {
+            error: None,
+            parameters,
+        }
+    }
+
+    fn error(error: String, parameters: Value) -> VarlinkReply {
+        VarlinkReply {
+            error: Some(error),
+            parameters: match parameters {
+                Value::Object(map) => map,
+                _ => Map::new(),
+            },
+        }
+    }
+}
+
+/// Varlink clients omit fields with a null value, but may also send them
+/// explicitly. The externally tagged pool identifier must have exactly one
+/// field, so drop any null fields from it.
+fn normalize_pool_id(parameters: &mut Map<String, Value>) {
+    if let Some(Value::Object(id)) = parameters.get_mut("id") {
+        id.retain(|_, v| !v.is_null());
+    }
+}
+
+fn service_call(method: &str, parameters: &Map<String, Value>) -> VarlinkReply {
+    match method {
+        "GetInfo" => {
+            let interfaces = std::iter::once(VARLINK_SERVICE)
+                .chain(INTERFACES.iter().map(|iface| iface.name))
+                .collect::<Vec<_>>();
+            match json!({
+                "vendor": "Stratis",
+                "product": "stratisd",
+                "version": VERSION,
+                "url": "https://stratis-storage.github.io",
+                "interfaces": interfaces,
+            }) {
+                Value::Object(map) => VarlinkReply::ok(map),
+                _ => unreachable!("json! object literal is an object"),
+            }
+        }
+        "GetInterfaceDescription" => {
+            let interface = parameters
+                .get("interface")
+                .and_then(|i| i.as_str())
+                .unwrap_or_default();
+            match description(interface) {
+                Some(description) => {
+                    let mut map = Map::new();
+                    map.insert("description".to_string(), Value::from(description));
+                    VarlinkReply::ok(map)
+                }
+                None => VarlinkReply::error(
+                    format!("{VARLINK_SERVICE}.InterfaceNotFound"),
+                    json!({ "interface": interface }),
+                ),
+            }
+        }
+        _ => VarlinkReply::error(
+            format!("{VARLINK_SERVICE}.MethodNotFound"),
+            json!({ "method": method }),
+        ),
+    }
+}
+
+/// Process a single method call, dispatching calls to the stratisd
+/// interfaces to the same handlers as the JSON-RPC server. As for the
+/// JSON-RPC server, policy decides whether the peer may make the call.
+pub async fn process_call(
+    call: VarlinkCall,
+    engine: Arc<dyn Engine>,
+    peer: &PeerCreds,
+    policy: &AccessPolicy,
+) -> VarlinkReply {
+    let VarlinkCall {
+        method: qualified,
+        mut parameters,
+        more,
+        ..
+    } = call;
+    // No method has more than one reply.
+    if more {
+        return VarlinkReply::error(
+            format!("{VARLINK_SERVICE}.MethodNotImplemented"),
+            json!({ "method": qualified }),
+        );
+    }
+    let (interface, method) = match qualified.rsplit_once('.') {
+        Some(split) => split,
+        None => {
+            return VarlinkReply::error(
+                format!("{VARLINK_SERVICE}.MethodNotFound"),
+                json!({ "method": qualified }),
+            )
+        }
+    };
+
+    if interface == VARLINK_SERVICE {
+        return service_call(method, &parameters);
+    }
+    if !has_interface(interface) {
+        return VarlinkReply::error(
+            format!("{VARLINK_SERVICE}.InterfaceNotFound"),
+            json!({ "interface": interface }),
+        );
+    }
+    let schema = match find_method(interface, method) {
+        Some(schema) => schema,
+        None => {
+            return VarlinkReply::error(
+                format!("{VARLINK_SERVICE}.MethodNotFound"),
+                json!({ "method": qualified }),
+            )
+        }
+    };
+
+    normalize_pool_id(&mut parameters);
+    if !policy.permits(peer, is_read_only_named(schema, &parameters)) {
+        warn!(
+            "Refused Varlink call {} from process with UID {} which is not permitted to make it",
+            qualified,
+            peer.uid()
+        );
+        return VarlinkReply::error(format!("{VARLINK_SERVICE}.PermissionDenied"), json!({}));
+    }
+    let caller = Caller::Socket { uid: peer.uid() };
+    match caller
+        .scope(process_named(schema, parameters, None, engine))
+        .await
+    {
+        Ok(result) => VarlinkReply::ok(result),
+        Err(e) => VarlinkReply::error(
+            format!("{interface}.{ERROR}"),
+            json!({ "code": e.code, "message": e.message }),
+        ),
+    }
+}
+
+/// Read a single message, without its terminating NUL byte, or None if the
+/// client has closed the connection. A message longer than MAX_MESSAGE_SIZE
+/// is an error.
+async fn read_message<R>(reader: &mut R) -> StratisResult<Option<Vec<u8>>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut message = Vec::new();
+    if reader
+        .take(MAX_MESSAGE_SIZE)
+        .read_until(b'\0', &mut message)
+        .await?
+        == 0
+    {
+        return Ok(None);
+    }
+    if message.last() == Some(&b'\0') {
+        message.pop();
+    } else if message.len() as u64 == MAX_MESSAGE_SIZE {
+        return Err(StratisError::Msg(format!(
+            "Varlink message exceeds the maximum size of {MAX_MESSAGE_SIZE} bytes"
+        )));
+    }
+    Ok(Some(message))
+}
+
+/// Serve the calls of a single client until it closes the connection.
+/// Messages are JSON objects, each terminated by a NUL byte. The connection
+/// is dropped if the client sends a message which is too long.
+async fn handle_connection(
+    stream: UnixStream,
+    engine: Arc<dyn Engine>,
+    policy: Arc<AccessPolicy>,
+) -> StratisResult<()> {
+    let peer = PeerCreds::from_socket(stream.as_raw_fd())?;
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+    while let Some(message) = read_message(&mut reader).await? {
+        let call = serde_json::from_slice::<VarlinkCall>(&message)?;
+        let oneway = call.oneway;
+        let reply = process_call(call, engine.clone(), &peer, &policy).await;
+        if !oneway {
+            let mut encoded = serde_json::to_vec(&reply)?;
+            encoded.push(b'\0');
+            write.write_all(&encoded).await?;
+        }
+    }
+    Ok(())
+}
+
+fn bind<P>(path: P) -> StratisResult<UnixListener>
+where
+    P: AsRef<Path>,
+{
+    let _ = create_dir_all(
+        Path::new(VARLINK_SOCKADDR)
+            .parent()
+            .expect("Static path always has parent"),
+    );
+    let _ = remove_file(path.as_ref());
+    let listener = UnixListener::bind(path.as_ref())?;
+    // Any user may connect; whether a call is permitted is decided by the
+    // credentials of the peer.
+    set_permissions(path.as_ref(), Permissions::from_mode(0o666))?;
+    Ok(listener)
+}
+
+pub fn run_server(engine: Arc<dyn Engine>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let policy = Arc::new(AccessPolicy::load(RPC_ACCESS_CONFIG).unwrap_or_else(|e| {
+            warn!(
+                "Failed to load access configuration for Varlink: {}; only root will be permitted to make calls",
+                e
+            );
+            AccessPolicy::default()
+        }));
+        let listener = match bind(VARLINK_SOCKADDR) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to start Varlink server: {}", e);
+                return;
+            }
+        };
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let engine = engine.clone();
+                    let policy = Arc::clone(&policy);
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, engine, policy).await {
+                            warn!("Varlink connection closed with an error: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    warn!("Failed to accept Varlink connection: {}", e);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::SimEngine;
+
+    use super::*;
+
+    #[test]
+    fn test_service_calls() {
+        let reply = service_call("GetInfo", &Map::new());
+        assert!(reply.error.is_none());
+        assert_eq!(
+            reply.parameters["interfaces"].as_array().map(|i| i.len()),
+            Some(INTERFACES.len() + 1)
+        );
+
+        let mut params = Map::new();
+        params.insert(
+            "interface".to_string(),
+            Value::from("org.storage.stratis1.Key"),
+        );
+        let reply = service_call("GetInterfaceDescription", &params);
+        assert!(reply.error.is_none());
+        assert!(reply.parameters["description"].as_str().is_some());
+
+        params.insert("interface".to_string(), Value::from("org.example.Nope"));
+        assert_eq!(
+            service_call("GetInterfaceDescription", &params).error,
+            Some("org.varlink.service.InterfaceNotFound".to_string())
+        );
+        assert_eq!(
+            service_call("Nope", &params).error,
+            Some("org.varlink.service.MethodNotFound".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_message() {
+        let mut reader: &[u8] = b"{}\0{\"method\": \"m\"}\0";
+        assert_eq!(
+            test_async!(read_message(&mut reader)).unwrap(),
+            Some(b"{}".to_vec())
+        );
+        assert_eq!(
+            test_async!(read_message(&mut reader)).unwrap(),
+            Some(b"{\"method\": \"m\"}".to_vec())
+        );
+        assert_eq!(test_async!(read_message(&mut reader)).unwrap(), None);
+
+        let limit = usize::try_from(MAX_MESSAGE_SIZE).unwrap();
+        let mut fits = vec![b' '; limit - 1];
+        fits.push(b'\0');
+        let mut reader = fits.as_slice();
+        assert!(test_async!(read_message(&mut reader)).unwrap().is_some());
+
+        let too_long = vec![b' '; limit + 1];
+        let mut reader = too_long.as_slice();
+        assert!(test_async!(read_message(&mut reader)).is_err());
+    }
+
+    #[test]
+    fn test_more_refused() {
+        let (stream, _) = std::os::unix::net::UnixStream::pair().unwrap();
+        let peer = PeerCreds::from_socket(stream.as_raw_fd()).unwrap();
+        let call = serde_json::from_value::<VarlinkCall>(json!({
+            "method": "org.varlink.service.GetInfo",
+            "more": true,
+        }))
+        .unwrap();
+        let reply = test_async!(process_call(
+            call,
+            Arc::new(SimEngine::default()),
+            &peer,
+            &AccessPolicy::default(),
+        ));
+        assert_eq!(
+            reply.error,
+            Some("org.varlink.service.MethodNotImplemented".to_string())
+        );
+    }
+
+    #[test]
+    fn test_normalize_pool_id() {
+        let mut params = match json!({"id": {"Name": "p", "Uuid": null}}) {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        };
+        normalize_pool_id(&mut params);
+        assert_eq!(Value::Object(params), json!({"id": {"Name": "p"}}));
+    }
+}
@@ -6,8 +6,8 @@ SPECS = {
     </method>
   </interface>
 """,
-    "org.storage.stratis3.Manager.r8": """
-<interface name="org.storage.stratis3.Manager.r8">
+    "org.storage.stratis3.Manager.r9": """
+<interface name="org.storage.stratis3.Manager.r9">
     <method name="CreatePool">
       <arg name="name" type="s" direction="in" />
       <arg name="devices" type="as" direction="in" />
@@ -75,8 +75,8 @@ SPECS = {
     </property>
   </interface>
 """,
-    "org.storage.stratis3.Report.r8": """
-<interface name="org.storage.stratis3.Report.r8">
+    "org.storage.stratis3.Report.r9": """
+<interface name="org.storage.stratis3.Report.r9">
     <method name="GetReport">
       <arg name="name" type="s" direction="in" />
       <arg name="result" type="s" direction="out" />
@@ -85,8 +85,8 @@ SPECS = {
     </method>
   </interface>
 """,
-    "org.storage.stratis3.blockdev.r8": """
-<interface name="org.storage.stratis3.blockdev.r8">
+    "org.storage.stratis3.blockdev.r9": """
+<interface name="org.storage.stratis3.blockdev.r9">
     <property name="Devnode" type="s" access="read">
       <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const" />
     </property>
@@ -113,8 +113,8 @@ SPECS = {
     </property>
   </interface>
 """,
-    "org.storage.stratis3.filesystem.r8": """
-<interface name="org.storage.stratis3.filesystem.r8">
+    "org.storage.stratis3.filesystem.r9": """
+<interface name="org.storage.stratis3.filesystem.r9">
     <method name="SetName">
       <arg name="name" type="s" direction="in" />
       <arg name="result" type="(bs)" direction="out" />
@@ -133,6 +133,7 @@ SPECS = {
     <property name="Pool" type="o" access="read">
       <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const" />
     </property>
+    <property name="Reservation" type="(bs)" access="readwrite" />
     <property name="Size" type="s" access="read" />
     <property name="SizeLimit" type="(bs)" access="readwrite" />
     <property name="Used" type="(bs)" access="read" />
@@ -141,8 +142,8 @@ SPECS = {
     </property>
   </interface>
 """,
-    "org.storage.stratis3.pool.r8": """
-<interface name="org.storage.stratis3.pool.r8">
+    "org.storage.stratis3.pool.r9": """
+<interface name="org.storage.stratis3.pool.r9">
     <method name="AddCacheDevs">
       <arg name="devices" type="as" direction="in" />
       <arg name="results" type="(bao)" direction="out" />
@@ -170,6 +171,14 @@ SPECS = {
       <arg name="return_code" type="q" direction="out" />
       <arg name="return_string" type="s" direction="out" />
     </method>
+    <method name="CloneFilesystem">
+      <arg name="origin" type="o" direction="in" />
+      <arg name="name" type="s" direction="in" />
+      <arg name="preserve_uuid" type="b" direction="in" />
+      <arg name="result" type="(bo)" direction="out" />
+      <arg name="return_code" type="q" direction="out" />
+      <arg name="return_string" type="s" direction="out" />
+    </method>
     <method name="CreateFilesystems">
       <arg name="specs" type="a(s(bs)(bs))" direction="in" />
       <arg name="results" type="(ba(os))" direction="out" />